@@ -0,0 +1,86 @@
+//! Measures time of update of hierarchical data (global transforms and visibility) of a
+//! graph with 100k nodes. Static case shows the cost of update when nothing has moved,
+//! full case moves every top-level node which forces recalculation of every global
+//! transform, which is what graph did on every frame before dirty flags were introduced.
+//! Every case is measured twice - with serial and parallel update, speedup of parallel
+//! update depends on amount of CPU cores.
+//!
+//! Run with `cargo bench --bench graph_update`.
+
+use rg3d::{
+    core::{algebra::Vector3, pool::Handle},
+    scene::{base::BaseBuilder, graph::Graph, node::Node},
+};
+use std::time::{Duration, Instant};
+
+const GROUP_COUNT: usize = 1000;
+const GROUP_SIZE: usize = 100;
+const ITERATIONS: u32 = 100;
+
+fn create_graph() -> (Graph, Vec<Handle<Node>>) {
+    let mut graph = Graph::new();
+    let mut groups = Vec::with_capacity(GROUP_COUNT);
+    for _ in 0..GROUP_COUNT {
+        // Group node itself is a node too, so there is 100k nodes in total.
+        let children = (0..GROUP_SIZE - 1)
+            .map(|_| BaseBuilder::new().build(&mut graph))
+            .collect::<Vec<_>>();
+        groups.push(
+            BaseBuilder::new()
+                .with_children(&children)
+                .build(&mut graph),
+        );
+    }
+    (graph, groups)
+}
+
+fn measure<F: FnMut(&mut Graph, u32)>(graph: &mut Graph, mut prepare: F) -> Duration {
+    let mut total = Duration::default();
+    for i in 0..ITERATIONS {
+        prepare(graph, i);
+        let start = Instant::now();
+        graph.update_hierarchical_data();
+        total += start.elapsed();
+    }
+    total / ITERATIONS
+}
+
+fn run(parallel_update_threshold: usize) {
+    let (mut graph, groups) = create_graph();
+    graph.set_parallel_update_threshold(parallel_update_threshold);
+    graph.update_hierarchical_data();
+
+    let full = measure(&mut graph, |graph, i| {
+        for &group in groups.iter() {
+            graph[group]
+                .local_transform_mut()
+                .set_position(Vector3::new(i as f32, 0.0, 0.0));
+        }
+    });
+
+    let static_scene = measure(&mut graph, |_, _| {});
+
+    let single = measure(&mut graph, |graph, i| {
+        graph[groups[0]]
+            .local_transform_mut()
+            .set_position(Vector3::new(0.0, i as f32, 0.0));
+    });
+
+    println!("  every node moved (full recalculation): {:?}", full);
+    println!("  one group of {} nodes moved: {:?}", GROUP_SIZE, single);
+    println!("  static scene: {:?}", static_scene);
+}
+
+fn main() {
+    let node_count = GROUP_COUNT * GROUP_SIZE;
+    println!(
+        "Update of {} nodes, average of {} iterations.",
+        node_count, ITERATIONS
+    );
+
+    println!("Serial:");
+    run(usize::MAX);
+
+    println!("Parallel ({} threads):", rayon::current_num_threads());
+    run(0);
+}
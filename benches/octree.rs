@@ -0,0 +1,102 @@
+//! Measures time of construction of an octree from 1M triangles and time of ray queries
+//! against it. Construction is measured twice - with single thread and with every available
+//! thread, speedup of parallel construction depends on amount of CPU cores. Ray queries are
+//! compared with linear scan of bounds of every triangle.
+//!
+//! Run with `cargo bench --bench octree`.
+
+use rg3d::core::{
+    algebra::Vector3,
+    math::{aabb::AxisAlignedBoundingBox, ray::Ray},
+    octree::Octree,
+    rand::{rngs::StdRng, Rng, SeedableRng},
+};
+use std::time::{Duration, Instant};
+
+const TRIANGLE_COUNT: usize = 1_000_000;
+const RAY_COUNT: usize = 1000;
+const SPLIT_THRESHOLD: usize = 64;
+
+fn random_point(rng: &mut StdRng, extent: f32) -> Vector3<f32> {
+    Vector3::new(
+        rng.gen_range(-extent, extent),
+        rng.gen_range(-extent, extent),
+        rng.gen_range(-extent, extent),
+    )
+}
+
+fn create_triangles() -> Vec<[Vector3<f32>; 3]> {
+    let mut rng = StdRng::seed_from_u64(123);
+    (0..TRIANGLE_COUNT)
+        .map(|_| {
+            let a = random_point(&mut rng, 500.0);
+            [
+                a,
+                a + random_point(&mut rng, 1.0),
+                a + random_point(&mut rng, 1.0),
+            ]
+        })
+        .collect()
+}
+
+fn measure_build(triangles: &[[Vector3<f32>; 3]], thread_count: usize) -> (Octree, Duration) {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(thread_count)
+        .build()
+        .unwrap();
+    pool.install(|| {
+        let start = Instant::now();
+        let octree = Octree::new(triangles, SPLIT_THRESHOLD);
+        (octree, start.elapsed())
+    })
+}
+
+fn main() {
+    let triangles = create_triangles();
+
+    println!("Construction from {} triangles:", TRIANGLE_COUNT);
+    let (_, serial) = measure_build(&triangles, 1);
+    println!("  single thread: {:?}", serial);
+    let thread_count = rayon::current_num_threads();
+    let (octree, parallel) = measure_build(&triangles, thread_count);
+    println!("  {} threads: {:?}", thread_count, parallel);
+
+    let mut rng = StdRng::seed_from_u64(321);
+    let rays = (0..RAY_COUNT)
+        .map(|_| {
+            Ray::from_two_points(
+                &random_point(&mut rng, 600.0),
+                &random_point(&mut rng, 600.0),
+            )
+            .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    let mut buffer = Vec::new();
+    let mut octree_hits = 0;
+    let start = Instant::now();
+    for ray in rays.iter() {
+        octree.ray_query(ray, &mut buffer);
+        octree_hits += buffer.len();
+    }
+    let octree_time = start.elapsed();
+
+    let bounds = triangles
+        .iter()
+        .map(|triangle| AxisAlignedBoundingBox::from_points(triangle))
+        .collect::<Vec<_>>();
+    let mut linear_hits = 0;
+    let start = Instant::now();
+    for ray in rays.iter() {
+        linear_hits += bounds
+            .iter()
+            .filter(|aabb| ray.aabb_intersection(aabb).is_some())
+            .count();
+    }
+    let linear_time = start.elapsed();
+    assert_eq!(octree_hits, linear_hits);
+
+    println!("Ray query, average of {} rays:", RAY_COUNT);
+    println!("  octree: {:?}", octree_time / RAY_COUNT as u32);
+    println!("  linear scan: {:?}", linear_time / RAY_COUNT as u32);
+}
@@ -0,0 +1,67 @@
+//! Measures time of update of particle systems of a graph. There are 1000 particle systems
+//! with up to 1000 particles each, particle systems are updated in parallel by the graph, so
+//! update is measured twice - with single thread and with every available thread, speedup
+//! depends on amount of CPU cores.
+//!
+//! Run with `cargo bench --bench particles`.
+
+use rg3d::{
+    core::numeric_range::NumericRange,
+    scene::{
+        base::BaseBuilder,
+        graph::Graph,
+        particle_system::{BaseEmitterBuilder, ParticleSystemBuilder, SphereEmitterBuilder},
+    },
+};
+use std::time::{Duration, Instant};
+
+const PARTICLE_SYSTEM_COUNT: usize = 1000;
+const MAX_PARTICLES: u32 = 1000;
+const ITERATIONS: u32 = 100;
+const DT: f32 = 1.0 / 60.0;
+
+fn create_graph() -> Graph {
+    let mut graph = Graph::new();
+    for _ in 0..PARTICLE_SYSTEM_COUNT {
+        let emitter = SphereEmitterBuilder::new(
+            BaseEmitterBuilder::new()
+                .with_spawn_rate(500)
+                .with_max_particles(MAX_PARTICLES)
+                .with_lifetime_range(NumericRange::new(1.0, 3.0)),
+        )
+        .build();
+        ParticleSystemBuilder::new(BaseBuilder::new())
+            .with_emitters(vec![emitter])
+            .build(&mut graph);
+    }
+    graph
+}
+
+fn measure(thread_count: usize) -> Duration {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(thread_count)
+        .build()
+        .unwrap();
+    pool.install(|| {
+        let mut graph = create_graph();
+        // Warm up until emitters reach their limits, so every iteration does the same work.
+        for _ in 0..(3.0 / DT) as usize {
+            graph.update_nodes(Default::default(), DT);
+        }
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            graph.update_nodes(Default::default(), DT);
+        }
+        start.elapsed() / ITERATIONS
+    })
+}
+
+fn main() {
+    println!(
+        "Update of {} particle systems, average of {} iterations.",
+        PARTICLE_SYSTEM_COUNT, ITERATIONS
+    );
+    println!("  single thread: {:?}", measure(1));
+    let thread_count = rayon::current_num_threads();
+    println!("  {} threads: {:?}", thread_count, measure(thread_count));
+}
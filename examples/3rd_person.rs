@@ -29,7 +29,7 @@ pub mod shared;
 use crate::shared::{create_ui, fix_shadows_distance, Game, GameScene};
 use rg3d::core::algebra::Vector2;
 use rg3d::{
-    event::{Event, VirtualKeyCode, WindowEvent},
+    event::{ElementState, Event, VirtualKeyCode, WindowEvent},
     event_loop::ControlFlow,
     gui::message::{MessageDirection, ProgressBarMessage, TextMessage, WidgetMessage},
     renderer::QualitySettings,
@@ -122,13 +122,19 @@ fn main() {
                         // Use stored scene handle to borrow a mutable reference of scene in
                         // engine.
                         let scene = &mut game.engine.scenes[game_scene.scene];
-                        game_scene.player.update(scene, fixed_timestep);
+                        // Game logic of the scene runs in scene time, so it slows down or stops
+                        // together with the scene.
+                        if !scene.paused && scene.time_scale > 0.0 {
+                            let dt = fixed_timestep * scene.time_scale;
+                            game_scene.player.update(scene, dt);
+                        }
                     }
 
                     let debug_text = format!(
                         "Example 03 - 3rd Person\n\
                         [W][S][A][D] - walk, [SPACE] - jump.\n\
                         Use [1][2][3][4] to select graphics quality.\n\
+                        [T] - toggle bullet time, [P] - pause.\n\
                         {}",
                         game.engine.renderer.get_statistics()
                     );
@@ -166,11 +172,6 @@ fn main() {
                 match event {
                     WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
                     WindowEvent::Resized(size) => {
-                        // It is very important to handle Resized event from window, because
-                        // renderer knows nothing about window size - it must be notified
-                        // directly when window size has changed.
-                        game.engine.renderer.set_frame_size(size.into());
-
                         // Root UI node should be resized too, otherwise progress bar will stay
                         // in wrong position after resize.
                         let size = size.to_logical(game.engine.get_window().scale_factor());
@@ -210,6 +211,22 @@ fn main() {
                                     .set_quality_settings(&fix_shadows_distance(settings))
                                     .unwrap();
                             }
+
+                            // Time of the scene can be slowed down or stopped independently from
+                            // the rest of the engine, UI for example will still be responsive.
+                            if input.state == ElementState::Pressed {
+                                if let Some(game_scene) = game.game_scene.as_ref() {
+                                    let scene = &mut game.engine.scenes[game_scene.scene];
+                                    match code {
+                                        VirtualKeyCode::T => {
+                                            scene.time_scale =
+                                                if scene.time_scale < 1.0 { 1.0 } else { 0.1 };
+                                        }
+                                        VirtualKeyCode::P => scene.paused = !scene.paused,
+                                        _ => (),
+                                    }
+                                }
+                            }
                         }
                     }
                     _ => (),
@@ -363,11 +363,6 @@ fn main() {
                         *control_flow = ControlFlow::Exit
                     }
                     WindowEvent::Resized(size) => {
-                        // It is very important to handle Resized event from window, because
-                        // renderer knows nothing about window size - it must be notified
-                        // directly when window size has changed.
-                        engine.renderer.set_frame_size(size.into());
-
                         // Root UI node should be resized too, otherwise progress bar will stay
                         // in wrong position after resize.
                         let size = size.to_logical(engine.get_window().scale_factor());
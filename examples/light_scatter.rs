@@ -0,0 +1,253 @@
+//! Example 11. Light scattering.
+//!
+//! Difficulty: Easy.
+//!
+//! This example shows how to create volumetric light shafts: a spot light shines into a dusty
+//! warehouse through a row of pillars, and the pillars cut the light shafts using the shadow map.
+
+extern crate rg3d;
+
+pub mod shared;
+
+use crate::shared::create_camera;
+use rg3d::{
+    core::{
+        algebra::{Matrix4, UnitQuaternion, Vector3},
+        color::Color,
+        pool::Handle,
+    },
+    engine::resource_manager::ResourceManager,
+    event::{ElementState, Event, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    gui::{
+        message::{MessageDirection, TextMessage},
+        node::StubNode,
+        text::TextBuilder,
+        widget::WidgetBuilder,
+    },
+    renderer::{
+        surface::{SurfaceBuilder, SurfaceSharedData},
+        LightScatterResolution, QualitySettings,
+    },
+    scene::{
+        base::BaseBuilder,
+        light::{BaseLightBuilder, SpotLightBuilder},
+        mesh::MeshBuilder,
+        node::Node,
+        transform::TransformBuilder,
+        Scene,
+    },
+    utils::translate_event,
+};
+use std::{
+    sync::{Arc, RwLock},
+    time::Instant,
+};
+
+// Create our own engine type aliases. These specializations are needed
+// because engine provides a way to extend UI with custom nodes and messages.
+type GameEngine = rg3d::engine::Engine<(), StubNode>;
+type UiNode = rg3d::gui::node::UINode<(), StubNode>;
+type BuildContext<'a> = rg3d::gui::BuildContext<'a, (), StubNode>;
+
+fn create_ui(ctx: &mut BuildContext) -> Handle<UiNode> {
+    TextBuilder::new(WidgetBuilder::new()).build(ctx)
+}
+
+fn add_box(
+    scene: &mut Scene,
+    resource_manager: &ResourceManager,
+    position: Vector3<f32>,
+    size: Vector3<f32>,
+) {
+    MeshBuilder::new(
+        BaseBuilder::new().with_local_transform(
+            TransformBuilder::new()
+                .with_local_position(position)
+                .build(),
+        ),
+    )
+    .with_surfaces(vec![SurfaceBuilder::new(Arc::new(RwLock::new(
+        SurfaceSharedData::make_cube(Matrix4::new_nonuniform_scaling(&size)),
+    )))
+    .with_diffuse_texture(resource_manager.request_texture("examples/data/concrete2.dds"))
+    .build()])
+    .build(&mut scene.graph);
+}
+
+struct GameScene {
+    scene: Scene,
+    camera: Handle<Node>,
+}
+
+async fn create_scene(resource_manager: ResourceManager) -> GameScene {
+    let mut scene = Scene::new();
+
+    let camera = create_camera(
+        resource_manager.clone(),
+        Vector3::new(0.0, 3.0, -14.0),
+        &mut scene.graph,
+    )
+    .await;
+
+    // Floor and back wall of the warehouse.
+    add_box(
+        &mut scene,
+        &resource_manager,
+        Vector3::new(0.0, -0.25, 0.0),
+        Vector3::new(30.0, 0.25, 30.0),
+    );
+    add_box(
+        &mut scene,
+        &resource_manager,
+        Vector3::new(0.0, 6.0, 15.0),
+        Vector3::new(30.0, 6.0, 0.25),
+    );
+
+    // Row of pillars between the light and the camera, they will cut light shafts.
+    for i in -3..=3 {
+        add_box(
+            &mut scene,
+            &resource_manager,
+            Vector3::new(i as f32 * 3.0, 4.0, 6.0),
+            Vector3::new(0.35, 4.0, 0.35),
+        );
+    }
+
+    // Spot light shines down towards the camera like the sun through a skylight. Scatter
+    // factor defines color and density of the dust in the air.
+    SpotLightBuilder::new(
+        BaseLightBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(Vector3::new(0.0, 11.0, 12.0))
+                    .with_local_rotation(UnitQuaternion::from_axis_angle(
+                        &Vector3::x_axis(),
+                        35.0f32.to_radians(),
+                    ))
+                    .build(),
+            ),
+        )
+        .with_color(Color::opaque(255, 230, 190))
+        .with_scatter_enabled(true)
+        .with_scatter_factor(Vector3::new(0.05, 0.045, 0.035)),
+    )
+    .with_hotspot_cone_angle(50.0f32.to_radians())
+    .with_falloff_angle_delta(10.0f32.to_radians())
+    .with_distance(30.0)
+    .build(&mut scene.graph);
+
+    GameScene { scene, camera }
+}
+
+struct InputController {
+    rotate_left: bool,
+    rotate_right: bool,
+}
+
+fn main() {
+    let event_loop = EventLoop::new();
+
+    let window_builder = rg3d::window::WindowBuilder::new()
+        .with_title("Example - Light Scattering")
+        .with_resizable(true);
+
+    let mut engine = GameEngine::new(window_builder, &event_loop, false).unwrap();
+
+    // Light scattering is controlled globally by quality settings: amount of ray marching
+    // steps defines how accurately shadows will cut light shafts and resolution defines
+    // size of buffer in which scattering will be accumulated.
+    let mut settings = QualitySettings::ultra();
+    settings.light_scatter_enabled = true;
+    settings.light_scatter_steps = 48;
+    settings.light_scatter_resolution = LightScatterResolution::Half;
+    engine.renderer.set_quality_settings(&settings).unwrap();
+
+    let debug_text = create_ui(&mut engine.user_interface.build_ctx());
+
+    let GameScene { scene, camera } =
+        rg3d::futures::executor::block_on(create_scene(engine.resource_manager.clone()));
+
+    let scene_handle = engine.scenes.add(scene);
+
+    // Keep ambient light low, so light shafts will be clearly visible.
+    engine.renderer.set_ambient_color(Color::opaque(30, 30, 30));
+
+    let clock = Instant::now();
+    let fixed_timestep = 1.0 / 60.0;
+    let mut elapsed_time = 0.0;
+
+    let mut camera_angle = 0.0f32.to_radians();
+
+    let mut input_controller = InputController {
+        rotate_left: false,
+        rotate_right: false,
+    };
+
+    event_loop.run(move |event, _, control_flow| match event {
+        Event::MainEventsCleared => {
+            let mut dt = clock.elapsed().as_secs_f32() - elapsed_time;
+            while dt >= fixed_timestep {
+                dt -= fixed_timestep;
+                elapsed_time += fixed_timestep;
+
+                let scene = &mut engine.scenes[scene_handle];
+
+                if input_controller.rotate_left {
+                    camera_angle -= 1.0f32.to_radians();
+                } else if input_controller.rotate_right {
+                    camera_angle += 1.0f32.to_radians();
+                }
+
+                scene.graph[camera].local_transform_mut().set_rotation(
+                    UnitQuaternion::from_axis_angle(&Vector3::y_axis(), camera_angle),
+                );
+
+                engine.update(fixed_timestep);
+            }
+
+            let text = format!(
+                "Example 11 - Light Scattering\n\
+                    Use [A][D] keys to rotate camera.\n\
+                    {}",
+                engine.renderer.get_statistics()
+            );
+            engine.user_interface.send_message(TextMessage::text(
+                debug_text,
+                MessageDirection::ToWidget,
+                text,
+            ));
+
+            while let Some(_ui_event) = engine.user_interface.poll_message() {}
+
+            engine.get_window().request_redraw();
+        }
+        Event::RedrawRequested(_) => {
+            engine.render(fixed_timestep).unwrap();
+        }
+        Event::WindowEvent { event, .. } => {
+            match event {
+                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                WindowEvent::KeyboardInput { input, .. } => {
+                    if let Some(key_code) = input.virtual_keycode {
+                        match key_code {
+                            VirtualKeyCode::A => {
+                                input_controller.rotate_left = input.state == ElementState::Pressed
+                            }
+                            VirtualKeyCode::D => {
+                                input_controller.rotate_right = input.state == ElementState::Pressed
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                _ => (),
+            }
+
+            if let Some(os_event) = translate_event(&event) {
+                engine.user_interface.process_os_event(&os_event);
+            }
+        }
+        _ => *control_flow = ControlFlow::Poll,
+    });
+}
@@ -0,0 +1,196 @@
+//! Example 12. Procedural texture.
+//!
+//! Difficulty: Easy.
+//!
+//! This example shows how to create a texture from raw pixels and how to update its pixels
+//! every frame. A cube shows a simple "minimap" with a marker which moves in circles, the
+//! minimap is redrawn 30 times per second.
+
+extern crate rg3d;
+
+pub mod shared;
+
+use crate::shared::create_camera;
+use rg3d::{
+    core::{
+        algebra::{Matrix4, Vector3},
+        color::Color,
+        pool::Handle,
+    },
+    engine::resource_manager::ResourceManager,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    gui::{
+        message::{MessageDirection, TextMessage},
+        node::StubNode,
+        text::TextBuilder,
+        widget::WidgetBuilder,
+    },
+    renderer::surface::{SurfaceBuilder, SurfaceSharedData},
+    resource::texture::{Texture, TexturePixelKind},
+    scene::{base::BaseBuilder, mesh::MeshBuilder, Scene},
+    utils::translate_event,
+};
+use std::{
+    sync::{Arc, RwLock},
+    time::Instant,
+};
+
+// Create our own engine type aliases. These specializations are needed
+// because engine provides a way to extend UI with custom nodes and messages.
+type GameEngine = rg3d::engine::Engine<(), StubNode>;
+type UiNode = rg3d::gui::node::UINode<(), StubNode>;
+type BuildContext<'a> = rg3d::gui::BuildContext<'a, (), StubNode>;
+
+const MINIMAP_SIZE: u32 = 128;
+const MARKER_SIZE: u32 = 6;
+const BACKGROUND: [u8; 4] = [40, 60, 40, 255];
+const MARKER: [u8; 4] = [255, 40, 40, 255];
+
+fn create_ui(ctx: &mut BuildContext) -> Handle<UiNode> {
+    TextBuilder::new(WidgetBuilder::new()).build(ctx)
+}
+
+struct GameScene {
+    scene: Scene,
+    minimap: Texture,
+}
+
+async fn create_scene(resource_manager: ResourceManager) -> GameScene {
+    let mut scene = Scene::new();
+
+    create_camera(
+        resource_manager.clone(),
+        Vector3::new(0.0, 0.0, -3.0),
+        &mut scene.graph,
+    )
+    .await;
+
+    // Create texture from raw pixels, it has no source file - all its pixels are in memory.
+    let minimap = Texture::from_bytes(
+        MINIMAP_SIZE,
+        MINIMAP_SIZE,
+        TexturePixelKind::RGBA8,
+        BACKGROUND.repeat((MINIMAP_SIZE * MINIMAP_SIZE) as usize),
+    )
+    .unwrap();
+
+    // Procedural texture can be used as any other texture.
+    MeshBuilder::new(BaseBuilder::new())
+        .with_surfaces(vec![SurfaceBuilder::new(Arc::new(RwLock::new(
+            SurfaceSharedData::make_cube(Matrix4::identity()),
+        )))
+        .with_diffuse_texture(minimap.clone())
+        .build()])
+        .build(&mut scene.graph);
+
+    GameScene { scene, minimap }
+}
+
+fn marker_position(time: f32) -> (u32, u32) {
+    let center = (MINIMAP_SIZE - MARKER_SIZE) as f32 / 2.0;
+    let radius = center * 0.8;
+    (
+        (center + radius * time.cos()) as u32,
+        (center + radius * time.sin()) as u32,
+    )
+}
+
+fn main() {
+    let event_loop = EventLoop::new();
+
+    let window_builder = rg3d::window::WindowBuilder::new()
+        .with_title("Example - Procedural Texture")
+        .with_resizable(true);
+
+    let mut engine = GameEngine::new(window_builder, &event_loop, false).unwrap();
+
+    let debug_text = create_ui(&mut engine.user_interface.build_ctx());
+
+    let GameScene { scene, minimap } =
+        rg3d::futures::executor::block_on(create_scene(engine.resource_manager.clone()));
+
+    engine.scenes.add(scene);
+
+    engine.renderer.set_ambient_color(Color::WHITE);
+
+    let clock = Instant::now();
+    let fixed_timestep = 1.0 / 60.0;
+    let mut elapsed_time = 0.0;
+
+    let minimap_timestep = 1.0 / 30.0;
+    let mut minimap_time = 0.0;
+    let mut marker = marker_position(0.0);
+
+    let background = BACKGROUND.repeat((MARKER_SIZE * MARKER_SIZE) as usize);
+    let foreground = MARKER.repeat((MARKER_SIZE * MARKER_SIZE) as usize);
+
+    event_loop.run(move |event, _, control_flow| {
+        match event {
+            Event::MainEventsCleared => {
+                let mut dt = clock.elapsed().as_secs_f32() - elapsed_time;
+                while dt >= fixed_timestep {
+                    dt -= fixed_timestep;
+                    elapsed_time += fixed_timestep;
+
+                    if elapsed_time - minimap_time >= minimap_timestep {
+                        minimap_time = elapsed_time;
+
+                        // Erase marker at old position and draw it at new one. Only these
+                        // two small regions will be uploaded to GPU, not the whole texture.
+                        let mut pixels = minimap.modify();
+                        pixels
+                            .update_region(
+                                marker.0,
+                                marker.1,
+                                MARKER_SIZE,
+                                MARKER_SIZE,
+                                &background,
+                            )
+                            .unwrap();
+                        marker = marker_position(elapsed_time);
+                        pixels
+                            .update_region(
+                                marker.0,
+                                marker.1,
+                                MARKER_SIZE,
+                                MARKER_SIZE,
+                                &foreground,
+                            )
+                            .unwrap();
+                    }
+
+                    engine.update(fixed_timestep);
+                }
+
+                let text = format!(
+                    "Example 12 - Procedural Texture\n{}",
+                    engine.renderer.get_statistics()
+                );
+                engine.user_interface.send_message(TextMessage::text(
+                    debug_text,
+                    MessageDirection::ToWidget,
+                    text,
+                ));
+
+                while let Some(_ui_event) = engine.user_interface.poll_message() {}
+
+                engine.get_window().request_redraw();
+            }
+            Event::RedrawRequested(_) => {
+                engine.render(fixed_timestep).unwrap();
+            }
+            Event::WindowEvent { event, .. } => {
+                match event {
+                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                    _ => (),
+                }
+
+                if let Some(os_event) = translate_event(&event) {
+                    engine.user_interface.process_os_event(&os_event);
+                }
+            }
+            _ => *control_flow = ControlFlow::Poll,
+        }
+    });
+}
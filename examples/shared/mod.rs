@@ -25,7 +25,7 @@ use rg3d::{
         progress_bar::ProgressBarBuilder,
         text::TextBuilder,
         widget::WidgetBuilder,
-        HorizontalAlignment, Thickness, VerticalAlignment,
+        HorizontalAlignment, Thickness, VerticalAlignment, WrapMode,
     },
     renderer::QualitySettings,
     resource::texture::TextureWrapMode,
@@ -161,7 +161,7 @@ pub fn create_ui(ui: &mut BuildContext, screen_size: Vector2<f32>) -> Interface
             .with_height(screen_size.y)
             .with_child({
                 debug_text = TextBuilder::new(WidgetBuilder::new().on_row(0).on_column(0))
-                    .with_wrap(true)
+                    .with_wrap(WrapMode::Word)
                     .build(ui);
                 debug_text
             })
@@ -17,7 +17,7 @@ use rg3d::{
         pool::Handle,
     },
     engine::resource_manager::ResourceManager,
-    event::{ElementState, Event, VirtualKeyCode, WindowEvent},
+    event::{ElementState, Event, ModifiersState, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     gui::{
         message::{MessageDirection, TextMessage},
@@ -89,10 +89,17 @@ async fn create_scene(resource_manager: ResourceManager) -> GameScene {
     // Why? Because animation in *resource* uses information about *resource* bones,
     // not model instance bones, retarget_animations maps animations of each bone on
     // model instance so animation will know about nodes it should operate on.
-    let walk_animation = *walk_animation_resource
+    let walk_animations = walk_animation_resource
         .unwrap()
-        .retarget_animations(model_handle, &mut scene)
-        .get(0)
+        .retarget_animations(model_handle, &mut scene);
+
+    // FBX file can contain multiple takes (idle, walk, run, etc.), each take becomes separate
+    // animation named after the take, so we can select the one we need by its name. Our file
+    // has single take exported from mixamo.com.
+    let walk_animation = walk_animations
+        .iter()
+        .cloned()
+        .find(|&animation| scene.animations.get(animation).name() == "mixamo.com")
         .unwrap();
 
     // Add floor.
@@ -175,10 +182,13 @@ fn main() {
         rotate_right: false,
     };
 
+    // Keyboard modifiers are needed to handle Alt+Enter.
+    let mut modifiers = ModifiersState::default();
+
     // Finally run our event loop which will respond to OS and window events and update
     // engine state accordingly. Engine lets you to decide which event should be handled,
     // this is minimal working example if how it should be.
-    event_loop.run(move |event, _, control_flow| {
+    event_loop.run(move |event, window_target, control_flow| {
         match event {
             Event::MainEventsCleared => {
                 // This main game loop - it has fixed time step which means that game
@@ -221,7 +231,9 @@ fn main() {
 
                     let fps = engine.renderer.get_statistics().frames_per_second;
                     let text = format!(
-                        "Example 01 - Simple Scene\nUse [A][D] keys to rotate model.\nFPS: {}",
+                        "Example 01 - Simple Scene\nUse [A][D] keys to rotate model.\n\
+                        [Alt+Enter] - toggle fullscreen, [V] - toggle vsync ({}).\nFPS: {}",
+                        if engine.vsync() { "on" } else { "off" },
                         fps
                     );
                     engine.user_interface.send_message(TextMessage::text(
@@ -254,12 +266,7 @@ fn main() {
             Event::WindowEvent { event, .. } => {
                 match event {
                     WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                    WindowEvent::Resized(size) => {
-                        // It is very important to handle Resized event from window, because
-                        // renderer knows nothing about window size - it must be notified
-                        // directly when window size has changed.
-                        engine.renderer.set_frame_size(size.into());
-                    }
+                    WindowEvent::ModifiersChanged(state) => modifiers = state,
                     WindowEvent::KeyboardInput { input, .. } => {
                         // Handle key input events via `WindowEvent`, not via `DeviceEvent` (#32)
                         if let Some(key_code) = input.virtual_keycode {
@@ -272,6 +279,28 @@ fn main() {
                                     input_controller.rotate_right =
                                         input.state == ElementState::Pressed
                                 }
+                                VirtualKeyCode::Return
+                                    if modifiers.alt() && input.state == ElementState::Pressed =>
+                                {
+                                    if engine.is_fullscreen() {
+                                        engine.set_fullscreen(None);
+                                    } else {
+                                        // Pick video mode with highest resolution and refresh rate.
+                                        let video_mode =
+                                            engine.video_modes().into_iter().max_by_key(|mode| {
+                                                (
+                                                    mode.size().width,
+                                                    mode.size().height,
+                                                    mode.refresh_rate(),
+                                                )
+                                            });
+                                        engine.set_fullscreen(video_mode);
+                                    }
+                                }
+                                VirtualKeyCode::V if input.state == ElementState::Pressed => {
+                                    let vsync = !engine.vsync();
+                                    engine.set_vsync(vsync, window_target).unwrap();
+                                }
                                 _ => (),
                             }
                         }
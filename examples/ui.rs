@@ -472,12 +472,6 @@ fn main() {
                                         engine.get_window().set_fullscreen(Some(
                                             Fullscreen::Exclusive(video_mode.clone()),
                                         ));
-
-                                        // Due to some weird bug in winit it does not send Resized event.
-                                        engine.renderer.set_frame_size((
-                                            video_mode.size().width,
-                                            video_mode.size().height,
-                                        ));
                                     }
                                 }
                             }
@@ -496,12 +490,6 @@ fn main() {
             Event::WindowEvent { event, .. } => {
                 match event {
                     WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                    WindowEvent::Resized(size) => {
-                        // It is very important to handle Resized event from window, because
-                        // renderer knows nothing about window size - it must be notified
-                        // directly when window size has changed.
-                        engine.renderer.set_frame_size(size.into());
-                    }
                     WindowEvent::KeyboardInput { input, .. } => {
                         if let Some(key_code) = input.virtual_keycode {
                             if input.state == ElementState::Pressed
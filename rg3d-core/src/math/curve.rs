@@ -0,0 +1,425 @@
+//! Parametric curve which is defined by set of keys. Curves are used to describe how some
+//! value changes over time (or any other parameter) - size of particles over their lifetime,
+//! intensity of light during animation, etc. Curves are generic infrastructure, so they can
+//! be shared between animations, particle systems, materials and so on.
+//!
+//! Each key defines interpolation mode of segment which starts at the key: constant (stepped),
+//! linear or cubic Hermite. Outside of keys range curve is extrapolated, it either clamps to
+//! the value of first or last key or repeats itself.
+//!
+//! Evaluation uses binary search over the keys, so it takes O(log n) time and never allocates.
+
+use crate::visitor::{Visit, VisitError, VisitResult, Visitor};
+use std::cmp::Ordering;
+
+/// Defines how a segment that starts at a key is interpolated.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum CurveKeyKind {
+    /// Value remains the same until next key (stepped curve).
+    Constant,
+    /// Value is linearly interpolated to the value of next key.
+    #[default]
+    Linear,
+    /// Cubic Hermite segment. Tangents are slopes (change of value per unit of location) of
+    /// the curve at the left and right side of the key. Right tangent of a key and left
+    /// tangent of next key define shape of the segment between them.
+    Cubic {
+        left_tangent: f32,
+        right_tangent: f32,
+    },
+}
+
+impl Visit for CurveKeyKind {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id: u32 = match self {
+            CurveKeyKind::Constant => 0,
+            CurveKeyKind::Linear => 1,
+            CurveKeyKind::Cubic { .. } => 2,
+        };
+        id.visit("Id", visitor)?;
+        if visitor.is_reading() {
+            *self = match id {
+                0 => CurveKeyKind::Constant,
+                1 => CurveKeyKind::Linear,
+                2 => CurveKeyKind::Cubic {
+                    left_tangent: 0.0,
+                    right_tangent: 0.0,
+                },
+                _ => return Err(VisitError::User(format!("Invalid curve key kind {}!", id))),
+            };
+        }
+        if let CurveKeyKind::Cubic {
+            left_tangent,
+            right_tangent,
+        } = self
+        {
+            left_tangent.visit("LeftTangent", visitor)?;
+            right_tangent.visit("RightTangent", visitor)?;
+        }
+
+        visitor.leave_region()
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct CurveKey {
+    location: f32,
+    value: f32,
+    kind: CurveKeyKind,
+}
+
+impl CurveKey {
+    pub fn new(location: f32, value: f32, kind: CurveKeyKind) -> Self {
+        Self {
+            location,
+            value,
+            kind,
+        }
+    }
+
+    pub fn location(&self) -> f32 {
+        self.location
+    }
+
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    pub fn kind(&self) -> CurveKeyKind {
+        self.kind
+    }
+
+    /// Interpolates value between this key and next key, `t` is in [0; 1] range.
+    fn interpolate(&self, next: &CurveKey, t: f32) -> f32 {
+        match self.kind {
+            CurveKeyKind::Constant => self.value,
+            CurveKeyKind::Linear => self.value + (next.value - self.value) * t,
+            CurveKeyKind::Cubic { right_tangent, .. } => {
+                let span = next.location - self.location;
+                let next_tangent = match next.kind {
+                    CurveKeyKind::Cubic { left_tangent, .. } => left_tangent,
+                    // Continue segment as a straight line if next key has no tangents.
+                    _ => (next.value - self.value) / span,
+                };
+                // Hermite segment in Bezier form: control points are placed at 1/3 and 2/3
+                // of the segment, so location changes linearly along the segment and
+                // tangents are preserved.
+                let p0 = self.value;
+                let p1 = self.value + right_tangent * span / 3.0;
+                let p2 = next.value - next_tangent * span / 3.0;
+                let p3 = next.value;
+                let s = 1.0 - t;
+                s * s * s * p0 + 3.0 * s * s * t * p1 + 3.0 * s * t * t * p2 + t * t * t * p3
+            }
+        }
+    }
+}
+
+impl Visit for CurveKey {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.location.visit("Location", visitor)?;
+        self.value.visit("Value", visitor)?;
+        self.kind.visit("Kind", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Defines how curve is evaluated outside of range of its keys.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum Extrapolation {
+    /// Value of first key is used before the range, value of last key - after the range.
+    #[default]
+    Clamp,
+    /// Curve repeats itself, range of keys is used as period.
+    Loop,
+}
+
+impl Visit for Extrapolation {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        let mut id = *self as u32;
+        id.visit(name, visitor)?;
+        if visitor.is_reading() {
+            *self = match id {
+                0 => Self::Clamp,
+                1 => Self::Loop,
+                _ => return Err(VisitError::User(format!("Invalid extrapolation {}!", id))),
+            };
+        }
+        Ok(())
+    }
+}
+
+/// See module docs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Curve {
+    keys: Vec<CurveKey>,
+    extrapolation: Extrapolation,
+}
+
+impl Visit for Curve {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.keys.visit("Keys", visitor)?;
+        self.extrapolation.visit("Extrapolation", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+impl Curve {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates new curve from given keys, keys will be sorted by their location.
+    pub fn from_keys(keys: Vec<CurveKey>) -> Self {
+        let mut curve = Self {
+            keys,
+            extrapolation: Default::default(),
+        };
+        curve.sort_keys();
+        curve
+    }
+
+    fn sort_keys(&mut self) {
+        self.keys.sort_by(|a, b| {
+            a.location
+                .partial_cmp(&b.location)
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+
+    /// Adds new key, keys are kept sorted by their location. If there are keys with the same
+    /// location, new key will be put after them.
+    pub fn add_key(&mut self, key: CurveKey) {
+        let index = match self.keys.binary_search_by(|other| {
+            if other.location <= key.location {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }
+        }) {
+            Ok(index) | Err(index) => index,
+        };
+        self.keys.insert(index, key);
+    }
+
+    pub fn keys(&self) -> &[CurveKey] {
+        &self.keys
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear()
+    }
+
+    pub fn set_extrapolation(&mut self, extrapolation: Extrapolation) {
+        self.extrapolation = extrapolation;
+    }
+
+    pub fn with_extrapolation(mut self, extrapolation: Extrapolation) -> Self {
+        self.extrapolation = extrapolation;
+        self
+    }
+
+    pub fn extrapolation(&self) -> Extrapolation {
+        self.extrapolation
+    }
+
+    /// Returns value of curve at given location. Curve without keys is zero everywhere.
+    pub fn value_at(&self, location: f32) -> f32 {
+        let (first, last) = match (self.keys.first(), self.keys.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return 0.0,
+        };
+
+        let span = last.location - first.location;
+        let location = match self.extrapolation {
+            Extrapolation::Loop if span > 0.0 => {
+                first.location + (location - first.location).rem_euclid(span)
+            }
+            _ => location,
+        };
+
+        if location <= first.location {
+            return first.value;
+        } else if location >= last.location {
+            return last.value;
+        }
+
+        // Find segment which contains location, keys are sorted so binary search can be used.
+        let index = match self.keys.binary_search_by(|key| {
+            key.location
+                .partial_cmp(&location)
+                .unwrap_or(Ordering::Equal)
+        }) {
+            Ok(index) => return self.keys[index].value,
+            Err(index) => index,
+        };
+        let left = &self.keys[index - 1];
+        let right = &self.keys[index];
+        let t = (location - left.location) / (right.location - left.location);
+        left.interpolate(right, t)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::math::curve::{Curve, CurveKey, CurveKeyKind, Extrapolation};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn test_curve_value_at() {
+        assert_eq!(Curve::new().value_at(1.0), 0.0);
+
+        let curve = Curve::from_keys(vec![
+            CurveKey::new(1.0, 2.0, CurveKeyKind::Constant),
+            CurveKey::new(0.0, 0.0, CurveKeyKind::Linear),
+            CurveKey::new(2.0, 4.0, CurveKeyKind::Linear),
+        ]);
+        assert_eq!(curve.value_at(-1.0), 0.0);
+        assert_eq!(curve.value_at(0.5), 1.0);
+        assert_eq!(curve.value_at(1.0), 2.0);
+        assert_eq!(curve.value_at(1.5), 2.0);
+        assert_eq!(curve.value_at(3.0), 4.0);
+
+        let curve = curve.with_extrapolation(Extrapolation::Loop);
+        assert_eq!(curve.value_at(2.5), 1.0);
+        assert_eq!(curve.value_at(-1.5), 1.0);
+    }
+
+    #[test]
+    fn test_curve_cubic() {
+        // Cubic segment with tangents of straight line must be a straight line.
+        let curve = Curve::from_keys(vec![
+            CurveKey::new(
+                0.0,
+                0.0,
+                CurveKeyKind::Cubic {
+                    left_tangent: 2.0,
+                    right_tangent: 2.0,
+                },
+            ),
+            CurveKey::new(
+                2.0,
+                4.0,
+                CurveKeyKind::Cubic {
+                    left_tangent: 2.0,
+                    right_tangent: 2.0,
+                },
+            ),
+        ]);
+        for i in 0..=10 {
+            let x = i as f32 * 0.2;
+            assert!((curve.value_at(x) - 2.0 * x).abs() < 0.0001);
+        }
+
+        // Flat tangents gives smooth step, symmetric around the middle of segment.
+        let curve = Curve::from_keys(vec![
+            CurveKey::new(
+                0.0,
+                0.0,
+                CurveKeyKind::Cubic {
+                    left_tangent: 0.0,
+                    right_tangent: 0.0,
+                },
+            ),
+            CurveKey::new(
+                1.0,
+                1.0,
+                CurveKeyKind::Cubic {
+                    left_tangent: 0.0,
+                    right_tangent: 0.0,
+                },
+            ),
+        ]);
+        assert!((curve.value_at(0.5) - 0.5).abs() < 0.0001);
+        assert!(curve.value_at(0.1) < 0.1);
+        assert!((curve.value_at(0.25) + curve.value_at(0.75) - 1.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_curve_add_key() {
+        let mut curve = Curve::new();
+        for &location in &[2.0, 0.0, 3.0, 1.0, 1.0] {
+            curve.add_key(CurveKey::new(location, location, CurveKeyKind::Linear));
+        }
+        let locations = curve
+            .keys()
+            .iter()
+            .map(|k| k.location())
+            .collect::<Vec<_>>();
+        assert_eq!(locations, vec![0.0, 1.0, 1.0, 2.0, 3.0]);
+    }
+
+    /// Makes random cubic curve which tangents satisfy Fritsch-Carlson condition, so every
+    /// segment must be monotone.
+    fn make_monotone_cubic_curve(rng: &mut StdRng) -> Curve {
+        let count = rng.gen_range(2, 10);
+        let mut location = rng.gen_range(-10.0, 10.0);
+        let mut keys = Vec::new();
+        for _ in 0..count {
+            keys.push(CurveKey::new(
+                location,
+                rng.gen_range(-10.0, 10.0),
+                CurveKeyKind::Cubic {
+                    left_tangent: 0.0,
+                    right_tangent: 0.0,
+                },
+            ));
+            location += rng.gen_range(0.1, 5.0);
+        }
+        for i in 0..keys.len() - 1 {
+            let slope =
+                (keys[i + 1].value - keys[i].value) / (keys[i + 1].location - keys[i].location);
+            if let CurveKeyKind::Cubic { right_tangent, .. } = &mut keys[i].kind {
+                *right_tangent = slope * rng.gen_range(0.0, 3.0);
+            }
+            if let CurveKeyKind::Cubic { left_tangent, .. } = &mut keys[i + 1].kind {
+                *left_tangent = slope * rng.gen_range(0.0, 3.0);
+            }
+        }
+        Curve::from_keys(keys)
+    }
+
+    #[test]
+    fn test_curve_cubic_passes_through_keys() {
+        let mut rng = StdRng::seed_from_u64(123);
+        for _ in 0..100 {
+            let curve = make_monotone_cubic_curve(&mut rng);
+            for key in curve.keys() {
+                assert_eq!(curve.value_at(key.location()), key.value());
+            }
+            let first = curve.keys().first().unwrap();
+            let last = curve.keys().last().unwrap();
+            assert_eq!(curve.value_at(first.location() - 1.0), first.value());
+            assert_eq!(curve.value_at(last.location() + 1.0), last.value());
+        }
+    }
+
+    #[test]
+    fn test_curve_cubic_monotone_between_keys() {
+        let mut rng = StdRng::seed_from_u64(321);
+        for _ in 0..100 {
+            let curve = make_monotone_cubic_curve(&mut rng);
+            for pair in curve.keys().windows(2) {
+                let (left, right) = (pair[0], pair[1]);
+                let sign = (right.value() - left.value()).signum();
+                let tolerance = 1.0e-4 * (1.0 + left.value().abs().max(right.value().abs()));
+                let mut prev = left.value();
+                for i in 1..=50 {
+                    let location =
+                        left.location() + (right.location() - left.location()) * i as f32 / 50.0;
+                    let value = curve.value_at(location);
+                    assert!((value - prev) * sign >= -tolerance);
+                    prev = value;
+                }
+            }
+        }
+    }
+}
@@ -196,6 +196,8 @@ fn quadrilaterals_triangulation_concave() {
 
 #[test]
 fn ear_clip_test() {
+    use nalgebra::{Point3, Unit, UnitQuaternion};
+
     let polygon = vec![
         Vector3::new(0.0, 0.0, 1.0),
         Vector3::new(1.0, 2.0, 1.0),
@@ -226,9 +228,12 @@ fn ear_clip_test() {
         let mut angle: f32 = 0.0;
         while angle <= 360.0 {
             let mrot =
-                Matrix4::from_quat(UnitQuaternion::from_axis_angle(*axis, angle.to_radians()));
-            let rotated: Vec<Vector3<f32>> =
-                polygon.iter().map(|v| mrot.transform_point(*v)).collect();
+                UnitQuaternion::from_axis_angle(&Unit::new_normalize(*axis), angle.to_radians())
+                    .to_homogeneous();
+            let rotated: Vec<Vector3<f32>> = polygon
+                .iter()
+                .map(|v| mrot.transform_point(&Point3::from(*v)).coords)
+                .collect();
             let mut new_indices = Vec::new();
             triangulate(rotated.as_slice(), &mut new_indices);
             assert_eq!(new_indices, ref_indices);
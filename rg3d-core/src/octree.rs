@@ -1,9 +1,30 @@
+//! Octree is a spatial index for fast point, sphere, box, ray and frustum queries against
+//! large sets of objects described by their bounding boxes.
+//!
+//! Each object of octree is identified by its index in the array of bounds the octree was
+//! built from, so payload is stored outside the octree. Queries return indices of objects
+//! whose bounds intersects the query shape.
+//!
+//! # Performance
+//!
+//! Octree stores objects only in leaves, an object that intersects multiple leaves will be
+//! stored in each of them. Octree is `Send + Sync`, so queries can be done from multiple
+//! threads at once. Large octrees are built in parallel using all available threads.
+
 use crate::algebra::Vector3;
 use crate::{
-    math::{aabb::AxisAlignedBoundingBox, ray::Ray},
+    math::{aabb::AxisAlignedBoundingBox, frustum::Frustum, ray::Ray},
     pool::{Handle, Pool},
 };
 use arrayvec::{Array, ArrayVec};
+use rayon::prelude::*;
+
+/// Maximum depth of octree. Prevents infinite splitting when a lot of objects
+/// intersect each other.
+const MAX_DEPTH: usize = 16;
+
+/// Amount of objects in a node starting from which its leaves are built in parallel.
+const PARALLEL_BUILD_THRESHOLD: usize = 4096;
 
 #[derive(Clone, Debug)]
 pub enum OctreeNode {
@@ -17,121 +38,301 @@ pub enum OctreeNode {
     },
 }
 
+impl OctreeNode {
+    fn bounds(&self) -> &AxisAlignedBoundingBox {
+        match self {
+            OctreeNode::Leaf { bounds, .. } => bounds,
+            OctreeNode::Branch { bounds, .. } => bounds,
+        }
+    }
+}
+
 #[derive(Default, Clone, Debug)]
 pub struct Octree {
     nodes: Pool<OctreeNode>,
     root: Handle<OctreeNode>,
+    // Bounds of every object, `None` means that object was removed.
+    items: Vec<Option<AxisAlignedBoundingBox>>,
+    // Objects that does not fit into bounds of root node.
+    outside: Vec<u32>,
+    split_threshold: usize,
 }
 
 impl Octree {
+    /// Creates new octree from a set of triangles, each triangle is identified by its index.
     pub fn new(triangles: &[[Vector3<f32>; 3]], split_threshold: usize) -> Self {
-        // Calculate bounds.
+        let bounds = triangles
+            .iter()
+            .map(|triangle| AxisAlignedBoundingBox::from_points(triangle))
+            .collect::<Vec<_>>();
+
+        Self::from_aabbs(&bounds, split_threshold)
+    }
+
+    /// Creates new octree from a set of bounding boxes, each object is identified by index of
+    /// its bounds. Leaf is split when it contains more than `split_threshold` objects.
+    pub fn from_aabbs(aabbs: &[AxisAlignedBoundingBox], split_threshold: usize) -> Self {
         let mut bounds = AxisAlignedBoundingBox::default();
-        for triangle in triangles {
-            for pt in triangle.iter() {
-                bounds.add_point(*pt);
+        for aabb in aabbs {
+            bounds.add_box(*aabb);
+        }
+
+        let mut octree = Self {
+            nodes: Pool::new(),
+            root: Handle::NONE,
+            items: aabbs.iter().map(|aabb| Some(*aabb)).collect(),
+            outside: Default::default(),
+            split_threshold: split_threshold.max(1),
+        };
+
+        if !aabbs.is_empty() {
+            let indices = (0..aabbs.len() as u32).collect();
+            let root = octree.make_node(bounds, indices, 0);
+            octree.root = octree.nodes.spawn(root);
+        }
+
+        octree
+    }
+
+    fn make_node(
+        &mut self,
+        bounds: AxisAlignedBoundingBox,
+        indices: Vec<u32>,
+        depth: usize,
+    ) -> OctreeNode {
+        let node = build_recursive(&self.items, bounds, indices, depth, self.split_threshold);
+        self.flatten(node)
+    }
+
+    // Moves temporary tree into the pool, children of returned node are already in the pool.
+    fn flatten(&mut self, node: BuildNode) -> OctreeNode {
+        match node {
+            BuildNode::Leaf { bounds, indices } => OctreeNode::Leaf { bounds, indices },
+            BuildNode::Branch { bounds, children } => {
+                let mut leaves = [Handle::NONE; 8];
+                for (leaf, child) in leaves.iter_mut().zip(children) {
+                    let child = self.flatten(child);
+                    *leaf = self.nodes.spawn(child);
+                }
+                OctreeNode::Branch { bounds, leaves }
             }
         }
+    }
 
-        // Get initial list of indices.
-        let mut indices = Vec::new();
-        for i in 0..triangles.len() {
-            indices.push(i as u32);
+    fn item_bounds(&self, index: u32) -> AxisAlignedBoundingBox {
+        self.items[index as usize].unwrap()
+    }
+
+    fn is_inside_root(&self, aabb: &AxisAlignedBoundingBox) -> bool {
+        self.root.is_some() && {
+            let bounds = self.nodes[self.root].bounds();
+            bounds.is_contains_point(aabb.min) && bounds.is_contains_point(aabb.max)
         }
+    }
 
-        let mut nodes = Pool::new();
-        let root = build_recursive(&mut nodes, triangles, bounds, indices, split_threshold);
+    /// Returns total amount of objects in the octree, including removed ones. Index of
+    /// next inserted object will be equal to this value.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
 
-        Self { nodes, root }
+    /// Returns true if octree has no objects.
+    pub fn is_empty(&self) -> bool {
+        self.items.iter().all(|item| item.is_none())
     }
 
-    pub fn sphere_query(&self, position: Vector3<f32>, radius: f32, buffer: &mut Vec<u32>) {
-        buffer.clear();
-        self.sphere_recursive_query(self.root, position, radius, buffer);
+    /// Returns amount of objects that are outside of bounds of the octree, see `insert`.
+    pub fn outside_count(&self) -> usize {
+        self.outside.len()
     }
 
-    fn sphere_recursive_query(
-        &self,
+    /// Returns bounds of an object, or `None` if object was removed.
+    pub fn bounds_of(&self, index: u32) -> Option<AxisAlignedBoundingBox> {
+        self.items.get(index as usize).cloned().flatten()
+    }
+
+    /// Adds new object with given bounds and returns its index. Objects that are outside of
+    /// bounds of the octree are stored in a separate list and checked on every query, so
+    /// it is better to rebuild the octree if a lot of such objects were added.
+    pub fn insert(&mut self, aabb: AxisAlignedBoundingBox) -> u32 {
+        let index = self.items.len() as u32;
+        self.items.push(Some(aabb));
+        self.link(index);
+        index
+    }
+
+    /// Removes an object from the octree. Index of removed object is not reused.
+    pub fn remove(&mut self, index: u32) {
+        if self.bounds_of(index).is_some() {
+            self.unlink(index);
+            self.items[index as usize] = None;
+        }
+    }
+
+    /// Changes bounds of an object, it should be used when object moves or changes its size.
+    pub fn move_item(&mut self, index: u32, aabb: AxisAlignedBoundingBox) {
+        if self.bounds_of(index).is_some() {
+            self.unlink(index);
+            self.items[index as usize] = Some(aabb);
+            self.link(index);
+        }
+    }
+
+    fn link(&mut self, index: u32) {
+        let aabb = self.item_bounds(index);
+        if self.is_inside_root(&aabb) {
+            self.link_recursive(self.root, index, &aabb, 0);
+        } else {
+            self.outside.push(index);
+        }
+    }
+
+    fn link_recursive(
+        &mut self,
         node: Handle<OctreeNode>,
-        position: Vector3<f32>,
-        radius: f32,
-        buffer: &mut Vec<u32>,
+        index: u32,
+        aabb: &AxisAlignedBoundingBox,
+        depth: usize,
     ) {
-        match self.nodes.borrow(node) {
+        match &mut self.nodes[node] {
             OctreeNode::Leaf { indices, bounds } => {
-                if bounds.is_intersects_sphere(position, radius) {
-                    buffer.extend_from_slice(indices)
+                if bounds.intersect_aabb(aabb) {
+                    indices.push(index);
+                    if indices.len() > self.split_threshold && depth < MAX_DEPTH {
+                        let bounds = *bounds;
+                        let indices = std::mem::take(indices);
+                        let new_node = self.make_node(bounds, indices, depth);
+                        self.nodes[node] = new_node;
+                    }
                 }
             }
             OctreeNode::Branch { bounds, leaves } => {
-                if bounds.is_intersects_sphere(position, radius) {
-                    for leaf in leaves {
-                        self.sphere_recursive_query(*leaf, position, radius, buffer)
+                if bounds.intersect_aabb(aabb) {
+                    for leaf in *leaves {
+                        self.link_recursive(leaf, index, aabb, depth + 1);
                     }
                 }
             }
         }
     }
 
-    pub fn aabb_query(&self, aabb: &AxisAlignedBoundingBox, buffer: &mut Vec<u32>) {
-        buffer.clear();
-        self.aabb_recursive_query(self.root, aabb, buffer);
+    fn unlink(&mut self, index: u32) {
+        if let Some(position) = self.outside.iter().position(|i| *i == index) {
+            self.outside.swap_remove(position);
+        } else if self.root.is_some() {
+            let aabb = self.item_bounds(index);
+            self.unlink_recursive(self.root, index, &aabb);
+        }
     }
 
-    fn aabb_recursive_query(
-        &self,
+    fn unlink_recursive(
+        &mut self,
         node: Handle<OctreeNode>,
+        index: u32,
         aabb: &AxisAlignedBoundingBox,
-        buffer: &mut Vec<u32>,
     ) {
-        match self.nodes.borrow(node) {
+        match &mut self.nodes[node] {
             OctreeNode::Leaf { indices, bounds } => {
                 if bounds.intersect_aabb(aabb) {
-                    buffer.extend_from_slice(indices)
+                    indices.retain(|i| *i != index);
                 }
             }
             OctreeNode::Branch { bounds, leaves } => {
                 if bounds.intersect_aabb(aabb) {
-                    for leaf in leaves {
-                        self.aabb_recursive_query(*leaf, aabb, buffer)
+                    for leaf in *leaves {
+                        self.unlink_recursive(leaf, index, aabb);
                     }
                 }
             }
         }
     }
 
-    pub fn ray_query(&self, ray: &Ray, buffer: &mut Vec<u32>) {
+    /// Collects indices of every object that satisfies given predicate, node bounds are
+    /// checked first so whole subtrees are skipped if they do not satisfy the predicate.
+    fn query<P>(&self, predicate: P, buffer: &mut Vec<u32>)
+    where
+        P: Fn(&AxisAlignedBoundingBox) -> bool,
+    {
         buffer.clear();
-        self.ray_recursive_query(self.root, ray, buffer);
+
+        if self.root.is_some() {
+            self.query_recursive(self.root, &predicate, buffer);
+        }
+
+        for &index in self.outside.iter() {
+            if predicate(&self.item_bounds(index)) {
+                buffer.push(index);
+            }
+        }
+
+        // Object could be stored in multiple leaves.
+        buffer.sort_unstable();
+        buffer.dedup();
     }
 
-    fn ray_recursive_query(&self, node: Handle<OctreeNode>, ray: &Ray, buffer: &mut Vec<u32>) {
+    fn query_recursive<P>(&self, node: Handle<OctreeNode>, predicate: &P, buffer: &mut Vec<u32>)
+    where
+        P: Fn(&AxisAlignedBoundingBox) -> bool,
+    {
         match self.nodes.borrow(node) {
             OctreeNode::Leaf { indices, bounds } => {
-                if ray.box_intersection(&bounds.min, &bounds.max).is_some() {
-                    buffer.extend_from_slice(indices)
+                if predicate(bounds) {
+                    for &index in indices {
+                        if predicate(&self.item_bounds(index)) {
+                            buffer.push(index);
+                        }
+                    }
                 }
             }
             OctreeNode::Branch { bounds, leaves } => {
-                if ray.box_intersection(&bounds.min, &bounds.max).is_some() {
+                if predicate(bounds) {
                     for leaf in leaves {
-                        self.ray_recursive_query(*leaf, ray, buffer)
+                        self.query_recursive(*leaf, predicate, buffer)
                     }
                 }
             }
         }
     }
 
+    pub fn sphere_query(&self, position: Vector3<f32>, radius: f32, buffer: &mut Vec<u32>) {
+        self.query(
+            |bounds| bounds.is_intersects_sphere(position, radius),
+            buffer,
+        )
+    }
+
+    pub fn aabb_query(&self, aabb: &AxisAlignedBoundingBox, buffer: &mut Vec<u32>) {
+        self.query(|bounds| bounds.intersect_aabb(aabb), buffer)
+    }
+
+    /// Collects indices of objects whose bounds are intersected by given ray. Ray is treated
+    /// as a segment, so its direction must have length of the segment.
+    pub fn ray_query(&self, ray: &Ray, buffer: &mut Vec<u32>) {
+        self.query(|bounds| ray.aabb_intersection(bounds).is_some(), buffer)
+    }
+
+    pub fn frustum_query(&self, frustum: &Frustum, buffer: &mut Vec<u32>) {
+        self.query(|bounds| frustum.is_intersects_aabb(bounds), buffer)
+    }
+
+    pub fn point_query(&self, point: Vector3<f32>, buffer: &mut Vec<u32>) {
+        self.query(|bounds| bounds.is_contains_point(point), buffer)
+    }
+
     pub fn node(&self, handle: Handle<OctreeNode>) -> &OctreeNode {
         &self.nodes[handle]
     }
 
+    /// Collects leaves intersected by given ray. Leaves that does not fit into buffer are
+    /// ignored. Objects outside of bounds of the octree are not reported.
     pub fn ray_query_static<T>(&self, ray: &Ray, buffer: &mut ArrayVec<T>)
     where
         T: Array<Item = Handle<OctreeNode>>,
     {
         buffer.clear();
-        self.ray_recursive_query_static(self.root, ray, buffer);
+        if self.root.is_some() {
+            self.ray_recursive_query_static(self.root, ray, buffer);
+        }
     }
 
     fn ray_recursive_query_static<T>(
@@ -145,7 +346,7 @@ impl Octree {
         match self.nodes.borrow(node) {
             OctreeNode::Leaf { bounds, .. } => {
                 if ray.box_intersection(&bounds.min, &bounds.max).is_some() {
-                    buffer.push(node);
+                    let _ = buffer.try_push(node);
                 }
             }
             OctreeNode::Branch { bounds, leaves } => {
@@ -157,73 +358,71 @@ impl Octree {
             }
         }
     }
+}
 
-    pub fn point_query(&self, point: Vector3<f32>, buffer: &mut Vec<u32>) {
-        buffer.clear();
-        self.point_recursive_query(self.root, point, buffer);
-    }
-
-    fn point_recursive_query(
-        &self,
-        node: Handle<OctreeNode>,
-        point: Vector3<f32>,
-        buffer: &mut Vec<u32>,
-    ) {
-        match self.nodes.borrow(node) {
-            OctreeNode::Leaf { indices, bounds } => {
-                if bounds.is_contains_point(point) {
-                    buffer.extend_from_slice(indices)
-                }
-            }
-            OctreeNode::Branch { bounds, leaves } => {
-                if bounds.is_contains_point(point) {
-                    for leaf in leaves {
-                        self.point_recursive_query(*leaf, point, buffer)
-                    }
-                }
-            }
-        }
-    }
+/// Temporary tree which is built without access to the pool of nodes, so independent
+/// subtrees can be built in parallel.
+enum BuildNode {
+    Leaf {
+        bounds: AxisAlignedBoundingBox,
+        indices: Vec<u32>,
+    },
+    Branch {
+        bounds: AxisAlignedBoundingBox,
+        children: Vec<BuildNode>,
+    },
 }
 
 fn build_recursive(
-    nodes: &mut Pool<OctreeNode>,
-    triangles: &[[Vector3<f32>; 3]],
+    items: &[Option<AxisAlignedBoundingBox>],
     bounds: AxisAlignedBoundingBox,
     indices: Vec<u32>,
+    depth: usize,
     split_threshold: usize,
-) -> Handle<OctreeNode> {
-    if indices.len() <= split_threshold {
-        nodes.spawn(OctreeNode::Leaf { bounds, indices })
-    } else {
-        let mut leaves = [Handle::NONE; 8];
-        let leaf_bounds = split_bounds(bounds);
-
-        for i in 0..8 {
-            let mut leaf_indices = Vec::new();
+) -> BuildNode {
+    if indices.len() <= split_threshold || depth >= MAX_DEPTH {
+        return BuildNode::Leaf { bounds, indices };
+    }
 
-            for index in indices.iter() {
-                let index = *index;
+    let leaf_bounds = split_bounds(bounds);
 
-                let triangle_bounds =
-                    AxisAlignedBoundingBox::from_points(&triangles[index as usize]);
+    let classify = |leaf_bounds: &AxisAlignedBoundingBox| {
+        indices
+            .iter()
+            .copied()
+            .filter(|&index| items[index as usize].unwrap().intersect_aabb(leaf_bounds))
+            .collect::<Vec<_>>()
+    };
+    let parallel = indices.len() >= PARALLEL_BUILD_THRESHOLD;
+    let leaf_indices = if parallel {
+        leaf_bounds[..].par_iter().map(classify).collect::<Vec<_>>()
+    } else {
+        leaf_bounds.iter().map(classify).collect::<Vec<_>>()
+    };
 
-                if triangle_bounds.intersect_aabb(&bounds) {
-                    leaf_indices.push(index);
-                }
-            }
+    // Splitting makes no sense if every object intersects every leaf.
+    if leaf_indices.iter().all(|i| i.len() == indices.len()) {
+        return BuildNode::Leaf { bounds, indices };
+    }
 
-            leaves[i] = build_recursive(
-                nodes,
-                triangles,
-                leaf_bounds[i],
-                leaf_indices,
-                split_threshold,
-            );
-        }
+    let build_leaf = |(indices, bounds): (Vec<u32>, &AxisAlignedBoundingBox)| {
+        build_recursive(items, *bounds, indices, depth + 1, split_threshold)
+    };
+    let children = if parallel {
+        leaf_indices
+            .into_par_iter()
+            .zip(leaf_bounds[..].par_iter())
+            .map(build_leaf)
+            .collect()
+    } else {
+        leaf_indices
+            .into_iter()
+            .zip(leaf_bounds.iter())
+            .map(build_leaf)
+            .collect()
+    };
 
-        nodes.spawn(OctreeNode::Branch { leaves, bounds })
-    }
+    BuildNode::Branch { bounds, children }
 }
 
 fn split_bounds(bounds: AxisAlignedBoundingBox) -> [AxisAlignedBoundingBox; 8] {
@@ -268,11 +467,178 @@ fn split_bounds(bounds: AxisAlignedBoundingBox) -> [AxisAlignedBoundingBox; 8] {
 
 #[cfg(test)]
 mod test {
-    use crate::octree::Octree;
+    use crate::{
+        algebra::{Matrix4, Point3, Vector3},
+        math::{aabb::AxisAlignedBoundingBox, frustum::Frustum, ray::Ray},
+        octree::Octree,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn random_boxes(count: usize) -> Vec<AxisAlignedBoundingBox> {
+        let mut rng = StdRng::seed_from_u64(123);
+        (0..count)
+            .map(|_| {
+                let min = Vector3::new(
+                    rng.gen_range(-100.0, 100.0),
+                    rng.gen_range(-100.0, 100.0),
+                    rng.gen_range(-100.0, 100.0),
+                );
+                let size = Vector3::new(
+                    rng.gen_range(0.0, 5.0),
+                    rng.gen_range(0.0, 5.0),
+                    rng.gen_range(0.0, 5.0),
+                );
+                AxisAlignedBoundingBox::from_min_max(min, min + size)
+            })
+            .collect()
+    }
+
+    // Reference implementation of a query.
+    fn linear_query<P>(boxes: &[Option<AxisAlignedBoundingBox>], predicate: P) -> Vec<u32>
+    where
+        P: Fn(&AxisAlignedBoundingBox) -> bool,
+    {
+        boxes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, b)| b.filter(|b| predicate(b)).map(|_| i as u32))
+            .collect()
+    }
+
+    #[test]
+    fn octree_queries_match_linear_scan() {
+        // Enough objects to build upper levels of the octree in parallel.
+        let boxes = random_boxes(5000);
+        let octree = Octree::from_aabbs(&boxes, 16);
+        let items = boxes.iter().cloned().map(Some).collect::<Vec<_>>();
+        let mut buffer = Vec::new();
+
+        let position = Vector3::new(10.0, -5.0, 3.0);
+        octree.sphere_query(position, 20.0, &mut buffer);
+        assert!(!buffer.is_empty());
+        assert_eq!(
+            buffer,
+            linear_query(&items, |b| b.is_intersects_sphere(position, 20.0))
+        );
+
+        let aabb = AxisAlignedBoundingBox::from_min_max(
+            Vector3::new(-30.0, -30.0, -30.0),
+            Vector3::new(0.0, 10.0, 20.0),
+        );
+        octree.aabb_query(&aabb, &mut buffer);
+        assert_eq!(buffer, linear_query(&items, |b| b.intersect_aabb(&aabb)));
+
+        let ray = Ray::from_two_points(
+            &Vector3::new(-120.0, -80.0, -50.0),
+            &Vector3::new(120.0, 90.0, 60.0),
+        )
+        .unwrap();
+        octree.ray_query(&ray, &mut buffer);
+        assert_eq!(
+            buffer,
+            linear_query(&items, |b| ray.aabb_intersection(b).is_some())
+        );
+
+        let point = boxes[42].center();
+        octree.point_query(point, &mut buffer);
+        assert!(buffer.contains(&42));
+        assert_eq!(buffer, linear_query(&items, |b| b.is_contains_point(point)));
+
+        let view_projection = Matrix4::new_perspective(1.0, 1.0, 0.1, 50.0)
+            * Matrix4::look_at_rh(
+                &Point3::new(0.0, 0.0, 0.0),
+                &Point3::new(0.0, 0.0, 1.0),
+                &Vector3::y(),
+            );
+        let frustum = Frustum::from(view_projection).unwrap();
+        octree.frustum_query(&frustum, &mut buffer);
+        assert_eq!(
+            buffer,
+            linear_query(&items, |b| frustum.is_intersects_aabb(b))
+        );
+    }
+
+    #[test]
+    fn octree_incremental_update() {
+        let boxes = random_boxes(1000);
+        let mut octree = Octree::from_aabbs(&boxes, 8);
+        let mut items = boxes.iter().cloned().map(Some).collect::<Vec<_>>();
+        let mut buffer = Vec::new();
+
+        let everything = AxisAlignedBoundingBox::from_min_max(
+            Vector3::new(-1000.0, -1000.0, -1000.0),
+            Vector3::new(1000.0, 1000.0, 1000.0),
+        );
+
+        // Move into dense area to force splitting and far outside of the octree.
+        let mut rng = StdRng::seed_from_u64(321);
+        for i in 0..200u32 {
+            let aabb = if i % 10 == 0 {
+                AxisAlignedBoundingBox::from_min_max(
+                    Vector3::new(500.0, 500.0, 500.0),
+                    Vector3::new(501.0, 501.0, 501.0),
+                )
+            } else {
+                let min = Vector3::new(
+                    rng.gen_range(0.0, 1.0),
+                    rng.gen_range(0.0, 1.0),
+                    rng.gen_range(0.0, 1.0),
+                );
+                AxisAlignedBoundingBox::from_min_max(min, min.add_scalar(0.1))
+            };
+            octree.move_item(i, aabb);
+            items[i as usize] = Some(aabb);
+        }
+
+        for i in (200..400u32).step_by(3) {
+            octree.remove(i);
+            items[i as usize] = None;
+        }
+        octree.remove(0);
+        items[0] = None;
+
+        let new_box = AxisAlignedBoundingBox::from_min_max(
+            Vector3::new(0.2, 0.2, 0.2),
+            Vector3::new(0.3, 0.3, 0.3),
+        );
+        assert_eq!(octree.insert(new_box), items.len() as u32);
+        items.push(Some(new_box));
+
+        octree.aabb_query(&everything, &mut buffer);
+        assert_eq!(buffer, linear_query(&items, |_| true));
+
+        let position = Vector3::new(0.5, 0.5, 0.5);
+        octree.sphere_query(position, 0.4, &mut buffer);
+        assert!(buffer.contains(&(items.len() as u32 - 1)));
+        assert_eq!(
+            buffer,
+            linear_query(&items, |b| b.is_intersects_sphere(position, 0.4))
+        );
+
+        octree.point_query(Vector3::new(500.5, 500.5, 500.5), &mut buffer);
+        assert_eq!(
+            buffer,
+            linear_query(&items, |b| b
+                .is_contains_point(Vector3::new(500.5, 500.5, 500.5)))
+        );
+        assert!(!buffer.contains(&0));
+    }
+
+    #[test]
+    fn octree_of_overlapping_objects() {
+        // Every object is the same, it must not split infinitely.
+        let boxes = vec![AxisAlignedBoundingBox::unit(); 1000];
+        let octree = Octree::from_aabbs(&boxes, 4);
+        let mut buffer = Vec::new();
+        octree.point_query(Vector3::default(), &mut buffer);
+        assert_eq!(buffer.len(), boxes.len());
+    }
 
     #[test]
-    fn octree() {
-        // TODO
-        // It works fine in rusty-shooter game.
+    fn empty_octree() {
+        let mut buffer = vec![1, 2, 3];
+        Octree::default().sphere_query(Vector3::default(), 1.0, &mut buffer);
+        assert!(buffer.is_empty());
+        assert!(Octree::from_aabbs(&[], 8).is_empty());
     }
 }
@@ -19,6 +19,7 @@
 #![allow(clippy::unneeded_field_pattern)]
 
 use crate::visitor::{Visit, VisitResult, Visitor};
+use rayon::prelude::*;
 use std::ops::{Index, IndexMut};
 use std::{
     fmt::{Debug, Formatter},
@@ -705,7 +706,7 @@ impl<T> Pool<T> {
     #[inline]
     #[must_use]
     pub fn alive_count(&self) -> usize {
-        self.records.iter().count()
+        self.iter().count()
     }
 
     #[inline]
@@ -815,11 +816,50 @@ impl<T> Pool<T> {
         }
     }
 
+    /// Creates new parallel iterator over filled records. It allows to process every object of
+    /// the pool on multiple threads without collecting references to the objects first. Order
+    /// in which objects are processed is unspecified.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rg3d_core::pool::Pool;
+    /// use rayon::prelude::*;
+    /// let mut pool = Pool::<u32>::new();
+    /// pool.spawn(1);
+    /// pool.spawn(2);
+    /// pool.par_iter_mut().for_each(|value| *value *= 10);
+    /// assert_eq!(pool.iter().copied().collect::<Vec<_>>(), vec![10, 20]);
+    /// ```
+    pub fn par_iter_mut(&mut self) -> impl ParallelIterator<Item = &mut T>
+    where
+        T: Send,
+    {
+        self.records
+            .par_iter_mut()
+            .filter_map(|record| record.payload.as_mut())
+    }
+
     /// Retains pool records selected by `pred`. Useful when you need to remove all pool records
-    /// by some criteria.
+    /// by some criteria. Predicate receives handle of a record together with its payload.
+    /// Records rejected by the predicate are freed exactly as [`free`] does - their indices
+    /// go to the free list and generation will be bumped when a record is reused, so every
+    /// handle to a removed object becomes invalid.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rg3d_core::pool::Pool;
+    /// let mut pool = Pool::<u32>::new();
+    /// let a = pool.spawn(1);
+    /// let b = pool.spawn(2);
+    /// pool.retain(|handle, _| handle != a);
+    /// assert!(!pool.is_valid_handle(a));
+    /// assert!(pool.is_valid_handle(b));
+    /// ```
     pub fn retain<F>(&mut self, mut pred: F)
     where
-        F: FnMut(&T) -> bool,
+        F: FnMut(Handle<T>, &T) -> bool,
     {
         for (i, record) in self.records.iter_mut().enumerate() {
             if record.generation == INVALID_GENERATION {
@@ -827,7 +867,7 @@ impl<T> Pool<T> {
             }
 
             let retain = if let Some(payload) = record.payload.as_ref() {
-                pred(payload)
+                pred(Handle::new(i as u32, record.generation), payload)
             } else {
                 continue;
             };
@@ -839,6 +879,71 @@ impl<T> Pool<T> {
         }
     }
 
+    /// Reserves capacity for at least `additional` more records, so pool won't reallocate
+    /// its memory when new objects are spawned. Useful to move possible reallocation to
+    /// some moment when it does not hurt, for example at loading stage.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.records.reserve(additional);
+    }
+
+    /// Puts an object at exact place given by the handle. This allows to reproduce exactly the
+    /// same handles as on some other pool, for example when objects are replicated over network.
+    /// Pool will be extended if handle's index is out of bounds, skipped records will be put
+    /// into the free list.
+    ///
+    /// # Errors
+    ///
+    /// Returns payload back if handle has invalid generation, if the record at handle's index is already
+    /// occupied or reserved by [`take_reserve`](Self::take_reserve), or if generation of handle is not
+    /// greater than generation of the record (spawning at such handle would make already dead handles
+    /// valid again).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rg3d_core::pool::{Handle, Pool};
+    /// let mut pool = Pool::<u32>::new();
+    /// let handle = pool.spawn_at(Handle::new(3, 5), 123).unwrap();
+    /// assert_eq!(pool[handle], 123);
+    /// assert!(pool.spawn_at(handle, 321).is_err());
+    /// ```
+    pub fn spawn_at(&mut self, handle: Handle<T>, payload: T) -> Result<Handle<T>, T> {
+        if handle.generation == INVALID_GENERATION {
+            return Err(payload);
+        }
+
+        let index = handle.index as usize;
+
+        if let Some(record) = self.records.get(index) {
+            // Vacant record which is not in free list is reserved by a ticket.
+            let reserved = record.payload.is_none() && !self.free_stack.contains(&handle.index);
+            if record.payload.is_some() || reserved || record.generation >= handle.generation {
+                return Err(payload);
+            }
+        } else {
+            // Fill the gap with vacant records and make them available for further spawns.
+            for i in self.records.len()..=index {
+                self.records.push(PoolRecord::default());
+                if i != index {
+                    self.free_stack.push(i as u32);
+                }
+            }
+        }
+
+        // Record may be in free list, it must be removed from there to prevent spawning
+        // over the payload later on.
+        if let Some(position) = self.free_stack.iter().position(|i| *i == handle.index) {
+            self.free_stack.remove(position);
+        }
+
+        let record = &mut self.records[index];
+        record.generation = handle.generation;
+        record.payload = Some(payload);
+
+        Ok(handle)
+    }
+
     fn end(&self) -> *const PoolRecord<T> {
         unsafe { self.records.as_ptr().add(self.records.len()) }
     }
@@ -982,7 +1087,7 @@ impl<'a, T> Iterator for PoolPairIteratorMut<'a, T> {
 
 #[cfg(test)]
 mod test {
-    use crate::pool::{Pool, INVALID_GENERATION};
+    use crate::pool::{Handle, Pool, INVALID_GENERATION};
 
     #[test]
     fn pool_sanity_tests() {
@@ -1039,4 +1144,125 @@ mod test {
         assert_eq!(pool.handle_of(pool.borrow(bar)), bar);
         assert_eq!(pool.handle_of(pool.borrow(baz)), baz);
     }
+
+    #[test]
+    fn retain_frees_rejected_records() {
+        let mut pool = Pool::new();
+        let handles = (0..6).map(|i| pool.spawn(i)).collect::<Vec<_>>();
+
+        pool.retain(|handle, &value| {
+            assert_eq!(handle.index, value);
+            value % 2 == 0
+        });
+
+        assert_eq!(pool.alive_count(), 3);
+        for (i, &handle) in handles.iter().enumerate() {
+            assert_eq!(pool.is_valid_handle(handle), i % 2 == 0);
+        }
+
+        // Freed records must be reused and handles to them must have new generation.
+        let reused = (0..3).map(|i| pool.spawn(10 + i)).collect::<Vec<_>>();
+        assert_eq!(pool.get_capacity(), handles.len());
+        for handle in reused {
+            let old = handles[handle.index as usize];
+            assert_eq!(old.index % 2, 1);
+            assert!(handle.generation > old.generation);
+            assert!(!pool.is_valid_handle(old));
+        }
+        assert_eq!(pool.alive_count(), 6);
+    }
+
+    #[test]
+    fn pair_iterators() {
+        let mut pool = Pool::new();
+        let a = pool.spawn(1);
+        let b = pool.spawn(2);
+        let c = pool.spawn(3);
+        pool.free(b);
+
+        for (handle, value) in pool.pair_iter_mut() {
+            *value += handle.index * 10;
+        }
+
+        let pairs = pool.pair_iter().collect::<Vec<_>>();
+        assert_eq!(pairs, vec![(a, &1), (c, &23)]);
+    }
+
+    #[test]
+    fn reserve() {
+        let mut pool = Pool::<u32>::new();
+        pool.reserve(10);
+        assert_eq!(pool.get_capacity(), 0);
+        assert!(pool.records.capacity() >= 10);
+    }
+
+    #[test]
+    fn spawn_at() {
+        let mut pool = Pool::new();
+
+        let handle = Handle::new(3, 7);
+        assert_eq!(pool.spawn_at(handle, 3), Ok(handle));
+        assert_eq!(pool[handle], 3);
+        assert_eq!(pool.alive_count(), 1);
+
+        // Occupied record and invalid generation must be rejected.
+        assert_eq!(pool.spawn_at(handle, 4), Err(4));
+        assert_eq!(pool.spawn_at(Handle::new(1, INVALID_GENERATION), 5), Err(5));
+
+        // Gap must be available for regular spawns and must never hit spawned record.
+        let mut spawned = (0..4).map(|i| pool.spawn(i)).collect::<Vec<_>>();
+        spawned.sort_by_key(|h| h.index);
+        assert_eq!(
+            spawned.iter().map(|h| h.index).collect::<Vec<_>>(),
+            vec![0, 1, 2, 4]
+        );
+        assert_eq!(pool[handle], 3);
+
+        // Dead handles must not become valid again.
+        pool.free(handle);
+        let newer = pool.spawn(8);
+        assert_eq!(newer.index, 3);
+        pool.free(newer);
+        assert_eq!(pool.spawn_at(handle, 9), Err(9));
+        let replicated = Handle::new(3, newer.generation + 1);
+        assert_eq!(pool.spawn_at(replicated, 10), Ok(replicated));
+        assert!(!pool.is_valid_handle(newer));
+        assert_eq!(pool.alive_count(), 5);
+    }
+
+    #[test]
+    fn spawn_at_freed_record_of_same_generation() {
+        let mut pool = Pool::new();
+        let handle = pool.spawn(1);
+        pool.free(handle);
+
+        // Freed handle must stay dead.
+        assert_eq!(pool.spawn_at(handle, 2), Err(2));
+        assert!(!pool.is_valid_handle(handle));
+
+        let newer = Handle::new(handle.index, handle.generation + 1);
+        assert_eq!(pool.spawn_at(newer, 3), Ok(newer));
+        assert_eq!(pool[newer], 3);
+    }
+
+    #[test]
+    fn spawn_at_reserved_record() {
+        let mut pool = Pool::new();
+        let handle = pool.spawn(1);
+        let (ticket, value) = pool.take_reserve(handle);
+
+        // Reserved record belongs to the ticket, no matter of generation.
+        assert_eq!(pool.spawn_at(handle, 2), Err(2));
+        let newer = Handle::new(handle.index, handle.generation + 1);
+        assert_eq!(pool.spawn_at(newer, 3), Err(3));
+
+        assert_eq!(pool.put_back(ticket, value), handle);
+        assert_eq!(pool[handle], 1);
+
+        // Record becomes available again once ticket is forgotten.
+        let (ticket, _) = pool.take_reserve(handle);
+        pool.forget_ticket(ticket);
+        assert_eq!(pool.spawn_at(newer, 4), Ok(newer));
+        assert_eq!(pool[newer], 4);
+    }
 }
@@ -127,6 +127,14 @@ pub struct Data<'a> {
     vec: &'a mut Vec<u8>,
 }
 
+impl<'a> Data<'a> {
+    /// Wraps given bytes, so they can be visited as a single field. Bytes are replaced with
+    /// content of the field on read.
+    pub fn new(vec: &'a mut Vec<u8>) -> Self {
+        Self { vec }
+    }
+}
+
 impl_field_data!(u64, FieldKind::U64);
 impl_field_data!(i64, FieldKind::I64);
 impl_field_data!(u32, FieldKind::U32);
@@ -376,8 +384,7 @@ impl Field {
 
     fn load(file: &mut dyn Read) -> Result<Field, VisitError> {
         let name_len = file.read_u32::<LittleEndian>()? as usize;
-        let mut raw_name = Vec::with_capacity(name_len);
-        unsafe { raw_name.set_len(name_len) };
+        let mut raw_name = vec![0; name_len];
         file.read_exact(raw_name.as_mut_slice())?;
         let id = file.read_u8()?;
         Ok(Field::new(
@@ -415,8 +422,7 @@ impl Field {
                 }),
                 14 => FieldKind::Data({
                     let len = file.read_u32::<LittleEndian>()? as usize;
-                    let mut vec = Vec::with_capacity(len);
-                    unsafe { vec.set_len(len) };
+                    let mut vec = vec![0; len];
                     file.read_exact(vec.as_mut_slice())?;
                     vec
                 }),
@@ -631,8 +637,7 @@ impl Visitor {
 
     fn load_node_binary(&mut self, file: &mut dyn Read) -> Result<Handle<Node>, VisitError> {
         let name_len = file.read_u32::<LittleEndian>()? as usize;
-        let mut raw_name = Vec::with_capacity(name_len);
-        unsafe { raw_name.set_len(name_len) };
+        let mut raw_name = vec![0; name_len];
         file.read_exact(raw_name.as_mut_slice())?;
 
         let mut node = Node::default();
@@ -1235,7 +1240,7 @@ mod test {
 
             visitor.save_binary(path).unwrap();
             if let Ok(mut file) = File::create(Path::new("test.txt")) {
-                file.write(visitor.save_text().as_bytes()).unwrap();
+                file.write_all(visitor.save_text().as_bytes()).unwrap();
             }
         }
 
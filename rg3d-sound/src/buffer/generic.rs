@@ -20,8 +20,13 @@
 //! }
 //! ```
 
-use crate::{buffer::DataSource, decoder::Decoder};
-use rg3d_core::visitor::{Visit, VisitResult, Visitor};
+use crate::{
+    buffer::{DataSource, DownmixCoefficients},
+    decoder::Decoder,
+    dsp::resample::{self, ResamplingQuality},
+    error::SoundError,
+};
+use rg3d_core::visitor::{Data, Visit, VisitResult, Visitor};
 use std::path::Path;
 use std::{path::PathBuf, time::Duration};
 
@@ -54,6 +59,30 @@ impl Visit for GenericBuffer {
 
         self.external_source_path.visit("Path", visitor)?;
 
+        // Buffers that are not backed by a file (procedural or converted ones) cannot be
+        // reloaded, so their samples are saved as is. Old saves have no samples, such buffers
+        // stay empty.
+        if self.external_source_path.is_none() {
+            let mut channel_count = self.channel_count as u32;
+            let _ = channel_count.visit("ChannelCount", visitor);
+            let mut sample_rate = self.sample_rate as u32;
+            let _ = sample_rate.visit("SampleRate", visitor);
+            let mut bytes = self
+                .samples
+                .iter()
+                .flat_map(|s| s.to_le_bytes().to_vec())
+                .collect::<Vec<u8>>();
+            let _ = Data::new(&mut bytes).visit("Samples", visitor);
+            if visitor.is_reading() {
+                self.channel_count = channel_count as usize;
+                self.sample_rate = sample_rate as usize;
+                self.samples = bytes
+                    .chunks_exact(4)
+                    .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                    .collect();
+            }
+        }
+
         visitor.leave_region()
     }
 }
@@ -65,8 +94,8 @@ impl GenericBuffer {
     /// # Notes
     ///
     /// Data source with raw samples must have sample count multiple of channel count, otherwise this
-    /// function will return `Err`.
-    pub fn new(source: DataSource) -> Result<Self, DataSource> {
+    /// function will return `Err(SoundError::UnsupportedFormat)`.
+    pub fn new(source: DataSource) -> Result<Self, SoundError> {
         match source {
             DataSource::Raw {
                 sample_rate,
@@ -74,11 +103,7 @@ impl GenericBuffer {
                 samples,
             } => {
                 if samples.len() % channel_count != 0 {
-                    Err(DataSource::Raw {
-                        sample_rate,
-                        channel_count,
-                        samples,
-                    })
+                    Err(SoundError::UnsupportedFormat)
                 } else {
                     Ok(Self {
                         samples,
@@ -145,6 +170,87 @@ impl GenericBuffer {
         self.sample_rate
     }
 
+    /// Converts samples of the buffer to given sample rate, does nothing if buffer already has
+    /// this rate. Usually it is used right after loading to convert a buffer to the sample rate
+    /// of output device ([crate::context::SAMPLE_RATE]), so sources will not need to resample
+    /// it while playing. See [crate::dsp::resample] for more info.
+    ///
+    /// # Notes
+    ///
+    /// Sources that already use the buffer keep their old resampling settings, so it should be
+    /// done before the buffer is given to any source. It makes no sense for streaming buffers,
+    /// because they will overwrite the samples with new decoded data.
+    pub fn resample(&mut self, sample_rate: usize, quality: ResamplingQuality) {
+        if sample_rate == 0
+            || self.sample_rate == 0
+            || self.sample_rate == sample_rate
+            || self.channel_count == 0
+        {
+            return;
+        }
+        self.samples = resample::resample(
+            &self.samples,
+            self.channel_count,
+            self.sample_rate,
+            sample_rate,
+            quality,
+        );
+        self.sample_rate = sample_rate;
+    }
+
+    /// Creates new buffer with single channel. Stereo samples are mixed using given coefficients,
+    /// mono buffer is just copied. Returns `None` if buffer has more than two channels.
+    ///
+    /// # Notes
+    ///
+    /// New buffer is not associated with a file, so its samples are saved by serializer
+    /// instead of a path.
+    pub fn to_mono(&self, coefficients: DownmixCoefficients) -> Option<GenericBuffer> {
+        let samples = match self.channel_count {
+            1 => self.samples.clone(),
+            2 => self
+                .samples
+                .chunks_exact(2)
+                .map(|frame| frame[0] * coefficients.left + frame[1] * coefficients.right)
+                .collect(),
+            _ => return None,
+        };
+        Some(Self {
+            samples,
+            channel_count: 1,
+            sample_rate: self.sample_rate,
+            external_source_path: None,
+        })
+    }
+
+    /// Creates new buffer with two channels. Mono samples are duplicated to both channels,
+    /// stereo buffer is just copied. Returns `None` if buffer has more than two channels.
+    ///
+    /// # Notes
+    ///
+    /// New buffer is not associated with a file, so its samples are saved by serializer
+    /// instead of a path.
+    pub fn to_stereo(&self) -> Option<GenericBuffer> {
+        let samples = match self.channel_count {
+            1 => {
+                let mut samples = Vec::with_capacity(self.samples.len() * 2);
+                for &sample in self.samples.iter() {
+                    samples.push(sample);
+                    samples.push(sample);
+                }
+                samples
+            }
+            2 => self.samples.clone(),
+            _ => return None,
+        };
+        Some(Self {
+            samples,
+            channel_count: 2,
+            sample_rate: self.sample_rate,
+            external_source_path: None,
+        })
+    }
+
     /// Returns exact duration of the buffer.
     #[inline]
     pub fn duration(&self) -> Duration {
@@ -158,3 +264,91 @@ impl GenericBuffer {
         self.samples.len() - self.channel_count
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::buffer::{generic::GenericBuffer, DataSource, DownmixCoefficients, SoundBuffer};
+    use rg3d_core::visitor::{Visit, Visitor};
+
+    fn make_buffer(channel_count: usize, samples: Vec<f32>) -> GenericBuffer {
+        GenericBuffer::new(DataSource::Raw {
+            sample_rate: 22050,
+            channel_count,
+            samples,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_to_mono() {
+        let stereo = make_buffer(2, vec![1.0, 0.0, 0.5, 0.5, -1.0, 1.0]);
+
+        let mono = stereo.to_mono(DownmixCoefficients::default()).unwrap();
+        assert_eq!(mono.channel_count(), 1);
+        assert_eq!(mono.sample_rate(), 22050);
+        assert_eq!(mono.samples(), &[0.5, 0.5, 0.0]);
+
+        let left_only = DownmixCoefficients {
+            left: 1.0,
+            right: 0.0,
+        };
+        assert_eq!(
+            stereo.to_mono(left_only).unwrap().samples(),
+            &[1.0, 0.5, -1.0]
+        );
+
+        // Mono buffer is copied as is.
+        let mono = make_buffer(1, vec![0.1, 0.2, 0.3]);
+        assert_eq!(mono.to_mono(left_only).unwrap().samples(), mono.samples());
+
+        assert!(make_buffer(3, vec![0.0; 6])
+            .to_mono(DownmixCoefficients::default())
+            .is_none());
+    }
+
+    #[test]
+    fn test_to_stereo() {
+        let mono = make_buffer(1, vec![0.1, 0.2, 0.3]);
+
+        let stereo = mono.to_stereo().unwrap();
+        assert_eq!(stereo.channel_count(), 2);
+        assert_eq!(stereo.sample_rate(), 22050);
+        assert_eq!(stereo.samples(), &[0.1, 0.1, 0.2, 0.2, 0.3, 0.3]);
+
+        // Stereo buffer is copied as is.
+        assert_eq!(stereo.to_stereo().unwrap().samples(), stereo.samples());
+
+        assert!(make_buffer(3, vec![0.0; 6]).to_stereo().is_none());
+    }
+
+    #[test]
+    fn test_sound_buffer_conversion() {
+        let buffer = SoundBuffer::Generic(make_buffer(2, vec![1.0, 0.0]));
+        let mono = buffer.to_mono(DownmixCoefficients::default()).unwrap();
+        assert!(matches!(mono, SoundBuffer::Generic(_)));
+        assert_eq!(mono.samples(), &[0.5]);
+        assert_eq!(mono.to_stereo().unwrap().samples(), &[0.5, 0.5]);
+    }
+
+    #[test]
+    fn test_visit_converted_buffer() {
+        let mut mono = make_buffer(2, vec![1.0, 0.0, 0.5, 0.5])
+            .to_mono(DownmixCoefficients::default())
+            .unwrap();
+
+        let path = std::env::temp_dir().join("rg3d_sound_converted_buffer_test.bin");
+        let mut visitor = Visitor::new();
+        mono.visit("Buffer", &mut visitor).unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        let mut visitor = Visitor::load_binary(&path).unwrap();
+        let mut loaded = GenericBuffer::default();
+        loaded.visit("Buffer", &mut visitor).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(loaded.external_data_path(), None);
+        assert_eq!(loaded.channel_count(), 1);
+        assert_eq!(loaded.sample_rate(), 22050);
+        assert_eq!(loaded.samples(), &[0.5, 0.5]);
+    }
+}
@@ -7,8 +7,19 @@
 //! this is why each instance wrapped into `Arc<Mutex<>>`. Why not just load a buffer per source? This
 //! is just inefficient memory-wise. Sound samples are very heavy: for example a mono sound that lasts
 //! just 1 second will take ~172 Kb of memory (with 44100 Hz sampling rate and float sample representation).
+//!
+//! # Errors
+//!
+//! Every constructor of a buffer (`SoundBuffer::new_generic`, `SoundBuffer::new_streaming`,
+//! `SoundBuffer::raw_generic`, `SoundBuffer::raw_streaming`, `GenericBuffer::new` and
+//! `StreamingBuffer::new`) returns `SoundError` describing why the data cannot be loaded, for
+//! example `SoundError::InvalidWavChunk` for malformed wav file. This is a breaking change: previous
+//! versions returned the data source back instead and reason of the failure was lost.
 
-use crate::buffer::{generic::GenericBuffer, streaming::StreamingBuffer};
+use crate::{
+    buffer::{generic::GenericBuffer, streaming::StreamingBuffer},
+    error::SoundError,
+};
 use rg3d_core::visitor::{Visit, VisitError, VisitResult, Visitor};
 use std::{
     fs::File,
@@ -96,6 +107,26 @@ impl Seek for DataSource {
     }
 }
 
+/// Weights of channels that are used to mix a stereo buffer down to mono, see
+/// [SoundBuffer::to_mono]. Each output sample is `left * l + right * r`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DownmixCoefficients {
+    /// Weight of left channel.
+    pub left: f32,
+    /// Weight of right channel.
+    pub right: f32,
+}
+
+impl Default for DownmixCoefficients {
+    /// Averages both channels, so sound that is the same in both channels keeps its volume.
+    fn default() -> Self {
+        Self {
+            left: 0.5,
+            right: 0.5,
+        }
+    }
+}
+
 /// Sound buffer is a data source for sound sources. See module documentation for more info.
 #[derive(Debug)]
 pub enum SoundBuffer {
@@ -119,7 +150,7 @@ impl Default for SoundBuffer {
 impl SoundBuffer {
     /// Tries to create new streaming sound buffer from a given data source. Returns sound source
     /// wrapped into Arc<Mutex<>> that can be directly used with sound sources.
-    pub fn new_streaming(data_source: DataSource) -> Result<Arc<Mutex<Self>>, DataSource> {
+    pub fn new_streaming(data_source: DataSource) -> Result<Arc<Mutex<Self>>, SoundError> {
         Ok(Arc::new(Mutex::new(SoundBuffer::Streaming(
             StreamingBuffer::new(data_source)?,
         ))))
@@ -127,7 +158,7 @@ impl SoundBuffer {
 
     /// Tries to create new generic sound buffer from a given data source. Returns sound source
     /// wrapped into Arc<Mutex<>> that can be directly used with sound sources.
-    pub fn new_generic(data_source: DataSource) -> Result<Arc<Mutex<Self>>, DataSource> {
+    pub fn new_generic(data_source: DataSource) -> Result<Arc<Mutex<Self>>, SoundError> {
         Ok(Arc::new(Mutex::new(SoundBuffer::Generic(
             GenericBuffer::new(data_source)?,
         ))))
@@ -135,15 +166,49 @@ impl SoundBuffer {
 
     /// Tries to create new streaming sound buffer from a given data source. It returns raw sound
     /// buffer that has to be wrapped into Arc<Mutex<>> for use with sound sources.
-    pub fn raw_streaming(data_source: DataSource) -> Result<Self, DataSource> {
+    pub fn raw_streaming(data_source: DataSource) -> Result<Self, SoundError> {
         Ok(SoundBuffer::Streaming(StreamingBuffer::new(data_source)?))
     }
 
     /// Tries to create new generic sound buffer from a given data source. It returns raw sound
     /// buffer that has to be wrapped into Arc<Mutex<>> for use with sound sources.
-    pub fn raw_generic(data_source: DataSource) -> Result<Self, DataSource> {
+    pub fn raw_generic(data_source: DataSource) -> Result<Self, SoundError> {
         Ok(SoundBuffer::Generic(GenericBuffer::new(data_source)?))
     }
+
+    /// Returns total amount of frames (samples of each channel) in the buffer. Can be `None`
+    /// for streaming buffers whose decoder does not know length of data.
+    pub fn frame_count(&self) -> Option<usize> {
+        match self {
+            SoundBuffer::Generic(generic) => {
+                Some(generic.samples().len() / generic.channel_count().max(1))
+            }
+            SoundBuffer::Streaming(streaming) => streaming.frame_count(),
+        }
+    }
+
+    /// Creates new generic buffer with single channel from a mono or stereo buffer, channels
+    /// of stereo buffer are mixed using given coefficients. It is useful for spatial sounds,
+    /// because HRTF renderer can process only mono buffers. Returns `None` for streaming buffers
+    /// and buffers with more than two channels. See [GenericBuffer::to_mono] for more info.
+    pub fn to_mono(&self, coefficients: DownmixCoefficients) -> Option<SoundBuffer> {
+        match self {
+            SoundBuffer::Generic(generic) => {
+                generic.to_mono(coefficients).map(SoundBuffer::Generic)
+            }
+            SoundBuffer::Streaming(_) => None,
+        }
+    }
+
+    /// Creates new generic buffer with two channels from a mono or stereo buffer, the channel
+    /// of mono buffer is copied to both channels. Returns `None` for streaming buffers and
+    /// buffers with more than two channels. See [GenericBuffer::to_stereo] for more info.
+    pub fn to_stereo(&self) -> Option<SoundBuffer> {
+        match self {
+            SoundBuffer::Generic(generic) => generic.to_stereo().map(SoundBuffer::Generic),
+            SoundBuffer::Streaming(_) => None,
+        }
+    }
 }
 
 impl Deref for SoundBuffer {
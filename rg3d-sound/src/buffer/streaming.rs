@@ -79,11 +79,11 @@ impl StreamingBuffer {
     ///
     /// # Notes
     ///
-    /// This function will return Err if data source is `Raw`. It makes no sense to stream raw data which
-    /// is already loaded into memory. Use Generic source instead!
-    pub fn new(source: DataSource) -> Result<Self, DataSource> {
+    /// This function will return `Err(SoundError::UnsupportedFormat)` if data source is `Raw`. It makes
+    /// no sense to stream raw data which is already loaded into memory. Use Generic source instead!
+    pub fn new(source: DataSource) -> Result<Self, SoundError> {
         if let DataSource::Raw { .. } = source {
-            return Err(source);
+            return Err(SoundError::UnsupportedFormat);
         };
 
         let external_source_path = if let DataSource::File { path, .. } = &source {
@@ -134,6 +134,17 @@ impl StreamingBuffer {
         self.decoder.rewind()
     }
 
+    #[inline]
+    pub(in crate) fn frame_seek(&mut self, frame: usize) -> Result<(), SoundError> {
+        self.decoder.frame_seek(frame)
+    }
+
+    /// Returns total amount of frames (samples of each channel) in the data. Can be `None` if
+    /// internal decoder does not know length of data.
+    pub fn frame_count(&self) -> Option<usize> {
+        self.decoder.frame_count()
+    }
+
     #[inline]
     pub(in crate) fn time_seek(&mut self, location: Duration) {
         self.decoder.time_seek(location);
@@ -161,7 +172,10 @@ impl Visit for StreamingBuffer {
     fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
         visitor.enter_region(name)?;
 
-        self.generic.visit("Generic", visitor)?;
+        // Only path is saved, samples of current block are useless without a decoder.
+        visitor.enter_region("Generic")?;
+        self.generic.external_source_path.visit("Path", visitor)?;
+        visitor.leave_region()?;
 
         visitor.leave_region()
     }
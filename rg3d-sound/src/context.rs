@@ -4,6 +4,25 @@
 //!
 //! Context holds all sound sources, feeds renderer with samples, applies effects, applies master gain, etc.
 //!
+//! # Voice limit
+//!
+//! Every playing source takes some CPU time of mixer thread, so amount of simultaneously playing
+//! sources (voices) is limited. When limit is exceeded, least important sources are quickly faded
+//! out and stopped. See `GenericSource::set_priority` for more info.
+//!
+//! # Virtual sources
+//!
+//! Mixing of a lot of sources is expensive and when many sources play at once they're merged
+//! into indistinct noise anyway. So amount of audible sources is limited too, but unlike the voice
+//! limit, sources that exceed it are not stopped - they become virtual. Virtual source continues
+//! playing (its playback position advances), but it is not mixed. Each render playing sources are
+//! ranked by priority and effective gain (with distance attenuation and obstruction), so a virtual
+//! source will become audible again when it becomes important enough (for example when listener
+//! comes closer to it) and will continue from the right position. Sources are faded out when they
+//! become virtual and faded in when they become audible, so there are no clicks. See
+//! `Context::set_max_audible_sources`, `Context::audible_source_count` and
+//! `Context::virtual_source_count`.
+//!
 
 use crate::{
     device::run_device,
@@ -11,13 +30,15 @@ use crate::{
     error::SoundError,
     listener::Listener,
     renderer::{render_source_default, Renderer},
-    source::{SoundSource, Status},
+    source::{spatial::Obstruction, SoundSource, Status},
 };
 use rg3d_core::{
+    algebra::Vector3,
     pool::{Handle, Pool},
     visitor::{Visit, VisitResult, Visitor},
 };
 use std::{
+    cmp::Ordering,
     sync::{Arc, Mutex},
     time::{self, Duration},
 };
@@ -26,6 +47,9 @@ use std::{
 /// TODO: Make this configurable, for now its set to most commonly used sample rate of 44100 Hz.
 pub const SAMPLE_RATE: u32 = 44100;
 
+// Length of fade out of sources that are stopped because of voice limit.
+const VOICE_STEAL_FADE_DURATION: Duration = Duration::from_millis(20);
+
 /// Distance model defines how volume of sound will decay when distance to listener changes.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum DistanceModel {
@@ -70,6 +94,48 @@ pub enum DistanceModel {
     ExponentDistance,
 }
 
+/// A function that calculates obstruction of a spatial source. It takes position of the source
+/// and position of the listener (in this order) and usually casts a ray between them to find
+/// what is in the way.
+///
+/// # Notes
+///
+/// Provider is called from mixer thread for every playing spatial source, so it must be fast.
+pub type ObstructionProvider = Box<dyn FnMut(Vector3<f32>, Vector3<f32>) -> Obstruction + Send>;
+
+/// Information about an active (playing or paused) sound source, it is used to visualize sources
+/// for debugging, see `Context::debug_sources`.
+#[derive(Copy, Clone, Debug)]
+pub struct SourceDebugInfo {
+    /// Handle of the source.
+    pub handle: Handle<SoundSource>,
+    /// Position of the source in world space, `None` for generic sources.
+    pub position: Option<Vector3<f32>>,
+    /// Radius of the source, zero for generic sources.
+    pub radius: f32,
+    /// Max distance of the source, zero for generic sources.
+    pub max_distance: f32,
+    /// Gain of the source multiplied by its fade gain.
+    pub gain: f32,
+    /// Gain of the source as it is heard by listener, it includes distance attenuation and
+    /// obstruction for spatial sources.
+    pub effective_gain: f32,
+    /// Current status of the source.
+    pub status: Status,
+    /// Priority of the source.
+    pub priority: i32,
+    /// Whether the source is virtual or not, see module docs.
+    pub is_virtual: bool,
+}
+
+// Playing source that could be stopped to free a voice or made virtual.
+struct Voice {
+    index: usize,
+    priority: i32,
+    gain: f32,
+    age: u64,
+}
+
 /// See module docs.
 pub struct Context {
     sources: Pool<SoundSource>,
@@ -79,6 +145,13 @@ pub struct Context {
     renderer: Renderer,
     effects: Pool<Effect>,
     distance_model: DistanceModel,
+    obstruction_provider: Option<ObstructionProvider>,
+    max_voices: usize,
+    max_audible_sources: usize,
+    audible_source_count: usize,
+    virtual_source_count: usize,
+    // Reusable buffer to prevent memory allocations in mixer thread.
+    voices: Vec<Voice>,
 }
 
 impl Context {
@@ -91,21 +164,17 @@ impl Context {
     pub(in crate) const SAMPLES_PER_CHANNEL: usize =
         Self::HRTF_BLOCK_LEN * Self::HRTF_INTERPOLATION_STEPS;
 
+    /// Default amount of simultaneously playing sources.
+    pub const DEFAULT_MAX_VOICES: usize = 64;
+
+    /// Default amount of simultaneously audible sources.
+    pub const DEFAULT_MAX_AUDIBLE_SOURCES: usize = 32;
+
     /// Creates new instance of context. Internally context starts new thread which will call render all
     /// sound source and send samples to default output device. This method returns Arc<Mutex<Context>>
     /// because separate thread also uses context.
     pub fn new() -> Result<Arc<Mutex<Self>>, SoundError> {
-        let context = Self {
-            sources: Pool::new(),
-            listener: Listener::new(),
-            master_gain: 1.0,
-            render_duration: Default::default(),
-            renderer: Renderer::Default,
-            effects: Pool::new(),
-            distance_model: DistanceModel::InverseDistance,
-        };
-
-        let context = Arc::new(Mutex::new(context));
+        let context = Arc::new(Mutex::new(Self::without_device()));
 
         // Run device with a mixer callback. Mixer callback will mix samples
         // from source with a fixed rate.
@@ -121,6 +190,25 @@ impl Context {
         Ok(context)
     }
 
+    // Creates context that is not connected to output device.
+    fn without_device() -> Self {
+        Self {
+            sources: Pool::new(),
+            listener: Listener::new(),
+            master_gain: 1.0,
+            render_duration: Default::default(),
+            renderer: Renderer::Default,
+            effects: Pool::new(),
+            distance_model: DistanceModel::InverseDistance,
+            obstruction_provider: None,
+            max_voices: Self::DEFAULT_MAX_VOICES,
+            max_audible_sources: Self::DEFAULT_MAX_AUDIBLE_SOURCES,
+            audible_source_count: 0,
+            virtual_source_count: 0,
+            voices: Vec::new(),
+        }
+    }
+
     /// Sets new distance model.
     pub fn set_distance_model(&mut self, distance_model: DistanceModel) {
         self.distance_model = distance_model;
@@ -131,6 +219,37 @@ impl Context {
         self.distance_model
     }
 
+    /// Sets new obstruction provider which will be used to calculate obstruction of every playing
+    /// spatial source, it overrides obstruction that was set to sources manually. For example
+    /// provider could cast a ray through physics world and reduce gain of the source for each
+    /// obstacle hit. Pass `None` to stop calculating obstruction automatically.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rg3d_sound::{context::Context, source::spatial::Obstruction};
+    ///
+    /// fn setup(context: &mut Context) {
+    ///     // Muffle every source that is below the floor.
+    ///     context.set_obstruction_provider(Some(Box::new(|source, listener| {
+    ///         if source.y < 0.0 && listener.y >= 0.0 {
+    ///             Obstruction {
+    ///                 gain: 0.5,
+    ///                 cutoff_frequency: 800.0,
+    ///             }
+    ///         } else {
+    ///             Obstruction::NONE
+    ///         }
+    ///     })));
+    /// }
+    /// ```
+    pub fn set_obstruction_provider(
+        &mut self,
+        provider: Option<ObstructionProvider>,
+    ) -> Option<ObstructionProvider> {
+        std::mem::replace(&mut self.obstruction_provider, provider)
+    }
+
     /// Adds new effect to effects chain. Each sample from
     pub fn add_effect(&mut self, effect: Effect) -> Handle<Effect> {
         self.effects.spawn(effect)
@@ -173,6 +292,39 @@ impl Context {
         self.master_gain = gain;
     }
 
+    /// Sets maximum amount of simultaneously playing sources. When there are more playing
+    /// sources, least important ones will be faded out and stopped, fade out starts on next
+    /// render. See module docs.
+    pub fn set_max_voices(&mut self, max_voices: usize) {
+        self.max_voices = max_voices;
+    }
+
+    /// Returns maximum amount of simultaneously playing sources.
+    pub fn max_voices(&self) -> usize {
+        self.max_voices
+    }
+
+    /// Sets maximum amount of simultaneously audible sources. When there are more playing
+    /// sources, least important ones become virtual on next render. See module docs.
+    pub fn set_max_audible_sources(&mut self, max_audible_sources: usize) {
+        self.max_audible_sources = max_audible_sources;
+    }
+
+    /// Returns maximum amount of simultaneously audible sources.
+    pub fn max_audible_sources(&self) -> usize {
+        self.max_audible_sources
+    }
+
+    /// Returns amount of playing sources that were not virtual on last render.
+    pub fn audible_source_count(&self) -> usize {
+        self.audible_source_count
+    }
+
+    /// Returns amount of playing sources that were virtual on last render, see module docs.
+    pub fn virtual_source_count(&self) -> usize {
+        self.virtual_source_count
+    }
+
     /// Returns master gain.
     pub fn master_gain(&self) -> f32 {
         self.master_gain
@@ -203,6 +355,37 @@ impl Context {
         self.sources.borrow_mut(handle)
     }
 
+    /// Returns information about every playing or paused source. It is intended for debugging
+    /// purposes, for example to draw sources as gizmos to see where they are and how loud they
+    /// are.
+    pub fn debug_sources(&self) -> Vec<SourceDebugInfo> {
+        self.sources
+            .pair_iter()
+            .filter(|(_, source)| source.status() != Status::Stopped)
+            .map(|(handle, source)| {
+                let (position, radius, max_distance) = match source {
+                    SoundSource::Generic(_) => (None, 0.0, 0.0),
+                    SoundSource::Spatial(spatial) => (
+                        Some(spatial.position()),
+                        spatial.radius(),
+                        spatial.max_distance(),
+                    ),
+                };
+                SourceDebugInfo {
+                    handle,
+                    position,
+                    radius,
+                    max_distance,
+                    gain: source.gain() * source.fade_gain(),
+                    effective_gain: source.effective_gain(&self.listener, self.distance_model),
+                    status: source.status(),
+                    priority: source.priority(),
+                    is_virtual: source.is_virtual(),
+                }
+            })
+            .collect()
+    }
+
     /// Returns shared reference to listener. Engine has only one listener.
     pub fn listener(&self) -> &Listener {
         &self.listener
@@ -234,13 +417,34 @@ impl Context {
             }
         }
 
+        self.steal_voices();
+        self.virtualize_sources();
+
         for source in self
             .sources
             .iter_mut()
             .filter(|s| s.status() == Status::Playing)
         {
+            // Virtual source is still mixed while it fades out.
+            if source.is_virtual() && !source.is_fading() {
+                source.advance(buf.len());
+                continue;
+            }
+
+            if let SoundSource::Spatial(spatial) = source {
+                // Obstruction is usually calculated by ray casting which is expensive, so it is
+                // not updated for virtual sources - they're ranked by last known obstruction.
+                if let Some(provider) = self.obstruction_provider.as_mut() {
+                    spatial.set_obstruction(provider(spatial.position(), self.listener.position()));
+                }
+            }
+
             source.render(buf.len());
 
+            if let SoundSource::Spatial(spatial) = source {
+                spatial.apply_obstruction();
+            }
+
             match self.renderer {
                 Renderer::Default => {
                     // Simple rendering path. Much faster (4-5 times) than HRTF path.
@@ -264,6 +468,94 @@ impl Context {
 
         self.render_duration = time::Instant::now() - last_time;
     }
+
+    fn playing_source_count(&self) -> usize {
+        self.sources
+            .iter()
+            .filter(|s| s.status() == Status::Playing)
+            .count()
+    }
+
+    // Fills voices with every playing source, least important voices go first.
+    fn collect_voices(&mut self) {
+        self.voices.clear();
+        for i in 0..self.sources.get_capacity() {
+            if let Some(source) = self.sources.at(i) {
+                if source.status() == Status::Playing {
+                    self.voices.push(Voice {
+                        index: i,
+                        priority: source.priority(),
+                        gain: source.effective_gain(&self.listener, self.distance_model),
+                        age: source.age,
+                    });
+                }
+            }
+        }
+
+        // Least important voices go first. Unstable sort does not allocate.
+        self.voices.sort_unstable_by(|a, b| {
+            a.priority
+                .cmp(&b.priority)
+                .then(a.gain.partial_cmp(&b.gain).unwrap_or(Ordering::Equal))
+                .then(b.age.cmp(&a.age))
+        });
+    }
+
+    // Stops least important sources if there are more playing sources than allowed. Sources
+    // are faded out instead of being cut off, otherwise there would be a click. Sources that
+    // are already fading out to stop are not counted, they will stop soon anyway.
+    fn steal_voices(&mut self) {
+        let playing = self
+            .sources
+            .iter()
+            .filter(|s| s.status() == Status::Playing && !s.is_stopping())
+            .count();
+        if playing <= self.max_voices {
+            return;
+        }
+
+        self.collect_voices();
+
+        let mut excess = playing - self.max_voices;
+        for voice in self.voices.iter() {
+            if excess == 0 {
+                break;
+            }
+            if let Some(source) = self.sources.at_mut(voice.index) {
+                if !source.is_stopping() {
+                    // Play once sources will be removed when fade out is finished.
+                    source.stop_with_fade(VOICE_STEAL_FADE_DURATION);
+                    excess -= 1;
+                }
+            }
+        }
+    }
+
+    // Makes least important sources virtual if there are more playing sources than allowed to
+    // be audible, the rest become audible.
+    fn virtualize_sources(&mut self) {
+        let playing = self.playing_source_count();
+        let virtual_count = playing.saturating_sub(self.max_audible_sources);
+
+        if virtual_count == 0 {
+            for source in self.sources.iter_mut() {
+                if source.status() == Status::Playing {
+                    source.set_virtual(false);
+                }
+            }
+        } else {
+            self.collect_voices();
+
+            for (i, voice) in self.voices.iter().enumerate() {
+                if let Some(source) = self.sources.at_mut(voice.index) {
+                    source.set_virtual(i < virtual_count);
+                }
+            }
+        }
+
+        self.audible_source_count = playing - virtual_count;
+        self.virtual_source_count = virtual_count;
+    }
 }
 
 impl Visit for Context {
@@ -282,3 +574,175 @@ impl Visit for Context {
         visitor.leave_region()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        buffer::{DataSource, SoundBuffer},
+        context::{Context, SAMPLE_RATE},
+        source::{generic::GenericSourceBuilder, SoundSource, Status},
+    };
+    use rg3d_core::pool::Handle;
+    use std::time::Duration;
+
+    fn add_source(
+        context: &mut Context,
+        samples: usize,
+        sample_rate: usize,
+        gain: f32,
+        looping: bool,
+        play_once: bool,
+    ) -> Handle<SoundSource> {
+        let buffer = SoundBuffer::new_generic(DataSource::Raw {
+            sample_rate,
+            channel_count: 1,
+            samples: vec![1.0; samples],
+        })
+        .unwrap();
+        context.add_source(
+            GenericSourceBuilder::new(buffer)
+                .with_gain(gain)
+                .with_looping(looping)
+                .with_play_once(play_once)
+                .with_status(Status::Playing)
+                .build_source()
+                .unwrap(),
+        )
+    }
+
+    fn render(context: &mut Context, amount: usize) {
+        let mut buf = vec![(0.0, 0.0); amount];
+        context.render(&mut buf);
+    }
+
+    #[test]
+    fn test_audible_and_virtual_counts() {
+        let mut context = Context::without_device();
+        context.set_max_audible_sources(2);
+        let sources = [0.1, 0.4, 0.2, 0.3]
+            .iter()
+            .map(|&gain| add_source(&mut context, 100, SAMPLE_RATE as usize, gain, true, false))
+            .collect::<Vec<_>>();
+
+        render(&mut context, 1024);
+        assert_eq!(context.audible_source_count(), 2);
+        assert_eq!(context.virtual_source_count(), 2);
+        let is_virtual = sources
+            .iter()
+            .map(|&s| context.source(s).is_virtual())
+            .collect::<Vec<_>>();
+        assert_eq!(is_virtual, [true, false, true, false]);
+
+        // Source that became virtual is faded out, but its gain is still used for ranking.
+        let quietest = context.source(sources[0]);
+        assert_eq!(quietest.fade_gain(), 0.0);
+        context.source_mut(sources[1]).pause();
+        render(&mut context, 1024);
+        assert_eq!(context.audible_source_count(), 2);
+        assert_eq!(context.virtual_source_count(), 1);
+        assert!(context.source(sources[0]).is_virtual());
+        assert!(!context.source(sources[2]).is_virtual());
+
+        // Source that became audible again is faded in to its previous gain.
+        context.set_max_audible_sources(4);
+        render(&mut context, 1024);
+        assert_eq!(context.audible_source_count(), 3);
+        assert_eq!(context.virtual_source_count(), 0);
+        assert!(!context.source(sources[0]).is_virtual());
+        assert_eq!(context.source(sources[0]).fade_gain(), 1.0);
+    }
+
+    #[test]
+    fn test_virtual_source_resumes_at_position() {
+        let mut context = Context::without_device();
+        context.set_max_audible_sources(1);
+        // One sample per second, so playback time is exact.
+        let virtual_source = add_source(&mut context, 100, 1, 0.5, true, false);
+        let reference = add_source(&mut context, 100, 1, 1.0, true, false);
+
+        for _ in 0..3 {
+            render(&mut context, SAMPLE_RATE as usize);
+        }
+        assert!(context.source(virtual_source).is_virtual());
+
+        context.set_max_audible_sources(2);
+        render(&mut context, SAMPLE_RATE as usize);
+        assert!(!context.source(virtual_source).is_virtual());
+        assert_eq!(
+            context.source(virtual_source).playback_time(),
+            context.source(reference).playback_time()
+        );
+        assert_eq!(
+            context.source(virtual_source).playback_time(),
+            Duration::from_secs(4)
+        );
+    }
+
+    #[test]
+    fn test_steal_voices() {
+        let mut context = Context::without_device();
+        context.set_max_voices(2);
+        let sources = [0.1, 0.4, 0.2, 0.3]
+            .iter()
+            .map(|&gain| add_source(&mut context, 100, SAMPLE_RATE as usize, gain, true, false))
+            .collect::<Vec<_>>();
+        let important = add_source(&mut context, 100, SAMPLE_RATE as usize, 0.01, true, false);
+        context.source_mut(important).set_priority(1);
+
+        // Least important sources are faded out instead of being stopped immediately.
+        render(&mut context, 64);
+        let is_stopping = |context: &Context| {
+            sources
+                .iter()
+                .chain(&[important])
+                .map(|&s| context.source(s).is_stopping())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(is_stopping(&context), [true, false, true, true, false]);
+        for &source in [sources[0], sources[2], sources[3]].iter() {
+            let source = context.source(source);
+            assert_eq!(source.status(), Status::Playing);
+            assert!(source.fade_gain() > 0.0 && source.fade_gain() < 1.0);
+        }
+
+        // Sources that are fading out are not stolen again.
+        render(&mut context, 64);
+        assert_eq!(is_stopping(&context), [true, false, true, true, false]);
+
+        render(&mut context, SAMPLE_RATE as usize / 10);
+        let status = sources
+            .iter()
+            .chain(&[important])
+            .map(|&s| context.source(s).status())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            status,
+            [
+                Status::Stopped,
+                Status::Playing,
+                Status::Stopped,
+                Status::Stopped,
+                Status::Playing
+            ]
+        );
+    }
+
+    #[test]
+    fn test_play_once_source_removed_while_virtual() {
+        let mut context = Context::without_device();
+        context.set_max_audible_sources(1);
+        add_source(&mut context, 10_000, SAMPLE_RATE as usize, 1.0, true, false);
+        let play_once = add_source(&mut context, 2_000, SAMPLE_RATE as usize, 0.5, false, true);
+
+        render(&mut context, 1024);
+        assert!(context.source(play_once).is_virtual());
+
+        // Source reaches its end while virtual and is removed on next render.
+        render(&mut context, 1024);
+        assert_eq!(context.source(play_once).status(), Status::Stopped);
+        render(&mut context, 1024);
+        assert!(!context.sources().is_valid_handle(play_once));
+        assert_eq!(context.audible_source_count(), 1);
+        assert_eq!(context.virtual_source_count(), 0);
+    }
+}
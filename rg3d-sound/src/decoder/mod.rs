@@ -28,18 +28,16 @@ impl Iterator for Decoder {
 }
 
 impl Decoder {
-    pub fn new(source: DataSource) -> Result<Self, DataSource> {
-        // Try Wav
-        let source = match WavDecoder::new(source) {
-            Ok(wav_decoder) => return Ok(Decoder::Wav(wav_decoder)),
-            Err(source) => source,
-        };
+    pub fn new(mut source: DataSource) -> Result<Self, SoundError> {
+        // Try Wav, once the source is known to be wav, every header error is reported as is.
+        if WavDecoder::is_wav(&mut source) {
+            return Ok(Decoder::Wav(WavDecoder::new(source)?));
+        }
         // Try Vorbis/Ogg
-        let source = match OggDecoder::new(source) {
-            Ok(ogg_decoder) => return Ok(Decoder::Ogg(ogg_decoder)),
-            Err(source) => source,
-        };
-        Err(source)
+        match OggDecoder::new(source) {
+            Ok(ogg_decoder) => Ok(Decoder::Ogg(ogg_decoder)),
+            Err(_) => Err(SoundError::UnsupportedFormat),
+        }
     }
 
     pub fn rewind(&mut self) -> Result<(), SoundError> {
@@ -58,6 +56,24 @@ impl Decoder {
         }
     }
 
+    /// Moves decoder to exact frame (sample of each channel) of the data.
+    pub fn frame_seek(&mut self, frame: usize) -> Result<(), SoundError> {
+        match self {
+            Decoder::Wav(wav) => wav.frame_seek(frame),
+            Decoder::Ogg(ogg) => ogg.frame_seek(frame),
+            Decoder::Null => Ok(()),
+        }
+    }
+
+    /// Returns total amount of frames in the data. Can be `None` if decoder does not know
+    /// total length without decoding whole data.
+    pub fn frame_count(&self) -> Option<usize> {
+        match self {
+            Decoder::Wav(wav) => Some(wav.frame_count()),
+            Decoder::Ogg(_) | Decoder::Null => None,
+        }
+    }
+
     pub fn get_channel_count(&self) -> usize {
         match self {
             Decoder::Wav(wav) => wav.channel_count,
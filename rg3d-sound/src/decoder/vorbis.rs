@@ -17,6 +17,14 @@ pub struct OggDecoder {
     pub sample_rate: usize,
 }
 
+enum PageSeek {
+    Done,
+    // Seek has landed on the last page, contains estimated first frame of the page, it can be
+    // off by size of the priming packet.
+    Retry(usize),
+    Failed,
+}
+
 impl Debug for OggDecoder {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "OggDecoder")
@@ -96,18 +104,96 @@ impl OggDecoder {
     }
 
     pub fn time_seek(&mut self, location: Duration) {
-        // seek_absgp_pg seems to be bugged - it fails at seeking when all packets were read already.
-        // For more info see - https://github.com/RustAudio/lewton/issues/73
-        let sample_index =
-            self.channel_count as f64 * location.as_secs_f64() * self.sample_rate as f64;
+        let frame = location.as_secs_f64() * self.sample_rate as f64;
+        if self.frame_seek(frame as usize).is_err() {
+            println!("Failed to seek vorbis/ogg to {:?}", location)
+        }
+    }
+
+    pub fn frame_seek(&mut self, frame: usize) -> Result<(), SoundError> {
+        if frame == 0 {
+            return self.rewind();
+        }
+
+        // First packet after a seek is used only to prime the decoder, so target is moved back
+        // by the size of a long block to make sure that requested frame is not in the skipped
+        // packet.
+        let blocksize = 1 << self.reader.as_ref().unwrap().ident_hdr.blocksize_1;
+        let mut target = frame.saturating_sub(blocksize);
+        // Second attempt is needed only if first one has landed on the last page.
+        for _ in 0..2 {
+            match self.seek_page(target, frame)? {
+                PageSeek::Done => return Ok(()),
+                PageSeek::Retry(page_start) => target = page_start.saturating_sub(blocksize),
+                PageSeek::Failed => break,
+            }
+        }
+
+        // Slow path - decode everything from the beginning.
+        self.rewind()?;
+        for _ in 0..frame * self.channel_count {
+            if self.next().is_none() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn seek_page(&mut self, target: usize, frame: usize) -> Result<PageSeek, SoundError> {
+        // Granule position of vorbis stream is index of frame, seek_absgp_pg moves reader to a
+        // page which starts at or before the granule. seek_absgp_pg fails if every packet was
+        // read already (see rewind), in this case seek is retried on fresh reader.
         if self
             .reader
             .as_mut()
             .unwrap()
-            .seek_absgp_pg(sample_index as u64)
+            .seek_absgp_pg(target as u64)
             .is_err()
         {
-            println!("Failed to seek vorbis/ogg, see https://github.com/RustAudio/lewton/issues/73")
+            self.rewind()?;
+            self.reader.as_mut().unwrap().seek_absgp_pg(target as u64)?;
+        }
+
+        // Position of decoded samples is unknown until a packet that ends a page is read,
+        // granule of the page is the index of frame right after decoded samples.
+        let reader = self.reader.as_mut().unwrap();
+        let channel_count = self.channel_count;
+        let mut samples = Vec::new();
+        loop {
+            // Last packet of the stream is truncated to its granule only if position was known
+            // before the packet was read.
+            let position_known = reader.get_last_absgp().is_some();
+            match reader.read_dec_packet_generic::<InterleavedSamples<f32>>() {
+                Ok(Some(packet)) => samples.extend_from_slice(&packet.samples),
+                Ok(None) => {
+                    // Seek beyond end of stream.
+                    self.samples = Vec::new().into_iter();
+                    return Ok(PageSeek::Done);
+                }
+                // Seek to first frames can land on a page with headers.
+                Err(_) => return Ok(PageSeek::Failed),
+            }
+            if let Some(end) = reader.get_last_absgp() {
+                let end = end as usize;
+                let start = end.saturating_sub(samples.len() / channel_count);
+                if frame < start {
+                    return Ok(PageSeek::Failed);
+                }
+                if frame < end {
+                    if !position_known {
+                        // Make sure that this was not the last page, samples of the last
+                        // packet would be at wrong position otherwise.
+                        match reader.read_dec_packet_generic::<InterleavedSamples<f32>>() {
+                            Ok(Some(packet)) => samples.extend_from_slice(&packet.samples),
+                            _ => return Ok(PageSeek::Retry(start)),
+                        }
+                    }
+                    samples.drain(..(frame - start) * channel_count);
+                    self.samples = samples.into_iter();
+                    return Ok(PageSeek::Done);
+                }
+                samples.clear();
+            }
         }
     }
 
@@ -115,3 +201,40 @@ impl OggDecoder {
         None
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{buffer::DataSource, decoder::vorbis::OggDecoder};
+
+    fn make_decoder() -> OggDecoder {
+        OggDecoder::new(DataSource::from_file("examples/data/waterfall.ogg").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_frame_seek() {
+        let mut decoder = make_decoder();
+        let channel_count = decoder.channel_count;
+        let samples = decoder.by_ref().collect::<Vec<_>>();
+        let frame_count = samples.len() / channel_count;
+
+        // Seek backwards after whole stream was read, to the middle of a page, to the very
+        // first frames and to the last frame.
+        for &frame in &[
+            frame_count / 2,
+            1,
+            1000,
+            123_457,
+            frame_count / 3,
+            frame_count - 1,
+        ] {
+            decoder.frame_seek(frame).unwrap();
+            let expected = &samples[frame * channel_count..];
+            let actual = decoder.by_ref().take(4096).collect::<Vec<_>>();
+            assert_eq!(actual, &expected[..actual.len().min(expected.len())]);
+            assert_eq!(actual.len(), expected.len().min(4096));
+        }
+
+        decoder.frame_seek(frame_count + 10).unwrap();
+        assert_eq!(decoder.next(), None);
+    }
+}
@@ -1,118 +1,481 @@
+//! Wav decoder.
+//!
+//! Supports 8/16/24/32-bit integer PCM, 32-bit float and IMA-ADPCM (4 bits per sample) data,
+//! every format is normalized to -1..1 range. Only mono and stereo files are supported, files
+//! with more channels are rejected with an error.
+//!
+//! Header is validated while it is read, so malformed file produces
+//! `SoundError::InvalidWavChunk` with id of the offending chunk instead of playing noise.
+
 use crate::rg3d_core::byteorder::{LittleEndian, ReadBytesExt};
 use crate::{buffer::DataSource, error::SoundError};
 use std::io::{Read, Seek, SeekFrom};
 use std::time::Duration;
 
-struct WavHeader {
-    chunk_id: [u8; 4],
-    chunk_size: u32,
-    format: [u8; 4],
-    fmt_chunk_id: [u8; 4],
-    fmt_chunk_size: u32,
-    audio_format: u16,
+/// Format tag of uncompressed integer samples.
+const WAVE_FORMAT_PCM: u16 = 1;
+/// Format tag of 32-bit floating point samples.
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+/// Format tag of IMA (DVI) ADPCM compressed samples.
+const WAVE_FORMAT_IMA_ADPCM: u16 = 0x11;
+/// Format tag of extensible format, actual format is stored in sub-format GUID.
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+const IMA_INDEX_TABLE: [i32; 16] = [-1, -1, -1, -1, 2, 4, 6, 8, -1, -1, -1, -1, 2, 4, 6, 8];
+
+const IMA_STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+    494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+    2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+    10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+/// Layout of a single sample in data chunk.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum SampleFormat {
+    /// Unsigned 8-bit integer, 128 is silence.
+    U8,
+    /// Signed 16-bit integer.
+    I16,
+    /// Signed 24-bit integer packed into 3 bytes.
+    I24,
+    /// Signed 32-bit integer.
+    I32,
+    /// 32-bit float in -1..1 range.
+    F32,
+    /// IMA-ADPCM, data is split into blocks of `block_align` bytes, each block starts with a
+    /// header per channel and can be decoded independently.
+    ImaAdpcm { samples_per_block: usize },
+}
+
+impl SampleFormat {
+    fn byte_per_sample(self) -> usize {
+        match self {
+            SampleFormat::U8 => 1,
+            SampleFormat::I16 => 2,
+            SampleFormat::I24 => 3,
+            SampleFormat::I32 | SampleFormat::F32 => 4,
+            // Samples are not addressable, data is read by blocks.
+            SampleFormat::ImaAdpcm { .. } => 0,
+        }
+    }
+}
+
+fn invalid_chunk(chunk: &'static str, reason: String) -> SoundError {
+    SoundError::InvalidWavChunk { chunk, reason }
+}
+
+struct WavFormat {
     num_channels: u16,
     sample_rate: u32,
-    byte_rate: u32,
+    sample_format: SampleFormat,
     block_align: u16,
-    bits_per_sample: u16,
-    data_chunk_id: [u8; 4],
+}
+
+impl WavFormat {
+    fn read(source: &mut DataSource, chunk_size: u32) -> Result<Self, SoundError> {
+        if chunk_size < 16 {
+            return Err(invalid_chunk(
+                "fmt ",
+                format!("chunk is {} bytes long, at least 16 expected", chunk_size),
+            ));
+        }
+        let mut audio_format = source.read_u16::<LittleEndian>()?;
+        let num_channels = source.read_u16::<LittleEndian>()?;
+        let sample_rate = source.read_u32::<LittleEndian>()?;
+        let _byte_rate = source.read_u32::<LittleEndian>()?;
+        let block_align = source.read_u16::<LittleEndian>()?;
+        let bits_per_sample = source.read_u16::<LittleEndian>()?;
+        let mut read = 16;
+        let mut samples_per_block = None;
+        if chunk_size >= 18 {
+            let extension_size = source.read_u16::<LittleEndian>()?;
+            read += 2;
+            if audio_format == WAVE_FORMAT_EXTENSIBLE && extension_size >= 22 && chunk_size >= 40 {
+                let _valid_bits_per_sample = source.read_u16::<LittleEndian>()?;
+                let _channel_mask = source.read_u32::<LittleEndian>()?;
+                // First two bytes of sub-format GUID is the actual format tag, the rest
+                // is the same for every standard format.
+                audio_format = source.read_u16::<LittleEndian>()?;
+                read += 8;
+            } else if audio_format == WAVE_FORMAT_IMA_ADPCM
+                && extension_size >= 2
+                && chunk_size >= 20
+            {
+                samples_per_block = Some(source.read_u16::<LittleEndian>()? as usize);
+                read += 2;
+            }
+        }
+        // Chunks are aligned to two bytes.
+        let padded_size = i64::from(chunk_size) + i64::from(chunk_size & 1);
+        source.seek(SeekFrom::Current(padded_size - read))?;
+
+        if num_channels == 0 || num_channels > 2 {
+            return Err(invalid_chunk(
+                "fmt ",
+                format!(
+                    "{} channels are not supported, only mono and stereo files can be loaded",
+                    num_channels
+                ),
+            ));
+        }
+        if sample_rate == 0 {
+            return Err(invalid_chunk("fmt ", "sample rate is zero".to_owned()));
+        }
+
+        let sample_format = match (audio_format, bits_per_sample) {
+            (WAVE_FORMAT_PCM, 8) => SampleFormat::U8,
+            (WAVE_FORMAT_PCM, 16) => SampleFormat::I16,
+            (WAVE_FORMAT_PCM, 24) => SampleFormat::I24,
+            (WAVE_FORMAT_PCM, 32) => SampleFormat::I32,
+            (WAVE_FORMAT_IEEE_FLOAT, 32) => SampleFormat::F32,
+            (WAVE_FORMAT_IMA_ADPCM, 4) => {
+                // Each channel has 4 bytes header with first sample, the rest of the block is
+                // 4-bit samples interleaved by groups of 4 bytes (8 samples) per channel.
+                let header_size = 4 * num_channels as usize;
+                let data_size = (block_align as usize).saturating_sub(header_size);
+                if data_size == 0 || data_size % header_size != 0 {
+                    return Err(invalid_chunk(
+                        "fmt ",
+                        format!(
+                            "block align {} is invalid for IMA-ADPCM with {} channels",
+                            block_align, num_channels
+                        ),
+                    ));
+                }
+                let expected = data_size * 2 / num_channels as usize + 1;
+                match samples_per_block {
+                    Some(samples_per_block) if samples_per_block != expected => {
+                        return Err(invalid_chunk(
+                            "fmt ",
+                            format!(
+                                "{} samples per block does not match block align {}, {} expected",
+                                samples_per_block, block_align, expected
+                            ),
+                        ))
+                    }
+                    _ => SampleFormat::ImaAdpcm {
+                        samples_per_block: expected,
+                    },
+                }
+            }
+            _ => {
+                return Err(invalid_chunk(
+                    "fmt ",
+                    format!(
+                        "unsupported format tag {:#06x} with {} bits per sample",
+                        audio_format, bits_per_sample
+                    ),
+                ))
+            }
+        };
+
+        if let SampleFormat::ImaAdpcm { .. } = sample_format {
+        } else if block_align as usize != sample_format.byte_per_sample() * num_channels as usize {
+            return Err(invalid_chunk(
+                "fmt ",
+                format!(
+                    "block align {} does not match {} channels of {} bits",
+                    block_align, num_channels, bits_per_sample
+                ),
+            ));
+        }
+
+        Ok(Self {
+            num_channels,
+            sample_rate,
+            sample_format,
+            block_align,
+        })
+    }
+}
+
+struct WavHeader {
+    format: WavFormat,
+    data_offset: u64,
     data_chunk_size: u32,
+    frame_count: usize,
 }
 
-/// Wav decoder for uncompressed PCM data.
-/// TODO: Add support for RLE-compressed samples.
+/// Wav decoder, see module docs.
 #[derive(Debug)]
 pub(in crate) struct WavDecoder {
     pub channel_count: usize,
     pub sample_rate: usize,
-    byte_per_sample: usize,
+    sample_format: SampleFormat,
+    block_align: usize,
+    data_offset: u64,
+    data_chunk_size: usize,
     samples_left: usize,
     total_samples: usize,
+    // Decoded samples of current ADPCM block and index of next sample in it.
+    block: Vec<f32>,
+    block_position: usize,
+    // Index of next ADPCM block to decode.
+    next_block: usize,
+    raw_block: Vec<u8>,
     source: DataSource,
 }
 
 impl WavDecoder {
-    const HEADER_SIZE: u64 = 44;
+    /// Checks if the source starts with RIFF/WAVE header, position of the source is not changed.
+    pub fn is_wav(source: &mut DataSource) -> bool {
+        let pos = match source.seek(SeekFrom::Current(0)) {
+            Ok(pos) => pos,
+            Err(_) => return false,
+        };
+        let mut header = [0; 12];
+        let is_wav = source.read_exact(&mut header).is_ok()
+            && &header[0..4] == b"RIFF"
+            && &header[8..12] == b"WAVE";
+        source.seek(SeekFrom::Start(pos)).is_ok() && is_wav
+    }
 
     fn read_header(source: &mut DataSource) -> Result<WavHeader, SoundError> {
-        let mut header = WavHeader {
-            chunk_id: [0, 0, 0, 0],
-            chunk_size: 0,
-            format: [0, 0, 0, 0],
-            fmt_chunk_id: [0, 0, 0, 0],
-            fmt_chunk_size: 0,
-            audio_format: 0,
-            num_channels: 0,
-            sample_rate: 0,
-            byte_rate: 0,
-            block_align: 0,
-            bits_per_sample: 0,
-            data_chunk_id: [0, 0, 0, 0],
-            data_chunk_size: 0,
-        };
-        source.read_exact(&mut header.chunk_id)?;
-        if header.chunk_id.as_ref() != b"RIFF" {
-            return Err(SoundError::InvalidHeader);
-        }
-        header.chunk_size = source.read_u32::<LittleEndian>()?;
-        source.read_exact(&mut header.format)?;
-        if header.format.as_ref() != b"WAVE" {
-            return Err(SoundError::InvalidHeader);
-        }
-        source.read_exact(&mut header.fmt_chunk_id)?;
-        if header.fmt_chunk_id.as_ref() != b"fmt " {
-            return Err(SoundError::InvalidHeader);
-        }
-        header.fmt_chunk_size = source.read_u32::<LittleEndian>()?;
-        header.audio_format = source.read_u16::<LittleEndian>()?;
-        if header.audio_format != 1 {
-            return Err(SoundError::InvalidHeader);
-        }
-        header.num_channels = source.read_u16::<LittleEndian>()?;
-        header.sample_rate = source.read_u32::<LittleEndian>()?;
-        header.byte_rate = source.read_u32::<LittleEndian>()?;
-        header.block_align = source.read_u16::<LittleEndian>()?;
-        header.bits_per_sample = source.read_u16::<LittleEndian>()?;
-        source.read_exact(&mut header.data_chunk_id)?;
-        header.data_chunk_size = source.read_u32::<LittleEndian>()?;
-        Ok(header)
-    }
-
-    pub fn new(mut source: DataSource) -> Result<Self, DataSource> {
-        let pos = source.seek(SeekFrom::Current(0)).unwrap();
-        let header = match Self::read_header(&mut source) {
-            Ok(h) => h,
-            Err(_) => {
-                source.seek(SeekFrom::Start(pos)).unwrap();
-                return Err(source);
+        let mut id = [0; 4];
+        source.read_exact(&mut id)?;
+        if id.as_ref() != b"RIFF" {
+            return Err(invalid_chunk("RIFF", "file has no RIFF header".to_owned()));
+        }
+        let _riff_chunk_size = source.read_u32::<LittleEndian>()?;
+        source.read_exact(&mut id)?;
+        if id.as_ref() != b"WAVE" {
+            return Err(invalid_chunk("RIFF", "file is not a WAVE file".to_owned()));
+        }
+
+        let start = source.seek(SeekFrom::Current(0))?;
+        let file_size = source.seek(SeekFrom::End(0))?;
+        source.seek(SeekFrom::Start(start))?;
+
+        // Walk through chunks until data chunk is found, unknown chunks (LIST, cue, etc.)
+        // are skipped. Format and fact chunks must be placed before data chunk.
+        let mut format = None;
+        let mut fact_frame_count = None;
+        loop {
+            if source.read_exact(&mut id).is_err() {
+                return Err(invalid_chunk(
+                    if format.is_none() { "fmt " } else { "data" },
+                    "chunk is missing".to_owned(),
+                ));
             }
-        };
-        let byte_per_sample = (header.bits_per_sample / 8) as usize;
-        let total_samples = (header.data_chunk_size / byte_per_sample as u32) as usize;
-        Ok(Self {
-            channel_count: header.num_channels as usize,
-            sample_rate: header.sample_rate as usize,
-            byte_per_sample,
+            let chunk_size = source.read_u32::<LittleEndian>()?;
+            // Chunks are aligned to two bytes.
+            let padded_size = i64::from(chunk_size) + i64::from(chunk_size & 1);
+            match &id {
+                b"fmt " => {
+                    format = Some(WavFormat::read(source, chunk_size)?);
+                }
+                b"fact" => {
+                    if chunk_size < 4 {
+                        return Err(invalid_chunk(
+                            "fact",
+                            format!("chunk is {} bytes long, at least 4 expected", chunk_size),
+                        ));
+                    }
+                    fact_frame_count = Some(source.read_u32::<LittleEndian>()? as usize);
+                    source.seek(SeekFrom::Current(padded_size - 4))?;
+                }
+                b"data" => {
+                    let format = format.ok_or_else(|| {
+                        invalid_chunk("data", "chunk is placed before fmt chunk".to_owned())
+                    })?;
+                    let data_offset = source.seek(SeekFrom::Current(0))?;
+                    if data_offset + u64::from(chunk_size) > file_size {
+                        return Err(invalid_chunk(
+                            "data",
+                            format!(
+                                "chunk is {} bytes long, but only {} bytes left in file",
+                                chunk_size,
+                                file_size - data_offset
+                            ),
+                        ));
+                    }
+                    let block_align = format.block_align as usize;
+                    let frame_count = match format.sample_format {
+                        SampleFormat::ImaAdpcm { samples_per_block } => {
+                            let header_size = 4 * format.num_channels as usize;
+                            let full_blocks = chunk_size as usize / block_align;
+                            let tail = chunk_size as usize % block_align;
+                            // Last block can be shorter than others, but it still must
+                            // contain whole groups of 8 samples for every channel.
+                            let tail_frames =
+                                if tail >= header_size && (tail - header_size) % header_size == 0 {
+                                    (tail - header_size) * 2 / format.num_channels as usize + 1
+                                } else if tail > 0 {
+                                    return Err(invalid_chunk(
+                                        "data",
+                                        format!("last block is truncated to {} bytes", tail),
+                                    ));
+                                } else {
+                                    0
+                                };
+                            let capacity = full_blocks * samples_per_block + tail_frames;
+                            match fact_frame_count {
+                                Some(count) if count > capacity => {
+                                    return Err(invalid_chunk(
+                                        "fact",
+                                        format!(
+                                            "{} frames declared, but data chunk contains only {}",
+                                            count, capacity
+                                        ),
+                                    ))
+                                }
+                                Some(count) => count,
+                                None => capacity,
+                            }
+                        }
+                        _ => {
+                            if chunk_size as usize % block_align != 0 {
+                                return Err(invalid_chunk(
+                                    "data",
+                                    format!(
+                                        "chunk size {} is not multiple of block align {}",
+                                        chunk_size, block_align
+                                    ),
+                                ));
+                            }
+                            chunk_size as usize / block_align
+                        }
+                    };
+                    return Ok(WavHeader {
+                        format,
+                        data_offset,
+                        data_chunk_size: chunk_size,
+                        frame_count,
+                    });
+                }
+                _ => {
+                    source.seek(SeekFrom::Current(padded_size))?;
+                }
+            }
+        }
+    }
+
+    pub fn new(mut source: DataSource) -> Result<Self, SoundError> {
+        let header = Self::read_header(&mut source)?;
+        let channel_count = header.format.num_channels as usize;
+        let total_samples = header.frame_count * channel_count;
+        let mut decoder = Self {
+            channel_count,
+            sample_rate: header.format.sample_rate as usize,
+            sample_format: header.format.sample_format,
+            block_align: header.format.block_align as usize,
+            data_offset: header.data_offset,
+            data_chunk_size: header.data_chunk_size as usize,
             total_samples,
             samples_left: total_samples,
+            block: Vec::new(),
+            block_position: 0,
+            next_block: 0,
+            raw_block: Vec::new(),
             source,
-        })
+        };
+        decoder.rewind()?;
+        Ok(decoder)
     }
 
     pub fn rewind(&mut self) -> Result<(), SoundError> {
-        self.source.seek(SeekFrom::Start(Self::HEADER_SIZE))?;
-        self.samples_left = self.total_samples;
-        Ok(())
+        self.frame_seek(0)
     }
 
     pub fn time_seek(&mut self, location: Duration) {
-        let byte_index = self.channel_count as f64
-            * location.as_secs_f64()
-            * self.sample_rate as f64
-            * self.byte_per_sample as f64;
-        let _ = self
-            .source
-            .seek(SeekFrom::Start(Self::HEADER_SIZE + byte_index as u64));
+        let frame = (location.as_secs_f64() * self.sample_rate as f64) as usize;
+        let _ = self.frame_seek(frame);
+    }
+
+    pub fn frame_seek(&mut self, frame: usize) -> Result<(), SoundError> {
+        let frame = frame.min(self.frame_count());
+        self.samples_left = self.total_samples - frame * self.channel_count;
+        match self.sample_format {
+            SampleFormat::ImaAdpcm { samples_per_block } => {
+                // Decode block with the frame and skip preceding frames.
+                self.next_block = frame / samples_per_block;
+                self.source.seek(SeekFrom::Start(
+                    self.data_offset + (self.next_block * self.block_align) as u64,
+                ))?;
+                self.block.clear();
+                self.block_position = 0;
+                if self.samples_left > 0 {
+                    self.decode_block()?;
+                    self.block_position = (frame % samples_per_block) * self.channel_count;
+                }
+            }
+            _ => {
+                self.source.seek(SeekFrom::Start(
+                    self.data_offset + (frame * self.block_align) as u64,
+                ))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Reads and decodes next IMA-ADPCM block, see `SampleFormat::ImaAdpcm`.
+    fn decode_block(&mut self) -> Result<(), SoundError> {
+        let channel_count = self.channel_count;
+        let offset = self.next_block * self.block_align;
+        let size = self
+            .block_align
+            .min(self.data_chunk_size.saturating_sub(offset));
+        self.raw_block.resize(size, 0);
+        self.source.read_exact(&mut self.raw_block)?;
+        self.next_block += 1;
+
+        self.block.clear();
+        self.block_position = 0;
+
+        let mut predictors = [0i32; 2];
+        let mut step_indices = [0i32; 2];
+        for channel in 0..channel_count {
+            let header = &self.raw_block[channel * 4..channel * 4 + 4];
+            predictors[channel] = i32::from(i16::from_le_bytes([header[0], header[1]]));
+            step_indices[channel] = i32::from(header[2]).min(88);
+            self.block.push(predictors[channel] as f32 / 32768.0);
+        }
+
+        let mut decoded = [[0.0f32; 8]; 2];
+        for group in self.raw_block[channel_count * 4..].chunks(4 * channel_count) {
+            let mut count = 0;
+            for (channel, bytes) in group.chunks(4).enumerate() {
+                count = bytes.len() * 2;
+                for (i, byte) in bytes.iter().enumerate() {
+                    for (j, &nibble) in [byte & 0x0F, byte >> 4].iter().enumerate() {
+                        let predictor = &mut predictors[channel];
+                        let step_index = &mut step_indices[channel];
+                        let step = IMA_STEP_TABLE[*step_index as usize];
+                        let mut diff = step >> 3;
+                        if nibble & 1 != 0 {
+                            diff += step >> 2;
+                        }
+                        if nibble & 2 != 0 {
+                            diff += step >> 1;
+                        }
+                        if nibble & 4 != 0 {
+                            diff += step;
+                        }
+                        if nibble & 8 != 0 {
+                            diff = -diff;
+                        }
+                        *predictor = (*predictor + diff).max(-32768).min(32767);
+                        *step_index = (*step_index + IMA_INDEX_TABLE[nibble as usize])
+                            .max(0)
+                            .min(88);
+                        decoded[channel][i * 2 + j] = *predictor as f32 / 32768.0;
+                    }
+                }
+            }
+            for i in 0..count {
+                for channel_samples in decoded.iter().take(channel_count) {
+                    self.block.push(channel_samples[i]);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.total_samples / self.channel_count
     }
 
     pub fn duration(&self) -> Option<Duration> {
@@ -129,24 +492,318 @@ impl Iterator for WavDecoder {
     fn next(&mut self) -> Option<Self::Item> {
         if self.samples_left > 0 {
             self.samples_left -= 1;
-            if self.byte_per_sample == 1 {
-                Some(f32::from(self.source.read_i8().ok()?) / 127.0)
-            } else if self.byte_per_sample == 2 {
-                Some(f32::from(self.source.read_i16::<LittleEndian>().ok()?) / 32767.0)
-            } else if self.byte_per_sample == 3 {
-                let a = self.source.read_u8().ok()? as i32;
-                let b = self.source.read_u8().ok()? as i32;
-                let c = self.source.read_u8().ok()? as i32;
-                let ival = a | (b << 8) | (c << 16);
-                let sign = if ival & 0x0080_0000 != 0 { -1.0 } else { 1.0 };
-                Some(sign * ival as f32 / 8_388_608.0)
-            } else if self.byte_per_sample == 4 {
-                Some(self.source.read_f32::<LittleEndian>().ok()?)
-            } else {
-                None
+            match self.sample_format {
+                SampleFormat::U8 => Some((f32::from(self.source.read_u8().ok()?) - 128.0) / 128.0),
+                SampleFormat::I16 => {
+                    Some(f32::from(self.source.read_i16::<LittleEndian>().ok()?) / 32768.0)
+                }
+                SampleFormat::I24 => {
+                    Some(self.source.read_i24::<LittleEndian>().ok()? as f32 / 8_388_608.0)
+                }
+                SampleFormat::I32 => {
+                    Some(self.source.read_i32::<LittleEndian>().ok()? as f32 / 2_147_483_648.0)
+                }
+                SampleFormat::F32 => Some(self.source.read_f32::<LittleEndian>().ok()?),
+                SampleFormat::ImaAdpcm { .. } => {
+                    if self.block_position >= self.block.len() {
+                        self.decode_block().ok()?;
+                    }
+                    let sample = *self.block.get(self.block_position)?;
+                    self.block_position += 1;
+                    Some(sample)
+                }
             }
         } else {
             None
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        buffer::DataSource,
+        decoder::wav::{
+            WavDecoder, WAVE_FORMAT_EXTENSIBLE, WAVE_FORMAT_IEEE_FLOAT, WAVE_FORMAT_IMA_ADPCM,
+            WAVE_FORMAT_PCM,
+        },
+        error::SoundError,
+    };
+
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+        if data.len() % 2 != 0 {
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    fn fmt(format: u16, channels: u16, block_align: u16, bits: u16, extension: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&format.to_le_bytes());
+        data.extend_from_slice(&channels.to_le_bytes());
+        data.extend_from_slice(&44100u32.to_le_bytes());
+        data.extend_from_slice(&(44100 * u32::from(block_align)).to_le_bytes());
+        data.extend_from_slice(&block_align.to_le_bytes());
+        data.extend_from_slice(&bits.to_le_bytes());
+        if !extension.is_empty() {
+            data.extend_from_slice(&(extension.len() as u16).to_le_bytes());
+            data.extend_from_slice(extension);
+        }
+        chunk(b"fmt ", &data)
+    }
+
+    fn riff(chunks: &[Vec<u8>]) -> DataSource {
+        let body = chunks.concat();
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&(body.len() as u32 + 4).to_le_bytes());
+        bytes.extend_from_slice(b"WAVE");
+        bytes.extend_from_slice(&body);
+        DataSource::from_memory(bytes)
+    }
+
+    fn adpcm_fmt(channels: u16, block_align: u16) -> Vec<u8> {
+        let samples_per_block = (block_align - 4 * channels) * 2 / channels + 1;
+        fmt(
+            WAVE_FORMAT_IMA_ADPCM,
+            channels,
+            block_align,
+            4,
+            &samples_per_block.to_le_bytes(),
+        )
+    }
+
+    // Header of ADPCM block for one channel: first sample, step index and reserved byte.
+    fn adpcm_header(predictor: i16) -> Vec<u8> {
+        let mut header = predictor.to_le_bytes().to_vec();
+        header.extend_from_slice(&[0, 0]);
+        header
+    }
+
+    fn assert_samples(decoder: WavDecoder, expected: &[i16]) {
+        let samples = decoder.collect::<Vec<_>>();
+        let expected = expected
+            .iter()
+            .map(|&s| f32::from(s) / 32768.0)
+            .collect::<Vec<_>>();
+        assert_eq!(samples, expected);
+    }
+
+    fn invalid_chunk(result: Result<WavDecoder, SoundError>) -> &'static str {
+        match result {
+            Err(SoundError::InvalidWavChunk { chunk, .. }) => chunk,
+            other => panic!("expected invalid chunk error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_adpcm_mono() {
+        // Nibble 4 adds full step: 0 -> 7 (step 7) -> 17 (step 9) -> 29 (step 11), nibble 0
+        // adds 1/8 of the step and decreases step index.
+        let mut block = adpcm_header(0);
+        block.extend_from_slice(&[0x44, 0x04, 0x00, 0x00]);
+        let mut short_block = adpcm_header(-100);
+        short_block.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+
+        let decoder = WavDecoder::new(riff(&[
+            adpcm_fmt(1, 8),
+            chunk(b"data", &[block, short_block].concat()),
+        ]))
+        .unwrap();
+        assert_eq!(decoder.channel_count, 1);
+        // 9 frames of full block and 9 frames of last block.
+        assert_eq!(decoder.frame_count(), 18);
+        assert_samples(
+            decoder,
+            &[
+                0, 7, 17, 29, 30, 31, 32, 33, 34, // First block.
+                -100, -100, -100, -100, -100, -100, -100, -100, -100, // Last block.
+            ],
+        );
+    }
+
+    #[test]
+    fn test_adpcm_stereo_short_block() {
+        let mut block = [adpcm_header(0), adpcm_header(100)].concat();
+        block.extend_from_slice(&[0x44, 0x04, 0x00, 0x00]);
+        block.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+        let mut data = block.clone();
+        // Last block has only headers, so it contains single frame.
+        data.extend_from_slice(&[adpcm_header(5), adpcm_header(-5)].concat());
+
+        let mut decoder =
+            WavDecoder::new(riff(&[adpcm_fmt(2, 16), chunk(b"data", &data)])).unwrap();
+        assert_eq!(decoder.channel_count, 2);
+        assert_eq!(decoder.frame_count(), 10);
+
+        decoder.frame_seek(8).unwrap();
+        assert_samples(decoder, &[34, 100, 5, -5]);
+
+        let decoder = WavDecoder::new(riff(&[adpcm_fmt(2, 16), chunk(b"data", &block)])).unwrap();
+        assert_samples(
+            decoder,
+            &[
+                0, 100, 7, 100, 17, 100, 29, 100, 30, 100, 31, 100, 32, 100, 33, 100, 34, 100,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_adpcm_truncated_block() {
+        let mut data = [adpcm_header(0), adpcm_header(0)].concat();
+        data.extend_from_slice(&[0; 8]);
+        // Group of samples for left channel only.
+        data.extend_from_slice(&[adpcm_header(0), adpcm_header(0), vec![0; 4]].concat());
+        let result = WavDecoder::new(riff(&[adpcm_fmt(2, 16), chunk(b"data", &data)]));
+        assert_eq!(invalid_chunk(result), "data");
+    }
+
+    #[test]
+    fn test_fact_exceeds_data() {
+        let mut block = adpcm_header(0);
+        block.extend_from_slice(&[0; 4]);
+        let fact = chunk(b"fact", &10u32.to_le_bytes());
+        let result = WavDecoder::new(riff(&[adpcm_fmt(1, 8), fact, chunk(b"data", &block)]));
+        assert_eq!(invalid_chunk(result), "fact");
+
+        // Fact chunk can make last frames of the block unused.
+        let fact = chunk(b"fact", &5u32.to_le_bytes());
+        let decoder =
+            WavDecoder::new(riff(&[adpcm_fmt(1, 8), fact, chunk(b"data", &block)])).unwrap();
+        assert_eq!(decoder.frame_count(), 5);
+    }
+
+    #[test]
+    fn test_data_before_fmt() {
+        let result = WavDecoder::new(riff(&[
+            chunk(b"data", &[0; 4]),
+            fmt(WAVE_FORMAT_PCM, 1, 2, 16, &[]),
+        ]));
+        assert_eq!(invalid_chunk(result), "data");
+    }
+
+    #[test]
+    fn test_more_than_two_channels() {
+        let result = WavDecoder::new(riff(&[
+            fmt(WAVE_FORMAT_PCM, 6, 12, 16, &[]),
+            chunk(b"data", &[0; 24]),
+        ]));
+        assert_eq!(invalid_chunk(result), "fmt ");
+    }
+
+    #[test]
+    fn test_pcm_u8() {
+        // Odd data chunk is padded, 128 is silence for unsigned samples.
+        let decoder = WavDecoder::new(riff(&[
+            fmt(WAVE_FORMAT_PCM, 1, 1, 8, &[]),
+            chunk(b"data", &[0, 128, 255]),
+        ]))
+        .unwrap();
+        assert_eq!(decoder.frame_count(), 3);
+        assert_eq!(decoder.collect::<Vec<_>>(), vec![-1.0, 0.0, 127.0 / 128.0]);
+    }
+
+    #[test]
+    fn test_pcm_i24() {
+        let data = [0xFF, 0xFF, 0x7F, 0x00, 0x00, 0x80, 0x00, 0x40, 0x00];
+        let decoder = WavDecoder::new(riff(&[
+            fmt(WAVE_FORMAT_PCM, 1, 3, 24, &[]),
+            chunk(b"data", &data),
+        ]))
+        .unwrap();
+        assert_eq!(decoder.frame_count(), 3);
+        assert_eq!(
+            decoder.collect::<Vec<_>>(),
+            vec![8_388_607.0 / 8_388_608.0, -1.0, 16384.0 / 8_388_608.0]
+        );
+    }
+
+    #[test]
+    fn test_pcm_i32_stereo() {
+        let data = [i32::MIN, 1 << 30, 0, -(1 << 29)]
+            .iter()
+            .flat_map(|s| s.to_le_bytes().to_vec())
+            .collect::<Vec<_>>();
+        let decoder = WavDecoder::new(riff(&[
+            fmt(WAVE_FORMAT_PCM, 2, 8, 32, &[]),
+            chunk(b"data", &data),
+        ]))
+        .unwrap();
+        assert_eq!(decoder.channel_count, 2);
+        assert_eq!(decoder.frame_count(), 2);
+        assert_eq!(decoder.collect::<Vec<_>>(), vec![-1.0, 0.5, 0.0, -0.25]);
+    }
+
+    #[test]
+    fn test_float() {
+        let samples = [0.25f32, -0.75, 1.0];
+        let data = samples
+            .iter()
+            .flat_map(|s| s.to_le_bytes().to_vec())
+            .collect::<Vec<_>>();
+        let decoder = WavDecoder::new(riff(&[
+            fmt(WAVE_FORMAT_IEEE_FLOAT, 1, 4, 32, &[]),
+            chunk(b"data", &data),
+        ]))
+        .unwrap();
+        assert_eq!(decoder.collect::<Vec<_>>(), samples.to_vec());
+    }
+
+    #[test]
+    fn test_extensible_float() {
+        // Valid bits, channel mask and sub-format GUID which starts with actual format tag.
+        let mut extension = 32u16.to_le_bytes().to_vec();
+        extension.extend_from_slice(&3u32.to_le_bytes());
+        extension.extend_from_slice(&WAVE_FORMAT_IEEE_FLOAT.to_le_bytes());
+        extension.extend_from_slice(&[0; 14]);
+        let data = [0.5f32, -0.5]
+            .iter()
+            .flat_map(|s| s.to_le_bytes().to_vec())
+            .collect::<Vec<_>>();
+        let decoder = WavDecoder::new(riff(&[
+            fmt(WAVE_FORMAT_EXTENSIBLE, 2, 8, 32, &extension),
+            chunk(b"data", &data),
+        ]))
+        .unwrap();
+        assert_eq!(decoder.channel_count, 2);
+        assert_eq!(decoder.collect::<Vec<_>>(), vec![0.5, -0.5]);
+    }
+
+    #[test]
+    fn test_unknown_chunks_skipped() {
+        // Odd sized chunks are followed by padding byte which must be skipped as well.
+        let decoder = WavDecoder::new(riff(&[
+            chunk(b"LIST", b"INFOabc"),
+            fmt(WAVE_FORMAT_PCM, 1, 2, 16, &[]),
+            chunk(b"cue ", &[1, 2, 3]),
+            chunk(b"data", &[0x00, 0x40, 0x00, 0xC0]),
+            chunk(b"LIST", &[0; 5]),
+        ]))
+        .unwrap();
+        assert_samples(decoder, &[16384, -16384]);
+    }
+
+    #[test]
+    fn test_unsupported_format() {
+        let result = WavDecoder::new(riff(&[
+            fmt(WAVE_FORMAT_PCM, 1, 2, 12, &[]),
+            chunk(b"data", &[0; 4]),
+        ]));
+        assert_eq!(invalid_chunk(result), "fmt ");
+
+        let result = WavDecoder::new(riff(&[
+            fmt(WAVE_FORMAT_IEEE_FLOAT, 1, 8, 64, &[]),
+            chunk(b"data", &[0; 8]),
+        ]));
+        assert_eq!(invalid_chunk(result), "fmt ");
+
+        // Block align does not match 24-bit stereo.
+        let result = WavDecoder::new(riff(&[
+            fmt(WAVE_FORMAT_PCM, 2, 4, 24, &[]),
+            chunk(b"data", &[0; 8]),
+        ]));
+        assert_eq!(invalid_chunk(result), "fmt ");
+    }
+}
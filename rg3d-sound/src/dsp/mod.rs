@@ -11,6 +11,7 @@
 use rg3d_core::visitor::{Visit, VisitResult, Visitor};
 
 pub mod filters;
+pub mod resample;
 
 /// See more info here https://ccrma.stanford.edu/~jos/pasp/Delay_Lines.html
 pub struct DelayLine {
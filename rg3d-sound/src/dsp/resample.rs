@@ -0,0 +1,241 @@
+//! Sample rate conversion.
+//!
+//! # Overview
+//!
+//! Sound sources are able to play buffers of any sample rate, but they do this by stepping
+//! through samples every frame, which is cheap but low quality - high frequencies are aliased
+//! when a sound is played at lower rate than its own. Instead, a buffer can be converted to the
+//! sample rate of output device once (usually at load time) using functions of this module,
+//! then sources will play it sample-by-sample.
+//!
+//! # Quality
+//!
+//! - [ResamplingQuality::Linear] - linear interpolation between two nearest samples, very fast
+//!   but adds some aliasing and dulls high frequencies.
+//! - [ResamplingQuality::Sinc] - interpolation with windowed-sinc kernel, much better quality
+//!   but several times slower. When sample rate is lowered, the kernel also works as low-pass
+//!   filter which removes frequencies that cannot be represented at new rate.
+
+use std::f32::consts::PI;
+
+/// Half-width of windowed-sinc kernel in samples of the lowest of two rates.
+const SINC_HALF_WIDTH: usize = 16;
+
+/// Amount of precomputed values of windowed-sinc kernel per sample.
+const SINC_TABLE_RESOLUTION: usize = 256;
+
+/// Interpolation method for sample rate conversion. See module docs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ResamplingQuality {
+    /// Linear interpolation.
+    Linear,
+    /// Interpolation with windowed-sinc kernel.
+    Sinc,
+}
+
+impl Default for ResamplingQuality {
+    fn default() -> Self {
+        ResamplingQuality::Sinc
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < std::f32::EPSILON {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+// Blackman window centered at zero, `x` is in -1..1 range.
+fn blackman(x: f32) -> f32 {
+    0.42 + 0.5 * (PI * x).cos() + 0.08 * (2.0 * PI * x).cos()
+}
+
+// Windowed-sinc kernel is symmetric, so only its right half is stored. Values between
+// precomputed points are linearly interpolated, which is much faster than calculating
+// sin and cos for every weight.
+struct SincTable {
+    values: Vec<f32>,
+}
+
+impl SincTable {
+    fn new() -> Self {
+        // One extra zero at the end, so interpolation never goes out of bounds.
+        let len = SINC_HALF_WIDTH * SINC_TABLE_RESOLUTION + 2;
+        let values = (0..len)
+            .map(|i| {
+                let x = i as f32 / SINC_TABLE_RESOLUTION as f32;
+                let window_x = x / SINC_HALF_WIDTH as f32;
+                if window_x < 1.0 {
+                    sinc(x) * blackman(window_x)
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        Self { values }
+    }
+
+    fn weight(&self, x: f32) -> f32 {
+        let position = x.abs() * SINC_TABLE_RESOLUTION as f32;
+        let index = position as usize;
+        if index + 1 < self.values.len() {
+            let t = position - index as f32;
+            let a = self.values[index];
+            let b = self.values[index + 1];
+            a + (b - a) * t
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Converts interleaved samples from one sample rate to another and returns new interleaved
+/// samples. Input is returned as is if rates are equal. Length of the result is rounded up, so
+/// input with at least one frame always gives non-empty output. Incomplete frame at the end
+/// of input is ignored.
+///
+/// # Panics
+///
+/// Panics if `channel_count` or any of rates is zero.
+pub fn resample(
+    samples: &[f32],
+    channel_count: usize,
+    from_rate: usize,
+    to_rate: usize,
+    quality: ResamplingQuality,
+) -> Vec<f32> {
+    assert!(channel_count != 0 && from_rate != 0 && to_rate != 0);
+
+    if from_rate == to_rate {
+        return samples.to_vec();
+    }
+
+    let frame_count = samples.len() / channel_count;
+    if frame_count == 0 {
+        return Vec::new();
+    }
+
+    let out_frame_count = (frame_count * to_rate + from_rate - 1) / from_rate;
+    // Position in source frames that corresponds to one output frame.
+    let step = from_rate as f64 / to_rate as f64;
+    let last = frame_count - 1;
+    let sample = |frame: usize, channel: usize| samples[frame * channel_count + channel];
+
+    let mut out = Vec::with_capacity(out_frame_count * channel_count);
+    match quality {
+        ResamplingQuality::Linear => {
+            for i in 0..out_frame_count {
+                let position = i as f64 * step;
+                let index = (position as usize).min(last);
+                let next = (index + 1).min(last);
+                let t = (position - index as f64) as f32;
+                for channel in 0..channel_count {
+                    let a = sample(index, channel);
+                    let b = sample(next, channel);
+                    out.push(a + (b - a) * t);
+                }
+            }
+        }
+        ResamplingQuality::Sinc => {
+            // When downsampling, kernel is stretched to cut frequencies above new Nyquist
+            // frequency.
+            let cutoff = (to_rate as f32 / from_rate as f32).min(1.0);
+            let half_width = (SINC_HALF_WIDTH as f32 / cutoff).ceil() as isize;
+            let table = SincTable::new();
+            let mut weights = Vec::with_capacity(2 * half_width as usize);
+            for i in 0..out_frame_count {
+                let position = i as f64 * step;
+                let center = position.floor() as isize;
+                let first = (center - half_width + 1).max(0);
+                let end = (center + half_width).min(last as isize);
+
+                weights.clear();
+                let mut total_weight = 0.0;
+                for k in first..=end {
+                    let weight = table.weight((position - k as f64) as f32 * cutoff);
+                    weights.push(weight);
+                    total_weight += weight;
+                }
+                // Normalization keeps gain of the kernel at one, it also compensates cut
+                // kernel at the edges of the signal.
+                let norm = if total_weight.abs() > std::f32::EPSILON {
+                    1.0 / total_weight
+                } else {
+                    0.0
+                };
+
+                for channel in 0..channel_count {
+                    let mut value = 0.0;
+                    for (k, weight) in (first..=end).zip(weights.iter()) {
+                        value += sample(k as usize, channel) * weight;
+                    }
+                    out.push(value * norm);
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dsp::resample::{resample, ResamplingQuality};
+
+    const QUALITIES: [ResamplingQuality; 2] = [ResamplingQuality::Linear, ResamplingQuality::Sinc];
+
+    #[test]
+    fn test_empty_input() {
+        for &quality in QUALITIES.iter() {
+            assert!(resample(&[], 2, 44100, 48000, quality).is_empty());
+            // Less than one frame.
+            assert!(resample(&[1.0], 2, 44100, 48000, quality).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_length() {
+        for &quality in QUALITIES.iter() {
+            for &(from_rate, to_rate, frames, expected_frames) in [
+                (44100, 48000, 44100, 48000),
+                (48000, 44100, 48000, 44100),
+                (22050, 44100, 1000, 2000),
+                (44100, 22050, 1000, 500),
+                (44100, 22050, 1001, 501),
+                (48000, 44100, 1, 1),
+            ]
+            .iter()
+            {
+                for &channel_count in [1, 2].iter() {
+                    let samples = vec![0.0; frames * channel_count];
+                    let out = resample(&samples, channel_count, from_rate, to_rate, quality);
+                    assert_eq!(out.len(), expected_frames * channel_count);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_same_rate() {
+        let samples = [0.1, 0.2, 0.3, 0.4];
+        for &quality in QUALITIES.iter() {
+            assert_eq!(resample(&samples, 2, 44100, 44100, quality), samples);
+        }
+    }
+
+    #[test]
+    fn test_dc_signal() {
+        for &quality in QUALITIES.iter() {
+            for &(from_rate, to_rate) in [(44100, 48000), (48000, 44100), (11025, 44100)].iter() {
+                // Different levels in channels to check that channels are not mixed.
+                let samples = (0..4410).flat_map(|_| vec![0.5, -0.25]).collect::<Vec<_>>();
+                let out = resample(&samples, 2, from_rate, to_rate, quality);
+                for frame in out.chunks(2) {
+                    assert!((frame[0] - 0.5).abs() < 1.0e-3, "{:?}", quality);
+                    assert!((frame[1] + 0.25).abs() < 1.0e-3, "{:?}", quality);
+                }
+            }
+        }
+    }
+}
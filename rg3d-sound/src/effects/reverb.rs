@@ -20,19 +20,31 @@
 //! }
 //! ```
 //!
+//! Reverb could also be configured using presets, which is useful to simulate different kinds of
+//! environment. Presets could be blended together to make smooth transitions between environments.
+//!
+//! ```
+//! use rg3d_sound::effects::reverb::{Reverb, ReverbPreset};
+//!
+//! fn enter_cave(reverb: &mut Reverb) {
+//!     reverb.set_preset(&ReverbPreset::cave());
+//! }
+//! ```
+//!
 //! # Known problems
 //!
 //! This reverberator has little "metallic" tone, but since this is one of the simplest reverberators this
 //! is acceptable. To remove this effect, more complex reverberator should be implemented.
 
 use crate::{
-    context::DistanceModel,
+    context::{DistanceModel, SAMPLE_RATE},
     dsp::filters::{AllPass, LpfComb},
     effects::{BaseEffect, EffectRenderTrait},
     listener::Listener,
     source::SoundSource,
 };
 use rg3d_core::{
+    math,
     pool::Pool,
     visitor::{Visit, VisitResult, Visitor},
 };
@@ -150,6 +162,81 @@ impl Visit for ChannelReverb {
     }
 }
 
+/// A set of reverb parameters that describes an environment.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ReverbPreset {
+    /// Duration of reverberation, see [`Reverb::set_decay_time`].
+    pub decay_time: Duration,
+    /// Cutoff frequency (in Hz) of reflections, see [`Reverb::set_fc`].
+    pub cutoff_frequency: f32,
+    /// Amount of input signal passed to output, see [`Reverb::set_dry`].
+    pub dry: f32,
+    /// Stereo mixing of processed signal, see [`Reverb::set_wet`].
+    pub wet: f32,
+}
+
+impl Default for ReverbPreset {
+    fn default() -> Self {
+        Self::small_room()
+    }
+}
+
+impl ReverbPreset {
+    /// Open space with almost no reflections.
+    pub fn outdoors() -> Self {
+        Self {
+            decay_time: Duration::from_secs_f32(0.2),
+            cutoff_frequency: 11_000.0,
+            dry: 1.0,
+            wet: 1.0,
+        }
+    }
+
+    /// Small room with short and bright reflections.
+    pub fn small_room() -> Self {
+        Self {
+            decay_time: Duration::from_secs_f32(0.6),
+            cutoff_frequency: 8_000.0,
+            dry: 1.0,
+            wet: 1.0,
+        }
+    }
+
+    /// Large hall with long reflections.
+    pub fn hall() -> Self {
+        Self {
+            decay_time: Duration::from_secs_f32(2.5),
+            cutoff_frequency: 6_000.0,
+            dry: 1.0,
+            wet: 0.8,
+        }
+    }
+
+    /// Cave with very long and muffled reflections.
+    pub fn cave() -> Self {
+        Self {
+            decay_time: Duration::from_secs_f32(4.0),
+            cutoff_frequency: 3_000.0,
+            dry: 0.9,
+            wet: 0.7,
+        }
+    }
+
+    /// Linearly interpolates every parameter of two presets, `t` should be in `[0; 1]` range.
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            decay_time: Duration::from_secs_f32(math::lerpf(
+                self.decay_time.as_secs_f32(),
+                other.decay_time.as_secs_f32(),
+                t,
+            )),
+            cutoff_frequency: math::lerpf(self.cutoff_frequency, other.cutoff_frequency, t),
+            dry: math::lerpf(self.dry, other.dry, t),
+            wet: math::lerpf(self.wet, other.wet, t),
+        }
+    }
+}
+
 /// See module docs.
 pub struct Reverb {
     base: BaseEffect,
@@ -245,6 +332,14 @@ impl Reverb {
         self.left.set_fc(fc);
         self.right.set_fc(fc);
     }
+
+    /// Sets every parameter of reverb from given preset.
+    pub fn set_preset(&mut self, preset: &ReverbPreset) {
+        self.set_decay_time(preset.decay_time);
+        self.set_fc(preset.cutoff_frequency / SAMPLE_RATE as f32);
+        self.set_dry(preset.dry);
+        self.set_wet(preset.wet);
+    }
 }
 
 impl Visit for Reverb {
@@ -47,6 +47,18 @@ pub enum SoundError {
     /// Decoder specific error, can occur in the decoder by any reason (invalid format,
     /// insufficient data, etc.). Exact reason stored in inner value.
     DecoderError(DecoderError),
+
+    /// Loop region is invalid - its end is before its start or it is out of bounds of
+    /// the buffer.
+    InvalidLoopRegion,
+
+    /// Chunk of WAV file is malformed or describes unsupported data.
+    InvalidWavChunk {
+        /// Four-character id of the chunk, for example "fmt " or "data".
+        chunk: &'static str,
+        /// Human-readable description of the problem.
+        reason: String,
+    },
 }
 
 impl From<std::io::Error> for SoundError {
@@ -85,6 +97,10 @@ impl Display for SoundError {
                 write!(f, "streaming buffer in already in use")?
             }
             SoundError::DecoderError(de) => write!(f, "internal decoder error: {:?}", de)?,
+            SoundError::InvalidLoopRegion => write!(f, "invalid loop region")?,
+            SoundError::InvalidWavChunk { chunk, reason } => {
+                write!(f, "invalid \"{}\" chunk of wav file: {}", chunk, reason)?
+            }
         }
         Ok(())
     }
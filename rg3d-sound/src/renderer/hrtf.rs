@@ -34,6 +34,12 @@
 //! }
 //! ```
 //!
+//! # Stereo sounds
+//!
+//! HRTF simulates sound that comes from a single point, so it makes sense only for mono sounds. Stereo
+//! buffers that are used by spatial sources should be converted to mono at load using
+//! [crate::buffer::SoundBuffer::to_mono].
+//!
 //! # Performance
 //!
 //! HRTF is `heavy`. Usually it 4-5 slower than default renderer, this is essential because HRTF requires some heavy
@@ -28,10 +28,14 @@ pub enum Renderer {
     HrtfRenderer(HrtfRenderer),
 }
 
+/// Renders source with given gains. Width is a pair of previous and current stereo width,
+/// 1.0 means that stereo channels are passed as is and 0.0 means that channels are mixed
+/// together.
 fn render_with_params(
     source: &mut GenericSource,
     left_gain: f32,
     right_gain: f32,
+    width: (f32, f32),
     mix_buffer: &mut [(f32, f32)],
 ) {
     let step = 1.0 / mix_buffer.len() as f32;
@@ -43,6 +47,14 @@ fn render_with_params(
     for ((out_left, out_right), &(raw_left, raw_right)) in
         mix_buffer.iter_mut().zip(source.frame_samples())
     {
+        let (raw_left, raw_right) = if width == (1.0, 1.0) {
+            (raw_left, raw_right)
+        } else {
+            let mid = (raw_left + raw_right) * 0.5;
+            let side = (raw_left - raw_right) * 0.5 * math::lerpf(width.0, width.1, t);
+            (mid + side, mid - side)
+        };
+
         // Interpolation of gain is very important to remove clicks which appears
         // when gain changes by significant value between frames.
         *out_left += math::lerpf(last_left_gain, left_gain, t) * raw_left;
@@ -64,17 +76,33 @@ pub(in crate) fn render_source_default(
             let panning = generic.panning();
             let left_gain = gain * (1.0 + panning);
             let right_gain = gain * (1.0 - panning);
-            render_with_params(generic, left_gain, right_gain, mix_buffer);
+            render_with_params(generic, left_gain, right_gain, (1.0, 1.0), mix_buffer);
             generic.last_left_gain = Some(left_gain);
             generic.last_right_gain = Some(right_gain);
         }
         SoundSource::Spatial(spatial) => {
             let distance_gain = spatial.get_distance_gain(listener, distance_model);
-            let panning = spatial.get_panning(listener);
+            let mut panning = spatial.get_panning(listener);
+            let width = match spatial.get_spread(listener) {
+                Some(spread) => {
+                    // Wide source surrounds listener, so its direction matters less.
+                    panning *= 1.0 - spread;
+                    let last_spread = *spatial.prev_spread.get_or_insert(spread);
+                    spatial.prev_spread = Some(spread);
+                    (last_spread, spread)
+                }
+                None => (1.0, 1.0),
+            };
             let gain = distance_gain * spatial.generic().gain();
             let left_gain = gain * (1.0 + panning);
             let right_gain = gain * (1.0 - panning);
-            render_with_params(spatial.generic_mut(), left_gain, right_gain, mix_buffer);
+            render_with_params(
+                spatial.generic_mut(),
+                left_gain,
+                right_gain,
+                width,
+                mix_buffer,
+            );
             spatial.generic_mut().last_left_gain = Some(left_gain);
             spatial.generic_mut().last_right_gain = Some(right_gain);
         }
@@ -26,18 +26,124 @@
 //! }
 //!
 //! ```
+//!
+//! # Fading
+//!
+//! Abrupt start or stop of a sound produces audible click, use `fade` to smoothly change volume
+//! of a source over time or `stop_with_fade` to smoothly stop it. Fades are processed by mixer
+//! per-sample, so they're not dependent on frame rate of a game.
+//!
+//! ```no_run
+//! use rg3d_sound::source::generic::GenericSource;
+//! use std::time::Duration;
+//!
+//! fn fade_out_music(source: &mut GenericSource) {
+//!     source.stop_with_fade(Duration::from_secs(2));
+//! }
+//! ```
+//!
+//! # Loop region
+//!
+//! Music often has non-looping intro and looping body. Use `set_loop_region` to define a range
+//! of frames that will be repeated when source is looping: playback starts from the beginning
+//! of the buffer and when it reaches end of the region it continues from start of the region.
+//! Wrap is sample-accurate, fractional part of read position is preserved.
+//!
+//! ```no_run
+//! use rg3d_sound::source::generic::GenericSource;
+//!
+//! fn setup_music(source: &mut GenericSource) {
+//!     source.set_looping(true);
+//!     // Intro is first 88200 frames, then only frames in 88200..441000 range will be played.
+//!     source.set_loop_region(88200, 441000).unwrap();
+//! }
+//! ```
+//!
+//! # Priority
+//!
+//! Context has limited amount of voices (see `Context::set_max_voices`), when there are more
+//! playing sources than voices, least important sources will be stopped. Importance is defined
+//! by priority first, then by effective gain (with distance attenuation and obstruction), then
+//! by age - sources that play longer are stopped first. Use `set_priority` to protect important
+//! sounds (dialogs, music, etc.) from being stopped.
+//!
+//! Amount of audible sources is limited as well (see `Context::set_max_audible_sources`), least
+//! important sources that exceed this limit become virtual - they continue playing, but they're
+//! not mixed. See `is_virtual` for more info.
 
 use crate::{
     buffer::{streaming::StreamingBuffer, SoundBuffer},
     error::SoundError,
     source::{SoundSource, Status},
 };
-use rg3d_core::visitor::{Visit, VisitResult, Visitor};
+use rg3d_core::{
+    math,
+    visitor::{Visit, VisitError, VisitResult, Visitor},
+};
 use std::{
     sync::{Arc, Mutex},
     time::Duration,
 };
 
+/// Range of frames (samples of each channel) of a buffer that will be repeated when source
+/// is looping. Start is inclusive, end is exclusive.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoopRegion {
+    /// Index of first frame of the region.
+    pub start: usize,
+    /// Index of frame right after the last frame of the region.
+    pub end: usize,
+}
+
+impl Visit for LoopRegion {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut start = self.start as u64;
+        start.visit("Start", visitor)?;
+        let mut end = self.end as u64;
+        end.visit("End", visitor)?;
+
+        if visitor.is_reading() {
+            if start >= end {
+                return Err(VisitError::User("Invalid loop region!".to_owned()));
+            }
+            self.start = start as usize;
+            self.end = end as usize;
+        }
+
+        visitor.leave_region()
+    }
+}
+
+// Length of fade in or fade out when a source becomes audible or virtual.
+const VIRTUALIZATION_FADE_DURATION: Duration = Duration::from_millis(20);
+
+// Gain ramp which is applied on top of the gain of a source, see `GenericSource::fade`.
+#[derive(Copy, Clone, Debug, Default)]
+struct Fade {
+    from: f32,
+    to: f32,
+    // Length and progress of the ramp in device samples.
+    length: u32,
+    elapsed: u32,
+    stop_on_end: bool,
+}
+
+impl Visit for Fade {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.from.visit("From", visitor)?;
+        self.to.visit("To", visitor)?;
+        self.length.visit("Length", visitor)?;
+        self.elapsed.visit("Elapsed", visitor)?;
+        self.stop_on_end.visit("StopOnEnd", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
 /// See module info.
 pub struct GenericSource {
     buffer: Option<Arc<Mutex<SoundBuffer>>>,
@@ -51,6 +157,10 @@ pub struct GenericSource {
     pitch: f64,
     gain: f32,
     looping: bool,
+    loop_region: Option<LoopRegion>,
+    // Current value of gain ramp, it is multiplied with `gain`.
+    fade_gain: f32,
+    fade: Option<Fade>,
     // Important coefficient for runtime resampling. It is used to modify playback speed
     // of a source in order to match output device sampling rate. PCM data can be stored
     // in various sampling rates (22050 Hz, 44100 Hz, 88200 Hz, etc.) but output device
@@ -62,6 +172,15 @@ pub struct GenericSource {
     resampling_multiplier: f64,
     status: Status,
     play_once: bool,
+    priority: i32,
+    // Amount of samples rendered since the source has started playing, it is used to select
+    // a voice to stop when there are too many playing sources.
+    pub(in crate) age: u64,
+    // Virtual source keeps playing, but its samples are not rendered and mixed.
+    is_virtual: bool,
+    // Fade gain the source had before it became virtual, it is restored when the source
+    // becomes audible again.
+    audible_fade_gain: f32,
     // Here we use Option because when source is just created it has no info about it
     // previous left and right channel gains. We can't set it to 1.0 for example
     // because it would give incorrect results: a sound would just start as loud as it
@@ -83,9 +202,16 @@ impl Default for GenericSource {
             pitch: 1.0,
             gain: 1.0,
             looping: false,
+            loop_region: None,
+            fade_gain: 1.0,
+            fade: None,
             resampling_multiplier: 1.0,
             status: Status::Stopped,
             play_once: false,
+            priority: 0,
+            age: 0,
+            is_virtual: false,
+            audible_fade_gain: 1.0,
             last_left_gain: None,
             last_right_gain: None,
             frame_samples: Default::default(),
@@ -119,13 +245,14 @@ fn position_to_index(position: f64, channel_count: usize) -> usize {
 
 impl GenericSource {
     /// Changes buffer of source. Returns old buffer. Source will continue playing from beginning, old
-    /// position will be discarded.
+    /// position and loop region will be discarded.
     pub fn set_buffer(
         &mut self,
         buffer: Arc<Mutex<SoundBuffer>>,
     ) -> Result<Option<Arc<Mutex<SoundBuffer>>>, SoundError> {
         self.buf_read_pos = 0.0;
         self.playback_pos = 0.0;
+        self.loop_region = None;
 
         // Check new buffer if streaming - it must not be used by anyone else.
         if let SoundBuffer::Streaming(ref mut streaming) = *buffer.lock()? {
@@ -167,6 +294,18 @@ impl GenericSource {
         self.play_once
     }
 
+    /// Sets priority of the source. When there are more playing sources than context allows,
+    /// sources with lowest priority will be stopped first. Default priority is 0.
+    pub fn set_priority(&mut self, priority: i32) -> &mut Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Returns priority of the source. See `set_priority` for more info.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
     /// Sets new gain (volume) of sound. Value should be in 0..1 range, but it is not clamped
     /// and larger values can be used to "overdrive" sound.
     ///
@@ -196,6 +335,119 @@ impl GenericSource {
         self.panning
     }
 
+    /// Smoothly changes fade gain of the source from current value to `target_gain` over given duration.
+    /// Fade gain is multiplied with gain of the source, it is 1.0 by default. Fade request that was made
+    /// while other fade is in progress continues from current interpolated value, so there won't be any
+    /// jumps in volume. Zero duration changes fade gain immediately.
+    pub fn fade(&mut self, target_gain: f32, duration: Duration) -> &mut Self {
+        self.start_fade(target_gain, duration, false);
+        self
+    }
+
+    /// Smoothly fades the source out over given duration and then stops it. Fade gain is restored
+    /// to 1.0 after stop, so the source can be played again at normal volume. If the source is
+    /// marked as "play once", it will be removed from context right after fade completes.
+    pub fn stop_with_fade(&mut self, duration: Duration) -> &mut Self {
+        self.start_fade(0.0, duration, true);
+        self
+    }
+
+    /// Returns current value of fade gain, see `fade` for more info.
+    pub fn fade_gain(&self) -> f32 {
+        self.fade_gain
+    }
+
+    /// Returns true if the source is fading now.
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    // Returns true if the source is fading out to stop, see `stop_with_fade`.
+    pub(in crate) fn is_stopping(&self) -> bool {
+        self.fade.map_or(false, |f| f.stop_on_end)
+    }
+
+    fn start_fade(&mut self, target_gain: f32, duration: Duration, stop_on_end: bool) {
+        let length =
+            (duration.as_secs_f64() * f64::from(crate::context::SAMPLE_RATE)).round() as u32;
+        self.fade = Some(Fade {
+            from: self.fade_gain,
+            to: target_gain,
+            length,
+            elapsed: 0,
+            stop_on_end,
+        });
+        if length == 0 {
+            // Apply instantly, there is nothing to interpolate.
+            self.next_fade_gain();
+            if stop_on_end {
+                let _ = self.stop();
+            }
+        }
+    }
+
+    // Advances fade by one sample and returns new fade gain. If fade has to stop the source,
+    // only status will be changed, the rest will be done by `stop` when the buffer is not locked.
+    fn next_fade_gain(&mut self) -> f32 {
+        if let Some(fade) = self.fade.as_mut() {
+            fade.elapsed = fade.elapsed.saturating_add(1).min(fade.length);
+            let t = if fade.length == 0 {
+                1.0
+            } else {
+                fade.elapsed as f32 / fade.length as f32
+            };
+            self.fade_gain = math::lerpf(fade.from, fade.to, t);
+            if fade.elapsed == fade.length {
+                if fade.stop_on_end {
+                    // Completed fade-out is kept, so `stop` will restore fade gain. Otherwise
+                    // last sample of the fade would be played at full volume.
+                    self.status = Status::Stopped;
+                } else {
+                    self.fade = None;
+                }
+            }
+        }
+        self.fade_gain
+    }
+
+    /// Returns true if the source is virtual. Virtual source is playing, but it is not mixed
+    /// because there are too many audible sources, see `Context::set_max_audible_sources`.
+    /// Playback position of virtual source is still advanced, so when the source becomes
+    /// audible again it continues from where it would be if it was audible all the time.
+    /// Play once sources are removed at the end of playback regardless of being virtual.
+    pub fn is_virtual(&self) -> bool {
+        self.is_virtual
+    }
+
+    // Makes the source virtual or audible. Source is faded out when it becomes virtual and
+    // faded in when it becomes audible, otherwise it would be cut off (or started) abruptly and
+    // produce a click. Fade that was in progress is replaced, unless the source is fading out
+    // to stop.
+    pub(in crate) fn set_virtual(&mut self, is_virtual: bool) {
+        if self.is_virtual == is_virtual {
+            return;
+        }
+        self.is_virtual = is_virtual;
+        if self.is_stopping() {
+            return;
+        }
+        if is_virtual {
+            self.audible_fade_gain = self.fade.map_or(self.fade_gain, |f| f.to);
+            self.start_fade(0.0, VIRTUALIZATION_FADE_DURATION, false);
+        } else {
+            self.start_fade(self.audible_fade_gain, VIRTUALIZATION_FADE_DURATION, false);
+        }
+    }
+
+    // Returns fade gain that is set by user, it ignores fade out of virtual source.
+    pub(in crate) fn audible_fade_gain(&self) -> f32 {
+        if self.is_virtual {
+            self.audible_fade_gain
+        } else {
+            self.fade_gain
+        }
+    }
+
     /// Returns status of sound source.
     pub fn status(&self) -> Status {
         self.status
@@ -225,6 +477,52 @@ impl GenericSource {
         self.looping
     }
 
+    /// Sets range of frames (samples of each channel) that will be repeated when source is looping.
+    /// Source plays from the beginning of the buffer and then loops only within the region. Region is
+    /// used only if looping is enabled. Returns error if end is not after start or end is beyond length
+    /// of the buffer. Length of some streaming buffers is unknown, in this case only order of bounds is
+    /// checked.
+    pub fn set_loop_region(&mut self, start: usize, end: usize) -> Result<(), SoundError> {
+        if start >= end {
+            return Err(SoundError::InvalidLoopRegion);
+        }
+        if let Some(buffer) = self.buffer.as_ref() {
+            if let Some(frame_count) = buffer.lock()?.frame_count() {
+                if end > frame_count {
+                    return Err(SoundError::InvalidLoopRegion);
+                }
+            }
+        }
+        self.loop_region = Some(LoopRegion { start, end });
+        Ok(())
+    }
+
+    /// Same as `set_loop_region` but bounds of region are defined in time.
+    pub fn set_loop_region_time(
+        &mut self,
+        start: Duration,
+        end: Duration,
+    ) -> Result<(), SoundError> {
+        let sample_rate = match self.buffer.as_ref() {
+            Some(buffer) => buffer.lock()?.sample_rate() as f64,
+            None => return Err(SoundError::InvalidLoopRegion),
+        };
+        self.set_loop_region(
+            (start.as_secs_f64() * sample_rate) as usize,
+            (end.as_secs_f64() * sample_rate) as usize,
+        )
+    }
+
+    /// Removes loop region, looping source will repeat whole buffer again.
+    pub fn clear_loop_region(&mut self) {
+        self.loop_region = None;
+    }
+
+    /// Returns current loop region if any.
+    pub fn loop_region(&self) -> Option<LoopRegion> {
+        self.loop_region
+    }
+
     /// Sets sound pitch. Defines "tone" of sounds. Default value is 1.0
     pub fn set_pitch(&mut self, pitch: f64) -> &mut Self {
         self.pitch = pitch.abs();
@@ -240,8 +538,20 @@ impl GenericSource {
     pub fn stop(&mut self) -> Result<(), SoundError> {
         self.status = Status::Stopped;
 
+        // Pending or completed fade-out makes no sense for stopped source.
+        if self.is_stopping() {
+            self.fade = None;
+            self.fade_gain = 1.0;
+        } else if self.is_virtual {
+            // Fade out of virtual source must not make it silent on next play.
+            self.fade = None;
+            self.fade_gain = self.audible_fade_gain;
+        }
+
         self.buf_read_pos = 0.0;
         self.playback_pos = 0.0;
+        self.age = 0;
+        self.is_virtual = false;
 
         if let Some(mut buffer) = self.buffer.as_ref().and_then(|b| b.lock().ok()) {
             if let SoundBuffer::Streaming(ref mut streaming) = *buffer {
@@ -290,6 +600,31 @@ impl GenericSource {
         }
     }
 
+    fn wrap_to_loop_start(&mut self, buffer: &mut SoundBuffer, region: LoopRegion, overshoot: f64) {
+        let channel_count = buffer.channel_count();
+        let length = ((region.end - region.start) * channel_count) as f64;
+        let overshoot = overshoot % length;
+        self.playback_pos = (region.start * channel_count) as f64 + overshoot;
+        match buffer {
+            SoundBuffer::Generic(_) => self.buf_read_pos = self.playback_pos,
+            SoundBuffer::Streaming(streaming) => {
+                // Streaming buffer contains only small block of data, so decoder must be moved to
+                // start of region and the block must be refilled from there.
+                if streaming.frame_seek(region.start).is_ok() {
+                    streaming.read_next_block();
+                }
+                if streaming.generic.samples.is_empty() {
+                    // Region starts beyond actual data, fall back to full-buffer looping.
+                    self.loop_region = None;
+                    self.playback_pos = 0.0;
+                    let _ = streaming.rewind();
+                    streaming.read_next_block();
+                }
+                self.buf_read_pos = self.playback_pos - (region.start * channel_count) as f64;
+            }
+        }
+    }
+
     fn next_sample_pair(&mut self, buffer: &mut SoundBuffer) -> (f32, f32) {
         let step = self.pitch * self.resampling_multiplier;
 
@@ -297,6 +632,15 @@ impl GenericSource {
         self.playback_pos += step;
 
         let channel_count = buffer.channel_count();
+
+        let loop_region = if self.looping { self.loop_region } else { None };
+        if let Some(region) = loop_region {
+            let end = (region.end * channel_count) as f64;
+            if self.playback_pos >= end {
+                self.wrap_to_loop_start(buffer, region, self.playback_pos - end);
+            }
+        }
+
         let mut i = position_to_index(self.buf_read_pos, channel_count);
 
         let len = buffer.samples().len();
@@ -318,7 +662,11 @@ impl GenericSource {
                 self.playback_pos = 0.0;
             }
             self.buf_read_pos = 0.0;
-            i = 0;
+            // Data can end before loop region if length of streaming buffer is unknown.
+            if let (true, Some(region)) = (end_reached, loop_region) {
+                self.wrap_to_loop_start(buffer, region, 0.0);
+            }
+            i = position_to_index(self.buf_read_pos, channel_count);
         }
 
         let samples = buffer.samples();
@@ -339,6 +687,12 @@ impl GenericSource {
 
         self.frame_samples.clear();
 
+        if self.status == Status::Playing {
+            self.age += amount as u64;
+        }
+
+        let mut faded_out = false;
+
         if let Some(mut buffer) = self.buffer.clone().as_ref().and_then(|b| {
             b.lock()
                 .ok()
@@ -346,8 +700,11 @@ impl GenericSource {
         }) {
             for _ in 0..amount {
                 if self.status == Status::Playing {
-                    let pair = self.next_sample_pair(&mut buffer);
-                    self.frame_samples.push(pair);
+                    let (left, right) = self.next_sample_pair(&mut buffer);
+                    let fade_gain = self.next_fade_gain();
+                    faded_out |= self.status == Status::Stopped;
+                    self.frame_samples
+                        .push((left * fade_gain, right * fade_gain));
                 } else {
                     self.frame_samples.push((0.0, 0.0));
                 }
@@ -357,6 +714,45 @@ impl GenericSource {
                 self.frame_samples.push((0.0, 0.0));
             }
         }
+
+        // Rewind the source after fade was completed, it must be done when buffer is unlocked.
+        if faded_out {
+            let _ = self.stop();
+        }
+    }
+
+    // Moves playback position as if `amount` samples were rendered, but does not produce any
+    // samples. It is used for virtual sources, so they keep their place in the sound.
+    pub(in crate) fn advance(&mut self, amount: usize) {
+        self.frame_samples.clear();
+
+        if self.status != Status::Playing {
+            return;
+        }
+
+        self.age += amount as u64;
+
+        let mut stopped = false;
+
+        if let Some(mut buffer) = self.buffer.clone().as_ref().and_then(|b| {
+            b.lock()
+                .ok()
+                .and_then(|b| if b.is_empty() { None } else { Some(b) })
+        }) {
+            for _ in 0..amount {
+                self.next_sample_pair(&mut buffer);
+                self.next_fade_gain();
+                if self.status == Status::Stopped {
+                    stopped = true;
+                    break;
+                }
+            }
+        }
+
+        // Rewind the source when playback or fade has ended, same as `render` does.
+        if stopped {
+            let _ = self.stop();
+        }
     }
 
     pub(in crate) fn frame_samples(&self) -> &[(f32, f32)] {
@@ -385,10 +781,24 @@ impl Visit for GenericSource {
         self.pitch.visit("Pitch", visitor)?;
         self.gain.visit("Gain", visitor)?;
         self.looping.visit("Looping", visitor)?;
+        let _ = self.loop_region.visit("LoopRegion", visitor);
+        // Virtual source is faded out by context, so state of audible source is saved instead.
+        let (mut fade_gain, mut fade) = if self.is_virtual {
+            (self.audible_fade_gain, None)
+        } else {
+            (self.fade_gain, self.fade)
+        };
+        let _ = fade_gain.visit("FadeGain", visitor);
+        let _ = fade.visit("Fade", visitor);
+        if visitor.is_reading() {
+            self.fade_gain = fade_gain;
+            self.fade = fade;
+        }
         self.resampling_multiplier
             .visit("ResamplingMultiplier", visitor)?;
         self.status.visit("Status", visitor)?;
         self.play_once.visit("PlayOnce", visitor)?;
+        let _ = self.priority.visit("Priority", visitor);
 
         visitor.leave_region()
     }
@@ -430,8 +840,10 @@ pub struct GenericSourceBuilder {
     pitch: f32,
     panning: f32,
     looping: bool,
+    loop_region: Option<LoopRegion>,
     status: Status,
     play_once: bool,
+    priority: i32,
 }
 
 impl GenericSourceBuilder {
@@ -443,8 +855,10 @@ impl GenericSourceBuilder {
             pitch: 1.0,
             panning: 0.0,
             looping: false,
+            loop_region: None,
             status: Status::Stopped,
             play_once: false,
+            priority: 0,
         }
     }
 
@@ -472,6 +886,12 @@ impl GenericSourceBuilder {
         self
     }
 
+    /// See `set_loop_region` of GenericSource
+    pub fn with_loop_region(mut self, start: usize, end: usize) -> Self {
+        self.loop_region = Some(LoopRegion { start, end });
+        self
+    }
+
     /// Sets desired status of source.
     pub fn with_status(mut self, status: Status) -> Self {
         self.status = status;
@@ -484,31 +904,46 @@ impl GenericSourceBuilder {
         self
     }
 
-    /// Creates new instance of generic sound source. May fail if buffer is invalid.
+    /// See `set_priority` of GenericSource
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Creates new instance of generic sound source. May fail if buffer or loop region is invalid.
     pub fn build(self) -> Result<GenericSource, SoundError> {
         let device_sample_rate = f64::from(crate::context::SAMPLE_RATE);
-        let mut locked_buffer = self.buffer.lock()?;
-        if let SoundBuffer::Streaming(ref mut streaming) = *locked_buffer {
+        let locked_buffer = self.buffer.lock()?;
+        if let SoundBuffer::Streaming(ref streaming) = *locked_buffer {
             if streaming.use_count != 0 {
                 return Err(SoundError::StreamingBufferAlreadyInUse);
             }
-            streaming.use_count += 1;
         }
         let sample_rate = locked_buffer.sample_rate() as f64;
         let channel_count = locked_buffer.channel_count() as f64;
         let resampling_multiplier = sample_rate / device_sample_rate * channel_count;
-        Ok(GenericSource {
+        drop(locked_buffer);
+        let mut source = GenericSource {
             resampling_multiplier,
             buffer: Some(self.buffer.clone()),
             gain: self.gain,
             pitch: self.pitch as f64,
             play_once: self.play_once,
+            priority: self.priority,
             panning: self.panning,
             status: self.status,
             looping: self.looping,
             frame_samples: Default::default(),
             ..Default::default()
-        })
+        };
+        if let Some(region) = self.loop_region {
+            source.set_loop_region(region.start, region.end)?;
+        }
+        // Buffer is marked as used only when source is valid, otherwise it will stay in use forever.
+        if let SoundBuffer::Streaming(ref mut streaming) = *self.buffer.lock()? {
+            streaming.use_count += 1;
+        }
+        Ok(source)
     }
 
     /// Creates new instance of sound source of `Generic` variant.
@@ -516,3 +951,207 @@ impl GenericSourceBuilder {
         Ok(SoundSource::Generic(self.build()?))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        buffer::{DataSource, SoundBuffer},
+        context::SAMPLE_RATE,
+        error::SoundError,
+        source::{
+            generic::{GenericSource, GenericSourceBuilder},
+            Status,
+        },
+    };
+    use std::time::Duration;
+
+    // Looping mono source of constant 1.0 signal, so rendered samples are equal to fade gain.
+    fn make_source() -> GenericSource {
+        let buffer = SoundBuffer::new_generic(DataSource::Raw {
+            sample_rate: SAMPLE_RATE as usize,
+            channel_count: 1,
+            samples: vec![1.0; 64],
+        })
+        .unwrap();
+        GenericSourceBuilder::new(buffer)
+            .with_looping(true)
+            .with_status(Status::Playing)
+            .build()
+            .unwrap()
+    }
+
+    fn samples_duration(count: u32) -> Duration {
+        Duration::from_secs_f64(count as f64 / SAMPLE_RATE as f64)
+    }
+
+    #[test]
+    fn test_fade_in() {
+        let mut source = make_source();
+        source.fade(0.0, Duration::from_secs(0));
+        assert_eq!(source.fade_gain(), 0.0);
+
+        source.fade(1.0, samples_duration(10));
+        source.render(20);
+
+        let samples = source.frame_samples();
+        for pair in samples.windows(2) {
+            assert!(pair[1].0 >= pair[0].0);
+        }
+        assert!((samples[0].0 - 0.1).abs() < 1.0e-6);
+        assert_eq!(samples[9].0, 1.0);
+        assert_eq!(samples[19].0, 1.0);
+        assert!(!source.is_fading());
+        assert_eq!(source.status(), Status::Playing);
+    }
+
+    #[test]
+    fn test_fade_out() {
+        let mut source = make_source();
+        source.fade(0.0, samples_duration(10));
+        source.render(20);
+
+        let samples = source.frame_samples();
+        for pair in samples.windows(2) {
+            assert!(pair[1].0 <= pair[0].0);
+        }
+        assert_eq!(samples[9].0, 0.0);
+        assert_eq!(samples[19].0, 0.0);
+        assert_eq!(source.fade_gain(), 0.0);
+        assert!(!source.is_fading());
+        // Plain fade does not stop the source.
+        assert_eq!(source.status(), Status::Playing);
+    }
+
+    #[test]
+    fn test_stop_with_fade() {
+        let mut source = make_source();
+        source.stop_with_fade(samples_duration(10));
+        source.render(20);
+
+        let samples = source.frame_samples();
+        assert!(samples[0].0 > 0.0);
+        // Last sample of the fade must be silent, otherwise there is a click.
+        assert_eq!(samples[9].0, 0.0);
+        assert!(samples[10..].iter().all(|&(l, r)| l == 0.0 && r == 0.0));
+        assert_eq!(source.status(), Status::Stopped);
+        assert!(!source.is_fading());
+        // Gain is restored, so the source can be played again at normal volume.
+        assert_eq!(source.fade_gain(), 1.0);
+        assert_eq!(source.playback_time(), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn test_stop_with_zero_length_fade() {
+        let mut source = make_source();
+        source.stop_with_fade(Duration::from_secs(0));
+        assert_eq!(source.status(), Status::Stopped);
+        assert_eq!(source.fade_gain(), 1.0);
+        assert!(!source.is_fading());
+    }
+
+    // Mono buffer where value of each sample is equal to its index.
+    fn make_ramp_buffer(len: usize) -> std::sync::Arc<std::sync::Mutex<SoundBuffer>> {
+        SoundBuffer::new_generic(DataSource::Raw {
+            sample_rate: SAMPLE_RATE as usize,
+            channel_count: 1,
+            samples: (0..len).map(|i| i as f32).collect(),
+        })
+        .unwrap()
+    }
+
+    fn is_invalid_region(result: Result<(), SoundError>) -> bool {
+        matches!(result, Err(SoundError::InvalidLoopRegion))
+    }
+
+    #[test]
+    fn test_invalid_loop_region() {
+        let mut source = GenericSourceBuilder::new(make_ramp_buffer(16))
+            .build()
+            .unwrap();
+        assert!(is_invalid_region(source.set_loop_region(4, 4)));
+        assert!(is_invalid_region(source.set_loop_region(8, 4)));
+        assert!(is_invalid_region(source.set_loop_region(4, 17)));
+        assert_eq!(source.loop_region(), None);
+
+        assert!(source.set_loop_region(4, 16).is_ok());
+        assert!(is_invalid_region(source.set_loop_region_time(
+            Duration::from_secs(0),
+            Duration::from_secs(1)
+        )));
+
+        // Invalid region must not leave streaming buffer marked as used.
+        let buffer = SoundBuffer::new_streaming(
+            DataSource::from_file("examples/data/waterfall.ogg").unwrap(),
+        )
+        .unwrap();
+        assert!(matches!(
+            GenericSourceBuilder::new(buffer.clone())
+                .with_loop_region(10, 5)
+                .build(),
+            Err(SoundError::InvalidLoopRegion)
+        ));
+        assert!(GenericSourceBuilder::new(buffer).build().is_ok());
+    }
+
+    #[test]
+    fn test_loop_region_wrap() {
+        let mut source = GenericSourceBuilder::new(make_ramp_buffer(16))
+            .with_looping(true)
+            .with_loop_region(4, 8)
+            .with_status(Status::Playing)
+            .build()
+            .unwrap();
+        source.render(16);
+        let samples = source
+            .frame_samples()
+            .iter()
+            .map(|s| s.0)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            samples,
+            [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 4.0, 5.0, 6.0, 7.0, 4.0, 5.0, 6.0, 7.0, 4.0]
+        );
+
+        // Without looping region is ignored.
+        source.stop().unwrap();
+        source.set_looping(false);
+        source.play();
+        source.render(16);
+        assert_eq!(source.frame_samples()[14].0, 15.0);
+        assert_eq!(source.status(), Status::Stopped);
+    }
+
+    #[test]
+    fn test_streaming_loop_region_wrap() {
+        // Streaming buffer must produce exactly the same samples as generic buffer, decoder
+        // is moved to the start of region on every wrap.
+        let region = (50_000, 53_000);
+        let mut sources = [
+            SoundBuffer::new_generic(DataSource::from_file("examples/data/waterfall.ogg").unwrap()),
+            SoundBuffer::new_streaming(
+                DataSource::from_file("examples/data/waterfall.ogg").unwrap(),
+            ),
+        ]
+        .iter()
+        .map(|buffer| {
+            GenericSourceBuilder::new(buffer.as_ref().unwrap().clone())
+                .with_looping(true)
+                .with_loop_region(region.0, region.1)
+                .with_status(Status::Playing)
+                .build()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+        // Buffer has half of device sample rate, so each frame is played twice.
+        for _ in 0..4 {
+            for source in sources.iter_mut() {
+                source.render(30_000);
+            }
+            assert_eq!(sources[0].frame_samples(), sources[1].frame_samples());
+        }
+        // Position of stereo source is index of sample, not frame.
+        let position = sources[1].playback_pos / 2.0;
+        assert!(position >= region.0 as f64 && position < region.1 as f64);
+    }
+}
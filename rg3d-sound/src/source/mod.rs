@@ -6,7 +6,11 @@
 //! etc.), control volume, pitch, panning and other. Exact behaviour defined by a variant of sound buffer (generic or
 //! spatial). See docs at those modules for more info.
 
-use crate::source::{generic::GenericSource, spatial::SpatialSource};
+use crate::{
+    context::DistanceModel,
+    listener::Listener,
+    source::{generic::GenericSource, spatial::SpatialSource},
+};
 use rg3d_core::visitor::{Visit, VisitError, VisitResult, Visitor};
 use std::ops::{Deref, DerefMut};
 
@@ -57,6 +61,19 @@ impl SoundSource {
             SoundSource::Spatial(ref mut spatial) => spatial,
         }
     }
+
+    /// Returns gain of the source as it is heard by listener. It is used to find least audible
+    /// sources when there are too many playing sources.
+    pub(in crate) fn effective_gain(&self, listener: &Listener, distance_model: DistanceModel) -> f32 {
+        let gain = self.gain() * self.audible_fade_gain();
+        match self {
+            SoundSource::Generic(_) => gain,
+            SoundSource::Spatial(spatial) => {
+                gain * spatial.get_distance_gain(listener, distance_model)
+                    * spatial.obstruction().gain
+            }
+        }
+    }
 }
 
 impl Deref for SoundSource {
@@ -27,14 +27,41 @@
 //! ```
 
 use crate::{
-    context::DistanceModel,
+    context::{DistanceModel, SAMPLE_RATE},
+    dsp::filters::OnePole,
     listener::Listener,
     source::{generic::GenericSource, SoundSource},
 };
 use rg3d_core::algebra::Vector3;
+use rg3d_core::math::{self, curve::Curve};
 use rg3d_core::visitor::{Visit, VisitResult, Visitor};
 use std::ops::{Deref, DerefMut};
 
+/// Describes how geometry between a spatial source and the listener affects the sound. Obstructed
+/// sound is quieter and muffled - its high frequencies are cut off by a low-pass filter.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Obstruction {
+    /// Gain multiplier in `[0; 1]` range, 1.0 means that volume is not reduced.
+    pub gain: f32,
+    /// Cutoff frequency (in Hz) of low-pass filter. Frequencies above Nyquist frequency (half of
+    /// the sample rate) mean that sound is not filtered at all.
+    pub cutoff_frequency: f32,
+}
+
+impl Obstruction {
+    /// Obstruction which does not affect the sound.
+    pub const NONE: Self = Self {
+        gain: 1.0,
+        cutoff_frequency: std::f32::MAX,
+    };
+}
+
+impl Default for Obstruction {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
 /// See module docs.
 pub struct SpatialSource {
     pub(in crate) generic: GenericSource,
@@ -47,6 +74,11 @@ pub struct SpatialSource {
     pub(in crate) prev_right_samples: Vec<f32>,
     pub(in crate) prev_sampling_vector: Vector3<f32>,
     pub(in crate) prev_distance_gain: Option<f32>,
+    obstruction: Obstruction,
+    prev_obstruction_gain: Option<f32>,
+    obstruction_filters: (OnePole, OnePole),
+    spread_curve: Option<Curve>,
+    pub(in crate) prev_spread: Option<f32>,
 }
 
 impl SpatialSource {
@@ -99,6 +131,51 @@ impl SpatialSource {
         self.max_distance
     }
 
+    /// Sets curve that defines stereo spread of the source depending on distance to listener.
+    /// Spread is a value in `[0; 1]` range: 0 means that source is a point - stereo channels
+    /// are mixed together and sound is fully panned in direction of the source, 1 means that
+    /// source surrounds listener - stereo image is preserved and there is no panning. Usually
+    /// spread should be close to 1 near the source and fall to 0 with distance, this makes
+    /// close sources feel wide and distant ones - narrow, which improves perception of depth.
+    /// Spread is not used by HRTF renderer. Pass `None` to disable spread (default), in this
+    /// case source is panned as a point without mixing of stereo channels.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rg3d_sound::source::spatial::SpatialSource;
+    /// use rg3d_core::math::curve::{Curve, CurveKey, CurveKeyKind};
+    ///
+    /// fn make_wide(source: &mut SpatialSource) {
+    ///     // Fully wide up to 1 meter, then narrows linearly to a point at 10 meters.
+    ///     source.set_spread_curve(Some(Curve::from_keys(vec![
+    ///         CurveKey::new(1.0, 1.0, CurveKeyKind::Linear),
+    ///         CurveKey::new(10.0, 0.0, CurveKeyKind::Linear),
+    ///     ])));
+    /// }
+    /// ```
+    pub fn set_spread_curve(&mut self, curve: Option<Curve>) -> &mut Self {
+        self.spread_curve = curve;
+        self
+    }
+
+    /// Returns current spread curve.
+    pub fn spread_curve(&self) -> Option<&Curve> {
+        self.spread_curve.as_ref()
+    }
+
+    /// Sets obstruction of the source. Usually obstruction is calculated by casting a ray from
+    /// source to listener and checking what is hit, see `Context::set_obstruction_provider`.
+    pub fn set_obstruction(&mut self, obstruction: Obstruction) -> &mut Self {
+        self.obstruction = obstruction;
+        self
+    }
+
+    /// Returns current obstruction of the source.
+    pub fn obstruction(&self) -> Obstruction {
+        self.obstruction
+    }
+
     /// Returns shared reference to inner generic source.
     pub fn generic(&self) -> &GenericSource {
         &self.generic
@@ -134,6 +211,49 @@ impl SpatialSource {
         }
     }
 
+    /// Applies obstruction to samples of current frame.
+    pub(in crate) fn apply_obstruction(&mut self) {
+        let gain = self.obstruction.gain.min(1.0).max(0.0);
+        let last_gain = *self.prev_obstruction_gain.get_or_insert(gain);
+        self.prev_obstruction_gain = Some(gain);
+
+        let fc = self.obstruction.cutoff_frequency / SAMPLE_RATE as f32;
+        let filtered = fc < 0.5;
+
+        if !filtered && gain == 1.0 && last_gain == 1.0 {
+            return;
+        }
+
+        let (left_filter, right_filter) = &mut self.obstruction_filters;
+        if filtered {
+            left_filter.set_fc(fc);
+            right_filter.set_fc(fc);
+        } else {
+            // Zero pole makes filter pass signal as is.
+            left_filter.set_pole(0.0);
+            right_filter.set_pole(0.0);
+        }
+
+        let step = 1.0 / self.generic.frame_samples.len() as f32;
+        let mut t = 0.0;
+        for (left, right) in self.generic.frame_samples.iter_mut() {
+            // Interpolate gain to prevent clicks when obstruction changes significantly.
+            let k = math::lerpf(last_gain, gain, t);
+            *left = k * left_filter.feed(*left);
+            *right = k * right_filter.feed(*right);
+            t += step;
+        }
+    }
+
+    /// Returns stereo spread for current distance to listener, or `None` if there is no
+    /// spread curve.
+    pub(in crate) fn get_spread(&self, listener: &Listener) -> Option<f32> {
+        self.spread_curve.as_ref().map(|curve| {
+            let distance = (self.position - listener.position()).norm();
+            curve.value_at(distance).min(1.0).max(0.0)
+        })
+    }
+
     pub(in crate) fn get_panning(&self, listener: &Listener) -> f32 {
         (self.position - listener.position())
             .try_normalize(std::f32::EPSILON)
@@ -174,6 +294,7 @@ impl Visit for SpatialSource {
 
         self.radius.visit("Radius", visitor)?;
         self.position.visit("Position", visitor)?;
+        let _ = self.spread_curve.visit("SpreadCurve", visitor);
 
         visitor.leave_region()
     }
@@ -191,6 +312,11 @@ impl Default for SpatialSource {
             prev_right_samples: Default::default(),
             prev_sampling_vector: Vector3::new(0.0, 0.0, 1.0),
             prev_distance_gain: None,
+            obstruction: Default::default(),
+            prev_obstruction_gain: None,
+            obstruction_filters: Default::default(),
+            spread_curve: None,
+            prev_spread: None,
         }
     }
 }
@@ -202,6 +328,7 @@ pub struct SpatialSourceBuilder {
     position: Vector3<f32>,
     max_distance: f32,
     rolloff_factor: f32,
+    spread_curve: Option<Curve>,
 }
 
 impl SpatialSourceBuilder {
@@ -214,6 +341,7 @@ impl SpatialSourceBuilder {
             position: Vector3::new(0.0, 0.0, 0.0),
             max_distance: std::f32::MAX,
             rolloff_factor: 1.0,
+            spread_curve: None,
         }
     }
 
@@ -241,6 +369,12 @@ impl SpatialSourceBuilder {
         self
     }
 
+    /// See `set_spread_curve` of SpatialSource.
+    pub fn with_spread_curve(mut self, curve: Curve) -> Self {
+        self.spread_curve = Some(curve);
+        self
+    }
+
     /// Creates new instance of spatial sound source.
     pub fn build(self) -> SpatialSource {
         SpatialSource {
@@ -249,6 +383,7 @@ impl SpatialSourceBuilder {
             position: self.position,
             max_distance: self.max_distance,
             rolloff_factor: self.rolloff_factor,
+            spread_curve: self.spread_curve,
             prev_left_samples: Default::default(),
             prev_right_samples: Default::default(),
             ..Default::default()
@@ -260,3 +395,131 @@ impl SpatialSourceBuilder {
         SoundSource::Spatial(self.build())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        buffer::{DataSource, SoundBuffer},
+        context::DistanceModel,
+        listener::Listener,
+        renderer::render_source_default,
+        source::{
+            generic::GenericSourceBuilder,
+            spatial::{SpatialSource, SpatialSourceBuilder},
+            SoundSource, Status,
+        },
+    };
+    use rg3d_core::{
+        algebra::Vector3,
+        math::curve::{Curve, CurveKey, CurveKeyKind},
+        visitor::{Visit, Visitor},
+    };
+
+    fn make_source(position: Vector3<f32>, spread: Option<f32>) -> SpatialSource {
+        // Left and right channels are in opposite phase, so mixing them gives silence.
+        let buffer = SoundBuffer::new_generic(DataSource::Raw {
+            sample_rate: 44100,
+            channel_count: 2,
+            samples: vec![1.0, -1.0].repeat(1000),
+        })
+        .unwrap();
+        let mut builder = SpatialSourceBuilder::new(
+            GenericSourceBuilder::new(buffer)
+                .with_status(Status::Playing)
+                .build()
+                .unwrap(),
+        )
+        .with_position(position);
+        if let Some(spread) = spread {
+            builder = builder.with_spread_curve(Curve::from_keys(vec![CurveKey::new(
+                0.0,
+                spread,
+                CurveKeyKind::Constant,
+            )]));
+        }
+        builder.build()
+    }
+
+    fn render(source: SpatialSource) -> (f32, f32) {
+        let mut source = SoundSource::Spatial(source);
+        let mut buf = vec![(0.0, 0.0); 64];
+        source.render(buf.len());
+        render_source_default(&mut source, &Listener::new(), DistanceModel::None, &mut buf);
+        buf[32]
+    }
+
+    #[test]
+    fn test_panning() {
+        let listener = Listener::new();
+        let panning = |position| make_source(position, None).get_panning(&listener);
+        assert_eq!(panning(Vector3::new(5.0, 0.0, 0.0)), 1.0);
+        assert_eq!(panning(Vector3::new(-5.0, 0.0, 0.0)), -1.0);
+        assert_eq!(panning(Vector3::new(0.0, 0.0, 5.0)), 0.0);
+        // Source at listener position is not panned.
+        assert_eq!(panning(Vector3::new(0.0, 0.0, 0.0)), 0.0);
+
+        // Fully panned source is heard only by one ear.
+        let (left, right) = render(make_source(Vector3::new(5.0, 0.0, 0.0), None));
+        assert_eq!((left, right), (2.0, 0.0));
+    }
+
+    #[test]
+    fn test_spread() {
+        let listener = Listener::new();
+        let mut source = make_source(Vector3::new(0.0, 0.0, 0.0), None);
+        assert_eq!(source.get_spread(&listener), None);
+
+        source.set_spread_curve(Some(Curve::from_keys(vec![
+            CurveKey::new(1.0, 2.0, CurveKeyKind::Linear),
+            CurveKey::new(3.0, 1.0, CurveKeyKind::Linear),
+            CurveKey::new(5.0, 0.0, CurveKeyKind::Linear),
+        ])));
+        let mut spread_at = |distance| {
+            source.set_position(&Vector3::new(0.0, 0.0, distance));
+            source.get_spread(&listener).unwrap()
+        };
+        // Spread is clamped to 0..1 range.
+        assert_eq!(spread_at(0.5), 1.0);
+        assert_eq!(spread_at(4.0), 0.5);
+        assert_eq!(spread_at(10.0), 0.0);
+    }
+
+    #[test]
+    fn test_spread_render() {
+        // Source without spread keeps stereo image.
+        let front = Vector3::new(0.0, 0.0, 5.0);
+        assert_eq!(render(make_source(front, None)), (1.0, -1.0));
+        assert_eq!(render(make_source(front, Some(1.0))), (1.0, -1.0));
+        // Zero spread mixes channels together.
+        assert_eq!(render(make_source(front, Some(0.0))), (0.0, 0.0));
+        let (left, right) = render(make_source(front, Some(0.5)));
+        assert!((left - 0.5).abs() < 1.0e-6 && (right + 0.5).abs() < 1.0e-6);
+
+        // Wide source is not panned.
+        let side = Vector3::new(5.0, 0.0, 0.0);
+        assert_eq!(render(make_source(side, Some(1.0))), (1.0, -1.0));
+    }
+
+    #[test]
+    fn test_visit_spread_curve() {
+        let curve = Curve::from_keys(vec![
+            CurveKey::new(1.0, 1.0, CurveKeyKind::Linear),
+            CurveKey::new(10.0, 0.0, CurveKeyKind::Linear),
+        ]);
+        let mut source = make_source(Vector3::new(1.0, 2.0, 3.0), None);
+        source.set_spread_curve(Some(curve.clone()));
+
+        let path = std::env::temp_dir().join("rg3d_sound_spread_curve_test.bin");
+        let mut visitor = Visitor::new();
+        source.visit("Source", &mut visitor).unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        let mut visitor = Visitor::load_binary(&path).unwrap();
+        let mut loaded = SpatialSource::default();
+        loaded.visit("Source", &mut visitor).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(loaded.position(), Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(loaded.spread_curve(), Some(&curve));
+    }
+}
@@ -0,0 +1,197 @@
+//! Helpers for bidirectional text layout: contextual shaping of Arabic letters and mirroring
+//! of paired characters in right-to-left runs. Reordering itself is done by `unicode-bidi`.
+
+use crate::TextDirection;
+use unicode_bidi::Level;
+
+impl TextDirection {
+    pub(in crate) fn level(self) -> Option<Level> {
+        match self {
+            TextDirection::Auto => None,
+            TextDirection::LeftToRight => Some(Level::ltr()),
+            TextDirection::RightToLeft => Some(Level::rtl()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Joining {
+    /// Does not join with neighbours (spaces, latin letters, etc.)
+    None,
+    /// Joins only with previous (in logical order) letter.
+    Right,
+    /// Joins with both neighbours.
+    Dual,
+    /// Joins with both neighbours, but has no contextual forms (tatweel).
+    Causing,
+    /// Skipped when neighbours are searched (diacritics).
+    Transparent,
+}
+
+// Arabic letters U+0621..U+064A in order of their contextual forms in Arabic
+// Presentation Forms-B block which starts at U+FE80. Letters that join only with
+// previous letter have two forms (isolated, final), dual-joining letters have four
+// forms (isolated, final, initial, medial), hamza has only isolated form.
+const ARABIC_FORMS_BASE: u32 = 0xFE80;
+const ARABIC_LETTERS: [(u32, Joining); 42] = [
+    (0x0621, Joining::None),
+    (0x0622, Joining::Right),
+    (0x0623, Joining::Right),
+    (0x0624, Joining::Right),
+    (0x0625, Joining::Right),
+    (0x0626, Joining::Dual),
+    (0x0627, Joining::Right),
+    (0x0628, Joining::Dual),
+    (0x0629, Joining::Right),
+    (0x062A, Joining::Dual),
+    (0x062B, Joining::Dual),
+    (0x062C, Joining::Dual),
+    (0x062D, Joining::Dual),
+    (0x062E, Joining::Dual),
+    (0x062F, Joining::Right),
+    (0x0630, Joining::Right),
+    (0x0631, Joining::Right),
+    (0x0632, Joining::Right),
+    (0x0633, Joining::Dual),
+    (0x0634, Joining::Dual),
+    (0x0635, Joining::Dual),
+    (0x0636, Joining::Dual),
+    (0x0637, Joining::Dual),
+    (0x0638, Joining::Dual),
+    (0x0639, Joining::Dual),
+    (0x063A, Joining::Dual),
+    (0x0641, Joining::Dual),
+    (0x0642, Joining::Dual),
+    (0x0643, Joining::Dual),
+    (0x0644, Joining::Dual),
+    (0x0645, Joining::Dual),
+    (0x0646, Joining::Dual),
+    (0x0647, Joining::Dual),
+    (0x0648, Joining::Right),
+    (0x0649, Joining::Right),
+    (0x064A, Joining::Dual),
+    // Letters below have no forms in Presentation Forms-B, they are listed here only
+    // to be able to join with neighbours.
+    (0x0640, Joining::Causing),
+    (0x063B, Joining::None),
+    (0x063C, Joining::None),
+    (0x063D, Joining::None),
+    (0x063E, Joining::None),
+    (0x063F, Joining::None),
+];
+
+fn form_count(joining: Joining) -> u32 {
+    match joining {
+        Joining::None => 1,
+        Joining::Right => 2,
+        Joining::Dual => 4,
+        Joining::Causing | Joining::Transparent => 0,
+    }
+}
+
+fn joining(code: u32) -> Joining {
+    match code {
+        // Harakat, superscript alef and other combining marks.
+        0x064B..=0x065F | 0x0670 | 0x06D6..=0x06ED => Joining::Transparent,
+        _ => ARABIC_LETTERS
+            .iter()
+            .find(|(letter, _)| *letter == code)
+            .map_or(Joining::None, |(_, joining)| *joining),
+    }
+}
+
+/// Returns first contextual (isolated) form of a letter in Presentation Forms-B block.
+fn first_form(code: u32) -> Option<u32> {
+    let mut form = ARABIC_FORMS_BASE;
+    for &(letter, joining) in ARABIC_LETTERS.iter() {
+        if letter == code {
+            return if form_count(joining) > 0 && letter <= 0x064A {
+                Some(form)
+            } else {
+                None
+            };
+        }
+        form += form_count(joining);
+    }
+    None
+}
+
+/// Replaces Arabic letters with their contextual forms (isolated, initial, medial, final)
+/// depending on neighbour letters. Output has same length as input so indices of
+/// characters are preserved. Ligatures are not supported.
+pub(in crate) fn shape(text: &[u32], shaped: &mut Vec<u32>) {
+    let neighbour = |range: &mut dyn Iterator<Item = usize>| {
+        range
+            .map(|i| joining(text[i]))
+            .find(|joining| *joining != Joining::Transparent)
+            .unwrap_or(Joining::None)
+    };
+
+    shaped.clear();
+    shaped.extend(text.iter().enumerate().map(|(i, &code)| {
+        let form = match first_form(code) {
+            Some(form) => form,
+            None => return code,
+        };
+        let current = joining(code);
+        let prev = neighbour(&mut (0..i).rev());
+        let next = neighbour(&mut (i + 1..text.len()));
+
+        let joins_prev = prev == Joining::Dual || prev == Joining::Causing;
+        let joins_next = current == Joining::Dual && next != Joining::None;
+
+        match (joins_prev, joins_next) {
+            (false, false) => form,
+            (true, false) if current != Joining::None => form + 1,
+            (false, true) => form + 2,
+            (true, true) => form + 3,
+            _ => form,
+        }
+    }));
+}
+
+// Ranges of characters of right-to-left scripts and explicit right-to-left marks.
+const RTL_RANGES: [(u32, u32); 10] = [
+    // Hebrew, Arabic, Syriac, Thaana, NKo, Samaritan, Mandaic, Arabic Extended.
+    (0x0590, 0x08FF),
+    // Hebrew and Arabic presentation forms.
+    (0xFB1D, 0xFDFF),
+    (0xFE70, 0xFEFF),
+    // Historic right-to-left scripts, Adlam, Arabic mathematical symbols.
+    (0x10800, 0x10FFF),
+    (0x1E800, 0x1EFFF),
+    // Arabic letter mark, right-to-left mark, embedding, override and isolate.
+    (0x061C, 0x061C),
+    (0x200F, 0x200F),
+    (0x202B, 0x202B),
+    (0x202E, 0x202E),
+    (0x2067, 0x2067),
+];
+
+/// Checks whether text contains characters of right-to-left scripts or explicit right-to-left
+/// marks. Text without them is laid out from left to right without bidirectional analysis.
+pub(in crate) fn has_rtl(text: &[u32]) -> bool {
+    text.iter().any(|&code| {
+        RTL_RANGES
+            .iter()
+            .any(|&(first, last)| code >= first && code <= last)
+    })
+}
+
+/// Returns mirrored character for paired characters, it is used for characters in
+/// right-to-left runs.
+pub(in crate) fn mirror(code: u32) -> u32 {
+    match std::char::from_u32(code) {
+        Some('(') => ')' as u32,
+        Some(')') => '(' as u32,
+        Some('[') => ']' as u32,
+        Some(']') => '[' as u32,
+        Some('{') => '}' as u32,
+        Some('}') => '{' as u32,
+        Some('<') => '>' as u32,
+        Some('>') => '<' as u32,
+        Some('«') => '»' as u32,
+        Some('»') => '«' as u32,
+        _ => code,
+    }
+}
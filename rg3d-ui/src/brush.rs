@@ -20,3 +20,57 @@ pub enum Brush {
         stops: Vec<GradientPoint>,
     },
 }
+
+// Desaturates color and makes it half-transparent.
+fn dim_color(color: Color) -> Color {
+    let gray =
+        ((u32::from(color.r) * 30 + u32::from(color.g) * 59 + u32::from(color.b) * 11) / 100) as u8;
+    let dimmed = color.lerp(Color::from_rgba(gray, gray, gray, color.a), 0.6);
+    Color::from_rgba(dimmed.r, dimmed.g, dimmed.b, color.a / 2)
+}
+
+impl Brush {
+    /// Returns desaturated half-transparent copy of the brush, it is used to draw disabled
+    /// widgets.
+    pub fn dimmed(&self) -> Brush {
+        self.map_colors(dim_color)
+    }
+
+    /// Returns copy of the brush with alpha of every color multiplied by given opacity
+    /// (in `[0; 1]` range), it is used to draw semi-transparent widgets.
+    pub fn with_opacity(&self, opacity: f32) -> Brush {
+        self.map_colors(|color| {
+            Color::from_rgba(
+                color.r,
+                color.g,
+                color.b,
+                (f32::from(color.a) * opacity) as u8,
+            )
+        })
+    }
+
+    fn map_colors<F: Fn(Color) -> Color>(&self, func: F) -> Brush {
+        let map_stops = |stops: &[GradientPoint]| {
+            stops
+                .iter()
+                .map(|point| GradientPoint {
+                    stop: point.stop,
+                    color: func(point.color),
+                })
+                .collect()
+        };
+
+        match self {
+            Brush::Solid(color) => Brush::Solid(func(*color)),
+            Brush::LinearGradient { from, to, stops } => Brush::LinearGradient {
+                from: *from,
+                to: *to,
+                stops: map_stops(stops),
+            },
+            Brush::RadialGradient { center, stops } => Brush::RadialGradient {
+                center: *center,
+                stops: map_stops(stops),
+            },
+        }
+    }
+}
@@ -1,20 +1,89 @@
+//! Canvas is a panel with absolute positioning of children.
+//!
+//! # Overview
+//!
+//! Every child of a canvas is placed at its desired position (see `WidgetBuilder::with_desired_position`
+//! and `WidgetMessage::DesiredPosition`) and has its desired size, other children do not affect it.
+//! It is useful for HUDs, floating windows, etc.
+//!
+//! # Anchors
+//!
+//! By default desired position of a child is relative to top-left corner of the canvas. A child can
+//! be anchored to another edge, corner or to center of the canvas, then its desired position is an
+//! offset of the same point of the child from the same point of the canvas. Directions of axes are
+//! not changed, so a child anchored to bottom-right corner with position `(-10, -10)` will always be
+//! 10 pixels away from right and bottom edges, regardless of screen resolution.
+//!
+//! Children with widget anchors (see `Anchors`) are arranged relative to whole area of the canvas,
+//! their desired position and canvas anchor are ignored.
+
 use crate::{
     core::{algebra::Vector2, math::Rect, pool::Handle, scope_profile},
-    message::{MessageData, UiMessage},
+    message::{
+        CanvasMessage, MessageData, MessageDirection, UiMessage, UiMessageData, WidgetMessage,
+    },
     widget::{Widget, WidgetBuilder},
-    BuildContext, Control, UINode, UserInterface,
+    BuildContext, Control, NodeHandleMapping, UINode, UserInterface,
+};
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
 };
-use std::ops::{Deref, DerefMut};
+
+/// Point of a canvas to which position of a child is relative. See module docs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum CanvasAnchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl CanvasAnchor {
+    /// Returns position of the anchor relative to size of a rectangle, `(0, 0)` is top-left
+    /// corner and `(1, 1)` is bottom-right corner.
+    pub fn factor(self) -> Vector2<f32> {
+        match self {
+            CanvasAnchor::TopLeft => Vector2::new(0.0, 0.0),
+            CanvasAnchor::Top => Vector2::new(0.5, 0.0),
+            CanvasAnchor::TopRight => Vector2::new(1.0, 0.0),
+            CanvasAnchor::Left => Vector2::new(0.0, 0.5),
+            CanvasAnchor::Center => Vector2::new(0.5, 0.5),
+            CanvasAnchor::Right => Vector2::new(1.0, 0.5),
+            CanvasAnchor::BottomLeft => Vector2::new(0.0, 1.0),
+            CanvasAnchor::Bottom => Vector2::new(0.5, 1.0),
+            CanvasAnchor::BottomRight => Vector2::new(1.0, 1.0),
+        }
+    }
+}
 
 /// Allows user to directly set position and size of a node
 #[derive(Clone)]
 pub struct Canvas<M: MessageData, C: Control<M, C>> {
     widget: Widget<M, C>,
+    anchors: HashMap<Handle<UINode<M, C>>, CanvasAnchor>,
 }
 
 crate::define_widget_deref!(Canvas<M, C>);
 
 impl<M: MessageData, C: Control<M, C>> Control<M, C> for Canvas<M, C> {
+    fn resolve(&mut self, node_map: &NodeHandleMapping<M, C>) {
+        self.anchors = self
+            .anchors
+            .drain()
+            .map(|(mut child, anchor)| {
+                node_map.resolve(&mut child);
+                (child, anchor)
+            })
+            .collect();
+    }
+
     fn measure_override(
         &self,
         ui: &UserInterface<M, C>,
@@ -36,14 +105,18 @@ impl<M: MessageData, C: Control<M, C>> Control<M, C> for Canvas<M, C> {
 
         for child_handle in self.widget.children() {
             let child = ui.nodes.borrow(*child_handle);
+            if child.anchors().is_some() {
+                // Widget anchors are relative to whole canvas.
+                child.arrange(ui, &Rect::new(0.0, 0.0, final_size.x, final_size.y));
+                continue;
+            }
+            let factor = self.anchor(*child_handle).factor();
+            let desired_size = child.desired_size();
+            let position =
+                child.desired_local_position() + (final_size - desired_size).component_mul(&factor);
             child.arrange(
                 ui,
-                &Rect::new(
-                    child.desired_local_position().x,
-                    child.desired_local_position().y,
-                    child.desired_size().x,
-                    child.desired_size().y,
-                ),
+                &Rect::new(position.x, position.y, desired_size.x, desired_size.y),
             );
         }
 
@@ -56,28 +129,184 @@ impl<M: MessageData, C: Control<M, C>> Control<M, C> for Canvas<M, C> {
         message: &mut UiMessage<M, C>,
     ) {
         self.widget.handle_routed_message(ui, message);
+
+        match message.data() {
+            UiMessageData::Canvas(CanvasMessage::Anchor { child, anchor }) => {
+                if message.destination() == self.handle()
+                    && message.direction() == MessageDirection::ToWidget
+                    && self.anchor(*child) != *anchor
+                {
+                    self.anchors.insert(*child, *anchor);
+                    self.invalidate_arrange();
+                }
+            }
+            // Messages of children bubble up here before the children are unlinked, anchor
+            // of a child makes no sense for other parents.
+            UiMessageData::Widget(WidgetMessage::Unlink) => {
+                if message.direction() == MessageDirection::ToWidget {
+                    self.anchors.remove(&message.destination());
+                }
+            }
+            &UiMessageData::Widget(WidgetMessage::LinkWith(parent)) => {
+                if message.direction() == MessageDirection::ToWidget && parent != self.handle() {
+                    self.anchors.remove(&message.destination());
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn remove_ref(&mut self, handle: Handle<UINode<M, C>>) {
+        self.anchors.remove(&handle);
     }
 }
 
 impl<M: MessageData, C: Control<M, C>> Canvas<M, C> {
     pub fn new(widget: Widget<M, C>) -> Self {
-        Self { widget }
+        Self {
+            widget,
+            anchors: Default::default(),
+        }
+    }
+
+    /// Returns anchor of a child, children are anchored to top-left corner by default.
+    pub fn anchor(&self, child: Handle<UINode<M, C>>) -> CanvasAnchor {
+        self.anchors.get(&child).copied().unwrap_or_default()
     }
 }
 
 pub struct CanvasBuilder<M: MessageData, C: Control<M, C>> {
     widget_builder: WidgetBuilder<M, C>,
+    anchors: HashMap<Handle<UINode<M, C>>, CanvasAnchor>,
 }
 
 impl<M: MessageData, C: Control<M, C>> CanvasBuilder<M, C> {
     pub fn new(widget_builder: WidgetBuilder<M, C>) -> Self {
-        Self { widget_builder }
+        Self {
+            widget_builder,
+            anchors: Default::default(),
+        }
+    }
+
+    /// Anchors given child to a point of the canvas, see module docs.
+    pub fn with_anchor(mut self, child: Handle<UINode<M, C>>, anchor: CanvasAnchor) -> Self {
+        self.anchors.insert(child, anchor);
+        self
     }
 
     pub fn build(self, ui: &mut BuildContext<M, C>) -> Handle<UINode<M, C>> {
         let canvas = Canvas {
             widget: self.widget_builder.build(),
+            anchors: self.anchors,
         };
         ui.add_node(UINode::Canvas(canvas))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        border::BorderBuilder,
+        canvas::{CanvasAnchor, CanvasBuilder},
+        core::algebra::Vector2,
+        message::{CanvasMessage, MessageDirection, WidgetMessage},
+        node::StubNode,
+        widget::WidgetBuilder,
+        UINode, UserInterface,
+    };
+
+    #[test]
+    fn canvas_anchors() {
+        let screen_size = Vector2::new(1000.0, 1000.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+        let ctx = &mut ui.build_ctx();
+        let make_child = |ctx: &mut _, position| {
+            BorderBuilder::new(
+                WidgetBuilder::new()
+                    .with_width(20.0)
+                    .with_height(10.0)
+                    .with_desired_position(position),
+            )
+            .build(ctx)
+        };
+        let top_left = make_child(ctx, Vector2::new(5.0, 5.0));
+        let bottom_right = make_child(ctx, Vector2::new(-5.0, -5.0));
+        let canvas = CanvasBuilder::new(
+            WidgetBuilder::new()
+                .with_width(200.0)
+                .with_height(100.0)
+                .with_child(top_left)
+                .with_child(bottom_right),
+        )
+        .with_anchor(bottom_right, CanvasAnchor::BottomRight)
+        .build(ctx);
+        while let Some(_) = ui.poll_message() {}
+        ui.update(screen_size, 0.0);
+
+        assert_eq!(
+            ui.node(top_left).actual_local_position(),
+            Vector2::new(5.0, 5.0)
+        );
+        assert_eq!(
+            ui.node(bottom_right).actual_local_position(),
+            Vector2::new(175.0, 85.0)
+        );
+
+        ui.send_message(CanvasMessage::anchor(
+            canvas,
+            MessageDirection::ToWidget,
+            bottom_right,
+            CanvasAnchor::Center,
+        ));
+        while let Some(_) = ui.poll_message() {}
+        ui.update(screen_size, 0.0);
+
+        assert_eq!(
+            ui.node(bottom_right).actual_local_position(),
+            Vector2::new(85.0, 40.0)
+        );
+    }
+
+    #[test]
+    fn canvas_anchors_are_pruned() {
+        let screen_size = Vector2::new(1000.0, 1000.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+        let ctx = &mut ui.build_ctx();
+        let unlinked = BorderBuilder::new(WidgetBuilder::new()).build(ctx);
+        let moved = BorderBuilder::new(WidgetBuilder::new()).build(ctx);
+        let removed = BorderBuilder::new(WidgetBuilder::new()).build(ctx);
+        let kept = BorderBuilder::new(WidgetBuilder::new()).build(ctx);
+        let other = BorderBuilder::new(WidgetBuilder::new()).build(ctx);
+        let canvas = CanvasBuilder::new(
+            WidgetBuilder::new()
+                .with_child(unlinked)
+                .with_child(moved)
+                .with_child(removed)
+                .with_child(kept),
+        )
+        .with_anchor(unlinked, CanvasAnchor::Center)
+        .with_anchor(moved, CanvasAnchor::Center)
+        .with_anchor(removed, CanvasAnchor::Center)
+        .with_anchor(kept, CanvasAnchor::Center)
+        .build(ctx);
+        while let Some(_) = ui.poll_message() {}
+
+        ui.send_message(WidgetMessage::unlink(unlinked, MessageDirection::ToWidget));
+        ui.send_message(WidgetMessage::link(
+            moved,
+            MessageDirection::ToWidget,
+            other,
+        ));
+        ui.send_message(WidgetMessage::remove(removed, MessageDirection::ToWidget));
+        while let Some(_) = ui.poll_message() {}
+
+        if let UINode::Canvas(canvas) = ui.node(canvas) {
+            assert_eq!(canvas.anchors.len(), 1);
+            assert_eq!(canvas.anchor(kept), CanvasAnchor::Center);
+            assert_eq!(canvas.anchor(unlinked), CanvasAnchor::TopLeft);
+            assert_eq!(canvas.anchor(moved), CanvasAnchor::TopLeft);
+        } else {
+            unreachable!();
+        }
+    }
+}
@@ -0,0 +1,463 @@
+//! Undo/redo support for editors.
+//!
+//! Every change that user can undo is expressed as a command - an object that knows how to
+//! apply the change to some context (`Command::execute`) and how to take it back
+//! (`Command::revert`). Commands are executed through `CommandStack`, which remembers them and
+//! allows to move back and forth through the history. Stack is not tied to widgets at all -
+//! context is any type you want (document, scene, set of properties), UI only produces commands
+//! in response to messages and syncs itself with context after undo or redo.
+//!
+//! Several commands can be grouped into a transaction (`CommandStack::begin_transaction`), the
+//! whole group then is undone and redone in one step. It is useful when single user action
+//! consists of many small changes, for example dragging of a slider produces lots of value
+//! changes, but user expects to undo them at once.
+//!
+//! History has limited capacity, oldest commands are discarded when it is exceeded.
+//!
+//! # Example
+//!
+//! Property editor that allows to undo changes of a numeric property.
+//!
+//! ```
+//! use rg3d_ui::{
+//!     command::{Command, CommandStack},
+//!     core::pool::Handle,
+//!     message::{MessageDirection, NumericUpDownMessage, UiMessage, UiMessageData},
+//!     node::{StubNode, UINode},
+//!     UserInterface,
+//! };
+//!
+//! struct Properties {
+//!     speed: f32,
+//! }
+//!
+//! struct SetSpeedCommand {
+//!     value: f32,
+//! }
+//!
+//! impl SetSpeedCommand {
+//!     fn swap(&mut self, properties: &mut Properties) {
+//!         std::mem::swap(&mut self.value, &mut properties.speed);
+//!     }
+//! }
+//!
+//! impl Command<Properties> for SetSpeedCommand {
+//!     fn name(&self) -> String {
+//!         "Set Speed".to_owned()
+//!     }
+//!
+//!     fn execute(&mut self, properties: &mut Properties) {
+//!         self.swap(properties);
+//!     }
+//!
+//!     fn revert(&mut self, properties: &mut Properties) {
+//!         self.swap(properties);
+//!     }
+//! }
+//!
+//! struct PropertyEditor {
+//!     speed: Handle<UINode<(), StubNode>>,
+//!     properties: Properties,
+//!     commands: CommandStack<Properties>,
+//! }
+//!
+//! impl PropertyEditor {
+//!     fn handle_message(&mut self, message: &UiMessage<(), StubNode>) {
+//!         if let UiMessageData::NumericUpDown(NumericUpDownMessage::Value(value)) = message.data()
+//!         {
+//!             // Widget also reports values set by `sync`, they must not produce new commands.
+//!             if message.destination() == self.speed
+//!                 && message.direction() == MessageDirection::FromWidget
+//!                 && *value != self.properties.speed
+//!             {
+//!                 self.commands.do_command(
+//!                     Box::new(SetSpeedCommand { value: *value }),
+//!                     &mut self.properties,
+//!                 );
+//!             }
+//!         }
+//!     }
+//!
+//!     fn undo(&mut self, ui: &UserInterface<(), StubNode>) {
+//!         if self.commands.undo(&mut self.properties) {
+//!             self.sync(ui);
+//!         }
+//!     }
+//!
+//!     fn redo(&mut self, ui: &UserInterface<(), StubNode>) {
+//!         if self.commands.redo(&mut self.properties) {
+//!             self.sync(ui);
+//!         }
+//!     }
+//!
+//!     fn sync(&self, ui: &UserInterface<(), StubNode>) {
+//!         ui.send_message(NumericUpDownMessage::value(
+//!             self.speed,
+//!             MessageDirection::ToWidget,
+//!             self.properties.speed,
+//!         ));
+//!     }
+//! }
+//! ```
+
+/// Reversible change of a context, see module docs.
+pub trait Command<C> {
+    /// Returns human-readable name of the command, it can be used in menus ("Undo Set Speed")
+    /// or to show history.
+    fn name(&self) -> String;
+
+    /// Applies the change to the context. Called when command is executed first time and on
+    /// every redo.
+    fn execute(&mut self, context: &mut C);
+
+    /// Takes the change back, context must be in exactly the same state as before `execute`.
+    fn revert(&mut self, context: &mut C);
+}
+
+/// Set of commands that is executed and reverted as a single command.
+pub struct CommandGroup<C> {
+    name: String,
+    commands: Vec<Box<dyn Command<C>>>,
+}
+
+impl<C> CommandGroup<C> {
+    /// Creates new empty group with given name.
+    pub fn new<S: AsRef<str>>(name: S) -> Self {
+        Self {
+            name: name.as_ref().to_owned(),
+            commands: Default::default(),
+        }
+    }
+
+    /// Adds command to the end of the group. Command must be already executed.
+    pub fn push(&mut self, command: Box<dyn Command<C>>) {
+        self.commands.push(command);
+    }
+
+    /// Returns true if group has no commands.
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Returns amount of commands in the group.
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+}
+
+impl<C> Command<C> for CommandGroup<C> {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn execute(&mut self, context: &mut C) {
+        for command in self.commands.iter_mut() {
+            command.execute(context);
+        }
+    }
+
+    fn revert(&mut self, context: &mut C) {
+        // Reverse order, every command expects state that was left by previous ones.
+        for command in self.commands.iter_mut().rev() {
+            command.revert(context);
+        }
+    }
+}
+
+/// History of executed commands with undo and redo, see module docs.
+pub struct CommandStack<C> {
+    commands: Vec<Box<dyn Command<C>>>,
+    // Amount of commands which are currently applied, commands after this index can be redone.
+    top: usize,
+    capacity: usize,
+    transaction: Option<CommandGroup<C>>,
+}
+
+impl<C: 'static> Default for CommandStack<C> {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl<C: 'static> CommandStack<C> {
+    /// Capacity of stack created by `Default` implementation.
+    pub const DEFAULT_CAPACITY: usize = 256;
+
+    /// Creates new empty stack which remembers at most `capacity` commands (transaction is
+    /// counted as one command).
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            commands: Default::default(),
+            top: 0,
+            capacity: capacity.max(1),
+            transaction: None,
+        }
+    }
+
+    /// Executes command and puts it in the history. Every command that was undone before is
+    /// discarded, so it cannot be redone anymore. If transaction is active, command becomes
+    /// part of it.
+    pub fn do_command(&mut self, mut command: Box<dyn Command<C>>, context: &mut C) {
+        command.execute(context);
+        if let Some(transaction) = self.transaction.as_mut() {
+            transaction.push(command);
+        } else {
+            self.push(command);
+        }
+    }
+
+    fn push(&mut self, command: Box<dyn Command<C>>) {
+        self.commands.truncate(self.top);
+        self.commands.push(command);
+        if self.commands.len() > self.capacity {
+            let excess = self.commands.len() - self.capacity;
+            self.commands.drain(0..excess);
+        }
+        self.top = self.commands.len();
+    }
+
+    /// Reverts last applied command. Returns false if there is nothing to undo.
+    ///
+    /// # Panics
+    ///
+    /// Panics if transaction is active - it must be committed or rolled back first.
+    pub fn undo(&mut self, context: &mut C) -> bool {
+        assert!(self.transaction.is_none(), "undo during transaction");
+        if self.top > 0 {
+            self.top -= 1;
+            self.commands[self.top].revert(context);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Executes last undone command again. Returns false if there is nothing to redo.
+    ///
+    /// # Panics
+    ///
+    /// Panics if transaction is active - it must be committed or rolled back first.
+    pub fn redo(&mut self, context: &mut C) -> bool {
+        assert!(self.transaction.is_none(), "redo during transaction");
+        if self.top < self.commands.len() {
+            self.commands[self.top].execute(context);
+            self.top += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns true if there is a command to undo.
+    pub fn can_undo(&self) -> bool {
+        self.top > 0
+    }
+
+    /// Returns true if there is a command to redo.
+    pub fn can_redo(&self) -> bool {
+        self.top < self.commands.len()
+    }
+
+    /// Returns name of command that will be reverted by `undo`.
+    pub fn undo_name(&self) -> Option<String> {
+        self.top
+            .checked_sub(1)
+            .and_then(|i| self.commands.get(i))
+            .map(|c| c.name())
+    }
+
+    /// Returns name of command that will be executed by `redo`.
+    pub fn redo_name(&self) -> Option<String> {
+        self.commands.get(self.top).map(|c| c.name())
+    }
+
+    /// Starts new transaction, every command executed until `commit_transaction` will be put
+    /// in a group with given name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if transaction is already active, transactions cannot be nested.
+    pub fn begin_transaction<S: AsRef<str>>(&mut self, name: S) {
+        assert!(self.transaction.is_none(), "nested transaction");
+        self.transaction = Some(CommandGroup::new(name));
+    }
+
+    /// Finishes active transaction and puts it in the history as a single command. Empty
+    /// transaction is discarded. Does nothing if there is no active transaction.
+    pub fn commit_transaction(&mut self) {
+        if let Some(transaction) = self.transaction.take() {
+            if !transaction.is_empty() {
+                self.push(Box::new(transaction));
+            }
+        }
+    }
+
+    /// Reverts every command of active transaction and discards it. Does nothing if there is
+    /// no active transaction.
+    pub fn rollback_transaction(&mut self, context: &mut C) {
+        if let Some(mut transaction) = self.transaction.take() {
+            transaction.revert(context);
+        }
+    }
+
+    /// Returns true if transaction is active.
+    pub fn is_in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    /// Sets maximum amount of commands in the history. If there are more commands than new
+    /// capacity allows, undone commands are discarded first (starting from the last one) and
+    /// then oldest applied commands. Undone commands cannot be discarded from the beginning,
+    /// because every command can be redone only after commands before it.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        if self.commands.len() > self.capacity {
+            let redo_excess =
+                (self.commands.len() - self.capacity).min(self.commands.len() - self.top);
+            self.commands.truncate(self.commands.len() - redo_excess);
+        }
+        if self.commands.len() > self.capacity {
+            let excess = self.commands.len() - self.capacity;
+            self.commands.drain(0..excess);
+            self.top -= excess;
+        }
+    }
+
+    /// Returns maximum amount of commands in the history.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns amount of commands in the history, both applied and undone.
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Returns true if history is empty.
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Forgets every command, context is left intact. Active transaction is discarded too.
+    pub fn clear(&mut self) {
+        self.commands.clear();
+        self.top = 0;
+        self.transaction = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::command::{Command, CommandStack};
+
+    struct Add(i32);
+
+    impl Command<Vec<i32>> for Add {
+        fn name(&self) -> String {
+            format!("Add {}", self.0)
+        }
+
+        fn execute(&mut self, context: &mut Vec<i32>) {
+            context.push(self.0);
+        }
+
+        fn revert(&mut self, context: &mut Vec<i32>) {
+            assert_eq!(context.pop(), Some(self.0));
+        }
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut context = Vec::new();
+        let mut stack = CommandStack::new(8);
+        stack.do_command(Box::new(Add(1)), &mut context);
+        stack.do_command(Box::new(Add(2)), &mut context);
+        assert_eq!(stack.undo_name(), Some("Add 2".to_owned()));
+
+        assert!(stack.undo(&mut context));
+        assert_eq!(context, vec![1]);
+        assert_eq!(stack.redo_name(), Some("Add 2".to_owned()));
+        assert!(stack.redo(&mut context));
+        assert_eq!(context, vec![1, 2]);
+        assert!(!stack.redo(&mut context));
+
+        // New command discards undone ones.
+        stack.undo(&mut context);
+        stack.do_command(Box::new(Add(3)), &mut context);
+        assert!(!stack.can_redo());
+        assert_eq!(stack.len(), 2);
+        assert_eq!(context, vec![1, 3]);
+    }
+
+    #[test]
+    fn transactions() {
+        let mut context = Vec::new();
+        let mut stack = CommandStack::new(8);
+        stack.do_command(Box::new(Add(1)), &mut context);
+        stack.begin_transaction("Drag");
+        stack.do_command(Box::new(Add(2)), &mut context);
+        stack.do_command(Box::new(Add(3)), &mut context);
+        stack.commit_transaction();
+        assert_eq!(stack.len(), 2);
+        assert_eq!(stack.undo_name(), Some("Drag".to_owned()));
+
+        stack.undo(&mut context);
+        assert_eq!(context, vec![1]);
+        stack.redo(&mut context);
+        assert_eq!(context, vec![1, 2, 3]);
+
+        stack.begin_transaction("Cancelled");
+        stack.do_command(Box::new(Add(4)), &mut context);
+        stack.rollback_transaction(&mut context);
+        assert_eq!(context, vec![1, 2, 3]);
+        assert_eq!(stack.len(), 2);
+    }
+
+    #[test]
+    fn capacity() {
+        let mut context = Vec::new();
+        let mut stack = CommandStack::new(2);
+        for i in 0..4 {
+            stack.do_command(Box::new(Add(i)), &mut context);
+        }
+        assert_eq!(stack.len(), 2);
+        while stack.undo(&mut context) {}
+        assert_eq!(context, vec![0, 1]);
+
+        // Undone commands are discarded first.
+        stack.redo(&mut context);
+        stack.set_capacity(1);
+        assert_eq!(stack.len(), 1);
+        assert_eq!(stack.undo_name(), Some("Add 2".to_owned()));
+        assert!(!stack.can_redo());
+    }
+
+    #[test]
+    fn capacity_keeps_redo_order() {
+        let mut context = Vec::new();
+        let mut stack = CommandStack::new(2);
+        for i in 0..4 {
+            stack.do_command(Box::new(Add(i)), &mut context);
+        }
+        while stack.undo(&mut context) {}
+        assert_eq!(context, vec![0, 1]);
+
+        // Nothing is applied, so the next command to redo must survive.
+        stack.set_capacity(1);
+        assert_eq!(stack.len(), 1);
+        assert!(!stack.can_undo());
+        assert_eq!(stack.redo_name(), Some("Add 2".to_owned()));
+        assert!(stack.redo(&mut context));
+        assert_eq!(context, vec![0, 1, 2]);
+        assert!(!stack.redo(&mut context));
+
+        // Applied commands are discarded from the beginning.
+        stack.set_capacity(2);
+        stack.do_command(Box::new(Add(5)), &mut context);
+        stack.set_capacity(1);
+        assert_eq!(stack.undo_name(), Some("Add 5".to_owned()));
+        assert!(stack.undo(&mut context));
+        assert!(!stack.can_undo());
+        assert_eq!(context, vec![0, 1, 2]);
+    }
+}
@@ -316,6 +316,18 @@ impl<M: MessageData, C: Control<M, C>> Control<M, C> for Tile<M, C> {
                                 self.split(ui, window, direction, first);
                             }
                         }
+                        &TileMessage::Splitter(value) => match self.content {
+                            TileContent::VerticalTiles {
+                                ref mut splitter, ..
+                            }
+                            | TileContent::HorizontalTiles {
+                                ref mut splitter, ..
+                            } => {
+                                *splitter = value.max(0.0).min(1.0);
+                                self.invalidate_layout();
+                            }
+                            _ => (),
+                        },
                     }
                 }
             }
@@ -718,6 +730,19 @@ pub enum SplitDirection {
 }
 
 impl<M: MessageData, C: Control<M, C>> Tile<M, C> {
+    pub fn content(&self) -> &TileContent<M, C> {
+        &self.content
+    }
+
+    /// Returns position of splitter in `[0; 1]` range, or `None` if tile is not split.
+    pub fn splitter(&self) -> Option<f32> {
+        match self.content {
+            TileContent::VerticalTiles { splitter, .. }
+            | TileContent::HorizontalTiles { splitter, .. } => Some(splitter),
+            _ => None,
+        }
+    }
+
     pub fn anchors(&self) -> [Handle<UINode<M, C>>; 5] {
         [
             self.left_anchor,
@@ -16,8 +16,8 @@ use std::{any::Any, ops::Deref, ops::Range, sync::Arc};
 
 #[repr(C)]
 pub struct Vertex {
-    pos: Vector2<f32>,
-    tex_coord: Vector2<f32>,
+    pub(in crate) pos: Vector2<f32>,
+    pub(in crate) tex_coord: Vector2<f32>,
     color: Color,
 }
 
@@ -54,14 +54,14 @@ impl PartialEq for SharedTexture {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub enum CommandTexture {
     None,
     Texture(SharedTexture),
     Font(SharedFont),
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct Bounds {
     pub min: Vector2<f32>,
     pub max: Vector2<f32>,
@@ -84,6 +84,26 @@ impl Bounds {
         self.max.x = self.max.x.max(p.x);
         self.max.y = self.max.y.max(p.y);
     }
+
+    /// Returns true if bounds have common area, bounds which only touch each other are not
+    /// intersecting.
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        self.min.x < other.max.x
+            && other.min.x < self.max.x
+            && self.min.y < other.max.y
+            && other.min.y < self.max.y
+    }
+
+    fn area(&self) -> f32 {
+        ((self.max.x - self.min.x) * (self.max.y - self.min.y)).max(0.0)
+    }
+
+    fn merged(&self, other: &Bounds) -> Bounds {
+        let mut merged = self.clone();
+        merged.push(other.min);
+        merged.push(other.max);
+        merged
+    }
 }
 
 #[derive(Clone)]
@@ -103,6 +123,8 @@ pub struct DrawingContext {
     clip_cmd_stack: Vec<usize>,
     triangles_to_commit: usize,
     current_nesting: u8,
+    dimmed: bool,
+    opacity: f32,
 }
 
 fn get_line_thickness_vector(a: Vector2<f32>, b: Vector2<f32>, thickness: f32) -> Vector2<f32> {
@@ -127,6 +149,8 @@ impl DrawingContext {
             command_buffer: Vec::new(),
             clip_cmd_stack: Vec::new(),
             triangles_to_commit: 0,
+            dimmed: false,
+            opacity: 1.0,
             current_nesting: 0,
         }
     }
@@ -139,6 +163,8 @@ impl DrawingContext {
         self.clip_cmd_stack.clear();
         self.triangles_to_commit = 0;
         self.current_nesting = 0;
+        self.dimmed = false;
+        self.opacity = 1.0;
     }
 
     #[inline]
@@ -161,6 +187,20 @@ impl DrawingContext {
         self.current_nesting = nesting;
     }
 
+    /// Sets whether brushes of next commands should be dimmed or not, it is used to draw
+    /// disabled widgets. See `Brush::dimmed`.
+    #[inline]
+    pub fn set_dimmed(&mut self, dimmed: bool) {
+        self.dimmed = dimmed;
+    }
+
+    /// Sets opacity by which alpha of brushes of next commands will be multiplied, it is
+    /// used to draw semi-transparent widgets. See `Brush::with_opacity`.
+    #[inline]
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity;
+    }
+
     #[inline]
     fn push_triangle(&mut self, a: u32, b: u32, c: u32) {
         self.triangle_buffer.push(TriangleDefinition([a, b, c]));
@@ -303,6 +343,70 @@ impl DrawingContext {
         self.push_triangle(index, index + 2, index + 3);
     }
 
+    /// Pushes nine-slice (nine-patch) rectangle - corners keep their size, edges are stretched
+    /// along one axis and center is stretched along both axes. `insets` is size of borders on
+    /// screen in pixels, `tex_insets` is size of borders in texture in fractions of the range of
+    /// texture coordinates defined by `tex_coords` (left-top, right-top, right-bottom and
+    /// left-bottom corners of the whole rectangle). Borders are shrunk proportionally if the
+    /// rectangle is smaller than sum of opposite borders.
+    pub fn push_nine_slice(
+        &mut self,
+        rect: &Rect<f32>,
+        insets: Thickness,
+        tex_insets: Thickness,
+        tex_coords: &[Vector2<f32>; 4],
+    ) {
+        let fit = |size: f32, a: f32, b: f32| {
+            let sum = a + b;
+            if sum > size && sum > 0.0 {
+                (a * size / sum, b * size / sum)
+            } else {
+                (a, b)
+            }
+        };
+        let (left, right) = fit(rect.w(), insets.left, insets.right);
+        let (top, bottom) = fit(rect.h(), insets.top, insets.bottom);
+
+        let xs = [
+            rect.x(),
+            rect.x() + left,
+            rect.x() + rect.w() - right,
+            rect.x() + rect.w(),
+        ];
+        let ys = [
+            rect.y(),
+            rect.y() + top,
+            rect.y() + rect.h() - bottom,
+            rect.y() + rect.h(),
+        ];
+        let us = [0.0, tex_insets.left, 1.0 - tex_insets.right, 1.0];
+        let vs = [0.0, tex_insets.top, 1.0 - tex_insets.bottom, 1.0];
+
+        // Bilinear interpolation of texture coordinates of the whole rectangle, this way
+        // flipped or rotated coordinates work too.
+        let uv = |u: f32, v: f32| {
+            let top = tex_coords[0].lerp(&tex_coords[1], u);
+            let bottom = tex_coords[3].lerp(&tex_coords[2], u);
+            top.lerp(&bottom, v)
+        };
+
+        for row in 0..3 {
+            for column in 0..3 {
+                let w = xs[column + 1] - xs[column];
+                let h = ys[row + 1] - ys[row];
+                if w <= 0.0 || h <= 0.0 {
+                    continue;
+                }
+                let (u0, u1) = (us[column], us[column + 1]);
+                let (v0, v1) = (vs[row], vs[row + 1]);
+                self.push_rect_filled(
+                    &Rect::new(xs[column], ys[row], w, h),
+                    Some(&[uv(u0, v0), uv(u1, v0), uv(u1, v1), uv(u0, v1)]),
+                );
+            }
+        }
+    }
+
     pub fn push_rect_multicolor(&mut self, rect: &Rect<f32>, colors: [Color; 4]) {
         let index = self.last_vertex_index();
         self.vertex_buffer.push(Vertex {
@@ -393,6 +497,12 @@ impl DrawingContext {
         if self.triangles_to_commit > 0 {
             let triangles = self.pending_range();
             let bounds = self.bounds_of(triangles.clone());
+            let brush = if self.dimmed { brush.dimmed() } else { brush };
+            let brush = if self.opacity < 1.0 {
+                brush.with_opacity(self.opacity)
+            } else {
+                brush
+            };
             self.command_buffer.push(Command {
                 bounds,
                 kind,
@@ -413,6 +523,11 @@ impl DrawingContext {
             return;
         };
 
+        // Atlas of the font could be changed after the text was built (some other text added
+        // new glyphs), in this case texture coordinates must be taken from font again.
+        let font_ref = font.0.lock().unwrap();
+        let outdated = font_ref.atlas_revision() != formatted_text.atlas_revision();
+
         for element in formatted_text.get_glyphs() {
             let bounds = element.get_bounds();
 
@@ -423,9 +538,19 @@ impl DrawingContext {
                 bounds.h(),
             );
 
-            self.push_rect_filled(&final_bounds, Some(element.get_tex_coords()));
+            let tex_coords = if outdated {
+                font_ref
+                    .glyph(element.code())
+                    .map_or(element.get_tex_coords(), |glyph| &glyph.tex_coords)
+            } else {
+                element.get_tex_coords()
+            };
+
+            self.push_rect_filled(&final_bounds, Some(tex_coords));
         }
 
+        drop(font_ref);
+
         self.commit(
             CommandKind::Geometry,
             formatted_text.brush(),
@@ -457,3 +582,354 @@ impl DrawingContext {
         }
     }
 }
+
+/// Maximum amount of batches checked when a command is looking for a batch to join, it keeps
+/// batching linear for interfaces with lots of different brushes.
+const MAX_BATCH_LOOKBACK: usize = 32;
+
+/// Maximum amount of bounds that cover commands of a batch, see `add_cover`.
+const MAX_BATCH_COVERS: usize = 8;
+
+/// Maximum gap (in pixels) between neighbour bounds which can be merged without loss of
+/// precision, see `add_cover`.
+const MAX_COVER_GAP: f32 = 8.0;
+
+/// Draw list prepared for rendering. Commands of drawing context are kept per widget (they
+/// are used for picking), which gives one draw call per command. Batches merge commands with
+/// the same texture, brush and clipping state into single range of triangles, so they can
+/// be drawn by one draw call.
+///
+/// Command can be moved back to join earlier batch only if it does not overlap any batch
+/// drawn between them, so visual output stays the same. Clipping commands change stencil
+/// buffer and commands are never moved across them.
+#[derive(Default)]
+pub struct DrawBatches {
+    batches: Vec<Command>,
+    triangles: Vec<TriangleDefinition>,
+    source_commands: usize,
+    // Indices of commands of each batch, inner vectors are reused between frames.
+    members: Vec<Vec<usize>>,
+    // Bounds that cover commands of each batch, inner vectors are reused between frames.
+    covers: Vec<Vec<Bounds>>,
+}
+
+// Returns true if union of bounds covers almost nothing but them: one of them contains other
+// one or they are neighbours in a row or in a column (cells of a grid, items of a list).
+fn fits(a: &Bounds, b: &Bounds) -> bool {
+    let contains = |a: &Bounds, b: &Bounds| {
+        a.min.x <= b.min.x && a.min.y <= b.min.y && a.max.x >= b.max.x && a.max.y >= b.max.y
+    };
+    let same = |a_min: f32, a_max: f32, b_min: f32, b_max: f32| {
+        (a_min - b_min).abs() <= 0.5 && (a_max - b_max).abs() <= 0.5
+    };
+    let close = |a_min: f32, a_max: f32, b_min: f32, b_max: f32| {
+        a_min.max(b_min) - a_max.min(b_max) <= MAX_COVER_GAP
+    };
+    contains(a, b)
+        || contains(b, a)
+        || (same(a.min.y, a.max.y, b.min.y, b.max.y) && close(a.min.x, a.max.x, b.min.x, b.max.x))
+        || (same(a.min.x, a.max.x, b.min.x, b.max.x) && close(a.min.y, a.max.y, b.min.y, b.max.y))
+}
+
+/// Adds bounds of a command to bounds that cover commands of a batch. Neighbour bounds are
+/// merged, and the closest ones are merged when there are too many of them, so overlap check
+/// of a batch takes constant time. Merged bounds may cover some free space, so a command may
+/// be not moved to a batch when it could be, but it is never moved over something it overlaps.
+fn add_cover(covers: &mut Vec<Bounds>, mut bounds: Bounds) {
+    // Merged bounds may fit other bounds too, rows of a grid are merged into one block.
+    while let Some(i) = covers.iter().position(|cover| fits(cover, &bounds)) {
+        bounds = covers.swap_remove(i).merged(&bounds);
+    }
+
+    if covers.len() >= MAX_BATCH_COVERS {
+        // Merge with the cover that grows the least.
+        let mut best = 0;
+        let mut best_growth = std::f32::MAX;
+        for (i, cover) in covers.iter().enumerate() {
+            let growth = cover.merged(&bounds).area() - cover.area();
+            if growth < best_growth {
+                best = i;
+                best_growth = growth;
+            }
+        }
+        bounds = covers.swap_remove(best).merged(&bounds);
+    }
+
+    covers.push(bounds);
+}
+
+fn can_merge(batch: &Command, command: &Command) -> bool {
+    batch.kind == CommandKind::Geometry
+        && command.kind == CommandKind::Geometry
+        && batch.nesting == command.nesting
+        && batch.texture == command.texture
+        && batch.brush == command.brush
+        // Gradients are mapped onto bounds of a command, so merged bounds would change them.
+        && (matches!(command.brush, Brush::Solid(_)) || batch.bounds == command.bounds)
+}
+
+impl DrawBatches {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rebuilds batches from commands of given drawing context.
+    pub fn build(&mut self, context: &DrawingContext) {
+        self.batches.clear();
+        self.triangles.clear();
+        for members in self.members.iter_mut() {
+            members.clear();
+        }
+        for covers in self.covers.iter_mut() {
+            covers.clear();
+        }
+
+        let commands = context.get_commands();
+        self.source_commands = commands.len();
+
+        let mut slice_start = 0;
+        for (index, command) in commands.iter().enumerate() {
+            let mut target = None;
+            for (i, batch) in self
+                .batches
+                .iter()
+                .enumerate()
+                .skip(slice_start)
+                .rev()
+                .take(MAX_BATCH_LOOKBACK)
+            {
+                if can_merge(batch, command) {
+                    target = Some(i);
+                    break;
+                }
+                // Bounds of a batch are checked first, they cover every command of the batch.
+                if batch.bounds.intersects(&command.bounds)
+                    && self.covers[i]
+                        .iter()
+                        .any(|cover| cover.intersects(&command.bounds))
+                {
+                    break;
+                }
+            }
+
+            match target {
+                Some(i) => {
+                    let batch = &mut self.batches[i];
+                    batch.bounds.push(command.bounds.min);
+                    batch.bounds.push(command.bounds.max);
+                    self.members[i].push(index);
+                    add_cover(&mut self.covers[i], command.bounds.clone());
+                }
+                None => {
+                    if self.members.len() == self.batches.len() {
+                        self.members.push(Vec::new());
+                        self.covers.push(Vec::new());
+                    }
+                    self.members[self.batches.len()].push(index);
+                    add_cover(&mut self.covers[self.batches.len()], command.bounds.clone());
+                    self.batches.push(command.clone());
+                    if command.kind == CommandKind::Clip {
+                        slice_start = self.batches.len();
+                    }
+                }
+            }
+        }
+
+        let source = context.get_triangles();
+        for (batch, members) in self.batches.iter_mut().zip(self.members.iter()) {
+            let start = self.triangles.len();
+            for &member in members {
+                self.triangles
+                    .extend_from_slice(&source[commands[member].triangles.clone()]);
+            }
+            batch.triangles = start..self.triangles.len();
+        }
+    }
+
+    /// Returns batches in drawing order, triangle ranges point into `triangles`.
+    #[inline]
+    pub fn batches(&self) -> &[Command] {
+        &self.batches
+    }
+
+    /// Returns triangles of every batch, vertices are shared with drawing context.
+    #[inline]
+    pub fn triangles(&self) -> &[TriangleDefinition] {
+        &self.triangles
+    }
+
+    /// Returns amount of commands from which batches were built.
+    #[inline]
+    pub fn source_command_count(&self) -> usize {
+        self.source_commands
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        brush::Brush,
+        core::{algebra::Vector2, color::Color, math::Rect},
+        draw::{
+            CommandKind, CommandTexture, DrawBatches, DrawingContext, SharedTexture,
+            MAX_BATCH_COVERS,
+        },
+        Thickness,
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn nine_slice() {
+        let mut ctx = DrawingContext::new();
+        let tex_coords = [
+            Vector2::new(0.0, 0.0),
+            Vector2::new(1.0, 0.0),
+            Vector2::new(1.0, 1.0),
+            Vector2::new(0.0, 1.0),
+        ];
+        ctx.push_nine_slice(
+            &Rect::new(10.0, 20.0, 100.0, 50.0),
+            Thickness::uniform(8.0),
+            Thickness::uniform(0.25),
+            &tex_coords,
+        );
+        let vertices = ctx.get_vertices();
+        assert_eq!(vertices.len(), 9 * 4);
+
+        // Top-left corner keeps its size.
+        assert_eq!(vertices[0].pos, Vector2::new(10.0, 20.0));
+        assert_eq!(vertices[2].pos, Vector2::new(18.0, 28.0));
+        assert_eq!(vertices[2].tex_coord, Vector2::new(0.25, 0.25));
+        // Center is stretched.
+        assert_eq!(vertices[4 * 4].pos, Vector2::new(18.0, 28.0));
+        assert_eq!(vertices[4 * 4 + 2].pos, Vector2::new(102.0, 62.0));
+        assert_eq!(vertices[4 * 4 + 2].tex_coord, Vector2::new(0.75, 0.75));
+        // Bottom-right corner keeps its size.
+        assert_eq!(vertices[8 * 4].pos, Vector2::new(102.0, 62.0));
+        assert_eq!(vertices[8 * 4 + 2].pos, Vector2::new(110.0, 70.0));
+        assert_eq!(vertices[8 * 4 + 2].tex_coord, Vector2::new(1.0, 1.0));
+
+        // Borders are shrunk when rectangle is too small, degenerated slices are skipped.
+        let mut ctx = DrawingContext::new();
+        ctx.push_nine_slice(
+            &Rect::new(0.0, 0.0, 8.0, 8.0),
+            Thickness::uniform(8.0),
+            Thickness::uniform(0.25),
+            &tex_coords,
+        );
+        let vertices = ctx.get_vertices();
+        assert_eq!(vertices.len(), 4 * 4);
+        assert_eq!(vertices[2].pos, Vector2::new(4.0, 4.0));
+    }
+
+    #[test]
+    fn batching_merges_inventory_slots() {
+        let icon = SharedTexture::from(Arc::new(0u32));
+        let mut ctx = DrawingContext::new();
+        for i in 0..400 {
+            let rect = Rect::new((i % 20) as f32 * 44.0, (i / 20) as f32 * 44.0, 40.0, 40.0);
+            // Background, icon and frame of a slot.
+            ctx.push_rect_filled(&rect, None);
+            ctx.commit(
+                CommandKind::Geometry,
+                Brush::Solid(Color::opaque(40, 40, 40)),
+                CommandTexture::None,
+            );
+            ctx.push_rect_filled(&rect, None);
+            ctx.commit(
+                CommandKind::Geometry,
+                Brush::Solid(Color::WHITE),
+                CommandTexture::Texture(icon.clone()),
+            );
+            ctx.push_rect(&rect, 1.0);
+            ctx.commit(
+                CommandKind::Geometry,
+                Brush::Solid(Color::opaque(200, 200, 200)),
+                CommandTexture::None,
+            );
+        }
+
+        let mut batches = DrawBatches::new();
+        batches.build(&ctx);
+        assert_eq!(batches.source_command_count(), 1200);
+        assert_eq!(batches.batches().len(), 3);
+        assert_eq!(batches.triangles().len(), ctx.get_triangles().len());
+
+        // Every triangle is drawn exactly once.
+        let mut source = ctx.get_triangles().to_vec();
+        let mut batched = batches.triangles().to_vec();
+        source.sort_by_key(|t| t.0);
+        batched.sort_by_key(|t| t.0);
+        assert_eq!(source, batched);
+    }
+
+    #[test]
+    fn batching_keeps_visual_order() {
+        let red = Brush::Solid(Color::RED);
+        let green = Brush::Solid(Color::GREEN);
+        let mut ctx = DrawingContext::new();
+        let push = |ctx: &mut DrawingContext, rect: Rect<f32>, brush: &Brush| {
+            ctx.push_rect_filled(&rect, None);
+            ctx.commit(CommandKind::Geometry, brush.clone(), CommandTexture::None);
+        };
+
+        // Green overlaps first red, so second red cannot be moved before green.
+        push(&mut ctx, Rect::new(0.0, 0.0, 10.0, 10.0), &red);
+        push(&mut ctx, Rect::new(5.0, 5.0, 10.0, 10.0), &green);
+        push(&mut ctx, Rect::new(0.0, 0.0, 10.0, 10.0), &red);
+
+        // Commands are never moved across clipping.
+        ctx.commit_clip_rect(&Rect::new(0.0, 0.0, 100.0, 100.0));
+        push(&mut ctx, Rect::new(50.0, 50.0, 10.0, 10.0), &green);
+
+        let mut batches = DrawBatches::new();
+        batches.build(&ctx);
+        let kinds = batches
+            .batches()
+            .iter()
+            .map(|b| (b.kind, b.brush.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                (CommandKind::Geometry, red.clone()),
+                (CommandKind::Geometry, green.clone()),
+                (CommandKind::Geometry, red),
+                (CommandKind::Clip, Brush::Solid(Color::WHITE)),
+                (CommandKind::Geometry, green),
+            ]
+        );
+    }
+
+    #[test]
+    fn batching_large_batches() {
+        let red = Brush::Solid(Color::RED);
+        let green = Brush::Solid(Color::GREEN);
+        let mut ctx = DrawingContext::new();
+        let push = |ctx: &mut DrawingContext, rect: Rect<f32>, brush: &Brush| {
+            ctx.push_rect_filled(&rect, None);
+            ctx.commit(CommandKind::Geometry, brush.clone(), CommandTexture::None);
+        };
+
+        // Scattered commands, so their bounds cannot be merged tightly.
+        for i in 0..1000 {
+            let x = (i * 37 % 1000) as f32 * 10.0;
+            let y = (i * 91 % 1000) as f32 * 10.0;
+            push(&mut ctx, Rect::new(x, y, 5.0, 5.0), &red);
+        }
+        // Overlaps first red command.
+        push(&mut ctx, Rect::new(0.0, 0.0, 2.0, 2.0), &green);
+        // Far from everything, joins first batch.
+        push(&mut ctx, Rect::new(-100.0, -100.0, 5.0, 5.0), &red);
+        // Overlaps green, cannot be moved before it.
+        push(&mut ctx, Rect::new(1.0, 1.0, 2.0, 2.0), &red);
+
+        let mut batches = DrawBatches::new();
+        batches.build(&ctx);
+        assert_eq!(batches.batches().len(), 3);
+        assert!(batches
+            .covers
+            .iter()
+            .all(|covers| covers.len() <= MAX_BATCH_COVERS));
+    }
+}
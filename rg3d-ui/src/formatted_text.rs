@@ -1,18 +1,26 @@
 use crate::{
+    bidi,
     brush::Brush,
     core::{algebra::Vector2, color::Color, math::Rect},
     ttf::SharedFont,
-    HorizontalAlignment, VerticalAlignment,
+    HorizontalAlignment, TextDirection, VerticalAlignment, WrapMode,
 };
 use std::ops::Range;
+use unicode_bidi::BidiInfo;
 
 #[derive(Debug, Clone)]
 pub struct TextGlyph {
     bounds: Rect<f32>,
     tex_coords: [Vector2<f32>; 4],
+    code: u32,
 }
 
 impl TextGlyph {
+    /// Returns character code of the glyph.
+    pub fn code(&self) -> u32 {
+        self.code
+    }
+
     pub fn get_bounds(&self) -> Rect<f32> {
         self.bounds
     }
@@ -36,6 +44,8 @@ pub struct TextLine {
     pub x_offset: f32,
     /// Local vertical position of line.
     pub y_offset: f32,
+    /// Whether base direction of line is right-to-left.
+    pub rtl: bool,
 }
 
 impl TextLine {
@@ -47,6 +57,7 @@ impl TextLine {
             height: 0.0,
             x_offset: 0.0,
             y_offset: 0.0,
+            rtl: false,
         }
     }
 
@@ -59,6 +70,15 @@ impl TextLine {
     }
 }
 
+/// Visual placement of a character in a line.
+#[derive(Copy, Clone, Debug, Default)]
+struct CharLayout {
+    /// Position of left edge of character relative to line origin.
+    x: f32,
+    advance: f32,
+    rtl: bool,
+}
+
 #[derive(Clone, Debug)]
 pub struct FormattedText {
     font: Option<SharedFont>,
@@ -74,10 +94,28 @@ pub struct FormattedText {
     horizontal_alignment: HorizontalAlignment,
     brush: Brush,
     constraint: Vector2<f32>,
-    wrap: bool,
+    wrap: WrapMode,
+    justify: bool,
+    /// Revision of font atlas at the moment of last build.
+    atlas_revision: usize,
+    direction: TextDirection,
+    /// Visual placement of each character of text, in logical order.
+    char_layout: Vec<CharLayout>,
+    // Temporary buffers of `build`, they are kept to avoid allocations on each build.
+    utf8: String,
+    byte_offsets: Vec<usize>,
+    shaped: Vec<u32>,
+    wrapped: Vec<bool>,
+    visual_order: Vec<usize>,
 }
 
 impl FormattedText {
+    /// Returns revision of font atlas that was used to build glyphs. If it differs from
+    /// current revision of font atlas, texture coordinates of glyphs are outdated.
+    pub fn atlas_revision(&self) -> usize {
+        self.atlas_revision
+    }
+
     pub fn get_glyphs(&self) -> &[TextGlyph] {
         &self.glyphs
     }
@@ -116,6 +154,69 @@ impl FormattedText {
         self.horizontal_alignment
     }
 
+    /// Sets base direction of text. Horizontal alignment is not affected by direction,
+    /// right-to-left text usually should be aligned to the right.
+    pub fn set_direction(&mut self, direction: TextDirection) -> &mut Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn direction(&self) -> TextDirection {
+        self.direction
+    }
+
+    /// Returns horizontal position and width of a character (by its index in text) relative
+    /// to the origin of its line. Characters of right-to-left runs are placed in reversed
+    /// order, so positions are not monotonic in general.
+    pub fn char_span(&self, index: usize) -> Option<(f32, f32)> {
+        self.char_layout
+            .get(index)
+            .map(|layout| (layout.x, layout.advance))
+    }
+
+    /// Returns horizontal position of caret relative to the origin of a line. Caret is placed
+    /// at the leading edge of a character at `offset` (left edge for left-to-right characters,
+    /// right edge for right-to-left ones), or after the last character of the line.
+    pub fn caret_x(&self, line: usize, offset: usize) -> f32 {
+        let line = match self.lines.get(line) {
+            Some(line) => line,
+            None => return 0.0,
+        };
+        let index = line.begin + offset;
+        if offset < line.len() {
+            if let Some(layout) = self.char_layout.get(index) {
+                return if layout.rtl {
+                    layout.x + layout.advance
+                } else {
+                    layout.x
+                };
+            }
+        }
+        // Caret is at the end of line - place it after last character.
+        match line
+            .end
+            .checked_sub(1)
+            .filter(|&last| last >= line.begin)
+            .and_then(|last| self.char_layout.get(last))
+        {
+            Some(layout) if layout.rtl => layout.x,
+            Some(layout) => layout.x + layout.advance,
+            None if line.rtl => line.width,
+            None => 0.0,
+        }
+    }
+
+    /// Returns offset (from the beginning of a line) of a character which contains given
+    /// horizontal position relative to the origin of the line.
+    pub fn char_offset_at(&self, line: usize, x: f32) -> Option<usize> {
+        let line = self.lines.get(line)?;
+        (line.begin..line.end).position(|index| {
+            self.char_layout.get(index).map_or(false, |layout| {
+                x >= layout.x && x < layout.x + layout.advance
+            })
+        })
+    }
+
     pub fn set_brush(&mut self, brush: Brush) -> &mut Self {
         self.brush = brush;
         self
@@ -161,15 +262,28 @@ impl FormattedText {
         self
     }
 
-    pub fn set_wrap(&mut self, wrap: bool) -> &mut Self {
+    pub fn set_wrap(&mut self, wrap: WrapMode) -> &mut Self {
         self.wrap = wrap;
         self
     }
 
-    pub fn is_wrap(&self) -> bool {
+    pub fn wrap_mode(&self) -> WrapMode {
         self.wrap
     }
 
+    /// Enables or disables justification. Justified lines that were wrapped are stretched to
+    /// the width of constraint by expanding whitespaces between words, other lines (last line
+    /// of each paragraph and lines ended by line break) are aligned according to horizontal
+    /// alignment.
+    pub fn set_justify(&mut self, justify: bool) -> &mut Self {
+        self.justify = justify;
+        self
+    }
+
+    pub fn is_justify(&self) -> bool {
+        self.justify
+    }
+
     pub fn insert_char(&mut self, c: char, index: usize) -> &mut Self {
         let c = c as u32;
         if index == self.text.len() {
@@ -191,50 +305,138 @@ impl FormattedText {
     }
 
     pub fn build(&mut self) -> Vector2<f32> {
-        let font = if let Some(font) = &self.font {
+        let mut font = if let Some(font) = &self.font {
             font.0.lock().unwrap()
         } else {
             return Vector2::default();
         };
 
-        // Split on lines.
-        let mut total_height = 0.0;
-        let mut current_line = TextLine::new();
+        // Temporary buffers are reused between builds to avoid allocations.
+        let mut utf8 = std::mem::take(&mut self.utf8);
+        let mut byte_offsets = std::mem::take(&mut self.byte_offsets);
+        let mut shaped = std::mem::take(&mut self.shaped);
+        let mut wrapped = std::mem::take(&mut self.wrapped);
+        let mut visual_order = std::mem::take(&mut self.visual_order);
+
+        // Run bidirectional analysis, it gives embedding level (and so direction) for each
+        // character. Analyzer works with UTF-8, so keep byte offset of each character. Most
+        // texts are purely left-to-right, analysis is skipped for them.
+        let bidi_info = if self.direction == TextDirection::RightToLeft || bidi::has_rtl(&self.text)
+        {
+            utf8.clear();
+            utf8.extend(
+                self.text
+                    .iter()
+                    .map(|&c| std::char::from_u32(c).unwrap_or(std::char::REPLACEMENT_CHARACTER)),
+            );
+            byte_offsets.clear();
+            byte_offsets.extend(utf8.char_indices().map(|(offset, _)| offset));
+            byte_offsets.push(utf8.len());
+            Some(BidiInfo::new(&utf8, self.direction.level()))
+        } else {
+            None
+        };
+        let is_rtl = |index: usize| {
+            bidi_info
+                .as_ref()
+                .map_or(false, |info| info.levels[byte_offsets[index]].is_rtl())
+        };
+
+        // Select contextual forms of letters and mirror paired characters in right-to-left
+        // runs. Shaped text has same length as source text.
+        if bidi_info.is_some() {
+            bidi::shape(&self.text, &mut shaped);
+            for (index, code) in shaped.iter_mut().enumerate() {
+                if is_rtl(index) {
+                    *code = bidi::mirror(*code);
+                }
+            }
+        } else {
+            shaped.clear();
+            shaped.extend_from_slice(&self.text);
+        }
+
+        // Rasterize glyphs that are not in font atlas yet.
+        font.cache_glyphs(&shaped);
+        self.atlas_revision = font.atlas_revision();
+
+        // Split on lines. Line break characters are not included in lines. Lines that were
+        // wrapped (not ended by line break) are marked, only they can be justified.
         self.lines.clear();
-        for (i, code) in self.text.iter().enumerate() {
-            let advance = match font.glyph(*code) {
-                Some(glyph) => glyph.advance,
-                None => font.height(),
-            };
-            let is_new_line = *code == u32::from(b'\n') || *code == u32::from(b'\r');
-            let new_width = current_line.width + advance;
-            if self.wrap && new_width > self.constraint.x || is_new_line {
+        wrapped.clear();
+        let advance = |code: u32| font.glyph_advance(code);
+        let is_space = |code: u32| code == u32::from(b' ') || code == u32::from(b'\t');
+        let mut current_line = TextLine::new();
+        // Index at which next line will begin if a word will not fit, and width of current
+        // line before whitespace at that index.
+        let mut word_break: Option<(usize, f32)> = None;
+        let mut i = 0;
+        while i < shaped.len() {
+            let code = shaped[i];
+            if code == u32::from(b'\n') || code == u32::from(b'\r') {
+                // Windows-style line break is a single break.
+                let crlf = code == u32::from(b'\r') && shaped.get(i + 1) == Some(&u32::from(b'\n'));
+                current_line.end = i;
                 self.lines.push(current_line);
-                current_line.begin = if is_new_line { i + 1 } else { i };
-                current_line.end = current_line.begin + 1;
-                current_line.width = advance;
-                total_height += font.ascender();
-            } else {
-                current_line.width = new_width;
-                current_line.end += 1;
+                wrapped.push(false);
+                i += if crlf { 2 } else { 1 };
+                current_line = TextLine::new();
+                current_line.begin = i;
+                word_break = None;
+                continue;
             }
-        }
-        // Commit rest of text.
-        if current_line.begin != current_line.end {
-            for code in self.text.iter().skip(current_line.end) {
-                let advance = match font.glyph(*code) {
-                    Some(glyph) => glyph.advance,
-                    None => font.height(),
+
+            let char_advance = advance(code);
+            if self.wrap != WrapMode::NoWrap
+                && current_line.width + char_advance > self.constraint.x
+                && i > current_line.begin
+                && !is_space(code)
+            {
+                let (next_begin, width) = match (self.wrap, word_break) {
+                    (WrapMode::Word, Some(word_break)) => word_break,
+                    // Words that does not fit in line are split at any character.
+                    _ => (i, current_line.width),
                 };
-                current_line.width += advance;
+                current_line.end = next_begin;
+                current_line.width = width;
+                self.lines.push(current_line);
+                wrapped.push(true);
+                current_line = TextLine::new();
+                current_line.begin = next_begin;
+                current_line.width = shaped[next_begin..i].iter().map(|&c| advance(c)).sum();
+                word_break = None;
+                // Check current character again, it may not fit in new line too.
+                continue;
             }
-            current_line.end = self.text.len();
+
+            if is_space(code) && i > current_line.begin && !is_space(shaped[i - 1]) {
+                word_break = Some((i + 1, current_line.width));
+            } else if is_space(code) {
+                if let Some(word_break) = word_break.as_mut() {
+                    // Skip sequence of whitespaces.
+                    word_break.0 = i + 1;
+                }
+            }
+            current_line.width += char_advance;
+            i += 1;
+        }
+        // Commit rest of text.
+        if !shaped.is_empty() {
+            current_line.end = shaped.len();
             self.lines.push(current_line);
-            total_height += font.ascender();
+            wrapped.push(false);
         }
+        let total_height = self.lines.len() as f32 * font.ascender();
+
+        let justify = self.justify && self.constraint.x.is_finite();
 
         // Align lines according to desired alignment.
-        for line in self.lines.iter_mut() {
+        for (line, &wrapped) in self.lines.iter_mut().zip(wrapped.iter()) {
+            if justify && wrapped {
+                // Justified line occupies whole width.
+                line.x_offset = 0.0;
+                continue;
+            }
             match self.horizontal_alignment {
                 HorizontalAlignment::Left => line.x_offset = 0.0,
                 HorizontalAlignment::Center => {
@@ -283,12 +485,55 @@ impl FormattedText {
             self.constraint.x
         };
 
+        self.char_layout.clear();
+        self.char_layout
+            .resize(self.text.len(), CharLayout::default());
+
         let mut cursor = Vector2::new(cursor_x_start, cursor_y_start);
-        for line in self.lines.iter_mut() {
+        for (line, &wrapped) in self.lines.iter_mut().zip(wrapped.iter()) {
             cursor.x = line.x_offset;
 
-            for code_index in line.begin..line.end {
-                let code = self.text[code_index];
+            line.rtl = match bidi_info.as_ref() {
+                Some(bidi_info) => {
+                    fill_visual_order(bidi_info, &byte_offsets, line, &mut visual_order)
+                }
+                None => {
+                    visual_order.clear();
+                    visual_order.extend(line.begin..line.end);
+                    false
+                }
+            };
+
+            // Justify wrapped lines by expanding whitespaces between words, trailing
+            // whitespaces are not expanded.
+            let content_end = (line.begin..line.end)
+                .rev()
+                .find(|&i| !is_space(shaped[i]))
+                .map_or(line.begin, |i| i + 1);
+            let space_count = (line.begin..content_end)
+                .filter(|&i| is_space(shaped[i]))
+                .count();
+            let space_extra = if justify && wrapped && space_count > 0 {
+                (self.constraint.x - line.width).max(0.0) / space_count as f32
+            } else {
+                0.0
+            };
+
+            for &code_index in visual_order.iter() {
+                let code = shaped[code_index];
+
+                let extra = if code_index < content_end && is_space(code) {
+                    space_extra
+                } else {
+                    0.0
+                };
+
+                if let Some(layout) = self.char_layout.get_mut(code_index) {
+                    layout.x = cursor.x - line.x_offset;
+                    layout.advance = font.glyph_advance(code) + extra;
+                    layout.rtl = is_rtl(code_index);
+                }
+                cursor.x += extra;
 
                 match font.glyph(code) {
                     Some(glyph) => {
@@ -304,6 +549,7 @@ impl FormattedText {
                         let text_glyph = TextGlyph {
                             bounds: rect,
                             tex_coords: glyph.tex_coords,
+                            code,
                         };
                         self.glyphs.push(text_glyph);
 
@@ -320,6 +566,7 @@ impl FormattedText {
                         self.glyphs.push(TextGlyph {
                             bounds: rect,
                             tex_coords: [Vector2::default(); 4],
+                            code,
                         });
                         cursor.x += rect.w();
                     }
@@ -330,6 +577,13 @@ impl FormattedText {
             cursor.y += font.ascender();
         }
 
+        drop(bidi_info);
+        self.utf8 = utf8;
+        self.byte_offsets = byte_offsets;
+        self.shaped = shaped;
+        self.wrapped = wrapped;
+        self.visual_order = visual_order;
+
         let mut full_size = Vector2::new(0.0, total_height);
         for line in self.lines.iter() {
             full_size.x = line.width.max(full_size.x);
@@ -338,6 +592,51 @@ impl FormattedText {
     }
 }
 
+/// Fills `order` with indices of characters of a line in visual order (left to right) and
+/// returns base direction of the line.
+fn fill_visual_order(
+    bidi_info: &BidiInfo,
+    byte_offsets: &[usize],
+    line: &TextLine,
+    order: &mut Vec<usize>,
+) -> bool {
+    let char_count = byte_offsets.len() - 1;
+    let begin = line.begin.min(char_count);
+    let end = line.end.min(char_count).max(begin);
+    let (byte_begin, byte_end) = (byte_offsets[begin], byte_offsets[end]);
+
+    order.clear();
+
+    let paragraph = bidi_info
+        .paragraphs
+        .iter()
+        .find(|paragraph| paragraph.range.start <= byte_begin && byte_begin < paragraph.range.end);
+
+    if let Some(paragraph) = paragraph {
+        let paragraph_end = byte_end.min(paragraph.range.end);
+        if byte_begin < paragraph_end {
+            let (levels, runs) = bidi_info.visual_runs(paragraph, byte_begin..paragraph_end);
+            let to_char_index = |byte| byte_offsets.binary_search(&byte).unwrap_or(char_count);
+            for run in runs {
+                let chars = to_char_index(run.start)..to_char_index(run.end);
+                if levels[run.start].is_rtl() {
+                    order.extend(chars.rev());
+                } else {
+                    order.extend(chars);
+                }
+            }
+            // Characters of other paragraphs (if any) are left in logical order.
+            order.extend(to_char_index(paragraph_end)..end);
+        } else {
+            order.extend(begin..end);
+        }
+        return paragraph.level.is_rtl();
+    }
+
+    order.extend(begin..end);
+    false
+}
+
 pub struct FormattedTextBuilder {
     font: Option<SharedFont>,
     brush: Brush,
@@ -345,7 +644,9 @@ pub struct FormattedTextBuilder {
     text: String,
     vertical_alignment: VerticalAlignment,
     horizontal_alignment: HorizontalAlignment,
-    wrap: bool,
+    wrap: WrapMode,
+    justify: bool,
+    direction: TextDirection,
 }
 
 impl Default for FormattedTextBuilder {
@@ -364,7 +665,9 @@ impl FormattedTextBuilder {
             vertical_alignment: VerticalAlignment::Top,
             brush: Brush::Solid(Color::WHITE),
             constraint: Vector2::new(128.0, 128.0),
-            wrap: false,
+            wrap: WrapMode::NoWrap,
+            justify: false,
+            direction: TextDirection::Auto,
         }
     }
 
@@ -378,7 +681,7 @@ impl FormattedTextBuilder {
         self
     }
 
-    pub fn with_wrap(mut self, wrap: bool) -> Self {
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
         self.wrap = wrap;
         self
     }
@@ -388,6 +691,12 @@ impl FormattedTextBuilder {
         self
     }
 
+    /// See [`FormattedText::set_justify`].
+    pub fn with_justify(mut self, justify: bool) -> Self {
+        self.justify = justify;
+        self
+    }
+
     pub fn with_text(mut self, text: String) -> Self {
         self.text = text;
         self
@@ -403,6 +712,11 @@ impl FormattedTextBuilder {
         self
     }
 
+    pub fn with_direction(mut self, direction: TextDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
     pub fn build(self) -> FormattedText {
         FormattedText {
             font: self.font,
@@ -414,6 +728,192 @@ impl FormattedTextBuilder {
             brush: self.brush,
             constraint: self.constraint,
             wrap: self.wrap,
+            justify: self.justify,
+            atlas_revision: 0,
+            direction: self.direction,
+            char_layout: Vec::new(),
+            utf8: String::new(),
+            byte_offsets: Vec::new(),
+            shaped: Vec::new(),
+            wrapped: Vec::new(),
+            visual_order: Vec::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::algebra::Vector2,
+        formatted_text::{FormattedText, FormattedTextBuilder},
+        HorizontalAlignment, TextDirection, WrapMode,
+    };
+
+    fn build(text: &str, direction: TextDirection) -> FormattedText {
+        let mut text = FormattedTextBuilder::new()
+            .with_font(crate::DEFAULT_FONT.clone())
+            .with_constraint(Vector2::new(f32::INFINITY, f32::INFINITY))
+            .with_direction(direction)
+            .with_text(text.to_owned())
+            .build();
+        text.build();
+        text
+    }
+
+    fn x(text: &FormattedText, index: usize) -> f32 {
+        text.char_span(index).unwrap().0
+    }
+
+    fn width(text: &str) -> f32 {
+        let codes = text.chars().map(|c| c as u32).collect::<Vec<_>>();
+        let mut font = crate::DEFAULT_FONT.0.lock().unwrap();
+        font.cache_glyphs(&codes);
+        codes.iter().map(|&c| font.glyph_advance(c)).sum()
+    }
+
+    fn wrap(text: &str, wrap: WrapMode, max_width: f32) -> FormattedText {
+        let mut text = FormattedTextBuilder::new()
+            .with_font(crate::DEFAULT_FONT.clone())
+            .with_constraint(Vector2::new(max_width, f32::INFINITY))
+            .with_wrap(wrap)
+            .with_text(text.to_owned())
+            .build();
+        text.build();
+        text
+    }
+
+    fn lines(text: &FormattedText) -> Vec<String> {
+        let chars = text.text().chars().collect::<Vec<_>>();
+        text.get_lines()
+            .iter()
+            .map(|line| chars[line.begin..line.end].iter().collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_word_wrap() {
+        let text = wrap("one two three", WrapMode::Word, width("one two") + 1.0);
+        assert_eq!(lines(&text), vec!["one two ", "three"]);
+        // Trailing whitespace is not included in width of the line.
+        assert_eq!(text.get_lines()[0].width, width("one two"));
+
+        // Whole text fits.
+        let text = wrap("one two three", WrapMode::Word, 10000.0);
+        assert_eq!(lines(&text), vec!["one two three"]);
+
+        let text = wrap("one two three", WrapMode::NoWrap, 1.0);
+        assert_eq!(lines(&text), vec!["one two three"]);
+
+        // Line breaks are kept in every mode, "\r\n" is a single break.
+        let text = wrap("one\r\ntwo\n\nthree", WrapMode::Word, 10000.0);
+        assert_eq!(lines(&text), vec!["one", "two", "", "three"]);
+    }
+
+    #[test]
+    fn test_letter_wrap() {
+        let text = wrap("abc def", WrapMode::Letter, width("abc d") + 1.0);
+        assert_eq!(lines(&text), vec!["abc d", "ef"]);
+    }
+
+    #[test]
+    fn test_long_word_wrap() {
+        // Word that is longer than available width is split at any character, next word
+        // starts on a new line.
+        let max_width = width("abcd") + 1.0;
+        let text = wrap("abcdefghij xy", WrapMode::Word, max_width);
+        assert_eq!(lines(&text), vec!["abcd", "efgh", "ij ", "xy"]);
+        assert!(text.get_lines().iter().all(|line| line.width <= max_width));
+
+        // Character that is wider than available width takes a whole line.
+        let text = wrap("ab", WrapMode::Word, 1.0);
+        assert_eq!(lines(&text), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_justify() {
+        let max_width = width("aa bb cc") + 1.0;
+        let build = |justify: bool| {
+            let mut text = FormattedTextBuilder::new()
+                .with_font(crate::DEFAULT_FONT.clone())
+                .with_constraint(Vector2::new(max_width, f32::INFINITY))
+                .with_wrap(WrapMode::Word)
+                .with_horizontal_alignment(HorizontalAlignment::Center)
+                .with_justify(justify)
+                .with_text("aa bb cc dd\nee ff".to_owned())
+                .build();
+            text.build();
+            text
+        };
+
+        let text = build(true);
+        assert_eq!(lines(&text), vec!["aa bb cc ", "dd", "ee ff"]);
+        // Wrapped line is stretched to the whole width.
+        let (last_x, last_advance) = text.char_span(7).unwrap();
+        assert_eq!(x(&text, 0), 0.0);
+        assert!((last_x + last_advance - max_width).abs() < 1.0e-3);
+        // Words are not stretched, only spaces between them.
+        assert_eq!(x(&text, 1) - x(&text, 0), width("a"));
+        // Last line of paragraph and line ended by line break are centered.
+        for line in &text.get_lines()[1..] {
+            assert_eq!(line.x_offset, 0.5 * (max_width - line.width));
+        }
+
+        let text = build(false);
+        let line = text.get_lines()[0];
+        assert_eq!(line.x_offset, 0.5 * (max_width - line.width));
+        let (last_x, last_advance) = text.char_span(7).unwrap();
+        assert_eq!(last_x + last_advance, line.width);
+    }
+
+    #[test]
+    fn test_mixed_direction() {
+        // Hebrew word inside of left-to-right paragraph is reversed: "abc גבא def".
+        let text = build("abc \u{5D0}\u{5D1}\u{5D2} def", TextDirection::Auto);
+        assert!(!text.get_lines()[0].rtl);
+        assert!(x(&text, 0) < x(&text, 1) && x(&text, 2) < x(&text, 3));
+        assert!(x(&text, 3) < x(&text, 6));
+        assert!(x(&text, 6) < x(&text, 5) && x(&text, 5) < x(&text, 4));
+        assert!(x(&text, 4) < x(&text, 7));
+        assert!(x(&text, 7) < x(&text, 8) && x(&text, 9) < x(&text, 10));
+
+        // Same text with right-to-left base direction: latin words are placed at the left,
+        // and the first one is at the right side.
+        let text = build("abc \u{5D0}\u{5D1}\u{5D2} def", TextDirection::RightToLeft);
+        assert!(text.get_lines()[0].rtl);
+        assert!(x(&text, 8) < x(&text, 9) && x(&text, 9) < x(&text, 10));
+        assert!(x(&text, 10) < x(&text, 6));
+        assert!(x(&text, 6) < x(&text, 5) && x(&text, 5) < x(&text, 4));
+        assert!(x(&text, 4) < x(&text, 0));
+        assert!(x(&text, 0) < x(&text, 1) && x(&text, 1) < x(&text, 2));
+    }
+
+    #[test]
+    fn test_mirroring() {
+        // Brackets of right-to-left run are mirrored, so visually "(ב)א" is shown.
+        let text = build("\u{5D0}(\u{5D1})", TextDirection::Auto);
+        let codes = text
+            .get_glyphs()
+            .iter()
+            .map(|g| std::char::from_u32(g.code()).unwrap())
+            .collect::<String>();
+        assert_eq!(codes, "(\u{5D1})\u{5D0}");
+    }
+
+    #[test]
+    fn test_rebuild_reuses_layout() {
+        let mut text = build("abc \u{5D0}\u{5D1}", TextDirection::Auto);
+        let mixed_width = text.get_lines()[0].width;
+
+        // Left-to-right text is laid out without bidirectional analysis.
+        text.set_text("abc");
+        text.build();
+        assert!(!text.get_lines()[0].rtl);
+        assert!(x(&text, 0) < x(&text, 1) && x(&text, 1) < x(&text, 2));
+        assert!(text.char_span(3).is_none());
+
+        text.set_text("abc \u{5D0}\u{5D1}");
+        text.build();
+        assert_eq!(text.get_lines()[0].width, mixed_width);
+        assert!(x(&text, 5) < x(&text, 4));
+    }
+}
@@ -1,18 +1,37 @@
 use crate::{
     brush::Brush,
-    core::{algebra::Vector2, color::Color, pool::Handle},
+    core::{algebra::Vector2, color::Color, math::Rect, pool::Handle},
     draw::{CommandKind, CommandTexture, DrawingContext, SharedTexture},
     message::{ImageMessage, MessageData, UiMessage, UiMessageData},
     widget::{Widget, WidgetBuilder},
-    BuildContext, Control, UINode, UserInterface,
+    BuildContext, Control, Thickness, UINode, UserInterface,
 };
 use std::ops::{Deref, DerefMut};
 
+/// Nine-slice (nine-patch) mode of an image: texture is split into 9 parts by insets, corners
+/// keep their size, edges are stretched along one axis and center is stretched along both.
+/// Useful for scalable panels and buttons.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NineSlice {
+    /// Size of borders on screen in pixels.
+    pub insets: Thickness,
+    /// Size of borders in texture in fractions of uv rect, for example `0.25` means a quarter
+    /// of width (or height) of uv rect.
+    pub texture_insets: Thickness,
+}
+
+/// Image widget shows a texture, or a part of it defined by rectangle of texture coordinates
+/// (useful for texture atlases). Texture can be either stretched over the whole widget,
+/// repeated with given tile size or drawn in nine-slice mode (see `NineSlice`).
 #[derive(Clone)]
 pub struct Image<M: MessageData, C: Control<M, C>> {
     widget: Widget<M, C>,
     texture: Option<SharedTexture>,
-    flip: bool,
+    flip_horizontal: bool,
+    flip_vertical: bool,
+    uv_rect: Rect<f32>,
+    tiling: Option<Vector2<f32>>,
+    nine_slice: Option<NineSlice>,
 }
 
 crate::define_widget_deref!(Image<M, C>);
@@ -22,29 +41,107 @@ impl<M: MessageData, C: Control<M, C>> Image<M, C> {
         Self {
             widget,
             texture: None,
-            flip: false,
+            flip_horizontal: false,
+            flip_vertical: false,
+            uv_rect: Rect::new(0.0, 0.0, 1.0, 1.0),
+            tiling: None,
+            nine_slice: None,
         }
     }
 
     pub fn set_texture(&mut self, texture: SharedTexture) {
         self.texture = Some(texture);
     }
+
+    pub fn texture(&self) -> Option<&SharedTexture> {
+        self.texture.as_ref()
+    }
+
+    pub fn is_flipped_horizontally(&self) -> bool {
+        self.flip_horizontal
+    }
+
+    pub fn is_flipped_vertically(&self) -> bool {
+        self.flip_vertical
+    }
+
+    pub fn uv_rect(&self) -> Rect<f32> {
+        self.uv_rect
+    }
+
+    pub fn tiling(&self) -> Option<Vector2<f32>> {
+        self.tiling
+    }
+
+    pub fn nine_slice(&self) -> Option<NineSlice> {
+        self.nine_slice
+    }
+
+    /// Maps local coordinates of a quad (in [0; 1] range within uv rect) to texture
+    /// coordinates taking flipping into account.
+    fn tex_coords(&self, min: Vector2<f32>, max: Vector2<f32>) -> [Vector2<f32>; 4] {
+        let (u0, u1) = if self.flip_horizontal {
+            (1.0 - min.x, 1.0 - max.x)
+        } else {
+            (min.x, max.x)
+        };
+        let (v0, v1) = if self.flip_vertical {
+            (1.0 - min.y, 1.0 - max.y)
+        } else {
+            (min.y, max.y)
+        };
+        let uv = |u: f32, v: f32| {
+            Vector2::new(
+                self.uv_rect.x() + u * self.uv_rect.w(),
+                self.uv_rect.y() + v * self.uv_rect.h(),
+            )
+        };
+        [uv(u0, v0), uv(u1, v0), uv(u1, v1), uv(u0, v1)]
+    }
 }
 
 impl<M: MessageData, C: Control<M, C>> Control<M, C> for Image<M, C> {
     fn draw(&self, drawing_context: &mut DrawingContext) {
         let bounds = self.widget.screen_bounds();
-        let tex_coords = if self.flip {
-            Some([
-                Vector2::new(0.0, 0.0),
-                Vector2::new(1.0, 0.0),
-                Vector2::new(1.0, -1.0),
-                Vector2::new(0.0, -1.0),
-            ])
-        } else {
-            None
-        };
-        drawing_context.push_rect_filled(&bounds, tex_coords.as_ref());
+        match (self.nine_slice, self.tiling) {
+            (Some(nine_slice), _) => {
+                let tex_coords = self.tex_coords(Vector2::new(0.0, 0.0), Vector2::new(1.0, 1.0));
+                drawing_context.push_nine_slice(
+                    &bounds,
+                    nine_slice.insets,
+                    nine_slice.texture_insets,
+                    &tex_coords,
+                );
+            }
+            (None, Some(tile_size)) => {
+                // Tiles are generated as separate quads instead of using repeating texture
+                // coordinates, this way tiling works with a part of texture atlas too.
+                // Tiles at right and bottom edges are cut.
+                let tile_size = Vector2::new(tile_size.x.max(1.0), tile_size.y.max(1.0));
+                let mut y = 0.0;
+                while y < bounds.h() {
+                    let h = tile_size.y.min(bounds.h() - y);
+                    let mut x = 0.0;
+                    while x < bounds.w() {
+                        let w = tile_size.x.min(bounds.w() - x);
+                        let tex_coords = self.tex_coords(
+                            Vector2::new(0.0, 0.0),
+                            Vector2::new(w / tile_size.x, h / tile_size.y),
+                        );
+                        drawing_context.push_rect_filled(
+                            &Rect::new(bounds.x() + x, bounds.y() + y, w, h),
+                            Some(&tex_coords),
+                        );
+                        x += tile_size.x;
+                    }
+                    y += tile_size.y;
+                }
+            }
+            (None, None) => {
+                let tex_coords = self.tex_coords(Vector2::new(0.0, 0.0), Vector2::new(1.0, 1.0));
+                drawing_context.push_rect_filled(&bounds, Some(&tex_coords));
+            }
+        }
         let texture = self
             .texture
             .as_ref()
@@ -65,8 +162,24 @@ impl<M: MessageData, C: Control<M, C>> Control<M, C> for Image<M, C> {
                     ImageMessage::Texture(tex) => {
                         self.texture = tex.clone();
                     }
+                    #[allow(deprecated)]
                     &ImageMessage::Flip(flip) => {
-                        self.flip = flip;
+                        self.flip_vertical = flip;
+                    }
+                    &ImageMessage::FlipHorizontal(flip) => {
+                        self.flip_horizontal = flip;
+                    }
+                    &ImageMessage::FlipVertical(flip) => {
+                        self.flip_vertical = flip;
+                    }
+                    &ImageMessage::UvRect(uv_rect) => {
+                        self.uv_rect = uv_rect;
+                    }
+                    &ImageMessage::Tiling(tiling) => {
+                        self.tiling = tiling;
+                    }
+                    &ImageMessage::NineSlice(nine_slice) => {
+                        self.nine_slice = nine_slice;
                     }
                 }
             }
@@ -77,7 +190,11 @@ impl<M: MessageData, C: Control<M, C>> Control<M, C> for Image<M, C> {
 pub struct ImageBuilder<M: MessageData, C: Control<M, C>> {
     widget_builder: WidgetBuilder<M, C>,
     texture: Option<SharedTexture>,
-    flip: bool,
+    flip_horizontal: bool,
+    flip_vertical: bool,
+    uv_rect: Rect<f32>,
+    tiling: Option<Vector2<f32>>,
+    nine_slice: Option<NineSlice>,
 }
 
 impl<M: MessageData, C: Control<M, C>> ImageBuilder<M, C> {
@@ -85,12 +202,48 @@ impl<M: MessageData, C: Control<M, C>> ImageBuilder<M, C> {
         Self {
             widget_builder,
             texture: None,
-            flip: false,
+            flip_horizontal: false,
+            flip_vertical: false,
+            uv_rect: Rect::new(0.0, 0.0, 1.0, 1.0),
+            tiling: None,
+            nine_slice: None,
         }
     }
 
-    pub fn with_flip(mut self, flip: bool) -> Self {
-        self.flip = flip;
+    /// Same as `with_flip_vertical`, kept for compatibility.
+    #[deprecated(note = "use `with_flip_vertical` instead")]
+    pub fn with_flip(self, flip: bool) -> Self {
+        self.with_flip_vertical(flip)
+    }
+
+    pub fn with_flip_horizontal(mut self, flip: bool) -> Self {
+        self.flip_horizontal = flip;
+        self
+    }
+
+    pub fn with_flip_vertical(mut self, flip: bool) -> Self {
+        self.flip_vertical = flip;
+        self
+    }
+
+    /// Sets rectangle of texture coordinates that will be shown, by default it is whole
+    /// texture - (0, 0, 1, 1).
+    pub fn with_uv_rect(mut self, uv_rect: Rect<f32>) -> Self {
+        self.uv_rect = uv_rect;
+        self
+    }
+
+    /// Sets size of a tile in pixels, texture (or its part defined by uv rect) will be repeated
+    /// to fill the image. `None` - texture is stretched over the whole image.
+    pub fn with_tiling(mut self, tile_size: Option<Vector2<f32>>) -> Self {
+        self.tiling = tile_size;
+        self
+    }
+
+    /// Enables nine-slice mode, it takes precedence over tiling. `None` - nine-slice mode
+    /// is disabled.
+    pub fn with_nine_slice(mut self, nine_slice: Option<NineSlice>) -> Self {
+        self.nine_slice = nine_slice;
         self
     }
 
@@ -112,7 +265,11 @@ impl<M: MessageData, C: Control<M, C>> ImageBuilder<M, C> {
         let image = Image {
             widget: self.widget_builder.build(),
             texture: self.texture,
-            flip: self.flip,
+            flip_horizontal: self.flip_horizontal,
+            flip_vertical: self.flip_vertical,
+            uv_rect: self.uv_rect,
+            tiling: self.tiling,
+            nine_slice: self.nine_slice,
         };
         UINode::Image(image)
     }
@@ -121,3 +278,106 @@ impl<M: MessageData, C: Control<M, C>> ImageBuilder<M, C> {
         ctx.add_node(self.build_node())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{algebra::Vector2, math::Rect},
+        draw::DrawingContext,
+        image::{Image, ImageBuilder},
+        message::{ImageMessage, MessageDirection},
+        node::StubNode,
+        widget::WidgetBuilder,
+        Control, UserInterface,
+    };
+
+    #[test]
+    fn test_tex_coords() {
+        let mut image = Image::<(), StubNode>::new(WidgetBuilder::new().build());
+        image.uv_rect = Rect::new(0.5, 0.25, 0.5, 0.5);
+
+        let (min, max) = (Vector2::new(0.0, 0.0), Vector2::new(1.0, 1.0));
+        assert_eq!(
+            image.tex_coords(min, max),
+            [
+                Vector2::new(0.5, 0.25),
+                Vector2::new(1.0, 0.25),
+                Vector2::new(1.0, 0.75),
+                Vector2::new(0.5, 0.75)
+            ]
+        );
+
+        image.flip_horizontal = true;
+        assert_eq!(
+            image.tex_coords(min, max),
+            [
+                Vector2::new(1.0, 0.25),
+                Vector2::new(0.5, 0.25),
+                Vector2::new(0.5, 0.75),
+                Vector2::new(1.0, 0.75)
+            ]
+        );
+
+        image.flip_horizontal = false;
+        image.flip_vertical = true;
+        assert_eq!(
+            image.tex_coords(min, Vector2::new(0.5, 0.5)),
+            [
+                Vector2::new(0.5, 0.75),
+                Vector2::new(0.75, 0.75),
+                Vector2::new(0.75, 0.5),
+                Vector2::new(0.5, 0.5)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tiling() {
+        let screen_size = Vector2::new(1000.0, 1000.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+        let image = ImageBuilder::new(WidgetBuilder::new().with_width(25.0).with_height(10.0))
+            .with_uv_rect(Rect::new(0.0, 0.0, 0.5, 1.0))
+            .with_tiling(Some(Vector2::new(10.0, 10.0)))
+            .build(&mut ui.build_ctx());
+        ui.update(screen_size, 0.0);
+
+        let mut ctx = DrawingContext::new();
+        ui.node(image).draw(&mut ctx);
+        let vertices = ctx.get_vertices();
+
+        // Two full tiles and a half of tile at the right edge.
+        assert_eq!(vertices.len(), 3 * 4);
+        for (i, x) in [0.0, 10.0, 20.0].iter().enumerate() {
+            assert_eq!(vertices[i * 4].pos, Vector2::new(*x, 0.0));
+            assert_eq!(vertices[i * 4].tex_coord, Vector2::new(0.0, 0.0));
+        }
+        assert_eq!(vertices[2].pos, Vector2::new(10.0, 10.0));
+        assert_eq!(vertices[6].pos, Vector2::new(20.0, 10.0));
+        assert_eq!(vertices[6].tex_coord, Vector2::new(0.5, 1.0));
+        // Cut tile shows only a half of uv rect.
+        assert_eq!(vertices[10].pos, Vector2::new(25.0, 10.0));
+        assert_eq!(vertices[10].tex_coord, Vector2::new(0.25, 1.0));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_deprecated_flip() {
+        let screen_size = Vector2::new(1000.0, 1000.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+        let image = ImageBuilder::new(WidgetBuilder::new())
+            .with_flip(true)
+            .build(&mut ui.build_ctx());
+        let is_flipped = |ui: &UserInterface<(), StubNode>| match ui.node(image) {
+            crate::UINode::Image(image) => (
+                image.is_flipped_horizontally(),
+                image.is_flipped_vertically(),
+            ),
+            _ => unreachable!(),
+        };
+        assert_eq!(is_flipped(&ui), (false, true));
+
+        ui.send_message(ImageMessage::flip(image, MessageDirection::ToWidget, false));
+        while let Some(_) = ui.poll_message() {}
+        assert_eq!(is_flipped(&ui), (false, false));
+    }
+}
@@ -0,0 +1,115 @@
+//! Saving and restoring of user interface layout.
+//!
+//! Layout is a set of parameters which user usually changes in run-time: positions and sizes of
+//! windows, whether they are open or minimized, positions of splitters of docking manager tiles.
+//! It is useful for editors and tools, where layout should be kept between sessions.
+//!
+//! Widget handles are not stable - they depend on order in which widgets are created, so layout
+//! uses names of widgets (see `WidgetBuilder::with_name`) as identifiers. Only windows and tiles
+//! with non-empty names are saved, names must be unique, otherwise only one widget of a set with
+//! same name will be saved and restored.
+//!
+//! Docking tree itself is not restored - widgets must be created in the same docking hierarchy
+//! as before, only parameters of named windows and tiles are applied.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d_ui::{core::visitor::Visitor, layout::UiLayout, node::StubNode, UserInterface};
+//! use rg3d_ui::core::visitor::Visit;
+//!
+//! fn save(ui: &UserInterface<(), StubNode>) {
+//!     let mut layout = ui.save_layout();
+//!     let mut visitor = Visitor::new();
+//!     layout.visit("Layout", &mut visitor).unwrap();
+//!     visitor.save_binary("layout.bin").unwrap();
+//! }
+//!
+//! fn restore(ui: &mut UserInterface<(), StubNode>) {
+//!     let mut visitor = Visitor::load_binary("layout.bin").unwrap();
+//!     let mut layout = UiLayout::default();
+//!     layout.visit("Layout", &mut visitor).unwrap();
+//!     ui.restore_layout(&layout);
+//! }
+//! ```
+
+use crate::core::{
+    algebra::Vector2,
+    visitor::{Visit, VisitResult, Visitor},
+};
+use std::collections::HashMap;
+
+/// Saved state of a window.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WindowLayout {
+    /// Desired position of the window in local coordinates.
+    pub position: Vector2<f32>,
+    /// Explicit size of the window, NaN components mean that size was not set explicitly
+    /// and it won't be restored.
+    pub size: Vector2<f32>,
+    /// Whether window is minimized or not.
+    pub minimized: bool,
+    /// Whether window is open (visible) or not.
+    pub open: bool,
+}
+
+impl Default for WindowLayout {
+    fn default() -> Self {
+        Self {
+            position: Default::default(),
+            size: Vector2::new(std::f32::NAN, std::f32::NAN),
+            minimized: false,
+            open: true,
+        }
+    }
+}
+
+impl Visit for WindowLayout {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.position.visit("Position", visitor)?;
+        self.size.visit("Size", visitor)?;
+        self.minimized.visit("Minimized", visitor)?;
+        self.open.visit("Open", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Saved state of a docking manager tile.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TileLayout {
+    /// Position of splitter in `[0; 1]` range.
+    pub splitter: f32,
+}
+
+impl Visit for TileLayout {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.splitter.visit("Splitter", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Layout of user interface. See module docs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UiLayout {
+    /// Mapping window name -> window state.
+    pub windows: HashMap<String, WindowLayout>,
+    /// Mapping tile name -> tile state. Only split tiles are stored.
+    pub tiles: HashMap<String, TileLayout>,
+}
+
+impl Visit for UiLayout {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.windows.visit("Windows", visitor)?;
+        self.tiles.visit("Tiles", visitor)?;
+
+        visitor.leave_region()
+    }
+}
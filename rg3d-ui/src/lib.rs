@@ -10,15 +10,18 @@
 extern crate lazy_static;
 extern crate fontdue;
 extern crate sysinfo;
+extern crate unicode_bidi;
 
 pub use rg3d_core as core;
 
+mod bidi;
 pub mod border;
 pub mod brush;
 pub mod button;
 pub mod canvas;
 pub mod check_box;
 pub mod color;
+pub mod command;
 pub mod decorator;
 pub mod dock;
 pub mod draw;
@@ -27,6 +30,7 @@ pub mod file_browser;
 pub mod formatted_text;
 pub mod grid;
 pub mod image;
+pub mod layout;
 pub mod list_view;
 pub mod menu;
 pub mod message;
@@ -35,6 +39,7 @@ pub mod node;
 pub mod numeric;
 pub mod popup;
 pub mod progress_bar;
+pub mod rich_text;
 pub mod scroll_bar;
 pub mod scroll_panel;
 pub mod scroll_viewer;
@@ -60,9 +65,11 @@ use crate::{
         scope_profile,
     },
     draw::{CommandKind, CommandTexture, DrawingContext},
+    layout::{TileLayout, UiLayout, WindowLayout},
     message::{
-        ButtonState, CursorIcon, KeyboardModifiers, MessageData, MessageDirection, MouseButton,
-        OsEvent, UiMessage, UiMessageData, WidgetMessage,
+        ButtonState, CursorIcon, HotKey, KeyCode, KeyboardModifiers, MessageData, MessageDirection,
+        MouseButton, OsEvent, RoutingPhase, RoutingStrategy, TileMessage, UiMessage, UiMessageData,
+        WidgetMessage, WindowMessage,
     },
     node::UINode,
     ttf::{Font, SharedFont},
@@ -95,6 +102,42 @@ pub enum VerticalAlignment {
     Bottom,
 }
 
+/// Base direction of text. Text can contain runs of both directions, base direction
+/// defines order of such runs and a side at which text of a line begins.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TextDirection {
+    /// Direction is taken from first strong character (letter) of each paragraph.
+    Auto,
+    /// Left-to-right, latin, cyrillic, etc.
+    LeftToRight,
+    /// Right-to-left, arabic, hebrew, etc.
+    RightToLeft,
+}
+
+impl Default for TextDirection {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+/// Defines how text is split on lines when it does not fit in available width.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum WrapMode {
+    /// Text is split on lines only by line break characters.
+    NoWrap,
+    /// Text is split at any character.
+    Letter,
+    /// Text is split at whitespaces between words, words that are longer than available
+    /// width are split at any character.
+    Word,
+}
+
+impl Default for WrapMode {
+    fn default() -> Self {
+        Self::NoWrap
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Thickness {
     pub left: f32,
@@ -180,6 +223,80 @@ impl Thickness {
     }
 }
 
+/// Anchors define a rectangle of a widget relative to the rectangle given to the widget by its
+/// parent panel (whole client area for most widgets, a cell for a grid, whole area of a canvas)
+/// so the widget will stick to edges of the parent or stretch with it when the parent resizes.
+///
+/// `min` and `max` are positions of top-left and bottom-right corners of the widget in fractions
+/// of the parent rectangle (`(0, 0)` is top-left corner, `(1, 1)` is bottom-right corner),
+/// `offset_min` and `offset_max` are offsets in pixels added to these corners. When `min` and
+/// `max` are equal on an axis, the widget is pinned to a point of the parent and has fixed size
+/// on that axis, otherwise it stretches. For example a 100x50 widget at bottom-right corner with
+/// 10 pixel margin is `Anchors::pinned(Vector2::new(1.0, 1.0), Vector2::new(-10.0, -10.0),
+/// Vector2::new(100.0, 50.0))`.
+///
+/// Anchored widgets ignore margin, alignments and explicit width and height in arrange pass.
+/// Min and max size are still respected.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Anchors {
+    pub min: Vector2<f32>,
+    pub max: Vector2<f32>,
+    pub offset_min: Vector2<f32>,
+    pub offset_max: Vector2<f32>,
+}
+
+impl Default for Anchors {
+    fn default() -> Self {
+        Self::stretch(Thickness::zero())
+    }
+}
+
+impl Anchors {
+    /// Creates anchors with given relative corners and zero offsets.
+    pub fn new(min: Vector2<f32>, max: Vector2<f32>) -> Self {
+        Self {
+            min,
+            max,
+            offset_min: Vector2::default(),
+            offset_max: Vector2::default(),
+        }
+    }
+
+    pub fn with_offsets(mut self, offset_min: Vector2<f32>, offset_max: Vector2<f32>) -> Self {
+        self.offset_min = offset_min;
+        self.offset_max = offset_max;
+        self
+    }
+
+    /// Stretches widget over whole parent rectangle with given distances to each edge.
+    pub fn stretch(margin: Thickness) -> Self {
+        Self::new(Vector2::new(0.0, 0.0), Vector2::new(1.0, 1.0)).with_offsets(
+            Vector2::new(margin.left, margin.top),
+            Vector2::new(-margin.right, -margin.bottom),
+        )
+    }
+
+    /// Pins a point of widget with fixed size to the same point of parent rectangle, `point` is
+    /// in fractions of size (`(0.5, 0.5)` is center), `offset` is distance in pixels between the
+    /// points.
+    pub fn pinned(point: Vector2<f32>, offset: Vector2<f32>, size: Vector2<f32>) -> Self {
+        let offset_min = offset - size.component_mul(&point);
+        Self::new(point, point).with_offsets(offset_min, offset_min + size)
+    }
+
+    /// Calculates rectangle of widget for given parent rectangle.
+    pub fn rect(&self, parent: &Rect<f32>) -> Rect<f32> {
+        let min = parent.position + parent.size.component_mul(&self.min) + self.offset_min;
+        let max = parent.position + parent.size.component_mul(&self.max) + self.offset_max;
+        Rect::new(
+            min.x,
+            min.y,
+            (max.x - min.x).max(0.0),
+            (max.y - min.y).max(0.0),
+        )
+    }
+}
+
 type NodeHandle<M, C> = Handle<UINode<M, C>>;
 
 pub struct NodeHandleMapping<M: MessageData, C: Control<M, C>> {
@@ -220,6 +337,15 @@ impl<M: MessageData, C: Control<M, C>> NodeHandleMapping<M, C> {
     }
 }
 
+/// Notification about layout invalidation of a node. Widgets send these to user interface,
+/// which then propagates the invalidation up to the root so only affected parts of the tree
+/// will be re-calculated.
+#[derive(Debug)]
+pub(in crate) enum LayoutEvent<M: MessageData, C: Control<M, C>> {
+    MeasurementInvalidated(Handle<UINode<M, C>>),
+    ArrangementInvalidated(Handle<UINode<M, C>>),
+}
+
 /// Trait for all UI controls in library.
 pub trait Control<M, C>: 'static + Deref<Target = Widget<M, C>> + DerefMut + Clone
 where
@@ -254,6 +380,17 @@ where
         if self.visibility() {
             self.prev_arrange.set(*final_rect);
 
+            if let Some(anchors) = self.anchors() {
+                let rect = anchors.rect(final_rect);
+                let size = Vector2::new(
+                    rect.w().min(self.max_size().x).max(self.min_size().x),
+                    rect.h().min(self.max_size().y).max(self.min_size().y),
+                );
+                let size = self.arrange_override(ui, size);
+                self.commit_arrange(rect.position, size);
+                return;
+            }
+
             let margin = self.margin().axes_margin();
 
             let mut size = Vector2::new(
@@ -304,32 +441,15 @@ where
         }
     }
 
-    fn is_measure_valid(&self, ui: &UserInterface<M, C>) -> bool {
-        let mut valid = self.deref().is_measure_valid()
-            && self.prev_global_visibility == self.is_globally_visible();
-        if valid {
-            for child in self.children() {
-                valid &= ui.node(*child).is_measure_valid(ui);
-                if !valid {
-                    break;
-                }
-            }
-        }
-        valid
+    /// Returns true if cached measurement of the node can be reused. There is no need to check
+    /// descendants here - invalidation of any descendant marks every ancestor as dirty too.
+    fn is_measure_valid(&self, _ui: &UserInterface<M, C>) -> bool {
+        self.deref().is_measure_valid()
     }
 
-    fn is_arrange_valid(&self, ui: &UserInterface<M, C>) -> bool {
-        let mut valid = self.deref().is_arrange_valid()
-            && self.prev_global_visibility == self.is_globally_visible();
-        if valid {
-            for child in self.children() {
-                valid &= ui.node(*child).is_arrange_valid(ui);
-                if !valid {
-                    break;
-                }
-            }
-        }
-        valid
+    /// Returns true if cached arrangement of the node can be reused. See `is_measure_valid`.
+    fn is_arrange_valid(&self, _ui: &UserInterface<M, C>) -> bool {
+        self.deref().is_arrange_valid()
     }
 
     fn measure(&self, ui: &UserInterface<M, C>, available_size: Vector2<f32>) {
@@ -391,6 +511,11 @@ where
 
     /// Performs event-specific actions. Must call widget.handle_message()!
     ///
+    /// Tunneled messages (see `RoutingStrategy::Tunnel`) are passed to this method twice: in
+    /// preview phase on the way down to destination, and in bubble phase on the way back. Use
+    /// `UiMessage::routing_phase` to check current phase and `UiMessage::set_handled` to stop
+    /// routing.
+    ///
     /// # Notes
     ///
     /// Do *not* try to borrow node by `self_handle` in UI - at this moment node has been moved
@@ -516,6 +641,10 @@ pub struct RestrictionEntry<M: MessageData, C: Control<M, C>> {
     pub stop: bool,
 }
 
+/// A closure which is called for every message extracted by `UserInterface::poll_message`,
+/// see `UserInterface::set_message_observer`.
+pub type MessageObserver<M, C> = Box<dyn FnMut(&UiMessage<M, C>)>;
+
 pub struct UserInterface<M: MessageData, C: Control<M, C>> {
     screen_size: Vector2<f32>,
     nodes: Pool<UINode<M, C>>,
@@ -529,6 +658,8 @@ pub struct UserInterface<M: MessageData, C: Control<M, C>> {
     cursor_position: Vector2<f32>,
     receiver: Receiver<UiMessage<M, C>>,
     sender: Sender<UiMessage<M, C>>,
+    layout_events_receiver: Receiver<LayoutEvent<M, C>>,
+    layout_events_sender: Sender<LayoutEvent<M, C>>,
     stack: Vec<Handle<UINode<M, C>>>,
     picking_stack: Vec<RestrictionEntry<M, C>>,
     bubble_queue: VecDeque<Handle<UINode<M, C>>>,
@@ -536,6 +667,8 @@ pub struct UserInterface<M: MessageData, C: Control<M, C>> {
     mouse_state: MouseState,
     keyboard_modifiers: KeyboardModifiers,
     cursor_icon: CursorIcon,
+    accelerators: HashMap<HotKey, UiMessage<M, C>>,
+    message_observer: Option<MessageObserver<M, C>>,
 }
 
 lazy_static! {
@@ -551,6 +684,9 @@ fn draw_node<M: MessageData, C: Control<M, C>>(
     node_handle: Handle<UINode<M, C>>,
     drawing_context: &mut DrawingContext,
     nesting: u8,
+    parent_enabled: bool,
+    parent_opacity: f32,
+    parent_clip_bounds: Rect<f32>,
 ) {
     scope_profile!();
 
@@ -559,23 +695,37 @@ fn draw_node<M: MessageData, C: Control<M, C>>(
         return;
     }
 
+    let enabled = parent_enabled && node.enabled();
+    let opacity = parent_opacity * node.opacity();
+
     // Crawl up on tree and check if current bounds are intersects with every screen bound
-    // of parents chain. This is needed because some control can move their children outside of
-    // their bounds (like scroll viewer, etc.) and single intersection test of parent bounds with
-    // current bounds is not enough.
+    // of clipping parents chain. This is needed because some control can move their children
+    // outside of their bounds (like scroll viewer, etc.) and single intersection test of parent
+    // bounds with current bounds is not enough.
     let bounds = node.screen_bounds();
     let mut parent = node.parent();
     while parent.is_some() {
         let parent_node = nodes.borrow(parent);
-        if !parent_node.screen_bounds().intersects(bounds) {
+        if parent_node.clip_to_bounds() && !parent_node.screen_bounds().intersects(bounds) {
             return;
         }
         parent = parent_node.parent();
     }
 
+    // Widget without clipping uses clipping geometry of its parent, so it and its descendants
+    // can overflow it. Nested clipping is done by stencil buffer - clipping geometry of each
+    // level is drawn only inside clipping geometry of previous level.
+    let clip_bounds = if node.clip_to_bounds() {
+        bounds.inflate(0.9, 0.9)
+    } else {
+        parent_clip_bounds
+    };
+
     let start_index = drawing_context.get_commands().len();
     drawing_context.set_nesting(nesting);
-    drawing_context.commit_clip_rect(&bounds.inflate(0.9, 0.9));
+    drawing_context.set_dimmed(!enabled);
+    drawing_context.set_opacity(opacity);
+    drawing_context.commit_clip_rect(&clip_bounds);
 
     node.draw(drawing_context);
 
@@ -588,7 +738,15 @@ fn draw_node<M: MessageData, C: Control<M, C>>(
     for &child_node in node.children().iter() {
         // Do not continue render of top-most nodes - they'll be rendered in separate pass.
         if !nodes[child_node].is_draw_on_top() {
-            draw_node(nodes, child_node, drawing_context, nesting + 1);
+            draw_node(
+                nodes,
+                child_node,
+                drawing_context,
+                nesting + 1,
+                enabled,
+                opacity,
+                clip_bounds,
+            );
         }
     }
 
@@ -598,10 +756,13 @@ fn draw_node<M: MessageData, C: Control<M, C>>(
 impl<M: MessageData, C: Control<M, C>> UserInterface<M, C> {
     pub fn new(screen_size: Vector2<f32>) -> UserInterface<M, C> {
         let (sender, receiver) = mpsc::channel();
+        let (layout_events_sender, layout_events_receiver) = mpsc::channel();
         let mut ui = UserInterface {
             screen_size,
             sender,
             receiver,
+            layout_events_sender,
+            layout_events_receiver,
             visual_debug: false,
             captured_node: Handle::NONE,
             root_canvas: Handle::NONE,
@@ -618,6 +779,8 @@ impl<M: MessageData, C: Control<M, C>> UserInterface<M, C> {
             mouse_state: Default::default(),
             keyboard_modifiers: Default::default(),
             cursor_icon: Default::default(),
+            accelerators: Default::default(),
+            message_observer: None,
         };
         ui.root_canvas = ui.add_node(UINode::Canvas(Canvas::new(WidgetBuilder::new().build())));
         ui
@@ -627,6 +790,48 @@ impl<M: MessageData, C: Control<M, C>> UserInterface<M, C> {
         self.keyboard_modifiers
     }
 
+    /// Registers an accelerator (global shortcut): given message will be sent every time when
+    /// hot key is pressed regardless of which widget has keyboard focus. Accelerators are
+    /// checked before key events are passed to focused widget, so the widget will not receive
+    /// key down message for the hot key. Accelerators are not fired while a text box has
+    /// keyboard focus, so it still can receive text input. Returns previous message bound to
+    /// the hot key, if any.
+    pub fn register_accelerator(
+        &mut self,
+        hot_key: HotKey,
+        message: UiMessage<M, C>,
+    ) -> Option<UiMessage<M, C>> {
+        self.accelerators.insert(hot_key, message)
+    }
+
+    /// Removes accelerator and returns its message, if any.
+    pub fn unregister_accelerator(&mut self, hot_key: HotKey) -> Option<UiMessage<M, C>> {
+        self.accelerators.remove(&hot_key)
+    }
+
+    /// Returns message bound to given hot key, if any.
+    pub fn accelerator(&self, hot_key: HotKey) -> Option<&UiMessage<M, C>> {
+        self.accelerators.get(&hot_key)
+    }
+
+    fn try_fire_accelerator(&self, code: KeyCode) -> bool {
+        let text_input = self.keyboard_focus_node.is_some()
+            && matches!(self.nodes[self.keyboard_focus_node], UINode::TextBox(_));
+        if text_input {
+            return false;
+        }
+        match self
+            .accelerators
+            .get(&HotKey::new(code, self.keyboard_modifiers))
+        {
+            Some(message) => {
+                self.send_message(message.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn build_ctx(&mut self) -> BuildContext<'_, M, C> {
         BuildContext { ui: self }
     }
@@ -675,6 +880,34 @@ impl<M: MessageData, C: Control<M, C>> UserInterface<M, C> {
         }
     }
 
+    /// Propagates layout invalidation of nodes up to the root. Every ancestor of a dirty node
+    /// is marked dirty too, while siblings and unrelated subtrees keep their cached layout.
+    fn handle_layout_events(&mut self) {
+        scope_profile!();
+
+        while let Ok(layout_event) = self.layout_events_receiver.try_recv() {
+            let (node_handle, measure) = match layout_event {
+                LayoutEvent::MeasurementInvalidated(node_handle) => (node_handle, true),
+                LayoutEvent::ArrangementInvalidated(node_handle) => (node_handle, false),
+            };
+
+            // Node could be already removed.
+            if !self.nodes.is_valid_handle(node_handle) {
+                continue;
+            }
+
+            let mut parent = self.nodes[node_handle].parent();
+            while parent.is_some() {
+                let parent_node = &self.nodes[parent];
+                if measure {
+                    parent_node.measure_valid.set(false);
+                }
+                parent_node.arrange_valid.set(false);
+                parent = parent_node.parent();
+            }
+        }
+    }
+
     fn update_transform(&mut self) {
         scope_profile!();
 
@@ -712,6 +945,8 @@ impl<M: MessageData, C: Control<M, C>> UserInterface<M, C> {
             }
         }
 
+        self.handle_layout_events();
+
         self.node(self.root_canvas).measure(self, screen_size);
         self.node(self.root_canvas)
             .arrange(self, &Rect::new(0.0, 0.0, screen_size.x, screen_size.y));
@@ -750,8 +985,18 @@ impl<M: MessageData, C: Control<M, C>> UserInterface<M, C> {
             node.command_indices.borrow_mut().clear();
         }
 
+        let screen_bounds = Rect::new(0.0, 0.0, self.screen_size.x, self.screen_size.y);
+
         // Draw everything except top-most nodes.
-        draw_node(&self.nodes, self.root_canvas, &mut self.drawing_context, 1);
+        draw_node(
+            &self.nodes,
+            self.root_canvas,
+            &mut self.drawing_context,
+            1,
+            true,
+            1.0,
+            screen_bounds,
+        );
 
         // Render top-most nodes in separate pass.
         // TODO: This may give weird results because of invalid nesting.
@@ -760,7 +1005,21 @@ impl<M: MessageData, C: Control<M, C>> UserInterface<M, C> {
         while let Some(node_handle) = self.stack.pop() {
             let node = &self.nodes[node_handle];
             if node.is_draw_on_top() {
-                draw_node(&self.nodes, node_handle, &mut self.drawing_context, 1);
+                let parent_enabled = node.parent().is_none() || self.is_enabled(node.parent());
+                let parent_opacity = if node.parent().is_some() {
+                    self.nodes[node.parent()].global_opacity(self)
+                } else {
+                    1.0
+                };
+                draw_node(
+                    &self.nodes,
+                    node_handle,
+                    &mut self.drawing_context,
+                    1,
+                    parent_enabled,
+                    parent_opacity,
+                    screen_bounds,
+                );
             }
             for &child in node.children() {
                 self.stack.push(child);
@@ -770,6 +1029,7 @@ impl<M: MessageData, C: Control<M, C>> UserInterface<M, C> {
         // Debug info rendered on top of other.
         if self.visual_debug {
             self.drawing_context.set_nesting(0);
+            self.drawing_context.set_dimmed(false);
 
             if self.picked_node.is_some() {
                 let bounds = self.nodes.borrow(self.picked_node).screen_bounds();
@@ -858,6 +1118,7 @@ impl<M: MessageData, C: Control<M, C>> UserInterface<M, C> {
 
         let widget = self.nodes.borrow(node_handle);
 
+        // Disabled widget hides its whole subtree from picking.
         if !widget.is_hit_test_visible() || !widget.enabled() {
             return Handle::NONE;
         }
@@ -881,14 +1142,37 @@ impl<M: MessageData, C: Control<M, C>> UserInterface<M, C> {
         picked
     }
 
+    /// Checks if a node and all its ancestors are enabled. Disabled nodes do not receive
+    /// input and are drawn dimmed. See `WidgetMessage::Enabled`.
+    pub fn is_enabled(&self, node: Handle<UINode<M, C>>) -> bool {
+        self.nodes[node].is_enabled(self)
+    }
+
     pub fn cursor_position(&self) -> Vector2<f32> {
         self.cursor_position
     }
 
+    /// Converts texture coordinates of a surface, to which the interface is rendered, to
+    /// screen position of the interface. Top-left corner of the interface has `(0, 0)`
+    /// texture coordinates, bottom-right corner - `(1, 1)`.
+    pub fn texture_coords_to_screen(&self, tex_coords: Vector2<f32>) -> Vector2<f32> {
+        tex_coords.component_mul(&self.screen_size)
+    }
+
+    /// Moves cursor to a point of a surface, to which the interface is rendered, given in
+    /// texture coordinates of the surface. This is the same as `OsEvent::CursorMoved`, but
+    /// for interfaces placed in a world: cast a ray from a camera through real cursor position,
+    /// find texture coordinates of intersection point with the surface and pass them here.
+    /// Mouse buttons, keyboard and other events can be passed to `process_os_event` as usual.
+    pub fn process_surface_cursor(&mut self, tex_coords: Vector2<f32>) -> bool {
+        let position = self.texture_coords_to_screen(tex_coords);
+        self.process_os_event(&OsEvent::CursorMoved { position })
+    }
+
     pub fn hit_test(&self, pt: Vector2<f32>) -> Handle<UINode<M, C>> {
         scope_profile!();
 
-        if self.nodes.is_valid_handle(self.captured_node) {
+        if self.nodes.is_valid_handle(self.captured_node) && self.is_enabled(self.captured_node) {
             self.captured_node
         } else if self.picking_stack.is_empty() {
             // We're not restricted to any node, just start from root.
@@ -1072,11 +1356,58 @@ impl<M: MessageData, C: Control<M, C>> UserInterface<M, C> {
     }
 
     /// Returns instance of message sender which can be used to push messages into queue
-    /// from other threads.
+    /// from other threads. Sender can be cloned and moved to any thread, but messages are
+    /// still processed only on the thread which owns the interface, in `poll_message`.
+    ///
+    /// # Ordering
+    ///
+    /// There is a single queue for every sender, including `send_message`. Messages sent
+    /// from one thread are processed in the same order as they were sent. Messages from
+    /// different threads are processed in order in which they were put into queue, there is no
+    /// other ordering between them. Messages sent by widgets while a message is processed are
+    /// put at the end of the queue, after messages which were already sent by other threads.
+    ///
+    /// Messages for nodes that were removed before the messages were processed are silently
+    /// discarded, so a background task can outlive widgets it updates.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rg3d_ui::{
+    ///     core::algebra::Vector2,
+    ///     message::{MessageDirection, ProgressBarMessage},
+    ///     node::StubNode,
+    ///     progress_bar::ProgressBarBuilder,
+    ///     widget::WidgetBuilder,
+    ///     UserInterface,
+    /// };
+    ///
+    /// let mut ui = UserInterface::<(), StubNode>::new(Vector2::new(100.0, 100.0));
+    /// let progress_bar = ProgressBarBuilder::new(WidgetBuilder::new()).build(&mut ui.build_ctx());
+    ///
+    /// let sender = ui.sender();
+    /// let loader = std::thread::spawn(move || {
+    ///     for i in 1..=10 {
+    ///         // Load something here.
+    ///         sender
+    ///             .send(ProgressBarMessage::progress(
+    ///                 progress_bar,
+    ///                 MessageDirection::ToWidget,
+    ///                 i as f32 / 10.0,
+    ///             ))
+    ///             .unwrap();
+    ///     }
+    /// });
+    /// loader.join().unwrap();
+    ///
+    /// // Somewhere in game loop.
+    /// while let Some(_message) = ui.poll_message() {}
+    /// ```
     pub fn sender(&self) -> Sender<UiMessage<M, C>> {
         self.sender.clone()
     }
 
+    /// Puts message into queue, it will be processed in `poll_message`.
     pub fn send_message(&self, message: UiMessage<M, C>) {
         self.sender.send(message).unwrap()
     }
@@ -1110,36 +1441,111 @@ impl<M: MessageData, C: Control<M, C>> UserInterface<M, C> {
         }
     }
 
-    fn bubble_message(&mut self, message: &mut UiMessage<M, C>) {
-        // Dispatch event using bubble strategy. Bubble routing means that message will go
-        // from specified destination up on tree to tree root.
-        // Gather chain of nodes from source to root.
+    // Gathers chain of nodes from destination of a message to root.
+    fn gather_route(&mut self, destination: Handle<UINode<M, C>>) {
         self.bubble_queue.clear();
-        self.bubble_queue.push_back(message.destination());
-        let mut parent = self.nodes[message.destination()].parent();
+        self.bubble_queue.push_back(destination);
+        let mut parent = self.nodes[destination].parent();
         while parent.is_some() && self.nodes.is_valid_handle(parent) {
             self.bubble_queue.push_back(parent);
             parent = self.nodes[parent].parent();
         }
+    }
+
+    fn route_message(&mut self, handle: Handle<UINode<M, C>>, message: &mut UiMessage<M, C>) {
+        let (ticket, mut node) = self.nodes.take_reserve(handle);
+        node.handle_routed_message(self, message);
+        self.nodes.put_back(ticket, node);
+    }
 
+    fn tunnel_message(&mut self, message: &mut UiMessage<M, C>) {
+        // Dispatch event using tunnel strategy. Tunnel routing means that message will go
+        // from tree root down to specified destination, so parent nodes can handle it before
+        // their children.
+        self.gather_route(message.destination());
+
+        message.set_routing_phase(RoutingPhase::Preview);
+        while let Some(handle) = self.bubble_queue.pop_back() {
+            if message.handled() {
+                break;
+            }
+            self.route_message(handle, message);
+        }
+        message.set_routing_phase(RoutingPhase::Bubble);
+    }
+
+    fn bubble_message(&mut self, message: &mut UiMessage<M, C>) {
+        // Dispatch event using bubble strategy. Bubble routing means that message will go
+        // from specified destination up on tree to tree root.
+        self.gather_route(message.destination());
+
+        // Only tunneled messages stop when handled, bubbling messages are delivered to all
+        // parents as before.
+        let stop_if_handled = message.routing_strategy() == RoutingStrategy::Tunnel;
         while let Some(handle) = self.bubble_queue.pop_front() {
-            let (ticket, mut node) = self.nodes.take_reserve(handle);
-            node.handle_routed_message(self, message);
-            self.nodes.put_back(ticket, node);
+            if stop_if_handled && message.handled() {
+                break;
+            }
+            self.route_message(handle, message);
         }
     }
 
+    /// Sets a closure which will be called for every message extracted by `poll_message` before
+    /// the message is routed to widgets. It is useful to log messages or to debug complex message
+    /// flows. Observer can intercept a message by marking it as handled (see `UiMessage::set_handled`),
+    /// in this case the message won't be routed to widgets and will be returned from `poll_message`
+    /// as is. Messages that were already handled when they were sent are routed as usual. Pass
+    /// `None` to remove current observer, messages are not checked at all when there is no observer.
+    /// Returns previous observer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rg3d_ui::{
+    ///     core::algebra::Vector2,
+    ///     message::{UiMessageData, WindowMessage},
+    ///     node::StubNode,
+    ///     UserInterface,
+    /// };
+    ///
+    /// let mut ui = UserInterface::<(), StubNode>::new(Vector2::new(100.0, 100.0));
+    /// ui.set_message_observer(Some(Box::new(|message| {
+    ///     if let UiMessageData::Window(WindowMessage::Move(position)) = message.data() {
+    ///         println!("{:?} moved to {:?}", message.destination(), position);
+    ///     }
+    /// })));
+    /// ```
+    pub fn set_message_observer(
+        &mut self,
+        observer: Option<MessageObserver<M, C>>,
+    ) -> Option<MessageObserver<M, C>> {
+        std::mem::replace(&mut self.message_observer, observer)
+    }
+
     /// Extracts UI event one-by-one from common queue. Each extracted event will go to *all*
     /// available nodes first and only then will be moved outside of this method. This is one
     /// of most important methods which must be called each frame of your game loop, otherwise
     /// UI will not respond to any kind of events and simply speaking will just not work.
+    /// Messages sent from other threads (see `sender`) are processed here as well.
     pub fn poll_message(&mut self) -> Option<UiMessage<M, C>> {
-        match self.receiver.try_recv() {
+        // Destination node may be destroyed at the time we receive message, we have to
+        // discard such messages.
+        let received = loop {
+            match self.receiver.try_recv() {
+                Ok(message) if !self.nodes.is_valid_handle(message.destination()) => continue,
+                received => break received,
+            }
+        };
+
+        match received {
             Ok(mut message) => {
-                // Destination node may be destroyed at the time we receive message,
-                // we have to discard such messages.
-                if !self.nodes.is_valid_handle(message.destination()) {
-                    return None;
+                if let Some(observer) = self.message_observer.as_mut() {
+                    let was_handled = message.handled();
+                    observer(&message);
+                    if !was_handled && message.handled() {
+                        // Intercepted by the observer.
+                        return Some(message);
+                    }
                 }
 
                 if message.need_perform_layout() {
@@ -1147,7 +1553,16 @@ impl<M: MessageData, C: Control<M, C>> UserInterface<M, C> {
                 }
 
                 self.preview_message(&mut message);
-                self.bubble_message(&mut message);
+                match message.routing_strategy() {
+                    RoutingStrategy::Direct => {
+                        self.route_message(message.destination(), &mut message)
+                    }
+                    RoutingStrategy::Bubble => self.bubble_message(&mut message),
+                    RoutingStrategy::Tunnel => {
+                        self.tunnel_message(&mut message);
+                        self.bubble_message(&mut message);
+                    }
+                }
 
                 if let UiMessageData::Widget(msg) = &message.data() {
                     match msg {
@@ -1418,25 +1833,32 @@ impl<M: MessageData, C: Control<M, C>> UserInterface<M, C> {
                 }
             }
             OsEvent::KeyboardInput { button, state } => {
-                if self.keyboard_focus_node.is_some() {
-                    self.send_message(match state {
-                        ButtonState::Pressed => WidgetMessage::key_down(
-                            self.keyboard_focus_node,
-                            MessageDirection::FromWidget,
-                            *button,
-                        ),
-                        ButtonState::Released => WidgetMessage::key_up(
-                            self.keyboard_focus_node,
-                            MessageDirection::FromWidget,
-                            *button,
-                        ),
-                    });
+                if *state == ButtonState::Pressed && self.try_fire_accelerator(*button) {
+                    event_processed = true;
+                } else if self.keyboard_focus_node.is_some()
+                    && self.is_enabled(self.keyboard_focus_node)
+                {
+                    self.send_message(
+                        match state {
+                            ButtonState::Pressed => WidgetMessage::key_down(
+                                self.keyboard_focus_node,
+                                MessageDirection::FromWidget,
+                                *button,
+                            ),
+                            ButtonState::Released => WidgetMessage::key_up(
+                                self.keyboard_focus_node,
+                                MessageDirection::FromWidget,
+                                *button,
+                            ),
+                        }
+                        .with_routing_strategy(RoutingStrategy::Tunnel),
+                    );
 
                     event_processed = true;
                 }
             }
             OsEvent::Character(unicode) => {
-                if self.keyboard_focus_node.is_some() {
+                if self.keyboard_focus_node.is_some() && self.is_enabled(self.keyboard_focus_node) {
                     self.send_message(WidgetMessage::text(
                         self.keyboard_focus_node,
                         MessageDirection::FromWidget,
@@ -1473,6 +1895,99 @@ impl<M: MessageData, C: Control<M, C>> UserInterface<M, C> {
         &self.nodes
     }
 
+    /// Captures current layout of named windows and docking tiles, see `layout` module docs
+    /// for more info.
+    pub fn save_layout(&self) -> UiLayout {
+        let mut layout = UiLayout::default();
+        for node in self.nodes.iter() {
+            if node.name().is_empty() {
+                continue;
+            }
+            match node {
+                UINode::Window(window) => {
+                    layout.windows.insert(
+                        window.name().to_owned(),
+                        WindowLayout {
+                            position: window.desired_local_position(),
+                            size: Vector2::new(window.width(), window.height()),
+                            minimized: window.is_minimized(),
+                            open: window.visibility(),
+                        },
+                    );
+                }
+                UINode::Tile(tile) => {
+                    if let Some(splitter) = tile.splitter() {
+                        layout
+                            .tiles
+                            .insert(tile.name().to_owned(), TileLayout { splitter });
+                    }
+                }
+                _ => (),
+            }
+        }
+        layout
+    }
+
+    /// Applies previously saved layout to named windows and docking tiles, entries with
+    /// names that do not exist in current user interface are ignored. Layout is applied
+    /// using messages, so it will take effect after messages are processed.
+    pub fn restore_layout(&mut self, layout: &UiLayout) {
+        for (handle, node) in self.nodes.pair_iter() {
+            match node {
+                UINode::Window(window) => {
+                    if let Some(state) = layout.windows.get(window.name()) {
+                        self.send_message(WindowMessage::move_to(
+                            handle,
+                            MessageDirection::ToWidget,
+                            state.position,
+                        ));
+                        if state.size.x.is_finite() {
+                            self.send_message(WidgetMessage::width(
+                                handle,
+                                MessageDirection::ToWidget,
+                                state.size.x,
+                            ));
+                        }
+                        if state.size.y.is_finite() {
+                            self.send_message(WidgetMessage::height(
+                                handle,
+                                MessageDirection::ToWidget,
+                                state.size.y,
+                            ));
+                        }
+                        self.send_message(WindowMessage::minimize(
+                            handle,
+                            MessageDirection::ToWidget,
+                            state.minimized,
+                        ));
+                        if state.open {
+                            self.send_message(WindowMessage::open(
+                                handle,
+                                MessageDirection::ToWidget,
+                                false,
+                            ));
+                        } else {
+                            self.send_message(WindowMessage::close(
+                                handle,
+                                MessageDirection::ToWidget,
+                            ));
+                        }
+                    }
+                }
+                UINode::Tile(tile) => {
+                    if let Some(state) = layout.tiles.get(tile.name()) {
+                        self.send_message(TileMessage::splitter(
+                            handle,
+                            MessageDirection::ToWidget,
+                            state.splitter,
+                        ));
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
     pub fn root(&self) -> Handle<UINode<M, C>> {
         self.root_canvas
     }
@@ -1481,14 +1996,15 @@ impl<M: MessageData, C: Control<M, C>> UserInterface<M, C> {
         let children = node.children().to_vec();
         node.clear_children();
         let node_handle = self.nodes.spawn(node);
+        let node = self.nodes[node_handle].deref_mut();
+        node.handle = node_handle;
+        node.layout_events_sender = Some(self.layout_events_sender.clone());
         if self.root_canvas.is_some() {
             self.link_nodes_internal(node_handle, self.root_canvas);
         }
         for child in children {
             self.link_nodes_internal(child, node_handle)
         }
-        let node = self.nodes[node_handle].deref_mut();
-        node.handle = node_handle;
         node_handle
     }
 
@@ -1632,11 +2148,25 @@ impl<M: MessageData, C: Control<M, C>> UserInterface<M, C> {
 mod test {
     use crate::{
         border::BorderBuilder,
-        core::math::vec2::Vector2,
-        message::{MessageDirection, WidgetMessage},
-        node::StubNode,
-        widget::WidgetBuilder,
-        UserInterface,
+        brush::Brush,
+        canvas::CanvasBuilder,
+        core::{algebra::Vector2, color::Color, pool::Handle},
+        draw::CommandKind,
+        message::{
+            ButtonMessage, ButtonState, HotKey, KeyCode, KeyboardModifiers, MessageDirection,
+            OsEvent, RoutingPhase, RoutingStrategy, UiMessage, UiMessageData, WidgetMessage,
+            WindowMessage,
+        },
+        node::{StubNode, UINode},
+        stack_panel::StackPanelBuilder,
+        widget::{Widget, WidgetBuilder},
+        window::WindowBuilder,
+        Anchors, Thickness, UserInterface,
+    };
+    use std::{
+        cell::RefCell,
+        ops::{Deref, DerefMut},
+        rc::Rc,
     };
 
     #[test]
@@ -1658,4 +2188,746 @@ mod test {
         let actual_position = ui.node(widget).actual_local_position();
         assert_eq!(actual_position, expected_position);
     }
+
+    #[test]
+    fn invalidate_layout_marks_minimal_subtree() {
+        let screen_size = Vector2::new(1000.0, 1000.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+
+        let mut panels = Vec::new();
+        let mut leaves = Vec::new();
+        for _ in 0..50 {
+            let children = (0..50)
+                .map(|_| {
+                    BorderBuilder::new(WidgetBuilder::new().with_height(10.0))
+                        .build(&mut ui.build_ctx())
+                })
+                .collect::<Vec<_>>();
+            panels.push(
+                StackPanelBuilder::new(WidgetBuilder::new().with_children(&children))
+                    .build(&mut ui.build_ctx()),
+            );
+            leaves.push(children);
+        }
+        let outer = StackPanelBuilder::new(WidgetBuilder::new().with_children(&panels))
+            .build(&mut ui.build_ctx());
+
+        ui.update(screen_size, 0.0);
+
+        for node in ui.nodes().iter() {
+            assert!(node.is_measure_valid() && node.is_arrange_valid());
+        }
+
+        let dirty = leaves[0][0];
+        ui.node(dirty).invalidate_layout();
+        ui.handle_layout_events();
+
+        // Only the node and its ancestors must become dirty.
+        for &handle in &[dirty, panels[0], outer, ui.root()] {
+            assert!(!ui.node(handle).is_measure_valid());
+            assert!(!ui.node(handle).is_arrange_valid());
+        }
+        for &handle in leaves[0][1..].iter().chain(&panels[1..]).chain(&leaves[1]) {
+            assert!(ui.node(handle).is_measure_valid());
+            assert!(ui.node(handle).is_arrange_valid());
+        }
+
+        // Change height of every leaf without invalidation, only leaves that are measured
+        // again will pick up new height.
+        for &handle in leaves.iter().flatten() {
+            ui.nodes[handle].set_height(20.0);
+        }
+        ui.update(screen_size, 0.0);
+
+        for node in ui.nodes().iter() {
+            assert!(node.is_measure_valid() && node.is_arrange_valid());
+        }
+        for &handle in leaves.iter().flatten() {
+            let expected_height = if handle == dirty { 20.0 } else { 10.0 };
+            assert_eq!(ui.node(handle).desired_size().y, expected_height);
+        }
+    }
+
+    #[test]
+    fn arrange_invalidation_keeps_measurement() {
+        let screen_size = Vector2::new(1000.0, 1000.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+        let child = BorderBuilder::new(WidgetBuilder::new()).build(&mut ui.build_ctx());
+        let parent =
+            BorderBuilder::new(WidgetBuilder::new().with_child(child)).build(&mut ui.build_ctx());
+        ui.update(screen_size, 0.0);
+
+        ui.node(child).invalidate_arrange();
+        ui.handle_layout_events();
+
+        assert!(ui.node(parent).is_measure_valid());
+        assert!(!ui.node(parent).is_arrange_valid());
+    }
+
+    #[test]
+    fn save_restore_layout() {
+        let screen_size = Vector2::new(1000.0, 1000.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+        let ctx = &mut ui.build_ctx();
+        // Header buttons without text.
+        let minimize_button = BorderBuilder::new(WidgetBuilder::new()).build(ctx);
+        let close_button = BorderBuilder::new(WidgetBuilder::new()).build(ctx);
+        let window = WindowBuilder::new(
+            WidgetBuilder::new()
+                .with_name("Tool")
+                .with_width(200.0)
+                .with_height(100.0),
+        )
+        .with_minimize_button(minimize_button)
+        .with_close_button(close_button)
+        .build(ctx);
+        ui.send_message(WindowMessage::move_to(
+            window,
+            MessageDirection::ToWidget,
+            Vector2::new(50.0, 60.0),
+        ));
+        ui.send_message(WindowMessage::minimize(
+            window,
+            MessageDirection::ToWidget,
+            true,
+        ));
+        while let Some(_) = ui.poll_message() {}
+
+        let layout = ui.save_layout();
+        let saved = layout.windows["Tool"];
+        assert_eq!(saved.position, Vector2::new(50.0, 60.0));
+        assert_eq!(saved.size, Vector2::new(200.0, 100.0));
+        assert!(saved.minimized);
+        assert!(saved.open);
+
+        ui.send_message(WindowMessage::move_to(
+            window,
+            MessageDirection::ToWidget,
+            Vector2::new(0.0, 0.0),
+        ));
+        ui.send_message(WindowMessage::minimize(
+            window,
+            MessageDirection::ToWidget,
+            false,
+        ));
+        ui.send_message(WidgetMessage::width(
+            window,
+            MessageDirection::ToWidget,
+            300.0,
+        ));
+        while let Some(_) = ui.poll_message() {}
+        assert_ne!(ui.save_layout(), layout);
+
+        ui.restore_layout(&layout);
+        while let Some(_) = ui.poll_message() {}
+        assert_eq!(ui.save_layout(), layout);
+    }
+
+    #[test]
+    fn accelerators() {
+        let screen_size = Vector2::new(1000.0, 1000.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+        let widget = BorderBuilder::new(WidgetBuilder::new()).build(&mut ui.build_ctx());
+        while let Some(_) = ui.poll_message() {}
+
+        let ctrl = KeyboardModifiers {
+            control: true,
+            ..Default::default()
+        };
+        let message = WidgetMessage::visibility(widget, MessageDirection::ToWidget, false);
+        assert!(ui
+            .register_accelerator(HotKey::new(KeyCode::S, ctrl), message.clone())
+            .is_none());
+
+        let press = |ui: &mut UserInterface<(), StubNode>, modifiers| {
+            ui.process_os_event(&OsEvent::KeyboardModifiers(modifiers));
+            ui.process_os_event(&OsEvent::KeyboardInput {
+                button: KeyCode::S,
+                state: ButtonState::Pressed,
+            });
+            let mut fired = false;
+            while let Some(received) = ui.poll_message() {
+                fired |= received == message;
+            }
+            fired
+        };
+
+        assert!(press(&mut ui, ctrl));
+        // Modifiers must match exactly.
+        assert!(!press(&mut ui, KeyboardModifiers::default()));
+        assert!(!press(
+            &mut ui,
+            KeyboardModifiers {
+                shift: true,
+                ..ctrl
+            }
+        ));
+
+        // Focused widget does not receive key down message when accelerator fires.
+        ui.keyboard_focus_node = widget;
+        ui.process_os_event(&OsEvent::KeyboardModifiers(ctrl));
+        ui.process_os_event(&OsEvent::KeyboardInput {
+            button: KeyCode::S,
+            state: ButtonState::Pressed,
+        });
+        while let Some(received) = ui.poll_message() {
+            assert!(!matches!(
+                received.data(),
+                UiMessageData::Widget(WidgetMessage::KeyDown(_))
+            ));
+        }
+
+        assert!(ui
+            .unregister_accelerator(HotKey::new(KeyCode::S, ctrl))
+            .is_some());
+        assert!(!press(&mut ui, ctrl));
+    }
+
+    #[test]
+    fn disabled_subtree() {
+        let screen_size = Vector2::new(1000.0, 1000.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+        let child = BorderBuilder::new(WidgetBuilder::new()).build(&mut ui.build_ctx());
+        let parent = BorderBuilder::new(
+            WidgetBuilder::new()
+                .with_width(100.0)
+                .with_height(100.0)
+                .with_child(child),
+        )
+        .build(&mut ui.build_ctx());
+        while let Some(_) = ui.poll_message() {}
+        ui.update(screen_size, 0.0);
+
+        let point = Vector2::new(50.0, 50.0);
+        let child_brushes = |ui: &mut UserInterface<(), StubNode>| {
+            ui.draw();
+            let commands = ui.drawing_context.get_commands();
+            ui.node(child)
+                .command_indices
+                .borrow()
+                .iter()
+                .map(|&i| commands[i].brush.clone())
+                .collect::<Vec<_>>()
+        };
+        // Picking uses drawn geometry.
+        let enabled_brushes = child_brushes(&mut ui);
+        assert_eq!(ui.hit_test(point), child);
+        assert!(!enabled_brushes.is_empty());
+
+        ui.send_message(WidgetMessage::enabled(
+            parent,
+            MessageDirection::ToWidget,
+            false,
+        ));
+        while let Some(_) = ui.poll_message() {}
+
+        // Whole subtree is disabled, but keeps its own state.
+        assert!(ui.node(child).enabled());
+        assert!(!ui.is_enabled(child));
+        let picked = ui.hit_test(point);
+        assert!(picked != child && picked != parent);
+
+        // Disabled widget is drawn with dimmed brushes.
+        assert_eq!(
+            child_brushes(&mut ui),
+            enabled_brushes
+                .iter()
+                .map(|brush| brush.dimmed())
+                .collect::<Vec<_>>()
+        );
+
+        // Focused disabled widget receives no keyboard input.
+        ui.keyboard_focus_node = child;
+        ui.process_os_event(&OsEvent::KeyboardInput {
+            button: KeyCode::A,
+            state: ButtonState::Pressed,
+        });
+        ui.process_os_event(&OsEvent::Character('a'));
+        assert!(ui.poll_message().is_none());
+
+        ui.send_message(WidgetMessage::enabled(
+            parent,
+            MessageDirection::ToWidget,
+            true,
+        ));
+        while let Some(_) = ui.poll_message() {}
+        assert_eq!(child_brushes(&mut ui), enabled_brushes);
+        assert_eq!(ui.hit_test(point), child);
+    }
+
+    #[test]
+    fn anchors() {
+        let screen_size = Vector2::new(1000.0, 1000.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+        let ctx = &mut ui.build_ctx();
+        let pinned = BorderBuilder::new(WidgetBuilder::new().with_anchors(Anchors::pinned(
+            Vector2::new(1.0, 1.0),
+            Vector2::new(-5.0, -5.0),
+            Vector2::new(20.0, 10.0),
+        )))
+        .build(ctx);
+        let stretched = BorderBuilder::new(
+            WidgetBuilder::new().with_anchors(Anchors::stretch(Thickness::uniform(10.0))),
+        )
+        .build(ctx);
+        let parent = BorderBuilder::new(
+            WidgetBuilder::new()
+                .with_width(200.0)
+                .with_height(100.0)
+                .with_child(pinned)
+                .with_child(stretched),
+        )
+        .with_stroke_thickness(Thickness::zero())
+        .build(ctx);
+        ui.update(screen_size, 0.0);
+
+        let rect = |ui: &UserInterface<(), StubNode>, node| {
+            (
+                ui.node(node).actual_local_position(),
+                ui.node(node).actual_size(),
+            )
+        };
+
+        assert_eq!(
+            rect(&ui, pinned),
+            (Vector2::new(175.0, 85.0), Vector2::new(20.0, 10.0))
+        );
+        assert_eq!(
+            rect(&ui, stretched),
+            (Vector2::new(10.0, 10.0), Vector2::new(180.0, 80.0))
+        );
+
+        // Pinned widget sticks to the corner, stretched one resizes with parent.
+        ui.send_message(WidgetMessage::width(
+            parent,
+            MessageDirection::ToWidget,
+            400.0,
+        ));
+        ui.send_message(WidgetMessage::height(
+            parent,
+            MessageDirection::ToWidget,
+            200.0,
+        ));
+        while let Some(_) = ui.poll_message() {}
+        ui.update(screen_size, 0.0);
+
+        assert_eq!(
+            rect(&ui, pinned),
+            (Vector2::new(375.0, 185.0), Vector2::new(20.0, 10.0))
+        );
+        assert_eq!(
+            rect(&ui, stretched),
+            (Vector2::new(10.0, 10.0), Vector2::new(380.0, 180.0))
+        );
+
+        // Without anchors widget is placed using alignments.
+        ui.send_message(WidgetMessage::anchors(
+            stretched,
+            MessageDirection::ToWidget,
+            None,
+        ));
+        while let Some(_) = ui.poll_message() {}
+        ui.update(screen_size, 0.0);
+
+        assert_eq!(
+            rect(&ui, stretched),
+            (Vector2::new(0.0, 0.0), Vector2::new(400.0, 200.0))
+        );
+    }
+
+    #[test]
+    fn clip_to_bounds() {
+        let screen_size = Vector2::new(1000.0, 1000.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+        let ctx = &mut ui.build_ctx();
+        // Child overflows its parent.
+        let child = BorderBuilder::new(
+            WidgetBuilder::new()
+                .with_width(20.0)
+                .with_height(20.0)
+                .with_desired_position(Vector2::new(110.0, 20.0)),
+        )
+        .with_stroke_thickness(Thickness::zero())
+        .build(ctx);
+        let parent = CanvasBuilder::new(
+            WidgetBuilder::new()
+                .with_width(100.0)
+                .with_height(100.0)
+                .with_child(child),
+        )
+        .build(ctx);
+        while let Some(_) = ui.poll_message() {}
+        ui.update(screen_size, 0.0);
+
+        // Widgets are clipped by default.
+        let point = Vector2::new(115.0, 30.0);
+        ui.draw();
+        assert_ne!(ui.hit_test(point), child);
+
+        ui.send_message(WidgetMessage::clip_to_bounds(
+            parent,
+            MessageDirection::ToWidget,
+            false,
+        ));
+        while let Some(_) = ui.poll_message() {}
+        ui.draw();
+        assert_eq!(ui.hit_test(point), child);
+
+        // Nested clipping - overflowing content is still clipped by clipping ancestors.
+        let outer = CanvasBuilder::new(WidgetBuilder::new().with_width(120.0).with_height(120.0))
+            .build(&mut ui.build_ctx());
+        ui.send_message(WidgetMessage::link(
+            parent,
+            MessageDirection::ToWidget,
+            outer,
+        ));
+        while let Some(_) = ui.poll_message() {}
+        ui.update(screen_size, 0.0);
+        ui.draw();
+        assert_eq!(ui.hit_test(point), child);
+        assert_ne!(ui.hit_test(Vector2::new(125.0, 30.0)), child);
+    }
+
+    #[test]
+    fn opacity() {
+        let screen_size = Vector2::new(1000.0, 1000.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+        let background = Brush::Solid(Color::opaque(200, 100, 50));
+        let child = BorderBuilder::new(
+            WidgetBuilder::new()
+                .with_opacity(0.5)
+                .with_background(background.clone()),
+        )
+        .build(&mut ui.build_ctx());
+        let parent = BorderBuilder::new(
+            WidgetBuilder::new()
+                .with_width(100.0)
+                .with_height(100.0)
+                .with_opacity(0.5)
+                .with_child(child),
+        )
+        .build(&mut ui.build_ctx());
+        while let Some(_) = ui.poll_message() {}
+        ui.update(screen_size, 0.0);
+
+        let child_alpha = |ui: &mut UserInterface<(), StubNode>| {
+            ui.draw();
+            let commands = ui.drawing_context.get_commands();
+            let indices = ui.node(child).command_indices.borrow();
+            let background = indices
+                .iter()
+                .map(|&i| &commands[i])
+                .find(|command| command.kind == CommandKind::Geometry)
+                .unwrap();
+            match background.brush {
+                Brush::Solid(color) => color.a,
+                _ => unreachable!(),
+            }
+        };
+        // Opacity of ancestors is multiplied by own opacity.
+        assert_eq!(ui.node(child).global_opacity(&ui), 0.25);
+        assert_eq!(child_alpha(&mut ui), 63);
+
+        ui.send_message(WidgetMessage::opacity(
+            parent,
+            MessageDirection::ToWidget,
+            1.0,
+        ));
+        while let Some(_) = ui.poll_message() {}
+        assert_eq!(child_alpha(&mut ui), 127);
+    }
+
+    #[test]
+    fn surface_cursor() {
+        let screen_size = Vector2::new(200.0, 100.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+        let button = BorderBuilder::new(
+            WidgetBuilder::new()
+                .with_width(20.0)
+                .with_height(20.0)
+                .with_desired_position(Vector2::new(100.0, 50.0)),
+        )
+        .with_stroke_thickness(Thickness::zero())
+        .build(&mut ui.build_ctx());
+        while let Some(_) = ui.poll_message() {}
+        ui.update(screen_size, 0.0);
+        ui.draw();
+
+        ui.process_surface_cursor(Vector2::new(0.55, 0.625));
+        assert_eq!(ui.cursor_position(), Vector2::new(110.0, 62.5));
+        assert_eq!(ui.picked_node, button);
+
+        ui.process_surface_cursor(Vector2::new(0.45, 0.625));
+        assert_ne!(ui.picked_node, button);
+    }
+
+    #[test]
+    fn message_observer() {
+        let screen_size = Vector2::new(1000.0, 1000.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+        let border =
+            BorderBuilder::new(WidgetBuilder::new().with_width(10.0)).build(&mut ui.build_ctx());
+        while let Some(_) = ui.poll_message() {}
+
+        let observed = Rc::new(RefCell::new(Vec::new()));
+        ui.set_message_observer(Some(Box::new({
+            let observed = observed.clone();
+            move |message| {
+                if let UiMessageData::Widget(WidgetMessage::Width(width)) = message.data() {
+                    observed.borrow_mut().push(*width);
+                    // Intercept too large widths.
+                    if *width > 100.0 {
+                        message.set_handled(true);
+                    }
+                }
+            }
+        })));
+
+        ui.send_message(WidgetMessage::width(
+            border,
+            MessageDirection::ToWidget,
+            20.0,
+        ));
+        while let Some(_) = ui.poll_message() {}
+        assert_eq!(ui.node(border).width(), 20.0);
+
+        ui.send_message(WidgetMessage::width(
+            border,
+            MessageDirection::ToWidget,
+            200.0,
+        ));
+        let message = ui.poll_message().unwrap();
+        assert!(message.handled());
+        assert_eq!(ui.node(border).width(), 20.0);
+        assert_eq!(*observed.borrow(), vec![20.0, 200.0]);
+
+        assert!(ui.set_message_observer(None).is_some());
+        ui.send_message(WidgetMessage::width(
+            border,
+            MessageDirection::ToWidget,
+            300.0,
+        ));
+        while let Some(_) = ui.poll_message() {}
+        assert_eq!(ui.node(border).width(), 300.0);
+        assert_eq!(observed.borrow().len(), 2);
+    }
+
+    #[test]
+    fn send_message_from_other_threads() {
+        let screen_size = Vector2::new(1000.0, 1000.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+        let removed = BorderBuilder::new(WidgetBuilder::new()).build(&mut ui.build_ctx());
+        let border = BorderBuilder::new(WidgetBuilder::new()).build(&mut ui.build_ctx());
+        ui.send_message(WidgetMessage::remove(removed, MessageDirection::ToWidget));
+        while let Some(_) = ui.poll_message() {}
+
+        let threads = (0..4)
+            .map(|i| {
+                let sender = ui.sender();
+                std::thread::spawn(move || {
+                    for k in 0..100 {
+                        let width = (i * 100 + k) as f32;
+                        // Messages for removed nodes must not stop processing of others.
+                        sender
+                            .send(WidgetMessage::width(
+                                removed,
+                                MessageDirection::ToWidget,
+                                width,
+                            ))
+                            .unwrap();
+                        sender
+                            .send(WidgetMessage::width(
+                                border,
+                                MessageDirection::ToWidget,
+                                width,
+                            ))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // Messages of each thread come in order they were sent.
+        let mut last = [None; 4];
+        let mut count = 0;
+        while let Some(message) = ui.poll_message() {
+            if let UiMessageData::Widget(WidgetMessage::Width(width)) = message.data() {
+                assert_eq!(message.destination(), border);
+                let thread = *width as usize / 100;
+                assert!(last[thread].map_or(true, |last| last < *width));
+                last[thread] = Some(*width);
+                count += 1;
+            }
+        }
+        assert_eq!(count, 400);
+    }
+
+    type RoutingLog = Rc<RefCell<Vec<(Handle<UINode<(), Interceptor>>, RoutingPhase)>>>;
+
+    // Records key messages passed through it, optionally intercepts them in preview phase.
+    #[derive(Clone)]
+    struct Interceptor {
+        widget: Widget<(), Interceptor>,
+        intercept: bool,
+        log: RoutingLog,
+    }
+
+    impl Deref for Interceptor {
+        type Target = Widget<(), Interceptor>;
+
+        fn deref(&self) -> &Self::Target {
+            &self.widget
+        }
+    }
+
+    impl DerefMut for Interceptor {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.widget
+        }
+    }
+
+    impl crate::Control<(), Interceptor> for Interceptor {
+        fn handle_routed_message(
+            &mut self,
+            ui: &mut UserInterface<(), Interceptor>,
+            message: &mut UiMessage<(), Interceptor>,
+        ) {
+            self.widget.handle_routed_message(ui, message);
+
+            if let UiMessageData::Widget(WidgetMessage::KeyDown(_)) = message.data() {
+                self.log
+                    .borrow_mut()
+                    .push((self.handle(), message.routing_phase()));
+                if self.intercept && message.routing_phase() == RoutingPhase::Preview {
+                    message.set_handled(true);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn message_routing() {
+        let mut ui = UserInterface::<(), Interceptor>::new(Vector2::new(100.0, 100.0));
+        let log = RoutingLog::default();
+        let child = ui.add_node(UINode::User(Interceptor {
+            widget: WidgetBuilder::new().build(),
+            intercept: false,
+            log: log.clone(),
+        }));
+        let parent = ui.add_node(UINode::User(Interceptor {
+            widget: WidgetBuilder::new().with_child(child).build(),
+            intercept: false,
+            log: log.clone(),
+        }));
+        while let Some(_) = ui.poll_message() {}
+
+        let route = |ui: &mut UserInterface<(), Interceptor>, strategy| {
+            log.borrow_mut().clear();
+            ui.send_message(
+                WidgetMessage::key_down(child, MessageDirection::FromWidget, KeyCode::A)
+                    .with_routing_strategy(strategy),
+            );
+            while let Some(_) = ui.poll_message() {}
+            log.borrow().clone()
+        };
+
+        use RoutingPhase::{Bubble, Preview};
+        assert_eq!(
+            route(&mut ui, RoutingStrategy::Bubble),
+            vec![(child, Bubble), (parent, Bubble)]
+        );
+        assert_eq!(
+            route(&mut ui, RoutingStrategy::Direct),
+            vec![(child, Bubble)]
+        );
+        assert_eq!(
+            route(&mut ui, RoutingStrategy::Tunnel),
+            vec![
+                (parent, Preview),
+                (child, Preview),
+                (child, Bubble),
+                (parent, Bubble)
+            ]
+        );
+
+        // Parent handles the message before its child, so routing stops.
+        if let UINode::User(interceptor) = &mut ui.nodes[parent] {
+            interceptor.intercept = true;
+        }
+        assert_eq!(
+            route(&mut ui, RoutingStrategy::Tunnel),
+            vec![(parent, Preview)]
+        );
+
+        // Keyboard input is tunneled.
+        log.borrow_mut().clear();
+        ui.keyboard_focus_node = child;
+        ui.process_os_event(&OsEvent::KeyboardInput {
+            button: KeyCode::A,
+            state: ButtonState::Pressed,
+        });
+        while let Some(_) = ui.poll_message() {}
+        assert_eq!(*log.borrow(), vec![(parent, Preview)]);
+    }
+
+    #[test]
+    fn window_closing_veto() {
+        let mut ui = UserInterface::<(), StubNode>::new(Vector2::new(1000.0, 1000.0));
+        let ctx = &mut ui.build_ctx();
+        // Header buttons without text.
+        let minimize_button = BorderBuilder::new(WidgetBuilder::new()).build(ctx);
+        let close_button = BorderBuilder::new(WidgetBuilder::new()).build(ctx);
+        let window = WindowBuilder::new(WidgetBuilder::new())
+            .with_minimize_button(minimize_button)
+            .with_close_button(close_button)
+            .open(false)
+            .build(ctx);
+
+        ui.send_message(WindowMessage::open_modal(
+            window,
+            MessageDirection::ToWidget,
+            false,
+        ));
+        let mut opened = false;
+        while let Some(message) = ui.poll_message() {
+            opened |= message.destination() == window
+                && message.direction() == MessageDirection::FromWidget
+                && message.data()
+                    == &UiMessageData::Window(WindowMessage::OpenModal { center: false });
+        }
+        assert!(opened);
+        assert!(ui.top_picking_restriction().is_some());
+
+        let click = |ui: &mut UserInterface<(), StubNode>| {
+            ui.send_message(ButtonMessage::click(
+                close_button,
+                MessageDirection::FromWidget,
+            ));
+            let mut closed = false;
+            while let Some(message) = ui.poll_message() {
+                closed |= message.destination() == window
+                    && message.direction() == MessageDirection::FromWidget
+                    && message.data() == &UiMessageData::Window(WindowMessage::Close);
+            }
+            closed
+        };
+
+        // Keep the window open.
+        ui.set_message_observer(Some(Box::new(|message| {
+            if message.data() == &UiMessageData::Window(WindowMessage::Closing) {
+                message.set_handled(true);
+            }
+        })));
+        assert!(!click(&mut ui));
+        assert!(ui.node(window).visibility());
+        assert!(ui.top_picking_restriction().is_some());
+
+        ui.set_message_observer(None);
+        assert!(click(&mut ui));
+        assert!(!ui.node(window).visibility());
+        assert!(ui.top_picking_restriction().is_none());
+    }
 }
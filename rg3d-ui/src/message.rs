@@ -12,22 +12,33 @@
 //! means that it can be sent either from internals of library or from user code.
 //! However [WidgetMessage::GotFocus](enum.WidgetMessage.html) has "Direction: From UI" which means that only
 //! internal library code can send such messages without a risk of breaking anything.
+//!
+//! # Routing
+//!
+//! Each message has a routing strategy which defines which widgets will receive the message
+//! and in which order, see [RoutingStrategy](enum.RoutingStrategy.html). By default messages
+//! bubble from destination up on tree to the root.
 
 use crate::core::algebra::{Vector2, Vector3};
 use crate::dock::SplitDirection;
 use crate::{
     brush::Brush,
+    canvas::CanvasAnchor,
     core::{
         color::{Color, Hsv},
+        math::Rect,
         pool::Handle,
     },
     dock::TileContent,
     draw::SharedTexture,
+    image::NineSlice,
     messagebox::MessageBoxResult,
     popup::Placement,
+    rich_text::TextSpan,
     ttf::SharedFont,
     window::WindowTitle,
-    Control, HorizontalAlignment, MouseState, Orientation, Thickness, UINode, VerticalAlignment,
+    Anchors, Control, HorizontalAlignment, MouseState, Orientation, TextDirection, Thickness,
+    UINode, VerticalAlignment, WrapMode,
 };
 use std::{cell::Cell, fmt::Debug, path::PathBuf};
 
@@ -39,7 +50,9 @@ macro_rules! define_constructor {
                 data: UiMessageData::$var($inner::$inner_var),
                 destination,
                 direction,
-                perform_layout: Cell::new($perform_layout)
+                perform_layout: Cell::new($perform_layout),
+                routing_strategy: Default::default(),
+                routing_phase: Cell::new(Default::default()),
             }
         }
     };
@@ -51,7 +64,9 @@ macro_rules! define_constructor {
                 data: UiMessageData::$var($inner::$inner_var(value)),
                 destination,
                 direction,
-                perform_layout: Cell::new($perform_layout)
+                perform_layout: Cell::new($perform_layout),
+                routing_strategy: Default::default(),
+                routing_phase: Cell::new(Default::default()),
             }
         }
     };
@@ -63,7 +78,9 @@ macro_rules! define_constructor {
                 data: UiMessageData::$var($inner::$inner_var { $($params),+ } ),
                 destination,
                 direction,
-                perform_layout: Cell::new($perform_layout)
+                perform_layout: Cell::new($perform_layout),
+                routing_strategy: Default::default(),
+                routing_phase: Cell::new(Default::default()),
             }
         }
     }
@@ -77,7 +94,9 @@ macro_rules! define_constructor_unbound {
                 data: UiMessageData::$var($inner::$inner_var),
                 destination,
                 direction,
-                perform_layout: Cell::new($perform_layout)
+                perform_layout: Cell::new($perform_layout),
+                routing_strategy: Default::default(),
+                routing_phase: Cell::new(Default::default()),
             }
         }
     };
@@ -89,7 +108,9 @@ macro_rules! define_constructor_unbound {
                 data: UiMessageData::$var($inner::$inner_var(value)),
                 destination,
                 direction,
-                perform_layout: Cell::new($perform_layout)
+                perform_layout: Cell::new($perform_layout),
+                routing_strategy: Default::default(),
+                routing_phase: Cell::new(Default::default()),
             }
         }
     };
@@ -101,7 +122,9 @@ macro_rules! define_constructor_unbound {
                 data: UiMessageData::$var($inner::$inner_var { $($params),+ } ),
                 destination,
                 direction,
-                perform_layout: Cell::new($perform_layout)
+                perform_layout: Cell::new($perform_layout),
+                routing_strategy: Default::default(),
+                routing_phase: Cell::new(Default::default()),
             }
         }
     }
@@ -303,6 +326,12 @@ pub enum WidgetMessage<M: MessageData, C: Control<M, C>> {
     /// Direction: **From/To UI**
     Margin(Thickness),
 
+    /// A request to set new anchors of widget, `None` means that widget is placed using its margin and alignments. See
+    /// `Anchors` for more info.
+    ///
+    /// Direction: **From/To UI**
+    Anchors(Option<Anchors>),
+
     /// A request to set new state hit test visibility. If set to false, widget will become "non-clickable". It is useful for
     /// decorations which should be transparent for mouse events.
     ///
@@ -329,12 +358,24 @@ pub enum WidgetMessage<M: MessageData, C: Control<M, C>> {
     /// Direction: **From/To UI**
     DesiredPosition(Vector2<f32>),
 
-    /// A request to enable or disable widget. Disabled widget won't receive mouse events and may look differently (it is defined
-    /// by internal styling).
+    /// A request to enable or disable widget. Disabled widget and all its descendants won't receive mouse and keyboard events
+    /// and are drawn with dimmed brushes, but still take space in layout (unlike invisible widgets).
     ///
     /// Direction: **From/To UI**
     Enabled(bool),
 
+    /// A request to enable or disable clipping of widget and its descendants by bounds of the widget. Content of a widget
+    /// without clipping may overflow it, but it is still clipped by ancestors with enabled clipping.
+    ///
+    /// Direction: **From/To UI**
+    ClipToBounds(bool),
+
+    /// A request to set new opacity of a widget, value is clamped to `[0; 1]` range. Opacity is multiplied by opacity of
+    /// ancestors, so it can be used to fade whole subtree.
+    ///
+    /// Direction: **From/To UI**
+    Opacity(f32),
+
     /// A request to set desired position at center in local coordinates.
     ///
     /// Direction: **From/To UI**
@@ -359,6 +400,8 @@ impl<M: MessageData, C: Control<M, C>> WidgetMessage<M, C> {
     define_constructor!(Widget(WidgetMessage:Center) => fn center(), layout: true);
     define_constructor!(Widget(WidgetMessage:TopMost) => fn topmost(), layout: false);
     define_constructor!(Widget(WidgetMessage:Enabled) => fn enabled(bool), layout: false);
+    define_constructor!(Widget(WidgetMessage:ClipToBounds) => fn clip_to_bounds(bool), layout: false);
+    define_constructor!(Widget(WidgetMessage:Opacity) => fn opacity(f32), layout: false);
     define_constructor!(Widget(WidgetMessage:Name) => fn name(String), layout: false);
     define_constructor!(Widget(WidgetMessage:Row) => fn row(usize), layout: false);
     define_constructor!(Widget(WidgetMessage:Column) => fn column(usize), layout: false);
@@ -366,6 +409,7 @@ impl<M: MessageData, C: Control<M, C>> WidgetMessage<M, C> {
     define_constructor!(Widget(WidgetMessage:ZIndex) => fn z_index(usize), layout: false);
     define_constructor!(Widget(WidgetMessage:HitTestVisibility) => fn hit_test_visibility(bool), layout: false);
     define_constructor!(Widget(WidgetMessage:Margin) => fn margin(Thickness), layout: false);
+    define_constructor!(Widget(WidgetMessage:Anchors) => fn anchors(Option<Anchors>), layout: false);
     define_constructor!(Widget(WidgetMessage:MinSize) => fn min_size(Vector2<f32>), layout: false);
     define_constructor!(Widget(WidgetMessage:MaxSize) => fn max_size(Vector2<f32>), layout: false);
     define_constructor!(Widget(WidgetMessage:HorizontalAlignment) => fn horizontal_alignment(HorizontalAlignment), layout: false);
@@ -423,17 +467,29 @@ impl CheckBoxMessage {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum WindowMessage<M: MessageData, C: Control<M, C>> {
-    /// Opens a window.
+    /// Opens a window. When a window is opened, it sends the same message with
+    /// `MessageDirection::FromWidget` direction.
     Open { center: bool },
 
     /// Opens window in modal mode. Modal mode does **not** blocks current thread, instead
     /// it just restricts mouse and keyboard events only to window so other content is not
-    /// clickable/type-able. Closing a window removes that restriction.
+    /// clickable/type-able. Closing a window removes that restriction. When a window is opened,
+    /// it sends the same message with `MessageDirection::FromWidget` direction.
     OpenModal { center: bool },
 
-    /// Closes a window.
+    /// Closes a window unconditionally. When a window is closed, it sends the same message with
+    /// `MessageDirection::FromWidget` direction, so it can be used to check whether a window was
+    /// closed by someone.
     Close,
 
+    /// Request to close a window, close button of a window sends it instead of `Close`. The
+    /// message is tunneled (see `RoutingStrategy::Tunnel`), window closes itself only if nobody
+    /// marked the message as handled while it was routed. This allows to keep a window open,
+    /// for example to ask user to save changes first. The message can be intercepted by message
+    /// observer (see `UserInterface::set_message_observer`) or by parents of the window in
+    /// preview phase.
+    Closing,
+
     /// Minimizes a window - it differs from classic minimization in window managers,
     /// instead of putting window in system tray, it just collapses internal content panel.
     Minimize(bool),
@@ -464,6 +520,22 @@ impl<M: MessageData, C: Control<M, C>> WindowMessage<M, C> {
     define_constructor!(Window(WindowMessage:Open) => fn open(center: bool), layout: false);
     define_constructor!(Window(WindowMessage:OpenModal) => fn open_modal(center: bool), layout: false);
     define_constructor!(Window(WindowMessage:Close) => fn close(), layout: false);
+
+    /// Creates tunneled request to close a window, see `WindowMessage::Closing`.
+    pub fn closing(
+        destination: Handle<UINode<M, C>>,
+        direction: MessageDirection,
+    ) -> UiMessage<M, C> {
+        UiMessage {
+            handled: Cell::new(false),
+            data: UiMessageData::Window(WindowMessage::Closing),
+            destination,
+            direction,
+            perform_layout: Cell::new(false),
+            routing_strategy: RoutingStrategy::Tunnel,
+            routing_phase: Cell::new(Default::default()),
+        }
+    }
     define_constructor!(Window(WindowMessage:Minimize) => fn minimize(bool), layout: false);
     define_constructor!(Window(WindowMessage:CanMinimize) => fn can_minimize(bool), layout: false);
     define_constructor!(Window(WindowMessage:CanClose) => fn can_close(bool), layout: false);
@@ -573,6 +645,8 @@ impl<M: MessageData, C: Control<M, C>> TreeMessage<M, C> {
             destination,
             direction,
             perform_layout: Cell::new(false),
+            routing_strategy: Default::default(),
+            routing_phase: Cell::new(Default::default()),
         }
     }
 }
@@ -615,34 +689,96 @@ impl TextBoxMessage {
 #[derive(Debug, Clone, PartialEq)]
 pub enum TextMessage {
     Text(String),
-    Wrap(bool),
+    Wrap(WrapMode),
     Font(SharedFont),
     VerticalAlignment(VerticalAlignment),
     HorizontalAlignment(HorizontalAlignment),
+    Direction(TextDirection),
+    Justify(bool),
 }
 
 impl TextMessage {
     define_constructor_unbound!(Text(TextMessage:Text) => fn text(String), layout: false);
-    define_constructor_unbound!(Text(TextMessage:Wrap) => fn wrap(bool), layout: false);
+    define_constructor_unbound!(Text(TextMessage:Wrap) => fn wrap(WrapMode), layout: false);
     define_constructor_unbound!(Text(TextMessage:Font) => fn font(SharedFont), layout: false);
     define_constructor_unbound!(Text(TextMessage:VerticalAlignment) => fn vertical_alignment(VerticalAlignment), layout: false);
     define_constructor_unbound!(Text(TextMessage:HorizontalAlignment) => fn horizontal_alignment(HorizontalAlignment), layout: false);
+    define_constructor_unbound!(Text(TextMessage:Direction) => fn direction(TextDirection), layout: false);
+    define_constructor_unbound!(Text(TextMessage:Justify) => fn justify(bool), layout: false);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RichTextMessage {
+    /// Replaces all spans of rich text.
+    ///
+    /// Direction: **To UI**.
+    Spans(Vec<TextSpan>),
+    /// Adds spans to the end of rich text, useful for logs and chats.
+    ///
+    /// Direction: **To UI**.
+    Append(Vec<TextSpan>),
+    /// Sets default font of spans.
+    ///
+    /// Direction: **To UI**.
+    Font(SharedFont),
+    /// Direction: **To UI**.
+    Wrap(WrapMode),
+}
+
+impl RichTextMessage {
+    define_constructor_unbound!(RichText(RichTextMessage:Spans) => fn spans(Vec<TextSpan>), layout: false);
+    define_constructor_unbound!(RichText(RichTextMessage:Append) => fn append(Vec<TextSpan>), layout: false);
+    define_constructor_unbound!(RichText(RichTextMessage:Font) => fn font(SharedFont), layout: false);
+    define_constructor_unbound!(RichText(RichTextMessage:Wrap) => fn wrap(WrapMode), layout: false);
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ImageMessage {
     Texture(Option<SharedTexture>),
+    /// Same as `FlipVertical`, kept for compatibility.
+    #[deprecated(note = "use `ImageMessage::FlipVertical` instead")]
     Flip(bool),
+    FlipHorizontal(bool),
+    FlipVertical(bool),
+    /// Sets rectangle of texture coordinates that will be shown.
+    ///
+    /// Direction: **To UI**.
+    UvRect(Rect<f32>),
+    /// Sets size of a tile in pixels, `None` - stretch texture over the whole image.
+    ///
+    /// Direction: **To UI**.
+    Tiling(Option<Vector2<f32>>),
+    /// Sets nine-slice mode, `None` - disables nine-slice mode.
+    ///
+    /// Direction: **To UI**.
+    NineSlice(Option<NineSlice>),
 }
 
 impl ImageMessage {
     define_constructor_unbound!(Image(ImageMessage:Texture) => fn texture(Option<SharedTexture>), layout: false);
-    define_constructor_unbound!(Image(ImageMessage:Flip) => fn flip(bool), layout: false);
+
+    #[deprecated(note = "use `ImageMessage::flip_vertical` instead")]
+    pub fn flip<M: MessageData, C: Control<M, C>>(
+        destination: Handle<UINode<M, C>>,
+        direction: MessageDirection,
+        flip: bool,
+    ) -> UiMessage<M, C> {
+        Self::flip_vertical(destination, direction, flip)
+    }
+
+    define_constructor_unbound!(Image(ImageMessage:FlipHorizontal) => fn flip_horizontal(bool), layout: false);
+    define_constructor_unbound!(Image(ImageMessage:FlipVertical) => fn flip_vertical(bool), layout: false);
+    define_constructor_unbound!(Image(ImageMessage:UvRect) => fn uv_rect(Rect<f32>), layout: false);
+    define_constructor_unbound!(Image(ImageMessage:Tiling) => fn tiling(Option<Vector2<f32>>), layout: false);
+    define_constructor_unbound!(Image(ImageMessage:NineSlice) => fn nine_slice(Option<NineSlice>), layout: false);
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TileMessage<M: MessageData, C: Control<M, C>> {
     Content(TileContent<M, C>),
+    /// Sets position of splitter of a split tile, value is clamped to `[0; 1]` range.
+    /// Has no effect if tile is not split.
+    Splitter(f32),
     /// Internal. Do not use.
     Split {
         window: Handle<UINode<M, C>>,
@@ -653,6 +789,7 @@ pub enum TileMessage<M: MessageData, C: Control<M, C>> {
 
 impl<M: MessageData, C: Control<M, C>> TileMessage<M, C> {
     define_constructor!(Tile(TileMessage:Content) => fn content(TileContent<M, C>), layout: false);
+    define_constructor!(Tile(TileMessage:Splitter) => fn splitter(f32), layout: false);
 
     pub(in crate) fn split(
         destination: Handle<UINode<M, C>>,
@@ -671,6 +808,8 @@ impl<M: MessageData, C: Control<M, C>> TileMessage<M, C> {
             destination,
             direction,
             perform_layout: Cell::new(false),
+            routing_strategy: Default::default(),
+            routing_phase: Cell::new(Default::default()),
         }
     }
 }
@@ -845,6 +984,19 @@ impl ColorFieldMessage {
     define_constructor_unbound!(ColorField(ColorFieldMessage:Color) => fn color(Color), layout: false);
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum CanvasMessage<M: MessageData, C: Control<M, C>> {
+    /// Anchors a child of a canvas to a point of the canvas, see `canvas` module docs.
+    Anchor {
+        child: Handle<UINode<M, C>>,
+        anchor: CanvasAnchor,
+    },
+}
+
+impl<M: MessageData, C: Control<M, C>> CanvasMessage<M, C> {
+    define_constructor!(Canvas(CanvasMessage:Anchor) => fn anchor(child: Handle<UINode<M, C>>, anchor: CanvasAnchor), layout: false);
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum UiMessageData<M: MessageData, C: Control<M, C>> {
     Widget(WidgetMessage<M, C>),
@@ -868,6 +1020,7 @@ pub enum UiMessageData<M: MessageData, C: Control<M, C>> {
     MessageBox(MessageBoxMessage),
     Decorator(DecoratorMessage),
     Text(TextMessage),
+    RichText(RichTextMessage),
     ScrollPanel(ScrollPanelMessage<M, C>),
     Tile(TileMessage<M, C>),
     ProgressBar(ProgressBarMessage),
@@ -877,6 +1030,7 @@ pub enum UiMessageData<M: MessageData, C: Control<M, C>> {
     ColorPicker(ColorPickerMessage),
     ColorField(ColorFieldMessage),
     SaturationBrightnessField(SaturationBrightnessFieldMessage),
+    Canvas(CanvasMessage<M, C>),
     User(M),
 }
 
@@ -914,7 +1068,48 @@ impl MessageDirection {
     }
 }
 
-pub trait MessageData: 'static + Debug + Clone + PartialEq {}
+/// Defines how a message is delivered to widgets, see `UiMessage::with_routing_strategy`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum RoutingStrategy {
+    /// Message is delivered only to its destination.
+    Direct,
+    /// Message goes from its destination up on tree to the root. Handled messages are still
+    /// passed to parents, so they can react to them, for example a window becomes topmost when
+    /// a button inside it is pressed.
+    Bubble,
+    /// Message goes down from the root to its destination in preview phase first, and then
+    /// bubbles back up to the root. Marking the message as handled in either phase stops
+    /// routing, this allows parents to intercept messages before their children handle them.
+    /// Keyboard input messages are routed this way.
+    Tunnel,
+}
+
+impl Default for RoutingStrategy {
+    fn default() -> Self {
+        Self::Bubble
+    }
+}
+
+/// Phase of routing in which a message is being handled, see `UiMessage::routing_phase`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum RoutingPhase {
+    /// Message goes from the root down to its destination, only tunneled messages have
+    /// this phase.
+    Preview,
+    /// Message goes from its destination up to the root. Direct messages are handled in
+    /// this phase too.
+    Bubble,
+}
+
+impl Default for RoutingPhase {
+    fn default() -> Self {
+        Self::Bubble
+    }
+}
+
+/// User-defined message data. It must be `Send`, so messages can be sent to the interface
+/// from any thread, see `UserInterface::sender`.
+pub trait MessageData: 'static + Debug + Clone + PartialEq + Send {}
 
 /// Message is basic communication element that is used to deliver information to UI nodes
 /// or to user code.
@@ -926,8 +1121,8 @@ pub struct UiMessage<M: MessageData, C: Control<M, C>> {
     /// Actual message data. Use pattern matching to get type specific data.
     data: UiMessageData<M, C>,
 
-    /// Handle of node that will receive message. Please note that all nodes in hierarchy will
-    /// also receive this message, order is "up-on-tree".
+    /// Handle of node that will receive message. Please note that other nodes in hierarchy may
+    /// also receive this message, see `RoutingStrategy`.
     destination: Handle<UINode<M, C>>,
 
     /// Indicates the direction of the message.
@@ -944,6 +1139,12 @@ pub struct UiMessage<M: MessageData, C: Control<M, C>> {
     /// since layout pass is super heavy we should do it **only** when it is
     /// actually needed.
     perform_layout: Cell<bool>,
+
+    /// Defines which nodes will receive the message and in which order.
+    routing_strategy: RoutingStrategy,
+
+    /// Current phase of routing, it is changed by the UI while the message is routed.
+    routing_phase: Cell<RoutingPhase>,
 }
 
 impl<M: MessageData, C: Control<M, C>> UiMessage<M, C> {
@@ -964,6 +1165,8 @@ impl<M: MessageData, C: Control<M, C>> UiMessage<M, C> {
             destination: self.destination,
             direction: self.direction.reverse(),
             perform_layout: self.perform_layout.clone(),
+            routing_strategy: self.routing_strategy,
+            routing_phase: Cell::new(Default::default()),
         }
     }
 
@@ -995,6 +1198,27 @@ impl<M: MessageData, C: Control<M, C>> UiMessage<M, C> {
         self.perform_layout.get()
     }
 
+    /// Sets routing strategy of the message, default is `RoutingStrategy::Bubble`.
+    #[must_use = "method creates new value which must be used"]
+    pub fn with_routing_strategy(mut self, strategy: RoutingStrategy) -> Self {
+        self.routing_strategy = strategy;
+        self
+    }
+
+    pub fn routing_strategy(&self) -> RoutingStrategy {
+        self.routing_strategy
+    }
+
+    /// Returns current phase of routing. Tunneled messages pass through the same nodes twice,
+    /// so widgets which handle them must check the phase to not handle a message twice.
+    pub fn routing_phase(&self) -> RoutingPhase {
+        self.routing_phase.get()
+    }
+
+    pub(in crate) fn set_routing_phase(&self, phase: RoutingPhase) {
+        self.routing_phase.set(phase);
+    }
+
     /// Allows you to construct a new user-defined message.
     pub fn user(destination: Handle<UINode<M, C>>, direction: MessageDirection, msg: M) -> Self {
         Self {
@@ -1003,6 +1227,8 @@ impl<M: MessageData, C: Control<M, C>> UiMessage<M, C> {
             destination,
             direction,
             perform_layout: Cell::new(false),
+            routing_strategy: Default::default(),
+            routing_phase: Cell::new(Default::default()),
         }
     }
 }
@@ -1063,6 +1289,21 @@ impl Default for KeyboardModifiers {
     }
 }
 
+/// Key combination which is used to fire accelerators (global shortcuts like Ctrl+S),
+/// see `UserInterface::register_accelerator`. Modifiers must match exactly, so Ctrl+S
+/// does not fire on Ctrl+Shift+S.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub struct HotKey {
+    pub code: KeyCode,
+    pub modifiers: KeyboardModifiers,
+}
+
+impl HotKey {
+    pub fn new(code: KeyCode, modifiers: KeyboardModifiers) -> Self {
+        Self { code, modifiers }
+    }
+}
+
 #[derive(Debug, Hash, Ord, PartialOrd, PartialEq, Eq, Clone, Copy)]
 #[repr(u32)]
 pub enum KeyCode {
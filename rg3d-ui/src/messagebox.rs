@@ -13,7 +13,7 @@ use crate::{
     widget::{Widget, WidgetBuilder},
     window::{Window, WindowBuilder, WindowTitle},
     BuildContext, Control, HorizontalAlignment, NodeHandleMapping, Orientation, RestrictionEntry,
-    Thickness, UserInterface,
+    Thickness, UserInterface, WrapMode,
 };
 use std::ops::{Deref, DerefMut};
 
@@ -233,7 +233,7 @@ impl<'a, 'b, M: MessageData, C: Control<M, C>> MessageBoxBuilder<'b, M, C> {
                             WidgetBuilder::new().with_margin(Thickness::uniform(4.0)),
                         )
                         .with_text(self.text)
-                        .with_wrap(true)
+                        .with_wrap(WrapMode::Word)
                         .build(ctx);
                         text
                     })
@@ -259,7 +259,7 @@ impl<'a, 'b, M: MessageData, C: Control<M, C>> MessageBoxBuilder<'b, M, C> {
                     .with_child({
                         text = TextBuilder::new(WidgetBuilder::new())
                             .with_text(self.text)
-                            .with_wrap(true)
+                            .with_wrap(WrapMode::Word)
                             .build(ctx);
                         text
                     })
@@ -302,7 +302,7 @@ impl<'a, 'b, M: MessageData, C: Control<M, C>> MessageBoxBuilder<'b, M, C> {
                     .with_child({
                         text = TextBuilder::new(WidgetBuilder::new())
                             .with_text(self.text)
-                            .with_wrap(true)
+                            .with_wrap(WrapMode::Word)
                             .build(ctx);
                         text
                     })
@@ -20,6 +20,7 @@ use crate::{
     numeric::NumericUpDown,
     popup::Popup,
     progress_bar::ProgressBar,
+    rich_text::RichText,
     scroll_bar::ScrollBar,
     scroll_panel::ScrollPanel,
     scroll_viewer::ScrollViewer,
@@ -57,6 +58,7 @@ pub enum UINode<M: MessageData, C: Control<M, C>> {
     StackPanel(StackPanel<M, C>),
     TabControl(TabControl<M, C>),
     Text(Text<M, C>),
+    RichText(RichText<M, C>),
     TextBox(TextBox<M, C>),
     Window(Window<M, C>),
     Popup(Popup<M, C>),
@@ -98,6 +100,7 @@ macro_rules! static_dispatch {
             UINode::StackPanel(v) => v.$func($($args),*),
             UINode::TabControl(v) => v.$func($($args),*),
             UINode::Text(v) => v.$func($($args),*),
+            UINode::RichText(v) => v.$func($($args),*),
             UINode::TextBox(v) => v.$func($($args),*),
             UINode::Window(v) => v.$func($($args),*),
             UINode::Popup(v) => v.$func($($args),*),
@@ -153,6 +156,7 @@ impl<M: MessageData, C: Control<M, C>> UINode<M, C> {
     define_is_as!(UINode : StackPanel -> ref StackPanel<M, C> => fn is_stack_panel, fn as_stack_panel, fn as_stack_panel_mut);
     define_is_as!(UINode : TabControl -> ref TabControl<M, C> => fn is_tab_control, fn as_tab_control, fn as_tab_control_mut);
     define_is_as!(UINode : Text -> ref Text<M, C> => fn is_text, fn as_text, fn as_text_mut);
+    define_is_as!(UINode : RichText -> ref RichText<M, C> => fn is_rich_text, fn as_rich_text, fn as_rich_text_mut);
     define_is_as!(UINode : TextBox -> ref TextBox<M, C> => fn is_text_box, fn as_text_box, fn as_text_box_mut);
     define_is_as!(UINode : Window -> ref Window<M, C> => fn is_window, fn as_window, fn as_window_mut);
     define_is_as!(UINode : Popup -> ref Popup<M, C> => fn is_popup, fn as_popup, fn as_popup_mut);
@@ -7,14 +7,14 @@ use crate::{
     core::pool::Handle,
     grid::{Column, GridBuilder, Row},
     message::{
-        ButtonMessage, KeyCode, MessageData, MessageDirection, NumericUpDownMessage,
+        ButtonMessage, KeyCode, MessageData, MessageDirection, NumericUpDownMessage, RoutingPhase,
         TextBoxMessage, UiMessage, UiMessageData, WidgetMessage,
     },
     node::UINode,
     text_box::TextBoxBuilder,
     widget::{Widget, WidgetBuilder},
     BuildContext, Control, HorizontalAlignment, NodeHandleMapping, Thickness, UserInterface,
-    VerticalAlignment,
+    VerticalAlignment, WrapMode,
 };
 use std::ops::{Deref, DerefMut};
 
@@ -70,10 +70,10 @@ impl<M: MessageData, C: Control<M, C>> Control<M, C> for NumericUpDown<M, C> {
                         WidgetMessage::LostFocus => {
                             self.try_parse_value(ui);
                         }
-                        WidgetMessage::KeyDown(key) => {
-                            if let KeyCode::Return = key {
-                                self.try_parse_value(ui);
-                            }
+                        WidgetMessage::KeyDown(KeyCode::Return)
+                            if message.routing_phase() == RoutingPhase::Bubble =>
+                        {
+                            self.try_parse_value(ui);
                         }
                         _ => {}
                     }
@@ -219,7 +219,7 @@ impl<M: MessageData, C: Control<M, C>> NumericUpDownBuilder<M, C> {
                     field = TextBoxBuilder::new(WidgetBuilder::new().on_row(0).on_column(0))
                         .with_vertical_text_alignment(VerticalAlignment::Center)
                         .with_horizontal_text_alignment(HorizontalAlignment::Left)
-                        .with_wrap(true)
+                        .with_wrap(WrapMode::Letter)
                         .with_text(self.value.to_string())
                         .build(ctx);
                     field
@@ -0,0 +1,835 @@
+//! Rich text is a text which consists of spans of different style - each span can have its
+//! own brush and font (for example bold font), also spans can be inline images (icons).
+//! It is useful for chat windows, logs and so on, where a line of text can contain parts
+//! of different colors.
+//!
+//! Rich text is described by list of [`TextSpan`]s, which can be replaced or extended by
+//! [`RichTextMessage`]s. The widget is read-only, it does not emit any messages. Spans can
+//! be also created from simple markup, see [`parse_markup`].
+//!
+//! Span boundary can be inside of a word (for example when a single letter has different
+//! color), such word is still wrapped as a whole.
+
+use crate::{
+    brush::Brush,
+    core::{algebra::Vector2, color::Color, math::Rect, pool::Handle},
+    draw::{CommandKind, CommandTexture, DrawingContext, SharedTexture},
+    formatted_text::{FormattedText, FormattedTextBuilder},
+    message::{MessageData, RichTextMessage, UiMessage, UiMessageData},
+    ttf::{Font, SharedFont},
+    widget::{Widget, WidgetBuilder},
+    BuildContext, Control, UINode, UserInterface, WrapMode,
+};
+use std::{
+    cell::RefCell,
+    ops::{Deref, DerefMut},
+};
+
+/// Content of a span.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpanContent {
+    /// Text, can contain line breaks.
+    Text(String),
+    /// Inline image of given size, its bottom is aligned with the bottom of text line.
+    Image {
+        texture: SharedTexture,
+        size: Vector2<f32>,
+    },
+}
+
+/// A piece of rich text with its own style.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSpan {
+    pub content: SpanContent,
+    /// Brush of text or tint of image. If `None`, foreground brush of the widget is used for
+    /// text, images are not tinted.
+    pub brush: Option<Brush>,
+    /// Font of text. If `None`, font of the widget is used. Ignored for images.
+    pub font: Option<SharedFont>,
+}
+
+impl TextSpan {
+    /// Creates new text span with default style.
+    pub fn text<S: AsRef<str>>(text: S) -> Self {
+        Self {
+            content: SpanContent::Text(text.as_ref().to_owned()),
+            brush: None,
+            font: None,
+        }
+    }
+
+    /// Creates new inline image span.
+    pub fn image(texture: SharedTexture, size: Vector2<f32>) -> Self {
+        Self {
+            content: SpanContent::Image { texture, size },
+            brush: None,
+            font: None,
+        }
+    }
+
+    pub fn with_brush(mut self, brush: Brush) -> Self {
+        self.brush = Some(brush);
+        self
+    }
+
+    /// Shortcut for `with_brush(Brush::Solid(color))`.
+    pub fn with_color(self, color: Color) -> Self {
+        self.with_brush(Brush::Solid(color))
+    }
+
+    pub fn with_font(mut self, font: SharedFont) -> Self {
+        self.font = Some(font);
+        self
+    }
+}
+
+fn parse_color(text: &str) -> Option<Color> {
+    let hex = text.strip_prefix('#')?;
+    // `from_str_radix` accepts sign, so every character is checked explicitly.
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) || (hex.len() != 6 && hex.len() != 8) {
+        return None;
+    }
+    let component = |i: usize| u8::from_str_radix(&hex[i..(i + 2)], 16).ok();
+    let alpha = if hex.len() == 8 { component(6)? } else { 255 };
+    Some(Color::from_rgba(
+        component(0)?,
+        component(2)?,
+        component(4)?,
+        alpha,
+    ))
+}
+
+fn flush_span(text: &mut String, color: Option<&Color>, spans: &mut Vec<TextSpan>) {
+    if !text.is_empty() {
+        let span = TextSpan::text(&text);
+        spans.push(match color {
+            Some(&color) => span.with_color(color),
+            None => span,
+        });
+        text.clear();
+    }
+}
+
+/// Creates list of spans from a markup. Color of text is set by `[color=#RRGGBB]text[/color]`
+/// or `[color=#RRGGBBAA]text[/color]` tags, tags can be nested. `[[` is used to get `[`
+/// character, anything that is not a valid tag is kept as is.
+///
+/// For example `"Player [color=#FF0000]Bob[/color] joined"` gives three spans, the second one
+/// is red.
+pub fn parse_markup(markup: &str) -> Vec<TextSpan> {
+    let mut spans = Vec::new();
+    let mut colors = Vec::new();
+    let mut text = String::new();
+    let mut rest = markup;
+    while let Some(position) = rest.find('[') {
+        text.push_str(&rest[..position]);
+        rest = &rest[position..];
+
+        if rest.starts_with("[[") {
+            text.push('[');
+            rest = &rest[2..];
+            continue;
+        }
+
+        if let Some(end) = rest.find(']') {
+            let tag = &rest[1..end];
+            if let Some(color) = tag.strip_prefix("color=").and_then(parse_color) {
+                flush_span(&mut text, colors.last(), &mut spans);
+                colors.push(color);
+                rest = &rest[(end + 1)..];
+                continue;
+            } else if tag == "/color" && !colors.is_empty() {
+                flush_span(&mut text, colors.last(), &mut spans);
+                colors.pop();
+                rest = &rest[(end + 1)..];
+                continue;
+            }
+        }
+
+        // Not a tag.
+        text.push('[');
+        rest = &rest[1..];
+    }
+    text.push_str(rest);
+    flush_span(&mut text, colors.last(), &mut spans);
+    spans
+}
+
+/// Laid out part of a span which lies on a single line.
+#[derive(Clone)]
+enum Piece {
+    Text {
+        position: Vector2<f32>,
+        text: FormattedText,
+    },
+    Image {
+        bounds: Rect<f32>,
+        texture: SharedTexture,
+        brush: Brush,
+    },
+}
+
+#[derive(Clone)]
+struct Item {
+    span: usize,
+    text: String,
+    size: Vector2<f32>,
+}
+
+#[derive(Default)]
+struct Line {
+    items: Vec<Item>,
+    width: f32,
+    height: f32,
+    /// Line was created by wrapping, not by line break.
+    wrapped: bool,
+}
+
+enum Token<'a> {
+    Word(&'a str),
+    Space(&'a str),
+    LineBreak,
+    /// Inline image, it is never produced by `tokenize`.
+    Image,
+}
+
+/// Splits text on words, runs of whitespaces and line breaks.
+fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut begin = 0;
+    let mut space = false;
+    for (i, c) in text.char_indices() {
+        let is_break = c == '\n' || c == '\r';
+        if is_break || c.is_whitespace() != space {
+            if begin < i {
+                let run = &text[begin..i];
+                tokens.push(if space {
+                    Token::Space(run)
+                } else {
+                    Token::Word(run)
+                });
+            }
+            begin = i;
+            space = c.is_whitespace();
+        }
+        if is_break {
+            // '\r' is ignored, it is always a part of "\r\n" on platforms that use it.
+            if c == '\n' {
+                tokens.push(Token::LineBreak);
+            }
+            begin = i + c.len_utf8();
+        }
+    }
+    if begin < text.len() {
+        let run = &text[begin..];
+        tokens.push(if space {
+            Token::Space(run)
+        } else {
+            Token::Word(run)
+        });
+    }
+    tokens
+}
+
+fn text_width(font: &Font, text: &str) -> f32 {
+    text.chars().map(|c| font.glyph_advance(c as u32)).sum()
+}
+
+#[derive(Clone)]
+pub struct RichText<M: MessageData, C: Control<M, C>> {
+    widget: Widget<M, C>,
+    spans: Vec<TextSpan>,
+    font: SharedFont,
+    wrap: WrapMode,
+    pieces: RefCell<Vec<Piece>>,
+}
+
+crate::define_widget_deref!(RichText<M, C>);
+
+impl<M: MessageData, C: Control<M, C>> RichText<M, C> {
+    pub fn spans(&self) -> &[TextSpan] {
+        &self.spans
+    }
+
+    pub fn font(&self) -> SharedFont {
+        self.font.clone()
+    }
+
+    pub fn wrap_mode(&self) -> WrapMode {
+        self.wrap
+    }
+
+    fn push_item(&self, lines: &mut Vec<Line>, item: Item, is_space: bool, constraint: f32) {
+        let line = lines.last_mut().unwrap();
+        if self.wrap != WrapMode::NoWrap
+            && !is_space
+            && !line.items.is_empty()
+            && line.width + item.size.x > constraint
+        {
+            lines.push(Line {
+                wrapped: true,
+                ..Default::default()
+            });
+        } else if is_space && line.items.is_empty() && line.wrapped {
+            // Do not start wrapped line with whitespaces.
+            return;
+        }
+        let line = lines.last_mut().unwrap();
+
+        line.width += item.size.x;
+        line.height = line.height.max(item.size.y);
+        match line.items.last_mut() {
+            // Merge text of same span, so it will be drawn at once.
+            Some(last) if last.span == item.span && !item.text.is_empty() => {
+                last.text += &item.text;
+                last.size.x += item.size.x;
+            }
+            _ => line.items.push(item),
+        }
+    }
+
+    fn make_item(&self, span: usize, text: &str) -> Item {
+        let font = self.spans[span].font.as_ref().unwrap_or(&self.font);
+        let font = font.0.lock().unwrap();
+        Item {
+            span,
+            text: text.to_owned(),
+            size: Vector2::new(text_width(&font, text), font.ascender()),
+        }
+    }
+
+    fn layout(&self, available_size: Vector2<f32>) -> Vector2<f32> {
+        let default_height = self.font.0.lock().unwrap().ascender();
+
+        let mut tokens = Vec::new();
+        for (span_index, span) in self.spans.iter().enumerate() {
+            match &span.content {
+                SpanContent::Text(text) => {
+                    let font = span.font.as_ref().unwrap_or(&self.font);
+                    let mut font = font.0.lock().unwrap();
+                    font.cache_glyphs(&text.chars().map(|c| c as u32).collect::<Vec<_>>());
+                    tokens.extend(tokenize(text).into_iter().map(|t| (span_index, t)));
+                }
+                SpanContent::Image { .. } => tokens.push((span_index, Token::Image)),
+            }
+        }
+
+        let mut lines = vec![Line::default()];
+        let mut i = 0;
+        while i < tokens.len() {
+            let span_index = tokens[i].0;
+            match tokens[i].1 {
+                Token::LineBreak => {
+                    let height = self.make_item(span_index, "").size.y;
+                    let line = lines.last_mut().unwrap();
+                    line.height = line.height.max(height);
+                    lines.push(Line::default());
+                    i += 1;
+                }
+                Token::Space(space) => {
+                    let item = self.make_item(span_index, space);
+                    self.push_item(&mut lines, item, true, available_size.x);
+                    i += 1;
+                }
+                Token::Image => {
+                    if let SpanContent::Image { size, .. } = self.spans[span_index].content {
+                        let item = Item {
+                            span: span_index,
+                            text: String::new(),
+                            size,
+                        };
+                        self.push_item(&mut lines, item, false, available_size.x);
+                    }
+                    i += 1;
+                }
+                Token::Word(_) => {
+                    // Collect parts of the word from consecutive spans.
+                    let mut parts = Vec::new();
+                    while let Some(&(span_index, Token::Word(word))) = tokens.get(i) {
+                        parts.push(self.make_item(span_index, word));
+                        i += 1;
+                    }
+                    let width = parts.iter().map(|p| p.size.x).sum::<f32>();
+
+                    if self.wrap == WrapMode::Letter
+                        || self.wrap == WrapMode::Word && width > available_size.x
+                    {
+                        // Split word at any character.
+                        let mut buffer = [0; 4];
+                        for part in parts {
+                            for c in part.text.chars() {
+                                let item = self.make_item(part.span, c.encode_utf8(&mut buffer));
+                                self.push_item(&mut lines, item, false, available_size.x);
+                            }
+                        }
+                    } else {
+                        // Wrap whole word at once, its parts must not be separated.
+                        let line = lines.last().unwrap();
+                        if self.wrap != WrapMode::NoWrap
+                            && !line.items.is_empty()
+                            && line.width + width > available_size.x
+                        {
+                            lines.push(Line {
+                                wrapped: true,
+                                ..Default::default()
+                            });
+                        }
+                        for part in parts {
+                            self.push_item(&mut lines, part, false, f32::INFINITY);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut pieces = self.pieces.borrow_mut();
+        pieces.clear();
+        let mut size = Vector2::<f32>::default();
+        for line in lines.iter() {
+            let height = if line.height > 0.0 {
+                line.height
+            } else {
+                default_height
+            };
+            let mut x = 0.0;
+            for item in line.items.iter() {
+                let span = &self.spans[item.span];
+                let position = Vector2::new(x, size.y + height - item.size.y);
+                match &span.content {
+                    SpanContent::Text(_) => {
+                        let mut text = FormattedTextBuilder::new()
+                            .with_font(span.font.clone().unwrap_or_else(|| self.font.clone()))
+                            .with_brush(
+                                span.brush
+                                    .clone()
+                                    .unwrap_or_else(|| self.widget.foreground()),
+                            )
+                            .with_constraint(Vector2::new(f32::INFINITY, f32::INFINITY))
+                            .with_text(item.text.clone())
+                            .build();
+                        text.build();
+                        pieces.push(Piece::Text { position, text });
+                    }
+                    SpanContent::Image { texture, .. } => pieces.push(Piece::Image {
+                        bounds: Rect::new(position.x, position.y, item.size.x, item.size.y),
+                        texture: texture.clone(),
+                        brush: span.brush.clone().unwrap_or(Brush::Solid(Color::WHITE)),
+                    }),
+                }
+                x += item.size.x;
+            }
+            size.x = size.x.max(line.width);
+            size.y += height;
+        }
+        size
+    }
+}
+
+impl<M: MessageData, C: Control<M, C>> Control<M, C> for RichText<M, C> {
+    fn measure_override(
+        &self,
+        _: &UserInterface<M, C>,
+        available_size: Vector2<f32>,
+    ) -> Vector2<f32> {
+        self.layout(available_size)
+    }
+
+    fn draw(&self, drawing_context: &mut DrawingContext) {
+        let origin = self.widget.screen_bounds().position;
+        for piece in self.pieces.borrow().iter() {
+            match piece {
+                Piece::Text { position, text } => {
+                    drawing_context.draw_text(origin + position, text);
+                }
+                Piece::Image {
+                    bounds,
+                    texture,
+                    brush,
+                } => {
+                    let bounds = Rect::new(
+                        origin.x + bounds.x(),
+                        origin.y + bounds.y(),
+                        bounds.w(),
+                        bounds.h(),
+                    );
+                    drawing_context.push_rect_filled(&bounds, None);
+                    drawing_context.commit(
+                        CommandKind::Geometry,
+                        brush.clone(),
+                        CommandTexture::Texture(texture.clone()),
+                    );
+                }
+            }
+        }
+    }
+
+    fn handle_routed_message(
+        &mut self,
+        ui: &mut UserInterface<M, C>,
+        message: &mut UiMessage<M, C>,
+    ) {
+        self.widget.handle_routed_message(ui, message);
+
+        if message.destination() == self.handle() {
+            if let UiMessageData::RichText(msg) = &message.data() {
+                match msg {
+                    RichTextMessage::Spans(spans) => {
+                        self.spans = spans.clone();
+                        self.invalidate_layout();
+                    }
+                    RichTextMessage::Append(spans) => {
+                        self.spans.extend(spans.iter().cloned());
+                        self.invalidate_layout();
+                    }
+                    RichTextMessage::Font(font) => {
+                        self.font = font.clone();
+                        self.invalidate_layout();
+                    }
+                    &RichTextMessage::Wrap(wrap) => {
+                        if self.wrap != wrap {
+                            self.wrap = wrap;
+                            self.invalidate_layout();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub struct RichTextBuilder<M: MessageData, C: Control<M, C>> {
+    widget_builder: WidgetBuilder<M, C>,
+    spans: Vec<TextSpan>,
+    font: Option<SharedFont>,
+    wrap: WrapMode,
+}
+
+impl<M: MessageData, C: Control<M, C>> RichTextBuilder<M, C> {
+    pub fn new(widget_builder: WidgetBuilder<M, C>) -> Self {
+        Self {
+            widget_builder,
+            spans: Default::default(),
+            font: None,
+            wrap: WrapMode::Word,
+        }
+    }
+
+    pub fn with_spans(mut self, spans: Vec<TextSpan>) -> Self {
+        self.spans = spans;
+        self
+    }
+
+    /// Sets default font of spans, by default built-in font is used.
+    pub fn with_font(mut self, font: SharedFont) -> Self {
+        self.font = Some(font);
+        self
+    }
+
+    /// Sets wrapping mode, by default text is wrapped at words.
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn build(mut self, ui: &mut BuildContext<M, C>) -> Handle<UINode<M, C>> {
+        if self.widget_builder.foreground.is_none() {
+            self.widget_builder.foreground = Some(Brush::Solid(Color::opaque(220, 220, 220)));
+        }
+
+        let rich_text = RichText {
+            widget: self.widget_builder.build(),
+            spans: self.spans,
+            font: self.font.unwrap_or_else(|| crate::DEFAULT_FONT.clone()),
+            wrap: self.wrap,
+            pieces: Default::default(),
+        };
+        ui.add_node(UINode::RichText(rich_text))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{algebra::Vector2, color::Color, math::Rect, pool::Handle},
+        message::{MessageDirection, RichTextMessage},
+        node::StubNode,
+        rich_text::{
+            parse_color, parse_markup, text_width, tokenize, Piece, RichTextBuilder, TextSpan,
+            Token,
+        },
+        widget::WidgetBuilder,
+        UINode, UserInterface, WrapMode,
+    };
+    use std::sync::Arc;
+
+    fn width(text: &str) -> f32 {
+        let mut font = crate::DEFAULT_FONT.0.lock().unwrap();
+        font.cache_glyphs(&text.chars().map(|c| c as u32).collect::<Vec<_>>());
+        text_width(&font, text)
+    }
+
+    fn ascender() -> f32 {
+        crate::DEFAULT_FONT.0.lock().unwrap().ascender()
+    }
+
+    /// Returns text and position of every text piece, images are returned with empty text.
+    fn pieces(
+        ui: &UserInterface<(), StubNode>,
+        handle: Handle<UINode<(), StubNode>>,
+    ) -> Vec<(String, Vector2<f32>)> {
+        if let UINode::RichText(rich_text) = ui.node(handle) {
+            rich_text
+                .pieces
+                .borrow()
+                .iter()
+                .map(|piece| match piece {
+                    Piece::Text { position, text } => (text.text(), *position),
+                    Piece::Image { bounds, .. } => (String::new(), bounds.position),
+                })
+                .collect()
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let tokens = tokenize("ab  cd\r\n\nef ")
+            .into_iter()
+            .map(|token| match token {
+                Token::Word(word) => format!("w:{}", word),
+                Token::Space(space) => format!("s:{}", space),
+                Token::LineBreak => "br".to_owned(),
+                Token::Image => "img".to_owned(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec!["w:ab", "s:  ", "w:cd", "br", "br", "w:ef", "s: "]
+        );
+        assert!(tokenize("").is_empty());
+    }
+
+    #[test]
+    fn test_split_word_wrapped_as_whole() {
+        let red = Color::from_rgba(255, 0, 0, 255);
+        let screen_size = Vector2::new(1000.0, 1000.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+        // "hello" does not fit on the first line, its parts of different color must be moved
+        // to the next line together.
+        let rich_text =
+            RichTextBuilder::new(WidgetBuilder::new().with_width(width("aaa hel") + 1.0))
+                .with_spans(vec![
+                    TextSpan::text("aaa hel"),
+                    TextSpan::text("lo").with_color(red),
+                ])
+                .build(&mut ui.build_ctx());
+        ui.update(screen_size, 0.0);
+
+        let height = ascender();
+        assert_eq!(
+            pieces(&ui, rich_text),
+            vec![
+                ("aaa ".to_owned(), Vector2::new(0.0, 0.0)),
+                ("hel".to_owned(), Vector2::new(0.0, height)),
+                ("lo".to_owned(), Vector2::new(width("hel"), height)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_image_and_line_break() {
+        let screen_size = Vector2::new(1000.0, 1000.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+        let texture = Arc::new(0u32).into();
+        let rich_text = RichTextBuilder::new(WidgetBuilder::new())
+            .with_spans(vec![
+                TextSpan::text("ab"),
+                TextSpan::image(texture, Vector2::new(30.0, 40.0)),
+                TextSpan::text("\ncd"),
+            ])
+            .build(&mut ui.build_ctx());
+        ui.update(screen_size, 0.0);
+
+        // Image is higher than text, so the first line is as high as the image and text is
+        // aligned with its bottom.
+        assert_eq!(
+            pieces(&ui, rich_text),
+            vec![
+                ("ab".to_owned(), Vector2::new(0.0, 40.0 - ascender())),
+                (String::new(), Vector2::new(width("ab"), 0.0)),
+                ("cd".to_owned(), Vector2::new(0.0, 40.0)),
+            ]
+        );
+        if let UINode::RichText(rich_text) = ui.node(rich_text) {
+            match &rich_text.pieces.borrow()[1] {
+                Piece::Image { bounds, .. } => {
+                    assert_eq!(*bounds, Rect::new(width("ab"), 0.0, 30.0, 40.0))
+                }
+                Piece::Text { .. } => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_messages() {
+        let screen_size = Vector2::new(1000.0, 1000.0);
+        let mut ui = UserInterface::<(), StubNode>::new(screen_size);
+        let rich_text = RichTextBuilder::new(WidgetBuilder::new().with_width(width("aaa") + 1.0))
+            .with_spans(vec![TextSpan::text("aaa")])
+            .build(&mut ui.build_ctx());
+        ui.update(screen_size, 0.0);
+
+        let height = ascender();
+        ui.send_message(RichTextMessage::append(
+            rich_text,
+            MessageDirection::ToWidget,
+            vec![TextSpan::text(" bbb")],
+        ));
+        while let Some(_) = ui.poll_message() {}
+        ui.update(screen_size, 0.0);
+        assert_eq!(
+            pieces(&ui, rich_text),
+            vec![
+                ("aaa".to_owned(), Vector2::new(0.0, 0.0)),
+                (" ".to_owned(), Vector2::new(width("aaa"), 0.0)),
+                ("bbb".to_owned(), Vector2::new(0.0, height)),
+            ]
+        );
+
+        ui.send_message(RichTextMessage::wrap(
+            rich_text,
+            MessageDirection::ToWidget,
+            WrapMode::NoWrap,
+        ));
+        while let Some(_) = ui.poll_message() {}
+        ui.update(screen_size, 0.0);
+        assert_eq!(
+            pieces(&ui, rich_text),
+            vec![
+                ("aaa".to_owned(), Vector2::new(0.0, 0.0)),
+                (" bbb".to_owned(), Vector2::new(width("aaa"), 0.0)),
+            ]
+        );
+
+        ui.send_message(RichTextMessage::spans(
+            rich_text,
+            MessageDirection::ToWidget,
+            vec![TextSpan::text("c")],
+        ));
+        while let Some(_) = ui.poll_message() {}
+        ui.update(screen_size, 0.0);
+        assert_eq!(
+            pieces(&ui, rich_text),
+            vec![("c".to_owned(), Vector2::new(0.0, 0.0))]
+        );
+        if let UINode::RichText(rich_text) = ui.node(rich_text) {
+            assert_eq!(rich_text.spans(), &[TextSpan::text("c")]);
+            assert_eq!(rich_text.wrap_mode(), WrapMode::NoWrap);
+        }
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(
+            parse_color("#FF8000"),
+            Some(Color::from_rgba(255, 128, 0, 255))
+        );
+        assert_eq!(
+            parse_color("#ff800040"),
+            Some(Color::from_rgba(255, 128, 0, 64))
+        );
+        assert_eq!(parse_color("FF8000"), None);
+        assert_eq!(parse_color("#FF80"), None);
+        assert_eq!(parse_color("#FF80001"), None);
+        assert_eq!(parse_color("#GG8000"), None);
+        assert_eq!(parse_color("#+F8000"), None);
+        // Multi-byte characters must not cause a panic when slicing.
+        assert_eq!(parse_color("#ÿÿÿ"), None);
+        assert_eq!(parse_color(""), None);
+    }
+
+    #[test]
+    fn test_parse_markup() {
+        let red = Color::from_rgba(255, 0, 0, 255);
+
+        assert_eq!(parse_markup(""), vec![]);
+        assert_eq!(
+            parse_markup("plain text"),
+            vec![TextSpan::text("plain text")]
+        );
+        assert_eq!(
+            parse_markup("Player [color=#FF0000]Bob[/color] joined"),
+            vec![
+                TextSpan::text("Player "),
+                TextSpan::text("Bob").with_color(red),
+                TextSpan::text(" joined"),
+            ]
+        );
+        assert_eq!(
+            parse_markup("[[color=#FF0000]] [[x"),
+            vec![TextSpan::text("[color=#FF0000]] [x")]
+        );
+    }
+
+    #[test]
+    fn test_parse_nested_markup() {
+        let red = Color::from_rgba(255, 0, 0, 255);
+        let green = Color::from_rgba(0, 255, 0, 128);
+        assert_eq!(
+            parse_markup("a[color=#FF0000]b[color=#00FF0080]c[/color]d[/color]e"),
+            vec![
+                TextSpan::text("a"),
+                TextSpan::text("b").with_color(red),
+                TextSpan::text("c").with_color(green),
+                TextSpan::text("d").with_color(red),
+                TextSpan::text("e"),
+            ]
+        );
+        // Empty spans are not created.
+        assert_eq!(
+            parse_markup("[color=#FF0000][color=#00FF0080]x[/color][/color]"),
+            vec![TextSpan::text("x").with_color(green)]
+        );
+    }
+
+    #[test]
+    fn test_parse_unclosed_markup() {
+        let red = Color::from_rgba(255, 0, 0, 255);
+        // Unclosed tag colors the rest of the text.
+        assert_eq!(
+            parse_markup("a[color=#FF0000]b"),
+            vec![TextSpan::text("a"), TextSpan::text("b").with_color(red)]
+        );
+        // Tag without closing bracket and closing tag without opening one are kept as text.
+        assert_eq!(
+            parse_markup("a[color=#FF0000 b"),
+            vec![TextSpan::text("a[color=#FF0000 b")]
+        );
+        assert_eq!(
+            parse_markup("a[/color]b"),
+            vec![TextSpan::text("a[/color]b")]
+        );
+        assert_eq!(
+            parse_markup("[color=#FF0000]a[/color][/color]"),
+            vec![
+                TextSpan::text("a").with_color(red),
+                TextSpan::text("[/color]")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_color_markup() {
+        // Tags with invalid colors are not tags.
+        for markup in &[
+            "[color=red]x[/color]",
+            "[color=#FF00]x[/color]",
+            "[color=#XYZXYZ]x[/color]",
+            "[color=]x[/color]",
+            "[colour=#FF0000]x[/colour]",
+        ] {
+            assert_eq!(parse_markup(markup), vec![TextSpan::text(markup)]);
+        }
+    }
+}
@@ -9,7 +9,8 @@ use crate::{
     message::UiMessage,
     message::{TextMessage, UiMessageData},
     widget::{Widget, WidgetBuilder},
-    BuildContext, Control, HorizontalAlignment, UINode, UserInterface, VerticalAlignment,
+    BuildContext, Control, HorizontalAlignment, TextDirection, UINode, UserInterface,
+    VerticalAlignment, WrapMode,
 };
 use std::{
     cell::RefCell,
@@ -57,7 +58,7 @@ impl<M: MessageData, C: Control<M, C>> Control<M, C> for Text<M, C> {
                         self.invalidate_layout();
                     }
                     &TextMessage::Wrap(wrap) => {
-                        if self.formatted_text.borrow().is_wrap() != wrap {
+                        if self.formatted_text.borrow().wrap_mode() != wrap {
                             self.formatted_text.borrow_mut().set_wrap(wrap);
                             self.invalidate_layout();
                         }
@@ -78,6 +79,16 @@ impl<M: MessageData, C: Control<M, C>> Control<M, C> for Text<M, C> {
                             .set_vertical_alignment(vertical_alignment);
                         self.invalidate_layout();
                     }
+                    &TextMessage::Direction(direction) => {
+                        self.formatted_text.borrow_mut().set_direction(direction);
+                        self.invalidate_layout();
+                    }
+                    &TextMessage::Justify(justify) => {
+                        if self.formatted_text.borrow().is_justify() != justify {
+                            self.formatted_text.borrow_mut().set_justify(justify);
+                            self.invalidate_layout();
+                        }
+                    }
                 }
             }
         }
@@ -96,8 +107,12 @@ impl<M: MessageData, C: Control<M, C>> Text<M, C> {
         }
     }
 
-    pub fn is_wrap(&self) -> bool {
-        self.formatted_text.borrow().is_wrap()
+    pub fn wrap_mode(&self) -> WrapMode {
+        self.formatted_text.borrow().wrap_mode()
+    }
+
+    pub fn is_justify(&self) -> bool {
+        self.formatted_text.borrow().is_justify()
     }
 
     pub fn text(&self) -> String {
@@ -115,6 +130,10 @@ impl<M: MessageData, C: Control<M, C>> Text<M, C> {
     pub fn horizontal_alignment(&self) -> HorizontalAlignment {
         self.formatted_text.borrow().horizontal_alignment()
     }
+
+    pub fn direction(&self) -> TextDirection {
+        self.formatted_text.borrow().direction()
+    }
 }
 
 pub struct TextBuilder<M: MessageData, C: Control<M, C>> {
@@ -123,7 +142,9 @@ pub struct TextBuilder<M: MessageData, C: Control<M, C>> {
     font: Option<SharedFont>,
     vertical_text_alignment: VerticalAlignment,
     horizontal_text_alignment: HorizontalAlignment,
-    wrap: bool,
+    wrap: WrapMode,
+    justify: bool,
+    direction: TextDirection,
 }
 
 impl<M: MessageData, C: Control<M, C>> TextBuilder<M, C> {
@@ -134,7 +155,9 @@ impl<M: MessageData, C: Control<M, C>> TextBuilder<M, C> {
             font: None,
             vertical_text_alignment: VerticalAlignment::Top,
             horizontal_text_alignment: HorizontalAlignment::Left,
-            wrap: false,
+            wrap: WrapMode::NoWrap,
+            justify: false,
+            direction: TextDirection::Auto,
         }
     }
 
@@ -163,11 +186,25 @@ impl<M: MessageData, C: Control<M, C>> TextBuilder<M, C> {
         self
     }
 
-    pub fn with_wrap(mut self, wrap: bool) -> Self {
+    /// Sets wrapping mode of text, line breaks are recalculated on each measure pass.
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
         self.wrap = wrap;
         self
     }
 
+    /// Stretches wrapped lines to the available width, last line of each paragraph is aligned
+    /// according to horizontal text alignment. Has no effect if text is not wrapped.
+    pub fn with_justify(mut self, justify: bool) -> Self {
+        self.justify = justify;
+        self
+    }
+
+    /// Sets base direction of text, by default it is detected from text itself.
+    pub fn with_direction(mut self, direction: TextDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
     pub fn build(mut self, ui: &mut BuildContext<M, C>) -> Handle<UINode<M, C>> {
         let font = if let Some(font) = self.font {
             font
@@ -188,6 +225,8 @@ impl<M: MessageData, C: Control<M, C>> TextBuilder<M, C> {
                     .with_horizontal_alignment(self.horizontal_text_alignment)
                     .with_font(font)
                     .with_wrap(self.wrap)
+                    .with_justify(self.justify)
+                    .with_direction(self.direction)
                     .build(),
             ),
         };
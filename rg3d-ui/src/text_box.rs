@@ -5,12 +5,13 @@ use crate::{
     draw::{CommandKind, CommandTexture, DrawingContext},
     formatted_text::{FormattedText, FormattedTextBuilder},
     message::{
-        CursorIcon, KeyCode, MessageData, MessageDirection, MouseButton, TextBoxMessage, UiMessage,
-        UiMessageData, WidgetMessage,
+        CursorIcon, KeyCode, MessageData, MessageDirection, MouseButton, RoutingPhase,
+        TextBoxMessage, UiMessage, UiMessageData, WidgetMessage,
     },
     ttf::SharedFont,
     widget::{Widget, WidgetBuilder},
-    BuildContext, Control, HorizontalAlignment, UINode, UserInterface, VerticalAlignment,
+    BuildContext, Control, HorizontalAlignment, TextDirection, UINode, UserInterface,
+    VerticalAlignment, WrapMode,
 };
 use std::cmp::Ordering;
 use std::{
@@ -314,9 +315,10 @@ impl<M: MessageData, C: Control<M, C>> TextBox<M, C> {
 
     pub fn screen_pos_to_text_pos(&self, screen_pos: Vector2<f32>) -> Option<Position> {
         let caret_pos = self.widget.screen_position;
-        if let Some(font) = self.formatted_text.borrow().get_font() {
+        let text = self.formatted_text.borrow();
+        if let Some(font) = text.get_font() {
             let font = font.0.lock().unwrap();
-            for (line_index, line) in self.formatted_text.borrow().get_lines().iter().enumerate() {
+            for (line_index, line) in text.get_lines().iter().enumerate() {
                 let line_bounds = Rect::new(
                     caret_pos.x + line.x_offset,
                     caret_pos.y + line.y_offset,
@@ -324,29 +326,15 @@ impl<M: MessageData, C: Control<M, C>> TextBox<M, C> {
                     font.ascender(),
                 );
                 if line_bounds.contains(screen_pos) {
-                    let mut x = line_bounds.x();
-                    // Check each character in line.
-                    for (offset, index) in (line.begin..line.end).enumerate() {
-                        let symbol = self.formatted_text.borrow().get_raw_text()[index];
-                        let (width, height, advance) = if let Some(glyph) = font.glyph(symbol) {
-                            (
-                                glyph.bitmap_width as f32,
-                                glyph.bitmap_height as f32,
-                                glyph.advance,
-                            )
-                        } else {
-                            // Stub
-                            let h = font.height();
-                            (h, h, h)
-                        };
-                        let char_bounds = Rect::new(x, line_bounds.y(), width, height);
-                        if char_bounds.contains(screen_pos) {
-                            return Some(Position {
-                                line: line_index,
-                                offset,
-                            });
-                        }
-                        x += advance;
+                    // Characters may be reordered (right-to-left runs), so check each
+                    // character at its visual position.
+                    if let Some(offset) =
+                        text.char_offset_at(line_index, screen_pos.x - line_bounds.x())
+                    {
+                        return Some(Position {
+                            line: line_index,
+                            offset,
+                        });
                     }
                 }
             }
@@ -358,13 +346,13 @@ impl<M: MessageData, C: Control<M, C>> TextBox<M, C> {
         self.formatted_text.borrow().text()
     }
 
-    pub fn set_wrap(&mut self, wrap: bool) -> &mut Self {
+    pub fn set_wrap(&mut self, wrap: WrapMode) -> &mut Self {
         self.formatted_text.borrow_mut().set_wrap(wrap);
         self
     }
 
-    pub fn is_wrap(&self) -> bool {
-        self.formatted_text.borrow().is_wrap()
+    pub fn wrap_mode(&self) -> WrapMode {
+        self.formatted_text.borrow().wrap_mode()
     }
 
     pub fn set_font(&mut self, font: SharedFont) -> &mut Self {
@@ -397,6 +385,31 @@ impl<M: MessageData, C: Control<M, C>> TextBox<M, C> {
     pub fn horizontal_alignment(&self) -> HorizontalAlignment {
         self.formatted_text.borrow().horizontal_alignment()
     }
+
+    pub fn set_direction(&mut self, direction: TextDirection) -> &mut Self {
+        self.formatted_text.borrow_mut().set_direction(direction);
+        self
+    }
+
+    pub fn direction(&self) -> TextDirection {
+        self.formatted_text.borrow().direction()
+    }
+
+    /// Arrow keys move caret visually, in right-to-left lines it means opposite logical
+    /// direction.
+    fn visual_direction(&self, direction: HorizontalDirection) -> HorizontalDirection {
+        let rtl = self
+            .formatted_text
+            .borrow()
+            .get_lines()
+            .get(self.caret_position.line)
+            .map_or(false, |line| line.rtl);
+        match (rtl, direction) {
+            (true, HorizontalDirection::Left) => HorizontalDirection::Right,
+            (true, HorizontalDirection::Right) => HorizontalDirection::Left,
+            _ => direction,
+        }
+    }
 }
 
 impl<M: MessageData, C: Control<M, C>> Control<M, C> for TextBox<M, C> {
@@ -428,61 +441,30 @@ impl<M: MessageData, C: Control<M, C>> Control<M, C> for TextBox<M, C> {
 
         if let Some(ref selection_range) = self.selection_range.map(|r| r.normalized()) {
             let text = self.formatted_text.borrow();
-            let lines = text.get_lines();
-            if selection_range.begin.line == selection_range.end.line {
-                let line = lines[selection_range.begin.line];
-                // Begin line
-                let offset =
-                    text.get_range_width(line.begin..(line.begin + selection_range.begin.offset));
-                let width = text.get_range_width(
-                    (line.begin + selection_range.begin.offset)
-                        ..(line.begin + selection_range.end.offset),
-                );
-                let bounds = Rect::new(
-                    bounds.x() + line.x_offset + offset,
-                    bounds.y() + line.y_offset,
-                    width,
-                    line.height,
-                );
-                drawing_context.push_rect_filled(&bounds, None);
-            } else {
-                for (i, line) in text.get_lines().iter().enumerate() {
-                    if i >= selection_range.begin.line && i <= selection_range.end.line {
-                        let bounds = if i == selection_range.begin.line {
-                            // Begin line
-                            let offset = text.get_range_width(
-                                line.begin..(line.begin + selection_range.begin.offset),
-                            );
-                            let width = text.get_range_width(
-                                (line.begin + selection_range.begin.offset)..line.end,
-                            );
-                            Rect::new(
-                                bounds.x() + line.x_offset + offset,
+            for (i, line) in text.get_lines().iter().enumerate() {
+                if i >= selection_range.begin.line && i <= selection_range.end.line {
+                    let begin = if i == selection_range.begin.line {
+                        selection_range.begin.offset
+                    } else {
+                        0
+                    };
+                    let end = if i == selection_range.end.line {
+                        selection_range.end.offset.min(line.len())
+                    } else {
+                        line.len()
+                    };
+                    // Selected characters are not contiguous on screen if line contains
+                    // runs of different directions, so select each character separately.
+                    for index in (line.begin + begin)..(line.begin + end) {
+                        if let Some((x, width)) = text.char_span(index) {
+                            let bounds = Rect::new(
+                                bounds.x() + line.x_offset + x,
                                 bounds.y() + line.y_offset,
                                 width,
                                 line.height,
-                            )
-                        } else if i == selection_range.end.line {
-                            // End line
-                            let width = text.get_range_width(
-                                line.begin..(line.begin + selection_range.end.offset),
                             );
-                            Rect::new(
-                                bounds.x() + line.x_offset,
-                                bounds.y() + line.y_offset,
-                                width,
-                                line.height,
-                            )
-                        } else {
-                            // Everything between
-                            Rect::new(
-                                bounds.x() + line.x_offset,
-                                bounds.y() + line.y_offset,
-                                line.width,
-                                line.height,
-                            )
-                        };
-                        drawing_context.push_rect_filled(&bounds, None);
+                            drawing_context.push_rect_filled(&bounds, None);
+                        }
                     }
                 }
             }
@@ -504,18 +486,11 @@ impl<M: MessageData, C: Control<M, C>> Control<M, C> for TextBox<M, C> {
 
                 let font = font.0.lock().unwrap();
                 if let Some(line) = text.get_lines().get(self.caret_position.line) {
-                    let text = text.get_raw_text();
-                    caret_pos += Vector2::new(line.x_offset, line.y_offset);
-                    for (offset, char_index) in (line.begin..line.end).enumerate() {
-                        if offset >= self.caret_position.offset {
-                            break;
-                        }
-                        if let Some(glyph) = font.glyph(text[char_index]) {
-                            caret_pos.x += glyph.advance;
-                        } else {
-                            caret_pos.x += font.height();
-                        }
-                    }
+                    caret_pos += Vector2::new(
+                        line.x_offset
+                            + text.caret_x(self.caret_position.line, self.caret_position.offset),
+                        line.y_offset,
+                    );
                 }
 
                 let caret_bounds = Rect::new(caret_pos.x, caret_pos.y, 2.0, font.height());
@@ -548,7 +523,8 @@ impl<M: MessageData, C: Control<M, C>> Control<M, C> for TextBox<M, C> {
     ) {
         self.widget.handle_routed_message(ui, message);
 
-        if message.destination() == self.handle() {
+        if message.destination() == self.handle() && message.routing_phase() == RoutingPhase::Bubble
+        {
             match &message.data() {
                 UiMessageData::Widget(msg) => match msg {
                     &WidgetMessage::Text(symbol)
@@ -586,14 +562,14 @@ impl<M: MessageData, C: Control<M, C>> Control<M, C> for TextBox<M, C> {
                         KeyCode::Right => {
                             self.move_caret_x(
                                 1,
-                                HorizontalDirection::Right,
+                                self.visual_direction(HorizontalDirection::Right),
                                 ui.keyboard_modifiers().shift,
                             );
                         }
                         KeyCode::Left => {
                             self.move_caret_x(
                                 1,
-                                HorizontalDirection::Left,
+                                self.visual_direction(HorizontalDirection::Left),
                                 ui.keyboard_modifiers().shift,
                             );
                         }
@@ -786,9 +762,10 @@ pub struct TextBoxBuilder<M: MessageData, C: Control<M, C>> {
     filter: Option<Rc<RefCell<FilterCallback>>>,
     vertical_alignment: VerticalAlignment,
     horizontal_alignment: HorizontalAlignment,
-    wrap: bool,
+    wrap: WrapMode,
     commit_mode: TextCommitMode,
     multiline: bool,
+    direction: TextDirection,
 }
 
 impl<M: MessageData, C: Control<M, C>> TextBoxBuilder<M, C> {
@@ -802,9 +779,10 @@ impl<M: MessageData, C: Control<M, C>> TextBoxBuilder<M, C> {
             filter: None,
             vertical_alignment: VerticalAlignment::Top,
             horizontal_alignment: HorizontalAlignment::Left,
-            wrap: false,
+            wrap: WrapMode::NoWrap,
             commit_mode: TextCommitMode::LostFocusPlusEnter,
             multiline: false,
+            direction: TextDirection::Auto,
         }
     }
 
@@ -843,7 +821,7 @@ impl<M: MessageData, C: Control<M, C>> TextBoxBuilder<M, C> {
         self
     }
 
-    pub fn with_wrap(mut self, wrap: bool) -> Self {
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
         self.wrap = wrap;
         self
     }
@@ -858,6 +836,11 @@ impl<M: MessageData, C: Control<M, C>> TextBoxBuilder<M, C> {
         self
     }
 
+    pub fn with_direction(mut self, direction: TextDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
     pub fn build(mut self, ctx: &mut BuildContext<M, C>) -> Handle<UINode<M, C>> {
         if self.widget_builder.foreground.is_none() {
             self.widget_builder.foreground = Some(Brush::Solid(Color::opaque(220, 220, 220)));
@@ -882,6 +865,7 @@ impl<M: MessageData, C: Control<M, C>> TextBoxBuilder<M, C> {
                     .with_horizontal_alignment(self.horizontal_alignment)
                     .with_vertical_alignment(self.vertical_alignment)
                     .with_wrap(self.wrap)
+                    .with_direction(self.direction)
                     .build(),
             ),
             selection_range: None,
@@ -41,6 +41,10 @@ pub struct Font {
     char_map: HashMap<u32, usize>,
     atlas: Vec<u8>,
     atlas_size: usize,
+    atlas_revision: usize,
+    packer: RectPacker<usize>,
+    source: fontdue::Font,
+    fallbacks: Vec<fontdue::Font>,
     pub texture: Option<SharedTexture>,
 }
 
@@ -65,6 +69,8 @@ impl Debug for Font {
     }
 }
 
+const ATLAS_BORDER: usize = 2;
+
 impl Font {
     pub fn default_char_set() -> &'static [Range<u32>] {
         &[0x0020..0x00FF] // Basic Latin + Latin Supplement
@@ -75,7 +81,32 @@ impl Font {
         height: f32,
         char_set: &[Range<u32>],
     ) -> Result<Self, &'static str> {
-        let fontdue_font = fontdue::Font::from_bytes(data, fontdue::FontSettings::default())?;
+        Self::from_memory_collection(data, 0, height, char_set)
+    }
+
+    /// Returns amount of fonts in font data, it is more than one for font collections (.ttc
+    /// files) and one for plain fonts (.ttf, .otf).
+    pub fn collection_len(data: &[u8]) -> usize {
+        if data.len() >= 12 && &data[0..4] == b"ttcf" {
+            u32::from_be_bytes([data[8], data[9], data[10], data[11]]) as usize
+        } else {
+            1
+        }
+    }
+
+    /// Loads font with given index from font collection (.ttc), see `collection_len`. Index
+    /// must be zero for plain fonts.
+    pub fn from_memory_collection(
+        data: Vec<u8>,
+        index: u32,
+        height: f32,
+        char_set: &[Range<u32>],
+    ) -> Result<Self, &'static str> {
+        let settings = fontdue::FontSettings {
+            collection_index: index,
+            ..Default::default()
+        };
+        let fontdue_font = fontdue::Font::from_bytes(data, settings)?;
         let font_metrics = fontdue_font.horizontal_line_metrics(height).unwrap();
 
         let mut font = Font {
@@ -86,27 +117,19 @@ impl Font {
             char_map: HashMap::new(),
             atlas: Vec::new(),
             atlas_size: 0,
+            atlas_revision: 0,
+            packer: RectPacker::new(0, 0),
+            source: fontdue_font,
+            fallbacks: Vec::new(),
             texture: None,
         };
 
-        let mut index = 0;
         for range in char_set {
             for unicode in range.start..range.end {
                 if let Some(character) = std::char::from_u32(unicode) {
-                    let (metrics, bitmap) = fontdue_font.rasterize(character, height);
-
-                    font.glyphs.push(FontGlyph {
-                        left: metrics.xmin as f32,
-                        top: metrics.ymin as f32,
-                        pixels: bitmap,
-                        advance: metrics.advance_width,
-                        tex_coords: Default::default(),
-                        bitmap_width: metrics.width,
-                        bitmap_height: metrics.height,
-                    });
-
-                    font.char_map.insert(unicode, index);
-                    index += 1;
+                    let glyph = font.rasterize(character);
+                    font.char_map.insert(unicode, font.glyphs.len());
+                    font.glyphs.push(glyph);
                 }
             }
         }
@@ -120,18 +143,116 @@ impl Font {
         path: P,
         height: f32,
         char_set: &[Range<u32>],
+    ) -> Result<Self, &'static str> {
+        Self::from_file_collection(path, 0, height, char_set)
+    }
+
+    /// Loads font with given index from font collection file (.ttc), see
+    /// `from_memory_collection`.
+    pub fn from_file_collection<P: AsRef<Path>>(
+        path: P,
+        index: u32,
+        height: f32,
+        char_set: &[Range<u32>],
     ) -> Result<Self, &'static str> {
         if let Ok(ref mut file) = File::open(path) {
             let mut file_content: Vec<u8> =
                 Vec::with_capacity(file.metadata().unwrap().len() as usize);
             file.read_to_end(&mut file_content).unwrap();
 
-            Self::from_memory(file_content, height, char_set)
+            Self::from_memory_collection(file_content, index, height, char_set)
         } else {
             Err("Unable to read file")
         }
     }
 
+    /// Adds a font to fallback chain. When the font does not have a glyph for a character,
+    /// fallback fonts are tried in order they were added. Glyphs of fallback font are
+    /// rasterized with the height of this font. Fallback chain of given font is appended too.
+    pub fn with_fallback(mut self, fallback: Font) -> Self {
+        self.fallbacks.push(fallback.source);
+        self.fallbacks.extend(fallback.fallbacks);
+
+        // Replace glyphs that were missing in the chain before.
+        let mut changed = false;
+        for (&unicode, &index) in self.char_map.iter() {
+            if let Some(character) = std::char::from_u32(unicode) {
+                if self.source.lookup_glyph_index(character) == 0 {
+                    self.glyphs[index] = self.rasterize(character);
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            self.pack();
+        }
+
+        self
+    }
+
+    /// Rasterizes glyph using first font in fallback chain that has the glyph. If there is
+    /// no such font, "missing glyph" symbol of this font is used.
+    fn rasterize(&self, character: char) -> FontGlyph {
+        let font = std::iter::once(&self.source)
+            .chain(self.fallbacks.iter())
+            .find(|font| font.lookup_glyph_index(character) != 0)
+            .unwrap_or(&self.source);
+
+        let (metrics, bitmap) = font.rasterize(character, self.height);
+
+        FontGlyph {
+            left: metrics.xmin as f32,
+            top: metrics.ymin as f32,
+            pixels: bitmap,
+            advance: metrics.advance_width,
+            tex_coords: Default::default(),
+            bitmap_width: metrics.width,
+            bitmap_height: metrics.height,
+        }
+    }
+
+    /// Makes sure that every character of given text has a glyph in atlas. Glyphs that were
+    /// not in character set of the font are rasterized and put in atlas on demand, this
+    /// allows to use large character sets (like CJK) without rasterizing all of them at
+    /// once. Returns true if atlas has changed.
+    pub fn cache_glyphs(&mut self, text: &[u32]) -> bool {
+        let mut changed = false;
+        let mut repack = false;
+        for &unicode in text {
+            if self.char_map.contains_key(&unicode) {
+                continue;
+            }
+            let character = match std::char::from_u32(unicode) {
+                Some(character) if !character.is_control() => character,
+                _ => continue,
+            };
+
+            let mut glyph = self.rasterize(character);
+            if !repack {
+                repack = !Self::place_glyph(
+                    &mut self.packer,
+                    &mut self.atlas,
+                    self.atlas_size,
+                    &mut glyph,
+                );
+            }
+            self.char_map.insert(unicode, self.glyphs.len());
+            self.glyphs.push(glyph);
+            changed = true;
+        }
+
+        if repack {
+            self.atlas_size *= 2;
+            self.pack();
+        } else if changed {
+            self.atlas_revision += 1;
+            // Force renderer to upload new atlas.
+            self.texture = None;
+        }
+
+        changed
+    }
+
     #[inline]
     pub fn glyph(&self, unicode: u32) -> Option<&FontGlyph> {
         match self.char_map.get(&unicode) {
@@ -165,6 +286,13 @@ impl Font {
         self.atlas_size
     }
 
+    /// Returns number that changes every time when atlas is changed, texture coordinates of
+    /// glyphs taken before the change may be invalid.
+    #[inline]
+    pub fn atlas_revision(&self) -> usize {
+        self.atlas_revision
+    }
+
     #[inline]
     pub fn glyph_advance(&self, c: u32) -> f32 {
         self.glyph(c).map_or(self.height(), |glyph| glyph.advance)
@@ -179,44 +307,202 @@ impl Font {
         (1.3 * area.sqrt()) as usize
     }
 
+    fn place_glyph(
+        rect_packer: &mut RectPacker<usize>,
+        atlas: &mut [u8],
+        atlas_size: usize,
+        glyph: &mut FontGlyph,
+    ) -> bool {
+        let border = ATLAS_BORDER;
+        if let Some(bounds) =
+            rect_packer.find_free(glyph.bitmap_width + border, glyph.bitmap_height + border)
+        {
+            let k = 1.0 / atlas_size as f32;
+
+            let bw = (bounds.w() - border) as usize;
+            let bh = (bounds.h() - border) as usize;
+            let bx = (bounds.x() + border / 2) as usize;
+            let by = (bounds.y() + border / 2) as usize;
+
+            let tw = bw as f32 * k;
+            let th = bh as f32 * k;
+            let tx = bx as f32 * k;
+            let ty = by as f32 * k;
+
+            glyph.tex_coords[0] = Vector2::new(tx, ty);
+            glyph.tex_coords[1] = Vector2::new(tx + tw, ty);
+            glyph.tex_coords[2] = Vector2::new(tx + tw, ty + th);
+            glyph.tex_coords[3] = Vector2::new(tx, ty + th);
+
+            let row_end = by + bh;
+            let col_end = bx + bw;
+
+            // Copy glyph pixels to atlas pixels
+            for (src_row, row) in (by..row_end).enumerate() {
+                for (src_col, col) in (bx..col_end).enumerate() {
+                    atlas[row * atlas_size + col] = glyph.pixels[src_row * bw + src_col];
+                }
+            }
+
+            true
+        } else {
+            false
+        }
+    }
+
     fn pack(&mut self) {
-        let border = 2;
-        self.atlas_size = self.compute_atlas_size(border);
-        self.atlas = vec![0; (self.atlas_size * self.atlas_size) as usize];
-        let k = 1.0 / self.atlas_size as f32;
-        let mut rect_packer = RectPacker::new(self.atlas_size, self.atlas_size);
-        for glyph in self.glyphs.iter_mut() {
-            if let Some(bounds) =
-                rect_packer.find_free(glyph.bitmap_width + border, glyph.bitmap_height + border)
-            {
-                let bw = (bounds.w() - border) as usize;
-                let bh = (bounds.h() - border) as usize;
-                let bx = (bounds.x() + border / 2) as usize;
-                let by = (bounds.y() + border / 2) as usize;
-
-                let tw = bw as f32 * k;
-                let th = bh as f32 * k;
-                let tx = bx as f32 * k;
-                let ty = by as f32 * k;
-
-                glyph.tex_coords[0] = Vector2::new(tx, ty);
-                glyph.tex_coords[1] = Vector2::new(tx + tw, ty);
-                glyph.tex_coords[2] = Vector2::new(tx + tw, ty + th);
-                glyph.tex_coords[3] = Vector2::new(tx, ty + th);
-
-                let row_end = by + bh;
-                let col_end = bx + bw;
-
-                // Copy glyph pixels to atlas pixels
-                for (src_row, row) in (by..row_end).enumerate() {
-                    for (src_col, col) in (bx..col_end).enumerate() {
-                        self.atlas[row * self.atlas_size + col] =
-                            glyph.pixels[src_row * bw + src_col];
-                    }
+        // Atlas never shrinks, so glyphs added on demand will less likely cause repacking.
+        let mut atlas_size = self.compute_atlas_size(ATLAS_BORDER).max(self.atlas_size);
+        'try_pack: loop {
+            self.atlas = vec![0; (atlas_size * atlas_size) as usize];
+            self.packer = RectPacker::new(atlas_size, atlas_size);
+            for glyph in self.glyphs.iter_mut() {
+                if !Self::place_glyph(&mut self.packer, &mut self.atlas, atlas_size, glyph) {
+                    atlas_size = (atlas_size * 2).max(1);
+                    continue 'try_pack;
                 }
-            } else {
-                println!("Insufficient atlas size!");
             }
+            break;
+        }
+        self.atlas_size = atlas_size;
+        self.atlas_revision += 1;
+        self.texture = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ttf::Font;
+
+    /// Creates minimal TrueType font with empty glyphs, glyph advances are given in font
+    /// units, there are 1000 units per em. Glyph 0 ("missing glyph") has zero advance.
+    fn make_font(glyphs: &[(char, u16)]) -> Vec<u8> {
+        let glyph_count = glyphs.len() as u16 + 1;
+
+        let mut cmap = Vec::new();
+        cmap.extend_from_slice(&0u16.to_be_bytes()); // Version
+        cmap.extend_from_slice(&1u16.to_be_bytes()); // Subtable count
+        cmap.extend_from_slice(&0u16.to_be_bytes()); // Unicode platform
+        cmap.extend_from_slice(&4u16.to_be_bytes()); // Full repertoire encoding
+        cmap.extend_from_slice(&12u32.to_be_bytes()); // Subtable offset
+        cmap.extend_from_slice(&12u16.to_be_bytes()); // Format
+        cmap.extend_from_slice(&0u16.to_be_bytes()); // Reserved
+        cmap.extend_from_slice(&(16 + 12 * glyphs.len() as u32).to_be_bytes()); // Length
+        cmap.extend_from_slice(&0u32.to_be_bytes()); // Language
+        cmap.extend_from_slice(&(glyphs.len() as u32).to_be_bytes());
+        for (i, &(character, _)) in glyphs.iter().enumerate() {
+            cmap.extend_from_slice(&(character as u32).to_be_bytes());
+            cmap.extend_from_slice(&(character as u32).to_be_bytes());
+            cmap.extend_from_slice(&(i as u32 + 1).to_be_bytes());
+        }
+
+        let mut head = vec![0; 54];
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes()); // Units per em
+
+        let mut hhea = vec![0; 36];
+        hhea[0..4].copy_from_slice(&0x0001_0000u32.to_be_bytes()); // Version
+        hhea[4..6].copy_from_slice(&800i16.to_be_bytes()); // Ascender
+        hhea[6..8].copy_from_slice(&(-200i16).to_be_bytes()); // Descender
+        hhea[34..36].copy_from_slice(&glyph_count.to_be_bytes()); // Horizontal metric count
+
+        let mut hmtx = vec![0; 4];
+        for &(_, advance) in glyphs {
+            hmtx.extend_from_slice(&advance.to_be_bytes());
+            hmtx.extend_from_slice(&0i16.to_be_bytes()); // Left side bearing
+        }
+
+        let mut maxp = Vec::new();
+        maxp.extend_from_slice(&0x0000_5000u32.to_be_bytes()); // Version
+        maxp.extend_from_slice(&glyph_count.to_be_bytes());
+
+        let tables = [
+            (b"cmap", cmap),
+            (b"head", head),
+            (b"hhea", hhea),
+            (b"hmtx", hmtx),
+            (b"maxp", maxp),
+        ];
+        let mut data = Vec::new();
+        data.extend_from_slice(&0x0001_0000u32.to_be_bytes()); // TrueType magic
+        data.extend_from_slice(&(tables.len() as u16).to_be_bytes());
+        data.extend_from_slice(&[0; 6]); // Binary search parameters, not used by parser
+        let mut offset = 12 + 16 * tables.len();
+        for (tag, table) in tables.iter() {
+            data.extend_from_slice(&tag[..]);
+            data.extend_from_slice(&0u32.to_be_bytes()); // Checksum
+            data.extend_from_slice(&(offset as u32).to_be_bytes());
+            data.extend_from_slice(&(table.len() as u32).to_be_bytes());
+            offset += table.len();
+        }
+        for (_, table) in tables.iter() {
+            data.extend_from_slice(table);
         }
+        data
+    }
+
+    /// Loads font of 10 pixels height, so advance of 100 font units is 1 pixel.
+    fn load(glyphs: &[(char, u16)], char_set: &[std::ops::Range<u32>]) -> Font {
+        Font::from_memory(make_font(glyphs), 10.0, char_set).unwrap()
+    }
+
+    fn advance(font: &Font, character: char) -> f32 {
+        font.glyph_advance(character as u32)
+    }
+
+    #[test]
+    fn test_fallback() {
+        let char_set = ['a' as u32..'d' as u32];
+        let font = load(&[('a', 100)], &char_set);
+        assert_eq!(advance(&font, 'a'), 1.0);
+        // Glyph is in character set, but font does not have it.
+        assert_eq!(advance(&font, 'b'), 0.0);
+
+        let font = font.with_fallback(load(&[('a', 200), ('b', 300)], &[]));
+        // Font has priority over its fallbacks.
+        assert_eq!(advance(&font, 'a'), 1.0);
+        // Missing glyph was replaced.
+        assert_eq!(advance(&font, 'b'), 3.0);
+        // Nobody has the glyph, "missing glyph" of the font is used.
+        assert_eq!(advance(&font, 'c'), 0.0);
+    }
+
+    #[test]
+    fn test_fallback_order() {
+        let font = load(&[('a', 100)], &['a' as u32..'d' as u32])
+            .with_fallback(load(&[('b', 200)], &[]))
+            .with_fallback(load(&[('b', 300), ('c', 400)], &[]));
+        // First fallback that has the glyph is used.
+        assert_eq!(advance(&font, 'b'), 2.0);
+        assert_eq!(advance(&font, 'c'), 4.0);
+    }
+
+    #[test]
+    fn test_fallback_chain_is_appended() {
+        let fallback = load(&[('b', 200)], &[]).with_fallback(load(&[('c', 300)], &[]));
+        let font = load(&[('a', 100)], &['a' as u32..'d' as u32]).with_fallback(fallback);
+        assert_eq!(advance(&font, 'a'), 1.0);
+        assert_eq!(advance(&font, 'b'), 2.0);
+        assert_eq!(advance(&font, 'c'), 3.0);
+    }
+
+    #[test]
+    fn test_cache_glyphs() {
+        let mut font = load(&[('a', 100)], &[]).with_fallback(load(&[('a', 200), ('b', 300)], &[]));
+        assert!(font.glyph('b' as u32).is_none());
+        // Advance of unknown glyph is the height of the font.
+        assert_eq!(advance(&font, 'b'), 10.0);
+
+        let revision = font.atlas_revision();
+        assert!(font.cache_glyphs(&['a' as u32, 'b' as u32, '\n' as u32]));
+        assert_ne!(font.atlas_revision(), revision);
+        assert_eq!(advance(&font, 'a'), 1.0);
+        assert_eq!(advance(&font, 'b'), 3.0);
+        // Control characters are not rasterized.
+        assert!(font.glyph('\n' as u32).is_none());
+
+        // Glyphs are already cached.
+        let revision = font.atlas_revision();
+        assert!(!font.cache_glyphs(&['a' as u32, 'b' as u32]));
+        assert_eq!(font.atlas_revision(), revision);
     }
 }
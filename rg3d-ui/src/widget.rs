@@ -1,16 +1,18 @@
 use crate::core::algebra::Vector2;
-use crate::message::{MessageData, MessageDirection};
+use crate::message::{MessageData, MessageDirection, RoutingPhase};
 use crate::{
     brush::Brush,
     core::{color::Color, math::Rect, pool::Handle},
     message::{CursorIcon, UiMessage, UiMessageData, WidgetMessage},
-    Control, HorizontalAlignment, Thickness, UINode, UserInterface, VerticalAlignment,
+    Anchors, Control, HorizontalAlignment, LayoutEvent, Thickness, UINode, UserInterface,
+    VerticalAlignment,
 };
 use std::{
     any::Any,
     cell::{Cell, RefCell},
     marker::PhantomData,
     rc::Rc,
+    sync::mpsc::Sender,
 };
 
 #[derive(Debug, Clone)]
@@ -41,6 +43,8 @@ pub struct Widget<M: MessageData, C: Control<M, C>> {
     horizontal_alignment: HorizontalAlignment,
     /// Margin (four sides)
     margin: Thickness,
+    /// Anchors to parent rectangle, if set, they're used instead of margin and alignments.
+    anchors: Option<Anchors>,
     /// Current visibility state
     visibility: bool,
     global_visibility: bool,
@@ -55,6 +59,11 @@ pub struct Widget<M: MessageData, C: Control<M, C>> {
     allow_drop: bool,
     pub user_data: Option<Rc<dyn Any>>,
     draw_on_top: bool,
+    /// Clip the widget and its descendants to bounds of the widget, otherwise they're clipped
+    /// only by clipping ancestors.
+    clip_to_bounds: bool,
+    /// Opacity of the widget, it is multiplied by opacity of ancestors.
+    opacity: f32,
     marker: PhantomData<M>,
     enabled: bool,
     cursor: Option<CursorIcon>,
@@ -72,6 +81,9 @@ pub struct Widget<M: MessageData, C: Control<M, C>> {
     /// Actual size of the node after Arrange pass.
     pub(in crate) actual_size: Cell<Vector2<f32>>,
     pub(in crate) prev_global_visibility: bool,
+    /// Channel to notify user interface about layout invalidation, so it will be able to
+    /// mark ancestors of the node as dirty. Set when node is added to user interface.
+    pub(in crate) layout_events_sender: Option<Sender<LayoutEvent<M, C>>>,
 }
 
 impl<M: MessageData, C: Control<M, C>> Widget<M, C> {
@@ -124,10 +136,35 @@ impl<M: MessageData, C: Control<M, C>> Widget<M, C> {
         self.allow_drop
     }
 
+    /// Marks measurement and arrangement of the node as invalid. Only the node and its
+    /// ancestors will be re-measured and re-arranged on next update of user interface,
+    /// the rest of the tree will keep its cached layout.
     #[inline]
     pub fn invalidate_layout(&self) {
+        self.invalidate_measure();
+        self.invalidate_arrange();
+    }
+
+    /// Marks measurement of the node as invalid. Use this when desired size of the node
+    /// could change.
+    #[inline]
+    pub fn invalidate_measure(&self) {
         self.measure_valid.set(false);
+
+        if let Some(sender) = self.layout_events_sender.as_ref() {
+            let _ = sender.send(LayoutEvent::MeasurementInvalidated(self.handle));
+        }
+    }
+
+    /// Marks arrangement of the node as invalid. Use this when only position or size of
+    /// the node within its parent could change, but not desired size.
+    #[inline]
+    pub fn invalidate_arrange(&self) {
         self.arrange_valid.set(false);
+
+        if let Some(sender) = self.layout_events_sender.as_ref() {
+            let _ = sender.send(LayoutEvent::ArrangementInvalidated(self.handle));
+        }
     }
 
     pub fn is_hit_test_visible(&self) -> bool {
@@ -203,6 +240,35 @@ impl<M: MessageData, C: Control<M, C>> Widget<M, C> {
         self.draw_on_top
     }
 
+    /// Sets whether the widget and its descendants are clipped to bounds of the widget. If
+    /// clipping is disabled, content may overflow the widget, but it is still clipped by
+    /// ancestors that have clipping enabled. Enabled by default.
+    #[inline]
+    pub fn set_clip_to_bounds(&mut self, clip_to_bounds: bool) -> &mut Self {
+        self.clip_to_bounds = clip_to_bounds;
+        self
+    }
+
+    #[inline]
+    pub fn clip_to_bounds(&self) -> bool {
+        self.clip_to_bounds
+    }
+
+    /// Sets opacity of the widget in `[0; 1]` range. Opacity affects the widget and all its
+    /// descendants - it is multiplied by opacity of ancestors, so a widget with opacity 0.5
+    /// in a panel with opacity 0.5 is drawn with opacity 0.25. Fully transparent widgets
+    /// still receive mouse events. Default opacity is 1.0.
+    #[inline]
+    pub fn set_opacity(&mut self, opacity: f32) -> &mut Self {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        self
+    }
+
+    #[inline]
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
+
     #[inline]
     pub fn set_height(&mut self, height: f32) -> &mut Self {
         self.height = height.max(self.min_size.y).min(self.max_size.y);
@@ -292,6 +358,8 @@ impl<M: MessageData, C: Control<M, C>> Widget<M, C> {
         self
     }
 
+    /// Checks if widget and all its ancestors are enabled, `enabled` returns only own state
+    /// of the widget.
     pub fn is_enabled(&self, ui: &UserInterface<M, C>) -> bool {
         let mut enabled = self.enabled;
         let mut parent = self.parent;
@@ -306,6 +374,19 @@ impl<M: MessageData, C: Control<M, C>> Widget<M, C> {
         enabled
     }
 
+    /// Returns opacity of the widget multiplied by opacity of all its ancestors, `opacity`
+    /// returns only own opacity of the widget.
+    pub fn global_opacity(&self, ui: &UserInterface<M, C>) -> f32 {
+        let mut opacity = self.opacity;
+        let mut parent = self.parent;
+        while parent.is_some() {
+            let node = ui.node(parent);
+            opacity *= node.opacity;
+            parent = node.parent;
+        }
+        opacity
+    }
+
     #[inline]
     pub fn screen_bounds(&self) -> Rect<f32> {
         Rect::new(
@@ -360,7 +441,10 @@ impl<M: MessageData, C: Control<M, C>> Widget<M, C> {
         _ui: &mut UserInterface<M, C>,
         msg: &mut UiMessage<M, C>,
     ) {
-        if msg.destination() == self.handle() && msg.direction() == MessageDirection::ToWidget {
+        if msg.destination() == self.handle()
+            && msg.direction() == MessageDirection::ToWidget
+            && msg.routing_phase() == RoutingPhase::Bubble
+        {
             if let UiMessageData::Widget(msg) = &msg.data() {
                 match msg {
                     WidgetMessage::Background(background) => self.background = background.clone(),
@@ -420,6 +504,12 @@ impl<M: MessageData, C: Control<M, C>> Widget<M, C> {
                             self.invalidate_layout();
                         }
                     }
+                    &WidgetMessage::Anchors(anchors) => {
+                        if self.anchors != anchors {
+                            self.anchors = anchors;
+                            self.invalidate_layout();
+                        }
+                    }
                     WidgetMessage::HitTestVisibility(hit_test_visibility) => {
                         self.hit_test_visibility = *hit_test_visibility
                     }
@@ -438,6 +528,12 @@ impl<M: MessageData, C: Control<M, C>> Widget<M, C> {
                     &WidgetMessage::Enabled(enabled) => {
                         self.enabled = enabled;
                     }
+                    &WidgetMessage::ClipToBounds(clip_to_bounds) => {
+                        self.clip_to_bounds = clip_to_bounds;
+                    }
+                    &WidgetMessage::Opacity(opacity) => {
+                        self.set_opacity(opacity);
+                    }
                     &WidgetMessage::Cursor(icon) => {
                         self.cursor = icon;
                     }
@@ -489,6 +585,19 @@ impl<M: MessageData, C: Control<M, C>> Widget<M, C> {
         self.margin
     }
 
+    /// Sets anchors of the widget, see `Anchors` for more info. `None` means that the widget
+    /// is placed using margin and alignments.
+    #[inline]
+    pub fn set_anchors(&mut self, anchors: Option<Anchors>) -> &mut Self {
+        self.anchors = anchors;
+        self
+    }
+
+    #[inline]
+    pub fn anchors(&self) -> Option<Anchors> {
+        self.anchors
+    }
+
     #[inline]
     pub fn measure_override(
         &self,
@@ -559,6 +668,9 @@ impl<M: MessageData, C: Control<M, C>> Widget<M, C> {
     pub(in crate) fn set_global_visibility(&mut self, value: bool) {
         self.prev_global_visibility = self.global_visibility;
         self.global_visibility = value;
+        if self.prev_global_visibility != self.global_visibility {
+            self.invalidate_layout();
+        }
     }
 
     #[inline]
@@ -629,6 +741,7 @@ pub struct WidgetBuilder<M: MessageData, C: Control<M, C>> {
     pub row: usize,
     pub column: usize,
     pub margin: Thickness,
+    pub anchors: Option<Anchors>,
     pub children: Vec<Handle<UINode<M, C>>>,
     pub is_hit_test_visible: bool,
     pub visibility: bool,
@@ -637,6 +750,8 @@ pub struct WidgetBuilder<M: MessageData, C: Control<M, C>> {
     pub allow_drop: bool,
     pub user_data: Option<Rc<dyn Any>>,
     pub draw_on_top: bool,
+    pub clip_to_bounds: bool,
+    pub opacity: f32,
     pub enabled: bool,
     pub cursor: Option<CursorIcon>,
 }
@@ -662,6 +777,7 @@ impl<M: MessageData, C: Control<M, C>> WidgetBuilder<M, C> {
             row: 0,
             column: 0,
             margin: Thickness::zero(),
+            anchors: None,
             desired_position: Vector2::default(),
             children: Vec::new(),
             is_hit_test_visible: true,
@@ -671,6 +787,8 @@ impl<M: MessageData, C: Control<M, C>> WidgetBuilder<M, C> {
             allow_drop: false,
             user_data: None,
             draw_on_top: false,
+            clip_to_bounds: true,
+            opacity: 1.0,
             enabled: true,
             cursor: None,
         }
@@ -691,6 +809,19 @@ impl<M: MessageData, C: Control<M, C>> WidgetBuilder<M, C> {
         self
     }
 
+    /// Sets whether the widget and its descendants are clipped to bounds of the widget,
+    /// see `Widget::set_clip_to_bounds`.
+    pub fn with_clip_to_bounds(mut self, clip_to_bounds: bool) -> Self {
+        self.clip_to_bounds = clip_to_bounds;
+        self
+    }
+
+    /// Sets opacity of the widget, see `Widget::set_opacity`.
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
     pub fn with_vertical_alignment(mut self, valign: VerticalAlignment) -> Self {
         self.vertical_alignment = valign;
         self
@@ -736,6 +867,12 @@ impl<M: MessageData, C: Control<M, C>> WidgetBuilder<M, C> {
         self
     }
 
+    /// Anchors the widget to parent rectangle, see `Anchors` for more info.
+    pub fn with_anchors(mut self, anchors: Anchors) -> Self {
+        self.anchors = Some(anchors);
+        self
+    }
+
     pub fn with_desired_position(mut self, desired_position: Vector2<f32>) -> Self {
         self.desired_position = desired_position;
         self
@@ -829,9 +966,11 @@ impl<M: MessageData, C: Control<M, C>> WidgetBuilder<M, C> {
             vertical_alignment: self.vertical_alignment,
             horizontal_alignment: self.horizontal_alignment,
             margin: self.margin,
+            anchors: self.anchors,
             visibility: self.visibility,
             global_visibility: true,
             prev_global_visibility: false,
+            layout_events_sender: None,
             children: self.children,
             parent: Handle::NONE,
             command_indices: Default::default(),
@@ -846,6 +985,8 @@ impl<M: MessageData, C: Control<M, C>> WidgetBuilder<M, C> {
             allow_drop: self.allow_drop,
             user_data: self.user_data.clone(),
             draw_on_top: self.draw_on_top,
+            clip_to_bounds: self.clip_to_bounds,
+            opacity: self.opacity.min(1.0).max(0.0),
             marker: PhantomData,
             enabled: self.enabled,
             cursor: self.cursor,
@@ -7,8 +7,8 @@ use crate::{
     core::{color::Color, math::Rect, pool::Handle},
     grid::{Column, GridBuilder, Row},
     message::{
-        ButtonMessage, CursorIcon, MessageData, MessageDirection, TextMessage, UiMessage,
-        UiMessageData, WidgetMessage, WindowMessage,
+        ButtonMessage, CursorIcon, MessageData, MessageDirection, RoutingPhase, TextMessage,
+        UiMessage, UiMessageData, WidgetMessage, WindowMessage,
     },
     text::TextBuilder,
     widget::{Widget, WidgetBuilder},
@@ -292,7 +292,7 @@ impl<M: MessageData, C: Control<M, C>> Control<M, C> for Window<M, C> {
                             !self.minimized,
                         ));
                     } else if message.destination() == self.close_button {
-                        ui.send_message(WindowMessage::close(
+                        ui.send_message(WindowMessage::closing(
                             self.handle(),
                             MessageDirection::ToWidget,
                         ));
@@ -321,6 +321,11 @@ impl<M: MessageData, C: Control<M, C>> Control<M, C> for Window<M, C> {
                                         MessageDirection::ToWidget,
                                     ));
                                 }
+                                ui.send_message(WindowMessage::open(
+                                    self.handle(),
+                                    MessageDirection::FromWidget,
+                                    center,
+                                ));
                             }
                         }
                         &WindowMessage::OpenModal { center } => {
@@ -344,6 +349,11 @@ impl<M: MessageData, C: Control<M, C>> Control<M, C> for Window<M, C> {
                                     handle: self.handle(),
                                     stop: true,
                                 });
+                                ui.send_message(WindowMessage::open_modal(
+                                    self.handle(),
+                                    MessageDirection::FromWidget,
+                                    center,
+                                ));
                             }
                         }
                         WindowMessage::Close => {
@@ -354,6 +364,21 @@ impl<M: MessageData, C: Control<M, C>> Control<M, C> for Window<M, C> {
                                     false,
                                 ));
                                 ui.remove_picking_restriction(self.handle());
+                                ui.send_message(WindowMessage::close(
+                                    self.handle(),
+                                    MessageDirection::FromWidget,
+                                ));
+                            }
+                        }
+                        WindowMessage::Closing => {
+                            // Request reaches window in bubble phase only if it wasn't
+                            // cancelled, modal restriction is removed only when window closes.
+                            if message.routing_phase() == RoutingPhase::Bubble && self.visibility()
+                            {
+                                ui.send_message(WindowMessage::close(
+                                    self.handle(),
+                                    MessageDirection::ToWidget,
+                                ));
                             }
                         }
                         &WindowMessage::Minimize(minimized) => {
@@ -525,6 +550,10 @@ impl<M: MessageData, C: Control<M, C>> Window<M, C> {
     pub fn can_resize(&self) -> bool {
         self.can_resize
     }
+
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
+    }
 }
 
 pub struct WindowBuilder<M: MessageData, C: Control<M, C>> {
@@ -16,6 +16,7 @@ use crate::{
     scene::{graph::Graph, node::Node},
     utils::log::Log,
 };
+use rayon::prelude::*;
 use std::collections::{HashMap, VecDeque};
 
 #[derive(Copy, Clone, Debug)]
@@ -263,12 +264,36 @@ impl Visit for AnimationSignal {
     }
 }
 
+/// Sub-range of animation time line.
+#[derive(Copy, Clone, Debug, Default)]
+struct TimeSlice {
+    start: f32,
+    end: f32,
+}
+
+impl Visit for TimeSlice {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.start.visit("Start", visitor)?;
+        self.end.visit("End", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Maximum amount of events that can be queued in an animation, if nobody pops them
+/// new events will be discarded.
+const MAX_EVENTS: usize = 32;
+
 #[derive(Debug)]
 pub struct Animation {
+    name: String,
     // TODO: Extract into separate struct AnimationTimeline
     tracks: Vec<Track>,
     length: f32,
     time_position: f32,
+    time_slice: Option<TimeSlice>,
     ///////////////////////////////////////////////////////
     speed: f32,
     looped: bool,
@@ -367,10 +392,12 @@ impl AnimationPose {
 impl Clone for Animation {
     fn clone(&self) -> Self {
         Self {
+            name: self.name.clone(),
             tracks: self.tracks.clone(),
             speed: self.speed,
             length: self.length,
             time_position: self.time_position,
+            time_slice: self.time_slice,
             looped: self.looped,
             enabled: self.enabled,
             resource: self.resource.clone(),
@@ -382,6 +409,19 @@ impl Clone for Animation {
 }
 
 impl Animation {
+    /// Sets new name of the animation. Model loaders use names of takes (idle, walk, etc.)
+    /// as names of animations, so specific animation can be found by name after
+    /// instantiation of a model.
+    pub fn set_name<N: AsRef<str>>(&mut self, name: N) -> &mut Self {
+        self.name = name.as_ref().to_owned();
+        self
+    }
+
+    /// Returns name of the animation.
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
     pub fn add_track(&mut self, track: Track) {
         self.tracks.push(track);
 
@@ -396,33 +436,125 @@ impl Animation {
         &self.tracks
     }
 
+    /// Returns bounds of the part of time line that is played: time slice if it is set,
+    /// or the whole time line otherwise.
+    fn time_bounds(&self) -> (f32, f32) {
+        match self.time_slice {
+            Some(slice) => (
+                clampf(slice.start, 0.0, self.length),
+                clampf(slice.end, 0.0, self.length),
+            ),
+            None => (0.0, self.length),
+        }
+    }
+
+    /// Sets new time position, it will be wrapped (for looped animations) or clamped into
+    /// current time slice (or whole time line if there is no time slice).
     pub fn set_time_position(&mut self, time: f32) -> &mut Self {
+        let (start, end) = self.time_bounds();
         if self.looped {
-            self.time_position = wrapf(time, 0.0, self.length);
+            self.time_position = wrapf(time, start, end);
         } else {
-            self.time_position = clampf(time, 0.0, self.length);
+            self.time_position = clampf(time, start, end);
         }
         self
     }
 
+    /// Sets sub-range of time line that will be played, the rest of animation is ignored.
+    /// It is useful when animation contains multiple clips concatenated one after another,
+    /// for example FBX files often contain every clip in a single take. Bounds are in seconds
+    /// and will be clamped to the length of animation.
+    pub fn set_time_slice(&mut self, start: f32, end: f32) -> &mut Self {
+        self.time_slice = Some(TimeSlice {
+            start: start.min(end),
+            end: start.max(end),
+        });
+        // Clamp instead of wrapping, so playback will start from the beginning of the
+        // slice if time position was before it.
+        let (start, end) = self.time_bounds();
+        self.time_position = clampf(self.time_position, start, end);
+        self
+    }
+
+    /// Removes time slice, whole time line will be played.
+    pub fn reset_time_slice(&mut self) -> &mut Self {
+        self.time_slice = None;
+        self
+    }
+
+    /// Returns current time slice as `(start, end)` pair, if any.
+    pub fn time_slice(&self) -> Option<(f32, f32)> {
+        self.time_slice.map(|slice| (slice.start, slice.end))
+    }
+
+    /// Moves time position to the beginning of animation, or to the end of animation if
+    /// it is played in reverse (has negative speed).
     pub fn rewind(&mut self) -> &mut Self {
-        self.set_time_position(0.0)
+        let (start, end) = self.time_bounds();
+        self.set_time_position(if self.speed < 0.0 { end } else { start })
+    }
+
+    fn push_signal_events(&mut self, from: f32, to: f32, include_from: bool, include_to: bool) {
+        for signal in self.signals.iter().filter(|s| s.enabled) {
+            let after_from = if include_from {
+                signal.time >= from
+            } else {
+                signal.time > from
+            };
+            let before_to = if include_to {
+                signal.time <= to
+            } else {
+                signal.time < to
+            };
+            // TODO: Make this configurable.
+            if after_from && before_to && self.events.len() < MAX_EVENTS {
+                self.events.push_back(AnimationEvent {
+                    signal_id: signal.id,
+                });
+            }
+        }
     }
 
     fn tick(&mut self, dt: f32) {
         self.update_pose();
 
+        let (start, end) = self.time_bounds();
+        let span = end - start;
         let current_time_position = self.get_time_position();
-        let new_time_position = current_time_position + dt * self.get_speed();
-
-        for signal in self.signals.iter_mut() {
-            if current_time_position < signal.time && new_time_position >= signal.time {
-                // TODO: Make this configurable.
-                if self.events.len() < 32 {
-                    self.events.push_back(AnimationEvent {
-                        signal_id: signal.id,
-                    });
+        let mut new_time_position = current_time_position + dt * self.get_speed();
+
+        // Signals are fired when time position crosses them in direction of playback.
+        // Looped animation may wrap around (possibly multiple times) during single tick,
+        // in this case signals of every passed segment are fired.
+        if new_time_position >= current_time_position {
+            let mut from = current_time_position;
+            let mut wrapped = false;
+            loop {
+                if new_time_position <= end || !self.looped || span <= f32::EPSILON {
+                    new_time_position = new_time_position.min(end);
+                    self.push_signal_events(from, new_time_position, wrapped, true);
+                    break;
+                }
+                self.push_signal_events(from, end, wrapped, true);
+                // End of the time line is the same point as the beginning, so signals at
+                // the beginning are fired when time wraps.
+                new_time_position -= span;
+                from = start;
+                wrapped = true;
+            }
+        } else {
+            let mut from = current_time_position;
+            let mut wrapped = false;
+            loop {
+                if new_time_position >= start || !self.looped || span <= f32::EPSILON {
+                    new_time_position = new_time_position.max(start);
+                    self.push_signal_events(new_time_position, from, true, wrapped);
+                    break;
                 }
+                self.push_signal_events(start, from, true, wrapped);
+                new_time_position += span;
+                from = end;
+                wrapped = true;
             }
         }
 
@@ -433,6 +565,11 @@ impl Animation {
         self.events.pop_front()
     }
 
+    /// Returns length of the animation in seconds, it is time of the last key frame.
+    pub fn length(&self) -> f32 {
+        self.length
+    }
+
     pub fn get_time_position(&self) -> f32 {
         self.time_position
     }
@@ -450,8 +587,12 @@ impl Animation {
         self.looped
     }
 
+    /// Returns true if non-looped animation has reached the end of its time line (or time
+    /// slice), the beginning of the time line is the end for animations played in reverse.
     pub fn has_ended(&self) -> bool {
-        !self.looped && (self.time_position - self.length).abs() <= std::f32::EPSILON
+        let (start, end) = self.time_bounds();
+        let last = if self.speed < 0.0 { start } else { end };
+        !self.looped && (self.time_position - last).abs() <= std::f32::EPSILON
     }
 
     pub fn set_enabled(&mut self, enabled: bool) -> &mut Self {
@@ -463,6 +604,7 @@ impl Animation {
         self.enabled
     }
 
+    /// Sets playback speed multiplier, negative values play animation in reverse.
     pub fn set_speed(&mut self, speed: f32) -> &mut Self {
         self.speed = speed;
         self
@@ -536,8 +678,15 @@ impl Animation {
         if let Some(resource) = self.resource.clone() {
             let resource = resource.state();
             if let ResourceState::Ok(ref data) = *resource {
-                // TODO: Here we assume that resource contains only *one* animation.
-                if let Some(ref_animation) = data.get_scene().animations.pool.at(0) {
+                // Resource can contain multiple animations, find one with the same name. Old
+                // save files have no names of animations, use first animation for them.
+                let ref_animation = data
+                    .get_scene()
+                    .animations
+                    .iter()
+                    .find(|a| a.name == self.name)
+                    .or_else(|| data.get_scene().animations.pool.at(0));
+                if let Some(ref_animation) = ref_animation {
                     for track in self.get_tracks_mut() {
                         // This may panic if animation has track that refers to a deleted node,
                         // it can happen if you deleted a node but forgot to remove animation
@@ -600,10 +749,12 @@ impl Animation {
 impl Default for Animation {
     fn default() -> Self {
         Self {
+            name: Default::default(),
             tracks: Vec::new(),
             speed: 1.0,
             length: 0.0,
             time_position: 0.0,
+            time_slice: None,
             enabled: true,
             looped: true,
             resource: Default::default(),
@@ -626,6 +777,8 @@ impl Visit for Animation {
         self.looped.visit("Looped", visitor)?;
         self.enabled.visit("Enabled", visitor)?;
         self.signals.visit("Signals", visitor)?;
+        let _ = self.time_slice.visit("TimeSlice", visitor);
+        let _ = self.name.visit("Name", visitor);
 
         visitor.leave_region()
     }
@@ -713,11 +866,11 @@ impl AnimationContainer {
     }
 
     #[inline]
-    pub fn retain<P>(&mut self, pred: P)
+    pub fn retain<P>(&mut self, mut pred: P)
     where
         P: FnMut(&Animation) -> bool,
     {
-        self.pool.retain(pred)
+        self.pool.retain(|_, animation| pred(animation))
     }
 
     pub fn resolve(&mut self, graph: &Graph) {
@@ -734,10 +887,15 @@ impl AnimationContainer {
         );
     }
 
+    /// Advances every enabled animation and evaluates its pose. Animations are independent
+    /// from each other, so they're updated in parallel, each into its own pose buffer. The
+    /// graph is not touched here: poses must be applied to the graph (see
+    /// [`AnimationPose::apply`]) after this method, serially and in a stable order.
     pub fn update_animations(&mut self, dt: f32) {
-        for animation in self.pool.iter_mut().filter(|anim| anim.enabled) {
-            animation.tick(dt);
-        }
+        self.pool
+            .par_iter_mut()
+            .filter(|animation| animation.enabled)
+            .for_each(|animation| animation.tick(dt));
     }
 }
 
@@ -754,3 +912,186 @@ impl Visit for AnimationContainer {
         visitor.leave_region()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        animation::{Animation, AnimationContainer, AnimationSignal, KeyFrame, Track},
+        core::{
+            algebra::{UnitQuaternion, Vector3},
+            pool::Handle,
+        },
+    };
+
+    fn make_animation() -> Animation {
+        let mut track = Track::new();
+        for &time in [0.0, 10.0].iter() {
+            track.add_key_frame(KeyFrame::new(
+                time,
+                Vector3::default(),
+                Vector3::new(1.0, 1.0, 1.0),
+                UnitQuaternion::identity(),
+            ));
+        }
+        let mut animation = Animation::default();
+        animation.add_track(track);
+        animation
+            .add_signal(AnimationSignal::new(0, 0.0))
+            .add_signal(AnimationSignal::new(1, 0.5))
+            .add_signal(AnimationSignal::new(2, 5.0))
+            .add_signal(AnimationSignal::new(3, 9.5));
+        animation
+    }
+
+    fn events(animation: &mut Animation) -> Vec<u64> {
+        let mut ids = Vec::new();
+        while let Some(event) = animation.pop_event() {
+            ids.push(event.signal_id);
+        }
+        ids.sort_unstable();
+        ids
+    }
+
+    fn assert_time(animation: &Animation, time: f32) {
+        assert!(
+            (animation.get_time_position() - time).abs() < 0.001,
+            "{} != {}",
+            animation.get_time_position(),
+            time
+        );
+    }
+
+    #[test]
+    fn test_forward_loop_wrap() {
+        let mut animation = make_animation();
+        animation.set_time_position(9.0);
+        animation.tick(2.0);
+        assert_time(&animation, 1.0);
+        assert_eq!(events(&mut animation), vec![0, 1, 3]);
+        assert!(!animation.has_ended());
+    }
+
+    #[test]
+    fn test_reverse_loop_wrap() {
+        let mut animation = make_animation();
+        animation.set_speed(-1.0).set_time_position(1.0);
+        animation.tick(2.0);
+        assert_time(&animation, 9.0);
+        assert_eq!(events(&mut animation), vec![0, 1, 3]);
+
+        animation.tick(4.5);
+        assert_time(&animation, 4.5);
+        assert_eq!(events(&mut animation), vec![2]);
+    }
+
+    #[test]
+    fn test_multiple_wraps_in_single_tick() {
+        let mut animation = make_animation();
+        animation.set_speed(-1.0).set_time_position(5.0);
+        animation.tick(20.0);
+        assert_time(&animation, 5.0);
+        // Every signal is crossed twice. Signal at 5.0 is not fired at the start of playback,
+        // but it is fired in the middle and at the end of the tick.
+        assert_eq!(events(&mut animation), vec![0, 0, 1, 1, 2, 2, 3, 3]);
+    }
+
+    #[test]
+    fn test_forward_non_looped_end() {
+        let mut animation = make_animation();
+        animation.set_loop(false).set_time_position(9.0);
+        animation.tick(2.0);
+        assert_time(&animation, 10.0);
+        assert!(animation.has_ended());
+        assert_eq!(events(&mut animation), vec![3]);
+    }
+
+    #[test]
+    fn test_reverse_non_looped_end() {
+        let mut animation = make_animation();
+        animation
+            .set_loop(false)
+            .set_speed(-1.0)
+            .set_time_position(1.0);
+        assert!(!animation.has_ended());
+        animation.tick(2.0);
+        assert_time(&animation, 0.0);
+        assert!(animation.has_ended());
+        assert_eq!(events(&mut animation), vec![0, 1]);
+
+        // Nothing happens after the end.
+        animation.tick(1.0);
+        assert_time(&animation, 0.0);
+        assert!(events(&mut animation).is_empty());
+    }
+
+    #[test]
+    fn test_time_slice() {
+        let mut animation = make_animation();
+        animation.set_time_slice(4.0, 6.0);
+        assert_time(&animation, 4.0);
+
+        animation.tick(2.5);
+        assert_time(&animation, 4.5);
+        assert_eq!(events(&mut animation), vec![2]);
+
+        animation.set_speed(-1.0).tick(1.0);
+        assert_time(&animation, 5.5);
+        assert!(events(&mut animation).is_empty());
+
+        animation.set_loop(false).rewind();
+        assert_time(&animation, 6.0);
+        animation.tick(3.0);
+        assert_time(&animation, 4.0);
+        assert!(animation.has_ended());
+        assert_eq!(events(&mut animation), vec![2]);
+
+        animation.reset_time_slice().set_speed(1.0).tick(7.0);
+        assert_time(&animation, 10.0);
+        assert!(animation.has_ended());
+    }
+
+    #[test]
+    fn test_parallel_update() {
+        let mut container = AnimationContainer::new();
+        let mut copies = Vec::new();
+        for i in 0..32 {
+            let mut track = Track::new();
+            track.set_node(Handle::new(i + 1, 1));
+            for &time in [0.0, 1.0, 2.5].iter() {
+                track.add_key_frame(KeyFrame::new(
+                    time,
+                    Vector3::new(time * i as f32, time, 0.0),
+                    Vector3::new(1.0, 1.0, 1.0),
+                    UnitQuaternion::from_axis_angle(&Vector3::y_axis(), time),
+                ));
+            }
+            let mut animation = Animation::default();
+            animation.add_track(track);
+            animation.set_speed(0.5 + i as f32 * 0.1);
+            // Disabled animations must stay intact.
+            animation.set_enabled(i % 5 != 0);
+            copies.push((container.add(animation.clone()), animation));
+        }
+
+        // Animations are updated in parallel, result must be exactly the same as for serial
+        // update.
+        for _ in 0..20 {
+            container.update_animations(0.1);
+            for (handle, copy) in copies.iter_mut() {
+                if copy.is_enabled() {
+                    copy.tick(0.1);
+                }
+                let animation = container.get(*handle);
+                assert_eq!(animation.get_time_position(), copy.get_time_position());
+                let pose = &animation.get_pose().local_poses;
+                let expected = &copy.get_pose().local_poses;
+                assert_eq!(pose.len(), expected.len());
+                for (node, local_pose) in pose.iter() {
+                    let expected = &expected[node];
+                    assert_eq!(local_pose.position, expected.position);
+                    assert_eq!(local_pose.rotation, expected.rotation);
+                }
+            }
+        }
+    }
+}
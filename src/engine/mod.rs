@@ -1,5 +1,12 @@
 //! Engine is container for all subsystems (renderer, ui, sound, resource manager). It also
 //! creates a window and an OpenGL context.
+//!
+//! # Updaters
+//!
+//! Reusable game systems (day/night cycle, footstep sounds, etc.) can be registered in engine as
+//! updaters, see [`Engine::add_updater`](struct.Engine.html#method.add_updater). Updaters are
+//! called by `Engine::update` in registration order, they get access to scenes, user interface
+//! and other sub-systems of the engine through [`EngineContext`](struct.EngineContext.html).
 
 #![warn(missing_docs)]
 
@@ -9,14 +16,19 @@ pub mod resource_manager;
 use crate::core::algebra::Vector2;
 use crate::resource::texture::TextureKind;
 use crate::{
-    core::visitor::{Visit, VisitResult, Visitor},
+    core::{
+        pool::Handle,
+        visitor::{Visit, VisitResult, Visitor},
+    },
     engine::{error::EngineError, resource_manager::ResourceManager},
-    event_loop::EventLoop,
+    event_loop::{EventLoop, EventLoopWindowTarget},
     gui::{Control, UserInterface},
+    monitor::VideoMode,
     renderer::{error::RendererError, Renderer},
-    scene::SceneContainer,
+    scene::{Scene, SceneContainer},
     sound::context::Context,
-    window::{Window, WindowBuilder},
+    utils::log::{Log, MessageKind},
+    window::{Fullscreen, Window, WindowBuilder},
     Api, GlProfile, GlRequest, NotCurrent, PossiblyCurrent, WindowedContext,
 };
 use rg3d_ui::message::MessageData;
@@ -49,6 +61,96 @@ pub struct Engine<M: MessageData, C: Control<M, C>> {
     /// for such statistics, probably it is best to make separate structure to hold all
     /// such data.
     pub ui_time: Duration,
+    updaters: Vec<Updater<M, C>>,
+    post_updaters: Vec<Updater<M, C>>,
+    // It is used to re-create the window when graphics context must be re-created.
+    window_builder: WindowBuilder,
+    vsync: bool,
+}
+
+/// User-defined callback which is called on each update of the engine, see
+/// [`Engine::add_updater`](struct.Engine.html#method.add_updater).
+pub type Updater<M, C> = Box<dyn FnMut(&mut EngineContext<M, C>)>;
+
+enum SceneCommand {
+    Add(Scene),
+    Remove(Handle<Scene>),
+}
+
+/// Gives access to engine sub-systems for updaters.
+pub struct EngineContext<'a, M: MessageData, C: Control<M, C>> {
+    scenes: &'a mut SceneContainer,
+    /// Current resource manager.
+    pub resource_manager: &'a ResourceManager,
+    /// User interface of the engine.
+    pub user_interface: &'a mut UserInterface<M, C>,
+    /// Sound context of the engine.
+    pub sound_context: &'a Arc<Mutex<Context>>,
+    /// Time delta of current update in seconds.
+    pub dt: f32,
+    scene_commands: &'a mut Vec<SceneCommand>,
+}
+
+impl<'a, M: MessageData, C: Control<M, C>> EngineContext<'a, M, C> {
+    /// Returns iterator which yields (handle, scene) pairs of every scene in the engine.
+    /// Scenes cannot be added or removed directly from updaters, use `add_scene` and
+    /// `remove_scene` instead.
+    pub fn scenes(&self) -> impl Iterator<Item = (Handle<Scene>, &Scene)> {
+        self.scenes.pair_iter()
+    }
+
+    /// Returns iterator which yields (handle, scene) pairs of every scene in the engine,
+    /// scenes can be modified.
+    pub fn scenes_mut(&mut self) -> impl Iterator<Item = (Handle<Scene>, &mut Scene)> {
+        self.scenes.pair_iter_mut()
+    }
+
+    /// Returns reference to a scene, or `None` if handle is invalid.
+    pub fn scene(&self, handle: Handle<Scene>) -> Option<&Scene> {
+        self.scenes.try_get(handle)
+    }
+
+    /// Returns mutable reference to a scene, or `None` if handle is invalid.
+    pub fn scene_mut(&mut self, handle: Handle<Scene>) -> Option<&mut Scene> {
+        self.scenes.try_get_mut(handle)
+    }
+
+    /// Queues scene for addition, it will be added to scene container when all updaters of
+    /// current stage are finished, so the scene will be updated only on next update.
+    pub fn add_scene(&mut self, scene: Scene) {
+        self.scene_commands.push(SceneCommand::Add(scene));
+    }
+
+    /// Queues scene for removal, it will be removed from scene container when all updaters of
+    /// current stage are finished. Removal of a scene that no longer exists (for example it
+    /// was queued for removal twice) is ignored with a warning in the log.
+    pub fn remove_scene(&mut self, handle: Handle<Scene>) {
+        self.scene_commands.push(SceneCommand::Remove(handle));
+    }
+}
+
+fn apply_scene_commands(scenes: &mut SceneContainer, commands: Vec<SceneCommand>) {
+    for command in commands {
+        match command {
+            SceneCommand::Add(scene) => {
+                scenes.add(scene);
+            }
+            SceneCommand::Remove(handle) => {
+                if scenes.is_valid_handle(handle) {
+                    scenes.remove(handle);
+                } else {
+                    Log::writeln(
+                        MessageKind::Warning,
+                        format!(
+                            "Unable to remove scene {:?}: handle is invalid, it was probably \
+                             removed already.",
+                            handle
+                        ),
+                    );
+                }
+            }
+        }
+    }
 }
 
 impl<M: MessageData, C: Control<M, C>> Engine<M, C> {
@@ -76,16 +178,7 @@ impl<M: MessageData, C: Control<M, C>> Engine<M, C> {
         events_loop: &EventLoop<()>,
         vsync: bool,
     ) -> Result<Self, EngineError> {
-        let context_wrapper: WindowedContext<NotCurrent> = glutin::ContextBuilder::new()
-            .with_vsync(vsync)
-            .with_gl_profile(GlProfile::Core)
-            .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
-            .build_windowed(window_builder, events_loop)?;
-
-        let mut context = match unsafe { context_wrapper.make_current() } {
-            Ok(context) => context,
-            Err((_, e)) => return Err(EngineError::from(e)),
-        };
+        let mut context = make_context(window_builder.clone(), events_loop, vsync)?;
 
         let client_size = context.window().inner_size();
 
@@ -99,10 +192,121 @@ impl<M: MessageData, C: Control<M, C>> Engine<M, C> {
                 client_size.height as f32,
             )),
             ui_time: Default::default(),
+            updaters: Default::default(),
+            post_updaters: Default::default(),
             context,
+            window_builder,
+            vsync,
         })
     }
 
+    /// Switches main window to exclusive fullscreen mode with given video mode, `None` switches
+    /// it back to windowed mode. Graphics context survives the switch, so nothing has to be
+    /// re-uploaded to GPU, renderer will be resized automatically on next frame.
+    pub fn set_fullscreen(&mut self, video_mode: Option<VideoMode>) {
+        self.get_window()
+            .set_fullscreen(video_mode.map(Fullscreen::Exclusive));
+    }
+
+    /// Returns true if main window is in fullscreen mode (exclusive or borderless).
+    pub fn is_fullscreen(&self) -> bool {
+        self.get_window().fullscreen().is_some()
+    }
+
+    /// Returns every video mode supported by monitor on which main window is. Could be used
+    /// to fill a list of resolutions in graphics settings menu.
+    pub fn video_modes(&self) -> Vec<VideoMode> {
+        self.get_window()
+            .current_monitor()
+            .map(|monitor| monitor.video_modes().collect())
+            .unwrap_or_default()
+    }
+
+    /// Enables or disables vertical synchronization.
+    ///
+    /// # Notes
+    ///
+    /// Graphics context does not allow to change vsync after it was created, so main window
+    /// and graphics context will be re-created. Renderer keeps its settings, but its GPU
+    /// resources will be re-created on demand, so next few frames may take more time than
+    /// usual. Since the window is new, it will have the title it was created with and its
+    /// `WindowId` will be different: events of new window must be matched against
+    /// `get_window().id()` after this call, ids stored before will never match again.
+    /// If an error occurs, old window, context and renderer are kept as is.
+    pub fn set_vsync(
+        &mut self,
+        vsync: bool,
+        window_target: &EventLoopWindowTarget<()>,
+    ) -> Result<(), EngineError> {
+        if self.vsync == vsync {
+            return Ok(());
+        }
+
+        let window = self.get_window();
+        let window_builder = self
+            .window_builder
+            .clone()
+            .with_inner_size(window.inner_size())
+            .with_fullscreen(window.fullscreen());
+
+        let mut context = make_context(window_builder, window_target, vsync)?;
+        let client_size = context.window().inner_size();
+        let renderer = Renderer::new(&mut context, client_size.into()).and_then(|mut renderer| {
+            renderer.inherit_settings(&self.renderer)?;
+            Ok(renderer)
+        });
+
+        // New context is current now, but everything created in old one must be released in
+        // old one, so it is made current again. Contexts are swapped to move old one out.
+        std::mem::swap(&mut self.context, &mut context);
+        let old_context = unsafe { context.make_current() };
+
+        let renderer = match renderer {
+            Ok(renderer) => renderer,
+            Err(e) => {
+                // Partially created renderer is already released in new context, new context
+                // and its window are destroyed here.
+                let old_context = old_context.unwrap_or_else(|(old_context, _)| old_context);
+                drop(std::mem::replace(&mut self.context, old_context));
+                return Err(e.into());
+            }
+        };
+
+        let old_renderer = std::mem::replace(&mut self.renderer, renderer);
+        match old_context {
+            Ok(old_context) => {
+                // GPU resources of old renderer must be released in its own context.
+                drop(old_renderer);
+                let context = std::mem::replace(&mut self.context, old_context);
+                let context = unsafe { context.make_current() }.map_err(|(_, e)| e)?;
+                // Old context and window are destroyed here.
+                drop(std::mem::replace(&mut self.context, context));
+            }
+            Err((old_context, e)) => {
+                // Old renderer must not be dropped while new context is current, its GPU
+                // resources will be destroyed together with old context.
+                std::mem::forget(old_renderer);
+                drop(old_context);
+                Log::writeln(
+                    MessageKind::Warning,
+                    format!(
+                        "Unable to make old context current to release its resources. Reason: {:?}",
+                        e
+                    ),
+                );
+            }
+        }
+
+        self.vsync = vsync;
+
+        Ok(())
+    }
+
+    /// Returns true if vertical synchronization is enabled.
+    pub fn vsync(&self) -> bool {
+        self.vsync
+    }
+
     /// Returns reference to main window. Could be useful to set fullscreen mode, change
     /// size of window, its title, etc.
     #[inline]
@@ -110,6 +314,35 @@ impl<M: MessageData, C: Control<M, C>> Engine<M, C> {
         self.context.window()
     }
 
+    /// Registers new updater which will be called on each `update` before scenes are updated
+    /// (before physics simulation). Updaters are called in registration order.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use rg3d::engine::Engine;
+    /// use rg3d::gui::node::StubNode;
+    ///
+    /// fn add_day_night_cycle(engine: &mut Engine<(), StubNode>) {
+    ///     let mut time_of_day = 0.0;
+    ///     engine.add_updater(Box::new(move |ctx| {
+    ///         time_of_day = (time_of_day + ctx.dt) % 600.0;
+    ///         for (_, scene) in ctx.scenes_mut() {
+    ///             // Change light sources of scene according to time of day.
+    ///         }
+    ///     }));
+    /// }
+    /// ```
+    pub fn add_updater(&mut self, updater: Updater<M, C>) {
+        self.updaters.push(updater);
+    }
+
+    /// Registers new updater which will be called on each `update` after scenes and user
+    /// interface are updated. Updaters are called in registration order.
+    pub fn add_post_updater(&mut self, updater: Updater<M, C>) {
+        self.post_updaters.push(updater);
+    }
+
     /// Performs single update tick with given time delta. Engine internally will perform update
     /// of all scenes, sub-systems, user interface, etc. Must be called in order to get engine
     /// functioning.
@@ -119,6 +352,8 @@ impl<M: MessageData, C: Control<M, C>> Engine<M, C> {
 
         self.resource_manager.state().update(dt);
 
+        self.run_updaters(false, dt);
+
         for scene in self.scenes.iter_mut() {
             let frame_size = scene.render_target.as_ref().map_or(window_size, |rt| {
                 if let TextureKind::Rectangle { width, height } = rt.data_ref().kind {
@@ -131,15 +366,64 @@ impl<M: MessageData, C: Control<M, C>> Engine<M, C> {
             scene.update(frame_size, dt);
         }
 
+        if self
+            .scenes
+            .iter()
+            .any(|scene| scene.sound_sources().next().is_some())
+        {
+            let mut sound_context = self.sound_context.lock().unwrap();
+            for scene in self.scenes.iter_mut() {
+                scene.sync_sound_sources(&mut sound_context);
+            }
+        }
+
         let time = time::Instant::now();
         self.user_interface.update(window_size, dt);
         self.ui_time = time::Instant::now() - time;
+
+        self.run_updaters(true, dt);
+    }
+
+    fn run_updaters(&mut self, post: bool, dt: f32) {
+        let updaters = if post {
+            &mut self.post_updaters
+        } else {
+            &mut self.updaters
+        };
+
+        if updaters.is_empty() {
+            return;
+        }
+
+        let mut scene_commands = Vec::new();
+        for updater in updaters.iter_mut() {
+            updater(&mut EngineContext {
+                scenes: &mut self.scenes,
+                resource_manager: &self.resource_manager,
+                user_interface: &mut self.user_interface,
+                sound_context: &self.sound_context,
+                dt,
+                scene_commands: &mut scene_commands,
+            });
+        }
+
+        apply_scene_commands(&mut self.scenes, scene_commands);
     }
 
     /// Performs rendering of single frame, must be called from your game loop, otherwise you won't
-    /// see anything.
+    /// see anything. Renderer is resized automatically to match size of the window, nothing is
+    /// rendered while the window has zero size (minimized).
     #[inline]
     pub fn render(&mut self, dt: f32) -> Result<(), RendererError> {
+        // Keep renderer in sync with the window, this is done here instead of handling
+        // Resized event because there could be lots of such events per frame when user
+        // drags a border of the window.
+        let inner_size = self.context.window().inner_size();
+        if self.renderer.get_frame_size() != (inner_size.width, inner_size.height) {
+            self.context.resize(inner_size);
+            self.renderer.try_set_frame_size(inner_size.into())?;
+        }
+
         self.user_interface.draw();
         self.renderer.render_and_swap_buffers(
             &self.scenes,
@@ -150,6 +434,23 @@ impl<M: MessageData, C: Control<M, C>> Engine<M, C> {
     }
 }
 
+fn make_context(
+    window_builder: WindowBuilder,
+    window_target: &EventLoopWindowTarget<()>,
+    vsync: bool,
+) -> Result<WindowedContext<PossiblyCurrent>, EngineError> {
+    let context_wrapper: WindowedContext<NotCurrent> = glutin::ContextBuilder::new()
+        .with_vsync(vsync)
+        .with_gl_profile(GlProfile::Core)
+        .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
+        .build_windowed(window_builder, window_target)?;
+
+    match unsafe { context_wrapper.make_current() } {
+        Ok(context) => Ok(context),
+        Err((_, e)) => Err(EngineError::from(e)),
+    }
+}
+
 impl<M: MessageData, C: Control<M, C>> Visit for Engine<M, C> {
     fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
         visitor.enter_region(name)?;
@@ -175,3 +476,34 @@ impl<M: MessageData, C: Control<M, C>> Visit for Engine<M, C> {
         visitor.leave_region()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        engine::{apply_scene_commands, SceneCommand},
+        scene::{Scene, SceneContainer},
+    };
+
+    #[test]
+    fn test_stale_scene_removal() {
+        let mut scenes = SceneContainer::default();
+        let first = scenes.add(Scene::new());
+        let second = scenes.add(Scene::new());
+
+        // Double removal must not panic.
+        apply_scene_commands(
+            &mut scenes,
+            vec![
+                SceneCommand::Remove(first),
+                SceneCommand::Remove(first),
+                SceneCommand::Add(Scene::new()),
+            ],
+        );
+        assert!(!scenes.is_valid_handle(first));
+        assert!(scenes.is_valid_handle(second));
+        assert_eq!(scenes.iter().count(), 2);
+
+        apply_scene_commands(&mut scenes, vec![SceneCommand::Remove(first)]);
+        assert_eq!(scenes.iter().count(), 2);
+    }
+}
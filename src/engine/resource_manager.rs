@@ -4,24 +4,36 @@ use crate::resource::texture::{TextureError, TextureWrapMode};
 use crate::resource::ResourceLoadError;
 use crate::utils::log::MessageKind;
 use crate::{
-    core::visitor::{Visit, VisitResult, Visitor},
+    core::{
+        algebra::{UnitQuaternion, Vector3},
+        visitor::{Visit, VisitResult, Visitor},
+    },
     resource::{
-        model::{Model, ModelData},
+        model::{Model, ModelData, ModelInstance, ModelLoadError},
         texture::{
-            Texture, TextureData, TextureMagnificationFilter, TextureMinificationFilter,
-            TextureState,
+            Texture, TextureColorSpace, TextureData, TextureMagnificationFilter,
+            TextureMinificationFilter, TextureState,
         },
         Resource, ResourceData, ResourceState,
     },
-    sound::buffer::{DataSource, SoundBuffer},
+    scene::Scene,
+    sound::{
+        buffer::{DataSource, SoundBuffer},
+        context::SAMPLE_RATE,
+        dsp::resample::ResamplingQuality,
+        error::SoundError,
+    },
     utils::log::Log,
 };
 use futures::executor::ThreadPool;
 use std::{
     borrow::Cow,
+    future::Future,
     ops::{Deref, DerefMut},
     path::{Path, PathBuf},
+    pin::Pin,
     sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll},
     time,
 };
 
@@ -118,6 +130,7 @@ pub struct ResourceManagerState {
     /// format (either relative or absolute) which is obviously not good for engine.
     textures_path: PathBuf,
     textures_import_options: TextureImportOptions,
+    sound_resampling_quality: Option<ResamplingQuality>,
     thread_pool: ThreadPool,
 }
 
@@ -129,11 +142,29 @@ impl Default for ResourceManagerState {
             sound_buffers: Default::default(),
             textures_path: Default::default(),
             textures_import_options: Default::default(),
+            sound_resampling_quality: Some(Default::default()),
             thread_pool: ThreadPool::new().unwrap(),
         }
     }
 }
 
+/// Loads sound buffer and converts generic buffers to sample rate of output device if needed.
+fn load_sound_buffer(
+    source: DataSource,
+    stream: bool,
+    resampling_quality: Option<ResamplingQuality>,
+) -> Result<SoundBuffer, SoundError> {
+    if stream {
+        SoundBuffer::raw_streaming(source)
+    } else {
+        let mut buffer = SoundBuffer::raw_generic(source)?;
+        if let Some(quality) = resampling_quality {
+            buffer.resample(SAMPLE_RATE as usize, quality);
+        }
+        Ok(buffer)
+    }
+}
+
 /// See module docs.
 #[derive(Clone)]
 pub struct ResourceManager {
@@ -157,7 +188,8 @@ pub struct TextureImportOptions {
     magnification_filter: TextureMagnificationFilter,
     s_wrap_mode: TextureWrapMode,
     t_wrap_mode: TextureWrapMode,
-    anisotropy: f32,
+    anisotropy: Option<f32>,
+    color_space: TextureColorSpace,
 }
 
 impl Default for TextureImportOptions {
@@ -167,7 +199,8 @@ impl Default for TextureImportOptions {
             magnification_filter: TextureMagnificationFilter::Linear,
             s_wrap_mode: TextureWrapMode::Repeat,
             t_wrap_mode: TextureWrapMode::Repeat,
-            anisotropy: 16.0,
+            anisotropy: None,
+            color_space: TextureColorSpace::Srgb,
         }
     }
 }
@@ -208,9 +241,18 @@ impl TextureImportOptions {
     }
 
     /// Sets new anisotropy level which will be applied to every imported texture as
-    /// default value.
+    /// default value. By default imported textures use global level from quality settings,
+    /// see `QualitySettings::anisotropy`.
     pub fn with_anisotropy(mut self, anisotropy: f32) -> Self {
-        self.anisotropy = anisotropy.min(1.0);
+        self.anisotropy = Some(anisotropy.max(1.0));
+        self
+    }
+
+    /// Sets new color space which will be applied to every imported texture as default value.
+    /// Use `TextureColorSpace::Linear` for textures which contain data instead of colors (normal
+    /// maps, roughness maps, etc.), see `ResourceManager::request_texture_with_options`.
+    pub fn with_color_space(mut self, color_space: TextureColorSpace) -> Self {
+        self.color_space = color_space;
         self
     }
 }
@@ -232,6 +274,316 @@ impl From<TextureError> for TextureRegistrationError {
     }
 }
 
+/// Kind of resource to request in a batch, see [`ResourceManager::request_batch`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ResourceKind {
+    /// Texture resource.
+    Texture,
+    /// Model resource.
+    Model,
+    /// Sound buffer resource.
+    SoundBuffer {
+        /// Whether sound buffer should be streamed or not.
+        stream: bool,
+    },
+}
+
+impl ResourceKind {
+    /// Tries to guess kind of resource by extension of its path, returns `None` if format
+    /// is not supported. Sound buffers are never streamed.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "png" | "jpg" | "jpeg" | "tga" | "bmp" | "gif" | "tif" | "tiff" | "dds" => {
+                Some(ResourceKind::Texture)
+            }
+            "fbx" | "gltf" | "glb" | "rgs" => Some(ResourceKind::Model),
+            "wav" | "ogg" => Some(ResourceKind::SoundBuffer { stream: false }),
+            _ => None,
+        }
+    }
+}
+
+/// A resource that was requested as a part of a batch.
+#[derive(Clone, Debug)]
+pub enum BatchResource {
+    /// Texture resource.
+    Texture(Texture),
+    /// Model resource.
+    Model(Model),
+    /// Sound buffer resource.
+    SoundBuffer(SharedSoundBuffer),
+}
+
+/// An error that occurred while loading a resource of a batch.
+#[derive(Clone, Debug)]
+pub enum BatchResourceError {
+    /// Texture failed to load.
+    Texture(Option<Arc<TextureError>>),
+    /// Model failed to load.
+    Model(Option<Arc<ModelLoadError>>),
+    /// Sound buffer failed to load.
+    SoundBuffer,
+    /// Kind of resource cannot be determined by its path.
+    UnsupportedFormat,
+}
+
+impl BatchResource {
+    fn poll_loaded(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BatchResourceError>> {
+        match self {
+            BatchResource::Texture(texture) => match Pin::new(texture).poll(cx) {
+                Poll::Ready(result) => {
+                    Poll::Ready(result.map(|_| ()).map_err(BatchResourceError::Texture))
+                }
+                Poll::Pending => Poll::Pending,
+            },
+            BatchResource::Model(model) => match Pin::new(model).poll(cx) {
+                Poll::Ready(result) => {
+                    Poll::Ready(result.map(|_| ()).map_err(BatchResourceError::Model))
+                }
+                Poll::Pending => Poll::Pending,
+            },
+            BatchResource::SoundBuffer(buffer) => match Pin::new(buffer).poll(cx) {
+                Poll::Ready(result) => Poll::Ready(
+                    result
+                        .map(|_| ())
+                        .map_err(|_| BatchResourceError::SoundBuffer),
+                ),
+                Poll::Pending => Poll::Pending,
+            },
+        }
+    }
+
+    /// Returns direct dependencies of the resource with their paths. Only models have
+    /// dependencies - textures used by their scene, including textures of nested models.
+    /// Dependencies are known only when resource is loaded, otherwise empty list is returned.
+    pub fn dependencies(&self) -> Vec<(PathBuf, BatchResource)> {
+        match self {
+            BatchResource::Model(model) => {
+                if let ResourceState::Ok(data) = &*model.state() {
+                    data.get_scene()
+                        .textures()
+                        .into_iter()
+                        .map(|texture| {
+                            let path = texture.state().path().into_owned();
+                            (path, BatchResource::Texture(texture))
+                        })
+                        .collect()
+                } else {
+                    Default::default()
+                }
+            }
+            BatchResource::Texture(_) | BatchResource::SoundBuffer(_) => Default::default(),
+        }
+    }
+
+    // Progress of loading of the resource and its dependencies in [0; 1] range. Loaded model
+    // gives half of progress, the rest depends on how much of its textures are loaded.
+    fn preload_progress(&self) -> f32 {
+        match self.state() {
+            BatchResourceState::Pending => 0.0,
+            BatchResourceState::Failed => 1.0,
+            BatchResourceState::Loaded => {
+                let dependencies = self.dependencies();
+                if dependencies.is_empty() {
+                    1.0
+                } else {
+                    let ready = dependencies
+                        .iter()
+                        .filter(|(_, d)| !matches!(d.state(), BatchResourceState::Pending))
+                        .count();
+                    0.5 + 0.5 * ready as f32 / dependencies.len() as f32
+                }
+            }
+        }
+    }
+
+    fn state(&self) -> BatchResourceState {
+        fn state_of<T: ResourceData, E: ResourceLoadError>(
+            resource: &Resource<T, E>,
+        ) -> BatchResourceState {
+            match *resource.state() {
+                ResourceState::Pending { .. } => BatchResourceState::Pending,
+                ResourceState::LoadError { .. } => BatchResourceState::Failed,
+                ResourceState::Ok(_) => BatchResourceState::Loaded,
+            }
+        }
+
+        match self {
+            BatchResource::Texture(texture) => state_of(texture),
+            BatchResource::Model(model) => state_of(model),
+            BatchResource::SoundBuffer(buffer) => state_of(buffer),
+        }
+    }
+}
+
+enum BatchResourceState {
+    Pending,
+    Loaded,
+    Failed,
+}
+
+/// Final result of a batch, it contains every requested resource (without duplicates) and
+/// a list of errors per path for resources that failed to load.
+#[derive(Clone, Debug)]
+pub struct BatchResult {
+    /// Every unique resource of the batch in order of request.
+    pub resources: Vec<(PathBuf, BatchResource)>,
+    /// Paths of resources that failed to load with respective errors.
+    pub errors: Vec<(PathBuf, BatchResourceError)>,
+}
+
+/// A handle to a set of resources requested at once, see [`ResourceManager::request_batch`].
+/// The handle implements Future trait and can be `.await`ed, it resolves when every resource
+/// of the batch is either loaded or failed to load.
+#[derive(Clone, Debug)]
+pub struct BatchHandle {
+    resources: Vec<(PathBuf, BatchResource)>,
+    duplicates: usize,
+}
+
+impl BatchHandle {
+    /// Returns loading progress of the batch as `(loaded, failed, total)` tuple. Duplicates
+    /// in the batch and resources that were already loaded are counted as loaded.
+    pub fn progress(&self) -> (usize, usize, usize) {
+        let mut loaded = self.duplicates;
+        let mut failed = 0;
+        for (_, resource) in self.resources.iter() {
+            match resource.state() {
+                BatchResourceState::Pending => (),
+                BatchResourceState::Loaded => loaded += 1,
+                BatchResourceState::Failed => failed += 1,
+            }
+        }
+        (loaded, failed, self.resources.len() + self.duplicates)
+    }
+
+    /// Returns true if every resource of the batch is either loaded or failed to load.
+    pub fn is_finished(&self) -> bool {
+        let (loaded, failed, total) = self.progress();
+        loaded + failed == total
+    }
+
+    /// Returns every unique resource of the batch in order of request.
+    pub fn resources(&self) -> &[(PathBuf, BatchResource)] {
+        &self.resources
+    }
+}
+
+impl Future for BatchHandle {
+    type Output = BatchResult;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut errors = Vec::new();
+        let mut pending = false;
+        // Poll every resource, even if some of them are pending - this registers waker in
+        // each pending resource so the task will be woken when any of them is loaded.
+        for (path, resource) in self.resources.iter_mut() {
+            match resource.poll_loaded(cx) {
+                Poll::Ready(Ok(())) => (),
+                Poll::Ready(Err(error)) => errors.push((path.clone(), error)),
+                Poll::Pending => pending = true,
+            }
+        }
+
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(BatchResult {
+                resources: self.resources.clone(),
+                errors,
+            })
+        }
+    }
+}
+
+/// A handle to a set of resources which are preloaded together with their dependencies, see
+/// [`ResourceManager::preload`]. The handle implements Future trait and can be `.await`ed,
+/// it resolves when every resource and every its dependency is either loaded or failed to load.
+#[derive(Clone, Debug)]
+pub struct PreloadHandle {
+    batch: BatchHandle,
+    unsupported: Vec<PathBuf>,
+}
+
+impl PreloadHandle {
+    /// Returns combined loading progress in [0; 1] range. Every requested resource has same
+    /// weight, model is counted as half-loaded when its data is loaded but its textures are
+    /// still loading. Progress never decreases, failed resources are counted as finished.
+    pub fn progress(&self) -> f32 {
+        let total = self.batch.resources.len() + self.unsupported.len();
+        if total == 0 {
+            return 1.0;
+        }
+
+        let finished = self.unsupported.len() as f32
+            + self
+                .batch
+                .resources
+                .iter()
+                .map(|(_, resource)| resource.preload_progress())
+                .sum::<f32>();
+
+        finished / total as f32
+    }
+
+    /// Returns true if every resource and every its dependency is either loaded or failed
+    /// to load.
+    pub fn is_finished(&self) -> bool {
+        self.batch.resources.iter().all(|(_, resource)| {
+            !matches!(resource.state(), BatchResourceState::Pending)
+                && resource
+                    .dependencies()
+                    .iter()
+                    .all(|(_, d)| !matches!(d.state(), BatchResourceState::Pending))
+        })
+    }
+
+    /// Returns every requested resource (without duplicates and unsupported ones) in order of
+    /// request.
+    pub fn resources(&self) -> &[(PathBuf, BatchResource)] {
+        &self.batch.resources
+    }
+}
+
+impl Future for PreloadHandle {
+    type Output = BatchResult;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut result = match Pin::new(&mut self.batch).poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        // Dependencies are known only when every requested resource is loaded.
+        let mut pending = false;
+        for (_, resource) in self.batch.resources.iter() {
+            for (path, mut dependency) in resource.dependencies() {
+                if result.resources.iter().any(|(p, _)| *p == path) {
+                    continue;
+                }
+                match dependency.poll_loaded(cx) {
+                    Poll::Ready(Ok(())) => (),
+                    Poll::Ready(Err(error)) => result.errors.push((path.clone(), error)),
+                    Poll::Pending => pending = true,
+                }
+                result.resources.push((path, dependency));
+            }
+        }
+
+        if pending {
+            Poll::Pending
+        } else {
+            for path in self.unsupported.iter() {
+                result
+                    .errors
+                    .push((path.clone(), BatchResourceError::UnsupportedFormat));
+            }
+            Poll::Ready(result)
+        }
+    }
+}
+
 impl ResourceManager {
     pub(in crate) fn new() -> Self {
         Self {
@@ -259,6 +611,58 @@ impl ResourceManager {
     /// To load images and decode them, rg3d uses image create which supports following image
     /// formats: png, tga, bmp, dds, jpg, gif, tiff, dxt.
     pub fn request_texture<P: AsRef<Path>>(&self, path: P) -> Texture {
+        let options = self.state().textures_import_options.clone();
+        self.request_texture_with(path, options, |path| TextureData::load_from_file(path))
+    }
+
+    /// Same as `request_texture`, but uses given import options instead of default ones. This is
+    /// useful for textures that need non-default settings, for example normal maps must be imported
+    /// with `TextureColorSpace::Linear`. Options are ignored if the texture is already loaded or
+    /// being loaded.
+    pub fn request_texture_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: TextureImportOptions,
+    ) -> Texture {
+        self.request_texture_with(path, options, |path| TextureData::load_from_file(path))
+    }
+
+    /// Tries to decode new texture from encoded file content (png, dds, etc.) which is already
+    /// in memory, or get instance of existing with the same path, if any. Path is used only to
+    /// identify the texture and as a hint for its format, so it does not need to point to an
+    /// existing file. This is useful for textures embedded in other resources, model loaders
+    /// use this method to share embedded textures across multiple instances of a model.
+    ///
+    /// Keep in mind that such textures cannot be reloaded from disk if their path does not point
+    /// to an existing file.
+    pub fn request_texture_from_memory<P: AsRef<Path>>(&self, path: P, bytes: Vec<u8>) -> Texture {
+        let options = self.state().textures_import_options.clone();
+        self.request_texture_from_memory_with_options(path, bytes, options)
+    }
+
+    /// Same as `request_texture_from_memory`, but uses given import options instead of default
+    /// ones. Options are ignored if the texture is already loaded or being loaded.
+    pub fn request_texture_from_memory_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        bytes: Vec<u8>,
+        options: TextureImportOptions,
+    ) -> Texture {
+        self.request_texture_with(path, options, move |path| {
+            TextureData::load_from_memory(&bytes, path)
+        })
+    }
+
+    fn request_texture_with<P, F>(
+        &self,
+        path: P,
+        options: TextureImportOptions,
+        loader: F,
+    ) -> Texture
+    where
+        P: AsRef<Path>,
+        F: FnOnce(&Path) -> Result<TextureData, TextureError> + Send + 'static,
+    {
         let mut state = self.state();
 
         if let Some(texture) = state.find_texture(path.as_ref()) {
@@ -271,13 +675,12 @@ impl ResourceManager {
             time_to_live: MAX_RESOURCE_TTL,
         });
         let result = texture.clone();
-        let options = state.textures_import_options.clone();
 
         let path = path.as_ref().to_owned();
 
         state.thread_pool.spawn_ok(async move {
             let time = time::Instant::now();
-            match TextureData::load_from_file(&path) {
+            match loader(&path) {
                 Ok(mut raw_texture) => {
                     Log::writeln(
                         MessageKind::Information,
@@ -286,9 +689,12 @@ impl ResourceManager {
 
                     raw_texture.set_magnification_filter(options.magnification_filter);
                     raw_texture.set_minification_filter(options.minification_filter);
-                    raw_texture.set_anisotropy_level(options.anisotropy);
+                    if let Some(anisotropy) = options.anisotropy {
+                        raw_texture.set_anisotropy_level(anisotropy);
+                    }
                     raw_texture.set_s_wrap_mode(options.s_wrap_mode);
                     raw_texture.set_t_wrap_mode(options.t_wrap_mode);
+                    raw_texture.set_color_space(options.color_space);
 
                     texture.state().commit(ResourceState::Ok(raw_texture));
                 }
@@ -352,8 +758,8 @@ impl ResourceManager {
     ///
     /// # Supported formats
     ///
-    /// Currently only FBX (common format in game industry for storing complex 3d models)
-    /// and RGS (native rusty-editor format) formats are supported.
+    /// Currently FBX (common format in game industry for storing complex 3d models), glTF 2.0
+    /// (both `.gltf` and `.glb`) and RGS (native rusty-editor format) formats are supported.
     pub fn request_model<P: AsRef<Path>>(&self, path: P) -> Model {
         let mut state = self.state();
 
@@ -398,6 +804,46 @@ impl ResourceManager {
         result
     }
 
+    /// Loads model from given path (or gets instance of existing), waits until it is loaded and
+    /// returns a function that instantiates it on a scene at given position with given rotation,
+    /// animations of the model are retargetted to the instance. It is a shortcut for
+    /// `request_model`, `.await` and `Model::instantiate_at`.
+    ///
+    /// The scene is not borrowed while the model is loading, it is passed to the returned
+    /// function only when the model is ready. Returns an error if the model failed to load.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rg3d::{
+    ///     core::algebra::{UnitQuaternion, Vector3},
+    ///     engine::resource_manager::ResourceManager,
+    ///     scene::Scene,
+    /// };
+    ///
+    /// async fn spawn_enemy(resource_manager: ResourceManager, scene: &mut Scene) {
+    ///     let spawn = resource_manager
+    ///         .spawn_model(
+    ///             "data/mutant.fbx",
+    ///             Vector3::new(1.0, 0.0, 3.0),
+    ///             UnitQuaternion::identity(),
+    ///         )
+    ///         .await
+    ///         .unwrap();
+    ///     let enemy = spawn(scene);
+    ///     println!("Enemy has {} animations", enemy.animations.len());
+    /// }
+    /// ```
+    pub async fn spawn_model<P: AsRef<Path>>(
+        &self,
+        path: P,
+        position: Vector3<f32>,
+        rotation: UnitQuaternion<f32>,
+    ) -> Result<impl FnOnce(&mut Scene) -> ModelInstance, Option<Arc<ModelLoadError>>> {
+        let model = self.request_model(path).await?;
+        Ok(move |scene: &mut Scene| model.instantiate_at(scene, position, rotation))
+    }
+
     /// Tries to load new sound buffer from given path or get instance of existing, if any.
     /// This method is **blocking**, so it will block current thread until sound buffer is
     /// loading. On failure it returns None and prints failure reason to log.
@@ -419,37 +865,33 @@ impl ResourceManager {
         });
         let result = resource.clone();
         let path = path.as_ref().to_owned();
+        let resampling_quality = state.sound_resampling_quality;
 
         state.thread_pool.spawn_ok(async move {
             match DataSource::from_file(&path) {
-                Ok(source) => {
-                    let buffer = if stream {
-                        SoundBuffer::new_streaming(source)
-                    } else {
-                        SoundBuffer::new_generic(source)
-                    };
-                    match buffer {
-                        Ok(sound_buffer) => {
-                            Log::writeln(
-                                MessageKind::Information,
-                                format!("Sound buffer {:?} is loaded!", path),
-                            );
-
-                            resource.state().commit(ResourceState::Ok(sound_buffer));
-                        }
-                        Err(_) => {
-                            Log::writeln(
-                                MessageKind::Error,
-                                format!("Unable to load sound buffer from {:?}!", path),
-                            );
-
-                            resource.state().commit(ResourceState::LoadError {
-                                path: path.clone(),
-                                error: Some(Arc::new(())),
-                            })
-                        }
+                Ok(source) => match load_sound_buffer(source, stream, resampling_quality) {
+                    Ok(sound_buffer) => {
+                        Log::writeln(
+                            MessageKind::Information,
+                            format!("Sound buffer {:?} is loaded!", path),
+                        );
+
+                        resource
+                            .state()
+                            .commit(ResourceState::Ok(Arc::new(Mutex::new(sound_buffer))));
                     }
-                }
+                    Err(e) => {
+                        Log::writeln(
+                            MessageKind::Error,
+                            format!("Unable to load sound buffer from {:?}! Reason: {}", path, e),
+                        );
+
+                        resource.state().commit(ResourceState::LoadError {
+                            path: path.clone(),
+                            error: Some(Arc::new(())),
+                        })
+                    }
+                },
                 Err(e) => {
                     Log::writeln(MessageKind::Error, format!("Invalid data source: {:?}", e));
 
@@ -464,6 +906,115 @@ impl ResourceManager {
         result
     }
 
+    /// Requests a set of resources at once. Each resource is requested as usual (see
+    /// `request_texture`, `request_model` and `request_sound_buffer`), so resources that are
+    /// already loaded will be shared. Returned handle allows you to track progress of loading
+    /// and can be `.await`ed to wait until every resource is either loaded or failed to load.
+    /// Failure of one resource does not affect others, final result contains a list of errors
+    /// per path.
+    ///
+    /// This method is useful for loading screens, where you need to load a lot of resources
+    /// and show loading progress.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rg3d::engine::resource_manager::{ResourceKind, ResourceManager};
+    ///
+    /// async fn load(resource_manager: ResourceManager) {
+    ///     let batch = resource_manager.request_batch(vec![
+    ///         ("data/wall.jpg", ResourceKind::Texture),
+    ///         ("data/mutant.FBX", ResourceKind::Model),
+    ///         ("data/door_open.wav", ResourceKind::SoundBuffer { stream: false }),
+    ///     ]);
+    ///
+    ///     let result = batch.await;
+    ///     for (path, error) in result.errors {
+    ///         println!("Unable to load {:?}: {:?}", path, error);
+    ///     }
+    /// }
+    /// ```
+    pub fn request_batch<I, P>(&self, resources: I) -> BatchHandle
+    where
+        I: IntoIterator<Item = (P, ResourceKind)>,
+        P: AsRef<Path>,
+    {
+        let mut handle = BatchHandle {
+            resources: Default::default(),
+            duplicates: 0,
+        };
+
+        for (path, kind) in resources {
+            let path = path.as_ref();
+            if handle.resources.iter().any(|(p, _)| p == path) {
+                handle.duplicates += 1;
+                continue;
+            }
+
+            let resource = match kind {
+                ResourceKind::Texture => BatchResource::Texture(self.request_texture(path)),
+                ResourceKind::Model => BatchResource::Model(self.request_model(path)),
+                ResourceKind::SoundBuffer { stream } => {
+                    BatchResource::SoundBuffer(self.request_sound_buffer(path, stream))
+                }
+            };
+
+            handle.resources.push((path.to_owned(), resource));
+        }
+
+        handle
+    }
+
+    /// Preloads a set of resources together with their dependencies (for example textures of
+    /// models), kind of each resource is determined by extension of its path. Returned handle
+    /// provides combined loading progress and can be `.await`ed to wait until everything is
+    /// either loaded or failed to load. Resources with unsupported extensions are reported as
+    /// errors in final result.
+    ///
+    /// Use this method on loading screens to avoid hitches caused by lazy loading during
+    /// gameplay.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rg3d::engine::resource_manager::ResourceManager;
+    ///
+    /// fn show_loading_screen(resource_manager: ResourceManager) {
+    ///     let handle = resource_manager.preload(&["data/mutant.FBX", "data/door_open.wav"]);
+    ///     while !handle.is_finished() {
+    ///         println!("Loading: {}%", (handle.progress() * 100.0) as u32);
+    ///     }
+    /// }
+    /// ```
+    pub fn preload<I, P>(&self, paths: I) -> PreloadHandle
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut unsupported = Vec::new();
+        let mut resources = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            match ResourceKind::from_path(path) {
+                Some(kind) => resources.push((path.to_owned(), kind)),
+                None => {
+                    if !unsupported.iter().any(|p| p == path) {
+                        Log::writeln(
+                            MessageKind::Warning,
+                            format!("Unable to preload {:?}, format is not supported!", path),
+                        );
+                        unsupported.push(path.to_owned());
+                    }
+                }
+            }
+        }
+
+        PreloadHandle {
+            batch: self.request_batch(resources),
+            unsupported,
+        }
+    }
+
     /// Reloads every loaded texture. This method is asynchronous, internally it uses thread pool
     /// to run reload on separate thread per texture.
     pub async fn reload_textures(&self) {
@@ -479,10 +1030,15 @@ impl ResourceManager {
 
             for resource in textures.iter().cloned() {
                 let path = resource.state().path().to_path_buf();
+                let color_space = match *resource.state() {
+                    ResourceState::Ok(ref data) => data.color_space(),
+                    _ => state.textures_import_options.color_space,
+                };
                 *resource.state() = ResourceState::new_pending(path.clone());
                 state.thread_pool.spawn_ok(async move {
                     match TextureData::load_from_file(&path) {
-                        Ok(data) => {
+                        Ok(mut data) => {
+                            data.set_color_space(color_space);
                             Log::writeln(
                                 MessageKind::Information,
                                 format!("Texture {:?} successfully reloaded!", path,),
@@ -592,14 +1148,11 @@ impl ResourceManager {
                 };
                 if let Some(ext_path) = path {
                     *resource.state() = ResourceState::new_pending(ext_path.clone());
+                    let resampling_quality = state.sound_resampling_quality;
 
                     state.thread_pool.spawn_ok(async move {
                         if let Ok(data_source) = DataSource::from_file(&ext_path) {
-                            let new_sound_buffer = match stream {
-                                false => SoundBuffer::raw_generic(data_source),
-                                true => SoundBuffer::raw_streaming(data_source),
-                            };
-                            match new_sound_buffer {
+                            match load_sound_buffer(data_source, stream, resampling_quality) {
                                 Ok(new_sound_buffer) => {
                                     Log::writeln(
                                         MessageKind::Information,
@@ -612,10 +1165,13 @@ impl ResourceManager {
                                     *inner_buffer.lock().unwrap() = new_sound_buffer;
                                     resource.state().commit(ResourceState::Ok(inner_buffer));
                                 }
-                                Err(_) => {
+                                Err(e) => {
                                     Log::writeln(
                                         MessageKind::Error,
-                                        format!("Unable to reload {:?} sound buffer!", ext_path),
+                                        format!(
+                                            "Unable to reload {:?} sound buffer! Reason: {}",
+                                            ext_path, e
+                                        ),
                                     );
 
                                     resource.state().commit(ResourceState::LoadError {
@@ -686,6 +1242,7 @@ impl ResourceManagerState {
             sound_buffers: Vec::new(),
             textures_path: PathBuf::from("data/textures/"),
             textures_import_options: Default::default(),
+            sound_resampling_quality: Some(Default::default()),
             thread_pool: ThreadPool::new().unwrap(),
         }
     }
@@ -696,6 +1253,26 @@ impl ResourceManagerState {
         self.textures_import_options = options;
     }
 
+    /// Returns current import options for textures.
+    pub fn textures_import_options(&self) -> &TextureImportOptions {
+        &self.textures_import_options
+    }
+
+    /// Sets quality of resampling of generic sound buffers whose sample rate differs from sample
+    /// rate of output device. Buffers are resampled once at load, so sources play them without
+    /// per-frame resampling. `None` disables it, such buffers will be resampled by sources while
+    /// playing. Streaming buffers are always resampled while playing. Previously loaded buffers
+    /// won't be affected by the new setting until they are reloaded. Default is
+    /// `Some(ResamplingQuality::Sinc)`.
+    pub fn set_sound_resampling_quality(&mut self, quality: Option<ResamplingQuality>) {
+        self.sound_resampling_quality = quality;
+    }
+
+    /// Returns current quality of resampling of generic sound buffers.
+    pub fn sound_resampling_quality(&self) -> Option<ResamplingQuality> {
+        self.sound_resampling_quality
+    }
+
     /// Returns shared reference to list of available textures.
     #[inline]
     pub fn textures(&self) -> &[TimedEntry<Texture>] {
@@ -928,3 +1505,96 @@ impl Visit for ResourceManagerState {
         visitor.leave_region()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::algebra::{UnitQuaternion, Vector3},
+        engine::resource_manager::{
+            BatchResourceError, ResourceKind, ResourceManager, TextureImportOptions,
+        },
+        resource::texture::TextureColorSpace,
+    };
+
+    #[test]
+    fn test_batch_with_failures_and_duplicates() {
+        let resource_manager = ResourceManager::new();
+
+        let batch = resource_manager.request_batch(vec![
+            ("this/texture/does/not/exist.png", ResourceKind::Texture),
+            ("this/model/does/not/exist.fbx", ResourceKind::Model),
+            ("this/texture/does/not/exist.png", ResourceKind::Texture),
+        ]);
+
+        let result = futures::executor::block_on(batch.clone());
+
+        assert_eq!(result.resources.len(), 2);
+        assert_eq!(result.errors.len(), 2);
+        assert!(batch.is_finished());
+        // Duplicate is counted as loaded.
+        assert_eq!(batch.progress(), (1, 2, 3));
+    }
+
+    #[test]
+    fn test_preload_with_unsupported_format() {
+        let resource_manager = ResourceManager::new();
+
+        let handle = resource_manager.preload(&[
+            "this/texture/does/not/exist.png",
+            "this/file/is/not/a/resource.txt",
+            "this/texture/does/not/exist.png",
+        ]);
+
+        assert_eq!(handle.resources().len(), 1);
+
+        let result = futures::executor::block_on(handle.clone());
+
+        assert_eq!(result.resources.len(), 1);
+        assert_eq!(result.errors.len(), 2);
+        assert!(matches!(
+            result.errors[1].1,
+            BatchResourceError::UnsupportedFormat
+        ));
+        assert!(handle.is_finished());
+        assert_eq!(handle.progress(), 1.0);
+    }
+
+    #[test]
+    fn test_spawn_model_failure() {
+        let resource_manager = ResourceManager::new();
+
+        let result = futures::executor::block_on(resource_manager.spawn_model(
+            "this/model/does/not/exist.fbx",
+            Vector3::new(1.0, 2.0, 3.0),
+            UnitQuaternion::identity(),
+        ));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_texture_color_space_import_option() {
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgba8(1, 1)
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .unwrap();
+
+        let resource_manager = ResourceManager::new();
+        let color = resource_manager.request_texture_from_memory("color.png", png.clone());
+        let normal_map = resource_manager.request_texture_from_memory_with_options(
+            "normal_map.png",
+            png,
+            TextureImportOptions::default().with_color_space(TextureColorSpace::Linear),
+        );
+
+        // Color textures are treated as sRGB and uploaded with sRGB formats, data textures
+        // must be left as is.
+        let color = futures::executor::block_on(color).unwrap();
+        assert_eq!(color.data_ref().color_space(), TextureColorSpace::Srgb);
+        let normal_map = futures::executor::block_on(normal_map).unwrap();
+        assert_eq!(
+            normal_map.data_ref().color_space(),
+            TextureColorSpace::Linear
+        );
+    }
+}
@@ -1,28 +1,41 @@
 use crate::core::arrayvec::ArrayVec;
 use crate::{
-    core::{algebra::Matrix4, color::Color, pool::Handle},
+    core::{
+        algebra::{Matrix4, Point3, Vector3, Vector4},
+        color::Color,
+        pool::Handle,
+    },
     renderer::{
         error::RendererError,
         framework::gpu_texture::{
             GpuTextureKind, MagnificationFilter, MinificationFilter, PixelKind,
         },
         framework::{gpu_texture::GpuTexture, state::PipelineState},
-        surface::SurfaceSharedData,
+        surface::{Surface, SurfaceSharedData},
         TextureCache,
     },
-    scene::{graph::Graph, node::Node},
+    resource::texture::Texture,
+    scene::{graph::Graph, mesh::Mesh, node::Node},
 };
 use std::sync::RwLock;
 use std::{
     cell::RefCell,
+    collections::hash_map::DefaultHasher,
     collections::HashMap,
-    fmt::{Debug, Formatter},
+    fmt::{Debug, Display, Formatter},
+    hash::{Hash, Hasher},
     iter::FromIterator,
+    ops::AddAssign,
     rc::Rc,
     sync::Arc,
 };
 
-pub const BONE_MATRICES_COUNT: usize = 64;
+/// Maximum amount of bones per surface that can be skinned on GPU. Shaders that do skinning
+/// declare arrays of bone matrices of exactly this size: 60 matrices (960 components) plus
+/// world and world-view-projection matrices fit into 1024 uniform components of vertex shader
+/// which every OpenGL 3.3 implementation must support. Surfaces with more bones are skinned
+/// on CPU.
+pub const BONE_MATRICES_COUNT: usize = 60;
 
 #[repr(C)]
 #[doc(hidden)]
@@ -41,6 +54,12 @@ pub struct SurfaceInstance {
     pub depth_offset: f32,
 }
 
+#[derive(Copy, Clone)]
+pub struct PbrParameters {
+    pub metallic: f32,
+    pub roughness: f32,
+}
+
 pub struct Batch {
     pub data: Arc<RwLock<SurfaceSharedData>>,
     pub instances: Vec<SurfaceInstance>,
@@ -49,7 +68,20 @@ pub struct Batch {
     pub specular_texture: Rc<RefCell<GpuTexture>>,
     pub roughness_texture: Rc<RefCell<GpuTexture>>,
     pub lightmap_texture: Rc<RefCell<GpuTexture>>,
+    pub metallic_texture: Rc<RefCell<GpuTexture>>,
+    pub ao_texture: Rc<RefCell<GpuTexture>>,
+    pub emissive_texture: Rc<RefCell<GpuTexture>>,
+    pub emissive_color: Color,
+    pub emissive_intensity: f32,
+    pub pbr: Option<PbrParameters>,
     pub is_skinned: bool,
+    /// Vertices of the batch were skinned on CPU and must be uploaded to GPU before rendering.
+    pub cpu_skinned: bool,
+    pub use_vertex_colors: bool,
+    /// Both faces of the batch must be rendered.
+    pub two_sided: bool,
+    /// Batch is rendered in forward transparent pass instead of deferred pass.
+    pub transparent: bool,
 }
 
 impl Debug for Batch {
@@ -63,14 +95,409 @@ impl Debug for Batch {
     }
 }
 
+/// Surface instance that is skinned on CPU, it has its own copy of vertices.
+struct CpuSkinnedSurface {
+    data: Arc<RwLock<SurfaceSharedData>>,
+    used: bool,
+}
+
+/// Graph address, mesh handle and index of surface in the mesh.
+type CpuSkinnedSurfaceKey = (usize, Handle<Node>, usize);
+
+/// Which of dummy textures replaces a texture of a surface when it is missing or not loaded yet.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum DummyTexture {
+    Black,
+    White,
+    Normal,
+    Specular,
+}
+
+/// Texture of a surface together with its replacement.
+#[derive(Clone)]
+struct TextureBinding {
+    texture: Option<Texture>,
+    dummy: DummyTexture,
+}
+
+impl TextureBinding {
+    fn new(texture: Option<Texture>, dummy: DummyTexture) -> Self {
+        Self { texture, dummy }
+    }
+
+    /// Returns GPU texture for the binding, texture cache uploads new or modified pixels and
+    /// applies sampler parameters here, so it must be called every frame.
+    fn resolve(
+        &self,
+        state: &mut PipelineState,
+        texture_cache: &mut TextureCache,
+        dummies: &DummyTextures,
+    ) -> Rc<RefCell<GpuTexture>> {
+        self.texture
+            .clone()
+            .and_then(|texture| texture_cache.get(state, texture))
+            .unwrap_or_else(|| dummies.get(self.dummy))
+    }
+}
+
+/// Everything that is needed to put a surface into a batch: textures, material parameters and
+/// per-instance data except bone matrices. Textures are kept as resources, not as GPU textures,
+/// so cached draw data does not prevent changes of textures from reaching GPU.
+#[derive(Clone)]
+struct SurfaceDrawData {
+    key: u64,
+    data: Arc<RwLock<SurfaceSharedData>>,
+    diffuse_texture: TextureBinding,
+    normal_texture: TextureBinding,
+    specular_texture: TextureBinding,
+    roughness_texture: TextureBinding,
+    lightmap_texture: TextureBinding,
+    metallic_texture: TextureBinding,
+    ao_texture: TextureBinding,
+    emissive_texture: TextureBinding,
+    emissive_color: Color,
+    emissive_intensity: f32,
+    pbr: Option<PbrParameters>,
+    is_skinned: bool,
+    cpu_skinned: bool,
+    use_vertex_colors: bool,
+    two_sided: bool,
+    transparent: bool,
+    world: Matrix4<f32>,
+    color: Color,
+    depth_offset: f32,
+}
+
+/// Prepared draw data of every surface of a mesh, valid while version of the mesh is the same.
+struct CachedMeshDrawData {
+    version: u64,
+    surfaces: Vec<SurfaceDrawData>,
+    used: bool,
+}
+
+/// Graph address and mesh handle.
+type MeshDrawDataKey = (usize, Handle<Node>);
+
+/// Shows how many meshes reused their cached draw data during last batch generation.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct DrawDataCacheStatistics {
+    /// Amount of meshes that reused cached draw data.
+    pub hits: usize,
+    /// Amount of meshes which draw data was collected from scratch. Includes skinned meshes
+    /// which are never cached.
+    pub misses: usize,
+}
+
+impl DrawDataCacheStatistics {
+    /// Returns ratio of cache hits to total amount of meshes in `[0; 1]` range.
+    pub fn hit_rate(&self) -> f32 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f32 / total as f32
+        }
+    }
+}
+
+impl Display for DrawDataCacheStatistics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Draw Data Cache: {} hits, {} misses ({:.1}% hit rate)",
+            self.hits,
+            self.misses,
+            self.hit_rate() * 100.0
+        )
+    }
+}
+
+impl AddAssign for DrawDataCacheStatistics {
+    fn add_assign(&mut self, rhs: Self) {
+        self.hits += rhs.hits;
+        self.misses += rhs.misses;
+    }
+}
+
 #[derive(Default)]
 pub struct BatchStorage {
     buffers: Vec<Vec<SurfaceInstance>>,
     inner: HashMap<u64, usize>,
+    cpu_skinned_surfaces: HashMap<CpuSkinnedSurfaceKey, CpuSkinnedSurface>,
+    draw_data_cache: HashMap<MeshDrawDataKey, CachedMeshDrawData>,
+    /// Statistics of draw data cache for last call of `generate_batches`.
+    pub cache_statistics: DrawDataCacheStatistics,
     /// Sorted list of batches.
     pub batches: Vec<Batch>,
 }
 
+/// Makes procedural copy of surface data to be skinned on CPU. Vertex colors are not affected
+/// by skinning, so they're just copied.
+fn copy_surface_data(source: &SurfaceSharedData) -> SurfaceSharedData {
+    let mut copy =
+        SurfaceSharedData::new(source.vertices.clone(), source.triangles().to_vec(), true);
+    copy.colors = source.colors.clone();
+    copy
+}
+
+/// Skins vertices of `source` using given bone matrices and writes result into `dest`.
+/// This is fallback for surfaces that have more bones than GPU skinning supports.
+fn skin_vertices(
+    source: &SurfaceSharedData,
+    dest: &mut SurfaceSharedData,
+    bone_matrices: &[Matrix4<f32>],
+) {
+    for (src, dst) in source.vertices.iter().zip(dest.vertices.iter_mut()) {
+        let mut position = Vector3::default();
+        let mut normal = Vector3::default();
+        let mut tangent = Vector3::default();
+        for (&index, &weight) in src.bone_indices.iter().zip(src.bone_weights.iter()) {
+            if weight > 0.0 {
+                if let Some(matrix) = bone_matrices.get(index as usize) {
+                    position += matrix
+                        .transform_point(&Point3::from(src.position))
+                        .coords
+                        .scale(weight);
+                    normal += matrix.transform_vector(&src.normal).scale(weight);
+                    tangent += matrix.transform_vector(&src.tangent.xyz()).scale(weight);
+                }
+            }
+        }
+        dst.position = position;
+        dst.normal = normal.try_normalize(f32::EPSILON).unwrap_or(src.normal);
+        let tangent = tangent
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(|| src.tangent.xyz());
+        dst.tangent = Vector4::new(tangent.x, tangent.y, tangent.z, src.tangent.w);
+    }
+}
+
+/// Dummy textures that are used when surface has no texture or it is not loaded yet.
+struct DummyTextures {
+    black: Rc<RefCell<GpuTexture>>,
+    white: Rc<RefCell<GpuTexture>>,
+    normal: Rc<RefCell<GpuTexture>>,
+    specular: Rc<RefCell<GpuTexture>>,
+}
+
+impl DummyTextures {
+    fn get(&self, dummy: DummyTexture) -> Rc<RefCell<GpuTexture>> {
+        match dummy {
+            DummyTexture::Black => self.black.clone(),
+            DummyTexture::White => self.white.clone(),
+            DummyTexture::Normal => self.normal.clone(),
+            DummyTexture::Specular => self.specular.clone(),
+        }
+    }
+}
+
+/// Collects textures and material parameters of a surface.
+fn collect_surface_draw_data(
+    surface: &Surface,
+    key: u64,
+    data: Arc<RwLock<SurfaceSharedData>>,
+    world: Matrix4<f32>,
+    depth_offset: f32,
+) -> SurfaceDrawData {
+    let is_skinned = !surface.bones.is_empty();
+    let cpu_skinned = surface.bones.len() > BONE_MATRICES_COUNT;
+
+    let pbr_material = surface.pbr_material();
+
+    let diffuse_texture = TextureBinding::new(
+        match pbr_material {
+            Some(material) => material.albedo_texture(),
+            None => surface.diffuse_texture(),
+        },
+        DummyTexture::White,
+    );
+
+    let normal_texture = TextureBinding::new(surface.normal_texture(), DummyTexture::Normal);
+
+    let specular_texture = TextureBinding::new(surface.specular_texture(), DummyTexture::Specular);
+
+    // Roughness of PBR material is multiplied by factor, so missing texture must
+    // not affect the result.
+    let roughness_texture = match pbr_material {
+        Some(material) => TextureBinding::new(material.roughness_texture(), DummyTexture::White),
+        None => TextureBinding::new(surface.roughness_texture(), DummyTexture::Black),
+    };
+
+    let lightmap_texture = TextureBinding::new(surface.lightmap_texture(), DummyTexture::Black);
+
+    let metallic_texture = TextureBinding::new(
+        pbr_material.and_then(|m| m.metallic_texture()),
+        DummyTexture::White,
+    );
+
+    let ao_texture = TextureBinding::new(
+        pbr_material.and_then(|m| m.ao_texture()),
+        DummyTexture::White,
+    );
+
+    // Surface provides emission of its physically-based material, if it has one.
+    let emissive_texture = TextureBinding::new(surface.emissive_texture(), DummyTexture::White);
+    let emissive_color = surface.emissive_color();
+    let emissive_intensity = surface.emissive_intensity();
+
+    let pbr = pbr_material.map(|material| PbrParameters {
+        metallic: material.metallic(),
+        roughness: material.roughness(),
+    });
+
+    let color = match pbr_material {
+        Some(material) => Color::from(
+            surface
+                .color()
+                .as_frgba()
+                .component_mul(&material.albedo_color().as_frgba()),
+        ),
+        None => surface.color(),
+    };
+
+    // Color attribute is bound only if surface data has colors.
+    let use_vertex_colors =
+        surface.vertex_colors_enabled() && data.read().unwrap().has_vertex_colors();
+
+    SurfaceDrawData {
+        key,
+        data,
+        diffuse_texture,
+        normal_texture,
+        specular_texture,
+        roughness_texture,
+        lightmap_texture,
+        metallic_texture,
+        ao_texture,
+        emissive_texture,
+        emissive_color,
+        emissive_intensity,
+        pbr,
+        is_skinned: is_skinned && !cpu_skinned,
+        cpu_skinned,
+        use_vertex_colors,
+        two_sided: surface.is_two_sided(),
+        transparent: surface.is_transparent(),
+        world,
+        color,
+        depth_offset,
+    }
+}
+
+/// Returns draw data of every surface of a mesh without bones. Draw data is taken from the
+/// cache if version of the mesh is the same, otherwise it is collected and cached again.
+fn static_mesh_draw_data<'a>(
+    cache: &'a mut HashMap<MeshDrawDataKey, CachedMeshDrawData>,
+    statistics: &mut DrawDataCacheStatistics,
+    graph_key: usize,
+    handle: Handle<Node>,
+    mesh: &Mesh,
+) -> &'a [SurfaceDrawData] {
+    let key = (graph_key, handle);
+    let version = mesh.version();
+
+    if matches!(cache.get(&key), Some(entry) if entry.version == version) {
+        statistics.hits += 1;
+    } else {
+        statistics.misses += 1;
+        let surfaces = mesh
+            .surfaces()
+            .iter()
+            .map(|surface| {
+                collect_surface_draw_data(
+                    surface,
+                    surface.batch_id(),
+                    surface.data(),
+                    mesh.global_transform(),
+                    mesh.depth_offset_factor(),
+                )
+            })
+            .collect();
+        cache.insert(
+            key,
+            CachedMeshDrawData {
+                version,
+                surfaces,
+                used: true,
+            },
+        );
+    }
+
+    let entry = cache.get_mut(&key).unwrap();
+    entry.used = true;
+    &entry.surfaces
+}
+
+/// Puts new instance of a surface into a batch, creates new batch if needed.
+#[allow(clippy::too_many_arguments)]
+fn push_instance(
+    state: &mut PipelineState,
+    texture_cache: &mut TextureCache,
+    dummies: &DummyTextures,
+    batches: &mut Vec<Batch>,
+    inner: &mut HashMap<u64, usize>,
+    buffers: &mut Vec<Vec<SurfaceInstance>>,
+    draw_data: &SurfaceDrawData,
+    owner: Handle<Node>,
+    bone_matrices: ArrayVec<[Matrix4<f32>; BONE_MATRICES_COUNT]>,
+) {
+    let mut resolve = |binding: &TextureBinding| binding.resolve(state, texture_cache, dummies);
+
+    let diffuse_texture = resolve(&draw_data.diffuse_texture);
+    let normal_texture = resolve(&draw_data.normal_texture);
+    let specular_texture = resolve(&draw_data.specular_texture);
+    let roughness_texture = resolve(&draw_data.roughness_texture);
+    let lightmap_texture = resolve(&draw_data.lightmap_texture);
+    let metallic_texture = resolve(&draw_data.metallic_texture);
+    let ao_texture = resolve(&draw_data.ao_texture);
+    let emissive_texture = resolve(&draw_data.emissive_texture);
+
+    let batch = if let Some(&batch_index) = inner.get(&draw_data.key) {
+        batches.get_mut(batch_index).unwrap()
+    } else {
+        inner.insert(draw_data.key, batches.len());
+        batches.push(Batch {
+            data: draw_data.data.clone(),
+            instances: buffers.pop().unwrap_or_default(),
+            diffuse_texture: diffuse_texture.clone(),
+            normal_texture: normal_texture.clone(),
+            specular_texture: specular_texture.clone(),
+            roughness_texture: roughness_texture.clone(),
+            lightmap_texture: lightmap_texture.clone(),
+            metallic_texture: metallic_texture.clone(),
+            ao_texture: ao_texture.clone(),
+            emissive_texture: emissive_texture.clone(),
+            emissive_color: draw_data.emissive_color,
+            emissive_intensity: draw_data.emissive_intensity,
+            pbr: draw_data.pbr,
+            is_skinned: draw_data.is_skinned,
+            cpu_skinned: draw_data.cpu_skinned,
+            use_vertex_colors: draw_data.use_vertex_colors,
+            two_sided: draw_data.two_sided,
+            transparent: draw_data.transparent,
+        });
+        batches.last_mut().unwrap()
+    };
+
+    // Update textures.
+    batch.diffuse_texture = diffuse_texture;
+    batch.normal_texture = normal_texture;
+    batch.specular_texture = specular_texture;
+    batch.roughness_texture = roughness_texture;
+    batch.lightmap_texture = lightmap_texture;
+    batch.metallic_texture = metallic_texture;
+    batch.ao_texture = ao_texture;
+    batch.emissive_texture = emissive_texture;
+
+    batch.instances.push(SurfaceInstance {
+        world_transform: draw_data.world,
+        bone_matrices,
+        color: draw_data.color,
+        owner,
+        depth_offset: draw_data.depth_offset,
+    });
+}
+
 impl BatchStorage {
     pub(in crate) fn generate_batches(
         &mut self,
@@ -89,6 +516,27 @@ impl BatchStorage {
 
         self.batches.clear();
         self.inner.clear();
+        self.cache_statistics = Default::default();
+
+        let dummies = DummyTextures {
+            black: black_dummy,
+            white: white_dummy,
+            normal: normal_dummy,
+            specular: specular_dummy,
+        };
+
+        // Storage is shared between scenes, so surfaces of other graphs must be kept.
+        let graph_key = graph as *const _ as usize;
+        for (key, surface) in self.cpu_skinned_surfaces.iter_mut() {
+            if key.0 == graph_key {
+                surface.used = false;
+            }
+        }
+        for (key, entry) in self.draw_data_cache.iter_mut() {
+            if key.0 == graph_key {
+                entry.used = false;
+            }
+        }
 
         for (handle, mesh) in graph.pair_iter().filter_map(|(handle, node)| {
             if let Node::Mesh(mesh) = node {
@@ -97,8 +545,36 @@ impl BatchStorage {
                 None
             }
         }) {
-            for surface in mesh.surfaces().iter() {
+            // Skinned surfaces depend on bones which could move without any change of the
+            // mesh itself, so only meshes without bones could reuse their draw data.
+            if mesh.surfaces().iter().all(|s| s.bones.is_empty()) {
+                for draw_data in static_mesh_draw_data(
+                    &mut self.draw_data_cache,
+                    &mut self.cache_statistics,
+                    graph_key,
+                    handle,
+                    mesh,
+                ) {
+                    push_instance(
+                        state,
+                        texture_cache,
+                        &dummies,
+                        &mut self.batches,
+                        &mut self.inner,
+                        &mut self.buffers,
+                        draw_data,
+                        handle,
+                        Default::default(),
+                    );
+                }
+                continue;
+            }
+
+            self.cache_statistics.misses += 1;
+
+            for (surface_index, surface) in mesh.surfaces().iter().enumerate() {
                 let is_skinned = !surface.bones.is_empty();
+                let cpu_skinned = surface.bones.len() > BONE_MATRICES_COUNT;
 
                 let world = if is_skinned {
                     Matrix4::identity()
@@ -106,71 +582,79 @@ impl BatchStorage {
                     mesh.global_transform()
                 };
 
-                let data = surface.data();
-                let key = surface.batch_id();
+                let (data, key) = if cpu_skinned {
+                    let bone_matrices = surface
+                        .bones
+                        .iter()
+                        .map(|&bone_handle| {
+                            let bone_node = &graph[bone_handle];
+                            bone_node.global_transform() * bone_node.inv_bind_pose_transform()
+                        })
+                        .collect::<Vec<_>>();
 
-                let diffuse_texture = surface
-                    .diffuse_texture()
-                    .and_then(|texture| texture_cache.get(state, texture))
-                    .unwrap_or_else(|| white_dummy.clone());
+                    let source = surface.data();
+                    let source = source.read().unwrap();
 
-                let normal_texture = surface
-                    .normal_texture()
-                    .and_then(|texture| texture_cache.get(state, texture))
-                    .unwrap_or_else(|| normal_dummy.clone());
+                    let cpu_skinned_surface = self
+                        .cpu_skinned_surfaces
+                        .entry((graph_key, handle, surface_index))
+                        .or_insert_with(|| CpuSkinnedSurface {
+                            data: Arc::new(RwLock::new(copy_surface_data(&source))),
+                            used: true,
+                        });
+                    cpu_skinned_surface.used = true;
 
-                let specular_texture = surface
-                    .specular_texture()
-                    .and_then(|texture| texture_cache.get(state, texture))
-                    .unwrap_or_else(|| specular_dummy.clone());
+                    let mut dest = cpu_skinned_surface.data.write().unwrap();
+                    if dest.vertices.len() != source.vertices.len() {
+                        *dest = copy_surface_data(&source);
+                    }
+                    skin_vertices(&source, &mut dest, &bone_matrices);
 
-                let roughness_texture = surface
-                    .roughness_texture()
-                    .and_then(|texture| texture_cache.get(state, texture))
-                    .unwrap_or_else(|| black_dummy.clone());
+                    // Each instance has its own vertices, so it must be in separate batch.
+                    let mut hasher = DefaultHasher::new();
+                    surface.batch_id().hash(&mut hasher);
+                    handle.hash(&mut hasher);
+                    surface_index.hash(&mut hasher);
 
-                let lightmap_texture = surface
-                    .lightmap_texture()
-                    .and_then(|texture| texture_cache.get(state, texture))
-                    .unwrap_or_else(|| black_dummy.clone());
-
-                let batch = if let Some(&batch_index) = self.inner.get(&key) {
-                    self.batches.get_mut(batch_index).unwrap()
+                    (cpu_skinned_surface.data.clone(), hasher.finish())
                 } else {
-                    self.inner.insert(key, self.batches.len());
-                    self.batches.push(Batch {
-                        data,
-                        instances: self.buffers.pop().unwrap_or_default(),
-                        diffuse_texture: diffuse_texture.clone(),
-                        normal_texture: normal_texture.clone(),
-                        specular_texture: specular_texture.clone(),
-                        roughness_texture: roughness_texture.clone(),
-                        lightmap_texture: lightmap_texture.clone(),
-                        is_skinned: !surface.bones.is_empty(),
-                    });
-                    self.batches.last_mut().unwrap()
+                    (surface.data(), surface.batch_id())
                 };
 
-                // Update textures.
-                batch.diffuse_texture = diffuse_texture;
-                batch.normal_texture = normal_texture;
-                batch.specular_texture = specular_texture;
-                batch.roughness_texture = roughness_texture;
-                batch.lightmap_texture = lightmap_texture;
-
-                batch.instances.push(SurfaceInstance {
-                    world_transform: world,
-                    bone_matrices: ArrayVec::from_iter(surface.bones.iter().map(|&bone_handle| {
-                        let bone_node = &graph[bone_handle];
-                        bone_node.global_transform() * bone_node.inv_bind_pose_transform()
-                    })),
-                    color: surface.color(),
-                    owner: handle,
-                    depth_offset: mesh.depth_offset_factor(),
-                });
+                let draw_data = collect_surface_draw_data(
+                    surface,
+                    key,
+                    data,
+                    world,
+                    mesh.depth_offset_factor(),
+                );
+
+                push_instance(
+                    state,
+                    texture_cache,
+                    &dummies,
+                    &mut self.batches,
+                    &mut self.inner,
+                    &mut self.buffers,
+                    &draw_data,
+                    handle,
+                    if cpu_skinned {
+                        Default::default()
+                    } else {
+                        ArrayVec::from_iter(surface.bones.iter().map(|&bone_handle| {
+                            let bone_node = &graph[bone_handle];
+                            bone_node.global_transform() * bone_node.inv_bind_pose_transform()
+                        }))
+                    },
+                );
             }
         }
 
+        self.cpu_skinned_surfaces
+            .retain(|key, surface| key.0 != graph_key || surface.used);
+        self.draw_data_cache
+            .retain(|key, entry| key.0 != graph_key || entry.used);
+
         // Sort by diffuse texture, this will significantly decrease texture pipeline
         // state changes during the rendering.
         self.batches
@@ -257,3 +741,70 @@ impl MatrixStorage {
             .unwrap();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::algebra::{Matrix4, Vector2, Vector3},
+        renderer::{
+            batch::{skin_vertices, static_mesh_draw_data, DrawDataCacheStatistics},
+            surface::{SurfaceBuilder, SurfaceSharedData, Vertex},
+        },
+        resource::texture::{Texture, TexturePixelKind},
+        scene::{base::BaseBuilder, graph::Graph, mesh::MeshBuilder},
+    };
+    use std::{
+        collections::HashMap,
+        sync::{Arc, RwLock},
+    };
+
+    #[test]
+    fn test_cpu_skinning() {
+        let mut vertex = Vertex::from_pos_uv(Vector3::new(1.0, 0.0, 0.0), Vector2::default());
+        vertex.bone_indices = [0, 1, 0, 0];
+        vertex.bone_weights = [0.5, 0.5, 0.0, 0.0];
+        let source = SurfaceSharedData::new(vec![vertex], Vec::new(), true);
+        let mut dest = SurfaceSharedData::new(vec![vertex], Vec::new(), true);
+
+        let bone_matrices = [
+            Matrix4::new_translation(&Vector3::new(0.0, 2.0, 0.0)),
+            Matrix4::new_nonuniform_scaling(&Vector3::new(3.0, 1.0, 1.0)),
+        ];
+        skin_vertices(&source, &mut dest, &bone_matrices);
+
+        assert_eq!(dest.vertices[0].position, Vector3::new(2.0, 1.0, 0.0));
+        assert_eq!(dest.vertices[0].normal, Vector3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_cached_draw_data_follows_texture_changes() {
+        let mut graph = Graph::new();
+        let texture = Texture::from_bytes(2, 2, TexturePixelKind::R8, vec![0; 4]).unwrap();
+        let handle = MeshBuilder::new(BaseBuilder::new())
+            .with_surfaces(vec![SurfaceBuilder::new(Arc::new(RwLock::new(
+                SurfaceSharedData::make_cube(Matrix4::identity()),
+            )))
+            .with_diffuse_texture(texture.clone())
+            .build()])
+            .build(&mut graph);
+        graph.update_hierarchical_data();
+        let mesh = graph[handle].as_mesh();
+
+        let mut cache = HashMap::new();
+        let mut statistics = DrawDataCacheStatistics::default();
+        static_mesh_draw_data(&mut cache, &mut statistics, 0, handle, mesh);
+
+        // Modify texture of the static mesh, the mesh itself stays the same.
+        texture.modify().update_region(0, 0, 1, 1, &[255]).unwrap();
+
+        let draw_data = static_mesh_draw_data(&mut cache, &mut statistics, 0, handle, mesh);
+        assert_eq!(statistics.hits, 1);
+        assert_eq!(statistics.misses, 1);
+
+        // Cached draw data refers to the texture resource, not to its GPU copy, so the
+        // texture goes through texture cache every frame and modified pixels are uploaded.
+        let diffuse_texture = draw_data[0].diffuse_texture.texture.as_ref().unwrap();
+        assert_eq!(diffuse_texture.key(), texture.key());
+        assert!(diffuse_texture.modify().modified_region.is_some());
+    }
+}
@@ -0,0 +1,396 @@
+//! Color grading is the last post-processing step which is applied to the final frame in
+//! display color space, right before user interface is drawn.
+//!
+//! # Lookup tables
+//!
+//! Color grading uses 3D lookup tables (LUT), each color of the frame is used as coordinates in
+//! the table to fetch a new color. LUT can be made in any image editor: take a screenshot of the
+//! game, put neutral LUT strip next to it, apply any color correction you like to the whole image
+//! and then cut the strip back. Two layouts are supported:
+//!
+//! - Strip of `size` squares placed horizontally, so image has `size * size` by `size` pixels
+//!   (1024x32 or 256x16 for example). Red grows from left to right in each square, green - from
+//!   top to bottom, blue - from the first square to the last.
+//! - Adobe .cube file with 3D table.
+//!
+//! Both are loaded through resource manager and then converted into a volume texture using
+//! `ColorGradingLut::new`.
+//!
+//! # Blending
+//!
+//! Renderer can blend results of two LUTs, this is useful for smooth transitions between
+//! different "moods" (day and night, damage effect, etc.), see `ColorGrading::blend`.
+//!
+//! ```no_run
+//! use rg3d::{
+//!     engine::resource_manager::ResourceManager,
+//!     renderer::{
+//!         color_grading::{ColorGrading, ColorGradingLut},
+//!         Renderer,
+//!     },
+//! };
+//!
+//! async fn setup(renderer: &mut Renderer, resource_manager: ResourceManager) {
+//!     let day = ColorGradingLut::new(resource_manager.request_texture("data/day.png"))
+//!         .await
+//!         .unwrap();
+//!     let night = ColorGradingLut::new(resource_manager.request_texture("data/night.cube"))
+//!         .await
+//!         .unwrap();
+//!     renderer.set_color_grading(ColorGrading {
+//!         lut: Some(day),
+//!         blend_lut: Some(night),
+//!         blend: 0.0,
+//!     });
+//! }
+//!
+//! fn update(renderer: &mut Renderer, night_factor: f32) {
+//!     renderer.color_grading_mut().blend = night_factor;
+//! }
+//! ```
+
+use crate::{
+    core::algebra::Vector3,
+    resource::texture::{
+        Texture, TextureColorSpace, TextureData, TextureError, TextureKind,
+        TextureMagnificationFilter, TextureMinificationFilter, TexturePixelKind, TextureState,
+        TextureWrapMode,
+    },
+};
+use std::sync::Arc;
+
+/// An error that may occur during creation of a lookup table.
+#[derive(Debug)]
+pub enum ColorGradingError {
+    /// Source texture failed to load.
+    Texture(Option<Arc<TextureError>>),
+    /// Source texture has pixel format or dimensions that cannot be used as a LUT. Rectangle
+    /// textures must have `size * size` by `size` pixels, volume textures must be cubes.
+    UnsupportedFormat,
+}
+
+/// 3D color lookup table. See module docs.
+#[derive(Clone, Debug)]
+pub struct ColorGradingLut {
+    size: u32,
+    texture: Texture,
+    domain_min: Vector3<f32>,
+    domain_max: Vector3<f32>,
+}
+
+/// Set of lookup tables used by renderer. Missing table acts as neutral one, so setting only
+/// `blend_lut` and changing `blend` from 0 to 1 will smoothly fade in the table.
+#[derive(Clone, Debug, Default)]
+pub struct ColorGrading {
+    /// First lookup table.
+    pub lut: Option<ColorGradingLut>,
+    /// Second lookup table.
+    pub blend_lut: Option<ColorGradingLut>,
+    /// Blend factor in 0..1 range, 0 - only `lut` is used, 1 - only `blend_lut` is used.
+    pub blend: f32,
+}
+
+impl ColorGrading {
+    /// Returns true if there is at least one lookup table.
+    pub fn is_enabled(&self) -> bool {
+        self.lut.is_some() || self.blend_lut.is_some()
+    }
+}
+
+// Reads a pixel of 8 or 16 bits per channel as normalized RGB.
+fn read_rgb(bytes: &[u8], pixel_kind: TexturePixelKind, index: usize) -> Option<[f32; 3]> {
+    let (channels, bytes_per_channel, bgr) = match pixel_kind {
+        TexturePixelKind::RGB8 => (3, 1, false),
+        TexturePixelKind::RGBA8 => (4, 1, false),
+        TexturePixelKind::BGR8 => (3, 1, true),
+        TexturePixelKind::BGRA8 => (4, 1, true),
+        TexturePixelKind::RGB16 => (3, 2, false),
+        TexturePixelKind::RGBA16 => (4, 2, false),
+        _ => return None,
+    };
+    let offset = index * channels * bytes_per_channel;
+    let mut rgb = [0.0; 3];
+    for (i, component) in rgb.iter_mut().enumerate() {
+        let i = offset + i * bytes_per_channel;
+        *component = if bytes_per_channel == 1 {
+            f32::from(*bytes.get(i)?) / 255.0
+        } else {
+            f32::from(u16::from_ne_bytes([*bytes.get(i)?, *bytes.get(i + 1)?])) / 65535.0
+        };
+    }
+    if bgr {
+        rgb.swap(0, 2);
+    }
+    Some(rgb)
+}
+
+impl ColorGradingLut {
+    /// Waits until source texture is loaded and creates lookup table from it. See module docs
+    /// for supported layouts.
+    pub async fn new(source: Texture) -> Result<Self, ColorGradingError> {
+        let source = source.await.map_err(ColorGradingError::Texture)?;
+        let data = source.data_ref();
+        Self::from_texture_data(&data)
+    }
+
+    /// Creates lookup table from data of a texture which is already loaded. See module docs
+    /// for supported layouts.
+    pub fn from_texture_data(data: &TextureData) -> Result<Self, ColorGradingError> {
+        let size = match data.kind {
+            TextureKind::Rectangle { width, height } if height >= 2 && width == height * height => {
+                height
+            }
+            TextureKind::Volume {
+                width,
+                height,
+                depth,
+            } if width >= 2 && width == height && width == depth => width,
+            _ => return Err(ColorGradingError::UnsupportedFormat),
+        };
+
+        let is_strip = matches!(data.kind, TextureKind::Rectangle { .. });
+
+        let n = size as usize;
+        let mut table = Vec::with_capacity(n * n * n);
+        for b in 0..n {
+            for g in 0..n {
+                for r in 0..n {
+                    let index = if is_strip {
+                        g * n * n + b * n + r
+                    } else {
+                        (b * n + g) * n + r
+                    };
+                    table.push(
+                        read_rgb(&data.bytes, data.pixel_kind, index)
+                            .ok_or(ColorGradingError::UnsupportedFormat)?,
+                    );
+                }
+            }
+        }
+
+        let mut lut = Self::from_table(size, &table);
+        if let Some((min, max)) = data.lut_domain() {
+            lut.domain_min = min;
+            lut.domain_max = max;
+        }
+        Ok(lut)
+    }
+
+    /// Creates lookup table which does not change colors.
+    pub fn neutral(size: u32) -> Self {
+        let size = size.max(2);
+        let n = size as usize;
+        let max = (size - 1) as f32;
+        let mut table = Vec::with_capacity(n * n * n);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    table.push([r as f32 / max, g as f32 / max, b as f32 / max]);
+                }
+            }
+        }
+        Self::from_table(size, &table)
+    }
+
+    fn from_table(size: u32, table: &[[f32; 3]]) -> Self {
+        let mut bytes = Vec::with_capacity(table.len() * 6);
+        for rgb in table {
+            for component in rgb.iter() {
+                let value = (component.max(0.0).min(1.0) * 65535.0).round() as u16;
+                bytes.extend_from_slice(&value.to_ne_bytes());
+            }
+        }
+
+        let mut data = TextureData::from_bytes(
+            TextureKind::Volume {
+                width: size,
+                height: size,
+                depth: size,
+            },
+            TexturePixelKind::RGB16,
+            bytes,
+        )
+        .unwrap();
+        // Table contains raw values, they must not be converted.
+        data.set_color_space(TextureColorSpace::Linear);
+        data.set_minification_filter(TextureMinificationFilter::Linear);
+        data.set_magnification_filter(TextureMagnificationFilter::Linear);
+        data.set_s_wrap_mode(TextureWrapMode::ClampToEdge);
+        data.set_t_wrap_mode(TextureWrapMode::ClampToEdge);
+        data.set_anisotropy_level(1.0);
+
+        Self {
+            size,
+            texture: Texture::new(TextureState::Ok(data)),
+            domain_min: Vector3::new(0.0, 0.0, 0.0),
+            domain_max: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Returns amount of entries along each axis of the table.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Returns volume texture of the table.
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// Returns range (min, max) of input colors, it is 0..1 for every table except .cube files
+    /// with `DOMAIN_MIN` and `DOMAIN_MAX`. Colors are remapped from this range to coordinates
+    /// in the table.
+    pub fn domain(&self) -> (Vector3<f32>, Vector3<f32>) {
+        (self.domain_min, self.domain_max)
+    }
+
+    /// Applies the table to a color on CPU, result matches the one from renderer. Color
+    /// components outside of the domain are clamped.
+    pub fn apply(&self, color: Vector3<f32>) -> Vector3<f32> {
+        let data = self.texture.data_ref();
+        let n = self.size as usize;
+        let fetch = |r: usize, g: usize, b: usize| {
+            let rgb = read_rgb(&data.bytes, data.pixel_kind, (b * n + g) * n + r).unwrap();
+            Vector3::new(rgb[0], rgb[1], rgb[2])
+        };
+
+        // Trilinear filtering, texel centers are at integer coordinates.
+        let max = (n - 1) as f32;
+        let coords = (color - self.domain_min)
+            .component_div(&(self.domain_max - self.domain_min))
+            .map(|c| c.max(0.0).min(1.0) * max);
+        let i0 = coords.map(|c| (c.floor() as usize).min(n - 2));
+        let t = Vector3::new(
+            coords.x - i0.x as f32,
+            coords.y - i0.y as f32,
+            coords.z - i0.z as f32,
+        );
+
+        let mut result = Vector3::default();
+        for (db, wb) in [(0, 1.0 - t.z), (1, t.z)].iter() {
+            for (dg, wg) in [(0, 1.0 - t.y), (1, t.y)].iter() {
+                for (dr, wr) in [(0, 1.0 - t.x), (1, t.x)].iter() {
+                    result += fetch(i0.x + dr, i0.y + dg, i0.z + db) * (wr * wg * wb);
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::algebra::Vector3,
+        renderer::color_grading::ColorGradingLut,
+        resource::texture::{TextureData, TextureKind, TexturePixelKind},
+    };
+    use std::fmt::Write;
+
+    // Gradient image which stands in for an ungraded frame.
+    fn ungraded_frame() -> Vec<Vector3<f32>> {
+        let mut frame = Vec::new();
+        for y in 0..64 {
+            for x in 0..64 {
+                frame.push(Vector3::new(
+                    x as f32 / 63.0,
+                    y as f32 / 63.0,
+                    ((x * 7 + y * 13) % 64) as f32 / 63.0,
+                ));
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn test_neutral_lut_is_noop() {
+        for size in [2, 16, 32].iter() {
+            let lut = ColorGradingLut::neutral(*size);
+            for color in ungraded_frame() {
+                let graded = lut.apply(color);
+                assert!((graded - color).amax() < 1.0e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn test_neutral_strip() {
+        // 4x4x4 neutral strip in RGBA8.
+        let size = 4u32;
+        let mut bytes = Vec::new();
+        for g in 0..size {
+            for b in 0..size {
+                for r in 0..size {
+                    bytes.extend_from_slice(&[
+                        (r * 255 / (size - 1)) as u8,
+                        (g * 255 / (size - 1)) as u8,
+                        (b * 255 / (size - 1)) as u8,
+                        255,
+                    ]);
+                }
+            }
+        }
+        let data = TextureData::from_bytes(
+            TextureKind::Rectangle {
+                width: size * size,
+                height: size,
+            },
+            TexturePixelKind::RGBA8,
+            bytes,
+        )
+        .unwrap();
+        let lut = ColorGradingLut::from_texture_data(&data).unwrap();
+        assert_eq!(lut.size(), size);
+        for color in ungraded_frame() {
+            assert!((lut.apply(color) - color).amax() < 1.0e-4);
+        }
+
+        let invalid = TextureData::from_bytes(
+            TextureKind::Rectangle {
+                width: 8,
+                height: 4,
+            },
+            TexturePixelKind::R8,
+            vec![0; 32],
+        )
+        .unwrap();
+        assert!(ColorGradingLut::from_texture_data(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_cube_domain() {
+        // Neutral table over 0..2 input range which outputs halves of input colors.
+        let mut cube = "LUT_3D_SIZE 3\nDOMAIN_MIN 0 0 0\nDOMAIN_MAX 2 2 2\n".to_owned();
+        for b in 0..3 {
+            for g in 0..3 {
+                for r in 0..3 {
+                    writeln!(
+                        cube,
+                        "{} {} {}",
+                        r as f32 * 0.5,
+                        g as f32 * 0.5,
+                        b as f32 * 0.5
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        let data = TextureData::load_from_memory(cube.as_bytes(), "test.cube").unwrap();
+        let lut = ColorGradingLut::from_texture_data(&data).unwrap();
+        assert_eq!(
+            lut.domain(),
+            (Vector3::new(0.0, 0.0, 0.0), Vector3::new(2.0, 2.0, 2.0))
+        );
+
+        for color in ungraded_frame() {
+            assert!((lut.apply(color) - color * 0.5).amax() < 1.0e-4);
+        }
+        // Colors above 1 are inside of the domain.
+        let bright = Vector3::new(1.5, 2.0, 1.2);
+        assert!((lut.apply(bright) - bright * 0.5).amax() < 1.0e-4);
+        // Colors outside of the domain are clamped.
+        assert!(
+            (lut.apply(Vector3::new(3.0, -1.0, 1.0)) - Vector3::new(1.0, 0.0, 0.5)).amax() < 1.0e-4
+        );
+    }
+}
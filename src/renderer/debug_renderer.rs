@@ -8,7 +8,7 @@ use crate::{
     renderer::{
         error::RendererError,
         framework::{
-            framebuffer::{CullFace, DrawParameters, FrameBuffer, FrameBufferTrait},
+            framebuffer::{CullFace, DrawParameters, FrameBufferTrait},
             geometry_buffer::{
                 AttributeDefinition, AttributeKind, BufferBuilder, ElementKind, GeometryBuffer,
                 GeometryBufferBuilder, GeometryBufferKind,
@@ -84,7 +84,7 @@ impl DebugRenderer {
         &mut self,
         state: &mut PipelineState,
         viewport: Rect<i32>,
-        framebuffer: &mut FrameBuffer,
+        framebuffer: &mut dyn FrameBufferTrait,
         drawing_context: &SceneDrawingContext,
         camera: &Camera,
     ) -> RenderPassStatistics {
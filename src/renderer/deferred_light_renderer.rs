@@ -9,6 +9,7 @@ use crate::{
         batch::BatchStorage,
         error::RendererError,
         flat_shader::FlatShader,
+        fog::FogRenderer,
         framework::{
             framebuffer::{CullFace, DrawParameters, DrawPartContext, FrameBufferTrait},
             gl,
@@ -17,7 +18,7 @@ use crate::{
             state::{ColorMask, PipelineState, StencilFunc, StencilOp},
         },
         gbuffer::GBuffer,
-        light_volume::LightVolumeRenderer,
+        light_volume::{LightVolumeRenderer, LightVolumeShadow},
         shadow_map_renderer::{
             PointShadowMapRenderContext, PointShadowMapRenderer, SpotShadowMapRenderer,
         },
@@ -25,7 +26,12 @@ use crate::{
         surface::{SurfaceSharedData, Vertex},
         GeometryCache, QualitySettings, RenderPassStatistics, TextureCache,
     },
-    scene::{camera::Camera, light::Light, node::Node, Scene},
+    scene::{
+        camera::Camera,
+        light::{Light, ShadowMapResolution},
+        node::Node,
+        Scene,
+    },
 };
 use std::{
     cell::RefCell,
@@ -41,6 +47,7 @@ struct AmbientLightShader {
     ambient_color: UniformLocation,
     ao_sampler: UniformLocation,
     ambient_texture: UniformLocation,
+    material_texture: UniformLocation,
 }
 
 #[derive(Copy, Clone, Default)]
@@ -93,6 +100,7 @@ impl AmbientLightShader {
             ambient_color: program.uniform_location("ambientColor")?,
             ao_sampler: program.uniform_location("aoSampler")?,
             ambient_texture: program.uniform_location("ambientTexture")?,
+            material_texture: program.uniform_location("materialTexture")?,
             program,
         })
     }
@@ -104,6 +112,7 @@ struct SpotLightShader {
     depth_sampler: UniformLocation,
     color_sampler: UniformLocation,
     normal_sampler: UniformLocation,
+    material_sampler: UniformLocation,
     spot_shadow_texture: UniformLocation,
     cookie_enabled: UniformLocation,
     cookie_texture: UniformLocation,
@@ -133,6 +142,7 @@ impl SpotLightShader {
             depth_sampler: program.uniform_location("depthTexture")?,
             color_sampler: program.uniform_location("colorTexture")?,
             normal_sampler: program.uniform_location("normalTexture")?,
+            material_sampler: program.uniform_location("materialTexture")?,
             spot_shadow_texture: program.uniform_location("spotShadowTexture")?,
             cookie_enabled: program.uniform_location("cookieEnabled")?,
             cookie_texture: program.uniform_location("cookieTexture")?,
@@ -161,6 +171,7 @@ struct PointLightShader {
     depth_sampler: UniformLocation,
     color_sampler: UniformLocation,
     normal_sampler: UniformLocation,
+    material_sampler: UniformLocation,
     point_shadow_texture: UniformLocation,
     shadows_enabled: UniformLocation,
     soft_shadows: UniformLocation,
@@ -183,6 +194,7 @@ impl PointLightShader {
             depth_sampler: program.uniform_location("depthTexture")?,
             color_sampler: program.uniform_location("colorTexture")?,
             normal_sampler: program.uniform_location("normalTexture")?,
+            material_sampler: program.uniform_location("materialTexture")?,
             point_shadow_texture: program.uniform_location("pointShadowTexture")?,
             shadows_enabled: program.uniform_location("shadowsEnabled")?,
             soft_shadows: program.uniform_location("softShadows")?,
@@ -204,6 +216,7 @@ struct DirectionalLightShader {
     depth_sampler: UniformLocation,
     color_sampler: UniformLocation,
     normal_sampler: UniformLocation,
+    material_sampler: UniformLocation,
     light_direction: UniformLocation,
     light_color: UniformLocation,
     inv_view_proj_matrix: UniformLocation,
@@ -221,6 +234,7 @@ impl DirectionalLightShader {
             depth_sampler: program.uniform_location("depthTexture")?,
             color_sampler: program.uniform_location("colorTexture")?,
             normal_sampler: program.uniform_location("normalTexture")?,
+            material_sampler: program.uniform_location("materialTexture")?,
             light_direction: program.uniform_location("lightDirection")?,
             light_color: program.uniform_location("lightColor")?,
             inv_view_proj_matrix: program.uniform_location("invViewProj")?,
@@ -243,6 +257,8 @@ pub struct DeferredLightRenderer {
     spot_shadow_map_renderer: SpotShadowMapRenderer,
     point_shadow_map_renderer: PointShadowMapRenderer,
     light_volume: LightVolumeRenderer,
+    fog_renderer: FogRenderer,
+    frame_size: (u32, u32),
 }
 
 pub(in crate) struct DeferredRendererContext<'a> {
@@ -265,6 +281,7 @@ impl DeferredLightRenderer {
         settings: &QualitySettings,
     ) -> Result<Self, RendererError> {
         Ok(Self {
+            frame_size,
             ssao_renderer: ScreenSpaceAmbientOcclusionRenderer::new(
                 state,
                 frame_size.0 as usize,
@@ -337,6 +354,7 @@ impl DeferredLightRenderer {
                 QualitySettings::default().point_shadow_map_precision,
             )?,
             light_volume: LightVolumeRenderer::new()?,
+            fog_renderer: FogRenderer::new()?,
         })
     }
 
@@ -372,11 +390,14 @@ impl DeferredLightRenderer {
         state: &mut PipelineState,
         frame_size: (u32, u32),
     ) -> Result<(), RendererError> {
-        self.ssao_renderer = ScreenSpaceAmbientOcclusionRenderer::new(
-            state,
-            frame_size.0 as usize,
-            frame_size.1 as usize,
-        )?;
+        if self.frame_size != frame_size {
+            self.ssao_renderer = ScreenSpaceAmbientOcclusionRenderer::new(
+                state,
+                frame_size.0 as usize,
+                frame_size.1 as usize,
+            )?;
+            self.frame_size = frame_size;
+        }
         Ok(())
     }
 
@@ -546,11 +567,21 @@ impl DeferredLightRenderer {
                         texture: gbuffer.ambient_texture(),
                     },
                 ),
+                (
+                    self.ambient_light_shader.material_texture,
+                    UniformValue::Sampler {
+                        index: 3,
+                        texture: gbuffer.material_texture(),
+                    },
+                ),
             ],
         );
 
         state.set_blend_func(gl::ONE, gl::ONE);
 
+        let light_scatter_enabled = settings.light_scatter_enabled
+            && self.light_volume.begin(state, gbuffer, settings).is_ok();
+
         for light in scene.graph.linear_iter().filter_map(|node| {
             if let Node::Light(light) = node {
                 Some(light)
@@ -592,43 +623,59 @@ impl DeferredLightRenderer {
             };
             let b1 = v * 0.2;
             let b2 = v * 0.4;
-            let cascade_index = if distance_to_camera < b1 {
-                0
-            } else if distance_to_camera > b1 && distance_to_camera < b2 {
-                1
-            } else {
-                2
+            // Point lights can have fixed resolution of shadow map, otherwise cascade is
+            // selected by distance to camera.
+            let fixed_cascade = match light {
+                Light::Point(point_light) => match point_light.shadow_map_resolution() {
+                    ShadowMapResolution::Auto => None,
+                    ShadowMapResolution::Full => Some(0),
+                    ShadowMapResolution::Half => Some(1),
+                    ShadowMapResolution::Quarter => Some(2),
+                },
+                _ => None,
             };
+            let cascade_index = fixed_cascade.unwrap_or_else(|| {
+                if distance_to_camera < b1 {
+                    0
+                } else if distance_to_camera > b1 && distance_to_camera < b2 {
+                    1
+                } else {
+                    2
+                }
+            });
 
-            let mut light_view_projection = Matrix4::identity();
-            let shadows_enabled = light.is_cast_shadows()
-                && match light {
-                    Light::Spot(spot)
-                        if distance_to_camera <= settings.spot_shadows_distance
-                            && settings.spot_shadows_enabled =>
-                    {
-                        let light_projection_matrix = Matrix4::new_perspective(
-                            1.0,
-                            spot.full_cone_angle(),
-                            0.01,
-                            light_radius,
-                        );
+            // View-projection matrix of spot light is used both for shadows and cookie
+            // texture projection, so it must be calculated even if shadows are disabled.
+            let light_view_projection = match light {
+                Light::Spot(spot) => {
+                    let light_projection_matrix =
+                        Matrix4::new_perspective(1.0, spot.full_cone_angle(), 0.01, light_radius);
 
-                        let light_look_at = light_position - emit_direction;
+                    let light_look_at = light_position - emit_direction;
 
-                        let light_up_vec = light
-                            .look_vector()
-                            .try_normalize(std::f32::EPSILON)
-                            .unwrap_or_else(Vector3::y);
+                    let light_up_vec = light
+                        .look_vector()
+                        .try_normalize(std::f32::EPSILON)
+                        .unwrap_or_else(Vector3::y);
 
-                        let light_view_matrix = Matrix4::look_at_rh(
-                            &Point3::from(light_position),
-                            &Point3::from(light_look_at),
-                            &light_up_vec,
-                        );
+                    let light_view_matrix = Matrix4::look_at_rh(
+                        &Point3::from(light_position),
+                        &Point3::from(light_look_at),
+                        &light_up_vec,
+                    );
 
-                        light_view_projection = light_projection_matrix * light_view_matrix;
+                    light_projection_matrix * light_view_matrix
+                }
+                _ => Matrix4::identity(),
+            };
 
+            let shadows_enabled = light.is_cast_shadows()
+                && match light {
+                    Light::Spot(_)
+                        if distance_to_camera
+                            <= light.effective_shadow_distance(settings.spot_shadows_distance)
+                            && settings.spot_shadows_enabled =>
+                    {
                         pass_stats += self.spot_shadow_map_renderer.render(
                             state,
                             &scene.graph,
@@ -643,7 +690,8 @@ impl DeferredLightRenderer {
                         true
                     }
                     Light::Point(_)
-                        if distance_to_camera <= settings.point_shadows_distance
+                        if distance_to_camera
+                            <= light.effective_shadow_distance(settings.point_shadows_distance)
                             && settings.point_shadows_enabled =>
                     {
                         pass_stats +=
@@ -764,12 +812,15 @@ impl DeferredLightRenderer {
                 Light::Spot(spot_light) => {
                     let shader = &self.spot_light_shader;
 
-                    let (cookie_enabled, cookie_texture) =
-                        if let Some(texture) = spot_light.cookie_texture() {
-                            (true, textures.get(state, texture.clone()).unwrap())
-                        } else {
-                            (false, white_dummy.clone())
-                        };
+                    // Cookie texture may be still loading or failed to load, in this case light
+                    // falls back to plain cone.
+                    let (cookie_enabled, cookie_texture) = match spot_light
+                        .cookie_texture()
+                        .and_then(|texture| textures.get(state, texture.clone()))
+                    {
+                        Some(texture) => (true, texture),
+                        None => (false, white_dummy.clone()),
+                    };
 
                     let uniforms = [
                         (shader.shadows_enabled, UniformValue::Bool(shadows_enabled)),
@@ -850,6 +901,13 @@ impl DeferredLightRenderer {
                                 texture: cookie_texture,
                             },
                         ),
+                        (
+                            shader.material_sampler,
+                            UniformValue::Sampler {
+                                index: 5,
+                                texture: gbuffer.material_texture(),
+                            },
+                        ),
                         (
                             shader.shadow_bias,
                             UniformValue::Float(spot_light.shadow_bias()),
@@ -918,6 +976,13 @@ impl DeferredLightRenderer {
                                     .cascade_texture(cascade_index),
                             },
                         ),
+                        (
+                            shader.material_sampler,
+                            UniformValue::Sampler {
+                                index: 4,
+                                texture: gbuffer.material_texture(),
+                            },
+                        ),
                         (
                             shader.shadow_bias,
                             UniformValue::Float(point_light.shadow_bias()),
@@ -974,6 +1039,13 @@ impl DeferredLightRenderer {
                                 texture: gbuffer.normal_texture(),
                             },
                         ),
+                        (
+                            shader.material_sampler,
+                            UniformValue::Sampler {
+                                index: 3,
+                                texture: gbuffer.material_texture(),
+                            },
+                        ),
                     ];
 
                     light_stats.directional_lights_rendered += 1;
@@ -997,21 +1069,57 @@ impl DeferredLightRenderer {
                 }
             };
 
-            if settings.light_scatter_enabled {
+            if light_scatter_enabled {
+                let (shadow_map, bias) = match light {
+                    Light::Point(point_light) => (
+                        self.point_shadow_map_renderer
+                            .cascade_texture(cascade_index),
+                        point_light.shadow_bias(),
+                    ),
+                    Light::Spot(spot_light) => (
+                        self.spot_shadow_map_renderer.cascade_texture(cascade_index),
+                        spot_light.shadow_bias(),
+                    ),
+                    Light::Directional(_) => continue,
+                };
+
                 pass_stats += self.light_volume.render_volume(
                     state,
                     light,
                     gbuffer,
+                    LightVolumeShadow {
+                        enabled: shadows_enabled,
+                        shadow_map,
+                        light_view_projection,
+                        bias,
+                    },
                     &self.quad,
                     geometry_cache,
                     camera.view_matrix(),
                     projection_matrix.try_inverse().unwrap_or_default(),
                     camera.view_projection_matrix(),
-                    viewport,
                 );
             }
         }
 
+        if let Some(fog) = scene.fog() {
+            pass_stats += self.fog_renderer.render(
+                state,
+                gbuffer,
+                &self.quad,
+                geometry_cache,
+                fog,
+                inv_view_projection,
+                camera.global_position(),
+            );
+        }
+
+        if light_scatter_enabled {
+            pass_stats += self
+                .light_volume
+                .end(state, gbuffer, &self.quad, geometry_cache);
+        }
+
         (pass_stats, light_stats)
     }
 }
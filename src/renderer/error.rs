@@ -1,7 +1,10 @@
 //! Contains all possible errors that may occur during rendering, initialization of
 //! renderer structures, or GAPI.
 
-use crate::ContextError;
+use crate::{
+    renderer::{frame_graph::FrameGraphError, QualitySettingsProblem},
+    ContextError,
+};
 use std::ffi::NulError;
 
 /// Set of possible renderer errors.
@@ -54,6 +57,12 @@ pub enum RendererError {
     FailedToConstructFBO,
     /// Internal context error.
     Context(ContextError),
+    /// Quality settings contain invalid values, each problem is described separately.
+    InvalidQualitySettings(Vec<QualitySettingsProblem>),
+    /// Custom render passes cannot be scheduled or a pass does not exist.
+    FrameGraph(FrameGraphError),
+    /// Only rectangle textures can be used as render targets.
+    InvalidRenderTarget,
 }
 
 impl From<NulError> for RendererError {
@@ -67,3 +76,9 @@ impl From<ContextError> for RendererError {
         Self::Context(err)
     }
 }
+
+impl From<FrameGraphError> for RendererError {
+    fn from(err: FrameGraphError) -> Self {
+        Self::FrameGraph(err)
+    }
+}
@@ -0,0 +1,218 @@
+//! Distance fog renderer.
+//!
+//! Fog is applied in a single full screen pass after lighting: world position of every pixel
+//! is restored from depth buffer and fog color is blended on top of the frame using strength
+//! of fog as alpha. See `scene::fog` module docs for more info about fog settings.
+//!
+//! Forward passes (transparent surfaces, sprites, particles) do not write depth, so they apply
+//! the same fog in their shaders, see `FogUniforms`.
+
+use crate::{
+    core::{
+        algebra::{Matrix4, Vector3},
+        math::Rect,
+        scope_profile,
+    },
+    renderer::{
+        error::RendererError,
+        framework::{
+            framebuffer::{CullFace, DrawParameters, FrameBufferTrait},
+            gl,
+            gpu_program::{GpuProgram, UniformLocation, UniformValue},
+            state::{ColorMask, PipelineState},
+        },
+        gbuffer::GBuffer,
+        surface::SurfaceSharedData,
+        GeometryCache, RenderPassStatistics,
+    },
+    scene::fog::{FogMode, FogSettings},
+};
+
+/// Locations of fog uniforms of a shader that applies fog with `S_FogFactor` from shared.glsl,
+/// the shader must declare `uniform S_Fog fog;`.
+pub(in crate) struct FogUniforms {
+    enabled: UniformLocation,
+    color: UniformLocation,
+    mode: UniformLocation,
+    start: UniformLocation,
+    end: UniformLocation,
+    density: UniformLocation,
+    height_fog_enabled: UniformLocation,
+    height_fog_base: UniformLocation,
+    height_fog_falloff: UniformLocation,
+}
+
+impl FogUniforms {
+    pub fn new(program: &GpuProgram) -> Result<Self, RendererError> {
+        Ok(Self {
+            enabled: program.uniform_location("fog.enabled")?,
+            color: program.uniform_location("fog.color")?,
+            mode: program.uniform_location("fog.mode")?,
+            start: program.uniform_location("fog.start")?,
+            end: program.uniform_location("fog.end")?,
+            density: program.uniform_location("fog.density")?,
+            height_fog_enabled: program.uniform_location("fog.heightFogEnabled")?,
+            height_fog_base: program.uniform_location("fog.heightFogBase")?,
+            height_fog_falloff: program.uniform_location("fog.heightFogFalloff")?,
+        })
+    }
+
+    /// Sets fog uniforms of given program, `None` disables fog. Uniforms keep their values
+    /// until next call, so it can be called once before series of draw calls.
+    pub fn set(&self, program: &GpuProgram, state: &mut PipelineState, fog: Option<&FogSettings>) {
+        let fog = match fog {
+            Some(fog) => fog,
+            None => {
+                program.set_uniform(state, self.enabled, &UniformValue::Bool(false));
+                return;
+            }
+        };
+
+        let (start, end, density) = match fog.mode {
+            FogMode::Linear { start, end } => (start, end, 0.0),
+            FogMode::Exponential { density } | FogMode::ExponentialSquared { density } => {
+                (0.0, 0.0, density)
+            }
+        };
+        let height_fog = fog.height_fog.unwrap_or_default();
+
+        for (location, value) in [
+            (self.enabled, UniformValue::Bool(true)),
+            (self.color, UniformValue::Color(fog.color)),
+            (self.mode, UniformValue::Integer(fog.mode.id() as i32)),
+            (self.start, UniformValue::Float(start)),
+            (self.end, UniformValue::Float(end)),
+            (self.density, UniformValue::Float(density)),
+            (
+                self.height_fog_enabled,
+                UniformValue::Bool(fog.height_fog.is_some()),
+            ),
+            (
+                self.height_fog_base,
+                UniformValue::Float(height_fog.base_height),
+            ),
+            (
+                self.height_fog_falloff,
+                UniformValue::Float(height_fog.falloff),
+            ),
+        ]
+        .iter()
+        {
+            program.set_uniform(state, *location, value);
+        }
+    }
+}
+
+struct FogShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    depth_sampler: UniformLocation,
+    inv_view_proj_matrix: UniformLocation,
+    camera_position: UniformLocation,
+    fog: FogUniforms,
+}
+
+impl FogShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/fog_fs.glsl");
+        let vertex_source = include_str!("shaders/deferred_light_vs.glsl");
+        let program = GpuProgram::from_source("FogShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            depth_sampler: program.uniform_location("depthTexture")?,
+            inv_view_proj_matrix: program.uniform_location("invViewProj")?,
+            camera_position: program.uniform_location("cameraPosition")?,
+            fog: FogUniforms::new(&program)?,
+            program,
+        })
+    }
+}
+
+pub struct FogRenderer {
+    shader: FogShader,
+}
+
+impl FogRenderer {
+    pub fn new() -> Result<Self, RendererError> {
+        Ok(Self {
+            shader: FogShader::new()?,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(in crate) fn render(
+        &mut self,
+        state: &mut PipelineState,
+        gbuffer: &mut GBuffer,
+        quad: &SurfaceSharedData,
+        geometry_cache: &mut GeometryCache,
+        fog: &FogSettings,
+        inv_view_projection: Matrix4<f32>,
+        camera_position: Vector3<f32>,
+    ) -> RenderPassStatistics {
+        scope_profile!();
+
+        let viewport = Rect::new(0, 0, gbuffer.width, gbuffer.height);
+
+        let frame_matrix = Matrix4::new_orthographic(
+            0.0,
+            viewport.w() as f32,
+            viewport.h() as f32,
+            0.0,
+            -1.0,
+            1.0,
+        ) * Matrix4::new_nonuniform_scaling(&Vector3::new(
+            viewport.w() as f32,
+            viewport.h() as f32,
+            0.0,
+        ));
+
+        state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+        let mut stats = RenderPassStatistics::default();
+
+        let shader = &self.shader;
+        shader.fog.set(&shader.program, state, Some(fog));
+        stats += gbuffer.final_frame.draw(
+            geometry_cache.get(state, quad),
+            state,
+            viewport,
+            &shader.program,
+            &DrawParameters {
+                cull_face: CullFace::Back,
+                culling: false,
+                // Keep alpha of the frame untouched.
+                color_write: ColorMask {
+                    red: true,
+                    green: true,
+                    blue: true,
+                    alpha: false,
+                },
+                depth_write: false,
+                stencil_test: false,
+                depth_test: false,
+                blend: true,
+            },
+            &[
+                (shader.wvp_matrix, UniformValue::Matrix4(frame_matrix)),
+                (
+                    shader.depth_sampler,
+                    UniformValue::Sampler {
+                        index: 0,
+                        texture: gbuffer.depth(),
+                    },
+                ),
+                (
+                    shader.inv_view_proj_matrix,
+                    UniformValue::Matrix4(inv_view_projection),
+                ),
+                (
+                    shader.camera_position,
+                    UniformValue::Vector3(camera_position),
+                ),
+            ],
+        );
+
+        stats
+    }
+}
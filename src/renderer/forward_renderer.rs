@@ -0,0 +1,512 @@
+//! Forward rendering of transparent surfaces.
+//!
+//! Deferred shading cannot handle transparency, so surfaces marked as transparent (see
+//! `Surface::set_transparent`) are skipped by G-Buffer pass and drawn on top of lit frame one
+//! instance at a time in order defined by transparent queue. They are alpha-blended, tested
+//! against depth of opaque geometry, but do not write depth themselves.
+//!
+//! Lighting is simplified: each instance is lit by ambient light and by single light source which
+//! gives the most light at the origin of the instance. Shadows are not taken into account.
+//!
+//! Fog of the scene is applied per pixel the same way as for opaque surfaces.
+
+use crate::{
+    core::{
+        algebra::{Matrix4, Vector3},
+        color::Color,
+        math::Rect,
+        scope_profile,
+    },
+    renderer::{
+        batch::{BatchStorage, PbrParameters},
+        error::RendererError,
+        fog::FogUniforms,
+        framework::{
+            framebuffer::{CullFace, DrawParameters, FrameBufferTrait},
+            gl,
+            gpu_program::{GpuProgram, UniformLocation, UniformValue},
+            state::PipelineState,
+        },
+        GeometryCache, RenderPassStatistics,
+    },
+    scene::{camera::Camera, fog::FogSettings, graph::Graph, light::Light, node::Node},
+};
+use std::cmp::Ordering;
+
+struct TransparentShader {
+    program: GpuProgram,
+    world_matrix: UniformLocation,
+    wvp_matrix: UniformLocation,
+    use_skeletal_animation: UniformLocation,
+    use_vertex_colors: UniformLocation,
+    bone_matrices: UniformLocation,
+    diffuse_texture: UniformLocation,
+    normal_texture: UniformLocation,
+    specular_texture: UniformLocation,
+    lightmap_texture: UniformLocation,
+    roughness_texture: UniformLocation,
+    metallic_texture: UniformLocation,
+    emissive_texture: UniformLocation,
+    use_pbr: UniformLocation,
+    metallic_factor: UniformLocation,
+    roughness_factor: UniformLocation,
+    emissive_color: UniformLocation,
+    emissive_intensity: UniformLocation,
+    diffuse_color: UniformLocation,
+    ambient_color: UniformLocation,
+    camera_position: UniformLocation,
+    light_kind: UniformLocation,
+    light_position: UniformLocation,
+    light_direction: UniformLocation,
+    light_radius: UniformLocation,
+    light_color: UniformLocation,
+    half_hotspot_cone_angle_cos: UniformLocation,
+    half_cone_angle_cos: UniformLocation,
+    fog: FogUniforms,
+}
+
+impl TransparentShader {
+    fn new() -> Result<Self, RendererError> {
+        // Vertex shader is the same as for G-Buffer, only shading differs.
+        let vertex_source = include_str!("shaders/gbuffer_vs.glsl");
+        let fragment_source = include_str!("shaders/transparent_fs.glsl");
+        let program = GpuProgram::from_source("TransparentShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            world_matrix: program.uniform_location("worldMatrix")?,
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            use_skeletal_animation: program.uniform_location("useSkeletalAnimation")?,
+            use_vertex_colors: program.uniform_location("useVertexColors")?,
+            bone_matrices: program.uniform_location("boneMatrices")?,
+            diffuse_texture: program.uniform_location("diffuseTexture")?,
+            normal_texture: program.uniform_location("normalTexture")?,
+            specular_texture: program.uniform_location("specularTexture")?,
+            lightmap_texture: program.uniform_location("lightmapTexture")?,
+            roughness_texture: program.uniform_location("roughnessTexture")?,
+            metallic_texture: program.uniform_location("metallicTexture")?,
+            emissive_texture: program.uniform_location("emissiveTexture")?,
+            use_pbr: program.uniform_location("usePbr")?,
+            metallic_factor: program.uniform_location("metallicFactor")?,
+            roughness_factor: program.uniform_location("roughnessFactor")?,
+            emissive_color: program.uniform_location("emissiveColor")?,
+            emissive_intensity: program.uniform_location("emissiveIntensity")?,
+            diffuse_color: program.uniform_location("diffuseColor")?,
+            ambient_color: program.uniform_location("ambientColor")?,
+            camera_position: program.uniform_location("cameraPosition")?,
+            light_kind: program.uniform_location("lightKind")?,
+            light_position: program.uniform_location("lightPosition")?,
+            light_direction: program.uniform_location("lightDirection")?,
+            light_radius: program.uniform_location("lightRadius")?,
+            light_color: program.uniform_location("lightColor")?,
+            half_hotspot_cone_angle_cos: program.uniform_location("halfHotspotConeAngleCos")?,
+            half_cone_angle_cos: program.uniform_location("halfConeAngleCos")?,
+            fog: FogUniforms::new(&program)?,
+            program,
+        })
+    }
+}
+
+/// Kind of light source, must match constants in `transparent_fs.glsl`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum ForwardLightKind {
+    None = 0,
+    Directional = 1,
+    Point = 2,
+    Spot = 3,
+}
+
+/// Light source parameters prepared for forward shading.
+#[derive(Copy, Clone, Debug)]
+struct ForwardLight {
+    kind: ForwardLightKind,
+    position: Vector3<f32>,
+    /// Vector opposite to emission direction, the same as in deferred light renderer.
+    direction: Vector3<f32>,
+    radius: f32,
+    color: Color,
+    half_hotspot_cone_angle_cos: f32,
+    half_cone_angle_cos: f32,
+}
+
+impl Default for ForwardLight {
+    fn default() -> Self {
+        Self {
+            kind: ForwardLightKind::None,
+            position: Default::default(),
+            direction: Vector3::z(),
+            radius: 0.0,
+            color: Color::BLACK,
+            half_hotspot_cone_angle_cos: 0.0,
+            half_cone_angle_cos: 0.0,
+        }
+    }
+}
+
+impl ForwardLight {
+    fn new(light: &Light) -> Self {
+        let scale = light.local_transform().scale();
+        let radius_scale = scale.x.max(scale.y).max(scale.z);
+        let (kind, radius, half_hotspot_cone_angle_cos, half_cone_angle_cos) = match light {
+            Light::Directional(_) => (ForwardLightKind::Directional, f32::MAX, 0.0, 0.0),
+            Light::Point(point) => (
+                ForwardLightKind::Point,
+                point.radius() * radius_scale,
+                0.0,
+                0.0,
+            ),
+            Light::Spot(spot) => (
+                ForwardLightKind::Spot,
+                spot.distance() * radius_scale,
+                (spot.hotspot_cone_angle() * 0.5).cos(),
+                (spot.full_cone_angle() * 0.5).cos(),
+            ),
+        };
+        Self {
+            kind,
+            position: light.global_position(),
+            direction: light
+                .up_vector()
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vector3::z),
+            radius,
+            color: light.color(),
+            half_hotspot_cone_angle_cos,
+            half_cone_angle_cos,
+        }
+    }
+
+    /// Returns approximate amount of light at given point, orientation of surface is ignored.
+    fn brightness_at(&self, point: Vector3<f32>) -> f32 {
+        let color = self.color.as_frgba();
+        let luminance = 0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z;
+        let attenuation = match self.kind {
+            ForwardLightKind::None => 0.0,
+            ForwardLightKind::Directional => 1.0,
+            ForwardLightKind::Point | ForwardLightKind::Spot => {
+                let to_light = self.position - point;
+                let distance = to_light.norm();
+                let k = (1.0 - (distance * distance) / (self.radius * self.radius)).max(0.0);
+                let mut attenuation = k * k;
+                if self.kind == ForwardLightKind::Spot {
+                    let spot_angle_cos = self
+                        .direction
+                        .dot(&to_light.try_normalize(f32::EPSILON).unwrap_or_default());
+                    attenuation *= smoothstep(
+                        self.half_cone_angle_cos,
+                        self.half_hotspot_cone_angle_cos,
+                        spot_angle_cos,
+                    );
+                }
+                attenuation
+            }
+        };
+        luminance * attenuation
+    }
+}
+
+/// Fills `lights` with visible lights of the graph.
+fn collect_lights(graph: &Graph, lights: &mut Vec<ForwardLight>) {
+    lights.clear();
+    for node in graph.linear_iter() {
+        if let Node::Light(light) = node {
+            if light.global_visibility() {
+                lights.push(ForwardLight::new(light));
+            }
+        }
+    }
+}
+
+/// Returns light that gives the most light at given point or dummy light if there is no such.
+fn brightest_light(lights: &[ForwardLight], point: Vector3<f32>) -> ForwardLight {
+    lights
+        .iter()
+        .map(|light| (light, light.brightness_at(point)))
+        .filter(|(_, brightness)| *brightness > 0.0)
+        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+        .map(|(light, _)| *light)
+        .unwrap_or_default()
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+pub struct ForwardRenderer {
+    shader: TransparentShader,
+    lights: Vec<ForwardLight>,
+    bone_matrices: Vec<Matrix4<f32>>,
+}
+
+pub(in crate) struct ForwardRenderContext<'a, 'b, 'c> {
+    pub state: &'a mut PipelineState,
+    pub framebuffer: &'b mut dyn FrameBufferTrait,
+    pub camera: &'c Camera,
+    pub batch_storage: &'c BatchStorage,
+    /// Index of transparent batch and index of its instance to draw, instances are drawn one
+    /// by one in order defined by transparent queue.
+    pub batch: usize,
+    pub instance: usize,
+    pub viewport: Rect<i32>,
+    pub ambient_color: Color,
+    pub fog: Option<&'c FogSettings>,
+    pub geometry_cache: &'a mut GeometryCache,
+}
+
+impl ForwardRenderer {
+    pub fn new() -> Result<Self, RendererError> {
+        Ok(Self {
+            shader: TransparentShader::new()?,
+            lights: Default::default(),
+            bone_matrices: Default::default(),
+        })
+    }
+
+    /// Collects visible lights of the graph, must be called once per frame before rendering.
+    pub fn collect_lights(&mut self, graph: &Graph) {
+        collect_lights(graph, &mut self.lights);
+    }
+
+    #[must_use]
+    pub(in crate) fn render(&mut self, args: ForwardRenderContext) -> RenderPassStatistics {
+        scope_profile!();
+
+        let ForwardRenderContext {
+            state,
+            framebuffer,
+            camera,
+            batch_storage,
+            batch,
+            instance,
+            viewport,
+            ambient_color,
+            fog,
+            geometry_cache,
+        } = args;
+
+        let batch = &batch_storage.batches[batch];
+        let instance = &batch.instances[instance];
+
+        let view_projection = if instance.depth_offset != 0.0 {
+            let mut projection = camera.projection_matrix();
+            projection[14] -= instance.depth_offset;
+            projection * camera.view_matrix()
+        } else {
+            camera.view_projection_matrix()
+        };
+
+        let pbr = batch.pbr.unwrap_or(PbrParameters {
+            metallic: 0.0,
+            roughness: 1.0,
+        });
+
+        let light = brightest_light(&self.lights, instance.world_transform.column(3).xyz());
+
+        self.bone_matrices.clear();
+        self.bone_matrices
+            .extend_from_slice(instance.bone_matrices.as_slice());
+
+        state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+        let data = batch.data.read().unwrap();
+
+        let mut statistics = RenderPassStatistics::default();
+
+        self.shader.fog.set(&self.shader.program, state, fog);
+        statistics += framebuffer.draw(
+            geometry_cache.get(state, &data),
+            state,
+            viewport,
+            &self.shader.program,
+            &DrawParameters {
+                cull_face: CullFace::Back,
+                culling: !batch.two_sided,
+                color_write: Default::default(),
+                depth_write: false,
+                stencil_test: false,
+                depth_test: true,
+                blend: true,
+            },
+            &[
+                (
+                    self.shader.diffuse_texture,
+                    UniformValue::Sampler {
+                        index: 0,
+                        texture: batch.diffuse_texture.clone(),
+                    },
+                ),
+                (
+                    self.shader.normal_texture,
+                    UniformValue::Sampler {
+                        index: 1,
+                        texture: batch.normal_texture.clone(),
+                    },
+                ),
+                (
+                    self.shader.specular_texture,
+                    UniformValue::Sampler {
+                        index: 2,
+                        texture: batch.specular_texture.clone(),
+                    },
+                ),
+                (
+                    self.shader.lightmap_texture,
+                    UniformValue::Sampler {
+                        index: 3,
+                        texture: batch.lightmap_texture.clone(),
+                    },
+                ),
+                (
+                    self.shader.roughness_texture,
+                    UniformValue::Sampler {
+                        index: 4,
+                        texture: batch.roughness_texture.clone(),
+                    },
+                ),
+                (
+                    self.shader.metallic_texture,
+                    UniformValue::Sampler {
+                        index: 5,
+                        texture: batch.metallic_texture.clone(),
+                    },
+                ),
+                (
+                    self.shader.emissive_texture,
+                    UniformValue::Sampler {
+                        index: 6,
+                        texture: batch.emissive_texture.clone(),
+                    },
+                ),
+                (self.shader.use_pbr, UniformValue::Bool(batch.pbr.is_some())),
+                (
+                    self.shader.metallic_factor,
+                    UniformValue::Float(pbr.metallic),
+                ),
+                (
+                    self.shader.roughness_factor,
+                    UniformValue::Float(pbr.roughness),
+                ),
+                (
+                    self.shader.emissive_color,
+                    UniformValue::Color(batch.emissive_color),
+                ),
+                (
+                    self.shader.emissive_intensity,
+                    UniformValue::Float(batch.emissive_intensity),
+                ),
+                (
+                    self.shader.wvp_matrix,
+                    UniformValue::Matrix4(view_projection * instance.world_transform),
+                ),
+                (
+                    self.shader.world_matrix,
+                    UniformValue::Matrix4(instance.world_transform),
+                ),
+                (
+                    self.shader.use_skeletal_animation,
+                    UniformValue::Bool(batch.is_skinned),
+                ),
+                (
+                    self.shader.use_vertex_colors,
+                    UniformValue::Bool(batch.use_vertex_colors),
+                ),
+                (
+                    self.shader.bone_matrices,
+                    UniformValue::Mat4Array(&self.bone_matrices),
+                ),
+                (
+                    self.shader.diffuse_color,
+                    UniformValue::Color(instance.color),
+                ),
+                (
+                    self.shader.ambient_color,
+                    UniformValue::Color(ambient_color),
+                ),
+                (
+                    self.shader.camera_position,
+                    UniformValue::Vector3(camera.global_position()),
+                ),
+                (
+                    self.shader.light_kind,
+                    UniformValue::Integer(light.kind as i32),
+                ),
+                (
+                    self.shader.light_position,
+                    UniformValue::Vector3(light.position),
+                ),
+                (
+                    self.shader.light_direction,
+                    UniformValue::Vector3(light.direction),
+                ),
+                (self.shader.light_radius, UniformValue::Float(light.radius)),
+                (self.shader.light_color, UniformValue::Color(light.color)),
+                (
+                    self.shader.half_hotspot_cone_angle_cos,
+                    UniformValue::Float(light.half_hotspot_cone_angle_cos),
+                ),
+                (
+                    self.shader.half_cone_angle_cos,
+                    UniformValue::Float(light.half_cone_angle_cos),
+                ),
+            ],
+        );
+
+        statistics
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{algebra::Vector3, color::Color},
+        renderer::forward_renderer::{brightest_light, collect_lights, ForwardLightKind},
+        scene::{
+            base::BaseBuilder,
+            graph::Graph,
+            light::{BaseLightBuilder, DirectionalLightBuilder, PointLightBuilder},
+            transform::TransformBuilder,
+        },
+    };
+
+    #[test]
+    fn test_brightest_light() {
+        let mut graph = Graph::new();
+        DirectionalLightBuilder::new(
+            BaseLightBuilder::new(BaseBuilder::new()).with_color(Color::opaque(60, 60, 60)),
+        )
+        .build(&mut graph);
+        PointLightBuilder::new(BaseLightBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(Vector3::new(10.0, 0.0, 0.0))
+                    .build(),
+            ),
+        ))
+        .with_radius(5.0)
+        .build(&mut graph);
+        graph.update_hierarchical_data();
+
+        let mut lights = Vec::new();
+        collect_lights(&graph, &mut lights);
+
+        // Close to the lamp it outshines the sun.
+        assert_eq!(
+            brightest_light(&lights, Vector3::new(10.5, 0.0, 0.0)).kind,
+            ForwardLightKind::Point
+        );
+        // Lamp does not reach this point at all.
+        assert_eq!(
+            brightest_light(&lights, Vector3::new(0.0, 0.0, 0.0)).kind,
+            ForwardLightKind::Directional
+        );
+        // Degenerate point must not break the search.
+        assert_eq!(
+            brightest_light(&lights, Vector3::new(f32::NAN, 0.0, 0.0)).kind,
+            ForwardLightKind::Directional
+        );
+        // No lights - no lighting except ambient.
+        assert_eq!(
+            brightest_light(&[], Vector3::default()).kind,
+            ForwardLightKind::None
+        );
+    }
+}
@@ -0,0 +1,773 @@
+//! Custom render passes.
+//!
+//! Renderer has fixed set of built-in passes (geometry, lighting, forward geometry, tone
+//! mapping), but custom passes can be inserted between forward geometry pass and tone mapping
+//! to implement effects like outlines or custom post-effects without changes in the renderer.
+//!
+//! Custom pass is a full-screen pass with a fragment shader, it declares which textures it
+//! reads (see `FrameResource`) and which texture it writes. Every pass can write either the
+//! frame itself (HDR image before tone mapping) or a named texture, which can be read by other
+//! passes. Renderer orders passes using these declarations: a pass which produces a named
+//! texture runs before every pass that reads it, passes which read or write the frame keep
+//! order in which they were added. Graph is validated when a pass is added, see
+//! `FrameGraphError` for possible problems.
+//!
+//! Passes run for each camera of each scene, only in viewport of a camera. Named textures have
+//! the same size as the frame of a scene and are stored in RGBA16F format.
+//!
+//! # Shaders
+//!
+//! Fragment shader must be written in GLSL 3.3 and must have `in vec2 texCoord` input with
+//! texture coordinates of the frame, every input texture is bound to `sampler2D` uniform with
+//! name given in `CustomRenderPassBuilder::with_input`. Texture sizes can be fetched using
+//! `textureSize`. Uniforms that are not used by a shader are silently ignored.
+//!
+//! # Example
+//!
+//! Edge detection pass that darkens pixels on depth discontinuities.
+//!
+//! ```no_run
+//! use rg3d::renderer::{
+//!     frame_graph::{CustomRenderPassBuilder, FrameResource, PassUniform},
+//!     Renderer,
+//! };
+//!
+//! fn add_outline(renderer: &mut Renderer) {
+//!     let shader = r#"
+//!         #version 330 core
+//!
+//!         uniform sampler2D frameTexture;
+//!         uniform sampler2D depthTexture;
+//!         uniform float strength;
+//!
+//!         in vec2 texCoord;
+//!         out vec4 FragColor;
+//!
+//!         void main() {
+//!             vec2 texel = 1.0 / vec2(textureSize(depthTexture, 0));
+//!             float depth = texture(depthTexture, texCoord).r;
+//!             float edge = abs(texture(depthTexture, texCoord + vec2(texel.x, 0.0)).r - depth) +
+//!                 abs(texture(depthTexture, texCoord + vec2(0.0, texel.y)).r - depth);
+//!             vec4 color = texture(frameTexture, texCoord);
+//!             FragColor = vec4(color.rgb * (1.0 - clamp(edge * strength, 0.0, 1.0)), color.a);
+//!         }
+//!     "#;
+//!
+//!     let pass = CustomRenderPassBuilder::new("Outline", shader)
+//!         .with_input("frameTexture", FrameResource::Frame)
+//!         .with_input("depthTexture", FrameResource::Depth)
+//!         .with_output(FrameResource::Frame)
+//!         .with_uniform("strength", PassUniform::Float(500.0))
+//!         .build();
+//!
+//!     renderer.add_render_pass(pass).unwrap();
+//! }
+//! ```
+
+use crate::{
+    core::{
+        algebra::{Matrix4, Vector2, Vector3, Vector4},
+        color::Color,
+        math::Rect,
+        scope_profile,
+    },
+    renderer::{
+        error::RendererError,
+        framework::{
+            framebuffer::{
+                Attachment, AttachmentKind, CullFace, DrawParameters, FrameBuffer, FrameBufferTrait,
+            },
+            gpu_program::{GpuProgram, UniformValue},
+            gpu_texture::{
+                Coordinate, GpuTexture, GpuTextureKind, MagnificationFilter, MinificationFilter,
+                PixelKind, WrapMode,
+            },
+            state::PipelineState,
+        },
+        gbuffer::GBuffer,
+        surface::SurfaceSharedData,
+        GeometryCache, RenderPassStatistics,
+    },
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    rc::Rc,
+};
+
+/// Texture which can be read or written by a custom pass.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FrameResource {
+    /// Lit HDR frame before tone mapping, can be read and written.
+    Frame,
+    /// Depth buffer of the frame, read-only. Depth is stored in red channel in [0; 1] range.
+    Depth,
+    /// World-space normals packed into [0; 1] range, read-only.
+    Normal,
+    /// Albedo of surfaces, read-only.
+    Diffuse,
+    /// Texture produced by a custom pass.
+    Named(String),
+}
+
+impl Display for FrameResource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameResource::Named(name) => write!(f, "\"{}\"", name),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+/// Value of a uniform of custom pass shader.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PassUniform {
+    /// `float` uniform.
+    Float(f32),
+    /// `vec2` uniform.
+    Vector2(Vector2<f32>),
+    /// `vec3` uniform.
+    Vector3(Vector3<f32>),
+    /// `vec4` uniform.
+    Vector4(Vector4<f32>),
+    /// `vec4` uniform, color components are mapped to [0; 1] range.
+    Color(Color),
+}
+
+/// Custom full-screen render pass, see module docs.
+#[derive(Clone, Debug)]
+pub struct CustomRenderPass {
+    name: String,
+    fragment_shader: String,
+    inputs: Vec<(String, FrameResource)>,
+    output: FrameResource,
+    uniforms: Vec<(String, PassUniform)>,
+    enabled: bool,
+}
+
+impl CustomRenderPass {
+    /// Returns name of the pass.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns list of inputs as pairs of sampler name and resource.
+    pub fn inputs(&self) -> &[(String, FrameResource)] {
+        &self.inputs
+    }
+
+    /// Returns texture which is written by the pass.
+    pub fn output(&self) -> &FrameResource {
+        &self.output
+    }
+
+    /// Sets new value of a uniform, uniform will be added if there is no such uniform yet.
+    pub fn set_uniform<S: AsRef<str>>(&mut self, name: S, value: PassUniform) {
+        let name = name.as_ref();
+        match self.uniforms.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value,
+            None => self.uniforms.push((name.to_owned(), value)),
+        }
+    }
+
+    /// Returns value of a uniform.
+    pub fn uniform(&self, name: &str) -> Option<PassUniform> {
+        self.uniforms
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| *v)
+    }
+
+    /// Enables or disables the pass. Disabled pass is skipped, but still takes part in
+    /// scheduling, named texture of disabled pass keeps its last contents.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Returns true if the pass is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// Custom render pass builder.
+pub struct CustomRenderPassBuilder {
+    name: String,
+    fragment_shader: String,
+    inputs: Vec<(String, FrameResource)>,
+    output: FrameResource,
+    uniforms: Vec<(String, PassUniform)>,
+    enabled: bool,
+}
+
+impl CustomRenderPassBuilder {
+    /// Creates new builder of a pass with given unique name and fragment shader source, see
+    /// module docs for shader requirements. By default the pass writes the frame.
+    pub fn new<N: AsRef<str>, S: AsRef<str>>(name: N, fragment_shader: S) -> Self {
+        Self {
+            name: name.as_ref().to_owned(),
+            fragment_shader: fragment_shader.as_ref().to_owned(),
+            inputs: Default::default(),
+            output: FrameResource::Frame,
+            uniforms: Default::default(),
+            enabled: true,
+        }
+    }
+
+    /// Binds resource to a `sampler2D` uniform of the shader.
+    pub fn with_input<S: AsRef<str>>(mut self, sampler: S, resource: FrameResource) -> Self {
+        self.inputs.push((sampler.as_ref().to_owned(), resource));
+        self
+    }
+
+    /// Sets texture which will be written by the pass, it must be either `FrameResource::Frame`
+    /// or `FrameResource::Named`.
+    pub fn with_output(mut self, output: FrameResource) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Sets initial value of a uniform.
+    pub fn with_uniform<S: AsRef<str>>(mut self, name: S, value: PassUniform) -> Self {
+        self.uniforms.push((name.as_ref().to_owned(), value));
+        self
+    }
+
+    /// Sets whether the pass is enabled or not.
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Creates new pass, it can be added to renderer by `Renderer::add_render_pass`.
+    pub fn build(self) -> CustomRenderPass {
+        CustomRenderPass {
+            name: self.name,
+            fragment_shader: self.fragment_shader,
+            inputs: self.inputs,
+            output: self.output,
+            uniforms: self.uniforms,
+            enabled: self.enabled,
+        }
+    }
+}
+
+/// Problems of custom passes graph.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameGraphError {
+    /// There is already a pass with such name.
+    DuplicatePassName(String),
+    /// There is no pass with such name.
+    UnknownPass(String),
+    /// Named texture is written by more than one pass.
+    DuplicateOutput {
+        /// Name of the texture.
+        resource: String,
+        /// Names of passes which write the texture.
+        passes: (String, String),
+    },
+    /// Pass reads named texture which is not written by any pass.
+    MissingInput {
+        /// Name of the pass.
+        pass: String,
+        /// Name of the texture.
+        resource: String,
+    },
+    /// Pass tries to write read-only resource.
+    ReadOnlyOutput {
+        /// Name of the pass.
+        pass: String,
+        /// Resource which was declared as output.
+        resource: FrameResource,
+    },
+    /// Passes depend on each other, names of passes which form the cycle are stored inside.
+    Cycle(Vec<String>),
+}
+
+impl Display for FrameGraphError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameGraphError::DuplicatePassName(name) => {
+                write!(f, "render pass {} already exists", name)
+            }
+            FrameGraphError::UnknownPass(name) => write!(f, "there is no render pass {}", name),
+            FrameGraphError::DuplicateOutput { resource, passes } => write!(
+                f,
+                "texture \"{}\" is written by both {} and {} passes",
+                resource, passes.0, passes.1
+            ),
+            FrameGraphError::MissingInput { pass, resource } => write!(
+                f,
+                "texture \"{}\" read by {} pass is not written by any pass",
+                resource, pass
+            ),
+            FrameGraphError::ReadOnlyOutput { pass, resource } => {
+                write!(f, "{} pass cannot write read-only {}", pass, resource)
+            }
+            FrameGraphError::Cycle(passes) => {
+                write!(f, "render passes depend on each other: {:?}", passes)
+            }
+        }
+    }
+}
+
+/// Returns order of passes in which every pass runs after its dependencies, see module docs.
+fn schedule(passes: &[&CustomRenderPass]) -> Result<Vec<usize>, FrameGraphError> {
+    let mut dependencies = vec![Vec::new(); passes.len()];
+
+    let mut producers = HashMap::new();
+    for (i, pass) in passes.iter().enumerate() {
+        match &pass.output {
+            FrameResource::Frame => (),
+            FrameResource::Named(resource) => {
+                if let Some(other) = producers.insert(resource.as_str(), i) {
+                    return Err(FrameGraphError::DuplicateOutput {
+                        resource: resource.clone(),
+                        passes: (passes[other].name.clone(), pass.name.clone()),
+                    });
+                }
+            }
+            resource => {
+                return Err(FrameGraphError::ReadOnlyOutput {
+                    pass: pass.name.clone(),
+                    resource: resource.clone(),
+                })
+            }
+        }
+    }
+
+    // Frame is modified in place, so every reader must see result of last writer added before
+    // it, and every writer must wait for readers of previous version.
+    let mut last_frame_writer = None;
+    let mut frame_readers = Vec::new();
+    for (i, pass) in passes.iter().enumerate() {
+        for (_, input) in pass.inputs.iter() {
+            match input {
+                FrameResource::Named(resource) => match producers.get(resource.as_str()) {
+                    Some(&producer) => dependencies[i].push(producer),
+                    None => {
+                        return Err(FrameGraphError::MissingInput {
+                            pass: pass.name.clone(),
+                            resource: resource.clone(),
+                        })
+                    }
+                },
+                FrameResource::Frame => {
+                    dependencies[i].extend(last_frame_writer);
+                    frame_readers.push(i);
+                }
+                _ => (),
+            }
+        }
+        if pass.output == FrameResource::Frame {
+            dependencies[i].extend(last_frame_writer);
+            dependencies[i].extend(frame_readers.drain(..).filter(|&reader| reader != i));
+            last_frame_writer = Some(i);
+        }
+    }
+
+    // Topological sort, among passes which are ready the one which was added first is taken.
+    let mut order = Vec::with_capacity(passes.len());
+    let mut scheduled = vec![false; passes.len()];
+    while order.len() < passes.len() {
+        let ready = (0..passes.len()).find(|&i| {
+            !scheduled[i]
+                && dependencies[i]
+                    .iter()
+                    .all(|&dependency| scheduled[dependency])
+        });
+        match ready {
+            Some(i) => {
+                scheduled[i] = true;
+                order.push(i);
+            }
+            None => {
+                return Err(FrameGraphError::Cycle(
+                    (0..passes.len())
+                        .filter(|&i| !scheduled[i])
+                        .map(|i| passes[i].name.clone())
+                        .collect(),
+                ))
+            }
+        }
+    }
+
+    Ok(order)
+}
+
+struct CompiledPass {
+    pass: CustomRenderPass,
+    program: GpuProgram,
+}
+
+fn compile(name: &str, fragment_shader: &str) -> Result<GpuProgram, RendererError> {
+    GpuProgram::from_source(
+        name,
+        include_str!("shaders/frame_graph_vs.glsl"),
+        fragment_shader.trim_start(),
+    )
+}
+
+fn create_target(
+    state: &mut PipelineState,
+    width: usize,
+    height: usize,
+) -> Result<FrameBuffer, RendererError> {
+    let mut texture = GpuTexture::new(
+        state,
+        GpuTextureKind::Rectangle { width, height },
+        PixelKind::RGBA16F,
+        MinificationFilter::Linear,
+        MagnificationFilter::Linear,
+        1,
+        None,
+    )?;
+    texture
+        .bind_mut(state, 0)
+        .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+        .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
+
+    FrameBuffer::new(
+        state,
+        None,
+        vec![Attachment {
+            kind: AttachmentKind::Color,
+            texture: Rc::new(RefCell::new(texture)),
+        }],
+    )
+}
+
+fn target_texture(target: &FrameBuffer) -> Rc<RefCell<GpuTexture>> {
+    target.color_attachments()[0].texture.clone()
+}
+
+fn target_size(target: &FrameBuffer) -> (usize, usize) {
+    match target.color_attachments()[0].texture.borrow().kind() {
+        GpuTextureKind::Rectangle { width, height } => (width, height),
+        _ => (0, 0),
+    }
+}
+
+/// Custom passes with their shaders and textures.
+pub(in crate) struct FrameGraph {
+    passes: Vec<CompiledPass>,
+    order: Vec<usize>,
+    // Named textures and temporary frame copy, they are created on demand.
+    targets: HashMap<String, FrameBuffer>,
+    scratch: Option<FrameBuffer>,
+    copy_program: GpuProgram,
+}
+
+impl FrameGraph {
+    pub fn new() -> Result<Self, RendererError> {
+        Ok(Self {
+            passes: Default::default(),
+            order: Default::default(),
+            targets: Default::default(),
+            scratch: None,
+            copy_program: compile(
+                "FrameGraphCopyShader",
+                include_str!("shaders/frame_graph_copy_fs.glsl"),
+            )?,
+        })
+    }
+
+    fn reschedule(&self, passes: &[&CustomRenderPass]) -> Result<Vec<usize>, RendererError> {
+        schedule(passes).map_err(RendererError::FrameGraph)
+    }
+
+    pub fn add(&mut self, pass: CustomRenderPass) -> Result<(), RendererError> {
+        if self.passes.iter().any(|p| p.pass.name == pass.name) {
+            return Err(RendererError::FrameGraph(
+                FrameGraphError::DuplicatePassName(pass.name),
+            ));
+        }
+
+        let order = {
+            let mut passes = self.passes.iter().map(|p| &p.pass).collect::<Vec<_>>();
+            passes.push(&pass);
+            self.reschedule(&passes)?
+        };
+
+        let program = compile(&pass.name, &pass.fragment_shader)?;
+        self.passes.push(CompiledPass { pass, program });
+        self.order = order;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<CustomRenderPass, RendererError> {
+        let index = self
+            .passes
+            .iter()
+            .position(|p| p.pass.name == name)
+            .ok_or_else(|| {
+                RendererError::FrameGraph(FrameGraphError::UnknownPass(name.to_owned()))
+            })?;
+
+        let order = {
+            let mut passes = self.passes.iter().map(|p| &p.pass).collect::<Vec<_>>();
+            passes.remove(index);
+            self.reschedule(&passes)?
+        };
+
+        let removed = self.passes.remove(index);
+        if let FrameResource::Named(resource) = &removed.pass.output {
+            self.targets.remove(resource);
+        }
+        self.order = order;
+        Ok(removed.pass)
+    }
+
+    pub fn pass_mut(&mut self, name: &str) -> Option<&mut CustomRenderPass> {
+        self.passes
+            .iter_mut()
+            .find(|p| p.pass.name == name)
+            .map(|p| &mut p.pass)
+    }
+
+    /// Returns passes in order of execution.
+    pub fn passes(&self) -> impl Iterator<Item = &CustomRenderPass> {
+        self.order.iter().map(move |&i| &self.passes[i].pass)
+    }
+
+    fn target<'a>(
+        targets: &'a mut HashMap<String, FrameBuffer>,
+        state: &mut PipelineState,
+        name: &str,
+        width: usize,
+        height: usize,
+    ) -> Result<&'a mut FrameBuffer, RendererError> {
+        if targets
+            .get(name)
+            .map_or(true, |target| target_size(target) != (width, height))
+        {
+            targets.insert(name.to_owned(), create_target(state, width, height)?);
+        }
+        Ok(targets.get_mut(name).unwrap())
+    }
+
+    /// Runs every enabled pass for a camera with given viewport, must be called after forward
+    /// geometry was rendered and multisampled frame was resolved.
+    pub fn render(
+        &mut self,
+        state: &mut PipelineState,
+        gbuffer: &mut GBuffer,
+        viewport: Rect<i32>,
+        quad: &SurfaceSharedData,
+        geometry_cache: &mut GeometryCache,
+    ) -> Result<RenderPassStatistics, RendererError> {
+        scope_profile!();
+
+        let mut statistics = RenderPassStatistics::default();
+
+        if self.passes.iter().all(|p| !p.pass.enabled) {
+            return Ok(statistics);
+        }
+
+        let width = gbuffer.width.max(1) as usize;
+        let height = gbuffer.height.max(1) as usize;
+
+        let wvp = Matrix4::new_orthographic(
+            0.0,
+            viewport.w() as f32,
+            viewport.h() as f32,
+            0.0,
+            -1.0,
+            1.0,
+        ) * Matrix4::new_nonuniform_scaling(&Vector3::new(
+            viewport.w() as f32,
+            viewport.h() as f32,
+            0.0,
+        ));
+        let viewport_rect = Vector4::new(
+            viewport.x() as f32 / width as f32,
+            viewport.y() as f32 / height as f32,
+            viewport.w() as f32 / width as f32,
+            viewport.h() as f32 / height as f32,
+        );
+        let params = DrawParameters {
+            cull_face: CullFace::Back,
+            culling: false,
+            color_write: Default::default(),
+            depth_write: false,
+            stencil_test: false,
+            depth_test: false,
+            blend: false,
+        };
+
+        for &index in self.order.iter() {
+            let CompiledPass { pass, program } = &self.passes[index];
+            if !pass.enabled {
+                continue;
+            }
+
+            let mut uniforms = Vec::new();
+            for (sampler_index, (sampler, input)) in pass.inputs.iter().enumerate() {
+                let texture = match input {
+                    FrameResource::Frame => gbuffer.frame_texture(),
+                    FrameResource::Depth => gbuffer.depth(),
+                    FrameResource::Normal => gbuffer.normal_texture(),
+                    FrameResource::Diffuse => gbuffer.diffuse_texture(),
+                    FrameResource::Named(name) => {
+                        target_texture(Self::target(&mut self.targets, state, name, width, height)?)
+                    }
+                };
+                if let Ok(location) = program.uniform_location(sampler) {
+                    uniforms.push((
+                        location,
+                        UniformValue::Sampler {
+                            index: sampler_index,
+                            texture,
+                        },
+                    ));
+                }
+            }
+            for (name, value) in pass.uniforms.iter() {
+                if let Ok(location) = program.uniform_location(name) {
+                    uniforms.push((
+                        location,
+                        match *value {
+                            PassUniform::Float(v) => UniformValue::Float(v),
+                            PassUniform::Vector2(v) => UniformValue::Vector2(v),
+                            PassUniform::Vector3(v) => UniformValue::Vector3(v),
+                            PassUniform::Vector4(v) => UniformValue::Vector4(v),
+                            PassUniform::Color(v) => UniformValue::Color(v),
+                        },
+                    ));
+                }
+            }
+            if let Ok(location) = program.uniform_location("worldViewProjection") {
+                uniforms.push((location, UniformValue::Matrix4(wvp)));
+            }
+            if let Ok(location) = program.uniform_location("viewportRect") {
+                uniforms.push((location, UniformValue::Vector4(viewport_rect)));
+            }
+
+            // Frame cannot be read and written at the same time, so result is rendered into
+            // temporary texture first and then copied into the frame.
+            let target = match &pass.output {
+                FrameResource::Named(name) => {
+                    Self::target(&mut self.targets, state, name, width, height)?
+                }
+                _ => {
+                    if self
+                        .scratch
+                        .as_ref()
+                        .map_or(true, |scratch| target_size(scratch) != (width, height))
+                    {
+                        self.scratch = Some(create_target(state, width, height)?);
+                    }
+                    self.scratch.as_mut().unwrap()
+                }
+            };
+
+            statistics += target.draw(
+                geometry_cache.get(state, quad),
+                state,
+                viewport,
+                program,
+                &params,
+                &uniforms,
+            );
+
+            if pass.output == FrameResource::Frame {
+                let source = target_texture(self.scratch.as_ref().unwrap());
+                let copy_uniforms = [
+                    (
+                        self.copy_program.uniform_location("worldViewProjection")?,
+                        UniformValue::Matrix4(wvp),
+                    ),
+                    (
+                        self.copy_program.uniform_location("viewportRect")?,
+                        UniformValue::Vector4(viewport_rect),
+                    ),
+                    (
+                        self.copy_program.uniform_location("sourceTexture")?,
+                        UniformValue::Sampler {
+                            index: 0,
+                            texture: source,
+                        },
+                    ),
+                ];
+                statistics += gbuffer.final_frame.draw(
+                    geometry_cache.get(state, quad),
+                    state,
+                    viewport,
+                    &self.copy_program,
+                    &params,
+                    &copy_uniforms,
+                );
+            }
+        }
+
+        Ok(statistics)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::renderer::frame_graph::{
+        schedule, CustomRenderPass, CustomRenderPassBuilder, FrameGraphError, FrameResource,
+    };
+
+    fn pass(name: &str, inputs: &[FrameResource], output: FrameResource) -> CustomRenderPass {
+        let mut builder = CustomRenderPassBuilder::new(name, "").with_output(output);
+        for (i, input) in inputs.iter().enumerate() {
+            builder = builder.with_input(format!("input{}", i), input.clone());
+        }
+        builder.build()
+    }
+
+    fn named(name: &str) -> FrameResource {
+        FrameResource::Named(name.to_owned())
+    }
+
+    #[test]
+    fn test_frame_graph_schedule() {
+        let passes = [
+            pass(
+                "Composite",
+                &[FrameResource::Frame, named("Mask")],
+                FrameResource::Frame,
+            ),
+            pass("Mask", &[FrameResource::Depth], named("Mask")),
+            pass("Grade", &[FrameResource::Frame], FrameResource::Frame),
+            pass("Luminance", &[FrameResource::Frame], named("Luminance")),
+        ];
+        let passes = passes.iter().collect::<Vec<_>>();
+        // Mask must be ready before composite, luminance must see graded frame.
+        assert_eq!(schedule(&passes), Ok(vec![1, 0, 2, 3]));
+    }
+
+    #[test]
+    fn test_frame_graph_errors() {
+        let a = pass("A", &[named("B")], named("A"));
+        let b = pass("B", &[named("A")], named("B"));
+        assert_eq!(
+            schedule(&[&a, &b]),
+            Err(FrameGraphError::Cycle(vec!["A".to_owned(), "B".to_owned()]))
+        );
+
+        let c = pass("C", &[named("Unknown")], FrameResource::Frame);
+        assert_eq!(
+            schedule(&[&c]),
+            Err(FrameGraphError::MissingInput {
+                pass: "C".to_owned(),
+                resource: "Unknown".to_owned()
+            })
+        );
+
+        let d = pass("D", &[], FrameResource::Depth);
+        assert!(matches!(
+            schedule(&[&d]),
+            Err(FrameGraphError::ReadOnlyOutput { .. })
+        ));
+
+        let e = pass("E", &[], named("A"));
+        assert!(matches!(
+            schedule(&[&a, &e]),
+            Err(FrameGraphError::DuplicateOutput { .. })
+        ));
+    }
+}
@@ -7,11 +7,12 @@ use crate::{
             gl::{self, types::GLuint},
             gpu_program::{GpuProgram, UniformLocation, UniformValue},
             gpu_texture::{CubeMapFace, GpuTexture, GpuTextureKind},
+            readback::PixelReadback,
             state::{ColorMask, PipelineState},
         },
     },
 };
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, ptr, rc::Rc};
 
 #[derive(Copy, Clone, PartialOrd, PartialEq, Hash, Debug)]
 pub enum AttachmentKind {
@@ -172,6 +173,45 @@ impl FrameBuffer {
         self.depth_attachment.as_ref()
     }
 
+    /// Clears color attachment with unsigned integer pixel format (`PixelKind::R32UI`),
+    /// `clear` cannot be used for such attachments.
+    pub fn clear_u32(&mut self, state: &mut PipelineState, attachment_index: usize, value: u32) {
+        state.set_framebuffer(self.fbo);
+        state.set_color_write(ColorMask::default());
+
+        let values = [value, 0, 0, 0];
+        unsafe {
+            gl::ClearBufferuiv(gl::COLOR, attachment_index as i32, values.as_ptr());
+        }
+    }
+
+    /// Starts reading of single pixel of color attachment with unsigned integer pixel format
+    /// into given readback buffer. Reading does not wait for GPU, value can be fetched by
+    /// `PixelReadback::try_read` when it is ready, usually in a frame or two.
+    pub fn read_pixel_u32(
+        &self,
+        state: &mut PipelineState,
+        attachment_index: usize,
+        x: i32,
+        y: i32,
+        readback: &mut PixelReadback,
+    ) {
+        state.set_framebuffer(self.fbo);
+
+        readback.begin_read(|| unsafe {
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0 + attachment_index as u32);
+            gl::ReadPixels(
+                x,
+                y,
+                1,
+                1,
+                gl::RED_INTEGER,
+                gl::UNSIGNED_INT,
+                ptr::null_mut(),
+            );
+        });
+    }
+
     pub fn set_cubemap_face(
         &mut self,
         state: &mut PipelineState,
@@ -332,3 +372,126 @@ impl Drop for FrameBuffer {
         }
     }
 }
+
+/// Returns maximum amount of samples per pixel for multisample frame buffers supported by
+/// current OpenGL context.
+pub fn max_samples() -> u32 {
+    let mut max_samples = 0;
+    unsafe {
+        gl::GetIntegerv(gl::MAX_SAMPLES, &mut max_samples);
+    }
+    max_samples.max(0) as u32
+}
+
+/// Frame buffer with multisampled HDR color and depth-stencil render buffers. Its contents
+/// cannot be sampled in shaders, it must be resolved into ordinary frame buffer first.
+pub struct MultisampleFrameBuffer {
+    fbo: GLuint,
+    color: GLuint,
+    depth_stencil: GLuint,
+    width: i32,
+    height: i32,
+}
+
+impl MultisampleFrameBuffer {
+    pub fn new(
+        state: &mut PipelineState,
+        width: usize,
+        height: usize,
+        samples: u32,
+    ) -> Result<Self, RendererError> {
+        unsafe {
+            let mut framebuffer = Self {
+                fbo: 0,
+                color: 0,
+                depth_stencil: 0,
+                width: width as i32,
+                height: height as i32,
+            };
+
+            gl::GenFramebuffers(1, &mut framebuffer.fbo);
+            gl::GenRenderbuffers(1, &mut framebuffer.color);
+            gl::GenRenderbuffers(1, &mut framebuffer.depth_stencil);
+
+            state.set_framebuffer(framebuffer.fbo);
+
+            for &(renderbuffer, format, attachment) in &[
+                (framebuffer.color, gl::RGBA16F, gl::COLOR_ATTACHMENT0),
+                (
+                    framebuffer.depth_stencil,
+                    gl::DEPTH24_STENCIL8,
+                    gl::DEPTH_STENCIL_ATTACHMENT,
+                ),
+            ] {
+                gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
+                gl::RenderbufferStorageMultisample(
+                    gl::RENDERBUFFER,
+                    samples as i32,
+                    format,
+                    framebuffer.width,
+                    framebuffer.height,
+                );
+                gl::FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    attachment,
+                    gl::RENDERBUFFER,
+                    renderbuffer,
+                );
+            }
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+
+            state.set_framebuffer(0);
+
+            // Render buffers will be released by drop.
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(RendererError::FailedToConstructFBO);
+            }
+
+            Ok(framebuffer)
+        }
+    }
+
+    /// Averages samples of every pixel and writes result into first color attachment of
+    /// given frame buffer, it must have same size as this frame buffer.
+    pub fn resolve(&self, state: &mut PipelineState, dest: &FrameBuffer) {
+        scope_profile!();
+
+        state.set_framebuffer(self.fbo);
+
+        unsafe {
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, dest.fbo);
+            gl::BlitFramebuffer(
+                0,
+                0,
+                self.width,
+                self.height,
+                0,
+                0,
+                self.width,
+                self.height,
+                gl::COLOR_BUFFER_BIT,
+                gl::NEAREST,
+            );
+            // Restore binding to keep it in sync with pipeline state.
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.fbo);
+        }
+    }
+}
+
+impl FrameBufferTrait for MultisampleFrameBuffer {
+    fn id(&self) -> u32 {
+        self.fbo
+    }
+}
+
+impl Drop for MultisampleFrameBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteRenderbuffers(1, &self.color);
+            gl::DeleteRenderbuffers(1, &self.depth_stencil);
+        }
+    }
+}
@@ -9,8 +9,8 @@ use crate::{
         framework::{gl, gl::types::GLuint, state::PipelineState},
     },
     resource::texture::{
-        TextureKind, TextureMagnificationFilter, TextureMinificationFilter, TexturePixelKind,
-        TextureWrapMode,
+        TextureColorSpace, TextureData, TextureKind, TextureMagnificationFilter,
+        TextureMinificationFilter, TexturePixelKind, TextureWrapMode,
     },
     utils::log::Log,
 };
@@ -96,6 +96,18 @@ pub enum PixelKind {
     DXT3RGBA,
     DXT5RGBA,
     RGBA32F,
+    RGBA16F,
+    SRGB8,
+    SRGBA8,
+    SBGR8,
+    SBGRA8,
+    DXT1SRGB,
+    DXT1SRGBA,
+    DXT3SRGBA,
+    DXT5SRGBA,
+    /// Unsigned integer, can be used only as a render target, see
+    /// `FrameBuffer::read_pixel_u32`.
+    R32UI,
 }
 
 impl From<TexturePixelKind> for PixelKind {
@@ -120,27 +132,114 @@ impl From<TexturePixelKind> for PixelKind {
 }
 
 impl PixelKind {
+    /// Returns sRGB variant of the pixel kind, GPU will convert such pixels to linear space
+    /// on sampling. There are no sRGB variants for formats other than 8-bit per channel color
+    /// formats, so they're returned as is.
+    pub fn srgb(self) -> Self {
+        match self {
+            Self::RGB8 => Self::SRGB8,
+            Self::RGBA8 => Self::SRGBA8,
+            Self::BGR8 => Self::SBGR8,
+            Self::BGRA8 => Self::SBGRA8,
+            Self::DXT1RGB => Self::DXT1SRGB,
+            Self::DXT1RGBA => Self::DXT1SRGBA,
+            Self::DXT3RGBA => Self::DXT3SRGBA,
+            Self::DXT5RGBA => Self::DXT5SRGBA,
+            _ => self,
+        }
+    }
+
+    /// Returns pixel kind which must be used to upload given texture to GPU. Color textures
+    /// get sRGB formats so lighting works with linear colors, data textures are left as is.
+    pub fn from_texture(texture: &TextureData) -> Self {
+        let pixel_kind = Self::from(texture.pixel_kind);
+        match texture.color_space() {
+            TextureColorSpace::Srgb => pixel_kind.srgb(),
+            TextureColorSpace::Linear => pixel_kind,
+        }
+    }
+
     fn unpack_alignment(self) -> i32 {
         match self {
-            Self::RGBA16 | Self::RGB16 | Self::RGBA32F => 8,
+            Self::RGBA16 | Self::RGB16 | Self::RGBA32F | Self::RGBA16F => 8,
             Self::RGBA8
             | Self::RGB8
             | Self::BGRA8
             | Self::BGR8
+            | Self::SRGBA8
+            | Self::SRGB8
+            | Self::SBGRA8
+            | Self::SBGR8
             | Self::RG16
             | Self::R16
             | Self::D24S8
             | Self::D32
-            | Self::F32 => 4,
+            | Self::F32
+            | Self::R32UI => 4,
             Self::RG8 | Self::D16 | Self::F16 => 2,
             Self::R8 => 1,
-            Self::DXT1RGB | Self::DXT1RGBA | Self::DXT3RGBA | Self::DXT5RGBA => unreachable!(),
+            Self::DXT1RGB
+            | Self::DXT1RGBA
+            | Self::DXT3RGBA
+            | Self::DXT5RGBA
+            | Self::DXT1SRGB
+            | Self::DXT1SRGBA
+            | Self::DXT3SRGBA
+            | Self::DXT5SRGBA => unreachable!(),
+        }
+    }
+
+    /// Returns (type, format, internal format) triple of OpenGL constants for the pixel kind.
+    /// Type and format are zero for compressed formats.
+    fn gl_formats(self) -> (GLuint, GLuint, GLuint) {
+        match self {
+            Self::F32 => (gl::FLOAT, gl::RED, gl::R32F),
+            Self::R32UI => (gl::UNSIGNED_INT, gl::RED_INTEGER, gl::R32UI),
+            Self::F16 => (gl::FLOAT, gl::RED, gl::R16F),
+            Self::D32 => (gl::FLOAT, gl::DEPTH_COMPONENT, gl::DEPTH_COMPONENT32),
+            Self::D16 => (gl::FLOAT, gl::DEPTH_COMPONENT, gl::DEPTH_COMPONENT16),
+            Self::D24S8 => (
+                gl::UNSIGNED_INT_24_8,
+                gl::DEPTH_STENCIL,
+                gl::DEPTH24_STENCIL8,
+            ),
+            Self::RGBA8 => (gl::UNSIGNED_BYTE, gl::RGBA, gl::RGBA8),
+            Self::RGB8 => (gl::UNSIGNED_BYTE, gl::RGB, gl::RGB8),
+            Self::RG8 => (gl::UNSIGNED_BYTE, gl::RG, gl::RG8),
+            Self::R8 => (gl::UNSIGNED_BYTE, gl::RED, gl::R8),
+            Self::BGRA8 => (gl::UNSIGNED_BYTE, gl::BGRA, gl::RGBA8),
+            Self::BGR8 => (gl::UNSIGNED_BYTE, gl::BGR, gl::RGB8),
+            Self::RG16 => (gl::UNSIGNED_SHORT, gl::RG, gl::RG16),
+            Self::R16 => (gl::UNSIGNED_SHORT, gl::RED, gl::R16),
+            Self::RGB16 => (gl::UNSIGNED_SHORT, gl::RGB, gl::RGB16),
+            Self::RGBA16 => (gl::UNSIGNED_SHORT, gl::RGBA, gl::RGBA16),
+            Self::DXT1RGB => (0, 0, GL_COMPRESSED_RGB_S3TC_DXT1_EXT),
+            Self::DXT1RGBA => (0, 0, GL_COMPRESSED_RGBA_S3TC_DXT1_EXT),
+            Self::DXT3RGBA => (0, 0, GL_COMPRESSED_RGBA_S3TC_DXT3_EXT),
+            Self::DXT5RGBA => (0, 0, GL_COMPRESSED_RGBA_S3TC_DXT5_EXT),
+            Self::RGBA32F => (gl::FLOAT, gl::RGBA, gl::RGBA32F),
+            Self::RGBA16F => (gl::HALF_FLOAT, gl::RGBA, gl::RGBA16F),
+            Self::SRGB8 => (gl::UNSIGNED_BYTE, gl::RGB, gl::SRGB8),
+            Self::SRGBA8 => (gl::UNSIGNED_BYTE, gl::RGBA, gl::SRGB8_ALPHA8),
+            Self::SBGR8 => (gl::UNSIGNED_BYTE, gl::BGR, gl::SRGB8),
+            Self::SBGRA8 => (gl::UNSIGNED_BYTE, gl::BGRA, gl::SRGB8_ALPHA8),
+            Self::DXT1SRGB => (0, 0, GL_COMPRESSED_SRGB_S3TC_DXT1_EXT),
+            Self::DXT1SRGBA => (0, 0, GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT),
+            Self::DXT3SRGBA => (0, 0, GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT),
+            Self::DXT5SRGBA => (0, 0, GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT),
         }
     }
 
     fn is_compressed(self) -> bool {
         match self {
-            Self::DXT1RGB | Self::DXT1RGBA | Self::DXT3RGBA | Self::DXT5RGBA => true,
+            Self::DXT1RGB
+            | Self::DXT1RGBA
+            | Self::DXT3RGBA
+            | Self::DXT5RGBA
+            | Self::DXT1SRGB
+            | Self::DXT1SRGBA
+            | Self::DXT3SRGBA
+            | Self::DXT5SRGBA => true,
             // Explicit match for rest of formats instead of _ will help to not forget
             // to add new entry here.
             Self::RGBA16
@@ -158,7 +257,13 @@ impl PixelKind {
             | Self::D16
             | Self::F16
             | Self::R8
-            | Self::RGBA32F => false,
+            | Self::RGBA32F
+            | Self::RGBA16F
+            | Self::SRGB8
+            | Self::SRGBA8
+            | Self::SBGR8
+            | Self::SBGRA8
+            | Self::R32UI => false,
         }
     }
 }
@@ -185,48 +290,58 @@ fn image_3d_size_bytes(pixel_kind: PixelKind, width: usize, height: usize, depth
     let pixel_count = width * height * depth;
     match pixel_kind {
         PixelKind::RGBA32F => 16 * pixel_count,
-        PixelKind::RGBA16 => 8 * pixel_count,
+        PixelKind::RGBA16 | PixelKind::RGBA16F => 8 * pixel_count,
         PixelKind::RGB16 => 6 * pixel_count,
         PixelKind::RGBA8
         | PixelKind::BGRA8
+        | PixelKind::SRGBA8
+        | PixelKind::SBGRA8
         | PixelKind::RG16
         | PixelKind::D24S8
         | PixelKind::D32
-        | PixelKind::F32 => 4 * pixel_count,
-        PixelKind::RGB8 | PixelKind::BGR8 => 3 * pixel_count,
+        | PixelKind::F32
+        | PixelKind::R32UI => 4 * pixel_count,
+        PixelKind::RGB8 | PixelKind::BGR8 | PixelKind::SRGB8 | PixelKind::SBGR8 => 3 * pixel_count,
         PixelKind::RG8 | PixelKind::R16 | PixelKind::D16 | PixelKind::F16 => 2 * pixel_count,
         PixelKind::R8 => pixel_count,
-        PixelKind::DXT1RGB | PixelKind::DXT1RGBA => {
+        PixelKind::DXT1RGB | PixelKind::DXT1RGBA | PixelKind::DXT1SRGB | PixelKind::DXT1SRGBA => {
             // 8 here is block size.
             ceil_div_4(width) * ceil_div_4(height) * ceil_div_4(depth) * 8
         }
-        PixelKind::DXT3RGBA | PixelKind::DXT5RGBA => {
+        PixelKind::DXT3RGBA | PixelKind::DXT5RGBA | PixelKind::DXT3SRGBA | PixelKind::DXT5SRGBA => {
             // 16 here is block size.
             ceil_div_4(width) * ceil_div_4(height) * ceil_div_4(depth) * 16
         }
     }
 }
 
-fn image_2d_size_bytes(pixel_kind: PixelKind, width: usize, height: usize) -> usize {
+pub(in crate::renderer) fn image_2d_size_bytes(
+    pixel_kind: PixelKind,
+    width: usize,
+    height: usize,
+) -> usize {
     let pixel_count = width * height;
     match pixel_kind {
         PixelKind::RGBA32F => 16 * pixel_count,
-        PixelKind::RGBA16 => 8 * pixel_count,
+        PixelKind::RGBA16 | PixelKind::RGBA16F => 8 * pixel_count,
         PixelKind::RGB16 => 6 * pixel_count,
         PixelKind::RGBA8
         | PixelKind::BGRA8
+        | PixelKind::SRGBA8
+        | PixelKind::SBGRA8
         | PixelKind::RG16
         | PixelKind::D24S8
         | PixelKind::D32
-        | PixelKind::F32 => 4 * pixel_count,
-        PixelKind::RGB8 | PixelKind::BGR8 => 3 * pixel_count,
+        | PixelKind::F32
+        | PixelKind::R32UI => 4 * pixel_count,
+        PixelKind::RGB8 | PixelKind::BGR8 | PixelKind::SRGB8 | PixelKind::SBGR8 => 3 * pixel_count,
         PixelKind::RG8 | PixelKind::R16 | PixelKind::D16 | PixelKind::F16 => 2 * pixel_count,
         PixelKind::R8 => pixel_count,
-        PixelKind::DXT1RGB | PixelKind::DXT1RGBA => {
+        PixelKind::DXT1RGB | PixelKind::DXT1RGBA | PixelKind::DXT1SRGB | PixelKind::DXT1SRGBA => {
             // 8 here is block size.
             ceil_div_4(width) * ceil_div_4(height) * 8
         }
-        PixelKind::DXT3RGBA | PixelKind::DXT5RGBA => {
+        PixelKind::DXT3RGBA | PixelKind::DXT5RGBA | PixelKind::DXT3SRGBA | PixelKind::DXT5SRGBA => {
             // 16 here is block size.
             ceil_div_4(width) * ceil_div_4(height) * 16
         }
@@ -236,22 +351,25 @@ fn image_2d_size_bytes(pixel_kind: PixelKind, width: usize, height: usize) -> us
 fn image_1d_size_bytes(pixel_kind: PixelKind, length: usize) -> usize {
     match pixel_kind {
         PixelKind::RGBA32F => 16 * length,
-        PixelKind::RGBA16 => 8 * length,
+        PixelKind::RGBA16 | PixelKind::RGBA16F => 8 * length,
         PixelKind::RGB16 => 6 * length,
         PixelKind::RGBA8
         | PixelKind::BGRA8
+        | PixelKind::SRGBA8
+        | PixelKind::SBGRA8
         | PixelKind::RG16
         | PixelKind::D24S8
         | PixelKind::D32
-        | PixelKind::F32 => 4 * length,
-        PixelKind::RGB8 | PixelKind::BGR8 => 3 * length,
+        | PixelKind::F32
+        | PixelKind::R32UI => 4 * length,
+        PixelKind::RGB8 | PixelKind::BGR8 | PixelKind::SRGB8 | PixelKind::SBGR8 => 3 * length,
         PixelKind::RG8 | PixelKind::R16 | PixelKind::D16 | PixelKind::F16 => 2 * length,
         PixelKind::R8 => length,
-        PixelKind::DXT1RGB | PixelKind::DXT1RGBA => {
+        PixelKind::DXT1RGB | PixelKind::DXT1RGBA | PixelKind::DXT1SRGB | PixelKind::DXT1SRGBA => {
             // 8 here is block size.
             ceil_div_4(length) * 8
         }
-        PixelKind::DXT3RGBA | PixelKind::DXT5RGBA => {
+        PixelKind::DXT3RGBA | PixelKind::DXT5RGBA | PixelKind::DXT3SRGBA | PixelKind::DXT5SRGBA => {
             // 16 here is block size.
             ceil_div_4(length) * 16
         }
@@ -381,16 +499,28 @@ impl CubeMapFace {
     }
 }
 
+/// Returns maximum anisotropy level supported by current OpenGL context, 1.0 means that
+/// anisotropic filtering is not supported.
+pub fn max_anisotropy() -> f32 {
+    let mut max = 0.0;
+    unsafe {
+        gl::GetFloatv(gl::MAX_TEXTURE_MAX_ANISOTROPY_EXT, &mut max);
+    }
+    max.max(1.0)
+}
+
 impl<'a> TextureBinding<'a> {
     pub fn set_anisotropy(self, anisotropy: f32) -> Self {
         unsafe {
-            let mut max = 0.0;
-            gl::GetFloatv(gl::MAX_TEXTURE_MAX_ANISOTROPY_EXT, &mut max);
-            gl::TexParameterf(
-                gl::TEXTURE_2D,
-                gl::TEXTURE_MAX_ANISOTROPY_EXT,
-                anisotropy.max(1.0).min(max),
-            );
+            let max = max_anisotropy();
+            // Parameter is not available at all if anisotropic filtering is not supported.
+            if max > 1.0 {
+                gl::TexParameterf(
+                    self.texture.kind.to_texture_target(),
+                    gl::TEXTURE_MAX_ANISOTROPY_EXT,
+                    anisotropy.max(1.0).min(max),
+                );
+            }
 
             // Set it to requested value, instead of hardware-limited. This will allow
             // us to check if anisotropy needs to be changed.
@@ -405,9 +535,12 @@ impl<'a> TextureBinding<'a> {
 
             gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, min_filter.into_gl_value());
 
-            if self.texture.min_filter != MinificationFilter::Linear
-                && self.texture.min_filter != MinificationFilter::Nearest
-            {
+            // Texture could be created without mip levels, they must be generated when filter
+            // starts using them.
+            let uses_mips = |filter| {
+                filter != MinificationFilter::Linear && filter != MinificationFilter::Nearest
+            };
+            if uses_mips(min_filter) && !uses_mips(self.texture.min_filter) {
                 gl::GenerateMipmap(target);
             }
 
@@ -466,6 +599,57 @@ impl<'a> TextureBinding<'a> {
         self
     }
 
+    /// Uploads rectangular region of pixels of 2D texture. `data` must contain pixels of whole
+    /// texture (`row_length` pixels per row), only pixels of the region will be uploaded.
+    pub fn set_sub_data(
+        self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        row_length: usize,
+        data: &[u8],
+    ) -> Self {
+        let pixel_kind = self.texture.pixel_kind;
+
+        if let GpuTextureKind::Rectangle { .. } = self.texture.kind {
+            if !pixel_kind.is_compressed() {
+                let (type_, format, _) = pixel_kind.gl_formats();
+
+                unsafe {
+                    gl::PixelStorei(gl::UNPACK_ALIGNMENT, pixel_kind.unpack_alignment());
+                    gl::PixelStorei(gl::UNPACK_ROW_LENGTH, row_length as i32);
+                    gl::PixelStorei(gl::UNPACK_SKIP_PIXELS, x as i32);
+                    gl::PixelStorei(gl::UNPACK_SKIP_ROWS, y as i32);
+
+                    gl::TexSubImage2D(
+                        gl::TEXTURE_2D,
+                        0,
+                        x as i32,
+                        y as i32,
+                        width as i32,
+                        height as i32,
+                        format,
+                        type_,
+                        data.as_ptr() as *const c_void,
+                    );
+
+                    gl::PixelStorei(gl::UNPACK_ROW_LENGTH, 0);
+                    gl::PixelStorei(gl::UNPACK_SKIP_PIXELS, 0);
+                    gl::PixelStorei(gl::UNPACK_SKIP_ROWS, 0);
+
+                    if self.texture.min_filter != MinificationFilter::Linear
+                        && self.texture.min_filter != MinificationFilter::Nearest
+                    {
+                        gl::GenerateMipmap(gl::TEXTURE_2D);
+                    }
+                }
+            }
+        }
+
+        self
+    }
+
     pub fn set_data(
         self,
         state: &mut PipelineState,
@@ -543,32 +727,7 @@ impl<'a> TextureBinding<'a> {
         unsafe {
             state.set_texture(0, target, self.texture.texture);
 
-            let (type_, format, internal_format) = match pixel_kind {
-                PixelKind::F32 => (gl::FLOAT, gl::RED, gl::R32F),
-                PixelKind::F16 => (gl::FLOAT, gl::RED, gl::R16F),
-                PixelKind::D32 => (gl::FLOAT, gl::DEPTH_COMPONENT, gl::DEPTH_COMPONENT32),
-                PixelKind::D16 => (gl::FLOAT, gl::DEPTH_COMPONENT, gl::DEPTH_COMPONENT16),
-                PixelKind::D24S8 => (
-                    gl::UNSIGNED_INT_24_8,
-                    gl::DEPTH_STENCIL,
-                    gl::DEPTH24_STENCIL8,
-                ),
-                PixelKind::RGBA8 => (gl::UNSIGNED_BYTE, gl::RGBA, gl::RGBA8),
-                PixelKind::RGB8 => (gl::UNSIGNED_BYTE, gl::RGB, gl::RGB8),
-                PixelKind::RG8 => (gl::UNSIGNED_BYTE, gl::RG, gl::RG8),
-                PixelKind::R8 => (gl::UNSIGNED_BYTE, gl::RED, gl::R8),
-                PixelKind::BGRA8 => (gl::UNSIGNED_BYTE, gl::BGRA, gl::RGBA8),
-                PixelKind::BGR8 => (gl::UNSIGNED_BYTE, gl::BGR, gl::RGB8),
-                PixelKind::RG16 => (gl::UNSIGNED_SHORT, gl::RG, gl::RG16),
-                PixelKind::R16 => (gl::UNSIGNED_SHORT, gl::RED, gl::R16),
-                PixelKind::RGB16 => (gl::UNSIGNED_SHORT, gl::RGB, gl::RGB16),
-                PixelKind::RGBA16 => (gl::UNSIGNED_SHORT, gl::RGBA, gl::RGBA16),
-                PixelKind::DXT1RGB => (0, 0, GL_COMPRESSED_RGB_S3TC_DXT1_EXT),
-                PixelKind::DXT1RGBA => (0, 0, GL_COMPRESSED_RGBA_S3TC_DXT1_EXT),
-                PixelKind::DXT3RGBA => (0, 0, GL_COMPRESSED_RGBA_S3TC_DXT3_EXT),
-                PixelKind::DXT5RGBA => (0, 0, GL_COMPRESSED_RGBA_S3TC_DXT5_EXT),
-                PixelKind::RGBA32F => (gl::FLOAT, gl::RGBA, gl::RGBA32F),
-            };
+            let (type_, format, internal_format) = pixel_kind.gl_formats();
 
             let is_compressed = pixel_kind.is_compressed();
 
@@ -772,6 +931,10 @@ const GL_COMPRESSED_RGB_S3TC_DXT1_EXT: u32 = 0x83F0;
 const GL_COMPRESSED_RGBA_S3TC_DXT1_EXT: u32 = 0x83F1;
 const GL_COMPRESSED_RGBA_S3TC_DXT3_EXT: u32 = 0x83F2;
 const GL_COMPRESSED_RGBA_S3TC_DXT5_EXT: u32 = 0x83F3;
+const GL_COMPRESSED_SRGB_S3TC_DXT1_EXT: u32 = 0x8C4C;
+const GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT: u32 = 0x8C4D;
+const GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT: u32 = 0x8C4E;
+const GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT: u32 = 0x8C4F;
 
 impl GpuTexture {
     /// Creates new GPU texture of specified kind. Mip count must be at least 1, it means
@@ -899,3 +1062,57 @@ impl Drop for GpuTexture {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        renderer::framework::{gl, gpu_texture::PixelKind},
+        resource::texture::{TextureColorSpace, TextureData, TextureKind, TexturePixelKind},
+    };
+
+    fn internal_format(pixel_kind: TexturePixelKind, color_space: TextureColorSpace) -> u32 {
+        let bytes_per_pixel = match pixel_kind {
+            TexturePixelKind::R8 => 1,
+            TexturePixelKind::RGB8 => 3,
+            _ => 4,
+        };
+        let mut data = TextureData::from_bytes(
+            TextureKind::Rectangle {
+                width: 2,
+                height: 2,
+            },
+            pixel_kind,
+            vec![0; 4 * bytes_per_pixel],
+        )
+        .unwrap();
+        data.set_color_space(color_space);
+        PixelKind::from_texture(&data).gl_formats().2
+    }
+
+    #[test]
+    fn test_color_space_upload_format() {
+        // Color textures are decoded to linear space by GPU on sampling.
+        assert_eq!(
+            internal_format(TexturePixelKind::RGBA8, TextureColorSpace::Srgb),
+            gl::SRGB8_ALPHA8
+        );
+        assert_eq!(
+            internal_format(TexturePixelKind::RGB8, TextureColorSpace::Srgb),
+            gl::SRGB8
+        );
+        // Normal maps, roughness and other data must be sampled as is.
+        assert_eq!(
+            internal_format(TexturePixelKind::RGBA8, TextureColorSpace::Linear),
+            gl::RGBA8
+        );
+        assert_eq!(
+            internal_format(TexturePixelKind::RGB8, TextureColorSpace::Linear),
+            gl::RGB8
+        );
+        // There is no sRGB variant of single channel formats.
+        assert_eq!(
+            internal_format(TexturePixelKind::R8, TextureColorSpace::Srgb),
+            gl::R8
+        );
+    }
+}
@@ -17,6 +17,8 @@ pub mod framebuffer;
 pub mod geometry_buffer;
 pub mod gpu_program;
 pub mod gpu_texture;
+pub mod query;
+pub mod readback;
 pub mod state;
 
 pub fn check_gl_error_internal(line: u32, file: &str) {
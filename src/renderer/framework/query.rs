@@ -0,0 +1,65 @@
+use crate::renderer::framework::gl::{self, types::GLuint};
+
+/// Hardware occlusion query, tells whether any sample passed depth test between `begin`
+/// and `end` calls. Result becomes available asynchronously, usually in a frame or two.
+pub struct Query {
+    id: GLuint,
+    pending: bool,
+}
+
+impl Query {
+    pub fn new() -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::GenQueries(1, &mut id);
+        }
+        Self { id, pending: false }
+    }
+
+    pub fn begin(&mut self) {
+        unsafe {
+            gl::BeginQuery(gl::ANY_SAMPLES_PASSED, self.id);
+        }
+    }
+
+    pub fn end(&mut self) {
+        unsafe {
+            gl::EndQuery(gl::ANY_SAMPLES_PASSED);
+        }
+        self.pending = true;
+    }
+
+    /// Returns true if query was issued, but its result was not fetched yet.
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    /// Fetches result of the query without stalling pipeline: returns `None` if result is
+    /// not ready yet or query was not issued, otherwise returns true if any sample has passed.
+    pub fn try_get_result(&mut self) -> Option<bool> {
+        if !self.pending {
+            return None;
+        }
+
+        unsafe {
+            let mut available = 0;
+            gl::GetQueryObjectiv(self.id, gl::QUERY_RESULT_AVAILABLE, &mut available);
+            if available == 0 {
+                return None;
+            }
+
+            let mut result = 0;
+            gl::GetQueryObjectiv(self.id, gl::QUERY_RESULT, &mut result);
+            self.pending = false;
+            Some(result != 0)
+        }
+    }
+}
+
+impl Drop for Query {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteQueries(1, &self.id);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use crate::renderer::framework::gl::{
+    self,
+    types::{GLsync, GLuint},
+};
+use std::{ffi::c_void, ptr};
+
+/// Pixel pack buffer with a fence, it allows to read pixels of a frame buffer without waiting
+/// for GPU to finish rendering. Pixels are copied into the buffer on GPU side and fetched by
+/// CPU only when the fence is signaled, usually in a frame or two.
+pub struct PixelReadback {
+    id: GLuint,
+    size: usize,
+    fence: Option<GLsync>,
+}
+
+impl PixelReadback {
+    pub fn new(size: usize) -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, id);
+            gl::BufferData(
+                gl::PIXEL_PACK_BUFFER,
+                size as isize,
+                ptr::null(),
+                gl::STREAM_READ,
+            );
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+        Self {
+            id,
+            size,
+            fence: None,
+        }
+    }
+
+    /// Binds the buffer as pixel pack buffer and calls `read`, which must issue `glReadPixels`
+    /// with zero offset, then puts a fence after the read. Previous read is discarded.
+    pub fn begin_read<F: FnOnce()>(&mut self, read: F) {
+        self.discard();
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.id);
+            read();
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            self.fence = Some(gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0));
+        }
+    }
+
+    /// Returns true if read was issued, but its result was not fetched yet.
+    pub fn is_pending(&self) -> bool {
+        self.fence.is_some()
+    }
+
+    /// Fetches pixels without stalling pipeline: returns false if read is not finished yet or
+    /// was not issued, otherwise copies pixels into `data` and returns true. `data` must not be
+    /// larger than the buffer.
+    pub fn try_read(&mut self, data: &mut [u8]) -> bool {
+        assert!(data.len() <= self.size);
+
+        let fence = match self.fence {
+            Some(fence) => fence,
+            None => return false,
+        };
+
+        unsafe {
+            let status = gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, 0);
+            if status != gl::ALREADY_SIGNALED && status != gl::CONDITION_SATISFIED {
+                return false;
+            }
+
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.id);
+            gl::GetBufferSubData(
+                gl::PIXEL_PACK_BUFFER,
+                0,
+                data.len() as isize,
+                data.as_mut_ptr() as *mut c_void,
+            );
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+        self.discard();
+        true
+    }
+
+    fn discard(&mut self) {
+        if let Some(fence) = self.fence.take() {
+            unsafe {
+                gl::DeleteSync(fence);
+            }
+        }
+    }
+}
+
+impl Drop for PixelReadback {
+    fn drop(&mut self) {
+        self.discard();
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
@@ -1,17 +1,19 @@
 use crate::renderer::TextureCache;
 use crate::{
     core::{
-        algebra::{Matrix4, Vector4},
+        algebra::{Matrix4, Point3, Vector4},
         color::Color,
-        math::Rect,
+        math::{frustum::Frustum, Rect},
+        pool::Handle,
         scope_profile,
     },
     renderer::{
-        batch::{BatchStorage, InstanceData, MatrixStorage, BONE_MATRICES_COUNT},
+        batch::{BatchStorage, InstanceData, MatrixStorage, PbrParameters, BONE_MATRICES_COUNT},
         error::RendererError,
         framework::{
             framebuffer::{
-                Attachment, AttachmentKind, CullFace, DrawParameters, FrameBuffer, FrameBufferTrait,
+                Attachment, AttachmentKind, CullFace, DrawParameters, FrameBuffer,
+                FrameBufferTrait, MultisampleFrameBuffer,
             },
             gpu_program::{GpuProgram, UniformLocation, UniformValue},
             gpu_texture::{
@@ -20,20 +22,36 @@ use crate::{
             },
             state::PipelineState,
         },
+        occlusion::OcclusionCuller,
         GeometryCache, RenderPassStatistics,
     },
-    scene::camera::Camera,
+    scene::{
+        camera::Camera,
+        graph::Graph,
+        node::Node,
+        terrain::{Terrain, MAX_TERRAIN_LAYERS},
+    },
+    utils::log::{Log, MessageKind},
 };
 use std::{cell::RefCell, rc::Rc};
 
 struct InstancedShader {
     program: GpuProgram,
     use_skeletal_animation: UniformLocation,
+    use_vertex_colors: UniformLocation,
     diffuse_texture: UniformLocation,
     normal_texture: UniformLocation,
     specular_texture: UniformLocation,
     roughness_texture: UniformLocation,
     lightmap_texture: UniformLocation,
+    metallic_texture: UniformLocation,
+    ao_texture: UniformLocation,
+    emissive_texture: UniformLocation,
+    use_pbr: UniformLocation,
+    metallic_factor: UniformLocation,
+    roughness_factor: UniformLocation,
+    emissive_color: UniformLocation,
+    emissive_intensity: UniformLocation,
     matrix_buffer_stride: UniformLocation,
     matrix_storage_size: UniformLocation,
     matrix_storage: UniformLocation,
@@ -50,11 +68,20 @@ impl InstancedShader {
             GpuProgram::from_source("GBufferInstancedShader", vertex_source, fragment_source)?;
         Ok(Self {
             use_skeletal_animation: program.uniform_location("useSkeletalAnimation")?,
+            use_vertex_colors: program.uniform_location("useVertexColors")?,
             diffuse_texture: program.uniform_location("diffuseTexture")?,
             normal_texture: program.uniform_location("normalTexture")?,
             specular_texture: program.uniform_location("specularTexture")?,
             roughness_texture: program.uniform_location("roughnessTexture")?,
             lightmap_texture: program.uniform_location("lightmapTexture")?,
+            metallic_texture: program.uniform_location("metallicTexture")?,
+            ao_texture: program.uniform_location("aoTexture")?,
+            emissive_texture: program.uniform_location("emissiveTexture")?,
+            use_pbr: program.uniform_location("usePbr")?,
+            metallic_factor: program.uniform_location("metallicFactor")?,
+            roughness_factor: program.uniform_location("roughnessFactor")?,
+            emissive_color: program.uniform_location("emissiveColor")?,
+            emissive_intensity: program.uniform_location("emissiveIntensity")?,
             matrix_buffer_stride: program.uniform_location("matrixBufferStride")?,
             matrix_storage_size: program.uniform_location("matrixStorageSize")?,
             matrix_storage: program.uniform_location("matrixStorage")?,
@@ -71,12 +98,21 @@ struct Shader {
     world_matrix: UniformLocation,
     wvp_matrix: UniformLocation,
     use_skeletal_animation: UniformLocation,
+    use_vertex_colors: UniformLocation,
     bone_matrices: UniformLocation,
     diffuse_texture: UniformLocation,
     normal_texture: UniformLocation,
     specular_texture: UniformLocation,
     roughness_texture: UniformLocation,
     lightmap_texture: UniformLocation,
+    metallic_texture: UniformLocation,
+    ao_texture: UniformLocation,
+    emissive_texture: UniformLocation,
+    use_pbr: UniformLocation,
+    metallic_factor: UniformLocation,
+    roughness_factor: UniformLocation,
+    emissive_color: UniformLocation,
+    emissive_intensity: UniformLocation,
     diffuse_color: UniformLocation,
     environment_map: UniformLocation,
     camera_position: UniformLocation,
@@ -91,12 +127,21 @@ impl Shader {
             world_matrix: program.uniform_location("worldMatrix")?,
             wvp_matrix: program.uniform_location("worldViewProjection")?,
             use_skeletal_animation: program.uniform_location("useSkeletalAnimation")?,
+            use_vertex_colors: program.uniform_location("useVertexColors")?,
             bone_matrices: program.uniform_location("boneMatrices")?,
             diffuse_texture: program.uniform_location("diffuseTexture")?,
             normal_texture: program.uniform_location("normalTexture")?,
             specular_texture: program.uniform_location("specularTexture")?,
             roughness_texture: program.uniform_location("roughnessTexture")?,
             lightmap_texture: program.uniform_location("lightmapTexture")?,
+            metallic_texture: program.uniform_location("metallicTexture")?,
+            ao_texture: program.uniform_location("aoTexture")?,
+            emissive_texture: program.uniform_location("emissiveTexture")?,
+            use_pbr: program.uniform_location("usePbr")?,
+            metallic_factor: program.uniform_location("metallicFactor")?,
+            roughness_factor: program.uniform_location("roughnessFactor")?,
+            emissive_color: program.uniform_location("emissiveColor")?,
+            emissive_intensity: program.uniform_location("emissiveIntensity")?,
             diffuse_color: program.uniform_location("diffuseColor")?,
             environment_map: program.uniform_location("environmentMap")?,
             camera_position: program.uniform_location("cameraPosition")?,
@@ -105,11 +150,57 @@ impl Shader {
     }
 }
 
+struct TerrainShader {
+    program: GpuProgram,
+    world_matrix: UniformLocation,
+    wvp_matrix: UniformLocation,
+    diffuse_textures: [UniformLocation; MAX_TERRAIN_LAYERS],
+    normal_textures: [UniformLocation; MAX_TERRAIN_LAYERS],
+    control_map: UniformLocation,
+    use_control_map: UniformLocation,
+    layer_count: UniformLocation,
+    tile_scales: UniformLocation,
+}
+
+impl TerrainShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/gbuffer_terrain_fs.glsl");
+        let vertex_source = include_str!("shaders/gbuffer_vs.glsl");
+        let program =
+            GpuProgram::from_source("GBufferTerrainShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            world_matrix: program.uniform_location("worldMatrix")?,
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            diffuse_textures: [
+                program.uniform_location("diffuseTexture0")?,
+                program.uniform_location("diffuseTexture1")?,
+                program.uniform_location("diffuseTexture2")?,
+                program.uniform_location("diffuseTexture3")?,
+            ],
+            normal_textures: [
+                program.uniform_location("normalTexture0")?,
+                program.uniform_location("normalTexture1")?,
+                program.uniform_location("normalTexture2")?,
+                program.uniform_location("normalTexture3")?,
+            ],
+            control_map: program.uniform_location("controlMap")?,
+            use_control_map: program.uniform_location("useControlMap")?,
+            layer_count: program.uniform_location("layerCount")?,
+            tile_scales: program.uniform_location("tileScales")?,
+            program,
+        })
+    }
+}
+
 pub struct GBuffer {
     framebuffer: FrameBuffer,
     pub final_frame: FrameBuffer,
+    // Multisampled copy of final frame for forward rendered geometry, exists only if MSAA
+    // is enabled.
+    msaa_frame: Option<MultisampleFrameBuffer>,
     instanced_shader: InstancedShader,
     shader: Shader,
+    terrain_shader: TerrainShader,
     pub width: i32,
     pub height: i32,
     matrix_storage: MatrixStorage,
@@ -124,6 +215,10 @@ pub(in crate) struct GBufferRenderContext<'a, 'b> {
     pub batch_storage: &'a BatchStorage,
     pub texture_cache: &'a mut TextureCache,
     pub environment_dummy: Rc<RefCell<GpuTexture>>,
+    pub white_dummy: Rc<RefCell<GpuTexture>>,
+    pub normal_dummy: Rc<RefCell<GpuTexture>>,
+    pub occlusion_culler: Option<&'a OcclusionCuller>,
+    pub graph: &'b Graph,
 }
 
 impl GBuffer {
@@ -131,6 +226,7 @@ impl GBuffer {
         state: &mut PipelineState,
         width: usize,
         height: usize,
+        msaa_samples: u32,
     ) -> Result<Self, RendererError> {
         scope_profile!();
 
@@ -178,10 +274,11 @@ impl GBuffer {
             .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
             .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
 
+        // Lightmap and emission, floating-point to keep emission intensity greater than one.
         let mut ambient_texture = GpuTexture::new(
             state,
             GpuTextureKind::Rectangle { width, height },
-            PixelKind::RGBA8,
+            PixelKind::RGBA16F,
             MinificationFilter::Nearest,
             MagnificationFilter::Nearest,
             1,
@@ -192,6 +289,21 @@ impl GBuffer {
             .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
             .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
 
+        // Metallic, roughness, ambient occlusion and "is PBR" flag.
+        let mut material_texture = GpuTexture::new(
+            state,
+            GpuTextureKind::Rectangle { width, height },
+            PixelKind::RGBA8,
+            MinificationFilter::Nearest,
+            MagnificationFilter::Nearest,
+            1,
+            None,
+        )?;
+        material_texture
+            .bind_mut(state, 0)
+            .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+            .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
+
         let framebuffer = FrameBuffer::new(
             state,
             Some(Attachment {
@@ -211,13 +323,18 @@ impl GBuffer {
                     kind: AttachmentKind::Color,
                     texture: Rc::new(RefCell::new(ambient_texture)),
                 },
+                Attachment {
+                    kind: AttachmentKind::Color,
+                    texture: Rc::new(RefCell::new(material_texture)),
+                },
             ],
         )?;
 
+        // HDR frame, values are clamped only when frame is shown on screen.
         let frame_texture = GpuTexture::new(
             state,
             GpuTextureKind::Rectangle { width, height },
-            PixelKind::RGBA8,
+            PixelKind::RGBA16F,
             MinificationFilter::Nearest,
             MagnificationFilter::Nearest,
             1,
@@ -236,10 +353,30 @@ impl GBuffer {
             }],
         )?;
 
+        let msaa_frame = if msaa_samples > 1 {
+            match MultisampleFrameBuffer::new(state, width, height, msaa_samples) {
+                Ok(msaa_frame) => Some(msaa_frame),
+                Err(e) => {
+                    Log::writeln(
+                        MessageKind::Warning,
+                        format!(
+                            "Unable to create {}x MSAA frame buffer, MSAA is disabled. Reason: {:?}",
+                            msaa_samples, e
+                        ),
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
         Ok(Self {
             framebuffer,
+            msaa_frame,
             instanced_shader: InstancedShader::new()?,
             shader: Shader::new()?,
+            terrain_shader: TerrainShader::new()?,
             width: width as i32,
             height: height as i32,
             final_frame: opt_framebuffer,
@@ -253,6 +390,27 @@ impl GBuffer {
         self.final_frame.color_attachments()[0].texture.clone()
     }
 
+    /// Returns frame buffer for forward rendered geometry, it is either multisampled frame
+    /// (if MSAA is enabled) or final frame.
+    pub fn forward_frame(&mut self) -> &mut dyn FrameBufferTrait {
+        match self.msaa_frame.as_mut() {
+            Some(msaa_frame) => msaa_frame,
+            None => &mut self.final_frame,
+        }
+    }
+
+    pub fn msaa_frame(&mut self) -> Option<&mut MultisampleFrameBuffer> {
+        self.msaa_frame.as_mut()
+    }
+
+    /// Writes anti-aliased forward rendered geometry into final frame. Does nothing if MSAA
+    /// is disabled.
+    pub fn resolve_msaa(&self, state: &mut PipelineState) {
+        if let Some(msaa_frame) = self.msaa_frame.as_ref() {
+            msaa_frame.resolve(state, &self.final_frame);
+        }
+    }
+
     pub fn depth(&self) -> Rc<RefCell<GpuTexture>> {
         self.framebuffer.depth_attachment().unwrap().texture.clone()
     }
@@ -269,6 +427,10 @@ impl GBuffer {
         self.framebuffer.color_attachments()[2].texture.clone()
     }
 
+    pub fn material_texture(&self) -> Rc<RefCell<GpuTexture>> {
+        self.framebuffer.color_attachments()[3].texture.clone()
+    }
+
     #[must_use]
     pub(in crate) fn fill(&mut self, args: GBufferRenderContext) -> RenderPassStatistics {
         scope_profile!();
@@ -282,8 +444,17 @@ impl GBuffer {
             batch_storage,
             texture_cache,
             environment_dummy,
+            white_dummy,
+            normal_dummy,
+            occlusion_culler,
+            graph,
         } = args;
 
+        let is_visible = |handle: Handle<Node>| {
+            camera.visibility_cache.is_visible(handle)
+                && occlusion_culler.map_or(true, |culler| !culler.is_occluded(handle))
+        };
+
         let viewport = Rect::new(0, 0, self.width, self.height);
         self.framebuffer.clear(
             state,
@@ -305,8 +476,17 @@ impl GBuffer {
 
         let initial_view_projection = camera.view_projection_matrix();
 
-        for batch in batch_storage.batches.iter() {
+        // Transparent batches are drawn in forward pass after lighting.
+        for batch in batch_storage.batches.iter().filter(|b| !b.transparent) {
+            let batch_params = DrawParameters {
+                culling: !batch.two_sided,
+                ..params
+            };
             let data = batch.data.read().unwrap();
+            let pbr = batch.pbr.unwrap_or(PbrParameters {
+                metallic: 0.0,
+                roughness: 1.0,
+            });
             let geometry = geom_cache.get(state, &data);
 
             let environment = match camera.environment_ref() {
@@ -319,7 +499,7 @@ impl GBuffer {
                 // pass additional data via textures on GPU just to draw single instance.
 
                 let instance = batch.instances.first().unwrap();
-                if camera.visibility_cache.is_visible(instance.owner) {
+                if is_visible(instance.owner) {
                     let view_projection = if instance.depth_offset != 0.0 {
                         let mut projection = camera.projection_matrix();
                         projection[14] -= instance.depth_offset;
@@ -333,7 +513,7 @@ impl GBuffer {
                         state,
                         viewport,
                         &self.shader.program,
-                        &params,
+                        &batch_params,
                         &[
                             (
                                 self.shader.diffuse_texture,
@@ -381,6 +561,44 @@ impl GBuffer {
                                     texture: batch.roughness_texture.clone(),
                                 },
                             ),
+                            (
+                                self.shader.metallic_texture,
+                                UniformValue::Sampler {
+                                    index: 7,
+                                    texture: batch.metallic_texture.clone(),
+                                },
+                            ),
+                            (
+                                self.shader.ao_texture,
+                                UniformValue::Sampler {
+                                    index: 8,
+                                    texture: batch.ao_texture.clone(),
+                                },
+                            ),
+                            (
+                                self.shader.emissive_texture,
+                                UniformValue::Sampler {
+                                    index: 9,
+                                    texture: batch.emissive_texture.clone(),
+                                },
+                            ),
+                            (self.shader.use_pbr, UniformValue::Bool(batch.pbr.is_some())),
+                            (
+                                self.shader.metallic_factor,
+                                UniformValue::Float(pbr.metallic),
+                            ),
+                            (
+                                self.shader.roughness_factor,
+                                UniformValue::Float(pbr.roughness),
+                            ),
+                            (
+                                self.shader.emissive_color,
+                                UniformValue::Color(batch.emissive_color),
+                            ),
+                            (
+                                self.shader.emissive_intensity,
+                                UniformValue::Float(batch.emissive_intensity),
+                            ),
                             (
                                 self.shader.wvp_matrix,
                                 UniformValue::Matrix4(view_projection * instance.world_transform),
@@ -393,6 +611,10 @@ impl GBuffer {
                                 self.shader.use_skeletal_animation,
                                 UniformValue::Bool(batch.is_skinned),
                             ),
+                            (
+                                self.shader.use_vertex_colors,
+                                UniformValue::Bool(batch.use_vertex_colors),
+                            ),
                             (
                                 self.shader.diffuse_color,
                                 UniformValue::Color(instance.color),
@@ -413,7 +635,7 @@ impl GBuffer {
                 self.matrix_storage.clear();
                 self.instance_data_set.clear();
                 for instance in batch.instances.iter() {
-                    if camera.visibility_cache.is_visible(instance.owner) {
+                    if is_visible(instance.owner) {
                         self.instance_data_set.push(InstanceData {
                             color: instance.color,
                             world: instance.world_transform,
@@ -434,7 +656,7 @@ impl GBuffer {
                         state,
                         viewport,
                         &self.instanced_shader.program,
-                        &params,
+                        &batch_params,
                         &[
                             (
                                 self.instanced_shader.diffuse_texture,
@@ -482,6 +704,47 @@ impl GBuffer {
                                     texture: batch.roughness_texture.clone(),
                                 },
                             ),
+                            (
+                                self.instanced_shader.metallic_texture,
+                                UniformValue::Sampler {
+                                    index: 7,
+                                    texture: batch.metallic_texture.clone(),
+                                },
+                            ),
+                            (
+                                self.instanced_shader.ao_texture,
+                                UniformValue::Sampler {
+                                    index: 8,
+                                    texture: batch.ao_texture.clone(),
+                                },
+                            ),
+                            (
+                                self.instanced_shader.emissive_texture,
+                                UniformValue::Sampler {
+                                    index: 9,
+                                    texture: batch.emissive_texture.clone(),
+                                },
+                            ),
+                            (
+                                self.instanced_shader.use_pbr,
+                                UniformValue::Bool(batch.pbr.is_some()),
+                            ),
+                            (
+                                self.instanced_shader.metallic_factor,
+                                UniformValue::Float(pbr.metallic),
+                            ),
+                            (
+                                self.instanced_shader.roughness_factor,
+                                UniformValue::Float(pbr.roughness),
+                            ),
+                            (
+                                self.instanced_shader.emissive_color,
+                                UniformValue::Color(batch.emissive_color),
+                            ),
+                            (
+                                self.instanced_shader.emissive_intensity,
+                                UniformValue::Float(batch.emissive_intensity),
+                            ),
                             (
                                 self.instanced_shader.matrix_storage,
                                 UniformValue::Sampler {
@@ -493,6 +756,10 @@ impl GBuffer {
                                 self.instanced_shader.use_skeletal_animation,
                                 UniformValue::Bool(batch.is_skinned),
                             ),
+                            (
+                                self.instanced_shader.use_vertex_colors,
+                                UniformValue::Bool(batch.use_vertex_colors),
+                            ),
                             (
                                 self.instanced_shader.matrix_buffer_stride,
                                 UniformValue::Integer(BONE_MATRICES_COUNT as i32),
@@ -525,6 +792,139 @@ impl GBuffer {
             }
         }
 
+        for node in graph.linear_iter() {
+            if let Node::Terrain(terrain) = node {
+                if terrain.global_visibility() {
+                    statistics += self.draw_terrain(
+                        terrain,
+                        state,
+                        camera,
+                        geom_cache,
+                        texture_cache,
+                        &white_dummy,
+                        &normal_dummy,
+                        &params,
+                    );
+                }
+            }
+        }
+
+        statistics
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_terrain(
+        &mut self,
+        terrain: &Terrain,
+        state: &mut PipelineState,
+        camera: &Camera,
+        geom_cache: &mut GeometryCache,
+        texture_cache: &mut TextureCache,
+        white_dummy: &Rc<RefCell<GpuTexture>>,
+        normal_dummy: &Rc<RefCell<GpuTexture>>,
+        params: &DrawParameters,
+    ) -> RenderPassStatistics {
+        let mut statistics = RenderPassStatistics::default();
+
+        let viewport = Rect::new(0, 0, self.width, self.height);
+        let frustum = Frustum::from(camera.view_projection_matrix()).unwrap_or_default();
+        let world = terrain.global_transform();
+        let view_projection = camera.view_projection_matrix();
+
+        let mut diffuse_textures = Vec::with_capacity(MAX_TERRAIN_LAYERS);
+        let mut normal_textures = Vec::with_capacity(MAX_TERRAIN_LAYERS);
+        let mut tile_scales = Vector4::new(1.0, 1.0, 1.0, 1.0);
+        for i in 0..MAX_TERRAIN_LAYERS {
+            let layer = terrain.layers().get(i);
+            diffuse_textures.push(
+                layer
+                    .and_then(|l| l.diffuse_texture())
+                    .and_then(|t| texture_cache.get(state, t))
+                    .unwrap_or_else(|| white_dummy.clone()),
+            );
+            normal_textures.push(
+                layer
+                    .and_then(|l| l.normal_texture())
+                    .and_then(|t| texture_cache.get(state, t))
+                    .unwrap_or_else(|| normal_dummy.clone()),
+            );
+            if let Some(layer) = layer {
+                tile_scales[i] = layer.tile_scale();
+            }
+        }
+        let control_map = terrain
+            .control_map()
+            .and_then(|t| texture_cache.get(state, t));
+
+        for chunk in terrain.chunks() {
+            let bounds = chunk.bounding_box();
+            if !frustum.is_intersects_aabb_transform(&bounds, &world) {
+                continue;
+            }
+
+            let center = world.transform_point(&Point3::from(bounds.center())).coords;
+            let lod = terrain
+                .lod_for_distance(camera.global_position().metric_distance(&center))
+                .min(chunk.lods().len() - 1);
+            let data = chunk.lods()[lod].read().unwrap();
+            let geometry = geom_cache.get(state, &data);
+
+            let mut uniforms = vec![
+                (
+                    self.terrain_shader.wvp_matrix,
+                    UniformValue::Matrix4(view_projection * world),
+                ),
+                (
+                    self.terrain_shader.world_matrix,
+                    UniformValue::Matrix4(world),
+                ),
+                (
+                    self.terrain_shader.layer_count,
+                    UniformValue::Integer(terrain.layers().len() as i32),
+                ),
+                (
+                    self.terrain_shader.tile_scales,
+                    UniformValue::Vector4(tile_scales),
+                ),
+                (
+                    self.terrain_shader.use_control_map,
+                    UniformValue::Bool(control_map.is_some()),
+                ),
+                (
+                    self.terrain_shader.control_map,
+                    UniformValue::Sampler {
+                        index: 0,
+                        texture: control_map.clone().unwrap_or_else(|| white_dummy.clone()),
+                    },
+                ),
+            ];
+            for i in 0..MAX_TERRAIN_LAYERS {
+                uniforms.push((
+                    self.terrain_shader.diffuse_textures[i],
+                    UniformValue::Sampler {
+                        index: 1 + i,
+                        texture: diffuse_textures[i].clone(),
+                    },
+                ));
+                uniforms.push((
+                    self.terrain_shader.normal_textures[i],
+                    UniformValue::Sampler {
+                        index: 1 + MAX_TERRAIN_LAYERS + i,
+                        texture: normal_textures[i].clone(),
+                    },
+                ));
+            }
+
+            statistics += self.framebuffer.draw(
+                geometry,
+                state,
+                viewport,
+                &self.terrain_shader.program,
+                params,
+                &uniforms,
+            );
+        }
+
         statistics
     }
 }
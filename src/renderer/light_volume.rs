@@ -1,6 +1,16 @@
+//! Light scattering (volumetric light) renderer.
+//!
+//! Scattering is accumulated for every light that has scattering enabled in separate
+//! buffer which could be smaller than the frame (see `LightScatterResolution`). If a light
+//! casts shadows, its volume is ray marched against shadow map so shadow casters will cut
+//! light shafts. Lights without shadows use analytic in-scattering only. When all lights
+//! were processed, the buffer is blurred to hide noise of jittered ray marching and added
+//! on top of the frame.
+
 use crate::{
     core::{
         algebra::{Matrix4, Vector3},
+        color::Color,
         math::Rect,
         scope_profile,
     },
@@ -8,18 +18,25 @@ use crate::{
         error::RendererError,
         flat_shader::FlatShader,
         framework::{
-            framebuffer::{CullFace, DrawParameters, FrameBufferTrait},
+            framebuffer::{
+                Attachment, AttachmentKind, CullFace, DrawParameters, FrameBuffer, FrameBufferTrait,
+            },
             gl,
             gpu_program::{GpuProgram, UniformLocation, UniformValue},
+            gpu_texture::{
+                Coordinate, GpuTexture, GpuTextureKind, MagnificationFilter, MinificationFilter,
+                PixelKind, WrapMode,
+            },
             state::{ColorMask, PipelineState, StencilFunc, StencilOp},
         },
         gbuffer::GBuffer,
         surface::SurfaceSharedData,
-        GeometryCache, RenderPassStatistics,
+        GeometryCache, QualitySettings, RenderPassStatistics,
     },
     scene::light::Light,
 };
 use rapier3d::na::Point3;
+use std::{cell::RefCell, rc::Rc};
 
 struct SpotLightShader {
     program: GpuProgram,
@@ -31,6 +48,12 @@ struct SpotLightShader {
     light_color: UniformLocation,
     scatter_factor: UniformLocation,
     inv_proj: UniformLocation,
+    inv_view: UniformLocation,
+    shadows_enabled: UniformLocation,
+    shadow_texture: UniformLocation,
+    shadow_bias: UniformLocation,
+    step_count: UniformLocation,
+    light_view_proj_matrix: UniformLocation,
 }
 
 impl SpotLightShader {
@@ -48,6 +71,12 @@ impl SpotLightShader {
             light_color: program.uniform_location("lightColor")?,
             scatter_factor: program.uniform_location("scatterFactor")?,
             inv_proj: program.uniform_location("invProj")?,
+            inv_view: program.uniform_location("invViewMatrix")?,
+            shadows_enabled: program.uniform_location("shadowsEnabled")?,
+            shadow_texture: program.uniform_location("shadowTexture")?,
+            shadow_bias: program.uniform_location("shadowBias")?,
+            step_count: program.uniform_location("stepCount")?,
+            light_view_proj_matrix: program.uniform_location("lightViewProjMatrix")?,
             program,
         })
     }
@@ -62,6 +91,11 @@ struct PointLightShader {
     light_color: UniformLocation,
     scatter_factor: UniformLocation,
     inv_proj: UniformLocation,
+    inv_view: UniformLocation,
+    shadows_enabled: UniformLocation,
+    shadow_texture: UniformLocation,
+    shadow_bias: UniformLocation,
+    step_count: UniformLocation,
 }
 
 impl PointLightShader {
@@ -78,17 +112,165 @@ impl PointLightShader {
             light_radius: program.uniform_location("lightRadius")?,
             light_color: program.uniform_location("lightColor")?,
             scatter_factor: program.uniform_location("scatterFactor")?,
+            inv_view: program.uniform_location("invViewMatrix")?,
+            shadows_enabled: program.uniform_location("shadowsEnabled")?,
+            shadow_texture: program.uniform_location("shadowTexture")?,
+            shadow_bias: program.uniform_location("shadowBias")?,
+            step_count: program.uniform_location("stepCount")?,
             program,
         })
     }
 }
 
+struct BlurShader {
+    program: GpuProgram,
+    world_view_projection_matrix: UniformLocation,
+    input_texture: UniformLocation,
+}
+
+impl BlurShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/light_scatter_blur_fs.glsl");
+        let vertex_source = include_str!("shaders/blur_vs.glsl");
+        let program =
+            GpuProgram::from_source("LightScatterBlurShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            world_view_projection_matrix: program.uniform_location("worldViewProjection")?,
+            input_texture: program.uniform_location("inputTexture")?,
+            program,
+        })
+    }
+}
+
+/// Shadow map of a light which is used to occlude light volume.
+pub(in crate) struct LightVolumeShadow {
+    pub enabled: bool,
+    /// Shadow map of light, it must be 2D texture for spot lights and cube map for point lights.
+    pub shadow_map: Rc<RefCell<GpuTexture>>,
+    /// View-projection matrix of spot light, ignored for point lights.
+    pub light_view_projection: Matrix4<f32>,
+    pub bias: f32,
+}
+
+struct ScatterBuffer {
+    // Accumulates scattered light of all lights, has its own stencil buffer to mark
+    // pixels covered by light volumes.
+    accumulation: FrameBuffer,
+    blurred: FrameBuffer,
+    width: usize,
+    height: usize,
+}
+
+fn make_scatter_texture(
+    state: &mut PipelineState,
+    width: usize,
+    height: usize,
+    pixel_kind: PixelKind,
+    filter: MagnificationFilter,
+) -> Result<Rc<RefCell<GpuTexture>>, RendererError> {
+    let min_filter = match filter {
+        MagnificationFilter::Nearest => MinificationFilter::Nearest,
+        MagnificationFilter::Linear => MinificationFilter::Linear,
+    };
+    let mut texture = GpuTexture::new(
+        state,
+        GpuTextureKind::Rectangle { width, height },
+        pixel_kind,
+        min_filter,
+        filter,
+        1,
+        None,
+    )?;
+    texture
+        .bind_mut(state, 0)
+        .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+        .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
+    Ok(Rc::new(RefCell::new(texture)))
+}
+
+impl ScatterBuffer {
+    fn new(state: &mut PipelineState, width: usize, height: usize) -> Result<Self, RendererError> {
+        let depth_stencil = make_scatter_texture(
+            state,
+            width,
+            height,
+            PixelKind::D24S8,
+            MagnificationFilter::Nearest,
+        )?;
+        let accumulation_texture = make_scatter_texture(
+            state,
+            width,
+            height,
+            PixelKind::RGBA16,
+            MagnificationFilter::Nearest,
+        )?;
+        // Linear filtering is used to smoothly upscale blurred scattering to frame size.
+        let blurred_texture = make_scatter_texture(
+            state,
+            width,
+            height,
+            PixelKind::RGBA16,
+            MagnificationFilter::Linear,
+        )?;
+
+        let accumulation = FrameBuffer::new(
+            state,
+            Some(Attachment {
+                kind: AttachmentKind::DepthStencil,
+                texture: depth_stencil,
+            }),
+            vec![Attachment {
+                kind: AttachmentKind::Color,
+                texture: accumulation_texture,
+            }],
+        )?;
+
+        let blurred = FrameBuffer::new(
+            state,
+            None,
+            vec![Attachment {
+                kind: AttachmentKind::Color,
+                texture: blurred_texture,
+            }],
+        )?;
+
+        Ok(Self {
+            accumulation,
+            blurred,
+            width,
+            height,
+        })
+    }
+
+    fn viewport(&self) -> Rect<i32> {
+        Rect::new(0, 0, self.width as i32, self.height as i32)
+    }
+}
+
+fn make_frame_matrix(viewport: Rect<i32>) -> Matrix4<f32> {
+    Matrix4::new_orthographic(
+        0.0,
+        viewport.w() as f32,
+        viewport.h() as f32,
+        0.0,
+        -1.0,
+        1.0,
+    ) * Matrix4::new_nonuniform_scaling(&Vector3::new(
+        viewport.w() as f32,
+        viewport.h() as f32,
+        0.0,
+    ))
+}
+
 pub struct LightVolumeRenderer {
     spot_light_shader: SpotLightShader,
     point_light_shader: PointLightShader,
+    blur_shader: BlurShader,
     flat_shader: FlatShader,
     cone: SurfaceSharedData,
     sphere: SurfaceSharedData,
+    buffer: Option<ScatterBuffer>,
+    step_count: usize,
 }
 
 impl LightVolumeRenderer {
@@ -104,21 +286,146 @@ impl LightVolumeRenderer {
                 Matrix4::new_translation(&Vector3::new(0.0, -1.0, 0.0)),
             ),
             sphere: SurfaceSharedData::make_sphere(8, 8, 1.0),
+            blur_shader: BlurShader::new()?,
+            buffer: None,
+            step_count: 0,
         })
     }
 
+    /// Prepares scattering buffer for new frame. Buffer will be re-created if frame size
+    /// or scattering resolution has changed.
+    pub(in crate) fn begin(
+        &mut self,
+        state: &mut PipelineState,
+        gbuffer: &GBuffer,
+        settings: &QualitySettings,
+    ) -> Result<(), RendererError> {
+        scope_profile!();
+
+        let divisor = settings.light_scatter_resolution.divisor();
+        let width = (gbuffer.width as usize / divisor).max(1);
+        let height = (gbuffer.height as usize / divisor).max(1);
+
+        let must_recreate = match self.buffer.as_ref() {
+            Some(buffer) => buffer.width != width || buffer.height != height,
+            None => true,
+        };
+        if must_recreate {
+            self.buffer = Some(ScatterBuffer::new(state, width, height)?);
+        }
+
+        self.step_count = settings.light_scatter_steps;
+
+        if let Some(buffer) = self.buffer.as_mut() {
+            let viewport = buffer.viewport();
+            buffer.accumulation.clear(
+                state,
+                viewport,
+                Some(Color::from_rgba(0, 0, 0, 0)),
+                None,
+                Some(0),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Blurs accumulated scattering and adds it on top of final frame.
+    pub(in crate) fn end(
+        &mut self,
+        state: &mut PipelineState,
+        gbuffer: &mut GBuffer,
+        quad: &SurfaceSharedData,
+        geom_cache: &mut GeometryCache,
+    ) -> RenderPassStatistics {
+        scope_profile!();
+
+        let mut stats = RenderPassStatistics::default();
+
+        let buffer = match self.buffer.as_mut() {
+            Some(buffer) => buffer,
+            None => return stats,
+        };
+
+        let scatter_viewport = buffer.viewport();
+
+        stats += buffer.blurred.draw(
+            geom_cache.get(state, quad),
+            state,
+            scatter_viewport,
+            &self.blur_shader.program,
+            &DrawParameters {
+                cull_face: CullFace::Back,
+                culling: false,
+                color_write: Default::default(),
+                depth_write: false,
+                stencil_test: false,
+                depth_test: false,
+                blend: false,
+            },
+            &[
+                (
+                    self.blur_shader.world_view_projection_matrix,
+                    UniformValue::Matrix4(make_frame_matrix(scatter_viewport)),
+                ),
+                (
+                    self.blur_shader.input_texture,
+                    UniformValue::Sampler {
+                        index: 0,
+                        texture: buffer.accumulation.color_attachments()[0].texture.clone(),
+                    },
+                ),
+            ],
+        );
+
+        let viewport = Rect::new(0, 0, gbuffer.width, gbuffer.height);
+
+        state.set_blend_func(gl::ONE, gl::ONE);
+
+        stats += gbuffer.final_frame.draw(
+            geom_cache.get(state, quad),
+            state,
+            viewport,
+            &self.flat_shader.program,
+            &DrawParameters {
+                cull_face: CullFace::Back,
+                culling: false,
+                color_write: Default::default(),
+                depth_write: false,
+                stencil_test: false,
+                depth_test: false,
+                blend: true,
+            },
+            &[
+                (
+                    self.flat_shader.wvp_matrix,
+                    UniformValue::Matrix4(make_frame_matrix(viewport)),
+                ),
+                (
+                    self.flat_shader.diffuse_texture,
+                    UniformValue::Sampler {
+                        index: 0,
+                        texture: buffer.blurred.color_attachments()[0].texture.clone(),
+                    },
+                ),
+            ],
+        );
+
+        stats
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub(in crate) fn render_volume(
         &mut self,
         state: &mut PipelineState,
         light: &Light,
         gbuffer: &mut GBuffer,
+        shadow: LightVolumeShadow,
         quad: &SurfaceSharedData,
         geom_cache: &mut GeometryCache,
         view: Matrix4<f32>,
         inv_proj: Matrix4<f32>,
         view_proj: Matrix4<f32>,
-        viewport: Rect<i32>,
     ) -> RenderPassStatistics {
         scope_profile!();
 
@@ -128,236 +435,202 @@ impl LightVolumeRenderer {
             return stats;
         }
 
-        let frame_matrix = Matrix4::new_orthographic(
-            0.0,
-            viewport.w() as f32,
-            viewport.h() as f32,
-            0.0,
-            -1.0,
-            1.0,
-        ) * Matrix4::new_nonuniform_scaling(&Vector3::new(
-            viewport.w() as f32,
-            viewport.h() as f32,
-            0.0,
-        ));
+        let buffer = match self.buffer.as_mut() {
+            Some(buffer) => buffer,
+            None => return stats,
+        };
+
+        let viewport = buffer.viewport();
+        let frame_matrix = make_frame_matrix(viewport);
+        let inv_view = view.try_inverse().unwrap_or_default();
+        let step_count = self.step_count as i32;
 
         let position = view
             .transform_point(&Point3::from(light.global_position()))
             .coords;
 
+        // Light volume is drawn into stencil buffer - it will mark pixels for further volumetric
+        // light calculations, it will significantly reduce amount of pixels for far lights thus
+        // significantly improve performance. Scattering buffer has no scene depth, so volume
+        // marks every pixel it covers and depth is checked analytically in the shader.
+        let light_shape_matrix = match light {
+            Light::Spot(spot) => {
+                // Angle bias is used to to slightly increase cone radius to add small margin
+                // for fadeout effect.
+                let bias = 0.05;
+                let k = ((0.5 + bias) * spot.full_cone_angle()).sin() * spot.distance();
+                light.global_transform()
+                    * Matrix4::new_nonuniform_scaling(&Vector3::new(k, spot.distance(), k))
+            }
+            Light::Point(point) => {
+                // Radius bias is used to to slightly increase sphere radius to add small margin
+                // for fadeout effect. It is set to 5%.
+                let bias = 1.05;
+                let k = bias * point.radius();
+                light.global_transform() * Matrix4::new_nonuniform_scaling(&Vector3::new(k, k, k))
+            }
+            _ => return stats,
+        };
+        let volume = match light {
+            Light::Spot(_) => &self.cone,
+            _ => &self.sphere,
+        };
+
+        state.set_stencil_mask(0xFFFF_FFFF);
+        state.set_stencil_func(StencilFunc {
+            func: gl::EQUAL,
+            ref_value: 0xFF,
+            mask: 0xFFFF_FFFF,
+        });
+        state.set_stencil_op(StencilOp {
+            fail: gl::REPLACE,
+            zfail: gl::KEEP,
+            zpass: gl::REPLACE,
+        });
+
+        stats += buffer.accumulation.draw(
+            geom_cache.get(state, volume),
+            state,
+            viewport,
+            &self.flat_shader.program,
+            &DrawParameters {
+                cull_face: CullFace::Back,
+                culling: false,
+                color_write: ColorMask::all(false),
+                depth_write: false,
+                stencil_test: true,
+                depth_test: false,
+                blend: false,
+            },
+            &[(
+                self.flat_shader.wvp_matrix,
+                UniformValue::Matrix4(view_proj * light_shape_matrix),
+            )],
+        );
+
+        // Make sure to clean stencil buffer after drawing full screen quad.
+        state.set_stencil_op(StencilOp {
+            zpass: gl::ZERO,
+            ..Default::default()
+        });
+
+        let draw_params = DrawParameters {
+            cull_face: CullFace::Back,
+            culling: false,
+            color_write: Default::default(),
+            depth_write: false,
+            stencil_test: true,
+            depth_test: false,
+            blend: true,
+        };
+
+        // Finally draw fullscreen quad, GPU will calculate scattering only on pixels that were
+        // marked in stencil buffer. For distant lights it will be very low amount of pixels and
+        // so distant lights won't impact performance.
         match light {
             Light::Spot(spot) => {
                 let direction = view
-                    .transform_point(&Point3::from(
-                        -light
+                    .transform_vector(
+                        &(-light
                             .up_vector()
                             .try_normalize(std::f32::EPSILON)
-                            .unwrap_or_else(Vector3::z),
-                    ))
-                    .coords;
-
-                // Draw cone into stencil buffer - it will mark pixels for further volumetric light
-                // calculations, it will significantly reduce amount of pixels for far lights thus
-                // significantly improve performance.
+                            .unwrap_or_else(Vector3::z)),
+                    )
+                    .try_normalize(std::f32::EPSILON)
+                    .unwrap_or_else(Vector3::z);
 
-                // Angle bias is used to to slightly increase cone radius to add small margin
-                // for fadeout effect.
-                let bias = 0.05;
-                let k = ((0.5 + bias) * spot.full_cone_angle()).sin() * spot.distance();
-                let light_shape_matrix = light.global_transform()
-                    * Matrix4::new_nonuniform_scaling(&Vector3::new(k, spot.distance(), k));
-                let mvp = view_proj * light_shape_matrix;
-
-                gbuffer
-                    .final_frame
-                    .clear(state, viewport, None, None, Some(0));
-
-                state.set_stencil_mask(0xFFFF_FFFF);
-                state.set_stencil_func(StencilFunc {
-                    func: gl::EQUAL,
-                    ref_value: 0xFF,
-                    mask: 0xFFFF_FFFF,
-                });
-                state.set_stencil_op(StencilOp {
-                    fail: gl::REPLACE,
-                    zfail: gl::KEEP,
-                    zpass: gl::REPLACE,
-                });
-
-                stats += gbuffer.final_frame.draw(
-                    geom_cache.get(state, &self.cone),
-                    state,
-                    viewport,
-                    &self.flat_shader.program,
-                    &DrawParameters {
-                        cull_face: CullFace::Back,
-                        culling: false,
-                        color_write: ColorMask::all(false),
-                        depth_write: false,
-                        stencil_test: true,
-                        depth_test: true,
-                        blend: false,
-                    },
-                    &[(self.flat_shader.wvp_matrix, UniformValue::Matrix4(mvp))],
-                );
-
-                // Make sure to clean stencil buffer after drawing full screen quad.
-                state.set_stencil_op(StencilOp {
-                    zpass: gl::ZERO,
-                    ..Default::default()
-                });
-
-                // Finally draw fullscreen quad, GPU will calculate scattering only on pixels that were
-                // marked in stencil buffer. For distant lights it will be very low amount of pixels and
-                // so distant lights won't impact performance.
-                stats += gbuffer.final_frame.draw(
+                let shader = &self.spot_light_shader;
+                stats += buffer.accumulation.draw(
                     geom_cache.get(state, quad),
                     state,
                     viewport,
-                    &self.spot_light_shader.program,
-                    &DrawParameters {
-                        cull_face: CullFace::Back,
-                        culling: false,
-                        color_write: Default::default(),
-                        depth_write: false,
-                        stencil_test: true,
-                        depth_test: false,
-                        blend: true,
-                    },
+                    &shader.program,
+                    &draw_params,
                     &[
                         (
-                            self.spot_light_shader.world_view_proj_matrix,
+                            shader.world_view_proj_matrix,
                             UniformValue::Matrix4(frame_matrix),
                         ),
+                        (shader.inv_proj, UniformValue::Matrix4(inv_proj)),
+                        (shader.inv_view, UniformValue::Matrix4(inv_view)),
                         (
-                            self.spot_light_shader.inv_proj,
-                            UniformValue::Matrix4(inv_proj),
-                        ),
-                        (
-                            self.spot_light_shader.cone_angle_cos,
+                            shader.cone_angle_cos,
                             UniformValue::Float((spot.full_cone_angle() * 0.5).cos()),
                         ),
+                        (shader.light_position, UniformValue::Vector3(position)),
+                        (shader.light_direction, UniformValue::Vector3(direction)),
                         (
-                            self.spot_light_shader.light_position,
-                            UniformValue::Vector3(position),
-                        ),
-                        (
-                            self.spot_light_shader.light_direction,
-                            UniformValue::Vector3(direction),
-                        ),
-                        (
-                            self.spot_light_shader.depth_sampler,
+                            shader.depth_sampler,
                             UniformValue::Sampler {
                                 index: 0,
                                 texture: gbuffer.depth(),
                             },
                         ),
                         (
-                            self.spot_light_shader.light_color,
+                            shader.shadow_texture,
+                            UniformValue::Sampler {
+                                index: 1,
+                                texture: shadow.shadow_map,
+                            },
+                        ),
+                        (shader.shadows_enabled, UniformValue::Bool(shadow.enabled)),
+                        (shader.shadow_bias, UniformValue::Float(shadow.bias)),
+                        (shader.step_count, UniformValue::Integer(step_count)),
+                        (
+                            shader.light_view_proj_matrix,
+                            UniformValue::Matrix4(shadow.light_view_projection),
+                        ),
+                        (
+                            shader.light_color,
                             UniformValue::Vector3(light.color().as_frgba().xyz()),
                         ),
                         (
-                            self.spot_light_shader.scatter_factor,
+                            shader.scatter_factor,
                             UniformValue::Vector3(light.scatter()),
                         ),
                     ],
                 )
             }
             Light::Point(point) => {
-                gbuffer
-                    .final_frame
-                    .clear(state, viewport, None, None, Some(0));
-
-                state.set_stencil_mask(0xFFFF_FFFF);
-                state.set_stencil_func(StencilFunc {
-                    func: gl::EQUAL,
-                    ref_value: 0xFF,
-                    mask: 0xFFFF_FFFF,
-                });
-                state.set_stencil_op(StencilOp {
-                    fail: gl::REPLACE,
-                    zfail: gl::KEEP,
-                    zpass: gl::REPLACE,
-                });
-
-                // Radius bias is used to to slightly increase sphere radius to add small margin
-                // for fadeout effect. It is set to 5%.
-                let bias = 1.05;
-                let k = bias * point.radius();
-                let light_shape_matrix = light.global_transform()
-                    * Matrix4::new_nonuniform_scaling(&Vector3::new(k, k, k));
-                let mvp = view_proj * light_shape_matrix;
-
-                stats += gbuffer.final_frame.draw(
-                    geom_cache.get(state, &self.sphere),
-                    state,
-                    viewport,
-                    &self.flat_shader.program,
-                    &DrawParameters {
-                        cull_face: CullFace::Back,
-                        culling: false,
-                        color_write: ColorMask::all(false),
-                        depth_write: false,
-                        stencil_test: true,
-                        depth_test: true,
-                        blend: false,
-                    },
-                    &[(self.flat_shader.wvp_matrix, UniformValue::Matrix4(mvp))],
-                );
-
-                // Make sure to clean stencil buffer after drawing full screen quad.
-                state.set_stencil_op(StencilOp {
-                    zpass: gl::ZERO,
-                    ..Default::default()
-                });
-
-                // Finally draw fullscreen quad, GPU will calculate scattering only on pixels that were
-                // marked in stencil buffer. For distant lights it will be very low amount of pixels and
-                // so distant lights won't impact performance.
-                stats += gbuffer.final_frame.draw(
+                let shader = &self.point_light_shader;
+                stats += buffer.accumulation.draw(
                     geom_cache.get(state, quad),
                     state,
                     viewport,
-                    &self.point_light_shader.program,
-                    &DrawParameters {
-                        cull_face: CullFace::Back,
-                        culling: false,
-                        color_write: Default::default(),
-                        depth_write: false,
-                        stencil_test: true,
-                        depth_test: false,
-                        blend: true,
-                    },
+                    &shader.program,
+                    &draw_params,
                     &[
                         (
-                            self.point_light_shader.world_view_proj_matrix,
+                            shader.world_view_proj_matrix,
                             UniformValue::Matrix4(frame_matrix),
                         ),
+                        (shader.inv_proj, UniformValue::Matrix4(inv_proj)),
+                        (shader.inv_view, UniformValue::Matrix4(inv_view)),
+                        (shader.light_position, UniformValue::Vector3(position)),
                         (
-                            self.point_light_shader.inv_proj,
-                            UniformValue::Matrix4(inv_proj),
-                        ),
-                        (
-                            self.point_light_shader.light_position,
-                            UniformValue::Vector3(position),
-                        ),
-                        (
-                            self.point_light_shader.depth_sampler,
+                            shader.depth_sampler,
                             UniformValue::Sampler {
                                 index: 0,
                                 texture: gbuffer.depth(),
                             },
                         ),
                         (
-                            self.point_light_shader.light_radius,
-                            UniformValue::Float(point.radius()),
+                            shader.shadow_texture,
+                            UniformValue::Sampler {
+                                index: 1,
+                                texture: shadow.shadow_map,
+                            },
                         ),
+                        (shader.shadows_enabled, UniformValue::Bool(shadow.enabled)),
+                        (shader.shadow_bias, UniformValue::Float(shadow.bias)),
+                        (shader.step_count, UniformValue::Integer(step_count)),
+                        (shader.light_radius, UniformValue::Float(point.radius())),
                         (
-                            self.point_light_shader.light_color,
+                            shader.light_color,
                             UniformValue::Vector3(light.color().as_frgba().xyz()),
                         ),
                         (
-                            self.point_light_shader.scatter_factor,
+                            shader.scatter_factor,
                             UniformValue::Vector3(light.scatter()),
                         ),
                     ],
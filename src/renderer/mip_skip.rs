@@ -0,0 +1,218 @@
+//! Reduction of texture resolution on upload, see `QualitySettings::texture_mip_skip`.
+//!
+//! Only mip-mapped rectangle textures loaded from files are reduced: textures without
+//! mip-mapping (UI images, lookup tables) would visibly lose detail, and procedural textures
+//! can be modified at runtime by regions given in full resolution.
+//!
+//! Textures which already contain mip levels (DDS) are reduced by dropping top levels, others
+//! are downsampled on CPU with a box filter. Compressed textures without mip levels are
+//! uploaded as is. Texture is never reduced below 1x1 pixels, or below its last mip level.
+
+use crate::{
+    renderer::framework::gpu_texture::{image_2d_size_bytes, PixelKind},
+    resource::texture::{TextureData, TextureKind, TextureMinificationFilter, TexturePixelKind},
+};
+use std::borrow::Cow;
+
+/// Texture data prepared for upload.
+pub(in crate) struct ReducedTexture<'a> {
+    pub width: usize,
+    pub height: usize,
+    pub mip_count: usize,
+    pub bytes: Cow<'a, [u8]>,
+}
+
+fn uses_mip_maps(filter: TextureMinificationFilter) -> bool {
+    !matches!(
+        filter,
+        TextureMinificationFilter::Nearest | TextureMinificationFilter::Linear
+    )
+}
+
+// Returns amount of bytes per channel and channel count for uncompressed formats.
+fn channel_layout(pixel_kind: TexturePixelKind) -> Option<(usize, usize)> {
+    match pixel_kind {
+        TexturePixelKind::R8 => Some((1, 1)),
+        TexturePixelKind::RG8 => Some((1, 2)),
+        TexturePixelKind::RGB8 | TexturePixelKind::BGR8 => Some((1, 3)),
+        TexturePixelKind::RGBA8 | TexturePixelKind::BGRA8 => Some((1, 4)),
+        TexturePixelKind::R16 => Some((2, 1)),
+        TexturePixelKind::RG16 => Some((2, 2)),
+        TexturePixelKind::RGB16 => Some((2, 3)),
+        TexturePixelKind::RGBA16 => Some((2, 4)),
+        TexturePixelKind::DXT1RGB
+        | TexturePixelKind::DXT1RGBA
+        | TexturePixelKind::DXT3RGBA
+        | TexturePixelKind::DXT5RGBA => None,
+    }
+}
+
+// Returns amount of levels to skip and size of top level after skipping.
+fn plan(texture: &TextureData, mip_skip: usize) -> Option<(usize, usize, usize)> {
+    if mip_skip == 0 || texture.is_procedural() || !uses_mip_maps(texture.minification_filter()) {
+        return None;
+    }
+
+    if let TextureKind::Rectangle { width, height } = texture.kind {
+        let (width, height) = (width as usize, height as usize);
+        let skip = if texture.mip_count() > 1 {
+            mip_skip.min(texture.mip_count() as usize - 1)
+        } else if channel_layout(texture.pixel_kind).is_some() {
+            let largest = width.max(height).max(1);
+            let max_skip =
+                (std::mem::size_of::<usize>() * 8) - 1 - largest.leading_zeros() as usize;
+            mip_skip.min(max_skip)
+        } else {
+            0
+        };
+        if skip > 0 {
+            return Some((skip, (width >> skip).max(1), (height >> skip).max(1)));
+        }
+    }
+
+    None
+}
+
+/// Returns size of the texture on GPU if it will be reduced, `None` - texture is uploaded in
+/// full resolution.
+pub(in crate) fn reduced_size(texture: &TextureData, mip_skip: usize) -> Option<(usize, usize)> {
+    plan(texture, mip_skip).map(|(_, width, height)| (width, height))
+}
+
+/// Prepares texture for upload with given amount of skipped mip levels, `None` - texture must
+/// be uploaded as is.
+pub(in crate) fn reduce(texture: &TextureData, mip_skip: usize) -> Option<ReducedTexture<'_>> {
+    let (skip, new_width, new_height) = plan(texture, mip_skip)?;
+    let (width, height) = match texture.kind {
+        TextureKind::Rectangle { width, height } => (width as usize, height as usize),
+        _ => unreachable!(),
+    };
+
+    if texture.mip_count() > 1 {
+        let pixel_kind = PixelKind::from(texture.pixel_kind);
+        let offset = (0..skip)
+            .map(|mip| image_2d_size_bytes(pixel_kind, width >> mip, height >> mip))
+            .sum::<usize>();
+        Some(ReducedTexture {
+            width: width >> skip,
+            height: height >> skip,
+            mip_count: texture.mip_count() as usize - skip,
+            bytes: Cow::Borrowed(&texture.bytes[offset..]),
+        })
+    } else {
+        let (channel_size, channel_count) = channel_layout(texture.pixel_kind)?;
+        let mut bytes = Cow::Borrowed(texture.bytes.as_slice());
+        let (mut width, mut height) = (width, height);
+        for _ in 0..skip {
+            bytes = Cow::Owned(downsample(
+                &bytes,
+                width,
+                height,
+                channel_size,
+                channel_count,
+            ));
+            width = (width / 2).max(1);
+            height = (height / 2).max(1);
+        }
+        debug_assert_eq!((width, height), (new_width, new_height));
+        Some(ReducedTexture {
+            width,
+            height,
+            mip_count: 1,
+            bytes,
+        })
+    }
+}
+
+// Halves size of an image, every pixel of new image is an average of 2x2 pixels of the source.
+fn downsample(
+    bytes: &[u8],
+    width: usize,
+    height: usize,
+    channel_size: usize,
+    channel_count: usize,
+) -> Vec<u8> {
+    let new_width = (width / 2).max(1);
+    let new_height = (height / 2).max(1);
+    let pixel_size = channel_size * channel_count;
+
+    let read = |x: usize, y: usize, channel: usize| -> u32 {
+        let i = (y * width + x) * pixel_size + channel * channel_size;
+        if channel_size == 1 {
+            u32::from(bytes[i])
+        } else {
+            u32::from(u16::from_ne_bytes([bytes[i], bytes[i + 1]]))
+        }
+    };
+
+    let mut result = Vec::with_capacity(new_width * new_height * pixel_size);
+    for y in 0..new_height {
+        let (y0, y1) = ((y * 2).min(height - 1), (y * 2 + 1).min(height - 1));
+        for x in 0..new_width {
+            let (x0, x1) = ((x * 2).min(width - 1), (x * 2 + 1).min(width - 1));
+            for channel in 0..channel_count {
+                let sum = read(x0, y0, channel)
+                    + read(x1, y0, channel)
+                    + read(x0, y1, channel)
+                    + read(x1, y1, channel);
+                // Round to nearest.
+                let average = (sum + 2) / 4;
+                if channel_size == 1 {
+                    result.push(average as u8);
+                } else {
+                    result.extend_from_slice(&(average as u16).to_ne_bytes());
+                }
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        renderer::mip_skip::{reduce, reduced_size},
+        resource::texture::{
+            TextureData, TextureKind, TextureMinificationFilter, TexturePixelKind,
+        },
+    };
+
+    fn texture(width: u32, height: u32, bytes: Vec<u8>) -> TextureData {
+        let mut texture = TextureData::from_bytes(
+            TextureKind::Rectangle { width, height },
+            TexturePixelKind::R8,
+            bytes,
+        )
+        .unwrap();
+        // Textures without path are procedural and never reduced.
+        texture.path = "test.png".into();
+        texture
+    }
+
+    #[test]
+    fn test_mip_skip() {
+        #[rustfmt::skip]
+        let texture = texture(4, 2, vec![
+            0, 4, 10, 10,
+            8, 4, 20, 20,
+        ]);
+
+        let reduced = reduce(&texture, 1).unwrap();
+        assert_eq!((reduced.width, reduced.height), (2, 1));
+        assert_eq!(reduced.bytes.as_ref(), &[4, 15]);
+
+        // Texture cannot be smaller than 1x1.
+        assert_eq!(reduced_size(&texture, 10), Some((1, 1)));
+        let reduced = reduce(&texture, 10).unwrap();
+        assert_eq!(reduced.bytes.as_ref(), &[10]);
+
+        assert!(reduce(&texture, 0).is_none());
+    }
+
+    #[test]
+    fn test_mip_skip_ignores_textures_without_mips() {
+        let mut texture = texture(2, 2, vec![0; 4]);
+        texture.set_minification_filter(TextureMinificationFilter::Linear);
+        assert!(reduce(&texture, 1).is_none());
+    }
+}
@@ -1,15 +1,42 @@
 //! Renderer is a "workhorse" of the engine, it draws scenes and user interface.
-//! For now there is almost no possibility to change pipeline of renderer, you
-//! can only modify quality settings. This will change in future to make renderer
-//! more flexible.
+//! Pipeline of renderer is fixed, but it can be extended by custom full-screen passes
+//! which run before tone mapping, see `frame_graph` module.
 //!
 //! Renderer based on OpenGL 3.3+ Core.
+//!
+//! # Color space
+//!
+//! Lighting is done in linear color space: color textures (see `TextureColorSpace`) are
+//! converted from sRGB to linear space when sampled, and the frame is rendered into
+//! floating-point (HDR) buffer. When the frame is drawn on screen it is tone mapped and
+//! converted back to display color space using gamma, see `Renderer::set_tone_mapping` and
+//! `Renderer::set_gamma`.
+//!
+//! # Color grading
+//!
+//! Final image can be color graded using 3D lookup tables, see `color_grading` module.
+//!
+//! # Anti-aliasing
+//!
+//! Geometry which is rendered in forward manner (particle systems, sprites, transparent surfaces
+//! and debug geometry) can be anti-aliased by hardware multisampling, see `Renderer::set_msaa`.
+//!
+//! # Outlines
+//!
+//! Meshes can be highlighted by colored outline, see `Base::set_outline` and
+//! `Renderer::set_outline_thickness`.
+//!
+//! # Picking
+//!
+//! Mesh under a pixel of the window can be found on GPU, see `Renderer::pick`.
 
 #![warn(missing_docs)]
 //#![deny(unsafe_code)]
 
+pub mod color_grading;
 pub mod debug_renderer;
 pub mod error;
+pub mod frame_graph;
 pub mod surface;
 
 // Framework wraps all OpenGL calls so it has to be unsafe. Rest of renderer
@@ -22,12 +49,21 @@ mod batch;
 mod blur;
 mod deferred_light_renderer;
 mod flat_shader;
+mod fog;
+mod forward_renderer;
 mod gbuffer;
 mod light_volume;
+mod mip_skip;
+mod msaa;
+mod occlusion;
+mod outline;
 mod particle_system_renderer;
+mod picking;
 mod shadow_map_renderer;
 mod sprite_renderer;
 mod ssao;
+mod tone_mapping;
+mod transparent;
 mod ui_renderer;
 
 use crate::utils::log::{Log, MessageKind};
@@ -42,15 +78,17 @@ use crate::{
     engine::resource_manager::TimedEntry,
     gui::draw::DrawingContext,
     renderer::{
-        batch::{BatchStorage, InstanceData},
+        batch::{BatchStorage, DrawDataCacheStatistics, InstanceData},
+        color_grading::{ColorGrading, ColorGradingLut},
         debug_renderer::DebugRenderer,
         deferred_light_renderer::{
             DeferredLightRenderer, DeferredRendererContext, LightingStatistics,
         },
         error::RendererError,
-        flat_shader::FlatShader,
+        forward_renderer::{ForwardRenderContext, ForwardRenderer},
+        frame_graph::{CustomRenderPass, FrameGraph},
         framework::{
-            framebuffer::{BackBuffer, CullFace, DrawParameters, FrameBufferTrait},
+            framebuffer::{self, BackBuffer, CullFace, DrawParameters, FrameBufferTrait},
             geometry_buffer::{
                 AttributeDefinition, AttributeKind, BufferBuilder, DrawCallStatistics, ElementKind,
                 GeometryBuffer, GeometryBufferBuilder, GeometryBufferKind,
@@ -58,16 +96,22 @@ use crate::{
             gl,
             gpu_program::UniformValue,
             gpu_texture::{
-                Coordinate, GpuTexture, GpuTextureKind, MagnificationFilter, MinificationFilter,
-                PixelKind,
+                self, Coordinate, GpuTexture, GpuTextureKind, MagnificationFilter,
+                MinificationFilter, PixelKind,
             },
             state::{PipelineState, PipelineStatistics},
         },
         gbuffer::{GBuffer, GBufferRenderContext},
+        msaa::MsaaRenderer,
+        occlusion::{OcclusionCuller, OcclusionRenderContext, OcclusionRenderer},
+        outline::{OutlineRenderContext, OutlineRenderer},
         particle_system_renderer::{ParticleSystemRenderContext, ParticleSystemRenderer},
+        picking::{PickingRenderContext, PickingRenderer},
         sprite_renderer::{SpriteRenderContext, SpriteRenderer},
         surface::SurfaceSharedData,
-        ui_renderer::{UiRenderContext, UiRenderer},
+        tone_mapping::ToneMappingShader,
+        transparent::{TransparentDrawable, TransparentQueue},
+        ui_renderer::{UiRenderContext, UiRenderTarget, UiRenderer},
     },
     resource::texture::{Texture, TextureKind, TextureState},
     scene::{node::Node, Scene, SceneContainer},
@@ -76,9 +120,9 @@ use glutin::PossiblyCurrent;
 use std::collections::hash_map::Entry;
 use std::{
     cell::RefCell,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::{Display, Formatter},
-    ops::Deref,
+    ops::DerefMut,
     rc::Rc,
     time,
 };
@@ -93,6 +137,9 @@ pub struct Statistics {
     pub lighting: LightingStatistics,
     /// Shows how many draw calls was made and how many triangles were rendered.
     pub geometry: RenderPassStatistics,
+    /// Shows how many draw commands of user interfaces were merged into draw calls. Draw
+    /// calls of interfaces are included in `geometry` too.
+    pub ui: UiStatistics,
     /// Real time consumed to render frame. Time given in **seconds**.
     pub pure_frame_time: f32,
     /// Total time renderer took to process single frame, usually includes
@@ -101,6 +148,11 @@ pub struct Statistics {
     pub capped_frame_time: f32,
     /// Total amount of frames been rendered in one second.
     pub frames_per_second: usize,
+    /// Amount of meshes that were inside of camera frustum, but skipped because they're
+    /// hidden by other geometry. Counted only for cameras with enabled occlusion culling.
+    pub occlusion_culled_objects: usize,
+    /// Shows how many meshes reused their prepared draw data instead of collecting it again.
+    pub draw_data_cache: DrawDataCacheStatistics,
     frame_counter: usize,
     frame_start_time: time::Instant,
     last_fps_commit_time: time::Instant,
@@ -115,11 +167,17 @@ impl Display for Statistics {
             Capped Frame Time: {} ms\n\
             {}\n\
             {}\n\
+            Occlusion Culled Objects: {}\n\
+            {}\n\
+            {}\n\
             {}\n",
             self.frames_per_second,
             self.pure_frame_time * 1000.0,
             self.capped_frame_time * 1000.0,
             self.geometry,
+            self.ui,
+            self.occlusion_culled_objects,
+            self.draw_data_cache,
             self.lighting,
             self.pipeline
         )
@@ -175,6 +233,40 @@ impl std::ops::AddAssign<RenderPassStatistics> for Statistics {
     }
 }
 
+/// User interface rendering statistics for single frame.
+#[derive(Copy, Clone, Default)]
+pub struct UiStatistics {
+    /// Amount of draw commands produced by widgets.
+    pub commands: usize,
+    /// Draw calls and triangles, commands with the same texture, brush and clipping are
+    /// drawn by one draw call.
+    pub geometry: RenderPassStatistics,
+}
+
+impl Display for UiStatistics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "UI: {} commands in {} draw calls",
+            self.commands, self.geometry.draw_calls
+        )
+    }
+}
+
+impl std::ops::AddAssign for UiStatistics {
+    fn add_assign(&mut self, rhs: Self) {
+        self.commands += rhs.commands;
+        self.geometry += rhs.geometry;
+    }
+}
+
+impl std::ops::AddAssign<UiStatistics> for Statistics {
+    fn add_assign(&mut self, rhs: UiStatistics) {
+        self.geometry += rhs.geometry;
+        self.ui += rhs;
+    }
+}
+
 /// Shadow map precision allows you to select compromise between quality and performance.
 #[derive(Copy, Clone, Hash, PartialOrd, PartialEq, Eq, Ord)]
 pub enum ShadowMapPrecision {
@@ -191,7 +283,8 @@ pub enum ShadowMapPrecision {
 #[derive(Copy, Clone, PartialEq)]
 pub struct QualitySettings {
     /// Point shadows
-    /// Size of cube map face of shadow map texture in pixels.
+    /// Size of cube map face of shadow map texture in pixels. Each point light can use
+    /// half or quarter of this size, see `PointLight::set_shadow_map_resolution`.
     pub point_shadow_map_size: usize,
     /// Use or not percentage close filtering (smoothing) for point shadows.
     pub point_soft_shadows: bool,
@@ -225,6 +318,54 @@ pub struct QualitySettings {
     /// Global switch to enable or disable light scattering. Each light can have
     /// its own scatter switch, but this one is able to globally disable scatter.
     pub light_scatter_enabled: bool,
+    /// Amount of ray marching steps through light volume to check shadow map visibility.
+    /// Zero means that only analytic light volume will be used, so shadows won't occlude
+    /// light shafts.
+    pub light_scatter_steps: usize,
+    /// Resolution of buffer which is used to accumulate scattered light.
+    pub light_scatter_resolution: LightScatterResolution,
+
+    /// Anisotropic filtering level of textures which do not have level of their own (see
+    /// `TextureData::set_anisotropy_level`). Clamped to maximum supported by GPU, 1.0 disables
+    /// anisotropic filtering.
+    pub anisotropy: f32,
+    /// Amount of most detailed mip levels which are dropped when textures are uploaded to GPU,
+    /// each level halves width and height of a texture. Allows to save video memory on weak
+    /// machines, zero means full resolution. Only mip-mapped 2D textures loaded from files are
+    /// affected.
+    pub texture_mip_skip: usize,
+}
+
+/// Operator which maps colors of HDR frame to displayable [0; 1] range.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ToneMapping {
+    /// Colors are just clamped to [0; 1] range, bright areas lose details.
+    None = 0,
+    /// Reinhard operator `c / (c + 1)`, smoothly compresses bright colors.
+    Reinhard = 1,
+}
+
+/// Resolution of light scattering buffer relative to frame size.
+#[derive(Copy, Clone, Debug, Hash, PartialOrd, PartialEq, Eq, Ord)]
+pub enum LightScatterResolution {
+    /// Same size as frame, highest quality but slowest.
+    Full,
+    /// Two times smaller than frame on each axis, good balance between quality and performance.
+    Half,
+    /// Four times smaller than frame on each axis, fastest but light shafts may look blurry.
+    Quarter,
+}
+
+impl LightScatterResolution {
+    /// Returns frame size divisor for this resolution.
+    pub fn divisor(self) -> usize {
+        match self {
+            LightScatterResolution::Full => 1,
+            LightScatterResolution::Half => 2,
+            LightScatterResolution::Quarter => 4,
+        }
+    }
 }
 
 impl Default for QualitySettings {
@@ -251,9 +392,14 @@ impl QualitySettings {
             ssao_radius: 0.5,
 
             light_scatter_enabled: true,
+            light_scatter_steps: 32,
+            light_scatter_resolution: LightScatterResolution::Half,
 
             point_shadow_map_precision: ShadowMapPrecision::Full,
             spot_shadow_map_precision: ShadowMapPrecision::Full,
+
+            anisotropy: 16.0,
+            texture_mip_skip: 0,
         }
     }
 
@@ -274,9 +420,14 @@ impl QualitySettings {
             ssao_radius: 0.5,
 
             light_scatter_enabled: true,
+            light_scatter_steps: 16,
+            light_scatter_resolution: LightScatterResolution::Half,
 
             point_shadow_map_precision: ShadowMapPrecision::Half,
             spot_shadow_map_precision: ShadowMapPrecision::Half,
+
+            anisotropy: 16.0,
+            texture_mip_skip: 0,
         }
     }
 
@@ -297,9 +448,14 @@ impl QualitySettings {
             ssao_radius: 0.5,
 
             light_scatter_enabled: false,
+            light_scatter_steps: 8,
+            light_scatter_resolution: LightScatterResolution::Quarter,
 
             point_shadow_map_precision: ShadowMapPrecision::Half,
             spot_shadow_map_precision: ShadowMapPrecision::Half,
+
+            anisotropy: 4.0,
+            texture_mip_skip: 0,
         }
     }
 
@@ -320,9 +476,145 @@ impl QualitySettings {
             ssao_radius: 0.5,
 
             light_scatter_enabled: false,
+            light_scatter_steps: 0,
+            light_scatter_resolution: LightScatterResolution::Quarter,
 
             point_shadow_map_precision: ShadowMapPrecision::Half,
             spot_shadow_map_precision: ShadowMapPrecision::Half,
+
+            anisotropy: 1.0,
+            texture_mip_skip: 1,
+        }
+    }
+
+    /// Checks settings for invalid values and returns list of problems found. Empty list
+    /// means that settings can be safely applied to renderer.
+    pub fn validate(&self) -> Vec<QualitySettingsProblem> {
+        let mut problems = Vec::new();
+
+        for (size, kind) in [
+            (self.point_shadow_map_size, ShadowMapKind::Point),
+            (self.spot_shadow_map_size, ShadowMapKind::Spot),
+        ]
+        .iter()
+        {
+            if *size == 0 {
+                problems.push(QualitySettingsProblem::ZeroShadowMapSize(*kind));
+            } else if *size > MAX_SHADOW_MAP_SIZE {
+                problems.push(QualitySettingsProblem::ShadowMapTooLarge {
+                    kind: *kind,
+                    size: *size,
+                });
+            }
+        }
+
+        for (distance, kind) in [
+            (self.point_shadows_distance, ShadowMapKind::Point),
+            (self.spot_shadows_distance, ShadowMapKind::Spot),
+        ]
+        .iter()
+        {
+            if !distance.is_finite() || *distance < 0.0 {
+                problems.push(QualitySettingsProblem::InvalidShadowsDistance {
+                    kind: *kind,
+                    distance: *distance,
+                });
+            }
+        }
+
+        if !self.ssao_radius.is_finite() || self.ssao_radius <= 0.0 {
+            problems.push(QualitySettingsProblem::InvalidSsaoRadius(self.ssao_radius));
+        }
+
+        if !self.anisotropy.is_finite() || self.anisotropy < 1.0 {
+            problems.push(QualitySettingsProblem::InvalidAnisotropy(self.anisotropy));
+        }
+
+        if self.light_scatter_steps > MAX_LIGHT_SCATTER_STEPS {
+            problems.push(QualitySettingsProblem::TooManyLightScatterSteps(
+                self.light_scatter_steps,
+            ));
+        }
+
+        problems
+    }
+}
+
+/// Maximum size of shadow map side in pixels. Larger values are not supported by most
+/// of GPUs.
+pub const MAX_SHADOW_MAP_SIZE: usize = 16384;
+
+/// Maximum amount of ray marching steps for light scattering.
+pub const MAX_LIGHT_SCATTER_STEPS: usize = 128;
+
+/// Kind of shadow map which quality settings problem is related to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShadowMapKind {
+    /// Cube shadow map of point lights.
+    Point,
+    /// Square shadow map of spot lights.
+    Spot,
+}
+
+/// Single problem found by [`QualitySettings::validate`](struct.QualitySettings.html#method.validate).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum QualitySettingsProblem {
+    /// Shadow map has zero size. Use 1 if you want to disable shadows, or better turn
+    /// them off by appropriate flag.
+    ZeroShadowMapSize(ShadowMapKind),
+    /// Shadow map is larger than [`MAX_SHADOW_MAP_SIZE`](constant.MAX_SHADOW_MAP_SIZE.html).
+    ShadowMapTooLarge {
+        /// Kind of shadow map.
+        kind: ShadowMapKind,
+        /// Requested size.
+        size: usize,
+    },
+    /// Shadows distance is negative, infinite or NaN.
+    InvalidShadowsDistance {
+        /// Kind of shadow map.
+        kind: ShadowMapKind,
+        /// Requested distance.
+        distance: f32,
+    },
+    /// SSAO radius is zero, negative, infinite or NaN.
+    InvalidSsaoRadius(f32),
+    /// Amount of light scatter steps exceeds [`MAX_LIGHT_SCATTER_STEPS`](constant.MAX_LIGHT_SCATTER_STEPS.html).
+    TooManyLightScatterSteps(usize),
+    /// Anisotropy level is less than 1.0, infinite or NaN.
+    InvalidAnisotropy(f32),
+}
+
+impl Display for QualitySettingsProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QualitySettingsProblem::ZeroShadowMapSize(kind) => {
+                write!(f, "{:?} shadow map size must be non-zero", kind)
+            }
+            QualitySettingsProblem::ShadowMapTooLarge { kind, size } => write!(
+                f,
+                "{:?} shadow map size {} exceeds maximum of {}",
+                kind, size, MAX_SHADOW_MAP_SIZE
+            ),
+            QualitySettingsProblem::InvalidShadowsDistance { kind, distance } => write!(
+                f,
+                "{:?} shadows distance {} must be finite and non-negative",
+                kind, distance
+            ),
+            QualitySettingsProblem::InvalidSsaoRadius(radius) => write!(
+                f,
+                "SSAO radius {} must be finite and greater than zero",
+                radius
+            ),
+            QualitySettingsProblem::TooManyLightScatterSteps(steps) => write!(
+                f,
+                "light scatter steps {} exceeds maximum of {}",
+                steps, MAX_LIGHT_SCATTER_STEPS
+            ),
+            QualitySettingsProblem::InvalidAnisotropy(anisotropy) => write!(
+                f,
+                "anisotropy level {} must be finite and at least 1.0",
+                anisotropy
+            ),
         }
     }
 }
@@ -332,7 +624,10 @@ impl Statistics {
     fn begin_frame(&mut self) {
         self.frame_start_time = time::Instant::now();
         self.geometry = Default::default();
+        self.ui = Default::default();
         self.lighting = Default::default();
+        self.occlusion_culled_objects = 0;
+        self.draw_data_cache = Default::default();
     }
 
     /// Must be called before SwapBuffers but after all rendering is done.
@@ -369,9 +664,12 @@ impl Default for Statistics {
             pipeline: Default::default(),
             lighting: Default::default(),
             geometry: Default::default(),
+            ui: Default::default(),
             pure_frame_time: 0.0,
             capped_frame_time: 0.0,
             frames_per_second: 0,
+            occlusion_culled_objects: 0,
+            draw_data_cache: Default::default(),
             frame_counter: 0,
             frame_start_time: time::Instant::now(),
             last_fps_commit_time: time::Instant::now(),
@@ -379,14 +677,38 @@ impl Default for Statistics {
     }
 }
 
+/// Result of `Renderer::pick`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PickResult {
+    /// Picking at given position is scheduled, result will be available in one of next frames.
+    Pending,
+    /// There is no mesh at given position.
+    Nothing,
+    /// Mesh at given position in one of previous frames. The node could be removed since then,
+    /// so the handles must be checked before use (see `Graph::is_valid_handle`).
+    Node {
+        /// Scene of the node.
+        scene: Handle<Scene>,
+        /// Handle of the node.
+        node: Handle<Node>,
+    },
+}
+
 /// See module docs.
 pub struct Renderer {
     state: PipelineState,
     backbuffer: BackBuffer,
     deferred_light_renderer: DeferredLightRenderer,
-    flat_shader: FlatShader,
+    tone_mapping_shader: ToneMappingShader,
+    msaa_renderer: MsaaRenderer,
+    occlusion_renderer: OcclusionRenderer,
+    outline_renderer: OutlineRenderer,
+    picking_renderer: PickingRenderer,
     sprite_renderer: SpriteRenderer,
     particle_system_renderer: ParticleSystemRenderer,
+    forward_renderer: ForwardRenderer,
+    /// Sprites, particle systems and transparent surfaces sorted for current camera.
+    transparent_queue: TransparentQueue,
     /// Dummy white one pixel texture which will be used as stub when rendering
     /// something without texture specified.
     white_dummy: Rc<RefCell<GpuTexture>>,
@@ -398,11 +720,24 @@ pub struct Renderer {
     /// Dummy one pixel texture used as stub when rendering something without a
     /// specular texture
     specular_dummy: Rc<RefCell<GpuTexture>>,
+    /// Neutral 2x2x2 lookup table used as stub when color grading has only one table.
+    neutral_lut_dummy: Rc<RefCell<GpuTexture>>,
     ui_renderer: UiRenderer,
+    /// Off-screen targets of interfaces rendered to textures, texture key to target mapping.
+    /// Texture is kept to find out when it is not used anymore, see `render_frame`.
+    ui_render_targets: HashMap<usize, (Texture, UiRenderTarget)>,
+    frame_graph: FrameGraph,
     statistics: Statistics,
     quad: SurfaceSharedData,
     frame_size: (u32, u32),
     ambient_color: Color,
+    tone_mapping: ToneMapping,
+    exposure: f32,
+    gamma: f32,
+    color_grading: ColorGrading,
+    // Time since renderer creation, it is used to animate film grain.
+    time: f32,
+    msaa_samples: u32,
     quality_settings: QualitySettings,
     /// Debug renderer instance can be used for debugging purposes
     pub debug_renderer: DebugRenderer,
@@ -412,6 +747,10 @@ pub struct Renderer {
     texture_cache: TextureCache,
     geometry_cache: GeometryCache,
     batch_storage: BatchStorage,
+    /// Occlusion state of each camera (with enabled occlusion culling) of each scene.
+    occlusion_cullers: HashMap<(Handle<Scene>, Handle<Node>), OcclusionCuller>,
+    occlusion_culling: bool,
+    outline_thickness: u32,
 }
 
 #[derive(Default)]
@@ -426,7 +765,7 @@ impl GeometryCache {
         let key = (data as *const _) as usize;
 
         let geometry_buffer = self.map.entry(key).or_insert_with(|| {
-            let geometry_buffer = GeometryBufferBuilder::new(ElementKind::Triangle)
+            let mut builder = GeometryBufferBuilder::new(ElementKind::Triangle)
                 .with_buffer_builder(
                     BufferBuilder::new(
                         GeometryBufferKind::StaticDraw,
@@ -516,9 +855,24 @@ impl GeometryCache {
                             normalized: false,
                             divisor: 1,
                         }),
-                )
-                .build(state)
-                .unwrap();
+                );
+
+            // Vertex colors are optional, so they're stored in separate buffer which exists only
+            // if surface has colors. Location is after per-instance attributes to keep locations
+            // of those attributes unchanged.
+            if let Some(colors) = data.colors.as_ref() {
+                builder = builder.with_buffer_builder(
+                    BufferBuilder::new(GeometryBufferKind::StaticDraw, Some(colors.as_slice()))
+                        .with_attribute(AttributeDefinition {
+                            location: 13,
+                            divisor: 0,
+                            kind: AttributeKind::UnsignedByte4,
+                            normalized: true,
+                        }),
+                );
+            }
+
+            let geometry_buffer = builder.build(state).unwrap();
 
             geometry_buffer.bind(state).set_triangles(data.triangles());
 
@@ -546,12 +900,22 @@ impl GeometryCache {
     }
 }
 
-#[derive(Default)]
 pub(in crate) struct TextureCache {
     map: HashMap<usize, TimedEntry<Rc<RefCell<GpuTexture>>>>,
+    // Global texture settings, see `QualitySettings`.
+    anisotropy: f32,
+    mip_skip: usize,
 }
 
 impl TextureCache {
+    fn new(settings: &QualitySettings) -> Self {
+        Self {
+            map: Default::default(),
+            anisotropy: settings.anisotropy,
+            mip_skip: settings.texture_mip_skip,
+        }
+    }
+
     fn get(
         &mut self,
         state: &mut PipelineState,
@@ -560,20 +924,77 @@ impl TextureCache {
         scope_profile!();
 
         let key = texture.key();
-        let texture = texture.state();
+        let mut texture = texture.state();
+
+        if let TextureState::Ok(texture) = texture.deref_mut() {
+            // Texture must be uploaded again if amount of skipped mips has changed, pixels are
+            // still in memory so there is no need to load it from disk. Procedural textures are
+            // never reduced, moreover render targets are put in the cache with different size.
+            if let TextureKind::Rectangle { width, height } = texture.kind {
+                if !texture.is_procedural() {
+                    let size = mip_skip::reduced_size(texture, self.mip_skip)
+                        .unwrap_or((width as usize, height as usize));
+                    let outdated = self.map.get(&key).map_or(false, |entry| {
+                        match entry.value.borrow().kind() {
+                            GpuTextureKind::Rectangle { width, height } => (width, height) != size,
+                            _ => false,
+                        }
+                    });
+                    if outdated {
+                        self.map.remove(&key);
+                    }
+                }
+            }
 
-        if let TextureState::Ok(texture) = texture.deref() {
             let gpu_texture = match self.map.entry(key) {
-                Entry::Occupied(e) => e.into_mut(),
+                Entry::Occupied(e) => {
+                    let gpu_texture = e.into_mut();
+                    // Upload only modified pixels instead of re-creating whole texture.
+                    if let Some(region) = texture.modified_region.take() {
+                        if let TextureKind::Rectangle { width, .. } = texture.kind {
+                            gpu_texture.borrow_mut().bind_mut(state, 0).set_sub_data(
+                                region.x() as usize,
+                                region.y() as usize,
+                                region.w() as usize,
+                                region.h() as usize,
+                                width as usize,
+                                &texture.bytes,
+                            );
+                        }
+                    }
+                    gpu_texture
+                }
                 Entry::Vacant(e) => {
+                    // Whole texture will be uploaded.
+                    texture.modified_region = None;
+
+                    let pixel_kind = PixelKind::from_texture(texture);
+
+                    let reduced = mip_skip::reduce(texture, self.mip_skip);
+                    let (kind, mip_count, bytes) = match reduced.as_ref() {
+                        Some(reduced) => (
+                            GpuTextureKind::Rectangle {
+                                width: reduced.width,
+                                height: reduced.height,
+                            },
+                            reduced.mip_count,
+                            reduced.bytes.as_ref(),
+                        ),
+                        None => (
+                            texture.kind.into(),
+                            texture.mip_count() as usize,
+                            texture.bytes.as_slice(),
+                        ),
+                    };
+
                     let gpu_texture = match GpuTexture::new(
                         state,
-                        texture.kind.into(),
-                        PixelKind::from(texture.pixel_kind),
+                        kind,
+                        pixel_kind,
                         texture.minification_filter().into(),
                         texture.magnification_filter().into(),
-                        texture.mip_count() as usize,
-                        Some(texture.bytes.as_slice()),
+                        mip_count,
+                        Some(bytes),
                     ) {
                         Ok(texture) => texture,
                         Err(e) => {
@@ -608,15 +1029,12 @@ impl TextureCache {
                     .set_minification_filter(new_min_filter);
             }
 
-            if gpu_texture
-                .borrow()
-                .anisotropy()
-                .ne(&texture.anisotropy_level())
-            {
+            let anisotropy = texture.custom_anisotropy_level().unwrap_or(self.anisotropy);
+            if gpu_texture.borrow().anisotropy().ne(&anisotropy) {
                 gpu_texture
                     .borrow_mut()
                     .bind_mut(state, 0)
-                    .set_anisotropy(texture.anisotropy_level());
+                    .set_anisotropy(anisotropy);
             }
 
             let new_s_wrap_mode = texture.s_wrap_mode().into();
@@ -671,7 +1089,11 @@ impl Renderer {
             backbuffer: BackBuffer,
             frame_size,
             deferred_light_renderer: DeferredLightRenderer::new(&mut state, frame_size, &settings)?,
-            flat_shader: FlatShader::new()?,
+            tone_mapping_shader: ToneMappingShader::new()?,
+            msaa_renderer: MsaaRenderer::new()?,
+            occlusion_renderer: OcclusionRenderer::new()?,
+            outline_renderer: OutlineRenderer::new()?,
+            picking_renderer: PickingRenderer::new()?,
             statistics: Statistics::default(),
             sprite_renderer: SpriteRenderer::new()?,
             white_dummy: Rc::new(RefCell::new(GpuTexture::new(
@@ -741,18 +1163,48 @@ impl Renderer {
                 1,
                 Some(&[32u8, 32u8, 32u8, 32u8]),
             )?)),
+            neutral_lut_dummy: Rc::new(RefCell::new(GpuTexture::new(
+                &mut state,
+                GpuTextureKind::Volume {
+                    width: 2,
+                    height: 2,
+                    depth: 2,
+                },
+                PixelKind::RGBA8,
+                MinificationFilter::Linear,
+                MagnificationFilter::Linear,
+                1,
+                Some(&[
+                    0u8, 0u8, 0u8, 255u8, 255u8, 0u8, 0u8, 255u8, 0u8, 255u8, 0u8, 255u8, 255u8,
+                    255u8, 0u8, 255u8, 0u8, 0u8, 255u8, 255u8, 255u8, 0u8, 255u8, 255u8, 0u8,
+                    255u8, 255u8, 255u8, 255u8, 255u8, 255u8, 255u8,
+                ]),
+            )?)),
             quad: SurfaceSharedData::make_unit_xy_quad(),
             ui_renderer: UiRenderer::new(&mut state)?,
+            ui_render_targets: Default::default(),
+            frame_graph: FrameGraph::new()?,
             particle_system_renderer: ParticleSystemRenderer::new(&mut state)?,
+            forward_renderer: ForwardRenderer::new()?,
+            transparent_queue: Default::default(),
             ambient_color: Color::opaque(100, 100, 100),
+            tone_mapping: ToneMapping::None,
+            exposure: 1.0,
+            gamma: 2.2,
+            color_grading: Default::default(),
+            time: 0.0,
+            msaa_samples: 0,
             quality_settings: settings,
             debug_renderer: DebugRenderer::new(&mut state)?,
             gbuffers: Default::default(),
             backbuffer_clear_color: Color::from_rgba(0, 0, 0, 0),
-            texture_cache: Default::default(),
+            texture_cache: TextureCache::new(&settings),
             geometry_cache: Default::default(),
             state,
             batch_storage: Default::default(),
+            occlusion_cullers: Default::default(),
+            occlusion_culling: true,
+            outline_thickness: 2,
         })
     }
 
@@ -766,6 +1218,148 @@ impl Renderer {
         self.ambient_color
     }
 
+    /// Sets new tone mapping operator which is used to map HDR frame to displayable [0; 1]
+    /// range when frame is drawn on screen. Default is `ToneMapping::None`.
+    pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) {
+        self.tone_mapping = tone_mapping;
+    }
+
+    /// Returns current tone mapping operator.
+    pub fn tone_mapping(&self) -> ToneMapping {
+        self.tone_mapping
+    }
+
+    /// Sets new exposure, frame colors are multiplied by exposure before tone mapping.
+    /// Default is 1.0, negative values are clamped to zero.
+    pub fn set_exposure(&mut self, exposure: f32) {
+        self.exposure = exposure.max(0.0);
+    }
+
+    /// Returns current exposure.
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    /// Sets new gamma of display, it is used to convert linear colors of frame to display
+    /// color space. Default is 2.2 which is close to sRGB, 1.0 disables gamma correction.
+    /// Values less than 0.1 are clamped to 0.1.
+    pub fn set_gamma(&mut self, gamma: f32) {
+        self.gamma = gamma.max(0.1);
+    }
+
+    /// Returns current gamma.
+    pub fn gamma(&self) -> f32 {
+        self.gamma
+    }
+
+    /// Sets new color grading settings, see `color_grading` module docs for more info.
+    pub fn set_color_grading(&mut self, color_grading: ColorGrading) {
+        self.color_grading = color_grading;
+    }
+
+    /// Returns current color grading settings.
+    pub fn color_grading(&self) -> &ColorGrading {
+        &self.color_grading
+    }
+
+    /// Returns current color grading settings, could be used to change blend factor of
+    /// lookup tables.
+    pub fn color_grading_mut(&mut self) -> &mut ColorGrading {
+        &mut self.color_grading
+    }
+
+    /// Sets amount of samples per pixel for multisample anti-aliasing of forward rendered
+    /// geometry, 0 or 1 disables MSAA (default). Requested amount is rounded down to power
+    /// of two and clamped to maximum supported by GPU, actual amount of samples is returned.
+    /// If multisampled frame buffer cannot be created, MSAA is silently disabled and warning
+    /// is written to the log.
+    pub fn set_msaa(&mut self, samples: u32) -> u32 {
+        let max_samples = framebuffer::max_samples();
+        let mut actual_samples = samples.min(max_samples);
+        if actual_samples > 1 {
+            // Drivers are not required to support non-power-of-two amount of samples.
+            actual_samples = 1 << (31 - actual_samples.leading_zeros());
+        } else {
+            actual_samples = 0;
+        }
+
+        if actual_samples != samples && samples > 1 {
+            Log::writeln(
+                MessageKind::Warning,
+                format!(
+                    "{}x MSAA is not supported, {}x MSAA will be used instead. Max samples: {}",
+                    samples, actual_samples, max_samples
+                ),
+            );
+        }
+
+        if self.msaa_samples != actual_samples {
+            self.msaa_samples = actual_samples;
+            // G-buffers will be re-created with new amount of samples.
+            self.gbuffers.clear();
+        }
+
+        actual_samples
+    }
+
+    /// Returns maximum anisotropy level supported by GPU, anisotropy levels of textures (see
+    /// `TextureData::set_anisotropy_level`) and quality settings are clamped to it. 1.0 means
+    /// that anisotropic filtering is not supported.
+    pub fn max_anisotropy(&self) -> f32 {
+        gpu_texture::max_anisotropy()
+    }
+
+    /// Returns current amount of samples per pixel for multisample anti-aliasing, zero means
+    /// that MSAA is disabled.
+    pub fn msaa(&self) -> u32 {
+        self.msaa_samples
+    }
+
+    /// Globally enables or disables occlusion culling, it is enabled by default. When enabled,
+    /// occlusion culling is performed for every camera with `Camera::set_occlusion_culling`
+    /// set, when disabled - it is not performed for any camera. Could be useful to quickly
+    /// turn off occlusion culling on hardware where it makes things slower.
+    pub fn set_occlusion_culling(&mut self, enabled: bool) {
+        if !enabled {
+            self.occlusion_cullers.clear();
+        }
+        self.occlusion_culling = enabled;
+    }
+
+    /// Returns true if occlusion culling is globally enabled.
+    pub fn is_occlusion_culling_enabled(&self) -> bool {
+        self.occlusion_culling
+    }
+
+    /// Sets thickness of outlines of nodes in pixels, it is clamped to [1; 16] range. Default
+    /// thickness is 2 pixels. See `Base::set_outline`.
+    pub fn set_outline_thickness(&mut self, thickness: u32) {
+        self.outline_thickness = thickness.clamp(1, 16);
+    }
+
+    /// Returns thickness of outlines in pixels.
+    pub fn outline_thickness(&self) -> u32 {
+        self.outline_thickness
+    }
+
+    /// Requests a mesh under given pixel of the window, origin is at the top-left corner (the
+    /// same as cursor position). Meshes are drawn with unique ids by the top-most camera under
+    /// the pixel, scenes with render targets are ignored.
+    ///
+    /// Result is read back from GPU asynchronously, so the renderer never stalls waiting for
+    /// GPU. First call for a position returns `PickResult::Pending`, next calls return result
+    /// of one of previous frames (usually the previous one). Picking is performed only in
+    /// frames when it was requested, so call it each frame to keep result up to date.
+    ///
+    /// # Notes
+    ///
+    /// Returned handle belongs to the scene as it was when the frame was rendered, the node
+    /// could be removed (and its handle could become invalid) since then, so always check
+    /// the handle before use.
+    pub fn pick(&mut self, x: u32, y: u32) -> PickResult {
+        self.picking_renderer.pick(Vector2::new(x, y))
+    }
+
     /// Returns statistics for last frame.
     pub fn get_statistics(&self) -> Statistics {
         self.statistics
@@ -776,20 +1370,48 @@ impl Renderer {
         self.backbuffer_clear_color = color;
     }
 
-    /// Sets new frame size, should be called when received a Resize event.
+    /// Sets new frame size. Engine calls this method automatically before rendering when size
+    /// of the window has changed, so there is no need to call it manually when you use the
+    /// engine. Size-dependent buffers are re-created only if the size has actually changed.
     ///
     /// # Notes
     ///
-    /// Input values will be set to 1 pixel if new size is 0. Rendering cannot
-    /// be performed into 0x0 texture.
+    /// Zero size is allowed (for example when window is minimized), in this case rendering
+    /// is skipped until frame size becomes non-zero again. Errors are written to the log,
+    /// use `try_set_frame_size` to handle them manually.
     pub fn set_frame_size(&mut self, new_size: (u32, u32)) {
+        if let Err(e) = self.try_set_frame_size(new_size) {
+            Log::writeln(
+                MessageKind::Error,
+                format!(
+                    "Unable to set frame size {}x{}. Reason: {:?}",
+                    new_size.0, new_size.1, e
+                ),
+            );
+        }
+    }
+
+    /// Same as `set_frame_size`, but returns an error if size-dependent buffers
+    /// cannot be re-created.
+    pub fn try_set_frame_size(&mut self, new_size: (u32, u32)) -> Result<(), RendererError> {
+        if self.frame_size == new_size {
+            return Ok(());
+        }
+
+        self.frame_size = new_size;
+
+        if self.is_frame_empty() {
+            // Keep buffers of previous size, most likely window will be restored to it.
+            return Ok(());
+        }
+
+        // G-buffers are re-created on demand when their size does not match size of a frame.
         self.deferred_light_renderer
             .set_frame_size(&mut self.state, new_size)
-            .unwrap();
-        self.frame_size.0 = new_size.0.max(1);
-        self.frame_size.1 = new_size.1.max(1);
-        // Invalidate all g-buffers.
-        self.gbuffers.clear();
+    }
+
+    fn is_frame_empty(&self) -> bool {
+        self.frame_size.0 == 0 || self.frame_size.1 == 0
     }
 
     /// Returns current (width, height) pair of back buffer size.
@@ -802,16 +1424,30 @@ impl Renderer {
         Vector2::new(self.frame_size.0 as f32, self.frame_size.1 as f32)
     }
 
-    /// Sets new quality settings for renderer. Never call this method in a loop, otherwise
-    /// you may get **significant** lags. Always check if current quality setting differs
-    /// from new!
+    /// Sets new quality settings for renderer. Settings are validated first, invalid
+    /// settings are rejected with [`RendererError::InvalidQualitySettings`] and current
+    /// settings stay untouched. Only GPU resources affected by changed values are
+    /// re-created, however changing shadow map size or precision still may cause lags.
     pub fn set_quality_settings(
         &mut self,
         settings: &QualitySettings,
     ) -> Result<(), RendererError> {
-        self.quality_settings = *settings;
+        let problems = settings.validate();
+        if !problems.is_empty() {
+            return Err(RendererError::InvalidQualitySettings(problems));
+        }
+
+        if self.quality_settings == *settings {
+            return Ok(());
+        }
+
         self.deferred_light_renderer
-            .set_quality_settings(&mut self.state, settings)
+            .set_quality_settings(&mut self.state, settings)?;
+        // Cached textures pick up new values next time they are used.
+        self.texture_cache.anisotropy = settings.anisotropy;
+        self.texture_cache.mip_skip = settings.texture_mip_skip;
+        self.quality_settings = *settings;
+        Ok(())
     }
 
     /// Returns current quality settings.
@@ -819,6 +1455,96 @@ impl Renderer {
         self.quality_settings
     }
 
+    /// Copies every user-defined setting from other renderer, it is used when renderer is
+    /// re-created for new graphics context. GPU resources are not copied, they will be
+    /// re-created on demand.
+    pub(in crate) fn inherit_settings(&mut self, other: &Renderer) -> Result<(), RendererError> {
+        // Every field is listed here on purpose: a new field won't compile until it is
+        // either inherited or explicitly skipped.
+        let Renderer {
+            state: _,
+            backbuffer: _,
+            deferred_light_renderer: _,
+            tone_mapping_shader: _,
+            msaa_renderer: _,
+            occlusion_renderer: _,
+            outline_renderer: _,
+            picking_renderer: _,
+            sprite_renderer: _,
+            particle_system_renderer: _,
+            forward_renderer: _,
+            transparent_queue: _,
+            white_dummy: _,
+            black_dummy: _,
+            environment_dummy: _,
+            normal_dummy: _,
+            specular_dummy: _,
+            neutral_lut_dummy: _,
+            ui_renderer: _,
+            ui_render_targets: _,
+            frame_graph,
+            statistics: _,
+            quad: _,
+            frame_size: _,
+            ambient_color,
+            tone_mapping,
+            exposure,
+            gamma,
+            color_grading,
+            time: _,
+            msaa_samples,
+            quality_settings,
+            debug_renderer: _,
+            gbuffers: _,
+            backbuffer_clear_color,
+            texture_cache: _,
+            geometry_cache: _,
+            batch_storage: _,
+            occlusion_cullers: _,
+            occlusion_culling,
+            outline_thickness,
+        } = other;
+
+        self.set_quality_settings(quality_settings)?;
+        self.set_msaa(*msaa_samples);
+        self.set_occlusion_culling(*occlusion_culling);
+        self.ambient_color = *ambient_color;
+        self.tone_mapping = *tone_mapping;
+        self.exposure = *exposure;
+        self.gamma = *gamma;
+        self.color_grading = color_grading.clone();
+        self.backbuffer_clear_color = *backbuffer_clear_color;
+        self.outline_thickness = *outline_thickness;
+        for pass in frame_graph.passes() {
+            self.frame_graph.add(pass.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Adds new custom render pass, see `frame_graph` module docs. Shader of the pass is
+    /// compiled immediately, pass is rejected if its shader cannot be compiled or if it
+    /// cannot be scheduled with already added passes.
+    pub fn add_render_pass(&mut self, pass: CustomRenderPass) -> Result<(), RendererError> {
+        self.frame_graph.add(pass)
+    }
+
+    /// Removes custom render pass with given name and returns it. Pass cannot be removed if
+    /// other passes read a texture written by it.
+    pub fn remove_render_pass(&mut self, name: &str) -> Result<CustomRenderPass, RendererError> {
+        self.frame_graph.remove(name)
+    }
+
+    /// Returns reference to custom render pass with given name, it can be used to change
+    /// uniforms of the pass or to disable it.
+    pub fn render_pass_mut(&mut self, name: &str) -> Option<&mut CustomRenderPass> {
+        self.frame_graph.pass_mut(name)
+    }
+
+    /// Returns iterator over custom render passes in order of execution.
+    pub fn render_passes(&self) -> impl Iterator<Item = &CustomRenderPass> {
+        self.frame_graph.passes()
+    }
+
     /// Removes all cached GPU data, forces renderer to re-upload data to GPU.
     /// Do not call this method until you absolutely need! It may cause **significant**
     /// performance lag!
@@ -827,6 +1553,113 @@ impl Renderer {
         self.geometry_cache.clear();
     }
 
+    /// Renders a user interface into given texture, so the interface can be applied to a mesh
+    /// like any other texture (in-game monitors, control panels, etc.). The texture must be
+    /// created by `Texture::new_render_target`, its size is used as screen size of the
+    /// interface, so the interface must be updated with the same size (see `UserInterface::update`).
+    /// Rendered image has the same layout as textures loaded from files - top-left corner of the
+    /// interface has `(0, 0)` texture coordinates.
+    ///
+    /// Input can be fed back to the interface by ray-casting a mesh with the texture (see
+    /// `Mesh::ray_cast_tex_coords`) and passing found texture coordinates to
+    /// `UserInterface::process_surface_cursor`.
+    ///
+    /// Off-screen target is released on next frame after every other instance of the texture
+    /// is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RendererError::InvalidRenderTarget`] if the texture is not a rectangle texture.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use rg3d::{
+    ///     core::algebra::Vector2,
+    ///     engine::Engine,
+    ///     gui::node::StubNode,
+    ///     resource::texture::Texture,
+    /// };
+    ///
+    /// type UserInterface = rg3d::gui::UserInterface<(), StubNode>;
+    ///
+    /// fn render_panel(
+    ///     engine: &mut Engine<(), StubNode>,
+    ///     panel: &mut UserInterface,
+    ///     texture: Texture,
+    ///     dt: f32,
+    /// ) {
+    ///     panel.update(Vector2::new(512.0, 512.0), dt);
+    ///     while let Some(_message) = panel.poll_message() {}
+    ///     engine
+    ///         .renderer
+    ///         .render_ui_to_texture(texture, panel.draw())
+    ///         .unwrap();
+    /// }
+    /// ```
+    pub fn render_ui_to_texture(
+        &mut self,
+        render_target: Texture,
+        drawing_context: &DrawingContext,
+    ) -> Result<(), RendererError> {
+        scope_profile!();
+
+        let (width, height) =
+            if let TextureKind::Rectangle { width, height } = render_target.data_ref().kind {
+                ((width as usize).max(1), (height as usize).max(1))
+            } else {
+                return Err(RendererError::InvalidRenderTarget);
+            };
+
+        let state = &mut self.state;
+        let (_, ui_render_target) = match self.ui_render_targets.entry(render_target.key()) {
+            Entry::Occupied(entry) => {
+                let entry = entry.into_mut();
+                if entry.1.size() != (width, height) {
+                    entry.1 = UiRenderTarget::new(state, width, height)?;
+                }
+                entry
+            }
+            Entry::Vacant(entry) => entry.insert((
+                render_target.clone(),
+                UiRenderTarget::new(state, width, height)?,
+            )),
+        };
+
+        // Register color texture of the target, so it can be used as usual texture.
+        self.texture_cache.map.insert(
+            render_target.key(),
+            TimedEntry {
+                value: ui_render_target.color_texture(),
+                time_to_live: std::f32::INFINITY,
+            },
+        );
+
+        let viewport = Rect::new(0, 0, width as i32, height as i32);
+        let frame_buffer = ui_render_target.framebuffer();
+        frame_buffer.clear(
+            state,
+            viewport,
+            Some(Color::from_rgba(0, 0, 0, 0)),
+            None,
+            Some(0),
+        );
+
+        self.statistics += self.ui_renderer.render(UiRenderContext {
+            state,
+            viewport,
+            frame_buffer,
+            frame_width: width as f32,
+            frame_height: height as f32,
+            flip_y: true,
+            drawing_context,
+            white_dummy: self.white_dummy.clone(),
+            texture_cache: &mut self.texture_cache,
+        })?;
+
+        Ok(())
+    }
+
     fn render_frame(
         &mut self,
         scenes: &SceneContainer,
@@ -841,12 +1674,33 @@ impl Renderer {
         // object have same name.
         self.state.invalidate_resource_bindings_cache();
 
+        // Wrap time to keep precision, it is used only for noise.
+        self.time = (self.time + dt) % 1000.0;
+
         // Update caches - this will remove timed out resources.
         self.geometry_cache.update(dt);
         self.texture_cache.update(dt);
 
+        // Release targets of interfaces rendered to textures that are not used anymore, their
+        // color textures never time out in texture cache, so they're removed there too.
+        let texture_cache = &mut self.texture_cache;
+        self.ui_render_targets.retain(|key, (texture, _)| {
+            let is_used = texture.use_count() > 1;
+            if !is_used {
+                texture_cache.map.remove(key);
+            }
+            is_used
+        });
+
         self.statistics.begin_frame();
 
+        // Picking works in OpenGL window coordinates.
+        let frame_height = self.frame_size.1 as i32;
+        let pick_pixel = self
+            .picking_renderer
+            .begin_frame()
+            .map(|p| Vector2::new(p.x as i32, frame_height - 1 - p.y as i32));
+
         let window_viewport = Rect::new(0, 0, self.frame_size.0 as i32, self.frame_size.1 as i32);
         self.backbuffer.clear(
             &mut self.state,
@@ -859,6 +1713,8 @@ impl Renderer {
         let backbuffer_width = self.frame_size.0 as f32;
         let backbuffer_height = self.frame_size.1 as f32;
 
+        let mut active_cullers = HashSet::new();
+
         for (scene_handle, scene) in scenes.pair_iter() {
             let graph = &scene.graph;
 
@@ -886,7 +1742,17 @@ impl Renderer {
                 self.specular_dummy.clone(),
                 &mut self.texture_cache,
             );
+            self.statistics.draw_data_cache += self.batch_storage.cache_statistics;
+
+            // Upload vertices of surfaces that were skinned on CPU.
+            for batch in self.batch_storage.batches.iter().filter(|b| b.cpu_skinned) {
+                let data = batch.data.read().unwrap();
+                self.geometry_cache
+                    .get(state, &data)
+                    .set_buffer_data(state, 0, &data.vertices);
+            }
 
+            let msaa_samples = self.msaa_samples;
             let gbuffer = self
                 .gbuffers
                 .entry(scene_handle)
@@ -894,13 +1760,13 @@ impl Renderer {
                     if buf.width != frame_size.x as i32 || buf.height != frame_size.y as i32 {
                         let width = (frame_size.x as usize).max(1);
                         let height = (frame_size.y as usize).max(1);
-                        *buf = GBuffer::new(state, width, height).unwrap();
+                        *buf = GBuffer::new(state, width, height, msaa_samples).unwrap();
                     }
                 })
                 .or_insert_with(|| {
                     let width = (frame_size.x as usize).max(1);
                     let height = (frame_size.y as usize).max(1);
-                    GBuffer::new(state, width, height).unwrap()
+                    GBuffer::new(state, width, height, msaa_samples).unwrap()
                 });
 
             // If we specified a texture to draw to, we have to register it in texture cache
@@ -918,10 +1784,10 @@ impl Renderer {
                 );
             }
 
-            for camera in graph.linear_iter().filter_map(|node| {
+            for (camera_handle, camera) in graph.pair_iter().filter_map(|(handle, node)| {
                 if let Node::Camera(camera) = node {
                     if camera.is_enabled() {
-                        Some(camera)
+                        Some((handle, camera))
                     } else {
                         None
                     }
@@ -931,6 +1797,21 @@ impl Renderer {
             }) {
                 let viewport = camera.viewport_pixels(frame_size);
 
+                let mut occlusion_culler = if self.occlusion_culling
+                    && camera.is_occlusion_culling_enabled()
+                {
+                    let key = (scene_handle, camera_handle);
+                    active_cullers.insert(key);
+                    let culler = self
+                        .occlusion_cullers
+                        .entry(key)
+                        .or_insert_with(OcclusionCuller::new);
+                    self.statistics.occlusion_culled_objects += culler.begin_frame(camera, graph);
+                    Some(culler)
+                } else {
+                    None
+                };
+
                 self.statistics += gbuffer.fill(GBufferRenderContext {
                     state,
                     camera,
@@ -938,8 +1819,40 @@ impl Renderer {
                     batch_storage: &self.batch_storage,
                     texture_cache: &mut self.texture_cache,
                     environment_dummy: self.environment_dummy.clone(),
+                    white_dummy: self.white_dummy.clone(),
+                    normal_dummy: self.normal_dummy.clone(),
+                    occlusion_culler: occlusion_culler.as_deref(),
+                    graph,
                 });
 
+                if let (Some(pixel), None) = (pick_pixel, scene.render_target.as_ref()) {
+                    self.statistics.geometry += self.picking_renderer.render(
+                        PickingRenderContext {
+                            state,
+                            scene: scene_handle,
+                            graph,
+                            camera,
+                            viewport,
+                            batch_storage: &self.batch_storage,
+                            geometry_cache: &mut self.geometry_cache,
+                        },
+                        pixel,
+                    )?;
+                }
+
+                if let Some(culler) = occlusion_culler.as_mut() {
+                    self.statistics += self.occlusion_renderer.render(OcclusionRenderContext {
+                        state,
+                        framebuffer: &mut gbuffer.final_frame,
+                        viewport: Rect::new(0, 0, gbuffer.width, gbuffer.height),
+                        graph,
+                        camera,
+                        culler,
+                        geometry_cache: &mut self.geometry_cache,
+                        white_dummy: self.white_dummy.clone(),
+                    });
+                }
+
                 let (pass_stats, light_stats) =
                     self.deferred_light_renderer
                         .render(DeferredRendererContext {
@@ -960,47 +1873,135 @@ impl Renderer {
 
                 let depth = gbuffer.depth();
 
-                self.statistics +=
-                    self.particle_system_renderer
-                        .render(ParticleSystemRenderContext {
+                self.statistics.geometry +=
+                    self.msaa_renderer
+                        .begin(state, gbuffer, &self.quad, &mut self.geometry_cache);
+
+                // Transparent drawables are drawn back to front, see `transparent` module.
+                self.transparent_queue
+                    .build(graph, camera, &self.batch_storage);
+                self.forward_renderer.collect_lights(graph);
+                for drawable in self.transparent_queue.drawables() {
+                    let node = match drawable {
+                        TransparentDrawable::Node(node) => node,
+                        TransparentDrawable::Surface { batch, instance } => {
+                            self.statistics += self.forward_renderer.render(ForwardRenderContext {
+                                state,
+                                framebuffer: gbuffer.forward_frame(),
+                                camera,
+                                batch_storage: &self.batch_storage,
+                                batch,
+                                instance,
+                                viewport,
+                                ambient_color: self.ambient_color,
+                                fog: scene.fog(),
+                                geometry_cache: &mut self.geometry_cache,
+                            });
+                            continue;
+                        }
+                    };
+                    if let Node::ParticleSystem(_) = graph[node] {
+                        self.statistics +=
+                            self.particle_system_renderer
+                                .render(ParticleSystemRenderContext {
+                                    state,
+                                    framebuffer: gbuffer.forward_frame(),
+                                    graph,
+                                    node,
+                                    camera,
+                                    white_dummy: self.white_dummy.clone(),
+                                    depth: depth.clone(),
+                                    frame_width: frame_size.x,
+                                    frame_height: frame_size.y,
+                                    viewport,
+                                    fog: scene.fog(),
+                                    texture_cache: &mut self.texture_cache,
+                                });
+                    } else {
+                        self.statistics += self.sprite_renderer.render(SpriteRenderContext {
                             state,
-                            framebuffer: &mut gbuffer.final_frame,
+                            framebuffer: gbuffer.forward_frame(),
                             graph,
+                            node,
                             camera,
                             white_dummy: self.white_dummy.clone(),
-                            depth,
-                            frame_width: frame_size.x,
-                            frame_height: frame_size.y,
                             viewport,
-                            texture_cache: &mut self.texture_cache,
+                            fog: scene.fog(),
+                            textures: &mut self.texture_cache,
+                            geom_map: &mut self.geometry_cache,
                         });
-
-                self.statistics += self.sprite_renderer.render(SpriteRenderContext {
-                    state,
-                    framebuffer: &mut gbuffer.final_frame,
-                    graph,
-                    camera,
-                    white_dummy: self.white_dummy.clone(),
-                    viewport,
-                    textures: &mut self.texture_cache,
-                    geom_map: &mut self.geometry_cache,
-                });
+                    }
+                }
 
                 self.statistics += self.debug_renderer.render(
                     state,
                     viewport,
-                    &mut gbuffer.final_frame,
+                    gbuffer.forward_frame(),
                     &scene.drawing_context,
                     camera,
                 );
 
+                gbuffer.resolve_msaa(state);
+
+                self.statistics.geometry += self.outline_renderer.render(OutlineRenderContext {
+                    state,
+                    graph,
+                    camera,
+                    gbuffer,
+                    batch_storage: &self.batch_storage,
+                    geometry_cache: &mut self.geometry_cache,
+                    quad: &self.quad,
+                    thickness: self.outline_thickness,
+                })?;
+
+                self.statistics.geometry += self.frame_graph.render(
+                    state,
+                    gbuffer,
+                    viewport,
+                    &self.quad,
+                    &mut self.geometry_cache,
+                )?;
+
                 // Finally render everything into back buffer.
                 if scene.render_target.is_none() {
+                    let color_grading = &self.color_grading;
+                    let texture_cache = &mut self.texture_cache;
+                    let neutral_lut_dummy = &self.neutral_lut_dummy;
+                    let mut lut_texture = |lut: &Option<ColorGradingLut>| {
+                        lut.as_ref()
+                            .and_then(|lut| texture_cache.get(state, lut.texture().clone()))
+                            .unwrap_or_else(|| neutral_lut_dummy.clone())
+                    };
+                    let lut = lut_texture(&color_grading.lut);
+                    let blend_lut = lut_texture(&color_grading.blend_lut);
+                    // Neutral dummy has 0..1 domain.
+                    let lut_domain = |lut: &Option<ColorGradingLut>| {
+                        lut.as_ref().map_or(
+                            (Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 1.0)),
+                            |lut| lut.domain(),
+                        )
+                    };
+                    let (lut_domain_min, lut_domain_max) = lut_domain(&color_grading.lut);
+                    let (blend_lut_domain_min, blend_lut_domain_max) =
+                        lut_domain(&color_grading.blend_lut);
+
+                    let time = self.time;
+                    let post_effects = camera.post_effects();
+                    let vignette = post_effects.vignette.unwrap_or_default();
+                    let (grain_intensity, grain_seed) =
+                        post_effects.film_grain.map_or((0.0, 0.0), |grain| {
+                            if grain.animated {
+                                (grain.intensity, grain.seed + time)
+                            } else {
+                                (grain.intensity, grain.seed)
+                            }
+                        });
+
                     self.statistics.geometry += self.backbuffer.draw(
                         self.geometry_cache.get(state, &self.quad),
                         state,
                         viewport,
-                        &self.flat_shader.program,
+                        &self.tone_mapping_shader.program,
                         &DrawParameters {
                             cull_face: CullFace::Back,
                             culling: false,
@@ -1012,7 +2013,7 @@ impl Renderer {
                         },
                         &[
                             (
-                                self.flat_shader.wvp_matrix,
+                                self.tone_mapping_shader.wvp_matrix,
                                 UniformValue::Matrix4({
                                     Matrix4::new_orthographic(
                                         0.0,
@@ -1029,25 +2030,111 @@ impl Renderer {
                                 }),
                             ),
                             (
-                                self.flat_shader.diffuse_texture,
+                                self.tone_mapping_shader.frame_texture,
                                 UniformValue::Sampler {
                                     index: 0,
                                     texture: gbuffer.frame_texture(),
                                 },
                             ),
+                            (
+                                self.tone_mapping_shader.tone_mapping,
+                                UniformValue::Integer(self.tone_mapping as i32),
+                            ),
+                            (
+                                self.tone_mapping_shader.exposure,
+                                UniformValue::Float(self.exposure),
+                            ),
+                            (
+                                self.tone_mapping_shader.inv_gamma,
+                                UniformValue::Float(1.0 / self.gamma),
+                            ),
+                            (
+                                self.tone_mapping_shader.color_grading,
+                                UniformValue::Bool(color_grading.is_enabled()),
+                            ),
+                            (
+                                self.tone_mapping_shader.lut,
+                                UniformValue::Sampler {
+                                    index: 1,
+                                    texture: lut,
+                                },
+                            ),
+                            (
+                                self.tone_mapping_shader.lut_domain_min,
+                                UniformValue::Vector3(lut_domain_min),
+                            ),
+                            (
+                                self.tone_mapping_shader.lut_domain_max,
+                                UniformValue::Vector3(lut_domain_max),
+                            ),
+                            (
+                                self.tone_mapping_shader.blend_lut,
+                                UniformValue::Sampler {
+                                    index: 2,
+                                    texture: blend_lut,
+                                },
+                            ),
+                            (
+                                self.tone_mapping_shader.blend_lut_domain_min,
+                                UniformValue::Vector3(blend_lut_domain_min),
+                            ),
+                            (
+                                self.tone_mapping_shader.blend_lut_domain_max,
+                                UniformValue::Vector3(blend_lut_domain_max),
+                            ),
+                            (
+                                self.tone_mapping_shader.lut_blend,
+                                UniformValue::Float(color_grading.blend.max(0.0).min(1.0)),
+                            ),
+                            (
+                                self.tone_mapping_shader.chromatic_aberration,
+                                UniformValue::Float(
+                                    post_effects
+                                        .chromatic_aberration
+                                        .map_or(0.0, |ca| ca.strength),
+                                ),
+                            ),
+                            (
+                                self.tone_mapping_shader.vignette_enabled,
+                                UniformValue::Bool(post_effects.vignette.is_some()),
+                            ),
+                            (
+                                self.tone_mapping_shader.vignette_params,
+                                UniformValue::Vector2(Vector2::new(
+                                    vignette.radius,
+                                    vignette.softness.max(0.0001),
+                                )),
+                            ),
+                            (
+                                self.tone_mapping_shader.vignette_color,
+                                UniformValue::Color(vignette.color),
+                            ),
+                            (
+                                self.tone_mapping_shader.grain_intensity,
+                                UniformValue::Float(grain_intensity),
+                            ),
+                            (
+                                self.tone_mapping_shader.grain_seed,
+                                UniformValue::Float(grain_seed),
+                            ),
                         ],
                     );
                 }
             }
         }
 
+        // Forget occlusion state of removed cameras or cameras with disabled occlusion culling.
+        self.occlusion_cullers
+            .retain(|key, _| active_cullers.contains(key));
+
         // Render UI on top of everything.
         self.statistics += self.ui_renderer.render(UiRenderContext {
             state: &mut self.state,
             viewport: window_viewport,
-            backbuffer: &mut self.backbuffer,
+            frame_buffer: &mut self.backbuffer,
             frame_width: backbuffer_width,
             frame_height: backbuffer_height,
+            flip_y: false,
             drawing_context,
             white_dummy: self.white_dummy.clone(),
             texture_cache: &mut self.texture_cache,
@@ -1063,6 +2150,10 @@ impl Renderer {
         context: &glutin::WindowedContext<PossiblyCurrent>,
         dt: f32,
     ) -> Result<(), RendererError> {
+        if self.is_frame_empty() {
+            return Ok(());
+        }
+
         self.render_frame(scenes, drawing_context, dt)?;
         self.statistics.end_frame();
         context.swap_buffers()?;
@@ -1072,3 +2163,136 @@ impl Renderer {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::renderer::{
+        QualitySettings, QualitySettingsProblem, ShadowMapKind, MAX_LIGHT_SCATTER_STEPS,
+        MAX_SHADOW_MAP_SIZE,
+    };
+
+    #[test]
+    fn test_presets_are_valid() {
+        for settings in [
+            QualitySettings::ultra(),
+            QualitySettings::high(),
+            QualitySettings::medium(),
+            QualitySettings::low(),
+            QualitySettings::default(),
+        ]
+        .iter()
+        {
+            assert_eq!(settings.validate(), vec![]);
+        }
+    }
+
+    #[test]
+    fn test_shadow_map_size() {
+        let mut settings = QualitySettings::high();
+        settings.point_shadow_map_size = 0;
+        settings.spot_shadow_map_size = MAX_SHADOW_MAP_SIZE + 1;
+        assert_eq!(
+            settings.validate(),
+            vec![
+                QualitySettingsProblem::ZeroShadowMapSize(ShadowMapKind::Point),
+                QualitySettingsProblem::ShadowMapTooLarge {
+                    kind: ShadowMapKind::Spot,
+                    size: MAX_SHADOW_MAP_SIZE + 1
+                },
+            ]
+        );
+
+        // Limits are inclusive.
+        settings.point_shadow_map_size = 1;
+        settings.spot_shadow_map_size = MAX_SHADOW_MAP_SIZE;
+        assert_eq!(settings.validate(), vec![]);
+    }
+
+    #[test]
+    fn test_shadows_distance() {
+        let mut settings = QualitySettings::high();
+        settings.point_shadows_distance = -1.0;
+        settings.spot_shadows_distance = f32::INFINITY;
+        assert_eq!(
+            settings.validate(),
+            vec![
+                QualitySettingsProblem::InvalidShadowsDistance {
+                    kind: ShadowMapKind::Point,
+                    distance: -1.0
+                },
+                QualitySettingsProblem::InvalidShadowsDistance {
+                    kind: ShadowMapKind::Spot,
+                    distance: f32::INFINITY
+                },
+            ]
+        );
+
+        // NaN is not equal to itself, so the problem is checked by pattern.
+        settings.point_shadows_distance = 0.0;
+        settings.spot_shadows_distance = f32::NAN;
+        let problems = settings.validate();
+        assert_eq!(problems.len(), 1);
+        match problems[0] {
+            QualitySettingsProblem::InvalidShadowsDistance { kind, distance } => {
+                assert_eq!(kind, ShadowMapKind::Spot);
+                assert!(distance.is_nan());
+            }
+            _ => panic!("unexpected problem {:?}", problems[0]),
+        }
+    }
+
+    #[test]
+    fn test_ssao_radius() {
+        let mut settings = QualitySettings::high();
+        for &radius in [0.0, -0.5, f32::INFINITY].iter() {
+            settings.ssao_radius = radius;
+            assert_eq!(
+                settings.validate(),
+                vec![QualitySettingsProblem::InvalidSsaoRadius(radius)]
+            );
+        }
+        settings.ssao_radius = f32::NAN;
+        assert_eq!(settings.validate().len(), 1);
+    }
+
+    #[test]
+    fn test_anisotropy() {
+        let mut settings = QualitySettings::high();
+        for &anisotropy in [0.5, 0.0, f32::NEG_INFINITY].iter() {
+            settings.anisotropy = anisotropy;
+            assert_eq!(
+                settings.validate(),
+                vec![QualitySettingsProblem::InvalidAnisotropy(anisotropy)]
+            );
+        }
+        settings.anisotropy = 1.0;
+        assert_eq!(settings.validate(), vec![]);
+    }
+
+    #[test]
+    fn test_light_scatter_steps() {
+        let mut settings = QualitySettings::high();
+        settings.light_scatter_steps = MAX_LIGHT_SCATTER_STEPS + 1;
+        assert_eq!(
+            settings.validate(),
+            vec![QualitySettingsProblem::TooManyLightScatterSteps(
+                MAX_LIGHT_SCATTER_STEPS + 1
+            )]
+        );
+        settings.light_scatter_steps = MAX_LIGHT_SCATTER_STEPS;
+        assert_eq!(settings.validate(), vec![]);
+    }
+
+    #[test]
+    fn test_multiple_problems() {
+        let mut settings = QualitySettings::low();
+        settings.spot_shadow_map_size = 0;
+        settings.ssao_radius = 0.0;
+        settings.anisotropy = 0.0;
+        assert_eq!(settings.validate().len(), 3);
+        // Every problem has readable description.
+        for problem in settings.validate() {
+            assert!(!problem.to_string().is_empty());
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Multisample anti-aliasing for forward rendered geometry.
+//!
+//! Deferred geometry is lit per pixel, so MSAA is applied only to geometry which is rendered
+//! on top of the lit frame - particle systems, sprites and debug geometry. Lit frame and its
+//! depth are copied into multisampled frame buffer first, then forward geometry is drawn into
+//! it and finally it is resolved back into the frame before post-processing.
+
+use crate::{
+    core::{
+        algebra::{Matrix4, Vector3},
+        math::Rect,
+        scope_profile,
+    },
+    renderer::{
+        error::RendererError,
+        framework::{
+            framebuffer::{CullFace, DrawParameters, FrameBufferTrait},
+            gpu_program::{GpuProgram, UniformLocation, UniformValue},
+            state::{ColorMask, PipelineState},
+        },
+        gbuffer::GBuffer,
+        surface::SurfaceSharedData,
+        GeometryCache, RenderPassStatistics,
+    },
+};
+
+struct CopyShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    frame_texture: UniformLocation,
+    depth_texture: UniformLocation,
+}
+
+impl CopyShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/msaa_copy_fs.glsl");
+        let vertex_source = include_str!("shaders/flat_vs.glsl");
+        let program = GpuProgram::from_source("MsaaCopyShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            frame_texture: program.uniform_location("frameTexture")?,
+            depth_texture: program.uniform_location("depthTexture")?,
+            program,
+        })
+    }
+}
+
+pub struct MsaaRenderer {
+    shader: CopyShader,
+}
+
+impl MsaaRenderer {
+    pub fn new() -> Result<Self, RendererError> {
+        Ok(Self {
+            shader: CopyShader::new()?,
+        })
+    }
+
+    /// Copies lit frame and depth of given G-buffer into its multisampled frame buffer, so
+    /// forward geometry will be correctly occluded by deferred geometry. Does nothing if MSAA
+    /// is disabled.
+    pub(in crate) fn begin(
+        &mut self,
+        state: &mut PipelineState,
+        gbuffer: &mut GBuffer,
+        quad: &SurfaceSharedData,
+        geometry_cache: &mut GeometryCache,
+    ) -> RenderPassStatistics {
+        scope_profile!();
+
+        let mut stats = RenderPassStatistics::default();
+
+        let frame_texture = gbuffer.frame_texture();
+        let depth_texture = gbuffer.depth();
+        let viewport = Rect::new(0, 0, gbuffer.width, gbuffer.height);
+
+        let msaa_frame = match gbuffer.msaa_frame() {
+            Some(msaa_frame) => msaa_frame,
+            None => return stats,
+        };
+
+        msaa_frame.clear(state, viewport, None, Some(1.0), Some(0));
+
+        let frame_matrix = Matrix4::new_orthographic(
+            0.0,
+            viewport.w() as f32,
+            viewport.h() as f32,
+            0.0,
+            -1.0,
+            1.0,
+        ) * Matrix4::new_nonuniform_scaling(&Vector3::new(
+            viewport.w() as f32,
+            viewport.h() as f32,
+            0.0,
+        ));
+
+        let shader = &self.shader;
+        let uniforms = [
+            (shader.wvp_matrix, UniformValue::Matrix4(frame_matrix)),
+            (
+                shader.frame_texture,
+                UniformValue::Sampler {
+                    index: 0,
+                    texture: frame_texture,
+                },
+            ),
+            (
+                shader.depth_texture,
+                UniformValue::Sampler {
+                    index: 1,
+                    texture: depth_texture,
+                },
+            ),
+        ];
+
+        // Color is copied first with disabled depth test, because disabled depth test also
+        // disables depth writes.
+        stats += msaa_frame.draw(
+            geometry_cache.get(state, quad),
+            state,
+            viewport,
+            &shader.program,
+            &DrawParameters {
+                cull_face: CullFace::Back,
+                culling: false,
+                color_write: Default::default(),
+                depth_write: false,
+                stencil_test: false,
+                depth_test: false,
+                blend: false,
+            },
+            &uniforms,
+        );
+
+        // Then depth, pixels without geometry fail depth test and keep cleared depth.
+        stats += msaa_frame.draw(
+            geometry_cache.get(state, quad),
+            state,
+            viewport,
+            &shader.program,
+            &DrawParameters {
+                cull_face: CullFace::Back,
+                culling: false,
+                color_write: ColorMask::all(false),
+                depth_write: true,
+                stencil_test: false,
+                depth_test: true,
+                blend: false,
+            },
+            &uniforms,
+        );
+
+        stats
+    }
+}
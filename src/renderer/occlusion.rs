@@ -0,0 +1,227 @@
+//! Occlusion culling based on hardware occlusion queries.
+//!
+//! After G-buffer is filled, bounding box of every mesh which is visible from camera is drawn
+//! into depth buffer of the frame (without writing anything) inside occlusion query. Results
+//! of the queries are fetched on next frame without stalling the pipeline, meshes whose boxes
+//! have no visible samples are skipped when G-buffer is filled. See `Camera::set_occlusion_culling`
+//! for more info.
+
+use crate::{
+    core::{
+        algebra::{Matrix4, Vector3},
+        math::Rect,
+        pool::Handle,
+        scope_profile,
+    },
+    renderer::{
+        error::RendererError,
+        flat_shader::FlatShader,
+        framework::{
+            framebuffer::{CullFace, DrawParameters, FrameBuffer, FrameBufferTrait},
+            gpu_program::UniformValue,
+            gpu_texture::GpuTexture,
+            query::Query,
+            state::{ColorMask, PipelineState},
+        },
+        surface::SurfaceSharedData,
+        GeometryCache, RenderPassStatistics,
+    },
+    scene::{camera::Camera, graph::Graph, node::Node},
+};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+/// Camera movement (in units) between two frames that is considered as camera cut.
+const CAMERA_CUT_DISTANCE: f32 = 2.0;
+/// Cosine of camera rotation angle between two frames that is considered as camera cut.
+const CAMERA_CUT_COS_ANGLE: f32 = 0.866;
+
+/// Occlusion state of a single camera.
+pub struct OcclusionCuller {
+    queries: HashMap<Handle<Node>, Query>,
+    occluded: HashSet<Handle<Node>>,
+    // Position and look vector of camera on previous frame.
+    last_camera: Option<(Vector3<f32>, Vector3<f32>)>,
+}
+
+impl OcclusionCuller {
+    pub fn new() -> Self {
+        Self {
+            queries: Default::default(),
+            occluded: Default::default(),
+            last_camera: None,
+        }
+    }
+
+    /// Fetches results of queries issued on previous frames, must be called before G-buffer
+    /// is filled. Returns amount of meshes that are inside of frustum, but occluded.
+    pub fn begin_frame(&mut self, camera: &Camera, graph: &Graph) -> usize {
+        scope_profile!();
+
+        let position = camera.global_position();
+        let look = camera
+            .look_vector()
+            .try_normalize(std::f32::EPSILON)
+            .unwrap_or_else(Vector3::z);
+
+        let camera_cut = match self.last_camera {
+            Some((last_position, last_look)) => {
+                last_position.metric_distance(&position) > CAMERA_CUT_DISTANCE
+                    || last_look.dot(&look) < CAMERA_CUT_COS_ANGLE
+            }
+            None => true,
+        };
+        self.last_camera = Some((position, look));
+
+        if camera_cut {
+            // Results of pending queries are no longer valid.
+            self.queries.clear();
+            self.occluded.clear();
+            return 0;
+        }
+
+        let occluded = &mut self.occluded;
+        self.queries.retain(|&handle, query| {
+            if !graph.is_valid_handle(handle) {
+                occluded.remove(&handle);
+                return false;
+            }
+            match query.try_get_result() {
+                Some(true) => {
+                    occluded.remove(&handle);
+                }
+                Some(false) => {
+                    occluded.insert(handle);
+                }
+                None => (),
+            }
+            true
+        });
+
+        self.occluded
+            .iter()
+            .filter(|&&handle| camera.visibility_cache.is_visible(handle))
+            .count()
+    }
+
+    /// Returns true if given mesh was hidden by other geometry on previous frame.
+    pub fn is_occluded(&self, handle: Handle<Node>) -> bool {
+        self.occluded.contains(&handle)
+    }
+}
+
+pub(in crate) struct OcclusionRenderContext<'a, 'b> {
+    pub state: &'a mut PipelineState,
+    pub framebuffer: &'a mut FrameBuffer,
+    pub viewport: Rect<i32>,
+    pub graph: &'b Graph,
+    pub camera: &'b Camera,
+    pub culler: &'a mut OcclusionCuller,
+    pub geometry_cache: &'a mut GeometryCache,
+    pub white_dummy: Rc<RefCell<GpuTexture>>,
+}
+
+pub struct OcclusionRenderer {
+    shader: FlatShader,
+    cube: SurfaceSharedData,
+}
+
+impl OcclusionRenderer {
+    pub fn new() -> Result<Self, RendererError> {
+        Ok(Self {
+            shader: FlatShader::new()?,
+            cube: SurfaceSharedData::make_cube(Matrix4::identity()),
+        })
+    }
+
+    /// Issues occlusion query for every mesh which is visible from camera, framebuffer must
+    /// have filled depth buffer.
+    #[must_use]
+    pub(in crate) fn render(&mut self, args: OcclusionRenderContext) -> RenderPassStatistics {
+        scope_profile!();
+
+        let mut statistics = RenderPassStatistics::default();
+
+        let OcclusionRenderContext {
+            state,
+            framebuffer,
+            viewport,
+            graph,
+            camera,
+            culler,
+            geometry_cache,
+            white_dummy,
+        } = args;
+
+        let params = DrawParameters {
+            cull_face: CullFace::Back,
+            // Camera could be close enough to a box to clip its front faces by near plane.
+            culling: false,
+            color_write: ColorMask::all(false),
+            depth_write: false,
+            stencil_test: false,
+            depth_test: true,
+            blend: false,
+        };
+
+        let view_projection = camera.view_projection_matrix();
+        let camera_position = camera.global_position();
+
+        for (handle, node) in graph.pair_iter() {
+            let mesh = match node {
+                Node::Mesh(mesh) if camera.visibility_cache.is_visible(handle) => mesh,
+                _ => continue,
+            };
+
+            if mesh.surfaces().is_empty() {
+                continue;
+            }
+
+            let bounding_box = mesh.animated_world_bounding_box(graph);
+
+            // Camera is inside of the box - box faces cannot be used to check visibility.
+            if bounding_box.is_intersects_sphere(camera_position, camera.z_near() * 2.0) {
+                culler.occluded.remove(&handle);
+                culler.queries.remove(&handle);
+                continue;
+            }
+
+            let query = culler.queries.entry(handle).or_insert_with(Query::new);
+            if query.is_pending() {
+                // Previous query is still in flight, do not stall.
+                continue;
+            }
+
+            let world = Matrix4::new_translation(&bounding_box.center())
+                * Matrix4::new_nonuniform_scaling(&bounding_box.half_extents().scale(2.0));
+
+            query.begin();
+            statistics += framebuffer.draw(
+                geometry_cache.get(state, &self.cube),
+                state,
+                viewport,
+                &self.shader.program,
+                &params,
+                &[
+                    (
+                        self.shader.wvp_matrix,
+                        UniformValue::Matrix4(view_projection * world),
+                    ),
+                    (
+                        self.shader.diffuse_texture,
+                        UniformValue::Sampler {
+                            index: 0,
+                            texture: white_dummy.clone(),
+                        },
+                    ),
+                ],
+            );
+            query.end();
+        }
+
+        statistics
+    }
+}
@@ -0,0 +1,304 @@
+//! Outline renderer.
+//!
+//! Meshes with outline color (see `Base::set_outline`) are drawn into a separate mask with
+//! their outline colors, then a full screen pass finds pixels near silhouettes of the mask
+//! and blends color of closest outlined object on top of the frame. Outlines are drawn on
+//! top of everything, so outline of an object is visible even if the object is occluded.
+
+use crate::{
+    core::{
+        algebra::{Matrix4, Vector3},
+        color::Color,
+        math::{frustum::Frustum, Rect},
+        scope_profile,
+    },
+    renderer::{
+        batch::BatchStorage,
+        error::RendererError,
+        framework::{
+            framebuffer::{
+                Attachment, AttachmentKind, CullFace, DrawParameters, FrameBuffer, FrameBufferTrait,
+            },
+            gl,
+            gpu_program::{GpuProgram, UniformLocation, UniformValue},
+            gpu_texture::{
+                Coordinate, GpuTexture, GpuTextureKind, MagnificationFilter, MinificationFilter,
+                PixelKind, WrapMode,
+            },
+            state::{ColorMask, PipelineState},
+        },
+        gbuffer::GBuffer,
+        surface::SurfaceSharedData,
+        GeometryCache, RenderPassStatistics,
+    },
+    scene::{camera::Camera, graph::Graph, node::Node},
+};
+use std::{cell::RefCell, rc::Rc};
+
+struct MaskShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    use_skeletal_animation: UniformLocation,
+    bone_matrices: UniformLocation,
+    outline_color: UniformLocation,
+}
+
+impl MaskShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/outline_mask_fs.glsl");
+        let vertex_source = include_str!("shaders/outline_mask_vs.glsl");
+        let program = GpuProgram::from_source("OutlineMaskShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            use_skeletal_animation: program.uniform_location("useSkeletalAnimation")?,
+            bone_matrices: program.uniform_location("boneMatrices")?,
+            outline_color: program.uniform_location("outlineColor")?,
+            program,
+        })
+    }
+}
+
+struct OutlineShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    mask_texture: UniformLocation,
+    thickness: UniformLocation,
+}
+
+impl OutlineShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/outline_fs.glsl");
+        let vertex_source = include_str!("shaders/deferred_light_vs.glsl");
+        let program = GpuProgram::from_source("OutlineShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            mask_texture: program.uniform_location("maskTexture")?,
+            thickness: program.uniform_location("thickness")?,
+            program,
+        })
+    }
+}
+
+pub(in crate) struct OutlineRenderContext<'a, 'b> {
+    pub state: &'a mut PipelineState,
+    pub graph: &'b Graph,
+    pub camera: &'b Camera,
+    pub gbuffer: &'a mut GBuffer,
+    pub batch_storage: &'a BatchStorage,
+    pub geometry_cache: &'a mut GeometryCache,
+    pub quad: &'a SurfaceSharedData,
+    pub thickness: u32,
+}
+
+pub struct OutlineRenderer {
+    mask_shader: MaskShader,
+    shader: OutlineShader,
+    // Created on demand, so scenes without outlines do not waste memory.
+    mask: Option<FrameBuffer>,
+    bone_matrices: Vec<Matrix4<f32>>,
+}
+
+fn create_mask(
+    state: &mut PipelineState,
+    width: usize,
+    height: usize,
+) -> Result<FrameBuffer, RendererError> {
+    let mut texture = GpuTexture::new(
+        state,
+        GpuTextureKind::Rectangle { width, height },
+        PixelKind::RGBA8,
+        MinificationFilter::Nearest,
+        MagnificationFilter::Nearest,
+        1,
+        None,
+    )?;
+    texture
+        .bind_mut(state, 0)
+        .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+        .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
+
+    FrameBuffer::new(
+        state,
+        None,
+        vec![Attachment {
+            kind: AttachmentKind::Color,
+            texture: Rc::new(RefCell::new(texture)),
+        }],
+    )
+}
+
+fn outline_of(graph: &Graph, node: &Node, frustum: &Frustum) -> Option<Color> {
+    match node {
+        Node::Mesh(mesh) if node.global_visibility() => node
+            .outline()
+            .filter(|_| mesh.is_intersect_frustum(graph, frustum)),
+        _ => None,
+    }
+}
+
+impl OutlineRenderer {
+    pub fn new() -> Result<Self, RendererError> {
+        Ok(Self {
+            mask_shader: MaskShader::new()?,
+            shader: OutlineShader::new()?,
+            mask: None,
+            bone_matrices: Default::default(),
+        })
+    }
+
+    pub(in crate) fn render(
+        &mut self,
+        args: OutlineRenderContext,
+    ) -> Result<RenderPassStatistics, RendererError> {
+        scope_profile!();
+
+        let OutlineRenderContext {
+            state,
+            graph,
+            camera,
+            gbuffer,
+            batch_storage,
+            geometry_cache,
+            quad,
+            thickness,
+        } = args;
+
+        let mut stats = RenderPassStatistics::default();
+
+        let frustum = Frustum::from(camera.view_projection_matrix()).unwrap_or_default();
+        let has_outlines = batch_storage.batches.iter().any(|batch| {
+            batch
+                .instances
+                .iter()
+                .any(|instance| outline_of(graph, &graph[instance.owner], &frustum).is_some())
+        });
+        if !has_outlines {
+            return Ok(stats);
+        }
+
+        let width = gbuffer.width.max(1) as usize;
+        let height = gbuffer.height.max(1) as usize;
+        let outdated = self.mask.as_ref().map_or(true, |mask| {
+            match mask.color_attachments()[0].texture.borrow().kind() {
+                GpuTextureKind::Rectangle {
+                    width: w,
+                    height: h,
+                } => (w, h) != (width, height),
+                _ => true,
+            }
+        });
+        if outdated {
+            self.mask = Some(create_mask(state, width, height)?);
+        }
+        let mask = self.mask.as_mut().unwrap();
+
+        let viewport = Rect::new(0, 0, gbuffer.width, gbuffer.height);
+
+        mask.clear(
+            state,
+            viewport,
+            Some(Color::from_rgba(0, 0, 0, 0)),
+            None,
+            None,
+        );
+
+        let view_projection = camera.view_projection_matrix();
+        for batch in batch_storage.batches.iter() {
+            let geometry = geometry_cache.get(state, &batch.data.read().unwrap());
+
+            for instance in batch.instances.iter() {
+                if let Some(color) = outline_of(graph, &graph[instance.owner], &frustum) {
+                    stats += mask.draw(
+                        geometry,
+                        state,
+                        viewport,
+                        &self.mask_shader.program,
+                        &DrawParameters {
+                            cull_face: CullFace::Back,
+                            culling: false,
+                            color_write: Default::default(),
+                            depth_write: false,
+                            stencil_test: false,
+                            depth_test: false,
+                            blend: false,
+                        },
+                        &[
+                            (
+                                self.mask_shader.wvp_matrix,
+                                UniformValue::Matrix4(view_projection * instance.world_transform),
+                            ),
+                            (
+                                self.mask_shader.use_skeletal_animation,
+                                UniformValue::Bool(batch.is_skinned),
+                            ),
+                            (
+                                self.mask_shader.bone_matrices,
+                                UniformValue::Mat4Array({
+                                    self.bone_matrices.clear();
+                                    self.bone_matrices
+                                        .extend_from_slice(instance.bone_matrices.as_slice());
+                                    &self.bone_matrices
+                                }),
+                            ),
+                            (self.mask_shader.outline_color, UniformValue::Color(color)),
+                        ],
+                    );
+                }
+            }
+        }
+
+        let frame_matrix = Matrix4::new_orthographic(
+            0.0,
+            viewport.w() as f32,
+            viewport.h() as f32,
+            0.0,
+            -1.0,
+            1.0,
+        ) * Matrix4::new_nonuniform_scaling(&Vector3::new(
+            viewport.w() as f32,
+            viewport.h() as f32,
+            0.0,
+        ));
+
+        state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+        let mask_texture = mask.color_attachments()[0].texture.clone();
+        stats += gbuffer.final_frame.draw(
+            geometry_cache.get(state, quad),
+            state,
+            viewport,
+            &self.shader.program,
+            &DrawParameters {
+                cull_face: CullFace::Back,
+                culling: false,
+                // Keep alpha of the frame untouched.
+                color_write: ColorMask {
+                    red: true,
+                    green: true,
+                    blue: true,
+                    alpha: false,
+                },
+                depth_write: false,
+                stencil_test: false,
+                depth_test: false,
+                blend: true,
+            },
+            &[
+                (self.shader.wvp_matrix, UniformValue::Matrix4(frame_matrix)),
+                (
+                    self.shader.mask_texture,
+                    UniformValue::Sampler {
+                        index: 0,
+                        texture: mask_texture,
+                    },
+                ),
+                (
+                    self.shader.thickness,
+                    UniformValue::Integer(thickness as i32),
+                ),
+            ],
+        );
+
+        Ok(stats)
+    }
+}
@@ -1,9 +1,10 @@
 use crate::{
-    core::{algebra::Vector2, math::Matrix4Ext, math::Rect, scope_profile},
+    core::{algebra::Vector2, math::Matrix4Ext, math::Rect, pool::Handle, scope_profile},
     renderer::{
         error::RendererError,
+        fog::FogUniforms,
         framework::{
-            framebuffer::{CullFace, DrawParameters, FrameBuffer, FrameBufferTrait},
+            framebuffer::{CullFace, DrawParameters, FrameBufferTrait},
             geometry_buffer::{
                 AttributeDefinition, AttributeKind, BufferBuilder, ElementKind, GeometryBuffer,
                 GeometryBufferBuilder, GeometryBufferKind,
@@ -15,7 +16,7 @@ use crate::{
         },
         RenderPassStatistics, TextureCache,
     },
-    scene::{camera::Camera, graph::Graph, node::Node, particle_system},
+    scene::{camera::Camera, fog::FogSettings, graph::Graph, node::Node, particle_system},
 };
 use std::{cell::RefCell, rc::Rc};
 
@@ -29,6 +30,8 @@ struct ParticleSystemShader {
     depth_buffer_texture: UniformLocation,
     inv_screen_size: UniformLocation,
     proj_params: UniformLocation,
+    camera_position: UniformLocation,
+    fog: FogUniforms,
 }
 
 impl ParticleSystemShader {
@@ -46,6 +49,8 @@ impl ParticleSystemShader {
             depth_buffer_texture: program.uniform_location("depthBufferTexture")?,
             inv_screen_size: program.uniform_location("invScreenSize")?,
             proj_params: program.uniform_location("projParams")?,
+            camera_position: program.uniform_location("cameraPosition")?,
+            fog: FogUniforms::new(&program)?,
             program,
         })
     }
@@ -60,14 +65,18 @@ pub struct ParticleSystemRenderer {
 
 pub(in crate) struct ParticleSystemRenderContext<'a, 'b, 'c> {
     pub state: &'a mut PipelineState,
-    pub framebuffer: &'b mut FrameBuffer,
+    pub framebuffer: &'b mut dyn FrameBufferTrait,
     pub graph: &'c Graph,
+    /// Particle system to draw, particle systems are drawn one by one in order defined by
+    /// transparent queue.
+    pub node: Handle<Node>,
     pub camera: &'c Camera,
     pub white_dummy: Rc<RefCell<GpuTexture>>,
     pub depth: Rc<RefCell<GpuTexture>>,
     pub frame_width: f32,
     pub frame_height: f32,
     pub viewport: Rect<i32>,
+    pub fog: Option<&'c FogSettings>,
     pub texture_cache: &'a mut TextureCache,
 }
 
@@ -130,12 +139,14 @@ impl ParticleSystemRenderer {
             state,
             framebuffer,
             graph,
+            node,
             camera,
             white_dummy,
             depth,
             frame_width,
             frame_height,
             viewport,
+            fog,
             texture_cache,
         } = args;
 
@@ -146,13 +157,8 @@ impl ParticleSystemRenderer {
         let camera_up = inv_view.up();
         let camera_side = inv_view.side();
 
-        for node in graph.linear_iter() {
-            let particle_system = if let Node::ParticleSystem(particle_system) = node {
-                particle_system
-            } else {
-                continue;
-            };
-
+        let node = &graph[node];
+        if let Node::ParticleSystem(particle_system) = node {
             particle_system.generate_draw_data(
                 &mut self.sorted_particles,
                 &mut self.draw_data,
@@ -212,6 +218,10 @@ impl ParticleSystemRenderer {
                     self.shader.proj_params,
                     UniformValue::Vector2(Vector2::new(camera.z_far(), camera.z_near())),
                 ),
+                (
+                    self.shader.camera_position,
+                    UniformValue::Vector3(camera.global_position()),
+                ),
             ];
 
             let draw_params = DrawParameters {
@@ -224,6 +234,7 @@ impl ParticleSystemRenderer {
                 blend: true,
             };
 
+            self.shader.fog.set(&self.shader.program, state, fog);
             statistics += framebuffer.draw(
                 &self.geometry_buffer,
                 state,
@@ -0,0 +1,351 @@
+//! GPU picking, see `Renderer::pick`.
+//!
+//! When picking is requested, meshes of the top-most camera under given pixel are drawn with
+//! unique ids into 1x1 integer render target with depth buffer, projection matrix of the
+//! camera is adjusted so the pixel covers whole target. Id of the closest mesh is copied into
+//! pixel pack buffer and fetched when its fence is signaled, so the renderer never waits for
+//! GPU to finish a frame. New picking is not started until previous result is fetched.
+
+use crate::{
+    core::{
+        algebra::{Matrix4, Vector2},
+        math::{frustum::Frustum, Rect},
+        pool::Handle,
+        scope_profile,
+    },
+    renderer::{
+        batch::BatchStorage,
+        error::RendererError,
+        framework::{
+            framebuffer::{Attachment, AttachmentKind, CullFace, DrawParameters, FrameBuffer},
+            gpu_program::{GpuProgram, UniformLocation, UniformValue},
+            gpu_texture::{
+                GpuTexture, GpuTextureKind, MagnificationFilter, MinificationFilter, PixelKind,
+            },
+            readback::PixelReadback,
+            state::PipelineState,
+        },
+        FrameBufferTrait, GeometryCache, PickResult, RenderPassStatistics,
+    },
+    scene::{camera::Camera, graph::Graph, node::Node, Scene},
+};
+use std::{cell::RefCell, rc::Rc};
+
+struct PickingShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    use_skeletal_animation: UniformLocation,
+    bone_matrices: UniformLocation,
+    id: UniformLocation,
+}
+
+impl PickingShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/picking_fs.glsl");
+        let vertex_source = include_str!("shaders/outline_mask_vs.glsl");
+        let program = GpuProgram::from_source("PickingShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            use_skeletal_animation: program.uniform_location("useSkeletalAnimation")?,
+            bone_matrices: program.uniform_location("boneMatrices")?,
+            id: program.uniform_location("id")?,
+            program,
+        })
+    }
+}
+
+// Nodes drawn into the target, id of a node is its index + 1, zero means nothing.
+struct PickedFrame {
+    position: Vector2<u32>,
+    scene: Handle<Scene>,
+    nodes: Vec<Handle<Node>>,
+}
+
+pub(in crate) struct PickingRenderContext<'a, 'b> {
+    pub state: &'a mut PipelineState,
+    pub scene: Handle<Scene>,
+    pub graph: &'b Graph,
+    pub camera: &'b Camera,
+    pub viewport: Rect<i32>,
+    pub batch_storage: &'a BatchStorage,
+    pub geometry_cache: &'a mut GeometryCache,
+}
+
+pub struct PickingRenderer {
+    shader: PickingShader,
+    // Created on first request.
+    target: Option<FrameBuffer>,
+    readback: Option<PixelReadback>,
+    request: Option<Vector2<u32>>,
+    // Frame which was drawn and waits for read back.
+    drawn: Option<PickedFrame>,
+    result: Option<(Vector2<u32>, PickResult)>,
+    bone_matrices: Vec<Matrix4<f32>>,
+}
+
+/// Returns matrix which maps given pixel of the viewport to whole clip space, it must be
+/// applied after projection matrix.
+fn pick_matrix(viewport: Rect<i32>, pixel: Vector2<i32>) -> Matrix4<f32> {
+    let w = viewport.w().max(1) as f32;
+    let h = viewport.h().max(1) as f32;
+    // Center of the pixel in normalized device coordinates.
+    let nx = 2.0 * (pixel.x - viewport.x()) as f32 / w + 1.0 / w - 1.0;
+    let ny = 2.0 * (pixel.y - viewport.y()) as f32 / h + 1.0 / h - 1.0;
+    #[rustfmt::skip]
+    let matrix = Matrix4::new(
+        w, 0.0, 0.0, -nx * w,
+        0.0, h, 0.0, -ny * h,
+        0.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0,
+    );
+    matrix
+}
+
+impl PickingRenderer {
+    pub fn new() -> Result<Self, RendererError> {
+        Ok(Self {
+            shader: PickingShader::new()?,
+            target: None,
+            readback: None,
+            request: None,
+            drawn: None,
+            result: None,
+            bone_matrices: Default::default(),
+        })
+    }
+
+    pub fn pick(&mut self, position: Vector2<u32>) -> PickResult {
+        // Picking is repeated every frame while requested, so result stays up to date.
+        self.request = Some(position);
+        match self.result {
+            Some((result_position, result)) if result_position == position => result,
+            _ => PickResult::Pending,
+        }
+    }
+
+    /// Returns position of requested pixel, it is called once per frame before anything
+    /// is drawn. Nothing is requested while result of previous picking is not ready yet.
+    pub fn begin_frame(&mut self) -> Option<Vector2<u32>> {
+        if let Some(drawn) = self.drawn.take() {
+            let id = match self.readback.as_mut() {
+                Some(readback) if readback.is_pending() => {
+                    let mut bytes = [0; 4];
+                    if !readback.try_read(&mut bytes) {
+                        // GPU is still busy, check again in next frame.
+                        self.drawn = Some(drawn);
+                        return None;
+                    }
+                    u32::from_ne_bytes(bytes) as usize
+                }
+                // Requested pixel was not inside of any viewport.
+                _ => 0,
+            };
+            let result = match id.checked_sub(1).and_then(|i| drawn.nodes.get(i)) {
+                Some(&node) => PickResult::Node {
+                    scene: drawn.scene,
+                    node,
+                },
+                None => PickResult::Nothing,
+            };
+            self.result = Some((drawn.position, result));
+        }
+
+        let request = self.request.take();
+        if let Some(position) = request {
+            // Nothing can be picked if there is no camera under the pixel.
+            self.drawn = Some(PickedFrame {
+                position,
+                scene: Handle::NONE,
+                nodes: Default::default(),
+            });
+        }
+        request
+    }
+
+    /// Draws meshes visible from the camera if requested pixel is inside its viewport, pixel
+    /// is given in OpenGL window coordinates.
+    pub(in crate) fn render(
+        &mut self,
+        args: PickingRenderContext,
+        pixel: Vector2<i32>,
+    ) -> Result<RenderPassStatistics, RendererError> {
+        scope_profile!();
+
+        let PickingRenderContext {
+            state,
+            scene,
+            graph,
+            camera,
+            viewport,
+            batch_storage,
+            geometry_cache,
+        } = args;
+
+        let mut stats = RenderPassStatistics::default();
+
+        let inside = pixel.x >= viewport.x()
+            && pixel.x < viewport.x() + viewport.w()
+            && pixel.y >= viewport.y()
+            && pixel.y < viewport.y() + viewport.h();
+        let drawn = match self.drawn.as_mut() {
+            Some(drawn) if inside => drawn,
+            _ => return Ok(stats),
+        };
+        // Later cameras are drawn on top of previous ones.
+        drawn.scene = scene;
+        drawn.nodes.clear();
+
+        if self.target.is_none() {
+            let id_texture = GpuTexture::new(
+                state,
+                GpuTextureKind::Rectangle {
+                    width: 1,
+                    height: 1,
+                },
+                PixelKind::R32UI,
+                MinificationFilter::Nearest,
+                MagnificationFilter::Nearest,
+                1,
+                None,
+            )?;
+            let depth_texture = GpuTexture::new(
+                state,
+                GpuTextureKind::Rectangle {
+                    width: 1,
+                    height: 1,
+                },
+                PixelKind::D32,
+                MinificationFilter::Nearest,
+                MagnificationFilter::Nearest,
+                1,
+                None,
+            )?;
+            self.target = Some(FrameBuffer::new(
+                state,
+                Some(Attachment {
+                    kind: AttachmentKind::Depth,
+                    texture: Rc::new(RefCell::new(depth_texture)),
+                }),
+                vec![Attachment {
+                    kind: AttachmentKind::Color,
+                    texture: Rc::new(RefCell::new(id_texture)),
+                }],
+            )?);
+        }
+        let target = self.target.as_mut().unwrap();
+
+        let target_viewport = Rect::new(0, 0, 1, 1);
+        target.clear_u32(state, 0, 0);
+        target.clear(state, target_viewport, None, Some(1.0), None);
+
+        let view_projection = pick_matrix(viewport, pixel) * camera.view_projection_matrix();
+        let frustum = Frustum::from(view_projection).unwrap_or_default();
+
+        for batch in batch_storage.batches.iter() {
+            let geometry = geometry_cache.get(state, &batch.data.read().unwrap());
+
+            for instance in batch.instances.iter() {
+                let node = &graph[instance.owner];
+
+                let visible = node.global_visibility()
+                    && match node {
+                        Node::Mesh(mesh) => mesh.is_intersect_frustum(graph, &frustum),
+                        _ => true,
+                    };
+                if !visible {
+                    continue;
+                }
+
+                drawn.nodes.push(instance.owner);
+                let id = drawn.nodes.len();
+
+                stats += target.draw(
+                    geometry,
+                    state,
+                    target_viewport,
+                    &self.shader.program,
+                    &DrawParameters {
+                        cull_face: CullFace::Back,
+                        culling: !batch.two_sided,
+                        color_write: Default::default(),
+                        depth_write: true,
+                        stencil_test: false,
+                        depth_test: true,
+                        blend: false,
+                    },
+                    &[
+                        (
+                            self.shader.wvp_matrix,
+                            UniformValue::Matrix4(view_projection * instance.world_transform),
+                        ),
+                        (
+                            self.shader.use_skeletal_animation,
+                            UniformValue::Bool(batch.is_skinned),
+                        ),
+                        (
+                            self.shader.bone_matrices,
+                            UniformValue::Mat4Array({
+                                self.bone_matrices.clear();
+                                self.bone_matrices
+                                    .extend_from_slice(instance.bone_matrices.as_slice());
+                                &self.bone_matrices
+                            }),
+                        ),
+                        (self.shader.id, UniformValue::Integer(id as i32)),
+                    ],
+                );
+            }
+        }
+
+        // Later cameras re-issue the read, only the last one matters.
+        target.read_pixel_u32(
+            state,
+            0,
+            0,
+            0,
+            self.readback.get_or_insert_with(|| PixelReadback::new(4)),
+        );
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            algebra::{Vector2, Vector4},
+            math::Rect,
+        },
+        renderer::picking::pick_matrix,
+    };
+
+    #[test]
+    fn test_pick_matrix() {
+        let viewport = Rect::new(10, 20, 100, 50);
+        let matrix = pick_matrix(viewport, Vector2::new(35, 44));
+
+        // Center of picked pixel in clip space of the viewport goes to the center of clip
+        // space, its edges - to the edges of clip space.
+        let w = 2.0;
+        let center = Vector4::new(
+            (2.0 * 25.5 / 100.0 - 1.0) * w,
+            (2.0 * 24.5 / 50.0 - 1.0) * w,
+            0.5,
+            w,
+        );
+        let mapped = matrix * center;
+        assert!(mapped.x.abs() < 1.0e-5 && mapped.y.abs() < 1.0e-5);
+        assert_eq!(mapped.w, w);
+
+        let corner = Vector4::new(
+            (2.0 * 25.0 / 100.0 - 1.0) * w,
+            (2.0 * 24.0 / 50.0 - 1.0) * w,
+            0.5,
+            w,
+        );
+        let mapped = matrix * corner;
+        assert!((mapped.x / mapped.w + 1.0).abs() < 1.0e-5);
+        assert!((mapped.y / mapped.w + 1.0).abs() < 1.0e-5);
+    }
+}
@@ -3,6 +3,7 @@
 uniform sampler2D diffuseTexture;
 uniform sampler2D aoSampler;
 uniform sampler2D ambientTexture;
+uniform sampler2D materialTexture;
 uniform vec4 ambientColor;
 
 out vec4 FragColor;
@@ -10,7 +11,10 @@ in vec2 texCoord;
 
 void main()
 {
-    float ambientOcclusion = texture(aoSampler, texCoord).r;
-    FragColor = (ambientColor + texture(ambientTexture, texCoord)) * texture(diffuseTexture, texCoord);
-    FragColor.rgb *= ambientOcclusion;
-}
\ No newline at end of file
+    vec4 diffuse = texture(diffuseTexture, texCoord);
+    // Screen-space occlusion combined with per-material occlusion.
+    float ambientOcclusion = texture(aoSampler, texCoord).r * texture(materialTexture, texCoord).b;
+    // Ambient texture already contains lightmap (multiplied by diffuse color) and emission.
+    FragColor.rgb = ambientColor.rgb * diffuse.rgb * ambientOcclusion + texture(ambientTexture, texCoord).rgb;
+    FragColor.a = diffuse.a;
+}
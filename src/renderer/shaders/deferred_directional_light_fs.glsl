@@ -3,7 +3,7 @@
 uniform sampler2D depthTexture;
 uniform sampler2D colorTexture;
 uniform sampler2D normalTexture;
-uniform samplerCube pointShadowTexture;
+uniform sampler2D materialTexture;
 
 uniform vec3 lightDirection;
 uniform vec4 lightColor;
@@ -19,12 +19,23 @@ void main()
     vec3 fragmentPosition = S_UnProject(vec3(texCoord, texture(depthTexture, texCoord).r), invViewProj);
     float specularPower = 255.0 * texture(normalTexture, texCoord).w;
 
-    vec3 h = normalize(lightDirection + (cameraPosition - fragmentPosition));
-    float specular = pow(clamp(dot(fragmentNormal, h), 0.0, 1.0), specularPower);
+    vec4 material = texture(materialTexture, texCoord);
+    if (material.a > 0.5)
+    {
+        vec4 albedo = texture(colorTexture, texCoord);
+        vec3 viewVector = normalize(cameraPosition - fragmentPosition);
+        vec3 brdf = S_MetallicRoughnessBRDF(albedo.rgb, material.r, material.g, fragmentNormal, viewVector, lightDirection);
+        FragColor = vec4(brdf * lightColor.rgb, albedo.a);
+    }
+    else
+    {
+        vec3 h = normalize(lightDirection + (cameraPosition - fragmentPosition));
+        float specular = pow(clamp(dot(fragmentNormal, h), 0.0, 1.0), specularPower);
 
-    float lambertian = max(dot(fragmentNormal, lightDirection), 0);
+        float lambertian = max(dot(fragmentNormal, lightDirection), 0);
 
-    FragColor = texture(colorTexture, texCoord);
-    FragColor.xyz += 0.4 * specular;
-    FragColor *= lambertian * lightColor;
+        FragColor = texture(colorTexture, texCoord);
+        FragColor.xyz += 0.4 * specular;
+        FragColor *= lambertian * lightColor;
+    }
 }
@@ -3,6 +3,7 @@
 uniform sampler2D depthTexture;
 uniform sampler2D colorTexture;
 uniform sampler2D normalTexture;
+uniform sampler2D materialTexture;
 uniform samplerCube pointShadowTexture;
 
 uniform vec3 lightPos;
@@ -68,7 +69,19 @@ void main()
         }
     }
 
-    FragColor = texture(colorTexture, texCoord);
-    FragColor.xyz += 0.4 * lighting.specular;
-    FragColor *= lighting.attenuation * shadow * lightColor;
+    vec4 material = texture(materialTexture, texCoord);
+    if (material.a > 0.5)
+    {
+        vec4 albedo = texture(colorTexture, texCoord);
+        vec3 viewVector = normalize(cameraPosition - ctx.fragmentPosition);
+        vec3 brdf = S_MetallicRoughnessBRDF(albedo.rgb, material.r, material.g, ctx.fragmentNormal, viewVector, lighting.direction);
+        float distanceAttenuation = S_LightDistanceAttenuation(lighting.distance, lightRadius);
+        FragColor = vec4(brdf * distanceAttenuation * shadow * lightColor.rgb, albedo.a);
+    }
+    else
+    {
+        FragColor = texture(colorTexture, texCoord);
+        FragColor.xyz += 0.4 * lighting.specular;
+        FragColor *= lighting.attenuation * shadow * lightColor;
+    }
 }
@@ -5,6 +5,7 @@ uniform sampler2D colorTexture;
 uniform sampler2D normalTexture;
 uniform sampler2D spotShadowTexture;
 uniform sampler2D cookieTexture;
+uniform sampler2D materialTexture;
 
 uniform mat4 lightViewProjMatrix;
 uniform vec3 lightPos;
@@ -70,10 +71,28 @@ void main()
     vec4 cookieAttenuation = vec4(1.0);
     if (cookieEnabled) {
         vec2 texCoords = S_Project(ctx.fragmentPosition, lightViewProjMatrix).xy;
-        cookieAttenuation = texture(cookieTexture, texCoords);
+        // Emulate clamp to border (with black border color) regardless of wrap mode of the
+        // texture, otherwise cookie will be tiled outside of light cone.
+        if (texCoords.x >= 0.0 && texCoords.x <= 1.0 && texCoords.y >= 0.0 && texCoords.y <= 1.0) {
+            cookieAttenuation = texture(cookieTexture, texCoords);
+        } else {
+            cookieAttenuation = vec4(0.0);
+        }
     }
 
-    FragColor = texture(colorTexture, texCoord);
-    FragColor.rgb += 0.4 * lighting.specular;
-    FragColor *= cookieAttenuation * coneFactor * shadow * lighting.attenuation * lightColor;
+    vec4 material = texture(materialTexture, texCoord);
+    if (material.a > 0.5)
+    {
+        vec4 albedo = texture(colorTexture, texCoord);
+        vec3 viewVector = normalize(cameraPosition - ctx.fragmentPosition);
+        vec3 brdf = S_MetallicRoughnessBRDF(albedo.rgb, material.r, material.g, ctx.fragmentNormal, viewVector, lighting.direction);
+        float distanceAttenuation = S_LightDistanceAttenuation(lighting.distance, lightRadius);
+        FragColor = vec4(brdf * distanceAttenuation * coneFactor * shadow * cookieAttenuation.rgb * lightColor.rgb, albedo.a);
+    }
+    else
+    {
+        FragColor = texture(colorTexture, texCoord);
+        FragColor.rgb += 0.4 * lighting.specular;
+        FragColor *= cookieAttenuation * coneFactor * shadow * lighting.attenuation * lightColor;
+    }
 }
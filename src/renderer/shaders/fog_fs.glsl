@@ -0,0 +1,16 @@
+#version 330 core
+
+uniform sampler2D depthTexture;
+uniform mat4 invViewProj;
+uniform vec3 cameraPosition;
+uniform S_Fog fog;
+
+in vec2 texCoord;
+out vec4 FragColor;
+
+void main()
+{
+    vec3 fragmentPosition = S_UnProject(vec3(texCoord, texture(depthTexture, texCoord).r), invViewProj);
+
+    FragColor = vec4(fog.color.rgb, S_FogFactor(fog, fragmentPosition, cameraPosition));
+}
@@ -0,0 +1,15 @@
+#version 330 core
+
+layout(location = 0) in vec3 vertexPosition;
+
+uniform mat4 worldViewProjection;
+// xy - offset, zw - size of viewport in texture coordinates of the frame.
+uniform vec4 viewportRect;
+
+out vec2 texCoord;
+
+void main()
+{
+    gl_Position = worldViewProjection * vec4(vertexPosition, 1.0);
+    texCoord = viewportRect.xy + (gl_Position.xy * 0.5 + 0.5) * viewportRect.zw;
+}
@@ -3,13 +3,22 @@
 layout(location = 0) out vec4 outColor;
 layout(location = 1) out vec4 outNormal;
 layout(location = 2) out vec4 outAmbient;
+layout(location = 3) out vec4 outMaterial;
 
 uniform sampler2D diffuseTexture;
 uniform sampler2D normalTexture;
 uniform sampler2D specularTexture;
 uniform sampler2D lightmapTexture;
 uniform sampler2D roughnessTexture;
+uniform sampler2D metallicTexture;
+uniform sampler2D aoTexture;
+uniform sampler2D emissiveTexture;
 uniform samplerCube environmentMap;
+uniform bool usePbr;
+uniform float metallicFactor;
+uniform float roughnessFactor;
+uniform vec4 emissiveColor;
+uniform float emissiveIntensity;
 uniform vec4 diffuseColor;
 uniform vec3 cameraPosition;
 
@@ -19,20 +28,46 @@ in vec2 texCoord;
 in vec3 tangent;
 in vec3 binormal;
 in vec2 secondTexCoord;
+in vec4 color;
 
 void main()
 {
-    outColor = diffuseColor * texture(diffuseTexture, texCoord);
+    outColor = diffuseColor * color * texture(diffuseTexture, texCoord);
     if (outColor.a < 0.5) discard;
     outColor.a = 1;
     vec4 n = normalize(texture(normalTexture, texCoord) * 2.0 - 1.0);
     mat3 tangentSpace = mat3(tangent, binormal, normal);
-    outNormal.xyz = normalize(tangentSpace * n.xyz) * 0.5 + 0.5;
+    vec3 worldNormal = normalize(tangentSpace * n.xyz);
+    // Back faces are rendered only for two-sided surfaces, they must be lit as front faces.
+    if (!gl_FrontFacing) worldNormal = -worldNormal;
+    outNormal.xyz = worldNormal * 0.5 + 0.5;
     outNormal.w = texture(specularTexture, texCoord).r;
-    outAmbient = vec4(texture(lightmapTexture, secondTexCoord).rgb, 1.0);
+    vec3 lightmap = texture(lightmapTexture, secondTexCoord).rgb;
+    // Emission is added to ambient term, ambient term is pre-multiplied by albedo for that.
+    vec3 emission = emissiveIntensity * emissiveColor.rgb * texture(emissiveTexture, texCoord).rgb;
 
-    // reflection mapping
-    float roughness = texture(roughnessTexture, texCoord).r;
-    vec3 reflectionTexCoord = reflect(normalize(position-cameraPosition), normalize(n.xyz));
-    outColor = (1-roughness) * outColor + roughness * vec4(texture(environmentMap, reflectionTexCoord).rgb, outColor.a);
+    if (usePbr)
+    {
+        // Metallic-roughness workflow, channels are the same as in glTF packed textures.
+        float metallic = metallicFactor * texture(metallicTexture, texCoord).b;
+        float roughness = roughnessFactor * texture(roughnessTexture, texCoord).g;
+        float ao = texture(aoTexture, texCoord).r;
+        outAmbient = vec4(lightmap * outColor.rgb + emission, 1.0);
+        outMaterial = vec4(metallic, roughness, ao, 1.0);
+
+        // Smooth metals reflect environment.
+        vec3 reflectionTexCoord = reflect(normalize(position - cameraPosition), normalize(n.xyz));
+        vec3 reflection = texture(environmentMap, reflectionTexCoord).rgb * outColor.rgb;
+        outColor.rgb = mix(outColor.rgb, reflection, metallic * (1.0 - roughness));
+    }
+    else
+    {
+        // reflection mapping
+        float roughness = texture(roughnessTexture, texCoord).r;
+        vec3 reflectionTexCoord = reflect(normalize(position-cameraPosition), normalize(n.xyz));
+        outColor = (1-roughness) * outColor + roughness * vec4(texture(environmentMap, reflectionTexCoord).rgb, outColor.a);
+
+        outAmbient = vec4(lightmap * outColor.rgb + emission, 1.0);
+        outMaterial = vec4(0.0, 1.0, 1.0, 0.0);
+    }
 }
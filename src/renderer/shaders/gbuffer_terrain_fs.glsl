@@ -0,0 +1,55 @@
+#version 330 core
+
+layout(location = 0) out vec4 outColor;
+layout(location = 1) out vec4 outNormal;
+layout(location = 2) out vec4 outAmbient;
+layout(location = 3) out vec4 outMaterial;
+
+uniform sampler2D diffuseTexture0;
+uniform sampler2D diffuseTexture1;
+uniform sampler2D diffuseTexture2;
+uniform sampler2D diffuseTexture3;
+uniform sampler2D normalTexture0;
+uniform sampler2D normalTexture1;
+uniform sampler2D normalTexture2;
+uniform sampler2D normalTexture3;
+uniform sampler2D controlMap;
+uniform bool useControlMap;
+uniform int layerCount;
+uniform vec4 tileScales;
+
+in vec3 position;
+in vec3 normal;
+in vec2 texCoord;
+in vec3 tangent;
+in vec3 binormal;
+in vec2 secondTexCoord;
+in vec4 color;
+
+void main()
+{
+    // Each channel of control map is weight of respective layer.
+    vec4 weights = useControlMap ? texture(controlMap, texCoord) : vec4(1.0, 0.0, 0.0, 0.0);
+    weights *= vec4(greaterThan(vec4(float(layerCount)), vec4(0.0, 1.0, 2.0, 3.0)));
+    float totalWeight = dot(weights, vec4(1.0));
+    weights = totalWeight > 0.0 ? weights / totalWeight : vec4(1.0, 0.0, 0.0, 0.0);
+
+    vec3 diffuse = weights.x * texture(diffuseTexture0, texCoord * tileScales.x).rgb
+                 + weights.y * texture(diffuseTexture1, texCoord * tileScales.y).rgb
+                 + weights.z * texture(diffuseTexture2, texCoord * tileScales.z).rgb
+                 + weights.w * texture(diffuseTexture3, texCoord * tileScales.w).rgb;
+
+    vec3 n = weights.x * texture(normalTexture0, texCoord * tileScales.x).xyz
+           + weights.y * texture(normalTexture1, texCoord * tileScales.y).xyz
+           + weights.z * texture(normalTexture2, texCoord * tileScales.z).xyz
+           + weights.w * texture(normalTexture3, texCoord * tileScales.w).xyz;
+    n = normalize(n * 2.0 - 1.0);
+
+    outColor = vec4(diffuse, 1.0);
+    mat3 tangentSpace = mat3(tangent, binormal, normal);
+    outNormal.xyz = normalize(tangentSpace * n) * 0.5 + 0.5;
+    // Same specular strength as default specular texture of surfaces.
+    outNormal.w = 0.125;
+    outAmbient = vec4(0.0, 0.0, 0.0, 1.0);
+    outMaterial = vec4(0.0, 1.0, 1.0, 0.0);
+}
@@ -7,10 +7,12 @@ layout(location = 3) in vec3 vertexNormal;
 layout(location = 4) in vec4 vertexTangent;
 layout(location = 5) in vec4 boneWeights;
 layout(location = 6) in vec4 boneIndices;
+layout(location = 13) in vec4 vertexColor;
 
 uniform mat4 worldMatrix;
 uniform mat4 worldViewProjection;
 uniform bool useSkeletalAnimation;
+uniform bool useVertexColors;
 uniform mat4 boneMatrices[60];
 
 out vec3 position;
@@ -19,6 +21,7 @@ out vec2 texCoord;
 out vec3 tangent;
 out vec3 binormal;
 out vec2 secondTexCoord;
+out vec4 color;
 
 void main()
 {
@@ -61,5 +64,6 @@ void main()
     binormal = normalize(vertexTangent.w * cross(tangent, normal));
     texCoord = vertexTexCoord;
     secondTexCoord = vertexSecondTexCoord;
+    color = useVertexColors ? vertexColor : vec4(1.0);
     position = vec3(worldMatrix * localPosition);
 }
@@ -10,11 +10,13 @@ layout(location = 6) in vec4 boneIndices;
 layout(location = 7) in vec4 instanceColor;
 layout(location = 8) in mat4 worldMatrix;
 layout(location = 12) in float depthOffset;
+layout(location = 13) in vec4 vertexColor;
 
 uniform sampler2D matrixStorage;
 
 uniform vec4 matrixStorageSize; // vec4(1/w, 1/h, w, h)
 uniform bool useSkeletalAnimation;
+uniform bool useVertexColors;
 uniform int matrixBufferStride;
 uniform mat4 viewProjectionMatrix;
 
@@ -101,6 +103,6 @@ void main()
     binormal = normalize(vertexTangent.w * cross(tangent, normal));
     texCoord = vertexTexCoord;
     secondTexCoord = vertexSecondTexCoord;
-    diffuseColor = instanceColor;
+    diffuseColor = useVertexColors ? instanceColor * vertexColor : instanceColor;
     position = vec3(worldMatrix * localPosition);
 }
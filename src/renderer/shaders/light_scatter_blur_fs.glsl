@@ -0,0 +1,24 @@
+// Simple 4x4 box blur for light scattering buffer.
+
+#version 330 core
+
+uniform sampler2D inputTexture;
+
+out vec4 FragColor;
+
+in vec2 texCoord;
+
+void main()
+{
+    vec2 texelSize = 1.0 / vec2(textureSize(inputTexture, 0));
+    vec3 result = vec3(0.0);
+    for (int y = -2; y < 2; ++y)
+    {
+        for (int x = -2; x < 2; ++x)
+        {
+            vec2 offset = vec2(float(x), float(y)) * texelSize;
+            result += texture(inputTexture, texCoord + offset).rgb;
+        }
+    }
+    FragColor = vec4(result / 16.0, 1.0);
+}
@@ -0,0 +1,14 @@
+#version 330 core
+
+uniform sampler2D frameTexture;
+uniform sampler2D depthTexture;
+
+out vec4 FragColor;
+
+in vec2 texCoord;
+
+void main()
+{
+    FragColor = texture(frameTexture, texCoord);
+    gl_FragDepth = texture(depthTexture, texCoord).r;
+}
@@ -0,0 +1,44 @@
+#version 330 core
+
+uniform sampler2D maskTexture;
+uniform int thickness;
+
+in vec2 texCoord;
+out vec4 FragColor;
+
+void main()
+{
+    // Outlined objects itself stay untouched.
+    if (texture(maskTexture, texCoord).a > 0.0)
+    {
+        discard;
+    }
+
+    // Take color of closest covered pixel of the mask within thickness.
+    vec2 texelSize = 1.0 / vec2(textureSize(maskTexture, 0));
+    vec4 color = vec4(0.0);
+    float closest = float(thickness * thickness) + 1.0;
+    for (int y = -thickness; y <= thickness; ++y)
+    {
+        for (int x = -thickness; x <= thickness; ++x)
+        {
+            float distance = float(x * x + y * y);
+            if (distance < closest)
+            {
+                vec4 mask = texture(maskTexture, texCoord + vec2(x, y) * texelSize);
+                if (mask.a > 0.0)
+                {
+                    closest = distance;
+                    color = mask;
+                }
+            }
+        }
+    }
+
+    if (color.a == 0.0)
+    {
+        discard;
+    }
+
+    FragColor = color;
+}
@@ -4,10 +4,13 @@ uniform sampler2D diffuseTexture;
 uniform sampler2D depthBufferTexture;
 uniform vec2 invScreenSize;
 uniform vec2 projParams;
+uniform vec3 cameraPosition;
+uniform S_Fog fog;
 
 out vec4 FragColor;
 in vec2 texCoord;
 in vec4 color;
+in vec3 position;
 
 float toProjSpace(float z)
 {
@@ -21,5 +24,6 @@ void main()
     float sceneDepth = toProjSpace(texture(depthBufferTexture, gl_FragCoord.xy * invScreenSize).r);
     float depthOpacity = clamp((sceneDepth - gl_FragCoord.z / gl_FragCoord.w) * 2.0f, 0.0, 1.0);
     FragColor = color * texture(diffuseTexture, texCoord).r;
+    FragColor.rgb = mix(FragColor.rgb, fog.color.rgb, S_FogFactor(fog, position, cameraPosition));
     FragColor.a *= depthOpacity;
 }
\ No newline at end of file
@@ -12,6 +12,7 @@ uniform vec3 cameraUpVector;
 uniform vec3 cameraSideVector;
 
 out vec2 texCoord;
+out vec3 position;
 out vec4 color;
 
 vec2 rotateVec2(vec2 v, float angle)
@@ -29,5 +30,6 @@ void main()
     vec2 vertexOffset = rotateVec2(vertexTexCoord * 2.0 - 1.0, particleRotation);
     vec4 worldPosition = worldMatrix * vec4(vertexPosition, 1.0);
     vec3 offset = (vertexOffset.x * cameraSideVector + vertexOffset.y * cameraUpVector) * particleSize;
-    gl_Position = viewProjectionMatrix * (worldPosition + vec4(offset.x, offset.y, offset.z, 0.0));
+    position = worldPosition.xyz + offset;
+    gl_Position = viewProjectionMatrix * vec4(position, 1.0);
 }
\ No newline at end of file
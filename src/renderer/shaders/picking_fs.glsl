@@ -0,0 +1,10 @@
+#version 330 core
+
+uniform int id;
+
+out uint FragColor;
+
+void main()
+{
+    FragColor = uint(id);
+}
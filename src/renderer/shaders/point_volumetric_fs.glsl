@@ -1,17 +1,47 @@
 #version 330 core
 
 uniform sampler2D depthSampler;
+uniform samplerCube shadowTexture;
 // Warning! All coordinates are given in *view* space.
 uniform vec3 lightPosition;
 uniform mat4 invProj;
 uniform float lightRadius;
 uniform vec3 lightColor;
 uniform vec3 scatterFactor;
+uniform mat4 invViewMatrix;
+uniform bool shadowsEnabled;
+uniform float shadowBias;
+uniform int stepCount;
 
 out vec4 FragColor;
 
 in vec2 texCoord;
 
+// Returns fraction of light that reaches given ray segment, it is calculated by ray marching
+// against cube shadow map. See spot_volumetric_fs.glsl for details.
+float ShadowVisibility(vec3 viewDirection, float minDepth, float maxDepth)
+{
+    vec3 worldLightPosition = (invViewMatrix * vec4(lightPosition, 1.0)).xyz;
+    float stepSize = (maxDepth - minDepth) / float(stepCount);
+    float jitter = S_InterleavedGradientNoise(gl_FragCoord.xy);
+    float visible = 0.0;
+    float total = 0.0;
+    for (int i = 0; i < stepCount; ++i)
+    {
+        vec3 viewPosition = viewDirection * (minDepth + (float(i) + jitter) * stepSize);
+        vec3 toLight = lightPosition - viewPosition;
+        float weight = 1.0 / max(dot(toLight, toLight), 0.0001);
+        vec3 worldPosition = (invViewMatrix * vec4(viewPosition, 1.0)).xyz;
+        vec3 fromLight = worldPosition - worldLightPosition;
+        if (length(fromLight) - shadowBias <= texture(shadowTexture, fromLight).r)
+        {
+            visible += weight;
+        }
+        total += weight;
+    }
+    return total > 0.0 ? visible / total : 1.0;
+}
+
 void main()
 {
     vec3 fragmentPosition = S_UnProject(vec3(texCoord, texture(depthSampler, texCoord).r), invProj);
@@ -32,6 +62,11 @@ void main()
             vec3 closestPoint = viewDirection * minDepth;
 
             scatter = scatterFactor * S_InScatter(closestPoint, viewDirection, lightPosition, maxDepth - minDepth);
+
+            if (shadowsEnabled && stepCount > 0)
+            {
+                scatter *= ShadowVisibility(viewDirection, minDepth, maxDepth);
+            }
         }
     }
 
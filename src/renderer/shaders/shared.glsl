@@ -111,6 +111,37 @@ TBlinnPhong S_BlinnPhong(TBlinnPhongContext ctx)
     return TBlinnPhong(attenuation, specular, distance, lightVector);
 }
 
+// Cook-Torrance BRDF for metallic-roughness workflow (GGX distribution, Smith-Schlick geometry
+// and Schlick fresnel). Returns light reflected towards viewer from a light with unit color,
+// includes Lambert cosine term. Result is scaled by PI so diffuse part of fully rough
+// dielectric matches brightness of Blinn-Phong model used for the rest of surfaces.
+vec3 S_MetallicRoughnessBRDF(vec3 albedo, float metallic, float roughness, vec3 normal, vec3 viewVector, vec3 lightVector)
+{
+    const float PI = 3.14159265;
+
+    vec3 h = normalize(viewVector + lightVector);
+    float nDotL = max(dot(normal, lightVector), 0.0);
+    float nDotV = max(dot(normal, viewVector), 0.0001);
+    float nDotH = max(dot(normal, h), 0.0);
+    float hDotV = max(dot(h, viewVector), 0.0);
+
+    float alpha = max(roughness * roughness, 0.002);
+    float alphaSqr = alpha * alpha;
+    float denom = nDotH * nDotH * (alphaSqr - 1.0) + 1.0;
+    float distribution = alphaSqr / (PI * denom * denom);
+
+    float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    float geometry = (nDotV / (nDotV * (1.0 - k) + k)) * (nDotL / (nDotL * (1.0 - k) + k));
+
+    vec3 f0 = mix(vec3(0.04), albedo, metallic);
+    vec3 fresnel = f0 + (1.0 - f0) * pow(1.0 - hDotV, 5.0);
+
+    vec3 specular = distribution * geometry * fresnel / max(4.0 * nDotV * nDotL, 0.0001);
+    vec3 diffuse = (vec3(1.0) - fresnel) * (1.0 - metallic) * albedo / PI;
+
+    return PI * (diffuse + specular) * nDotL;
+}
+
 // Returns scatter amount for given parameters.
 // https://cseweb.ucsd.edu/~ravir/papers/singlescat/scattering.pdf
 // https://blog.mmacklin.com/2010/05/29/in-scattering-demo/
@@ -130,6 +161,15 @@ float S_InScatter(vec3 start, vec3 dir, vec3 lightPos, float d)
     return l;
 }
 
+// Returns pseudo-random value in [0; 1) range for given pixel coordinates. Noise has
+// good spatial distribution so it is well suited for jittering of ray marching which is
+// blurred afterwards.
+// http://www.iryoku.com/next-generation-post-processing-in-call-of-duty-advanced-warfare
+float S_InterleavedGradientNoise(vec2 fragCoord)
+{
+    return fract(52.9829189 * fract(dot(fragCoord, vec2(0.06711056, 0.00583715))));
+}
+
 // https://en.wikipedia.org/wiki/Rayleigh_scattering
 vec3 S_RayleighScatter(vec3 start, vec3 dir, vec3 lightPos, float d)
 {
@@ -149,4 +189,52 @@ bool S_RaySphereIntersection(vec3 origin, vec3 dir, vec3 center, float radius, o
     float b = 2.0 * dot(dir, d);
     float c = dot(d, d) - radius * radius;
     return S_SolveQuadraticEq(a, b, c, minT, maxT);
-}
\ No newline at end of file
+}
+// Parameters of distance fog, must match `FogUniforms` in renderer/fog.rs.
+struct S_Fog
+{
+    bool enabled;
+    vec4 color;
+    // 0 - linear, 1 - exponential, 2 - exponential squared
+    int mode;
+    float start;
+    float end;
+    float density;
+    bool heightFogEnabled;
+    float heightFogBase;
+    float heightFogFalloff;
+};
+
+// Returns strength of fog in [0; 1] range at given world space position, alpha of fog color
+// is taken into account.
+float S_FogFactor(S_Fog fog, vec3 fragmentPosition, vec3 cameraPosition)
+{
+    if (!fog.enabled)
+    {
+        return 0.0;
+    }
+
+    float distance = length(fragmentPosition - cameraPosition);
+
+    float factor;
+    if (fog.mode == 0)
+    {
+        factor = (distance - fog.start) / max(fog.end - fog.start, 0.0001);
+    }
+    else if (fog.mode == 1)
+    {
+        factor = 1.0 - exp(-fog.density * distance);
+    }
+    else
+    {
+        float x = fog.density * distance;
+        factor = 1.0 - exp(-x * x);
+    }
+
+    if (fog.heightFogEnabled)
+    {
+        factor *= exp(-fog.heightFogFalloff * max(fragmentPosition.y - fog.heightFogBase, 0.0));
+    }
+
+    return clamp(factor, 0.0, 1.0) * fog.color.a;
+}
@@ -1,6 +1,7 @@
 #version 330 core
 
 uniform sampler2D depthSampler;
+uniform sampler2D shadowTexture;
 // Warning! All coordinates are given in *view* space.
 uniform vec3 lightPosition;
 uniform vec3 lightDirection;
@@ -8,11 +9,41 @@ uniform float coneAngleCos;
 uniform mat4 invProj;
 uniform vec3 lightColor;
 uniform vec3 scatterFactor;
+uniform mat4 invViewMatrix;
+uniform bool shadowsEnabled;
+uniform float shadowBias;
+uniform int stepCount;
+uniform mat4 lightViewProjMatrix;
 
 out vec4 FragColor;
 
 in vec2 texCoord;
 
+// Returns fraction of light that reaches given ray segment, it is calculated by ray marching
+// against shadow map. Every sample is weighted by squared distance to light source, so
+// samples that contribute most to in-scattering also have most influence on visibility.
+float ShadowVisibility(vec3 viewDirection, float minDepth, float maxDepth)
+{
+    float stepSize = (maxDepth - minDepth) / float(stepCount);
+    float jitter = S_InterleavedGradientNoise(gl_FragCoord.xy);
+    float visible = 0.0;
+    float total = 0.0;
+    for (int i = 0; i < stepCount; ++i)
+    {
+        vec3 viewPosition = viewDirection * (minDepth + (float(i) + jitter) * stepSize);
+        vec3 toLight = lightPosition - viewPosition;
+        float weight = 1.0 / max(dot(toLight, toLight), 0.0001);
+        vec3 worldPosition = (invViewMatrix * vec4(viewPosition, 1.0)).xyz;
+        vec3 lightSpacePosition = S_Project(worldPosition, lightViewProjMatrix);
+        if (lightSpacePosition.z - shadowBias <= texture(shadowTexture, lightSpacePosition.xy).r)
+        {
+            visible += weight;
+        }
+        total += weight;
+    }
+    return total > 0.0 ? visible / total : 1.0;
+}
+
 void main()
 {
     vec3 fragmentPosition = S_UnProject(vec3(texCoord, texture(depthSampler, texCoord).r), invProj);
@@ -56,6 +87,11 @@ void main()
             maxDepth = clamp(maxDepth, 0.0, fragmentDepth);
 
             scatter = scatterFactor * S_InScatter(viewDirection * minDepth, viewDirection, lightPosition, maxDepth - minDepth);
+
+            if (shadowsEnabled && stepCount > 0)
+            {
+                scatter *= ShadowVisibility(viewDirection, minDepth, maxDepth);
+            }
         }
     }
 
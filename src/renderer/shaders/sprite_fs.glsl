@@ -2,12 +2,16 @@
 
 uniform sampler2D diffuseTexture;
 uniform vec4 color;
+uniform vec3 cameraPosition;
+uniform S_Fog fog;
 
 out vec4 FragColor;
 
 in vec2 texCoord;
+in vec3 position;
 
 void main()
 {
     FragColor = color * texture(diffuseTexture, texCoord).r;
+    FragColor.rgb = mix(FragColor.rgb, fog.color.rgb, S_FogFactor(fog, position, cameraPosition));
 }
\ No newline at end of file
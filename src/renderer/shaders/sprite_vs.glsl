@@ -11,6 +11,7 @@ uniform float size;
 uniform float rotation;
 
 out vec2 texCoord;
+out vec3 position;
 
 vec2 rotateVec2(vec2 v, float angle)
 {
@@ -26,5 +27,6 @@ void main()
     vec2 vertexOffset = rotateVec2(vertexTexCoord * 2.0 - 1.0, rotation);
     vec4 worldPosition = worldMatrix * vec4(vertexPosition, 1.0);
     vec3 offset = (vertexOffset.x * cameraSideVector + vertexOffset.y * cameraUpVector) * size;
-    gl_Position = viewProjectionMatrix * (worldPosition + vec4(offset.x, offset.y, offset.z, 0.0));
+    position = worldPosition.xyz + offset;
+    gl_Position = viewProjectionMatrix * vec4(position, 1.0);
 }
\ No newline at end of file
@@ -0,0 +1,89 @@
+#version 330 core
+
+uniform sampler2D frameTexture;
+uniform int toneMapping;
+uniform float exposure;
+uniform float invGamma;
+uniform bool colorGrading;
+uniform sampler3D lut;
+uniform vec3 lutDomainMin;
+uniform vec3 lutDomainMax;
+uniform sampler3D blendLut;
+uniform vec3 blendLutDomainMin;
+uniform vec3 blendLutDomainMax;
+uniform float lutBlend;
+uniform float chromaticAberration;
+uniform bool vignetteEnabled;
+uniform vec2 vignetteParams; // x - radius, y - softness
+uniform vec4 vignetteColor;
+uniform float grainIntensity;
+uniform float grainSeed;
+
+out vec4 FragColor;
+
+in vec2 texCoord;
+
+vec3 ApplyLut(sampler3D table, vec3 domainMin, vec3 domainMax, vec3 color)
+{
+    // Remap colors from input domain of the table to 0..1 range.
+    color = clamp((color - domainMin) / (domainMax - domainMin), 0.0, 1.0);
+    // Map colors to centers of texels, so edges of the table are not filtered with border.
+    float size = float(textureSize(table, 0).x);
+    return texture(table, color * ((size - 1.0) / size) + vec3(0.5 / size)).rgb;
+}
+
+float GrainNoise(vec2 p, float seed)
+{
+    return fract(sin(dot(p + vec2(seed, seed * 1.618), vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+void main()
+{
+    // Order of operations is fixed, see docs of `scene::post_effects` module.
+    vec4 color = texture(frameTexture, texCoord);
+
+    if (chromaticAberration > 0.0)
+    {
+        vec2 offset = (texCoord - vec2(0.5)) * chromaticAberration;
+        color.r = texture(frameTexture, texCoord + offset).r;
+        color.b = texture(frameTexture, texCoord - offset).b;
+    }
+
+    // Frame is in linear HDR space, bring it to [0; 1] range first.
+    vec3 hdr = color.rgb * exposure;
+    vec3 ldr;
+    if (toneMapping == 1)
+    {
+        // Reinhard
+        ldr = hdr / (hdr + vec3(1.0));
+    }
+    else
+    {
+        ldr = clamp(hdr, 0.0, 1.0);
+    }
+
+    // Then convert it to display color space.
+    vec3 display = pow(ldr, vec3(invGamma));
+
+    // Lookup tables are made in display color space, so grading goes after gamma correction.
+    if (colorGrading)
+    {
+        display = mix(ApplyLut(lut, lutDomainMin, lutDomainMax, display),
+            ApplyLut(blendLut, blendLutDomainMin, blendLutDomainMax, display), lutBlend);
+    }
+
+    if (vignetteEnabled)
+    {
+        // Normalized distance, 1.0 at corners.
+        float distance = length(texCoord - vec2(0.5)) * 1.41421356;
+        float strength = smoothstep(vignetteParams.x, vignetteParams.x + vignetteParams.y, distance);
+        display = mix(display, vignetteColor.rgb, strength * vignetteColor.a);
+    }
+
+    if (grainIntensity > 0.0)
+    {
+        display += (GrainNoise(gl_FragCoord.xy, grainSeed) - 0.5) * grainIntensity;
+    }
+
+    FragColor = vec4(display, color.a);
+}
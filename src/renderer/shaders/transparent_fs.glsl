@@ -0,0 +1,100 @@
+#version 330 core
+
+// Light kinds, must match `ForwardLightKind` in forward_renderer.rs
+const int LIGHT_NONE = 0;
+const int LIGHT_DIRECTIONAL = 1;
+const int LIGHT_POINT = 2;
+const int LIGHT_SPOT = 3;
+
+uniform sampler2D diffuseTexture;
+uniform sampler2D normalTexture;
+uniform sampler2D specularTexture;
+uniform sampler2D lightmapTexture;
+uniform sampler2D roughnessTexture;
+uniform sampler2D metallicTexture;
+uniform sampler2D emissiveTexture;
+uniform bool usePbr;
+uniform float metallicFactor;
+uniform float roughnessFactor;
+uniform vec4 emissiveColor;
+uniform float emissiveIntensity;
+uniform vec4 diffuseColor;
+uniform vec4 ambientColor;
+uniform vec3 cameraPosition;
+
+uniform int lightKind;
+uniform vec3 lightPosition;
+uniform vec3 lightDirection;
+uniform float lightRadius;
+uniform vec4 lightColor;
+uniform float halfHotspotConeAngleCos;
+uniform float halfConeAngleCos;
+
+uniform S_Fog fog;
+
+in vec3 position;
+in vec3 normal;
+in vec2 texCoord;
+in vec3 tangent;
+in vec3 binormal;
+in vec2 secondTexCoord;
+in vec4 color;
+
+out vec4 FragColor;
+
+void main()
+{
+    vec4 albedo = diffuseColor * color * texture(diffuseTexture, texCoord);
+    vec4 n = normalize(texture(normalTexture, texCoord) * 2.0 - 1.0);
+    mat3 tangentSpace = mat3(tangent, binormal, normal);
+    vec3 worldNormal = normalize(tangentSpace * n.xyz);
+    // Back faces are rendered only for two-sided surfaces, they must be lit as front faces.
+    if (!gl_FrontFacing) worldNormal = -worldNormal;
+
+    vec3 lightmap = texture(lightmapTexture, secondTexCoord).rgb;
+    vec3 emission = emissiveIntensity * emissiveColor.rgb * texture(emissiveTexture, texCoord).rgb;
+    vec3 result = (ambientColor.rgb + lightmap) * albedo.rgb + emission;
+
+    if (lightKind != LIGHT_NONE)
+    {
+        vec3 lightVector;
+        float attenuation;
+        if (lightKind == LIGHT_DIRECTIONAL)
+        {
+            lightVector = lightDirection;
+            attenuation = 1.0;
+        }
+        else
+        {
+            lightVector = lightPosition - position;
+            float distance = length(lightVector);
+            lightVector /= distance;
+            attenuation = S_LightDistanceAttenuation(distance, lightRadius);
+            if (lightKind == LIGHT_SPOT)
+            {
+                float spotAngleCos = dot(lightDirection, lightVector);
+                attenuation *= smoothstep(halfConeAngleCos, halfHotspotConeAngleCos, spotAngleCos);
+            }
+        }
+
+        if (usePbr)
+        {
+            float metallic = metallicFactor * texture(metallicTexture, texCoord).b;
+            float roughness = roughnessFactor * texture(roughnessTexture, texCoord).g;
+            vec3 viewVector = normalize(cameraPosition - position);
+            vec3 brdf = S_MetallicRoughnessBRDF(albedo.rgb, metallic, roughness, worldNormal, viewVector, lightVector);
+            result += brdf * attenuation * lightColor.rgb;
+        }
+        else
+        {
+            float specularPower = 255.0 * texture(specularTexture, texCoord).r;
+            float specular = S_SpecularFactor(lightVector, cameraPosition, position, worldNormal, specularPower);
+            float lambertian = max(dot(worldNormal, lightVector), 0.0);
+            result += (albedo.rgb + 0.4 * specular) * lambertian * attenuation * lightColor.rgb;
+        }
+    }
+
+    result = mix(result, fog.color.rgb, S_FogFactor(fog, position, cameraPosition));
+
+    FragColor = vec4(result, albedo.a);
+}
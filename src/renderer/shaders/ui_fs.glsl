@@ -2,6 +2,7 @@
 
 uniform sampler2D diffuseTexture;
 uniform bool isFont;
+uniform bool isSrgbTexture;
 uniform vec4 solidColor;
 
 uniform int brushType;
@@ -17,7 +18,6 @@ uniform vec2 gradientOrigin;
 // End point of linear gradient in normalized coordinates.
 uniform vec2 gradientEnd;
 
-uniform vec2 resolution;
 uniform vec2 boundsMin;
 uniform vec2 boundsMax;
 
@@ -25,12 +25,18 @@ out vec4 fragColor;
 
 in vec2 texCoord;
 in vec4 color;
+in vec2 screenPosition;
 
 float project_point(vec2 a, vec2 b, vec2 p) {
     vec2 ab = b - a;
     return clamp(dot(p - a, ab) / dot(ab, ab), 0.0, 1.0);
 }
 
+vec3 linear_to_srgb(vec3 color) {
+    color = clamp(color, 0.0, 1.0);
+    return mix(color * 12.92, 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, color));
+}
+
 int find_stop_index(float t) {
     int idx = 0;
 
@@ -46,7 +52,7 @@ int find_stop_index(float t) {
 void main()
 {
     vec2 size = vec2(boundsMax.x - boundsMin.x, boundsMax.y - boundsMin.y);
-    vec2 localPosition = (vec2(gl_FragCoord.x, resolution.y - gl_FragCoord.y) - boundsMin) / size;
+    vec2 localPosition = (screenPosition - boundsMin) / size;
 
     if (brushType == 0) {
         // Solid color
@@ -76,7 +82,12 @@ void main()
     }
     else
     {
-        fragColor *= texture(diffuseTexture, texCoord);
+        vec4 diffuseColor = texture(diffuseTexture, texCoord);
+        if (isSrgbTexture)
+        {
+            diffuseColor.rgb = linear_to_srgb(diffuseColor.rgb);
+        }
+        fragColor *= diffuseColor;
     }
 
     fragColor *= color;
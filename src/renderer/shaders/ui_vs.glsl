@@ -8,10 +8,12 @@ uniform mat4 worldViewProjection;
 
 out vec2 texCoord;
 out vec4 color;
+out vec2 screenPosition;
 
 void main()
 {
     texCoord = vertexTexCoord;
     color = vertexColor;
+    screenPosition = vertexPosition.xy;
     gl_Position = worldViewProjection * vec4(vertexPosition, 1.0);
 }
\ No newline at end of file
@@ -199,7 +199,8 @@ impl SpotShadowMapRenderer {
                         &self.shader.program,
                         &DrawParameters {
                             cull_face: CullFace::Back,
-                            culling: true,
+                            // Both faces of two-sided surfaces cast shadows.
+                            culling: !batch.two_sided,
                             color_write: ColorMask::all(false),
                             depth_write: true,
                             stencil_test: false,
@@ -493,7 +494,7 @@ impl PointShadowMapRenderer {
                             &self.shader.program,
                             &DrawParameters {
                                 cull_face: CullFace::Back,
-                                culling: true,
+                                culling: !batch.two_sided,
                                 color_write: Default::default(),
                                 depth_write: true,
                                 stencil_test: false,
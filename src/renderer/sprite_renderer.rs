@@ -1,9 +1,10 @@
 use crate::{
-    core::{math::Matrix4Ext, math::Rect, scope_profile},
+    core::{math::Matrix4Ext, math::Rect, pool::Handle, scope_profile},
     renderer::{
         error::RendererError,
+        fog::FogUniforms,
         framework::{
-            framebuffer::{CullFace, DrawParameters, FrameBuffer, FrameBufferTrait},
+            framebuffer::{CullFace, DrawParameters, FrameBufferTrait},
             gl,
             gpu_program::{GpuProgram, UniformLocation, UniformValue},
             gpu_texture::GpuTexture,
@@ -12,7 +13,7 @@ use crate::{
         surface::SurfaceSharedData,
         GeometryCache, RenderPassStatistics, TextureCache,
     },
-    scene::{camera::Camera, graph::Graph, node::Node},
+    scene::{camera::Camera, fog::FogSettings, graph::Graph, node::Node},
 };
 use std::{cell::RefCell, rc::Rc};
 
@@ -26,6 +27,8 @@ struct SpriteShader {
     diffuse_texture: UniformLocation,
     size: UniformLocation,
     rotation: UniformLocation,
+    camera_position: UniformLocation,
+    fog: FogUniforms,
 }
 
 impl SpriteShader {
@@ -42,6 +45,8 @@ impl SpriteShader {
             diffuse_texture: program.uniform_location("diffuseTexture")?,
             color: program.uniform_location("color")?,
             rotation: program.uniform_location("rotation")?,
+            camera_position: program.uniform_location("cameraPosition")?,
+            fog: FogUniforms::new(&program)?,
             program,
         })
     }
@@ -54,11 +59,14 @@ pub struct SpriteRenderer {
 
 pub(in crate) struct SpriteRenderContext<'a, 'b, 'c> {
     pub state: &'a mut PipelineState,
-    pub framebuffer: &'b mut FrameBuffer,
+    pub framebuffer: &'b mut dyn FrameBufferTrait,
     pub graph: &'c Graph,
+    /// Sprite to draw, sprites are drawn one by one in order defined by transparent queue.
+    pub node: Handle<Node>,
     pub camera: &'c Camera,
     pub white_dummy: Rc<RefCell<GpuTexture>>,
     pub viewport: Rect<i32>,
+    pub fog: Option<&'c FogSettings>,
     pub textures: &'a mut TextureCache,
     pub geom_map: &'a mut GeometryCache,
 }
@@ -83,9 +91,11 @@ impl SpriteRenderer {
             state,
             framebuffer,
             graph,
+            node,
             camera,
             white_dummy,
             viewport,
+            fog,
             textures,
             geom_map,
         } = args;
@@ -97,13 +107,8 @@ impl SpriteRenderer {
         let camera_up = inv_view.up();
         let camera_side = inv_view.side();
 
-        for node in graph.linear_iter() {
-            let sprite = if let Node::Sprite(sprite) = node {
-                sprite
-            } else {
-                continue;
-            };
-
+        let node = &graph[node];
+        if let Node::Sprite(sprite) = node {
             let diffuse_texture = if let Some(texture) = sprite.texture() {
                 if let Some(texture) = textures.get(state, texture) {
                     texture
@@ -114,6 +119,7 @@ impl SpriteRenderer {
                 white_dummy.clone()
             };
 
+            self.shader.fog.set(&self.shader.program, state, fog);
             statistics += framebuffer.draw(
                 geom_map.get(state, &self.surface),
                 state,
@@ -155,6 +161,10 @@ impl SpriteRenderer {
                     (self.shader.size, UniformValue::Float(sprite.size())),
                     (self.shader.color, UniformValue::Color(sprite.color())),
                     (self.shader.rotation, UniformValue::Float(sprite.rotation())),
+                    (
+                        self.shader.camera_position,
+                        UniformValue::Vector3(camera.global_position()),
+                    ),
                 ],
             );
         }
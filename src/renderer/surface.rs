@@ -3,6 +3,10 @@
 //!
 //! Surfaces can use the same data source across many instances, this is a memory optimization for
 //! being able to re-use data when you need to draw the same mesh in many places.
+//!
+//! Data for procedural meshes can be made using primitive generators (see
+//! `SurfaceSharedData::make_cube`, `SurfaceSharedData::make_sphere`, etc.) or from raw vertex
+//! attributes using `SurfaceSharedDataBuilder`.
 
 use crate::{
     core::{
@@ -124,6 +128,9 @@ impl Hash for Vertex {
 pub struct SurfaceSharedData {
     pub(in crate) vertices: Vec<Vertex>,
     pub(in crate) triangles: Vec<TriangleDefinition>,
+    // Colors are stored separately from vertices, so surfaces without vertex colors do not
+    // waste memory on them. Color attribute is bound only if there are colors.
+    pub(in crate) colors: Option<Vec<Color>>,
     // If true - indicates that surface was generated and does not have reference
     // resource. Procedural data will be serialized.
     is_procedural: bool,
@@ -134,6 +141,7 @@ impl Default for SurfaceSharedData {
         Self {
             vertices: Default::default(),
             triangles: Default::default(),
+            colors: None,
             is_procedural: false,
         }
     }
@@ -149,6 +157,7 @@ impl SurfaceSharedData {
         Self {
             vertices,
             triangles,
+            colors: None,
             is_procedural,
         }
     }
@@ -159,6 +168,7 @@ impl SurfaceSharedData {
         Self {
             vertices: raw.vertices,
             triangles: raw.triangles,
+            colors: None,
             is_procedural,
         }
     }
@@ -174,12 +184,71 @@ impl SurfaceSharedData {
         &mut self.vertices
     }
 
+    /// Adds copy of a vertex (and its color, if any) at the end of vertices array and returns
+    /// index of the copy.
+    pub(in crate) fn duplicate_vertex(&mut self, index: usize) -> u32 {
+        let vertex = self.vertices[index];
+        self.vertices.push(vertex);
+        if let Some(colors) = self.colors.as_mut() {
+            let color = colors[index];
+            colors.push(color);
+        }
+        (self.vertices.len() - 1) as u32
+    }
+
     /// Return shared reference to triangles array.
     #[inline]
     pub fn triangles(&self) -> &[TriangleDefinition] {
         self.triangles.as_slice()
     }
 
+    /// Returns colors of vertices, if any. Each color corresponds to a vertex with the same index.
+    #[inline]
+    pub fn vertex_colors(&self) -> Option<&[Color]> {
+        self.colors.as_deref()
+    }
+
+    /// Returns true if the surface has vertex colors. Color of each vertex is multiplied with
+    /// diffuse (albedo) color of surface.
+    #[inline]
+    pub fn has_vertex_colors(&self) -> bool {
+        self.colors.is_some()
+    }
+
+    /// Sets colors of vertices, there must be one color per vertex. `None` removes vertex
+    /// colors, so the surface won't spend memory on them.
+    pub fn set_vertex_colors(&mut self, colors: Option<Vec<Color>>) {
+        if let Some(colors) = colors.as_ref() {
+            assert_eq!(colors.len(), self.vertices.len());
+        }
+        self.colors = colors;
+    }
+
+    /// Sets same color for every vertex of the surface.
+    pub fn set_vertex_color(&mut self, color: Color) {
+        self.colors = Some(vec![color; self.vertices.len()]);
+    }
+
+    /// Sets color of each vertex using given function, it is useful to add details to procedural
+    /// meshes, for example to bake ambient occlusion in vertex colors.
+    ///
+    /// ```
+    /// use rg3d::{
+    ///     core::{algebra::Matrix4, color::Color},
+    ///     renderer::surface::SurfaceSharedData,
+    /// };
+    ///
+    /// let mut data = SurfaceSharedData::make_cube(Matrix4::identity());
+    /// // Darken bottom of the cube.
+    /// data.paint_vertices(|vertex| {
+    ///     let k = ((vertex.position.y + 0.5) * 255.0) as u8;
+    ///     Color::opaque(k, k, k)
+    /// });
+    /// ```
+    pub fn paint_vertices<F: FnMut(&Vertex) -> Color>(&mut self, func: F) {
+        self.colors = Some(self.vertices.iter().map(func).collect());
+    }
+
     /// Calculates tangents of surface. Tangents are needed for correct lighting, you will
     /// get incorrect lighting if tangents of your surface are invalid! When engine loads
     /// a mesh from "untrusted" source, it automatically calculates tangents for you, so
@@ -416,6 +485,39 @@ impl SurfaceSharedData {
         }
     }
 
+    /// Calculates smooth normals, normal of each vertex is an average of normals of triangles
+    /// that share the vertex (weighted by their area). It should be used for meshes with shared
+    /// vertices, for example for output of marching cubes.
+    pub fn calculate_smooth_normals(&mut self) {
+        for vertex in self.vertices.iter_mut() {
+            vertex.normal = Vector3::default();
+        }
+
+        for triangle in self.triangles.iter() {
+            let ia = triangle[0] as usize;
+            let ib = triangle[1] as usize;
+            let ic = triangle[2] as usize;
+
+            let a = self.vertices[ia].position;
+            let b = self.vertices[ib].position;
+            let c = self.vertices[ic].position;
+
+            // Length of cross product is twice the area of triangle.
+            let normal = (b - a).cross(&(c - a));
+
+            self.vertices[ia].normal += normal;
+            self.vertices[ib].normal += normal;
+            self.vertices[ic].normal += normal;
+        }
+
+        for vertex in self.vertices.iter_mut() {
+            vertex.normal = vertex
+                .normal
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vector3::y);
+        }
+    }
+
     /// Creates sphere of specified radius with given slices and stacks.
     pub fn make_sphere(slices: usize, stacks: usize, r: f32) -> Self {
         let mut builder = RawMeshBuilder::<Vertex>::new(stacks * slices, stacks * slices * 3);
@@ -878,6 +980,45 @@ impl SurfaceSharedData {
         data
     }
 
+    /// Creates unit plane at oXZ plane with given transform, the plane is split into grid of
+    /// given amount of segments along X and Z axes. Unlike quad, subdivided plane can be
+    /// deformed per-vertex, for example to make a terrain or a water surface.
+    pub fn make_plane(x_segments: usize, z_segments: usize, transform: Matrix4<f32>) -> Self {
+        let x_segments = x_segments.max(1);
+        let z_segments = z_segments.max(1);
+
+        let mut vertices = Vec::with_capacity((x_segments + 1) * (z_segments + 1));
+        for j in 0..=z_segments {
+            let z = 0.5 - j as f32 / z_segments as f32;
+            for i in 0..=x_segments {
+                let x = i as f32 / x_segments as f32 - 0.5;
+                let position = transform.transform_point(&Point3::new(x, 0.0, z)).coords;
+                vertices.push(Vertex::from_pos_uv(
+                    position,
+                    Vector2::new(x + 0.5, z + 0.5),
+                ));
+            }
+        }
+
+        let mut indices = Vec::with_capacity(2 * x_segments * z_segments);
+        let row = (x_segments + 1) as u32;
+        for j in 0..z_segments as u32 {
+            for i in 0..x_segments as u32 {
+                let a = j * row + i;
+                let b = a + 1;
+                let c = b + row;
+                let d = a + row;
+                indices.push(TriangleDefinition([a, b, c]));
+                indices.push(TriangleDefinition([a, c, d]));
+            }
+        }
+
+        let mut data = Self::new(vertices, indices, true);
+        data.calculate_smooth_normals();
+        data.calculate_tangents();
+        data
+    }
+
     /// Calculates unique id based on contents of surface shared data.
     pub fn id(&self) -> u64 {
         let mut hasher = DefaultHasher::new();
@@ -893,6 +1034,14 @@ impl SurfaceSharedData {
                 self.vertices.len() * std::mem::size_of::<Vertex>(),
             );
             vertices_bytes.hash(&mut hasher);
+
+            if let Some(colors) = self.colors.as_ref() {
+                let colors_bytes = std::slice::from_raw_parts(
+                    colors.as_ptr() as *const u8,
+                    colors.len() * std::mem::size_of::<Color>(),
+                );
+                colors_bytes.hash(&mut hasher);
+            }
         }
         hasher.finish()
     }
@@ -905,11 +1054,14 @@ impl Visit for SurfaceSharedData {
         if visitor.is_reading() || (self.is_procedural && !visitor.is_reading()) {
             self.vertices.visit("Vertices", visitor)?;
             self.triangles.visit("Triangles", visitor)?;
+            let _ = self.colors.visit("Colors", visitor);
         } else {
             let mut dummy = Vec::<Vertex>::new();
             dummy.visit("Vertices", visitor)?;
             let mut dummy = Vec::<TriangleDefinition>::new();
             dummy.visit("Triangles", visitor)?;
+            let mut dummy = Option::<Vec<Color>>::None;
+            dummy.visit("Colors", visitor)?;
         }
 
         self.is_procedural.visit("IsProcedural", visitor)?;
@@ -918,6 +1070,172 @@ impl Visit for SurfaceSharedData {
     }
 }
 
+/// An error that may occur when surface data is built from raw arrays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SurfaceDataError {
+    /// Length of an array of vertex attributes does not match the amount of positions.
+    AttributeCountMismatch {
+        /// Name of the attribute.
+        attribute: &'static str,
+        /// Amount of positions.
+        expected: usize,
+        /// Actual length of the array.
+        actual: usize,
+    },
+    /// Amount of indices is not a multiple of three.
+    InvalidIndexCount(usize),
+    /// Index refers to a vertex that does not exist.
+    IndexOutOfBounds {
+        /// Invalid index.
+        index: u32,
+        /// Amount of vertices.
+        vertex_count: usize,
+    },
+}
+
+/// Builds surface data from separate arrays of vertex attributes, it is the simplest way to
+/// make procedural geometry (custom shapes, output of marching cubes, etc.). Missing normals
+/// are calculated as smooth normals, tangents are always calculated.
+///
+/// ```
+/// use rg3d::{
+///     core::algebra::{Vector2, Vector3},
+///     renderer::surface::{SurfaceBuilder, SurfaceSharedDataBuilder},
+///     scene::{base::BaseBuilder, graph::Graph, mesh::MeshBuilder},
+/// };
+/// use std::sync::{Arc, RwLock};
+///
+/// let data = SurfaceSharedDataBuilder::new(
+///     vec![
+///         Vector3::new(0.0, 0.0, 0.0),
+///         Vector3::new(0.0, 1.0, 0.0),
+///         Vector3::new(1.0, 0.0, 0.0),
+///     ],
+///     vec![0, 1, 2],
+/// )
+/// .with_tex_coords(vec![
+///     Vector2::new(0.0, 0.0),
+///     Vector2::new(0.0, 1.0),
+///     Vector2::new(1.0, 0.0),
+/// ])
+/// .build()
+/// .unwrap();
+///
+/// let mut graph = Graph::new();
+/// MeshBuilder::new(BaseBuilder::new())
+///     .with_surfaces(vec![SurfaceBuilder::new(Arc::new(RwLock::new(data))).build()])
+///     .build(&mut graph);
+/// ```
+pub struct SurfaceSharedDataBuilder {
+    positions: Vec<Vector3<f32>>,
+    normals: Option<Vec<Vector3<f32>>>,
+    tex_coords: Option<Vec<Vector2<f32>>>,
+    colors: Option<Vec<Color>>,
+    indices: Vec<u32>,
+}
+
+impl SurfaceSharedDataBuilder {
+    /// Creates new builder with given vertex positions and indices, each three indices form
+    /// a triangle.
+    pub fn new(positions: Vec<Vector3<f32>>, indices: Vec<u32>) -> Self {
+        Self {
+            positions,
+            normals: None,
+            tex_coords: None,
+            colors: None,
+            indices,
+        }
+    }
+
+    /// Sets desired normals, there must be one normal per position.
+    pub fn with_normals(mut self, normals: Vec<Vector3<f32>>) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    /// Sets desired texture coordinates, there must be one pair per position.
+    pub fn with_tex_coords(mut self, tex_coords: Vec<Vector2<f32>>) -> Self {
+        self.tex_coords = Some(tex_coords);
+        self
+    }
+
+    /// Sets desired vertex colors, there must be one color per position.
+    pub fn with_colors(mut self, colors: Vec<Color>) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+
+    /// Creates new procedural surface data.
+    pub fn build(self) -> Result<SurfaceSharedData, SurfaceDataError> {
+        let vertex_count = self.positions.len();
+
+        fn check<T>(
+            attribute: &'static str,
+            array: &Option<Vec<T>>,
+            expected: usize,
+        ) -> Result<(), SurfaceDataError> {
+            match array {
+                Some(array) if array.len() != expected => {
+                    Err(SurfaceDataError::AttributeCountMismatch {
+                        attribute,
+                        expected,
+                        actual: array.len(),
+                    })
+                }
+                _ => Ok(()),
+            }
+        }
+        check("Normals", &self.normals, vertex_count)?;
+        check("TexCoords", &self.tex_coords, vertex_count)?;
+        check("Colors", &self.colors, vertex_count)?;
+
+        if self.indices.len() % 3 != 0 {
+            return Err(SurfaceDataError::InvalidIndexCount(self.indices.len()));
+        }
+        if let Some(&index) = self
+            .indices
+            .iter()
+            .find(|&&index| index as usize >= vertex_count)
+        {
+            return Err(SurfaceDataError::IndexOutOfBounds {
+                index,
+                vertex_count,
+            });
+        }
+
+        let mut vertices = self
+            .positions
+            .into_iter()
+            .map(|position| Vertex::from_pos_uv(position, Vector2::default()))
+            .collect::<Vec<_>>();
+        if let Some(tex_coords) = self.tex_coords {
+            for (vertex, tex_coord) in vertices.iter_mut().zip(tex_coords) {
+                vertex.tex_coord = tex_coord;
+            }
+        }
+
+        let triangles = self
+            .indices
+            .chunks_exact(3)
+            .map(|i| TriangleDefinition([i[0], i[1], i[2]]))
+            .collect();
+
+        let mut data = SurfaceSharedData::new(vertices, triangles, true);
+        data.colors = self.colors;
+        match self.normals {
+            Some(normals) => {
+                for (vertex, normal) in data.vertices.iter_mut().zip(normals) {
+                    vertex.normal = normal;
+                }
+            }
+            None => data.calculate_smooth_normals(),
+        }
+        data.calculate_tangents();
+
+        Ok(data)
+    }
+}
+
 /// Vertex weight is a pair of (bone; weight) that affects vertex.
 #[derive(Copy, Clone, Debug)]
 pub struct VertexWeight {
@@ -1005,6 +1323,311 @@ impl VertexWeightSet {
     }
 }
 
+/// Physically-based material which uses metallic-roughness workflow (the same as glTF 2.0).
+/// Surface with such material will be lit using Cook-Torrance BRDF instead of Blinn-Phong.
+///
+/// # Texture channels
+///
+/// Metallic, roughness and ambient occlusion values are fetched from different channels of
+/// their textures: metallic - from blue, roughness - from green and ambient occlusion - from
+/// red. This allows you to use single packed "ORM" texture (which is very common in asset
+/// pipelines) for all three slots at once.
+///
+/// Each texture value is multiplied by appropriate factor, so if a texture is not set only
+/// factor will be used.
+///
+/// # Emission
+///
+/// Emission (emissive color multiplied by emissive texture and intensity) is added to the
+/// final color of a surface after lighting, so self-illuminated surfaces like screens or lava
+/// look the same in dark and lit areas. Frame is rendered into floating-point target, so
+/// intensity greater than one is not clamped and can be used by post-effects to detect bright
+/// areas.
+#[derive(Debug, Clone)]
+pub struct PbrMaterial {
+    albedo_texture: Option<Texture>,
+    metallic_texture: Option<Texture>,
+    roughness_texture: Option<Texture>,
+    ao_texture: Option<Texture>,
+    emissive_texture: Option<Texture>,
+    albedo_color: Color,
+    metallic: f32,
+    roughness: f32,
+    emissive_color: Color,
+    emissive_intensity: f32,
+}
+
+impl Default for PbrMaterial {
+    fn default() -> Self {
+        Self {
+            albedo_texture: None,
+            metallic_texture: None,
+            roughness_texture: None,
+            ao_texture: None,
+            emissive_texture: None,
+            albedo_color: Color::WHITE,
+            metallic: 0.0,
+            roughness: 1.0,
+            emissive_color: Color::BLACK,
+            emissive_intensity: 1.0,
+        }
+    }
+}
+
+impl PbrMaterial {
+    /// Returns current albedo (base color) texture.
+    #[inline]
+    pub fn albedo_texture(&self) -> Option<Texture> {
+        self.albedo_texture.clone()
+    }
+
+    /// Sets new albedo (base color) texture.
+    #[inline]
+    pub fn set_albedo_texture(&mut self, tex: Option<Texture>) {
+        self.albedo_texture = tex;
+    }
+
+    /// Returns current metallic texture.
+    #[inline]
+    pub fn metallic_texture(&self) -> Option<Texture> {
+        self.metallic_texture.clone()
+    }
+
+    /// Sets new metallic texture. Metallic value is fetched from blue channel.
+    #[inline]
+    pub fn set_metallic_texture(&mut self, tex: Option<Texture>) {
+        self.metallic_texture = tex;
+    }
+
+    /// Returns current roughness texture.
+    #[inline]
+    pub fn roughness_texture(&self) -> Option<Texture> {
+        self.roughness_texture.clone()
+    }
+
+    /// Sets new roughness texture. Roughness value is fetched from green channel.
+    #[inline]
+    pub fn set_roughness_texture(&mut self, tex: Option<Texture>) {
+        self.roughness_texture = tex;
+    }
+
+    /// Returns current ambient occlusion texture.
+    #[inline]
+    pub fn ao_texture(&self) -> Option<Texture> {
+        self.ao_texture.clone()
+    }
+
+    /// Sets new ambient occlusion texture. Occlusion value is fetched from red channel.
+    #[inline]
+    pub fn set_ao_texture(&mut self, tex: Option<Texture>) {
+        self.ao_texture = tex;
+    }
+
+    /// Returns current emissive texture.
+    #[inline]
+    pub fn emissive_texture(&self) -> Option<Texture> {
+        self.emissive_texture.clone()
+    }
+
+    /// Sets new emissive texture.
+    #[inline]
+    pub fn set_emissive_texture(&mut self, tex: Option<Texture>) {
+        self.emissive_texture = tex;
+    }
+
+    /// Returns current albedo color.
+    #[inline]
+    pub fn albedo_color(&self) -> Color {
+        self.albedo_color
+    }
+
+    /// Sets new albedo color, it will be multiplied with albedo texture.
+    #[inline]
+    pub fn set_albedo_color(&mut self, color: Color) {
+        self.albedo_color = color;
+    }
+
+    /// Returns current metallic factor.
+    #[inline]
+    pub fn metallic(&self) -> f32 {
+        self.metallic
+    }
+
+    /// Sets new metallic factor. Value will be clamped to [0; 1] range.
+    #[inline]
+    pub fn set_metallic(&mut self, metallic: f32) {
+        self.metallic = metallic.clamp(0.0, 1.0);
+    }
+
+    /// Returns current roughness factor.
+    #[inline]
+    pub fn roughness(&self) -> f32 {
+        self.roughness
+    }
+
+    /// Sets new roughness factor. Value will be clamped to [0; 1] range.
+    #[inline]
+    pub fn set_roughness(&mut self, roughness: f32) {
+        self.roughness = roughness.clamp(0.0, 1.0);
+    }
+
+    /// Returns current emissive color.
+    #[inline]
+    pub fn emissive_color(&self) -> Color {
+        self.emissive_color
+    }
+
+    /// Sets new emissive color, it will be multiplied with emissive texture. Black color
+    /// means no emission.
+    #[inline]
+    pub fn set_emissive_color(&mut self, color: Color) {
+        self.emissive_color = color;
+    }
+
+    /// Returns current emissive intensity.
+    #[inline]
+    pub fn emissive_intensity(&self) -> f32 {
+        self.emissive_intensity
+    }
+
+    /// Sets new emissive intensity, it will be multiplied with emissive color. Negative values
+    /// are clamped to zero.
+    #[inline]
+    pub fn set_emissive_intensity(&mut self, intensity: f32) {
+        self.emissive_intensity = intensity.max(0.0);
+    }
+
+    /// Sets emissive color and intensity at once. Emission is not affected by scene lighting.
+    #[inline]
+    pub fn set_emissive(&mut self, color: Color, intensity: f32) {
+        self.set_emissive_color(color);
+        self.set_emissive_intensity(intensity);
+    }
+
+    pub(in crate) fn hash_into<H: Hasher>(&self, hasher: &mut H) {
+        for texture in [
+            &self.albedo_texture,
+            &self.metallic_texture,
+            &self.roughness_texture,
+            &self.ao_texture,
+            &self.emissive_texture,
+        ]
+        .iter()
+        .flat_map(|texture| texture.as_ref())
+        {
+            texture.key().hash(hasher);
+        }
+        self.metallic.to_bits().hash(hasher);
+        self.roughness.to_bits().hash(hasher);
+        Into::<u32>::into(self.emissive_color).hash(hasher);
+        self.emissive_intensity.to_bits().hash(hasher);
+    }
+}
+
+impl Visit for PbrMaterial {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.albedo_texture.visit("AlbedoTexture", visitor)?;
+        self.metallic_texture.visit("MetallicTexture", visitor)?;
+        self.roughness_texture.visit("RoughnessTexture", visitor)?;
+        self.ao_texture.visit("AoTexture", visitor)?;
+        self.emissive_texture.visit("EmissiveTexture", visitor)?;
+        self.albedo_color.visit("AlbedoColor", visitor)?;
+        self.metallic.visit("Metallic", visitor)?;
+        self.roughness.visit("Roughness", visitor)?;
+        self.emissive_color.visit("EmissiveColor", visitor)?;
+        let _ = self.emissive_intensity.visit("EmissiveIntensity", visitor);
+
+        visitor.leave_region()
+    }
+}
+
+/// PBR material builder allows you to create materials in declarative manner.
+pub struct PbrMaterialBuilder {
+    material: PbrMaterial,
+}
+
+impl Default for PbrMaterialBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PbrMaterialBuilder {
+    /// Creates new builder instance with white non-metallic fully rough material.
+    pub fn new() -> Self {
+        Self {
+            material: Default::default(),
+        }
+    }
+
+    /// Sets desired albedo (base color) texture.
+    pub fn with_albedo_texture(mut self, tex: Texture) -> Self {
+        self.material.albedo_texture = Some(tex);
+        self
+    }
+
+    /// Sets desired metallic texture, value is fetched from blue channel.
+    pub fn with_metallic_texture(mut self, tex: Texture) -> Self {
+        self.material.metallic_texture = Some(tex);
+        self
+    }
+
+    /// Sets desired roughness texture, value is fetched from green channel.
+    pub fn with_roughness_texture(mut self, tex: Texture) -> Self {
+        self.material.roughness_texture = Some(tex);
+        self
+    }
+
+    /// Sets desired ambient occlusion texture, value is fetched from red channel.
+    pub fn with_ao_texture(mut self, tex: Texture) -> Self {
+        self.material.ao_texture = Some(tex);
+        self
+    }
+
+    /// Sets desired emissive texture.
+    pub fn with_emissive_texture(mut self, tex: Texture) -> Self {
+        self.material.emissive_texture = Some(tex);
+        self
+    }
+
+    /// Sets desired albedo color.
+    pub fn with_albedo_color(mut self, color: Color) -> Self {
+        self.material.albedo_color = color;
+        self
+    }
+
+    /// Sets desired metallic factor.
+    pub fn with_metallic(mut self, metallic: f32) -> Self {
+        self.material.set_metallic(metallic);
+        self
+    }
+
+    /// Sets desired roughness factor.
+    pub fn with_roughness(mut self, roughness: f32) -> Self {
+        self.material.set_roughness(roughness);
+        self
+    }
+
+    /// Sets desired emissive color.
+    pub fn with_emissive_color(mut self, color: Color) -> Self {
+        self.material.emissive_color = color;
+        self
+    }
+
+    /// Sets desired emissive intensity.
+    pub fn with_emissive_intensity(mut self, intensity: f32) -> Self {
+        self.material.set_emissive_intensity(intensity);
+        self
+    }
+
+    /// Creates new material.
+    pub fn build(self) -> PbrMaterial {
+        self.material
+    }
+}
+
 /// See module docs.
 #[derive(Debug, Default)]
 pub struct Surface {
@@ -1016,6 +1639,7 @@ pub struct Surface {
     lightmap_texture: Option<Texture>,
     specular_texture: Option<Texture>,
     roughness_texture: Option<Texture>,
+    emissive_texture: Option<Texture>,
     /// Temporal array for FBX conversion needs, it holds skinning data (weight + bone handle)
     /// and will be used to fill actual bone indices and weight in vertices that will be
     /// sent to GPU. The idea is very simple: GPU needs to know only indices of matrices of
@@ -1026,6 +1650,12 @@ pub struct Surface {
     /// Array of handle to scene nodes which are used as bones.
     pub bones: Vec<Handle<Node>>,
     color: Color,
+    emissive_color: Color,
+    emissive_intensity: f32,
+    pbr_material: Option<PbrMaterial>,
+    vertex_colors_enabled: bool,
+    two_sided: bool,
+    transparent: bool,
 }
 
 /// Shallow copy of surface.
@@ -1042,10 +1672,17 @@ impl Clone for Surface {
             normal_texture: self.normal_texture.clone(),
             specular_texture: self.specular_texture.clone(),
             roughness_texture: self.roughness_texture.clone(),
+            emissive_texture: self.emissive_texture.clone(),
             bones: self.bones.clone(),
             vertex_weights: Vec::new(), // Intentionally not copied.
             color: self.color,
+            emissive_color: self.emissive_color,
+            emissive_intensity: self.emissive_intensity,
             lightmap_texture: self.lightmap_texture.clone(),
+            pbr_material: self.pbr_material.clone(),
+            vertex_colors_enabled: self.vertex_colors_enabled,
+            two_sided: self.two_sided,
+            transparent: self.transparent,
         }
     }
 }
@@ -1060,10 +1697,17 @@ impl Surface {
             normal_texture: None,
             specular_texture: None,
             roughness_texture: None,
+            emissive_texture: None,
             bones: Vec::new(),
             vertex_weights: Vec::new(),
             color: Color::WHITE,
+            emissive_color: Color::BLACK,
+            emissive_intensity: 1.0,
             lightmap_texture: None,
+            pbr_material: None,
+            vertex_colors_enabled: false,
+            two_sided: false,
+            transparent: false,
         }
     }
 
@@ -1089,6 +1733,17 @@ impl Surface {
         if let Some(lightmap_texture) = self.lightmap_texture.as_ref() {
             lightmap_texture.key().hash(&mut hasher);
         }
+        if let Some(emissive_texture) = self.emissive_texture.as_ref() {
+            emissive_texture.key().hash(&mut hasher);
+        }
+        Into::<u32>::into(self.emissive_color).hash(&mut hasher);
+        self.emissive_intensity.to_bits().hash(&mut hasher);
+        if let Some(pbr_material) = self.pbr_material.as_ref() {
+            pbr_material.hash_into(&mut hasher);
+        }
+        self.vertex_colors_enabled.hash(&mut hasher);
+        self.two_sided.hash(&mut hasher);
+        self.transparent.hash(&mut hasher);
 
         hasher.finish()
     }
@@ -1099,6 +1754,13 @@ impl Surface {
         self.data.as_ref().unwrap().clone()
     }
 
+    /// Sets new data source of the surface, it is used to change geometry of the surface while
+    /// keeping its material.
+    #[inline]
+    pub fn set_data(&mut self, data: Arc<RwLock<SurfaceSharedData>>) {
+        self.data = Some(data);
+    }
+
     /// Sets new diffuse texture.
     #[inline]
     pub fn set_diffuse_texture(&mut self, tex: Option<Texture>) {
@@ -1171,6 +1833,133 @@ impl Surface {
         self.color
     }
 
+    /// Sets new emissive texture. Emission of a surface is added to its final color after
+    /// lighting, so it is not affected by scene lighting. If surface has physically-based
+    /// material, emission parameters of the material are changed instead, so there is always
+    /// single source of emission for a surface.
+    #[inline]
+    pub fn set_emissive_texture(&mut self, tex: Option<Texture>) {
+        match self.pbr_material.as_mut() {
+            Some(material) => material.set_emissive_texture(tex),
+            None => self.emissive_texture = tex,
+        }
+    }
+
+    /// Returns current emissive texture, it is emissive texture of physically-based material
+    /// if surface has one.
+    #[inline]
+    pub fn emissive_texture(&self) -> Option<Texture> {
+        match self.pbr_material.as_ref() {
+            Some(material) => material.emissive_texture(),
+            None => self.emissive_texture.clone(),
+        }
+    }
+
+    /// Sets new emissive color, it will be multiplied with emissive texture. Black color
+    /// (default) means no emission. See `set_emissive_texture` for surfaces with
+    /// physically-based material.
+    #[inline]
+    pub fn set_emissive_color(&mut self, color: Color) {
+        match self.pbr_material.as_mut() {
+            Some(material) => material.set_emissive_color(color),
+            None => self.emissive_color = color,
+        }
+    }
+
+    /// Returns current emissive color.
+    #[inline]
+    pub fn emissive_color(&self) -> Color {
+        match self.pbr_material.as_ref() {
+            Some(material) => material.emissive_color(),
+            None => self.emissive_color,
+        }
+    }
+
+    /// Sets new emissive intensity (strength), it will be multiplied with emissive color.
+    /// Negative values are clamped to zero. See `set_emissive_texture` for surfaces with
+    /// physically-based material.
+    #[inline]
+    pub fn set_emissive_intensity(&mut self, intensity: f32) {
+        match self.pbr_material.as_mut() {
+            Some(material) => material.set_emissive_intensity(intensity),
+            None => self.emissive_intensity = intensity.max(0.0),
+        }
+    }
+
+    /// Returns current emissive intensity.
+    #[inline]
+    pub fn emissive_intensity(&self) -> f32 {
+        match self.pbr_material.as_ref() {
+            Some(material) => material.emissive_intensity(),
+            None => self.emissive_intensity,
+        }
+    }
+
+    /// Sets new physically-based material. When surface has such material, its diffuse,
+    /// specular and roughness textures are ignored and surface is lit using metallic-roughness
+    /// model. Normal and lightmap textures are still used. Emission of the material replaces
+    /// emission of the surface, emission of the surface is used again when material is removed.
+    #[inline]
+    pub fn set_pbr_material(&mut self, material: Option<PbrMaterial>) {
+        self.pbr_material = material;
+    }
+
+    /// Returns current physically-based material (if any).
+    #[inline]
+    pub fn pbr_material(&self) -> Option<&PbrMaterial> {
+        self.pbr_material.as_ref()
+    }
+
+    /// Returns current physically-based material (if any).
+    #[inline]
+    pub fn pbr_material_mut(&mut self) -> Option<&mut PbrMaterial> {
+        self.pbr_material.as_mut()
+    }
+
+    /// Enables or disables vertex colors. When enabled, color of each vertex is multiplied
+    /// with diffuse (albedo) color of surface. Disabled by default, because most meshes do
+    /// not have meaningful vertex colors. Has no effect if surface data has no vertex colors,
+    /// see `SurfaceSharedData::set_vertex_colors`.
+    #[inline]
+    pub fn set_vertex_colors_enabled(&mut self, enabled: bool) {
+        self.vertex_colors_enabled = enabled;
+    }
+
+    /// Returns true if vertex colors are enabled.
+    #[inline]
+    pub fn vertex_colors_enabled(&self) -> bool {
+        self.vertex_colors_enabled
+    }
+
+    /// Makes surface visible from both sides. Back faces of two-sided surface are lit as if
+    /// they face the viewer, it is useful for foliage, cloth, etc. Disabled by default.
+    #[inline]
+    pub fn set_two_sided(&mut self, two_sided: bool) {
+        self.two_sided = two_sided;
+    }
+
+    /// Returns true if surface is visible from both sides.
+    #[inline]
+    pub fn is_two_sided(&self) -> bool {
+        self.two_sided
+    }
+
+    /// Makes surface transparent. Transparent surfaces are not rendered in deferred pass, instead
+    /// they are drawn after lighting with alpha blending, back to front. Opacity is defined by
+    /// alpha of surface color (see `set_color`), diffuse texture and vertex colors. Lighting of
+    /// transparent surfaces is simplified: only ambient light and single brightest light source
+    /// is applied to them and they do not receive shadows. Disabled by default.
+    #[inline]
+    pub fn set_transparent(&mut self, transparent: bool) {
+        self.transparent = transparent;
+    }
+
+    /// Returns true if surface is transparent.
+    #[inline]
+    pub fn is_transparent(&self) -> bool {
+        self.transparent
+    }
+
     /// Returns list of bones that affects the surface.
     #[inline]
     pub fn bones(&self) -> &[Handle<Node>] {
@@ -1195,6 +1984,27 @@ impl Visit for Surface {
         // be missing on previous versions.
         let _ = self.lightmap_texture.visit("LightmapTexture", visitor);
 
+        let _ = self.pbr_material.visit("PbrMaterial", visitor);
+        let _ = self
+            .vertex_colors_enabled
+            .visit("VertexColorsEnabled", visitor);
+
+        // Emission is missing in previous versions, surfaces had no emission at all.
+        let _ = self.emissive_texture.visit("EmissiveTexture", visitor);
+        if self.emissive_color.visit("EmissiveColor", visitor).is_err() {
+            self.emissive_color = Color::BLACK;
+        }
+        if self
+            .emissive_intensity
+            .visit("EmissiveIntensity", visitor)
+            .is_err()
+        {
+            self.emissive_intensity = 1.0;
+        }
+
+        let _ = self.two_sided.visit("TwoSided", visitor);
+        let _ = self.transparent.visit("Transparent", visitor);
+
         visitor.leave_region()
     }
 }
@@ -1207,8 +2017,15 @@ pub struct SurfaceBuilder {
     lightmap_texture: Option<Texture>,
     specular_texture: Option<Texture>,
     roughness_texture: Option<Texture>,
+    emissive_texture: Option<Texture>,
     bones: Vec<Handle<Node>>,
     color: Color,
+    emissive_color: Option<Color>,
+    emissive_intensity: Option<f32>,
+    pbr_material: Option<PbrMaterial>,
+    vertex_colors_enabled: bool,
+    two_sided: bool,
+    transparent: bool,
 }
 
 impl SurfaceBuilder {
@@ -1221,8 +2038,15 @@ impl SurfaceBuilder {
             lightmap_texture: None,
             specular_texture: None,
             roughness_texture: None,
+            emissive_texture: None,
             bones: Default::default(),
             color: Color::WHITE,
+            emissive_color: None,
+            emissive_intensity: None,
+            pbr_material: None,
+            vertex_colors_enabled: false,
+            two_sided: false,
+            transparent: false,
         }
     }
 
@@ -1262,6 +2086,49 @@ impl SurfaceBuilder {
         self
     }
 
+    /// Sets desired emissive texture. Emission is applied after physically-based material, so
+    /// it changes emission of the material, see `Surface::set_emissive_texture`.
+    pub fn with_emissive_texture(mut self, tex: Texture) -> Self {
+        self.emissive_texture = Some(tex);
+        self
+    }
+
+    /// Sets desired emissive color.
+    pub fn with_emissive_color(mut self, color: Color) -> Self {
+        self.emissive_color = Some(color);
+        self
+    }
+
+    /// Sets desired emissive intensity.
+    pub fn with_emissive_intensity(mut self, intensity: f32) -> Self {
+        self.emissive_intensity = Some(intensity);
+        self
+    }
+
+    /// Sets desired physically-based material.
+    pub fn with_pbr_material(mut self, material: PbrMaterial) -> Self {
+        self.pbr_material = Some(material);
+        self
+    }
+
+    /// Enables or disables vertex colors.
+    pub fn with_vertex_colors_enabled(mut self, enabled: bool) -> Self {
+        self.vertex_colors_enabled = enabled;
+        self
+    }
+
+    /// Sets whether surface is visible from both sides or not.
+    pub fn with_two_sided(mut self, two_sided: bool) -> Self {
+        self.two_sided = two_sided;
+        self
+    }
+
+    /// Sets whether surface is transparent or not.
+    pub fn with_transparent(mut self, transparent: bool) -> Self {
+        self.transparent = transparent;
+        self
+    }
+
     /// Sets desired bones array. Make sure your vertices has valid indices of bones!
     pub fn with_bones(mut self, bones: Vec<Handle<Node>>) -> Self {
         self.bones = bones;
@@ -1270,16 +2137,196 @@ impl SurfaceBuilder {
 
     /// Creates new instance of surface.
     pub fn build(self) -> Surface {
-        Surface {
+        let mut surface = Surface {
             data: Some(self.data),
             diffuse_texture: self.diffuse_texture,
             normal_texture: self.normal_texture,
             lightmap_texture: self.lightmap_texture,
             specular_texture: self.specular_texture,
             roughness_texture: self.roughness_texture,
+            emissive_texture: None,
             vertex_weights: Default::default(),
             bones: self.bones,
             color: self.color,
+            emissive_color: Color::BLACK,
+            emissive_intensity: 1.0,
+            pbr_material: self.pbr_material,
+            vertex_colors_enabled: self.vertex_colors_enabled,
+            two_sided: self.two_sided,
+            transparent: self.transparent,
+        };
+        if self.emissive_texture.is_some() {
+            surface.set_emissive_texture(self.emissive_texture);
+        }
+        if let Some(color) = self.emissive_color {
+            surface.set_emissive_color(color);
+        }
+        if let Some(intensity) = self.emissive_intensity {
+            surface.set_emissive_intensity(intensity);
+        }
+        surface
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            algebra::{Matrix4, Vector2, Vector3},
+            color::Color,
+            visitor::{Visit, Visitor},
+        },
+        renderer::surface::{
+            PbrMaterialBuilder, Surface, SurfaceBuilder, SurfaceDataError, SurfaceSharedData,
+            SurfaceSharedDataBuilder,
+        },
+    };
+    use std::sync::{Arc, RwLock};
+
+    #[test]
+    fn test_emission_routed_to_pbr_material() {
+        let red = Color::opaque(255, 0, 0);
+        let green = Color::opaque(0, 255, 0);
+
+        let data = Arc::new(RwLock::new(SurfaceSharedData::make_cube(
+            Matrix4::identity(),
+        )));
+        let mut surface = Surface::new(data.clone());
+        surface.set_emissive_color(red);
+        surface.set_emissive_intensity(2.0);
+
+        // Material replaces emission of the surface.
+        surface.set_pbr_material(Some(
+            PbrMaterialBuilder::new().with_emissive_color(green).build(),
+        ));
+        assert_eq!(surface.emissive_color(), green);
+        assert_eq!(surface.emissive_intensity(), 1.0);
+
+        // Emission of the material is changed through the surface.
+        surface.set_emissive_intensity(3.0);
+        let material = surface.pbr_material().unwrap();
+        assert_eq!(material.emissive_color(), green);
+        assert_eq!(material.emissive_intensity(), 3.0);
+
+        // Emission of the surface is used again when material is removed.
+        surface.set_pbr_material(None);
+        assert_eq!(surface.emissive_color(), red);
+        assert_eq!(surface.emissive_intensity(), 2.0);
+
+        // Builder applies emission after material, regardless of order of calls.
+        let surface = SurfaceBuilder::new(data)
+            .with_emissive_color(red)
+            .with_pbr_material(
+                PbrMaterialBuilder::new()
+                    .with_emissive_intensity(4.0)
+                    .build(),
+            )
+            .build();
+        let material = surface.pbr_material().unwrap();
+        assert_eq!(material.emissive_color(), red);
+        assert_eq!(material.emissive_intensity(), 4.0);
+    }
+
+    #[test]
+    fn test_vertex_colors_round_trip() {
+        let mut data = SurfaceSharedData::make_cube(Matrix4::identity());
+        // Surfaces have no vertex colors until they're set.
+        assert!(!data.has_vertex_colors());
+        data.paint_vertices(|vertex| {
+            if vertex.position.y > 0.0 {
+                Color::WHITE
+            } else {
+                Color::opaque(50, 60, 70)
+            }
+        });
+        let expected = data.vertex_colors().unwrap().to_vec();
+        assert_eq!(expected.len(), data.get_vertices().len());
+        let mut surface = Surface::new(Arc::new(RwLock::new(data)));
+        surface.set_vertex_colors_enabled(true);
+
+        let path = std::env::temp_dir().join("rg3d_vertex_colors_test.bin");
+        let mut visitor = Visitor::new();
+        surface.visit("Surface", &mut visitor).unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        let mut visitor = Visitor::load_binary(&path).unwrap();
+        let mut loaded = Surface::default();
+        loaded.visit("Surface", &mut visitor).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(loaded.vertex_colors_enabled());
+        let data = loaded.data();
+        let data = data.read().unwrap();
+        assert_eq!(data.vertex_colors(), Some(expected.as_slice()));
+    }
+
+    #[test]
+    fn test_surface_data_builder() {
+        let data = SurfaceSharedDataBuilder::new(
+            vec![
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(0.0, 0.0, -1.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, -1.0),
+            ],
+            vec![0, 2, 1, 1, 2, 3],
+        )
+        .with_tex_coords(vec![
+            Vector2::new(0.0, 0.0),
+            Vector2::new(0.0, 1.0),
+            Vector2::new(1.0, 0.0),
+            Vector2::new(1.0, 1.0),
+        ])
+        .build()
+        .unwrap();
+
+        assert_eq!(data.triangles().len(), 2);
+        for vertex in data.get_vertices() {
+            assert!((vertex.normal - Vector3::y()).norm() < 1.0e-5);
+        }
+        assert_eq!(data.get_vertices()[3].tex_coord, Vector2::new(1.0, 1.0));
+
+        let positions = vec![Vector3::default(); 3];
+        assert_eq!(
+            SurfaceSharedDataBuilder::new(positions.clone(), vec![0, 1])
+                .build()
+                .unwrap_err(),
+            SurfaceDataError::InvalidIndexCount(2)
+        );
+        assert_eq!(
+            SurfaceSharedDataBuilder::new(positions.clone(), vec![0, 1, 3])
+                .build()
+                .unwrap_err(),
+            SurfaceDataError::IndexOutOfBounds {
+                index: 3,
+                vertex_count: 3
+            }
+        );
+        assert_eq!(
+            SurfaceSharedDataBuilder::new(positions, vec![0, 1, 2])
+                .with_normals(vec![Vector3::y(); 2])
+                .build()
+                .unwrap_err(),
+            SurfaceDataError::AttributeCountMismatch {
+                attribute: "Normals",
+                expected: 3,
+                actual: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_make_plane() {
+        let transform =
+            Matrix4::new_translation(&Vector3::new(1.0, 0.0, 0.0)) * Matrix4::new_scaling(2.0);
+        let data = SurfaceSharedData::make_plane(4, 2, transform);
+
+        assert_eq!(data.get_vertices().len(), 15);
+        assert_eq!(data.triangles().len(), 16);
+        for vertex in data.get_vertices() {
+            assert_eq!(vertex.position.y, 0.0);
+            assert!((vertex.position.x - 1.0).abs() <= 1.0 && vertex.position.z.abs() <= 1.0);
+            assert!((vertex.normal - Vector3::y()).norm() < 1.0e-5);
         }
     }
 }
@@ -0,0 +1,60 @@
+use crate::renderer::{
+    error::RendererError,
+    framework::gpu_program::{GpuProgram, UniformLocation},
+};
+
+/// Converts linear HDR frame to display color space and applies color grading and post effects
+/// of a camera, used when frame is drawn into back buffer.
+pub struct ToneMappingShader {
+    pub program: GpuProgram,
+    pub wvp_matrix: UniformLocation,
+    pub frame_texture: UniformLocation,
+    pub tone_mapping: UniformLocation,
+    pub exposure: UniformLocation,
+    pub inv_gamma: UniformLocation,
+    pub color_grading: UniformLocation,
+    pub lut: UniformLocation,
+    pub lut_domain_min: UniformLocation,
+    pub lut_domain_max: UniformLocation,
+    pub blend_lut: UniformLocation,
+    pub blend_lut_domain_min: UniformLocation,
+    pub blend_lut_domain_max: UniformLocation,
+    pub lut_blend: UniformLocation,
+    pub chromatic_aberration: UniformLocation,
+    pub vignette_enabled: UniformLocation,
+    pub vignette_params: UniformLocation,
+    pub vignette_color: UniformLocation,
+    pub grain_intensity: UniformLocation,
+    pub grain_seed: UniformLocation,
+}
+
+impl ToneMappingShader {
+    pub fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/tone_mapping_fs.glsl");
+        let vertex_source = include_str!("shaders/flat_vs.glsl");
+
+        let program = GpuProgram::from_source("ToneMappingShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            frame_texture: program.uniform_location("frameTexture")?,
+            tone_mapping: program.uniform_location("toneMapping")?,
+            exposure: program.uniform_location("exposure")?,
+            inv_gamma: program.uniform_location("invGamma")?,
+            color_grading: program.uniform_location("colorGrading")?,
+            lut: program.uniform_location("lut")?,
+            lut_domain_min: program.uniform_location("lutDomainMin")?,
+            lut_domain_max: program.uniform_location("lutDomainMax")?,
+            blend_lut: program.uniform_location("blendLut")?,
+            blend_lut_domain_min: program.uniform_location("blendLutDomainMin")?,
+            blend_lut_domain_max: program.uniform_location("blendLutDomainMax")?,
+            lut_blend: program.uniform_location("lutBlend")?,
+            chromatic_aberration: program.uniform_location("chromaticAberration")?,
+            vignette_enabled: program.uniform_location("vignetteEnabled")?,
+            vignette_params: program.uniform_location("vignetteParams")?,
+            vignette_color: program.uniform_location("vignetteColor")?,
+            grain_intensity: program.uniform_location("grainIntensity")?,
+            grain_seed: program.uniform_location("grainSeed")?,
+            program,
+        })
+    }
+}
@@ -0,0 +1,275 @@
+//! Ordering of transparent (alpha-blended) drawables.
+//!
+//! Alpha blending is order-dependent, so every transparent drawable (sprites, particle systems,
+//! instances of transparent surfaces) of a scene is put into a single queue which is sorted for
+//! each camera: first by render priority of a node (lowest first), then by distance to camera
+//! along its view direction (farthest first). Sorting is stable and the queue is filled in pool
+//! order, so drawables with equal priority and depth keep their relative order and do not
+//! flicker from frame to frame.
+//!
+//! Depth of a surface instance is measured at center of bounds of its mesh, not at position of
+//! the mesh node, so large objects with offset geometry (like windows of a building) are sorted
+//! correctly.
+
+use crate::{
+    core::{
+        algebra::{Point3, Vector3},
+        pool::Handle,
+    },
+    renderer::batch::{BatchStorage, SurfaceInstance},
+    scene::{camera::Camera, graph::Graph, node::Node},
+};
+use std::cmp::Ordering;
+
+/// Something that is drawn in transparent pass.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(in crate) enum TransparentDrawable {
+    /// Sprite or particle system.
+    Node(Handle<Node>),
+    /// Instance of a transparent surface: index of batch and index of instance in the batch.
+    Surface { batch: usize, instance: usize },
+}
+
+struct QueueEntry {
+    drawable: TransparentDrawable,
+    priority: i8,
+    depth: f32,
+}
+
+#[derive(Default)]
+pub(in crate) struct TransparentQueue {
+    entries: Vec<QueueEntry>,
+}
+
+impl TransparentQueue {
+    /// Fills the queue with transparent drawables of the graph sorted for given camera. Batches
+    /// must be generated for the same graph.
+    pub fn build(&mut self, graph: &Graph, camera: &Camera, batch_storage: &BatchStorage) {
+        let camera_position = camera.global_position();
+        let look = camera.look_vector();
+
+        self.entries.clear();
+        for (handle, node) in graph.pair_iter() {
+            match node {
+                Node::Sprite(_) | Node::ParticleSystem(_) => {
+                    self.entries.push(QueueEntry {
+                        drawable: TransparentDrawable::Node(handle),
+                        priority: node.render_priority(),
+                        depth: (node.global_position() - camera_position).dot(&look),
+                    });
+                }
+                _ => (),
+            }
+        }
+
+        for (batch_index, batch) in batch_storage.batches.iter().enumerate() {
+            if !batch.transparent {
+                continue;
+            }
+            for (instance_index, instance) in batch.instances.iter().enumerate() {
+                if camera.visibility_cache.is_visible(instance.owner) {
+                    self.push_surface(
+                        graph,
+                        camera_position,
+                        look,
+                        TransparentDrawable::Surface {
+                            batch: batch_index,
+                            instance: instance_index,
+                        },
+                        instance,
+                    );
+                }
+            }
+        }
+
+        self.sort();
+    }
+
+    fn push_surface(
+        &mut self,
+        graph: &Graph,
+        camera_position: Vector3<f32>,
+        look: Vector3<f32>,
+        drawable: TransparentDrawable,
+        instance: &SurfaceInstance,
+    ) {
+        let owner = &graph[instance.owner];
+        let center = match owner {
+            Node::Mesh(mesh) => mesh.bounding_box().center(),
+            _ => Vector3::default(),
+        };
+        let center = instance
+            .world_transform
+            .transform_point(&Point3::from(center))
+            .coords;
+        self.entries.push(QueueEntry {
+            drawable,
+            priority: owner.render_priority(),
+            depth: (center - camera_position).dot(&look),
+        });
+    }
+
+    fn sort(&mut self) {
+        self.entries.sort_by(|a, b| {
+            a.priority
+                .cmp(&b.priority)
+                .then_with(|| b.depth.partial_cmp(&a.depth).unwrap_or(Ordering::Equal))
+        });
+    }
+
+    /// Returns drawables in drawing order.
+    pub fn drawables(&self) -> impl Iterator<Item = TransparentDrawable> + '_ {
+        self.entries.iter().map(|e| e.drawable)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            algebra::{UnitQuaternion, Vector2, Vector3},
+            color::Color,
+            math::TriangleDefinition,
+            pool::Handle,
+        },
+        renderer::{
+            batch::{BatchStorage, SurfaceInstance},
+            surface::{SurfaceBuilder, SurfaceSharedData, Vertex},
+            transparent::{TransparentDrawable, TransparentQueue},
+        },
+        scene::{
+            base::BaseBuilder, camera::CameraBuilder, graph::Graph, mesh::MeshBuilder, node::Node,
+            sprite::SpriteBuilder, transform::TransformBuilder,
+        },
+    };
+    use std::sync::{Arc, RwLock};
+
+    fn sprite(graph: &mut Graph, z: f32, priority: i8) -> Handle<Node> {
+        SpriteBuilder::new(
+            BaseBuilder::new()
+                .with_render_priority(priority)
+                .with_local_transform(
+                    TransformBuilder::new()
+                        .with_local_position(Vector3::new(0.0, 0.0, z))
+                        .build(),
+                ),
+        )
+        .build(graph)
+    }
+
+    #[test]
+    fn test_transparent_sorting() {
+        let mut graph = Graph::new();
+        let camera = CameraBuilder::new(BaseBuilder::new()).build(&mut graph);
+        let near = sprite(&mut graph, 1.0, 0);
+        let far = sprite(&mut graph, 10.0, 0);
+        let weapon_glow = sprite(&mut graph, 0.5, 1);
+        let equal_a = sprite(&mut graph, 5.0, 0);
+        let equal_b = sprite(&mut graph, 5.0, 0);
+        graph.update_hierarchical_data();
+
+        let mut queue = TransparentQueue::default();
+        queue.build(&graph, graph[camera].as_camera(), &BatchStorage::default());
+
+        assert_eq!(
+            queue.drawables().collect::<Vec<_>>(),
+            vec![far, equal_a, equal_b, near, weapon_glow]
+                .into_iter()
+                .map(TransparentDrawable::Node)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_transparent_surface_sorting() {
+        let mut graph = Graph::new();
+
+        // Two overlapping glass windows, origin of both meshes is at (0, 0, 0), but geometry of
+        // the second one is much farther along Z axis.
+        let window = |graph: &mut Graph, z: f32| {
+            let data = SurfaceSharedData::new(
+                vec![
+                    Vertex::from_pos_uv(Vector3::new(-0.5, -0.5, z), Vector2::new(0.0, 0.0)),
+                    Vertex::from_pos_uv(Vector3::new(0.5, -0.5, z), Vector2::new(1.0, 0.0)),
+                    Vertex::from_pos_uv(Vector3::new(0.0, 0.5, z), Vector2::new(0.5, 1.0)),
+                ],
+                vec![TriangleDefinition([0, 1, 2])],
+                true,
+            );
+            MeshBuilder::new(BaseBuilder::new())
+                .with_surfaces(vec![SurfaceBuilder::new(Arc::new(RwLock::new(data)))
+                    .with_color(Color::from_rgba(255, 255, 255, 127))
+                    .with_transparent(true)
+                    .build()])
+                .build(graph)
+        };
+        let near_window = window(&mut graph, 2.0);
+        let far_window = window(&mut graph, 5.0);
+        // Camera behind both windows looking towards them.
+        let camera = CameraBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(Vector3::new(0.0, 0.0, 10.0))
+                    .with_local_rotation(UnitQuaternion::from_axis_angle(
+                        &Vector3::y_axis(),
+                        std::f32::consts::PI,
+                    ))
+                    .build(),
+            ),
+        )
+        .build(&mut graph);
+        graph.update_hierarchical_data();
+
+        let instance = |owner: Handle<Node>| SurfaceInstance {
+            owner,
+            world_transform: graph[owner].global_transform(),
+            bone_matrices: Default::default(),
+            color: Color::WHITE,
+            depth_offset: 0.0,
+        };
+        let near_instance = instance(near_window);
+        let far_instance = instance(far_window);
+
+        let order = |position: Vector3<f32>, look: Vector3<f32>| {
+            let mut queue = TransparentQueue::default();
+            for (index, instance) in [&near_instance, &far_instance].iter().enumerate() {
+                queue.push_surface(
+                    &graph,
+                    position,
+                    look,
+                    TransparentDrawable::Surface {
+                        batch: 0,
+                        instance: index,
+                    },
+                    instance,
+                );
+            }
+            queue.sort();
+            queue
+                .drawables()
+                .map(|d| match d {
+                    TransparentDrawable::Surface { instance, .. } => instance,
+                    TransparentDrawable::Node(_) => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Looking from the front: far window first.
+        assert_eq!(
+            order(Vector3::new(0.0, 0.0, -5.0), Vector3::z()),
+            vec![1, 0]
+        );
+        // Looking from behind: near window is now farther from camera.
+        assert_eq!(
+            order(Vector3::new(0.0, 0.0, 10.0), -Vector3::z()),
+            vec![0, 1]
+        );
+
+        // The same through camera.
+        let camera = graph[camera].as_camera();
+        assert_eq!(
+            order(camera.global_position(), camera.look_vector()),
+            vec![0, 1]
+        );
+    }
+}
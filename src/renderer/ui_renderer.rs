@@ -7,13 +7,14 @@ use crate::{
     },
     gui::{
         brush::Brush,
-        draw::{CommandKind, CommandTexture, DrawingContext, SharedTexture},
+        draw::{CommandKind, CommandTexture, DrawBatches, DrawingContext, SharedTexture},
     },
     renderer::{
         error::RendererError,
         framework::{
             framebuffer::{
-                BackBuffer, CullFace, DrawParameters, DrawPartContext, FrameBufferTrait,
+                Attachment, AttachmentKind, CullFace, DrawParameters, DrawPartContext, FrameBuffer,
+                FrameBufferTrait,
             },
             geometry_buffer::{
                 AttributeDefinition, AttributeKind, BufferBuilder, ElementKind, GeometryBuffer,
@@ -21,12 +22,18 @@ use crate::{
             },
             gl,
             gpu_program::{GpuProgram, UniformLocation, UniformValue},
-            gpu_texture::GpuTexture,
+            gpu_texture::{
+                Coordinate, GpuTexture, GpuTextureKind, MagnificationFilter, MinificationFilter,
+                PixelKind, WrapMode,
+            },
             state::{ColorMask, PipelineState, StencilFunc, StencilOp},
         },
-        RenderPassStatistics, TextureCache,
+        TextureCache, UiStatistics,
+    },
+    resource::texture::{
+        Texture, TextureColorSpace, TextureData, TextureKind, TexturePixelKind, TextureState,
+        TextureWrapMode,
     },
-    resource::texture::{Texture, TextureData, TextureKind, TexturePixelKind, TextureState},
 };
 use std::{
     cell::RefCell,
@@ -39,6 +46,7 @@ struct UiShader {
     wvp_matrix: UniformLocation,
     diffuse_texture: UniformLocation,
     is_font: UniformLocation,
+    is_srgb_texture: UniformLocation,
     solid_color: UniformLocation,
     brush_type: UniformLocation,
     gradient_point_count: UniformLocation,
@@ -46,7 +54,6 @@ struct UiShader {
     gradient_stops: UniformLocation,
     gradient_origin: UniformLocation,
     gradient_end: UniformLocation,
-    resolution: UniformLocation,
     bounds_min: UniformLocation,
     bounds_max: UniformLocation,
 }
@@ -60,6 +67,7 @@ impl UiShader {
             wvp_matrix: program.uniform_location("worldViewProjection")?,
             diffuse_texture: program.uniform_location("diffuseTexture")?,
             is_font: program.uniform_location("isFont")?,
+            is_srgb_texture: program.uniform_location("isSrgbTexture")?,
             solid_color: program.uniform_location("solidColor")?,
             brush_type: program.uniform_location("brushType")?,
             gradient_point_count: program.uniform_location("gradientPointCount")?,
@@ -69,7 +77,6 @@ impl UiShader {
             gradient_end: program.uniform_location("gradientEnd")?,
             bounds_min: program.uniform_location("boundsMin")?,
             bounds_max: program.uniform_location("boundsMax")?,
-            resolution: program.uniform_location("resolution")?,
             program,
         })
     }
@@ -78,19 +85,93 @@ impl UiShader {
 pub struct UiRenderer {
     shader: UiShader,
     geometry_buffer: GeometryBuffer,
+    batches: DrawBatches,
 }
 
 pub(in crate) struct UiRenderContext<'a, 'b, 'c> {
     pub state: &'a mut PipelineState,
     pub viewport: Rect<i32>,
-    pub backbuffer: &'b mut BackBuffer,
+    pub frame_buffer: &'b mut dyn FrameBufferTrait,
     pub frame_width: f32,
     pub frame_height: f32,
+    /// Whether first row of the frame buffer is the top edge of the interface. Back buffer
+    /// has bottom row first, textures have top row first.
+    pub flip_y: bool,
     pub drawing_context: &'c DrawingContext,
     pub white_dummy: Rc<RefCell<GpuTexture>>,
     pub texture_cache: &'a mut TextureCache,
 }
 
+/// Off-screen frame buffer to render a user interface into a texture.
+pub(in crate::renderer) struct UiRenderTarget {
+    framebuffer: FrameBuffer,
+    width: usize,
+    height: usize,
+}
+
+impl UiRenderTarget {
+    pub fn new(
+        state: &mut PipelineState,
+        width: usize,
+        height: usize,
+    ) -> Result<Self, RendererError> {
+        let mut color_texture = GpuTexture::new(
+            state,
+            GpuTextureKind::Rectangle { width, height },
+            PixelKind::RGBA8,
+            MinificationFilter::Linear,
+            MagnificationFilter::Linear,
+            1,
+            None,
+        )?;
+        color_texture
+            .bind_mut(state, 0)
+            .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+            .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
+
+        // Interface uses stencil buffer for clipping.
+        let depth_stencil = GpuTexture::new(
+            state,
+            GpuTextureKind::Rectangle { width, height },
+            PixelKind::D24S8,
+            MinificationFilter::Nearest,
+            MagnificationFilter::Nearest,
+            1,
+            None,
+        )?;
+
+        let framebuffer = FrameBuffer::new(
+            state,
+            Some(Attachment {
+                kind: AttachmentKind::DepthStencil,
+                texture: Rc::new(RefCell::new(depth_stencil)),
+            }),
+            vec![Attachment {
+                kind: AttachmentKind::Color,
+                texture: Rc::new(RefCell::new(color_texture)),
+            }],
+        )?;
+
+        Ok(Self {
+            framebuffer,
+            width,
+            height,
+        })
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn framebuffer(&mut self) -> &mut FrameBuffer {
+        &mut self.framebuffer
+    }
+
+    pub fn color_texture(&self) -> Rc<RefCell<GpuTexture>> {
+        self.framebuffer.color_attachments()[0].texture.clone()
+    }
+}
+
 impl UiRenderer {
     pub(in crate::renderer) fn new(state: &mut PipelineState) -> Result<Self, RendererError> {
         let geometry_buffer = GeometryBufferBuilder::new(ElementKind::Triangle)
@@ -123,27 +204,34 @@ impl UiRenderer {
         Ok(Self {
             geometry_buffer,
             shader: UiShader::new()?,
+            batches: Default::default(),
         })
     }
 
     pub(in crate::renderer) fn render(
         &mut self,
         args: UiRenderContext,
-    ) -> Result<RenderPassStatistics, RendererError> {
+    ) -> Result<UiStatistics, RendererError> {
         scope_profile!();
 
         let UiRenderContext {
             state,
             viewport,
-            backbuffer,
+            frame_buffer,
             frame_width,
             frame_height,
+            flip_y,
             drawing_context,
             white_dummy,
             texture_cache,
         } = args;
 
-        let mut statistics = RenderPassStatistics::default();
+        self.batches.build(drawing_context);
+
+        let mut statistics = UiStatistics {
+            commands: self.batches.source_command_count(),
+            ..Default::default()
+        };
 
         state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
 
@@ -151,19 +239,24 @@ impl UiRenderer {
             .set_buffer_data(state, 0, drawing_context.get_vertices());
 
         let geometry_buffer = self.geometry_buffer.bind(state);
-        geometry_buffer.set_triangles(drawing_context.get_triangles());
+        geometry_buffer.set_triangles(self.batches.triangles());
 
-        let ortho = Matrix4::new_orthographic(0.0, frame_width, frame_height, 0.0, -1.0, 1.0);
+        let ortho = if flip_y {
+            Matrix4::new_orthographic(0.0, frame_width, 0.0, frame_height, -1.0, 1.0)
+        } else {
+            Matrix4::new_orthographic(0.0, frame_width, frame_height, 0.0, -1.0, 1.0)
+        };
 
-        for cmd in drawing_context.get_commands() {
+        for cmd in self.batches.batches() {
             let mut diffuse_texture = white_dummy.clone();
             let mut is_font_texture = false;
+            let mut is_srgb_texture = false;
             let mut color_write = true;
 
             match cmd.kind {
                 CommandKind::Clip => {
                     if cmd.nesting == 1 {
-                        backbuffer.clear(state, viewport, None, None, Some(0));
+                        frame_buffer.clear(state, viewport, None, None, Some(0));
                     }
                     state.set_stencil_op(StencilOp {
                         zpass: gl::INCR,
@@ -192,7 +285,7 @@ impl UiRenderer {
                             let mut font = font_arc.0.lock().unwrap();
                             if font.texture.is_none() {
                                 let size = font.atlas_size() as u32;
-                                if let Ok(details) = TextureData::from_bytes(
+                                if let Ok(mut details) = TextureData::from_bytes(
                                     TextureKind::Rectangle {
                                         width: size,
                                         height: size,
@@ -200,6 +293,11 @@ impl UiRenderer {
                                     TexturePixelKind::R8,
                                     font.atlas_pixels().to_vec(),
                                 ) {
+                                    details.set_color_space(TextureColorSpace::Linear);
+                                    // Glyphs are packed tightly in the atlas, repeat would
+                                    // bleed pixels of opposite edge into glyphs at the border.
+                                    details.set_s_wrap_mode(TextureWrapMode::ClampToEdge);
+                                    details.set_t_wrap_mode(TextureWrapMode::ClampToEdge);
                                     font.texture = Some(SharedTexture(Arc::new(Mutex::new(
                                         TextureState::Ok(details),
                                     ))));
@@ -220,6 +318,11 @@ impl UiRenderer {
                         CommandTexture::Texture(texture) => {
                             if let Ok(texture) = texture.clone().0.downcast::<Mutex<TextureState>>()
                             {
+                                // UI is drawn directly into back buffer, so colors of sRGB
+                                // textures must be converted back from linear space.
+                                if let TextureState::Ok(data) = &*texture.lock().unwrap() {
+                                    is_srgb_texture = data.color_space() == TextureColorSpace::Srgb;
+                                }
                                 if let Some(texture) =
                                     texture_cache.get(state, Texture::from(texture))
                                 {
@@ -247,10 +350,6 @@ impl UiRenderer {
                     },
                 ),
                 (self.shader.wvp_matrix, UniformValue::Matrix4(ortho)),
-                (
-                    self.shader.resolution,
-                    UniformValue::Vector2(Vector2::new(frame_width, frame_height)),
-                ),
                 (
                     self.shader.bounds_min,
                     UniformValue::Vector2(cmd.bounds.min),
@@ -260,6 +359,10 @@ impl UiRenderer {
                     UniformValue::Vector2(cmd.bounds.max),
                 ),
                 (self.shader.is_font, UniformValue::Bool(is_font_texture)),
+                (
+                    self.shader.is_srgb_texture,
+                    UniformValue::Bool(is_srgb_texture),
+                ),
                 (
                     self.shader.brush_type,
                     UniformValue::Integer({
@@ -351,7 +454,7 @@ impl UiRenderer {
                 blend: true,
             };
 
-            statistics += backbuffer.draw_part(DrawPartContext {
+            statistics.geometry += frame_buffer.draw_part(DrawPartContext {
                 state,
                 viewport,
                 geometry: &mut self.geometry_buffer,
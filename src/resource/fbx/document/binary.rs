@@ -2,7 +2,9 @@ use crate::core::byteorder::{LittleEndian, ReadBytesExt};
 use crate::{
     core::pool::{Handle, Pool},
     resource::fbx::{
-        document::{attribute::FbxAttribute, FbxDocument, FbxNode, FbxNodeContainer},
+        document::{
+            attribute::FbxAttribute, is_supported_version, FbxDocument, FbxNode, FbxNodeContainer,
+        },
         error::FbxError,
     },
 };
@@ -25,6 +27,23 @@ where
     }
 }
 
+/// Reads exactly `length` bytes. Unlike pre-allocated buffer, this does not trust length read
+/// from a file, so corrupted length will produce an error instead of huge allocation.
+fn read_bytes<R>(file: &mut R, length: usize) -> Result<Vec<u8>, FbxError>
+where
+    R: Read,
+{
+    let mut bytes = Vec::new();
+    file.take(length as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != length {
+        return Err(FbxError::Io(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            format!("expected {} bytes, got {}", length, bytes.len()),
+        )));
+    }
+    Ok(bytes)
+}
+
 fn read_array<R>(type_code: u8, file: &mut R) -> Result<Vec<FbxAttribute>, FbxError>
 where
     R: Read,
@@ -39,9 +58,7 @@ where
             array.push(read_attribute(type_code, file)?);
         }
     } else {
-        let mut compressed = Vec::with_capacity(compressed_length);
-        unsafe { compressed.set_len(compressed_length) };
-        file.read_exact(compressed.as_mut_slice())?;
+        let compressed = read_bytes(file, compressed_length)?;
         let decompressed = inflate::inflate_bytes_zlib(&compressed)?;
         let mut cursor = Cursor::new(decompressed);
         for _ in 0..length {
@@ -57,11 +74,7 @@ where
     R: Read,
 {
     let length = file.read_u32::<LittleEndian>()? as usize;
-    let mut raw_string = Vec::with_capacity(length);
-    unsafe {
-        raw_string.set_len(length);
-    };
-    file.read_exact(raw_string.as_mut_slice())?;
+    let mut raw_string = read_bytes(file, length)?;
     // Find null terminator. It is required because for some reason some strings
     // have additional data after null terminator like this: Omni004\x0\x1Model, but
     // length still more than position of null terminator.
@@ -72,31 +85,102 @@ where
     Ok(FbxAttribute::String(string))
 }
 
+/// Layout of node records depends on version of a file: since 7500 offsets and counts are
+/// 64-bit wide and null record is 25 bytes long instead of 13.
+struct RecordLayout {
+    wide: bool,
+}
+
+impl RecordLayout {
+    fn read_offset<R>(&self, file: &mut R) -> Result<u64, FbxError>
+    where
+        R: Read,
+    {
+        if self.wide {
+            Ok(file.read_u64::<LittleEndian>()?)
+        } else {
+            Ok(u64::from(file.read_u32::<LittleEndian>()?))
+        }
+    }
+
+    fn null_record_len(&self) -> usize {
+        if self.wide {
+            25
+        } else {
+            13
+        }
+    }
+}
+
 /// Read binary FBX DOM using this specification:
 /// https://code.blender.org/2013/08/fbx-binary-file-format-specification/
 /// In case of success returns Ok(valid_handle), in case if no more nodes
-/// are present returns Ok(none_handle), in case of error returns some FbxError.
-fn read_binary_node<R>(file: &mut R, pool: &mut Pool<FbxNode>) -> Result<Handle<FbxNode>, FbxError>
+/// are present returns Ok(none_handle), in case of error returns FbxError::Node
+/// with full path to a node which is failed to read.
+fn read_binary_node<R>(
+    file: &mut R,
+    pool: &mut Pool<FbxNode>,
+    layout: &RecordLayout,
+) -> Result<Handle<FbxNode>, FbxError>
+where
+    R: Read + Seek,
+{
+    let offset = file.seek(SeekFrom::Current(0))?;
+    let mut name = String::new();
+    read_binary_node_record(file, pool, layout, &mut name).map_err(|error| {
+        let name = if name.is_empty() {
+            String::from("<unnamed>")
+        } else {
+            name
+        };
+        match error {
+            // Error has occurred in a child node, prepend name of this node to the path.
+            FbxError::Node {
+                path,
+                offset,
+                reason,
+            } => FbxError::Node {
+                path: format!("{}/{}", name, path),
+                offset,
+                reason,
+            },
+            _ => FbxError::Node {
+                path: name,
+                offset,
+                reason: Box::new(error),
+            },
+        }
+    })
+}
+
+fn read_binary_node_record<R>(
+    file: &mut R,
+    pool: &mut Pool<FbxNode>,
+    layout: &RecordLayout,
+    name: &mut String,
+) -> Result<Handle<FbxNode>, FbxError>
 where
     R: Read + Seek,
 {
-    let end_offset = u64::from(file.read_u32::<LittleEndian>()?);
+    let end_offset = layout.read_offset(file)?;
     if end_offset == 0 {
         // Footer found. We're done.
         return Ok(Handle::NONE);
     }
 
-    let num_attrib = file.read_u32::<LittleEndian>()? as usize;
-    let _attrib_list_len = file.read_u32::<LittleEndian>()?;
+    let num_attrib = layout.read_offset(file)? as usize;
+    let _attrib_list_len = layout.read_offset(file)?;
 
     // Read name.
     let name_len = file.read_u8()? as usize;
-    let mut raw_name = Vec::with_capacity(name_len);
-    unsafe { raw_name.set_len(name_len) };
-    file.read_exact(raw_name.as_mut_slice())?;
+    *name = String::from_utf8(read_bytes(file, name_len)?)?;
+
+    if end_offset < file.seek(SeekFrom::Current(0))? {
+        return Err(FbxError::InvalidEndOffset(end_offset));
+    }
 
     let mut node = FbxNode::default();
-    node.name = String::from_utf8(raw_name)?;
+    node.name = name.clone();
     let node_handle = pool.spawn(node);
 
     // Read attributes.
@@ -121,18 +205,24 @@ where
                 .attributes
                 .push(read_string(file)?),
             b'R' => {
-                // Ignore Raw data
-                let length = i64::from(file.read_u32::<LittleEndian>()?);
-                file.seek(SeekFrom::Current(length))?;
+                // Raw data is used to store embedded media (textures, etc.)
+                let length = file.read_u32::<LittleEndian>()? as usize;
+                let data = read_bytes(file, length)?;
+                pool.borrow_mut(node_handle)
+                    .attributes
+                    .push(FbxAttribute::RawData(data));
             }
-            _ => (),
+            _ => return Err(FbxError::UnknownAttributeType(type_code)),
         }
     }
 
+    let null_record_len = layout.null_record_len();
     if file.seek(SeekFrom::Current(0))? < end_offset {
-        let null_record_position = end_offset - 13;
+        let null_record_position = end_offset
+            .checked_sub(null_record_len as u64)
+            .ok_or(FbxError::InvalidEndOffset(end_offset))?;
         while file.seek(SeekFrom::Current(0))? < null_record_position {
-            let child_handle = read_binary_node(file, pool)?;
+            let child_handle = read_binary_node(file, pool, layout)?;
             if child_handle.is_none() {
                 return Ok(child_handle);
             }
@@ -141,8 +231,7 @@ where
         }
 
         // Check if we have a null-record
-        let mut null_record = [0; 13];
-        file.read_exact(&mut null_record)?;
+        let null_record = read_bytes(file, null_record_len)?;
         if !null_record.iter().all(|i| *i == 0) {
             return Err(FbxError::InvalidNullRecord);
         }
@@ -164,10 +253,14 @@ where
 
     // Verify version.
     let version = file.read_u32::<LittleEndian>()? as i32;
-    if version < 7100 || version > 7400 {
+    if !is_supported_version(version) {
         return Err(FbxError::UnsupportedVersion(version));
     }
 
+    let layout = RecordLayout {
+        wide: version >= 7500,
+    };
+
     let mut nodes = Pool::new();
     let mut root = FbxNode::default();
     root.name = String::from("__ROOT__");
@@ -176,7 +269,7 @@ where
     // FBX document can have multiple root nodes, so we must read the file
     // until the end.
     while file.seek(SeekFrom::Current(0))? < total_length {
-        let root_child = read_binary_node(file, &mut nodes)?;
+        let root_child = read_binary_node(file, &mut nodes, &layout)?;
         if root_child.is_none() {
             break;
         }
@@ -189,3 +282,115 @@ where
         root: root_handle,
     })
 }
+
+#[cfg(test)]
+mod test {
+    use crate::resource::fbx::{
+        document::{attribute::FbxAttribute, binary::read_binary},
+        error::FbxError,
+    };
+    use std::io::Cursor;
+
+    struct Node {
+        name: &'static str,
+        attributes: Vec<u8>,
+        attribute_count: u64,
+        children: Vec<Node>,
+    }
+
+    impl Node {
+        fn new(name: &'static str, children: Vec<Node>) -> Self {
+            Self {
+                name,
+                attributes: Vec::new(),
+                attribute_count: 0,
+                children,
+            }
+        }
+
+        fn with_int(mut self, value: i32) -> Self {
+            self.attributes.push(b'I');
+            self.attributes.extend_from_slice(&value.to_le_bytes());
+            self.attribute_count += 1;
+            self
+        }
+
+        fn with_raw(mut self, data: &[u8]) -> Self {
+            self.attributes.push(b'R');
+            self.attributes
+                .extend_from_slice(&(data.len() as u32).to_le_bytes());
+            self.attributes.extend_from_slice(data);
+            self.attribute_count += 1;
+            self
+        }
+
+        fn write(&self, out: &mut Vec<u8>) {
+            let start = out.len();
+            out.extend_from_slice(&[0; 8]);
+            out.extend_from_slice(&self.attribute_count.to_le_bytes());
+            out.extend_from_slice(&(self.attributes.len() as u64).to_le_bytes());
+            out.push(self.name.len() as u8);
+            out.extend_from_slice(self.name.as_bytes());
+            out.extend_from_slice(&self.attributes);
+            if !self.children.is_empty() {
+                for child in self.children.iter() {
+                    child.write(out);
+                }
+                out.extend_from_slice(&[0; 25]);
+            }
+            let end = out.len() as u64;
+            out[start..start + 8].copy_from_slice(&end.to_le_bytes());
+        }
+    }
+
+    fn make_document() -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(b"Kaydara FBX Binary  \0\x1a\0");
+        out.extend_from_slice(&7500u32.to_le_bytes());
+        Node::new(
+            "FBXHeaderExtension",
+            vec![Node::new("FBXVersion", vec![]).with_int(7500)],
+        )
+        .write(&mut out);
+        Node::new(
+            "Objects",
+            vec![Node::new(
+                "Video",
+                vec![Node::new("Content", vec![]).with_raw(&[1, 2, 3, 4, 5, 6, 7, 8])],
+            )],
+        )
+        .write(&mut out);
+        // Footer
+        out.extend_from_slice(&[0; 25]);
+        out
+    }
+
+    #[test]
+    fn test_read_binary_7500() {
+        let document = read_binary(&mut Cursor::new(make_document())).unwrap();
+        let nodes = document.nodes();
+
+        let version = nodes.get_by_name(document.root(), "FBXVersion").unwrap();
+        assert_eq!(version.get_attrib(0).unwrap().as_i32().unwrap(), 7500);
+
+        let content = nodes.get_by_name(document.root(), "Content").unwrap();
+        match content.get_attrib(0).unwrap() {
+            FbxAttribute::RawData(data) => assert_eq!(data, &[1, 2, 3, 4, 5, 6, 7, 8]),
+            _ => panic!("Content must be raw data"),
+        }
+    }
+
+    #[test]
+    fn test_read_binary_truncated() {
+        let mut bytes = make_document();
+        // Cut the file in the middle of the raw data of the Content node.
+        let position = bytes.windows(4).position(|w| w == [1, 2, 3, 4]).unwrap();
+        bytes.truncate(position + 4);
+
+        match read_binary(&mut Cursor::new(bytes)) {
+            Err(FbxError::Node { path, .. }) => assert_eq!(path, "Objects/Video/Content"),
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("truncated file must not be read"),
+        }
+    }
+}
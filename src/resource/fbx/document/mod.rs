@@ -111,6 +111,12 @@ impl FbxNodeContainer {
     }
 }
 
+/// Checks whether a document of given version can be read. Binary documents of 7500 and
+/// newer use 64-bit node records, which are supported as well as old 32-bit ones.
+pub(in crate::resource::fbx) fn is_supported_version(version: i32) -> bool {
+    (7100..=7700).contains(&version)
+}
+
 pub struct FbxDocument {
     root: Handle<FbxNode>,
     nodes: FbxNodeContainer,
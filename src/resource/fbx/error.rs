@@ -15,7 +15,7 @@ pub enum FbxError {
     InvalidString,
     /// Arbitrary error that can have any meaning.
     Custom(Box<String>),
-    /// Version is not supported. Keep in mind that binary FBX 7500 is still not supported!
+    /// Version is not supported. Supported versions are 7100 - 7700.
     UnsupportedVersion(i32),
     /// Internal handle is invalid.
     InvalidPoolHandle,
@@ -31,6 +31,18 @@ pub enum FbxError {
     InvalidMapping,
     /// Unknown or unsupported reference.
     InvalidReference,
+    /// End offset of a node record points outside of the file or inside the node header.
+    /// Usually it means that the file is truncated or corrupted.
+    InvalidEndOffset(u64),
+    /// Unable to read a node of binary FBX.
+    Node {
+        /// Path to the node from the root, for example `Objects/Geometry/Vertices`.
+        path: String,
+        /// Offset of the node record in the file.
+        offset: u64,
+        /// Actual reason of the error.
+        reason: Box<FbxError>,
+    },
 }
 
 impl std::fmt::Display for FbxError {
@@ -54,6 +66,18 @@ impl std::fmt::Display for FbxError {
             FbxError::UnableToRemapModelToNode => write!(f, "Unable to remap model to node."),
             FbxError::InvalidMapping => write!(f, "Unknown mapping"),
             FbxError::InvalidReference => write!(f, "Unknown reference"),
+            FbxError::InvalidEndOffset(offset) => {
+                write!(f, "Invalid end offset {} of node record", offset)
+            }
+            FbxError::Node {
+                path,
+                offset,
+                reason,
+            } => write!(
+                f,
+                "Unable to read node {} at offset {}. Reason: {}",
+                path, offset, reason
+            ),
         }
     }
 }
@@ -12,6 +12,7 @@ mod scene;
 
 use std::{
     collections::{HashMap, HashSet},
+    hash::{Hash, Hasher},
     path::Path,
     sync::Arc,
     time::Instant,
@@ -26,7 +27,7 @@ use crate::scene::transform::TransformBuilder;
 use crate::utils::log::MessageKind;
 use crate::{
     animation::{Animation, AnimationContainer, KeyFrame, Track},
-    core::{math::triangulator::triangulate, pool::Handle},
+    core::{color::Color, math::triangulator::triangulate, pool::Handle},
     engine::resource_manager::ResourceManager,
     renderer::surface::{Surface, SurfaceSharedData, Vertex, VertexWeightSet},
     resource::fbx::{
@@ -34,9 +35,10 @@ use crate::{
         error::FbxError,
         scene::{
             animation::FbxAnimationCurveNodeType, geometry::FbxGeometry, model::FbxModel,
-            FbxComponent, FbxMapping, FbxScene,
+            texture::FbxTexture, FbxComponent, FbxMapping, FbxScene,
         },
     },
+    resource::texture::{Texture, TextureColorSpace},
     scene::{base::Base, graph::Graph, node::Node, Scene},
     utils::{log::Log, raw_mesh::RawMeshBuilder},
 };
@@ -132,13 +134,35 @@ struct UnpackedVertex {
     normal: Vector3<f32>,
     tangent: Vector3<f32>,
     uv: Vector2<f32>,
+    color: Color,
     // Set of weights for skinning.
     weights: Option<VertexWeightSet>,
 }
 
-impl Into<Vertex> for UnpackedVertex {
-    fn into(self) -> Vertex {
-        Vertex {
+// Colors are stored separately from vertices in surface data, but they still must be taken
+// into account when duplicated vertices are skipped.
+#[derive(Copy, Clone)]
+struct ColoredVertex {
+    vertex: Vertex,
+    color: Color,
+}
+
+impl PartialEq for ColoredVertex {
+    fn eq(&self, other: &Self) -> bool {
+        self.vertex == other.vertex && self.color == other.color
+    }
+}
+
+impl Hash for ColoredVertex {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.vertex.hash(state);
+        Into::<u32>::into(self.color).hash(state);
+    }
+}
+
+impl Into<ColoredVertex> for UnpackedVertex {
+    fn into(self) -> ColoredVertex {
+        let vertex = Vertex {
             position: self.position,
             tex_coord: self.uv,
             // TODO: FBX can contain second texture coordinates so they should be
@@ -150,6 +174,10 @@ impl Into<Vertex> for UnpackedVertex {
             // when all nodes will be converted.
             bone_weights: Default::default(),
             bone_indices: Default::default(),
+        };
+        ColoredVertex {
+            vertex,
+            color: self.color,
         }
     }
 }
@@ -179,6 +207,11 @@ fn convert_vertex(
         None => Vector2::default(),
     };
 
+    let color = match geom.colors.as_ref() {
+        Some(colors) => Color::from(*colors.get(index, index_in_polygon)?),
+        None => Color::WHITE,
+    };
+
     let material = match geom.materials.as_ref() {
         Some(materials) => *materials.get(material_index, index_in_polygon)?,
         None => 0,
@@ -191,6 +224,7 @@ fn convert_vertex(
         normal: geometric_transform.transform_vector(&normal),
         tangent: geometric_transform.transform_vector(&tangent),
         uv: Vector2::new(uv.x, -uv.y), // Invert Y because OpenGL has origin at left *bottom* corner.
+        color,
         surface: material as usize,
         weights: if skin_data.is_empty() {
             None
@@ -202,42 +236,109 @@ fn convert_vertex(
 
 #[derive(Default, Clone)]
 struct SurfaceData {
-    builder: RawMeshBuilder<Vertex>,
+    builder: RawMeshBuilder<ColoredVertex>,
     skin_data: Vec<VertexWeightSet>,
 }
 
+impl SurfaceData {
+    fn build(self, with_colors: bool) -> (SurfaceSharedData, Vec<VertexWeightSet>) {
+        let raw = self.builder.build();
+        let mut data = SurfaceSharedData::new(
+            raw.vertices.iter().map(|v| v.vertex).collect(),
+            raw.triangles,
+            false,
+        );
+        if with_colors {
+            data.set_vertex_colors(Some(raw.vertices.iter().map(|v| v.color).collect()));
+        }
+        (data, self.skin_data)
+    }
+}
+
+/// Creates texture for given FBX texture. Embedded textures are decoded from memory and
+/// registered in resource manager under synthetic path `<model path>/<texture file name>`,
+/// so every instance of the model will share them. Other textures are loaded from textures
+/// path of resource manager.
+fn resolve_texture(
+    fbx_scene: &FbxScene,
+    texture: &FbxTexture,
+    resource_manager: &ResourceManager,
+    model_path: &Path,
+    color_space: TextureColorSpace,
+) -> Result<Option<Texture>, FbxError> {
+    let options = resource_manager
+        .state()
+        .textures_import_options()
+        .clone()
+        .with_color_space(color_space);
+
+    if texture.video.is_some() {
+        let video = fbx_scene.get(texture.video).as_video()?;
+        if !video.content().is_empty() {
+            let filename = video
+                .get_file_path()
+                .file_name()
+                .or_else(|| texture.get_file_path().file_name());
+            if let Some(filename) = filename {
+                return Ok(Some(
+                    resource_manager.request_texture_from_memory_with_options(
+                        model_path.join(filename),
+                        video.content().to_vec(),
+                        options,
+                    ),
+                ));
+            }
+        }
+    }
+
+    Ok(texture.get_file_path().file_name().map(|filename| {
+        let texture_path = resource_manager.state().textures_path().join(&filename);
+        resource_manager.request_texture_with_options(texture_path.as_path(), options)
+    }))
+}
+
 fn create_surfaces(
     fbx_scene: &FbxScene,
     data_set: Vec<SurfaceData>,
     resource_manager: ResourceManager,
     model: &FbxModel,
+    model_path: &Path,
+    with_colors: bool,
 ) -> Result<Vec<Surface>, FbxError> {
     let mut surfaces = Vec::new();
 
     // Create surfaces per material
     if model.materials.is_empty() {
         assert_eq!(data_set.len(), 1);
-        let data = data_set.into_iter().next().unwrap();
-        let mut surface = Surface::new(Arc::new(RwLock::new(SurfaceSharedData::from_raw_mesh(
-            data.builder.build(),
-            false,
-        ))));
-        surface.vertex_weights = data.skin_data;
+        let (data, skin_data) = data_set.into_iter().next().unwrap().build(with_colors);
+        let mut surface = Surface::new(Arc::new(RwLock::new(data)));
+        surface.vertex_weights = skin_data;
         surfaces.push(surface);
     } else {
         assert_eq!(data_set.len(), model.materials.len());
         for (&material_handle, data) in model.materials.iter().zip(data_set.into_iter()) {
-            let mut surface = Surface::new(Arc::new(RwLock::new(
-                SurfaceSharedData::from_raw_mesh(data.builder.build(), false),
-            )));
-            surface.vertex_weights = data.skin_data;
+            let (data, skin_data) = data.build(with_colors);
+            let mut surface = Surface::new(Arc::new(RwLock::new(data)));
+            surface.vertex_weights = skin_data;
             let material = fbx_scene.get(material_handle).as_material()?;
+            surface.set_emissive_color(material.emissive_color);
+            surface.set_emissive_intensity(material.emissive_factor);
+            surface.set_two_sided(material.two_sided);
             for (name, texture_handle) in material.textures.iter() {
                 let texture = fbx_scene.get(*texture_handle).as_texture()?;
-                let path = texture.get_file_path();
-                if let Some(filename) = path.file_name() {
-                    let texture_path = resource_manager.state().textures_path().join(&filename);
-                    let texture = resource_manager.request_texture(texture_path.as_path());
+                // Only diffuse and emissive textures contain colors, the rest contain data.
+                let color_space = if name == "DiffuseColor" || name == "EmissiveColor" {
+                    TextureColorSpace::Srgb
+                } else {
+                    TextureColorSpace::Linear
+                };
+                if let Some(texture) = resolve_texture(
+                    fbx_scene,
+                    texture,
+                    &resource_manager,
+                    model_path,
+                    color_space,
+                )? {
                     match name.as_str() {
                         "AmbientColor" => (), // TODO: Add ambient occlusion (AO) map support.
                         "DiffuseColor" => surface.set_diffuse_texture(Some(texture)),
@@ -245,6 +346,11 @@ fn create_surfaces(
                         "ShininessExponent" => surface.set_roughness_texture(Some(texture)),
                         // No idea why it can be different for normal maps.
                         "Bump" | "NormalMap" => surface.set_normal_texture(Some(texture)),
+                        "EmissiveColor" => {
+                            // Texture replaces color, as in FBX SDK.
+                            surface.set_emissive_texture(Some(texture));
+                            surface.set_emissive_color(Color::WHITE);
+                        }
                         _ => (),
                     }
                 }
@@ -262,6 +368,7 @@ fn convert_mesh(
     resource_manager: ResourceManager,
     model: &FbxModel,
     graph: &mut Graph,
+    model_path: &Path,
 ) -> Result<Handle<Node>, FbxError> {
     let geometric_transform = Matrix4::new_translation(&model.geometric_translation)
         * quat_from_euler(model.geometric_rotation).to_homogeneous()
@@ -326,7 +433,20 @@ fn convert_mesh(
             }
         }
 
-        let mut surfaces = create_surfaces(fbx_scene, data_set, resource_manager.clone(), model)?;
+        let mut surfaces = create_surfaces(
+            fbx_scene,
+            data_set,
+            resource_manager.clone(),
+            model,
+            model_path,
+            geom.colors.is_some(),
+        )?;
+
+        if geom.colors.is_some() {
+            for surface in surfaces.iter_mut() {
+                surface.set_vertex_colors_enabled(true);
+            }
+        }
 
         if geom.tangents.is_none() {
             for surface in surfaces.iter_mut() {
@@ -368,91 +488,182 @@ fn convert_model(
     model: &FbxModel,
     resource_manager: ResourceManager,
     graph: &mut Graph,
-    animations: &mut AnimationContainer,
-    animation_handle: Handle<Animation>,
+    model_path: &Path,
 ) -> Result<Handle<Node>, FbxError> {
     let base = convert_model_to_base(model);
 
     // Create node with correct kind.
     let node_handle = if !model.geoms.is_empty() {
-        convert_mesh(base, fbx_scene, resource_manager, model, graph)?
+        convert_mesh(base, fbx_scene, resource_manager, model, graph, model_path)?
     } else if model.light.is_some() {
         fbx_scene.get(model.light).as_light()?.convert(base, graph)
     } else {
         base.build(graph)
     };
 
-    // Convert animations
-    if !model.animation_curve_nodes.is_empty() {
-        // Find supported curve nodes (translation, rotation, scale)
-        let mut lcl_translation = None;
-        let mut lcl_rotation = None;
-        let mut lcl_scale = None;
-        for &anim_curve_node_handle in model.animation_curve_nodes.iter() {
-            let component = fbx_scene.get(anim_curve_node_handle);
-            if let FbxComponent::AnimationCurveNode(curve_node) = component {
-                if curve_node.actual_type == FbxAnimationCurveNodeType::Rotation {
-                    lcl_rotation = Some(curve_node);
-                } else if curve_node.actual_type == FbxAnimationCurveNodeType::Translation {
-                    lcl_translation = Some(curve_node);
-                } else if curve_node.actual_type == FbxAnimationCurveNodeType::Scale {
-                    lcl_scale = Some(curve_node);
+    Ok(node_handle)
+}
+
+/// Take is a single animation of a file. Each take is converted to separate animation.
+struct Take {
+    /// Set of curve nodes of the take. `None` means that file has no animation stacks and
+    /// every curve node belongs to the take.
+    curve_nodes: Option<HashSet<Handle<FbxComponent>>>,
+    /// Time of the first key of the take, it is subtracted from time of every key so each
+    /// animation starts at zero.
+    start_time: f32,
+    animation: Handle<Animation>,
+}
+
+impl Take {
+    fn contains(&self, curve_node: Handle<FbxComponent>) -> bool {
+        match self.curve_nodes.as_ref() {
+            Some(curve_nodes) => curve_nodes.contains(&curve_node),
+            None => true,
+        }
+    }
+}
+
+/// Creates one animation per animation stack. Files without stacks (exported by old
+/// versions of tools) will have single animation with every curve node.
+fn create_takes(fbx_scene: &FbxScene, animations: &mut AnimationContainer) -> Vec<Take> {
+    let mut takes = Vec::new();
+
+    for (_, component) in fbx_scene.pair_iter() {
+        if let FbxComponent::AnimationStack(stack) = component {
+            let mut curve_nodes = HashSet::new();
+            for &layer_handle in stack.layers.iter() {
+                if let FbxComponent::AnimationLayer(layer) = fbx_scene.get(layer_handle) {
+                    curve_nodes.extend(layer.curve_nodes.iter().cloned());
                 }
             }
-        }
 
-        // Convert to engine format
-        let mut track = Track::new();
-        track.set_node(node_handle);
-
-        let node_local_rotation = quat_from_euler(model.rotation);
-
-        let mut time = 0.0;
-        loop {
-            let translation = lcl_translation
-                .map(|curve| curve.eval_vec3(fbx_scene, time))
-                .unwrap_or(model.translation);
-
-            let rotation = lcl_rotation
-                .map(|curve| curve.eval_quat(fbx_scene, time))
-                .unwrap_or(node_local_rotation);
-
-            let scale = lcl_scale
-                .map(|curve| curve.eval_vec3(fbx_scene, time))
-                .unwrap_or(model.scale);
-
-            track.add_key_frame(KeyFrame::new(time, translation, scale, rotation));
-
-            let mut next_time = std::f32::MAX;
-            for node in &[lcl_translation, lcl_rotation, lcl_scale] {
-                if let Some(node) = node {
-                    for &curve_handle in node.curves.iter() {
-                        let curve_component = fbx_scene.get(curve_handle);
-                        if let FbxComponent::AnimationCurve(curve) = curve_component {
-                            for key in curve.keys.iter() {
-                                if key.time > time {
-                                    let distance = key.time - time;
-                                    if distance < next_time - key.time {
-                                        next_time = key.time;
-                                    }
-                                }
+            let mut start_time = std::f32::MAX;
+            for &curve_node_handle in curve_nodes.iter() {
+                if let FbxComponent::AnimationCurveNode(curve_node) =
+                    fbx_scene.get(curve_node_handle)
+                {
+                    for &curve_handle in curve_node.curves.iter() {
+                        if let FbxComponent::AnimationCurve(curve) = fbx_scene.get(curve_handle) {
+                            if let Some(first) = curve.keys.first() {
+                                start_time = start_time.min(first.time);
                             }
                         }
                     }
                 }
             }
+            if start_time == std::f32::MAX {
+                start_time = 0.0;
+            }
+
+            let mut animation = Animation::default();
+            animation.set_name(stack.name.as_str());
+
+            takes.push(Take {
+                curve_nodes: Some(curve_nodes),
+                start_time,
+                animation: animations.add(animation),
+            });
+        }
+    }
+
+    if takes.is_empty() {
+        takes.push(Take {
+            curve_nodes: None,
+            start_time: 0.0,
+            animation: animations.add(Animation::default()),
+        });
+    }
 
-            if next_time >= std::f32::MAX {
-                break;
+    takes
+}
+
+/// Converts animation curves of a model that belong to given take into a track. Returns
+/// `None` if the model is not animated in the take.
+fn convert_model_animation(
+    fbx_scene: &FbxScene,
+    model: &FbxModel,
+    node_handle: Handle<Node>,
+    take: &Take,
+) -> Option<Track> {
+    // Find supported curve nodes (translation, rotation, scale)
+    let mut lcl_translation = None;
+    let mut lcl_rotation = None;
+    let mut lcl_scale = None;
+    for &anim_curve_node_handle in model.animation_curve_nodes.iter() {
+        if !take.contains(anim_curve_node_handle) {
+            continue;
+        }
+        let component = fbx_scene.get(anim_curve_node_handle);
+        if let FbxComponent::AnimationCurveNode(curve_node) = component {
+            if curve_node.actual_type == FbxAnimationCurveNodeType::Rotation {
+                lcl_rotation = Some(curve_node);
+            } else if curve_node.actual_type == FbxAnimationCurveNodeType::Translation {
+                lcl_translation = Some(curve_node);
+            } else if curve_node.actual_type == FbxAnimationCurveNodeType::Scale {
+                lcl_scale = Some(curve_node);
             }
+        }
+    }
 
-            time = next_time;
+    if lcl_translation.is_none() && lcl_rotation.is_none() && lcl_scale.is_none() {
+        return None;
+    }
+
+    // Convert to engine format
+    let mut track = Track::new();
+    track.set_node(node_handle);
+
+    let node_local_rotation = quat_from_euler(model.rotation);
+
+    let mut time = take.start_time;
+    loop {
+        let translation = lcl_translation
+            .map(|curve| curve.eval_vec3(fbx_scene, time))
+            .unwrap_or(model.translation);
+
+        let rotation = lcl_rotation
+            .map(|curve| curve.eval_quat(fbx_scene, time))
+            .unwrap_or(node_local_rotation);
+
+        let scale = lcl_scale
+            .map(|curve| curve.eval_vec3(fbx_scene, time))
+            .unwrap_or(model.scale);
+
+        track.add_key_frame(KeyFrame::new(
+            time - take.start_time,
+            translation,
+            scale,
+            rotation,
+        ));
+
+        let mut next_time = std::f32::MAX;
+        for node in &[lcl_translation, lcl_rotation, lcl_scale] {
+            if let Some(node) = node {
+                for &curve_handle in node.curves.iter() {
+                    let curve_component = fbx_scene.get(curve_handle);
+                    if let FbxComponent::AnimationCurve(curve) = curve_component {
+                        for key in curve.keys.iter() {
+                            if key.time > time {
+                                let distance = key.time - time;
+                                if distance < next_time - key.time {
+                                    next_time = key.time;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if next_time >= std::f32::MAX {
+            break;
         }
 
-        animations.get_mut(animation_handle).add_track(track);
+        time = next_time;
     }
 
-    Ok(node_handle)
+    Some(track)
 }
 
 ///
@@ -462,9 +673,10 @@ fn convert(
     fbx_scene: &FbxScene,
     resource_manager: ResourceManager,
     scene: &mut Scene,
+    model_path: &Path,
 ) -> Result<Handle<Node>, FbxError> {
     let root = scene.graph.add_node(Node::Base(Base::default()));
-    let animation_handle = scene.animations.add(Animation::default());
+    let takes = create_takes(fbx_scene, &mut scene.animations);
     let mut fbx_model_to_node_map = HashMap::new();
     for (component_handle, component) in fbx_scene.pair_iter() {
         if let FbxComponent::Model(model) = component {
@@ -473,9 +685,13 @@ fn convert(
                 model,
                 resource_manager.clone(),
                 &mut scene.graph,
-                &mut scene.animations,
-                animation_handle,
+                model_path,
             )?;
+            for take in takes.iter() {
+                if let Some(track) = convert_model_animation(fbx_scene, model, node, take) {
+                    scene.animations.get_mut(take.animation).add_track(track);
+                }
+            }
             scene.graph.link_nodes(node, root);
             fbx_model_to_node_map.insert(component_handle, node);
         }
@@ -560,7 +776,7 @@ pub fn load_to_scene<P: AsRef<Path>>(
     let dom_prepare_time = now.elapsed().as_millis();
 
     let now = Instant::now();
-    let result = convert(&fbx_scene, resource_manager, scene);
+    let result = convert(&fbx_scene, resource_manager, scene, path.as_ref());
     let conversion_time = now.elapsed().as_millis();
 
     Log::writeln(MessageKind::Information,
@@ -569,3 +785,186 @@ pub fn load_to_scene<P: AsRef<Path>>(
 
     result
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::color::Color, engine::resource_manager::ResourceManager, resource::fbx, scene::Scene,
+    };
+
+    // Two takes: "Idle" animates only model A during first second, "Walk" animates only
+    // model B from 2nd to 4th second.
+    const TAKES_FBX: &str = r#"; FBX 7.4.0 project file
+FBXHeaderExtension:  {
+    FBXVersion: 7400
+}
+Objects:  {
+    Model: 1, "Model::A", "Null" {
+        Properties70:  {
+        }
+    }
+    Model: 2, "Model::B", "Null" {
+        Properties70:  {
+        }
+    }
+    AnimationStack: 10, "AnimStack::Idle", "" {
+    }
+    AnimationStack: 11, "AnimStack::Walk", "" {
+    }
+    AnimationLayer: 20, "AnimLayer::BaseLayer", "" {
+    }
+    AnimationLayer: 21, "AnimLayer::BaseLayer", "" {
+    }
+    AnimationCurveNode: 30, "AnimCurveNode::T", "" {
+    }
+    AnimationCurveNode: 31, "AnimCurveNode::T", "" {
+    }
+    AnimationCurve: 40, "AnimCurve::", "" {
+        KeyTime: *2 {
+            a: 0,46186158000
+        }
+        KeyValueFloat: *2 {
+            a: 0,1
+        }
+    }
+    AnimationCurve: 41, "AnimCurve::", "" {
+        KeyTime: *2 {
+            a: 92372316000,184744632000
+        }
+        KeyValueFloat: *2 {
+            a: 0,1
+        }
+    }
+}
+Connections:  {
+    C: "OO",20,10
+    C: "OO",21,11
+    C: "OO",30,20
+    C: "OO",31,21
+    C: "OP",30,1, "Lcl Translation"
+    C: "OP",31,2, "Lcl Translation"
+    C: "OP",40,30, "d|X"
+    C: "OP",40,30, "d|Y"
+    C: "OP",40,30, "d|Z"
+    C: "OP",41,31, "d|X"
+    C: "OP",41,31, "d|Y"
+    C: "OP",41,31, "d|Z"
+}
+"#;
+
+    #[test]
+    fn test_multiple_takes() {
+        let path = std::env::temp_dir().join("rg3d_fbx_takes_test.fbx");
+        std::fs::write(&path, TAKES_FBX).unwrap();
+
+        let mut scene = Scene::new();
+        fbx::load_to_scene(&mut scene, ResourceManager::new(), &path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let mut animations = scene.animations.iter().collect::<Vec<_>>();
+        animations.sort_by(|a, b| a.name().cmp(b.name()));
+        assert_eq!(animations.len(), 2);
+
+        let idle = animations[0];
+        assert_eq!(idle.name(), "Idle");
+        assert_eq!(idle.get_tracks().len(), 1);
+        assert_eq!(scene.graph[idle.get_tracks()[0].get_node()].name(), "A");
+        assert!((idle.length() - 1.0).abs() < 0.001);
+
+        // Walk starts at 2nd second, but the animation must start at zero.
+        let walk = animations[1];
+        assert_eq!(walk.name(), "Walk");
+        assert_eq!(walk.get_tracks().len(), 1);
+        assert_eq!(scene.graph[walk.get_tracks()[0].get_node()].name(), "B");
+        assert!((walk.length() - 2.0).abs() < 0.001);
+        assert_eq!(walk.get_tracks()[0].get_key_frames()[0].time, 0.0);
+    }
+
+    // Three meshes with the same triangle: "Lamp" has emissive color and factor, "Screen" has
+    // emissive texture, "Wall" has material without properties.
+    const EMISSION_FBX: &str = r#"; FBX 7.4.0 project file
+FBXHeaderExtension:  {
+    FBXVersion: 7400
+}
+Objects:  {
+    Geometry: 100, "Geometry::", "Mesh" {
+        Vertices: *9 {
+            a: 0,0,0,1,0,0,0,1,0
+        }
+        PolygonVertexIndex: *3 {
+            a: 0,1,-3
+        }
+    }
+    Model: 1, "Model::Lamp", "Mesh" {
+        Properties70:  {
+        }
+    }
+    Model: 2, "Model::Screen", "Mesh" {
+        Properties70:  {
+        }
+    }
+    Model: 3, "Model::Wall", "Mesh" {
+        Properties70:  {
+        }
+    }
+    Material: 10, "Material::Lamp", "" {
+        Properties70:  {
+            P: "EmissiveColor", "Color", "", "A",1,0.5,0
+            P: "EmissiveFactor", "Number", "", "A",2
+        }
+    }
+    Material: 11, "Material::Screen", "" {
+        Properties70:  {
+            P: "EmissiveColor", "Color", "", "A",0.2,0.2,0.2
+        }
+    }
+    Material: 12, "Material::Wall", "" {
+    }
+    Texture: 20, "Texture::Screen", "" {
+        RelativeFilename: "textures\\screen.png"
+    }
+}
+Connections:  {
+    C: "OO",100,1
+    C: "OO",100,2
+    C: "OO",100,3
+    C: "OO",10,1
+    C: "OO",11,2
+    C: "OO",12,3
+    C: "OP",20,11, "EmissiveColor"
+}
+"#;
+
+    #[test]
+    fn test_emission() {
+        let path = std::env::temp_dir().join("rg3d_fbx_emission_test.fbx");
+        std::fs::write(&path, EMISSION_FBX).unwrap();
+
+        let mut scene = Scene::new();
+        fbx::load_to_scene(&mut scene, ResourceManager::new(), &path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let surface = |name| {
+            let handle = scene.graph.find_by_name_from_root(name);
+            scene.graph[handle].as_mesh().surfaces()[0].clone()
+        };
+
+        let lamp = surface("Lamp");
+        assert_eq!(lamp.emissive_color(), Color::from_rgba(255, 127, 0, 255));
+        assert_eq!(lamp.emissive_intensity(), 2.0);
+        assert!(lamp.emissive_texture().is_none());
+
+        // Texture replaces emissive color.
+        let screen = surface("Screen");
+        assert_eq!(screen.emissive_color(), Color::WHITE);
+        assert_eq!(screen.emissive_intensity(), 1.0);
+        let texture = screen.emissive_texture().unwrap();
+        assert!(texture.state().path().ends_with("screen.png"));
+
+        // No emission by default.
+        let wall = surface("Wall");
+        assert_eq!(wall.emissive_color(), Color::BLACK);
+        assert_eq!(wall.emissive_intensity(), 1.0);
+        assert!(wall.emissive_texture().is_none());
+    }
+}
@@ -140,3 +140,44 @@ impl FbxAnimationCurveNode {
         quat_from_euler(self.eval_vec3(scene, time))
     }
 }
+
+/// Animation layer is a set of animation curve nodes. Layers are used to blend animations in
+/// authoring tools, there is no blending on import - all layers of a stack are merged.
+pub struct FbxAnimationLayer {
+    pub curve_nodes: Vec<Handle<FbxComponent>>,
+}
+
+impl FbxAnimationLayer {
+    pub(in crate::resource::fbx) fn read(
+        _layer_handle: Handle<FbxNode>,
+        _nodes: &FbxNodeContainer,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            curve_nodes: Default::default(),
+        })
+    }
+}
+
+/// Animation stack (also known as take) is a separate animation (idle, walk, run, etc.).
+/// Single file can contain multiple stacks.
+pub struct FbxAnimationStack {
+    pub name: String,
+    pub layers: Vec<Handle<FbxComponent>>,
+}
+
+impl FbxAnimationStack {
+    pub(in crate::resource::fbx) fn read(
+        stack_handle: Handle<FbxNode>,
+        nodes: &FbxNodeContainer,
+    ) -> Result<Self, String> {
+        let node = nodes.get(stack_handle);
+        // ASCII FBX has names like "AnimStack::Walk", binary FBX has names like
+        // "Walk\x00\x01AnimStack" which are truncated to "Walk" by the reader.
+        let name = node.get_attrib(1)?.as_string();
+        let name = name.trim_start_matches("AnimStack::").to_owned();
+        Ok(Self {
+            name,
+            layers: Default::default(),
+        })
+    }
+}
@@ -1,4 +1,4 @@
-use crate::core::algebra::{Vector2, Vector3};
+use crate::core::algebra::{Vector2, Vector3, Vector4};
 use crate::{
     core::pool::Handle,
     renderer::surface::{VertexWeight, VertexWeightSet},
@@ -23,6 +23,7 @@ pub struct FbxGeometry {
     pub materials: Option<FbxContainer<i32>>,
     pub tangents: Option<FbxContainer<Vector3<f32>>>,
     pub binormals: Option<FbxContainer<Vector3<f32>>>,
+    pub colors: Option<FbxContainer<Vector4<f32>>>,
 
     pub deformers: Vec<Handle<FbxComponent>>,
 }
@@ -125,6 +126,33 @@ fn read_uvs(
     }
 }
 
+fn read_colors(
+    geom_node_handle: Handle<FbxNode>,
+    nodes: &FbxNodeContainer,
+) -> Result<Option<FbxContainer<Vector4<f32>>>, FbxError> {
+    if let Ok(layer_element_color) = nodes.find(geom_node_handle, "LayerElementColor") {
+        Ok(Some(FbxContainer::new(
+            nodes,
+            layer_element_color,
+            "Colors",
+            |attributes| {
+                let mut colors = Vec::with_capacity(attributes.len() / 4);
+                for color in attributes.chunks_exact(4) {
+                    colors.push(Vector4::new(
+                        color[0].as_f32()?,
+                        color[1].as_f32()?,
+                        color[2].as_f32()?,
+                        color[3].as_f32()?,
+                    ));
+                }
+                Ok(colors)
+            },
+        )?))
+    } else {
+        Ok(None)
+    }
+}
+
 fn read_materials(
     geom_node_handle: Handle<FbxNode>,
     nodes: &FbxNodeContainer,
@@ -162,6 +190,7 @@ impl FbxGeometry {
             materials: read_materials(geom_node_handle, nodes)?,
             tangents: read_tangents(geom_node_handle, nodes)?,
             binormals: read_binormals(geom_node_handle, nodes)?,
+            colors: read_colors(geom_node_handle, nodes)?,
             deformers: Vec::new(),
         })
     }
@@ -1,15 +1,23 @@
 use crate::core::algebra::{Matrix4, Vector3};
 use crate::{
-    core::pool::{Handle, Pool, PoolPairIterator},
+    core::{
+        color::Color,
+        pool::{Handle, Pool, PoolPairIterator},
+    },
     resource::fbx::{
-        document::{attribute::FbxAttribute, FbxDocument, FbxNode, FbxNodeContainer},
+        document::{
+            attribute::FbxAttribute, is_supported_version, FbxDocument, FbxNode, FbxNodeContainer,
+        },
         error::FbxError,
         scene::{
-            animation::{FbxAnimationCurve, FbxAnimationCurveNode},
+            animation::{
+                FbxAnimationCurve, FbxAnimationCurveNode, FbxAnimationLayer, FbxAnimationStack,
+            },
             geometry::FbxGeometry,
             light::FbxLight,
             model::FbxModel,
             texture::FbxTexture,
+            video::FbxVideo,
         },
     },
 };
@@ -20,6 +28,7 @@ pub mod geometry;
 pub mod light;
 pub mod model;
 pub mod texture;
+pub mod video;
 
 pub struct FbxScene {
     components: Pool<FbxComponent>,
@@ -38,7 +47,7 @@ impl FbxScene {
         let header_handle = nodes.find(document.root(), "FBXHeaderExtension")?;
         let version = nodes.get_by_name(header_handle, "FBXVersion")?;
         let version = version.get_attrib(0)?.as_i32()?;
-        if version < 7100 && version > 7400 {
+        if !is_supported_version(version) {
             return Err(FbxError::UnsupportedVersion(version));
         }
 
@@ -60,8 +69,10 @@ impl FbxScene {
                     )));
                 }
                 "Material" => {
-                    component_handle = components
-                        .spawn(FbxComponent::Material(FbxMaterial::read(*object_handle)?));
+                    component_handle = components.spawn(FbxComponent::Material(FbxMaterial::read(
+                        *object_handle,
+                        nodes,
+                    )?));
                 }
                 "Texture" => {
                     component_handle = components.spawn(FbxComponent::Texture(FbxTexture::read(
@@ -69,6 +80,10 @@ impl FbxScene {
                         nodes,
                     )?));
                 }
+                "Video" => {
+                    component_handle = components
+                        .spawn(FbxComponent::Video(FbxVideo::read(*object_handle, nodes)?));
+                }
                 "NodeAttribute" => {
                     if object.attrib_count() > 2 && object.get_attrib(2)?.as_string() == "Light" {
                         component_handle = components
@@ -80,6 +95,16 @@ impl FbxScene {
                         FbxAnimationCurve::read(*object_handle, nodes)?,
                     ));
                 }
+                "AnimationStack" => {
+                    component_handle = components.spawn(FbxComponent::AnimationStack(
+                        FbxAnimationStack::read(*object_handle, nodes)?,
+                    ));
+                }
+                "AnimationLayer" => {
+                    component_handle = components.spawn(FbxComponent::AnimationLayer(
+                        FbxAnimationLayer::read(*object_handle, nodes)?,
+                    ));
+                }
                 "AnimationCurveNode" => {
                     component_handle = components.spawn(FbxComponent::AnimationCurveNode(
                         FbxAnimationCurveNode::read(*object_handle, nodes)?,
@@ -158,12 +183,30 @@ fn link_child_with_parent_component(
                 material.textures.push((property, child_handle));
             }
         }
+        // Link texture with video that contains embedded content of the texture
+        FbxComponent::Texture(texture) => {
+            if let FbxComponent::Video(_) = child {
+                texture.video = child_handle;
+            }
+        }
         // Link animation curve node with animation curve
         FbxComponent::AnimationCurveNode(anim_curve_node) => {
             if let FbxComponent::AnimationCurve(_) = child {
                 anim_curve_node.curves.push(child_handle);
             }
         }
+        // Link animation stack with its layers
+        FbxComponent::AnimationStack(stack) => {
+            if let FbxComponent::AnimationLayer(_) = child {
+                stack.layers.push(child_handle);
+            }
+        }
+        // Link animation layer with animation curve nodes
+        FbxComponent::AnimationLayer(layer) => {
+            if let FbxComponent::AnimationCurveNode(_) = child {
+                layer.curve_nodes.push(child_handle);
+            }
+        }
         // Link deformer with sub-deformers
         FbxComponent::Deformer(deformer) => {
             if let FbxComponent::SubDeformer(_) = child {
@@ -192,9 +235,12 @@ pub enum FbxComponent {
     Deformer(FbxDeformer),
     SubDeformer(FbxSubDeformer),
     Texture(FbxTexture),
+    Video(FbxVideo),
     Light(FbxLight),
     Model(Box<FbxModel>),
     Material(FbxMaterial),
+    AnimationStack(FbxAnimationStack),
+    AnimationLayer(FbxAnimationLayer),
     AnimationCurveNode(FbxAnimationCurveNode),
     AnimationCurve(FbxAnimationCurve),
     Geometry(Box<FbxGeometry>),
@@ -216,6 +262,7 @@ impl FbxComponent {
     define_as!(self, as_deformer, FbxDeformer, Deformer);
     define_as!(self, as_sub_deformer, FbxSubDeformer, SubDeformer);
     define_as!(self, as_texture, FbxTexture, Texture);
+    define_as!(self, as_video, FbxVideo, Video);
     define_as!(self, as_light, FbxLight, Light);
     define_as!(self, as_material, FbxMaterial, Material);
     define_as!(self, as_geometry, FbxGeometry, Geometry);
@@ -291,13 +338,47 @@ impl FbxSubDeformer {
 
 pub struct FbxMaterial {
     pub textures: Vec<(String, Handle<FbxComponent>)>,
+    pub emissive_color: Color,
+    pub emissive_factor: f32,
+    pub two_sided: bool,
 }
 
 impl FbxMaterial {
-    fn read(_material_node_handle: Handle<FbxNode>) -> Result<FbxMaterial, String> {
-        Ok(FbxMaterial {
+    fn read(
+        material_node_handle: Handle<FbxNode>,
+        nodes: &FbxNodeContainer,
+    ) -> Result<FbxMaterial, String> {
+        let mut material = FbxMaterial {
             textures: Default::default(),
-        })
+            emissive_color: Color::BLACK,
+            emissive_factor: 1.0,
+            two_sided: false,
+        };
+
+        // Properties are optional, material can have only textures.
+        if let Ok(props) = nodes.get_by_name(material_node_handle, "Properties70") {
+            for prop_handle in props.children() {
+                let prop = nodes.get(*prop_handle);
+                match prop.get_attrib(0)?.as_string().as_str() {
+                    "EmissiveColor" => {
+                        let r = (prop.get_attrib(4)?.as_f64()? * 255.0) as u8;
+                        let g = (prop.get_attrib(5)?.as_f64()? * 255.0) as u8;
+                        let b = (prop.get_attrib(6)?.as_f64()? * 255.0) as u8;
+                        material.emissive_color = Color::from_rgba(r, g, b, 255);
+                    }
+                    "EmissiveFactor" => {
+                        material.emissive_factor = prop.get_attrib(4)?.as_f64()? as f32
+                    }
+                    // There is no standard property, different exporters use different names.
+                    "TwoSided" | "DoubleSided" | "twoSided" | "doubleSided" => {
+                        material.two_sided = prop.get_attrib(4)?.as_i32()? != 0
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(material)
     }
 }
 
@@ -0,0 +1,61 @@
+use crate::{
+    core::pool::Handle,
+    resource::fbx::document::{attribute::FbxAttribute, FbxNode, FbxNodeContainer},
+};
+use std::path::PathBuf;
+
+/// Video is a media clip which can be embedded in a file. Despite the name it is mostly used
+/// to store content of textures, so they can be loaded without separate files.
+pub struct FbxVideo {
+    filename: PathBuf,
+    content: Vec<u8>,
+}
+
+impl FbxVideo {
+    pub(in crate::resource::fbx) fn read(
+        video_node_handle: Handle<FbxNode>,
+        nodes: &FbxNodeContainer,
+    ) -> Result<Self, String> {
+        let mut video = FbxVideo {
+            filename: PathBuf::new(),
+            content: Vec::new(),
+        };
+        if let Ok(relative_file_name_node) =
+            nodes.get_by_name(video_node_handle, "RelativeFilename")
+        {
+            let str_path = relative_file_name_node
+                .get_attrib(0)?
+                .as_string()
+                .replace("\\", "/");
+            video.filename = PathBuf::from(str_path);
+        }
+        if let Ok(content_node) = nodes.get_by_name(video_node_handle, "Content") {
+            // Binary FBX stores content as raw data, ASCII FBX stores content as base64 string
+            // which can be split into multiple parts.
+            let mut encoded = String::new();
+            for attribute in content_node.attributes() {
+                match attribute {
+                    FbxAttribute::RawData(data) => video.content.extend_from_slice(data),
+                    FbxAttribute::String(string) => encoded.push_str(string),
+                    _ => (),
+                }
+            }
+            if !encoded.is_empty() {
+                video.content = base64::decode(&encoded).map_err(|e| {
+                    format!("FBX: Unable to decode content of embedded media: {}", e)
+                })?;
+            }
+        }
+        Ok(video)
+    }
+
+    pub(in crate::resource::fbx) fn get_file_path(&self) -> &PathBuf {
+        &self.filename
+    }
+
+    /// Returns encoded content of a file (png, tga, etc.) or empty slice if the media is not
+    /// embedded.
+    pub(in crate::resource::fbx) fn content(&self) -> &[u8] {
+        &self.content
+    }
+}
@@ -0,0 +1,96 @@
+//! Contains all possible errors that can occur during glTF loading and conversion.
+
+use std::fmt::Formatter;
+
+/// See module docs.
+#[derive(Debug)]
+pub enum GltfError {
+    /// An input/output error has occurred (file not found, unexpected end of file, etc.)
+    Io(std::io::Error),
+    /// Document is malformed or uses unsupported features.
+    Gltf(gltf::Error),
+    /// Embedded data (data URI) is not valid base64.
+    Base64(base64::DecodeError),
+    /// URI scheme is not supported, only embedded data, relative paths and `file:` are supported.
+    UnsupportedUri(String),
+    /// Document references binary chunk, but there is no such chunk.
+    MissingBlob,
+    /// Buffer has less bytes than stated in the document.
+    BufferLength {
+        /// Index of the buffer.
+        index: usize,
+        /// Amount of bytes stated in the document.
+        expected: usize,
+        /// Actual amount of bytes.
+        actual: usize,
+    },
+    /// Mesh primitive does not have vertex positions.
+    MissingPositions,
+    /// Document does not contain any scenes.
+    NoScenes,
+    /// Vertex refers to a joint with index that does not fit in bone index of a vertex (255).
+    JointIndex(u16),
+    /// Animation sampler has output count that does not match its input count (tripled for
+    /// cubic spline interpolation).
+    SamplerLength {
+        /// Index of the animation.
+        animation: usize,
+        /// Amount of outputs required by inputs.
+        expected: usize,
+        /// Actual amount of outputs.
+        actual: usize,
+    },
+}
+
+impl std::fmt::Display for GltfError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            GltfError::Io(io) => write!(f, "Io error: {}", io),
+            GltfError::Gltf(gltf) => write!(f, "glTF error: {}", gltf),
+            GltfError::Base64(base64) => write!(f, "Base64 error: {}", base64),
+            GltfError::UnsupportedUri(uri) => write!(f, "Unsupported uri {}", uri),
+            GltfError::MissingBlob => write!(f, "Binary chunk is missing."),
+            GltfError::BufferLength {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Buffer {} has {} bytes, but {} expected.",
+                index, actual, expected
+            ),
+            GltfError::MissingPositions => write!(f, "Primitive has no vertex positions."),
+            GltfError::NoScenes => write!(f, "Document has no scenes."),
+            GltfError::JointIndex(index) => {
+                write!(f, "Joint index {} is out of supported range.", index)
+            }
+            GltfError::SamplerLength {
+                animation,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Sampler of animation {} has {} outputs, but {} expected.",
+                animation, actual, expected
+            ),
+        }
+    }
+}
+
+impl From<std::io::Error> for GltfError {
+    fn from(err: std::io::Error) -> Self {
+        GltfError::Io(err)
+    }
+}
+
+impl From<gltf::Error> for GltfError {
+    fn from(err: gltf::Error) -> Self {
+        GltfError::Gltf(err)
+    }
+}
+
+impl From<base64::DecodeError> for GltfError {
+    fn from(err: base64::DecodeError) -> Self {
+        GltfError::Base64(err)
+    }
+}
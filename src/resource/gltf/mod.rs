@@ -0,0 +1,985 @@
+//! Contains all methods to load and convert glTF 2.0 model format.
+//!
+//! glTF is an open standard for transmission and loading of 3D scenes and models. Both text
+//! (`.gltf`) and binary (`.glb`) variants are supported, buffers and images can be embedded
+//! (data URIs, binary chunk) or stored in external files. Importer converts node hierarchy,
+//! meshes, metallic-roughness materials, skins and animations. Cameras, lights and morph
+//! targets are ignored.
+//!
+//! Normally you should never use methods from this module directly, use resource manager to load
+//! models and create their instances.
+
+pub mod error;
+
+use crate::{
+    animation::{Animation, KeyFrame, Track},
+    core::{
+        algebra::{Matrix4, Quaternion, UnitQuaternion, Vector2, Vector3, Vector4},
+        color::Color,
+        math::TriangleDefinition,
+        pool::Handle,
+    },
+    engine::resource_manager::ResourceManager,
+    renderer::surface::{PbrMaterialBuilder, Surface, SurfaceSharedData, Vertex},
+    resource::{
+        gltf::error::GltfError,
+        texture::{
+            Texture, TextureColorSpace, TextureData, TextureKind, TexturePixelKind, TextureState,
+        },
+    },
+    scene::{
+        base::BaseBuilder, graph::Graph, mesh::MeshBuilder, node::Node,
+        transform::TransformBuilder, Scene,
+    },
+    utils::log::{Log, MessageKind},
+};
+use ::gltf::{animation::util::ReadOutputs, Document, Gltf};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Instant,
+};
+
+/// Decodes percent-encoded characters in relative URI, for example `%20` becomes a space.
+fn decode_uri_path(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            // Escape is decoded from bytes, because string slice could split multi-byte char.
+            let high = (bytes[i + 1] as char).to_digit(16);
+            let low = (bytes[i + 2] as char).to_digit(16);
+            if let (Some(high), Some(low)) = (high, low) {
+                decoded.push((high * 16 + low) as u8);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+enum UriSource<'a> {
+    Embedded(&'a str),
+    File(PathBuf),
+}
+
+fn parse_uri<'a>(base: &Path, uri: &'a str) -> Result<UriSource<'a>, GltfError> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        match data.split(";base64,").nth(1) {
+            Some(base64) => Ok(UriSource::Embedded(base64)),
+            None => Err(GltfError::UnsupportedUri(uri.to_owned())),
+        }
+    } else if let Some(path) = uri
+        .strip_prefix("file://")
+        .or_else(|| uri.strip_prefix("file:"))
+    {
+        Ok(UriSource::File(PathBuf::from(decode_uri_path(path))))
+    } else if uri.contains(':') {
+        Err(GltfError::UnsupportedUri(uri.to_owned()))
+    } else {
+        Ok(UriSource::File(base.join(decode_uri_path(uri))))
+    }
+}
+
+fn read_uri(base: &Path, uri: &str) -> Result<Vec<u8>, GltfError> {
+    match parse_uri(base, uri)? {
+        UriSource::Embedded(base64) => Ok(base64::decode(base64)?),
+        UriSource::File(path) => Ok(std::fs::read(path)?),
+    }
+}
+
+fn load_buffers(
+    document: &Document,
+    base: &Path,
+    mut blob: Option<Vec<u8>>,
+) -> Result<Vec<Vec<u8>>, GltfError> {
+    let mut buffers = Vec::new();
+    for buffer in document.buffers() {
+        let data = match buffer.source() {
+            ::gltf::buffer::Source::Uri(uri) => read_uri(base, uri)?,
+            ::gltf::buffer::Source::Bin => blob.take().ok_or(GltfError::MissingBlob)?,
+        };
+        if data.len() < buffer.length() {
+            return Err(GltfError::BufferLength {
+                index: buffer.index(),
+                expected: buffer.length(),
+                actual: data.len(),
+            });
+        }
+        buffers.push(data);
+    }
+    Ok(buffers)
+}
+
+fn decode_embedded_image(bytes: &[u8], color_space: TextureColorSpace) -> Option<Texture> {
+    match image::load_from_memory(bytes) {
+        Ok(image) => {
+            let image = image.into_rgba8();
+            let kind = TextureKind::Rectangle {
+                width: image.width(),
+                height: image.height(),
+            };
+            TextureData::from_bytes(kind, TexturePixelKind::RGBA8, image.into_raw())
+                .ok()
+                .map(|mut data| {
+                    data.set_color_space(color_space);
+                    Texture::new(TextureState::Ok(data))
+                })
+        }
+        Err(error) => {
+            Log::writeln(
+                MessageKind::Error,
+                format!("Unable to decode embedded glTF image. Reason: {:?}", error),
+            );
+            None
+        }
+    }
+}
+
+/// Returns indices of images which are used as data (normal, metallic-roughness and occlusion
+/// maps) instead of colors, such images must not be treated as sRGB.
+fn data_images(document: &Document) -> HashSet<usize> {
+    let mut images = HashSet::new();
+    for material in document.materials() {
+        if let Some(info) = material
+            .pbr_metallic_roughness()
+            .metallic_roughness_texture()
+        {
+            images.insert(info.texture().source().index());
+        }
+        if let Some(info) = material.normal_texture() {
+            images.insert(info.texture().source().index());
+        }
+        if let Some(info) = material.occlusion_texture() {
+            images.insert(info.texture().source().index());
+        }
+    }
+    images
+}
+
+/// Loads every image of the document. External images are requested through resource manager
+/// so they will be shared with other resources, embedded images are decoded in place.
+fn load_images(
+    document: &Document,
+    buffers: &[Vec<u8>],
+    base: &Path,
+    resource_manager: &ResourceManager,
+) -> Vec<Option<Texture>> {
+    let data_images = data_images(document);
+    document
+        .images()
+        .map(|image| {
+            let color_space = if data_images.contains(&image.index()) {
+                TextureColorSpace::Linear
+            } else {
+                TextureColorSpace::Srgb
+            };
+            (image, color_space)
+        })
+        .map(|(image, color_space)| match image.source() {
+            ::gltf::image::Source::Uri { uri, .. } => match parse_uri(base, uri) {
+                Ok(UriSource::File(path)) => {
+                    let options = resource_manager
+                        .state()
+                        .textures_import_options()
+                        .clone()
+                        .with_color_space(color_space);
+                    Some(resource_manager.request_texture_with_options(path, options))
+                }
+                Ok(UriSource::Embedded(base64)) => match base64::decode(base64) {
+                    Ok(bytes) => decode_embedded_image(&bytes, color_space),
+                    Err(error) => {
+                        Log::writeln(
+                            MessageKind::Error,
+                            format!("Unable to decode glTF image data. Reason: {:?}", error),
+                        );
+                        None
+                    }
+                },
+                Err(error) => {
+                    Log::writeln(
+                        MessageKind::Error,
+                        format!("Unable to load glTF image. Reason: {}", error),
+                    );
+                    None
+                }
+            },
+            ::gltf::image::Source::View { view, .. } => {
+                let begin = view.offset();
+                let end = begin + view.length();
+                buffers
+                    .get(view.buffer().index())
+                    .and_then(|buffer| buffer.get(begin..end))
+                    .and_then(|bytes| decode_embedded_image(bytes, color_space))
+            }
+        })
+        .collect()
+}
+
+fn convert_material(
+    material: &::gltf::Material,
+    images: &[Option<Texture>],
+    surface: &mut Surface,
+) {
+    let get_texture = |texture: ::gltf::Texture| -> Option<Texture> {
+        images.get(texture.source().index()).cloned().flatten()
+    };
+
+    let pbr = material.pbr_metallic_roughness();
+    let mut builder = PbrMaterialBuilder::new()
+        .with_albedo_color(Color::from(Vector4::from(pbr.base_color_factor())))
+        .with_metallic(pbr.metallic_factor())
+        .with_roughness(pbr.roughness_factor())
+        .with_emissive_color(Color::from(Vector3::from(material.emissive_factor())));
+
+    if let Some(albedo) = pbr
+        .base_color_texture()
+        .and_then(|info| get_texture(info.texture()))
+    {
+        // Diffuse texture is still used by passes which are not aware of PBR, for example
+        // by shadow maps for alpha test.
+        surface.set_diffuse_texture(Some(albedo.clone()));
+        builder = builder.with_albedo_texture(albedo);
+    }
+    // Metallic and roughness are packed in a single texture (roughness in G, metallic in B).
+    if let Some(metallic_roughness) = pbr
+        .metallic_roughness_texture()
+        .and_then(|info| get_texture(info.texture()))
+    {
+        builder = builder
+            .with_metallic_texture(metallic_roughness.clone())
+            .with_roughness_texture(metallic_roughness);
+    }
+    if let Some(ao) = material
+        .occlusion_texture()
+        .and_then(|info| get_texture(info.texture()))
+    {
+        builder = builder.with_ao_texture(ao);
+    }
+    if let Some(emissive) = material
+        .emissive_texture()
+        .and_then(|info| get_texture(info.texture()))
+    {
+        builder = builder.with_emissive_texture(emissive);
+    }
+    if let Some(normal) = material
+        .normal_texture()
+        .and_then(|info| get_texture(info.texture()))
+    {
+        surface.set_normal_texture(Some(normal));
+    }
+
+    surface.set_two_sided(material.double_sided());
+    surface.set_transparent(material.alpha_mode() == ::gltf::material::AlphaMode::Blend);
+    surface.set_pbr_material(Some(builder.build()));
+}
+
+fn convert_primitive(
+    primitive: &::gltf::Primitive,
+    buffers: &[Vec<u8>],
+    images: &[Option<Texture>],
+) -> Result<Option<Surface>, GltfError> {
+    if primitive.mode() != ::gltf::mesh::Mode::Triangles {
+        Log::writeln(
+            MessageKind::Warning,
+            format!(
+                "glTF primitive mode {:?} is not supported, primitive skipped.",
+                primitive.mode()
+            ),
+        );
+        return Ok(None);
+    }
+
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|b| b.as_slice()));
+
+    let mut vertices = reader
+        .read_positions()
+        .ok_or(GltfError::MissingPositions)?
+        .map(|position| Vertex {
+            position: Vector3::from(position),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+
+    let has_normals = if let Some(normals) = reader.read_normals() {
+        for (vertex, normal) in vertices.iter_mut().zip(normals) {
+            vertex.normal = Vector3::from(normal);
+        }
+        true
+    } else {
+        false
+    };
+
+    let has_tangents = if let Some(tangents) = reader.read_tangents() {
+        for (vertex, tangent) in vertices.iter_mut().zip(tangents) {
+            vertex.tangent = Vector4::from(tangent);
+        }
+        true
+    } else {
+        false
+    };
+
+    // glTF has texture origin at top left corner, so texture coordinates can be used as is.
+    if let Some(tex_coords) = reader.read_tex_coords(0) {
+        for (vertex, tex_coord) in vertices.iter_mut().zip(tex_coords.into_f32()) {
+            vertex.tex_coord = Vector2::from(tex_coord);
+        }
+    }
+    if let Some(tex_coords) = reader.read_tex_coords(1) {
+        for (vertex, tex_coord) in vertices.iter_mut().zip(tex_coords.into_f32()) {
+            vertex.second_tex_coord = Vector2::from(tex_coord);
+        }
+    }
+
+    // Colors are stored only if there is exactly one color per vertex.
+    let colors = reader
+        .read_colors(0)
+        .map(|colors| {
+            colors
+                .into_rgba_u8()
+                .map(|[r, g, b, a]| Color::from_rgba(r, g, b, a))
+                .collect::<Vec<_>>()
+        })
+        .filter(|colors| colors.len() == vertices.len());
+
+    // Joint indices refer to joints of a skin, they will become indices in array of bones
+    // of the surface.
+    if let (Some(joints), Some(weights)) = (reader.read_joints(0), reader.read_weights(0)) {
+        for ((vertex, joints), weights) in vertices
+            .iter_mut()
+            .zip(joints.into_u16())
+            .zip(weights.into_f32())
+        {
+            for k in 0..4 {
+                vertex.bone_indices[k] =
+                    u8::try_from(joints[k]).map_err(|_| GltfError::JointIndex(joints[k]))?;
+                vertex.bone_weights[k] = weights[k];
+            }
+        }
+    }
+
+    let triangles = match reader.read_indices() {
+        Some(indices) => indices
+            .into_u32()
+            .collect::<Vec<_>>()
+            .chunks_exact(3)
+            .map(|triangle| TriangleDefinition([triangle[0], triangle[1], triangle[2]]))
+            .collect(),
+        None => (0..vertices.len() as u32 / 3)
+            .map(|i| TriangleDefinition([3 * i, 3 * i + 1, 3 * i + 2]))
+            .collect(),
+    };
+
+    let mut data = SurfaceSharedData::new(vertices, triangles, false);
+    if !has_normals {
+        data.calculate_normals();
+    }
+    if !has_tangents {
+        data.calculate_tangents();
+    }
+
+    let has_colors = colors.is_some();
+    data.set_vertex_colors(colors);
+
+    let mut surface = Surface::new(Arc::new(RwLock::new(data)));
+    surface.set_vertex_colors_enabled(has_colors);
+    convert_material(&primitive.material(), images, &mut surface);
+
+    Ok(Some(surface))
+}
+
+fn convert_mesh(
+    mesh: &::gltf::Mesh,
+    buffers: &[Vec<u8>],
+    images: &[Option<Texture>],
+) -> Result<Vec<Surface>, GltfError> {
+    let mut surfaces = Vec::new();
+    for primitive in mesh.primitives() {
+        if let Some(surface) = convert_primitive(&primitive, buffers, images)? {
+            surfaces.push(surface);
+        }
+    }
+    Ok(surfaces)
+}
+
+fn node_name(node: &::gltf::Node) -> String {
+    // Names are used to retarget animations, so every node must have one.
+    node.name()
+        .map(|name| name.to_owned())
+        .unwrap_or_else(|| format!("Node{}", node.index()))
+}
+
+fn decompose(node: &::gltf::Node) -> (Vector3<f32>, UnitQuaternion<f32>, Vector3<f32>) {
+    let (translation, [x, y, z, w], scale) = node.transform().decomposed();
+    (
+        Vector3::from(translation),
+        UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z)),
+        Vector3::from(scale),
+    )
+}
+
+struct ConversionContext<'a> {
+    buffers: &'a [Vec<u8>],
+    images: &'a [Option<Texture>],
+    // Meshes can be shared between nodes, surfaces will share data in this case.
+    mesh_cache: HashMap<usize, Vec<Surface>>,
+    node_map: HashMap<usize, Handle<Node>>,
+}
+
+fn convert_node(
+    node: &::gltf::Node,
+    context: &mut ConversionContext,
+    graph: &mut Graph,
+) -> Result<Handle<Node>, GltfError> {
+    let (position, rotation, scale) = decompose(node);
+
+    let base = BaseBuilder::new()
+        .with_name(node_name(node))
+        .with_local_transform(
+            TransformBuilder::new()
+                .with_local_position(position)
+                .with_local_rotation(rotation)
+                .with_local_scale(scale)
+                .build(),
+        );
+
+    let handle = if let Some(mesh) = node.mesh() {
+        let surfaces = match context.mesh_cache.get(&mesh.index()) {
+            Some(surfaces) => surfaces.clone(),
+            None => {
+                let surfaces = convert_mesh(&mesh, context.buffers, context.images)?;
+                context.mesh_cache.insert(mesh.index(), surfaces.clone());
+                surfaces
+            }
+        };
+        MeshBuilder::new(base).with_surfaces(surfaces).build(graph)
+    } else {
+        base.build(graph)
+    };
+
+    context.node_map.insert(node.index(), handle);
+
+    for child in node.children() {
+        let child_handle = convert_node(&child, context, graph)?;
+        graph.link_nodes(child_handle, handle);
+    }
+
+    Ok(handle)
+}
+
+fn convert_skins(document: &Document, context: &ConversionContext, graph: &mut Graph) {
+    for node in document.nodes() {
+        let (skin, &mesh_handle) = match (node.skin(), context.node_map.get(&node.index())) {
+            (Some(skin), Some(handle)) => (skin, handle),
+            _ => continue,
+        };
+
+        // Vertices refer to bones by index of joint, so missing joints must keep their place.
+        // Mesh itself is used instead of them, so affected vertices stay in bind pose.
+        let joints = skin
+            .joints()
+            .map(|joint| context.node_map.get(&joint.index()).copied())
+            .collect::<Vec<_>>();
+        if joints.iter().any(|joint| joint.is_none()) {
+            Log::writeln(
+                MessageKind::Warning,
+                format!(
+                    "glTF skin {} has joints that are not in the scene.",
+                    skin.index()
+                ),
+            );
+        }
+        let bones = joints
+            .iter()
+            .map(|joint| joint.unwrap_or(mesh_handle))
+            .collect::<Vec<_>>();
+
+        if bones.len() > u8::MAX as usize {
+            Log::writeln(
+                MessageKind::Error,
+                format!(
+                    "glTF skin {} has {} joints, but only {} are supported.",
+                    skin.index(),
+                    bones.len(),
+                    u8::MAX
+                ),
+            );
+        }
+
+        let reader =
+            skin.reader(|buffer| context.buffers.get(buffer.index()).map(|b| b.as_slice()));
+        if let Some(inverse_bind_matrices) = reader.read_inverse_bind_matrices() {
+            for (joint, matrix) in joints.iter().zip(inverse_bind_matrices) {
+                if let Some(bone) = *joint {
+                    graph[bone].inv_bind_pose_transform = Matrix4::from(matrix);
+                }
+            }
+        }
+
+        if let Node::Mesh(mesh) = &mut graph[mesh_handle] {
+            for surface in mesh.surfaces_mut() {
+                surface.bones = bones.clone();
+            }
+        }
+    }
+}
+
+/// Keyframes of a single property of a node.
+struct Curve {
+    times: Vec<f32>,
+    // Rotations are stored as (x, y, z, w), other properties have w = 0.
+    values: Vec<Vector4<f32>>,
+    interpolation: ::gltf::animation::Interpolation,
+}
+
+impl Curve {
+    /// Creates new curve, checks that there is a value (or a triplet for cubic spline) for each
+    /// time, so sampling never goes out of bounds.
+    fn new(
+        animation: usize,
+        times: Vec<f32>,
+        values: Vec<Vector4<f32>>,
+        interpolation: ::gltf::animation::Interpolation,
+    ) -> Result<Self, GltfError> {
+        let expected = match interpolation {
+            ::gltf::animation::Interpolation::CubicSpline => 3 * times.len(),
+            _ => times.len(),
+        };
+        if values.len() != expected {
+            return Err(GltfError::SamplerLength {
+                animation,
+                expected,
+                actual: values.len(),
+            });
+        }
+        Ok(Self {
+            times,
+            values,
+            interpolation,
+        })
+    }
+
+    fn value(&self, index: usize) -> Vector4<f32> {
+        match self.interpolation {
+            // Each keyframe is a triplet (in-tangent, value, out-tangent).
+            ::gltf::animation::Interpolation::CubicSpline => self.values[3 * index + 1],
+            _ => self.values[index],
+        }
+    }
+
+    fn sample(&self, time: f32, is_rotation: bool) -> Vector4<f32> {
+        let last = self.times.len() - 1;
+        if time <= self.times[0] {
+            return self.value(0);
+        }
+        if time >= self.times[last] {
+            return self.value(last);
+        }
+
+        let next = self.times.iter().position(|&t| t > time).unwrap_or(last);
+        let prev = next - 1;
+        let dt = self.times[next] - self.times[prev];
+        let t = if dt > f32::EPSILON {
+            (time - self.times[prev]) / dt
+        } else {
+            0.0
+        };
+
+        match self.interpolation {
+            ::gltf::animation::Interpolation::Step => self.value(prev),
+            ::gltf::animation::Interpolation::Linear => {
+                if is_rotation {
+                    let a = UnitQuaternion::from_quaternion(Quaternion::from(self.value(prev)));
+                    let b = UnitQuaternion::from_quaternion(Quaternion::from(self.value(next)));
+                    a.slerp(&b, t).into_inner().coords
+                } else {
+                    self.value(prev).lerp(&self.value(next), t)
+                }
+            }
+            ::gltf::animation::Interpolation::CubicSpline => {
+                // Hermite spline.
+                let p0 = self.value(prev);
+                let m0 = self.values[3 * prev + 2] * dt;
+                let p1 = self.value(next);
+                let m1 = self.values[3 * next] * dt;
+                let t2 = t * t;
+                let t3 = t2 * t;
+                let value = p0 * (2.0 * t3 - 3.0 * t2 + 1.0)
+                    + m0 * (t3 - 2.0 * t2 + t)
+                    + p1 * (-2.0 * t3 + 3.0 * t2)
+                    + m1 * (t3 - t2);
+                if is_rotation {
+                    value.try_normalize(f32::EPSILON).unwrap_or(p0)
+                } else {
+                    value
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct NodeCurves {
+    translation: Option<Curve>,
+    rotation: Option<Curve>,
+    scale: Option<Curve>,
+}
+
+fn convert_animations(
+    document: &Document,
+    context: &ConversionContext,
+    scene: &mut Scene,
+) -> Result<(), GltfError> {
+    for gltf_animation in document.animations() {
+        let mut curves: HashMap<usize, NodeCurves> = HashMap::new();
+
+        for channel in gltf_animation.channels() {
+            let reader =
+                channel.reader(|buffer| context.buffers.get(buffer.index()).map(|b| b.as_slice()));
+            let (times, outputs) = match (reader.read_inputs(), reader.read_outputs()) {
+                (Some(times), Some(outputs)) => (times.collect::<Vec<_>>(), outputs),
+                _ => continue,
+            };
+            if times.is_empty() {
+                continue;
+            }
+
+            let interpolation = channel.sampler().interpolation();
+            let node_curves = curves.entry(channel.target().node().index()).or_default();
+
+            match outputs {
+                ReadOutputs::Translations(translations) => {
+                    node_curves.translation = Some(Curve::new(
+                        gltf_animation.index(),
+                        times,
+                        translations
+                            .map(|[x, y, z]| Vector4::new(x, y, z, 0.0))
+                            .collect(),
+                        interpolation,
+                    )?)
+                }
+                ReadOutputs::Rotations(rotations) => {
+                    node_curves.rotation = Some(Curve::new(
+                        gltf_animation.index(),
+                        times,
+                        rotations.into_f32().map(Vector4::from).collect(),
+                        interpolation,
+                    )?)
+                }
+                ReadOutputs::Scales(scales) => {
+                    node_curves.scale = Some(Curve::new(
+                        gltf_animation.index(),
+                        times,
+                        scales.map(|[x, y, z]| Vector4::new(x, y, z, 0.0)).collect(),
+                        interpolation,
+                    )?)
+                }
+                // Morph targets are not supported.
+                ReadOutputs::MorphTargetWeights(_) => (),
+            }
+        }
+
+        let mut animation = Animation::default();
+
+        for (node_index, node_curves) in curves {
+            let (node, &node_handle) = match (
+                document.nodes().nth(node_index),
+                context.node_map.get(&node_index),
+            ) {
+                (Some(node), Some(handle)) => (node, handle),
+                _ => continue,
+            };
+
+            let (position, rotation, scale) = decompose(&node);
+
+            // Engine tracks store all properties in each key frame, so key frames are placed
+            // at times of every property key and missing properties are sampled.
+            let mut times = [
+                &node_curves.translation,
+                &node_curves.rotation,
+                &node_curves.scale,
+            ]
+            .iter()
+            .filter_map(|curve| curve.as_ref())
+            .flat_map(|curve| curve.times.iter().copied())
+            .collect::<Vec<_>>();
+            times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            times.dedup();
+
+            let mut track = Track::new();
+            track.set_node(node_handle);
+
+            for time in times {
+                let key_position = node_curves
+                    .translation
+                    .as_ref()
+                    .map(|curve| curve.sample(time, false).xyz())
+                    .unwrap_or(position);
+                let key_rotation = node_curves
+                    .rotation
+                    .as_ref()
+                    .map(|curve| {
+                        UnitQuaternion::from_quaternion(Quaternion::from(curve.sample(time, true)))
+                    })
+                    .unwrap_or(rotation);
+                let key_scale = node_curves
+                    .scale
+                    .as_ref()
+                    .map(|curve| curve.sample(time, false).xyz())
+                    .unwrap_or(scale);
+
+                track.add_key_frame(KeyFrame::new(time, key_position, key_scale, key_rotation));
+            }
+
+            animation.add_track(track);
+        }
+
+        scene.animations.add(animation);
+    }
+
+    Ok(())
+}
+
+fn convert(
+    document: &Document,
+    buffers: &[Vec<u8>],
+    images: &[Option<Texture>],
+    scene: &mut Scene,
+) -> Result<Handle<Node>, GltfError> {
+    let gltf_scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or(GltfError::NoScenes)?;
+
+    let mut context = ConversionContext {
+        buffers,
+        images,
+        mesh_cache: Default::default(),
+        node_map: Default::default(),
+    };
+
+    let root = BaseBuilder::new()
+        .with_name(gltf_scene.name().unwrap_or("Root"))
+        .build(&mut scene.graph);
+    for node in gltf_scene.nodes() {
+        let node_handle = convert_node(&node, &mut context, &mut scene.graph)?;
+        scene.graph.link_nodes(node_handle, root);
+    }
+
+    convert_skins(document, &context, &mut scene.graph);
+    convert_animations(document, &context, scene)?;
+
+    scene.graph.update_hierarchical_data();
+
+    Ok(root)
+}
+
+/// Tries to load and convert glTF (or GLB) from given path.
+///
+/// Normally you should never use this method, use resource manager to load models.
+pub fn load_to_scene<P: AsRef<Path>>(
+    scene: &mut Scene,
+    resource_manager: ResourceManager,
+    path: P,
+) -> Result<Handle<Node>, GltfError> {
+    let start_time = Instant::now();
+
+    Log::writeln(
+        MessageKind::Information,
+        format!("Trying to load {:?}", path.as_ref()),
+    );
+
+    let base = path.as_ref().parent().unwrap_or_else(|| Path::new("."));
+
+    let now = Instant::now();
+    let Gltf { document, blob } = Gltf::open(path.as_ref())?;
+    let buffers = load_buffers(&document, base, blob)?;
+    let images = load_images(&document, &buffers, base, &resource_manager);
+    let parsing_time = now.elapsed().as_millis();
+
+    let now = Instant::now();
+    let result = convert(&document, &buffers, &images, scene);
+    let conversion_time = now.elapsed().as_millis();
+
+    Log::writeln(
+        MessageKind::Information,
+        format!(
+            "glTF {:?} loaded in {} ms\n\t- Parsing - {} ms\n\t- Conversion - {} ms",
+            path.as_ref(),
+            start_time.elapsed().as_millis(),
+            parsing_time,
+            conversion_time
+        ),
+    );
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{algebra::Vector3, pool::Handle},
+        engine::resource_manager::ResourceManager,
+        resource::gltf::{decode_uri_path, error::GltfError, load_to_scene},
+        scene::{node::Node, Scene},
+    };
+
+    // Single triangle with translation animation, buffer is embedded as data URI.
+    const TRIANGLE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [ { "nodes": [ 0 ] } ],
+        "nodes": [ { "name": "Triangle", "mesh": 0 } ],
+        "meshes": [ { "primitives": [ { "attributes": { "POSITION": 0 }, "indices": 1 } ] } ],
+        "animations": [ {
+            "channels": [ { "sampler": 0, "target": { "node": 0, "path": "translation" } } ],
+            "samplers": [ { "input": 2, "output": 3 } ]
+        } ],
+        "buffers": [ {
+            "byteLength": 76,
+            "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAAEAAAAAAAAAAAA=="
+        } ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 6 },
+            { "buffer": 0, "byteOffset": 44, "byteLength": 8 },
+            { "buffer": 0, "byteOffset": 52, "byteLength": 24 }
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+              "min": [ 0.0, 0.0, 0.0 ], "max": [ 1.0, 1.0, 0.0 ] },
+            { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" },
+            { "bufferView": 2, "componentType": 5126, "count": 2, "type": "SCALAR",
+              "min": [ 0.0 ], "max": [ 1.0 ] },
+            { "bufferView": 3, "componentType": 5126, "count": 2, "type": "VEC3" }
+        ]
+    }"#;
+
+    // Skinned triangle, vertices are bound to joint with index {joint}. First joint of the skin
+    // is not in the scene.
+    const SKINNED_TRIANGLE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [ { "nodes": [ 0, 1 ] } ],
+        "nodes": [
+            { "name": "Triangle", "mesh": 0, "skin": 0 },
+            { "name": "Bone" },
+            { "name": "Orphan" }
+        ],
+        "meshes": [ { "primitives": [ {
+            "attributes": { "POSITION": 0, "JOINTS_0": 1, "WEIGHTS_0": 2 }
+        } ] } ],
+        "skins": [ { "joints": [ 2, 1 ] } ],
+        "buffers": [ { "byteLength": 108, "uri": "data:application/octet-stream;base64,{data}" } ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 24 },
+            { "buffer": 0, "byteOffset": 60, "byteLength": 48 }
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+              "min": [ 0.0, 0.0, 0.0 ], "max": [ 1.0, 1.0, 0.0 ] },
+            { "bufferView": 1, "componentType": 5123, "count": 3, "type": "VEC4" },
+            { "bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC4" }
+        ]
+    }"#;
+
+    fn load(gltf: &str, name: &str) -> Result<(Scene, Handle<Node>), GltfError> {
+        // Tests of several checkouts can run at the same time.
+        let path =
+            std::env::temp_dir().join(format!("rg3d_gltf_{}_{}.gltf", name, std::process::id()));
+        std::fs::write(&path, gltf).unwrap();
+        let mut scene = Scene::new();
+        let result = load_to_scene(&mut scene, ResourceManager::new(), &path);
+        let _ = std::fs::remove_file(&path);
+        result.map(|root| (scene, root))
+    }
+
+    #[test]
+    fn test_decode_uri_path() {
+        assert_eq!(decode_uri_path("my%20model.bin"), "my model.bin");
+        assert_eq!(decode_uri_path("100%"), "100%");
+        assert_eq!(decode_uri_path("%E6%A8%A1%E5%9E%8B.bin"), "模型.bin");
+        // Non-ASCII chars right after escape sign must not split a char.
+        assert_eq!(decode_uri_path("%é%2é.bin"), "%é%2é.bin");
+        assert_eq!(decode_uri_path("модель%2.bin"), "модель%2.bin");
+    }
+
+    #[test]
+    fn test_load_embedded() {
+        let (scene, root) = load(TRIANGLE, "triangle").unwrap();
+
+        let triangle = scene.graph[root].children()[0];
+        assert_eq!(scene.graph[triangle].name(), "Triangle");
+        if let Node::Mesh(mesh) = &scene.graph[triangle] {
+            let data = mesh.surfaces()[0].data();
+            let data = data.read().unwrap();
+            assert_eq!(data.get_vertices().len(), 3);
+            assert_eq!(data.triangles().len(), 1);
+        } else {
+            panic!("Triangle must be a mesh");
+        }
+
+        let animation = scene.animations.iter().next().unwrap();
+        let track = &animation.get_tracks()[0];
+        assert_eq!(track.get_key_frames().len(), 2);
+        assert_eq!(
+            track.get_key_frames()[1].position,
+            Vector3::new(2.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_short_sampler_output() {
+        // Two key frames, but only one translation.
+        let gltf = TRIANGLE.replace(
+            r#"{ "bufferView": 3, "componentType": 5126, "count": 2, "type": "VEC3" }"#,
+            r#"{ "bufferView": 3, "componentType": 5126, "count": 1, "type": "VEC3" }"#,
+        );
+        assert!(matches!(
+            load(&gltf, "short_output"),
+            Err(GltfError::SamplerLength {
+                animation: 0,
+                expected: 2,
+                actual: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn test_skin_with_missing_joint() {
+        // Vertices are bound to joint 1.
+        let gltf = SKINNED_TRIANGLE.replace("{data}", "AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAQAAAAAAAAABAAAAAAAAAAEAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAAAAAAAAAAAAIA/AAAAAAAAAAAAAAAA");
+        let (scene, root) = load(&gltf, "skin").unwrap();
+
+        let children = scene.graph[root].children();
+        let (triangle, bone) = (children[0], children[1]);
+        assert_eq!(scene.graph[bone].name(), "Bone");
+        if let Node::Mesh(mesh) = &scene.graph[triangle] {
+            let surface = &mesh.surfaces()[0];
+            // Missing joint keeps its place, so index 1 still refers to the bone.
+            assert_eq!(surface.bones, vec![triangle, bone]);
+            let data = surface.data();
+            let data = data.read().unwrap();
+            assert_eq!(data.get_vertices()[0].bone_indices[0], 1);
+        } else {
+            panic!("Triangle must be a mesh");
+        }
+    }
+
+    #[test]
+    fn test_joint_index_out_of_range() {
+        // Vertices are bound to joint 300.
+        let gltf = SKINNED_TRIANGLE.replace("{data}", "AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAALAEAAAAAAAAsAQAAAAAAACwBAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAAAAAAAAAAAAIA/AAAAAAAAAAAAAAAA");
+        assert!(matches!(
+            load(&gltf, "joint_index"),
+            Err(GltfError::JointIndex(300))
+        ));
+    }
+}
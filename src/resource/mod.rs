@@ -15,6 +15,7 @@ use std::{
 };
 
 pub mod fbx;
+pub mod gltf;
 pub mod model;
 pub mod texture;
 
@@ -15,17 +15,18 @@
 //!
 //! # Supported formats
 //!
-//! Currently only FBX (common format in game industry for storing complex 3d models)
-//! and RGS (native rusty-editor format) formats are supported.
+//! Currently FBX (common format in game industry for storing complex 3d models), glTF 2.0
+//! (both `.gltf` and `.glb`) and RGS (native rusty-editor format) formats are supported.
 use crate::utils::log::MessageKind;
 use crate::{
     animation::Animation,
     core::{
+        algebra::{UnitQuaternion, Vector3},
         pool::Handle,
         visitor::{Visit, VisitError, VisitResult, Visitor},
     },
     engine::resource_manager::ResourceManager,
-    resource::{fbx, fbx::error::FbxError, Resource, ResourceData},
+    resource::{fbx, fbx::error::FbxError, gltf, gltf::error::GltfError, Resource, ResourceData},
     scene::{node::Node, Scene},
     utils::log::Log,
 };
@@ -88,6 +89,23 @@ impl Model {
         }
     }
 
+    /// Same as [`instantiate`](Self::instantiate), but also places root node of the instance
+    /// at given position with given rotation. Other properties of local transform of the root
+    /// (scale, pivots, etc.) are kept as is.
+    pub fn instantiate_at(
+        &self,
+        dest_scene: &mut Scene,
+        position: Vector3<f32>,
+        rotation: UnitQuaternion<f32>,
+    ) -> ModelInstance {
+        let instance = self.instantiate(dest_scene);
+        dest_scene.graph[instance.root]
+            .local_transform_mut()
+            .set_position(position)
+            .set_rotation(rotation);
+        instance
+    }
+
     /// Tries to retarget animations from given model resource to a node hierarchy starting
     /// from `root` on a given scene.
     ///
@@ -107,8 +125,9 @@ impl Model {
     ///
     /// # Notes
     ///
-    /// Most of the 3d model formats can contain only one animation, so in most cases
-    /// this function will return vector with only one animation.
+    /// Some model formats can contain multiple animations (for example FBX can have
+    /// multiple takes), in this case every animation will be retargetted. Animations
+    /// are named after takes, use `Animation::name` to find specific one.
     pub fn retarget_animations(
         &self,
         root: Handle<Node>,
@@ -198,6 +217,8 @@ pub enum ModelLoadError {
     NotSupported(String),
     /// An error occurred while loading FBX file.
     Fbx(FbxError),
+    /// An error occurred while loading glTF file.
+    Gltf(GltfError),
 }
 
 impl From<FbxError> for ModelLoadError {
@@ -206,6 +227,12 @@ impl From<FbxError> for ModelLoadError {
     }
 }
 
+impl From<GltfError> for ModelLoadError {
+    fn from(gltf: GltfError) -> Self {
+        ModelLoadError::Gltf(gltf)
+    }
+}
+
 impl From<VisitError> for ModelLoadError {
     fn from(e: VisitError) -> Self {
         ModelLoadError::Visit(e)
@@ -230,6 +257,11 @@ impl ModelData {
                 fbx::load_to_scene(&mut scene, resource_manager, path.as_ref())?;
                 scene
             }
+            "gltf" | "glb" => {
+                let mut scene = Scene::new();
+                gltf::load_to_scene(&mut scene, resource_manager, path.as_ref())?;
+                scene
+            }
             // Scene can be used directly as model resource. Such scenes can be created from
             // rusty-editor (https://github.com/mrDIMAS/rusty-editor) for example.
             "rgs" => Scene::from_file(path.as_ref(), resource_manager).await?,
@@ -261,3 +293,37 @@ impl ModelData {
         self.scene.graph.find_by_name_from_root(name)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::algebra::{UnitQuaternion, Vector3},
+        resource::{
+            model::{Model, ModelData},
+            ResourceState,
+        },
+        scene::{base::BaseBuilder, Scene},
+    };
+
+    #[test]
+    fn test_instantiate_at() {
+        let mut data = ModelData::default();
+        BaseBuilder::new()
+            .with_name("Child")
+            .build(&mut data.scene.graph);
+        let model = Model::new(ResourceState::Ok(data));
+
+        let mut scene = Scene::new();
+        let position = Vector3::new(1.0, 2.0, 3.0);
+        let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), 1.0);
+        let instance = model.instantiate_at(&mut scene, position, rotation);
+        scene.graph.update_hierarchical_data();
+
+        assert!(scene.graph[instance.root].is_resource_instance);
+        assert!((scene.graph.global_position(instance.root) - position).norm() < 0.001);
+        let child = scene.graph.find_by_name(instance.root, "Child");
+        assert!((scene.graph.global_position(child) - position).norm() < 0.001);
+        let instance_rotation = scene.graph[instance.root].local_transform().rotation();
+        assert!(instance_rotation.angle_to(&rotation) < 0.001);
+    }
+}
@@ -9,28 +9,70 @@
 //! To load images and decode them, rg3d uses image and ddsfile crates. Here is the list of
 //! supported formats: png, tga, bmp, dds, jpg, gif, tiff, dds.
 //!
+//! Also 3D color lookup tables in Adobe .cube format are loaded as volume textures with RGB16
+//! pixels in linear color space, such textures are used for color grading (see
+//! `ColorGradingLut` in renderer module).
+//!
 //! ## Compressed textures
 //!
 //! rg3d supports most commonly used formats of compressed textures: DXT1, DXT3, DXT5.
 //!
+//! ## Color space
+//!
+//! Pixels of a texture can be colors (albedo, emission, etc.) which are usually stored in sRGB
+//! color space, or data (normal maps, roughness, lightmaps, etc.) which are stored in linear
+//! space. Lighting is done in linear space, so renderer must know what kind of data is stored
+//! in a texture, see `TextureColorSpace` for more info. Textures are treated as sRGB by default,
+//! for textures loaded by resource manager this can be changed in `TextureImportOptions`.
+//!
+//! ## Filtering
+//!
+//! Each texture has its own minification and magnification filters and anisotropy level, they
+//! can be changed at any time and renderer will apply them to GPU texture. Pixel-art textures
+//! should use `TextureMagnificationFilter::Nearest` to stay sharp, distant surfaces look better
+//! with mip-mapping and anisotropic filtering. Textures loaded by resource manager get these
+//! settings from `TextureImportOptions`.
+//!
+//! ## Wrap mode
+//!
+//! Wrap mode defines what happens when texture coordinates are outside of [0; 1] range, it is
+//! set separately for S and T coordinates, see `TextureWrapMode`. Tiling textures should use
+//! `TextureWrapMode::Repeat` (default), while atlases and UI textures should use
+//! `TextureWrapMode::ClampToEdge`, otherwise filtering will bleed pixels of opposite edge.
+//!
 //! ## Render target
 //!
 //! Texture can be used as render target to render scene in it. To do this you should use
 //! new_render_target method and pass its result to scene's render target property. Renderer
 //! will automatically provide you info about metrics of texture, but it won't give you
 //! access to pixels of render target.
+//!
+//! ## Procedural textures
+//!
+//! Texture can be created from raw pixel data using `Texture::from_bytes`, this is useful for
+//! procedurally generated content (minimaps, noise textures, video frames, etc.). Pixels of such
+//! texture can be changed at any time using `Texture::modify`, renderer will upload only modified
+//! region to GPU. Procedural textures have no source file, so they're serialized together with
+//! their pixel data.
 
 use crate::{
-    core::visitor::{Visit, VisitError, VisitResult, Visitor},
+    core::{
+        algebra::Vector3,
+        math::Rect,
+        visitor::{Data, Visit, VisitError, VisitResult, Visitor},
+    },
+    renderer::QualitySettings,
     resource::{Resource, ResourceData, ResourceState},
 };
 use ddsfile::{Caps2, D3DFormat};
 use futures::io::Error;
-use image::{ColorType, DynamicImage, GenericImageView, ImageError};
+use image::{ColorType, DynamicImage, GenericImageView, ImageError, ImageFormat};
 use std::{
     borrow::Cow,
-    fs::File,
+    io::Cursor,
+    ops::Deref,
     path::{Path, PathBuf},
+    sync::MutexGuard,
 };
 
 /// Texture kind.
@@ -149,7 +191,12 @@ pub struct TextureData {
     s_wrap_mode: TextureWrapMode,
     t_wrap_mode: TextureWrapMode,
     mip_count: u32,
-    anisotropy: f32,
+    anisotropy: Option<f32>,
+    color_space: TextureColorSpace,
+    // Range of input colors of 3D lookup table loaded from .cube file.
+    lut_domain: Option<(Vector3<f32>, Vector3<f32>)>,
+    /// Region of pixels that was modified since last upload to GPU.
+    pub(in crate) modified_region: Option<Rect<u32>>,
 }
 
 impl ResourceData for TextureData {
@@ -177,11 +224,23 @@ impl Visit for TextureData {
         let _ = self
             .magnification_filter
             .visit("MagnificationFilter", visitor);
-        let _ = self.anisotropy.visit("Anisotropy", visitor);
+        if self.anisotropy.visit("AnisotropyLevel", visitor).is_err() && visitor.is_reading() {
+            // Older versions always had texture-specific level.
+            let mut anisotropy = 0.0f32;
+            if anisotropy.visit("Anisotropy", visitor).is_ok() {
+                self.anisotropy = Some(anisotropy);
+            }
+        }
         let _ = self.s_wrap_mode.visit("SWrapMode", visitor);
         let _ = self.t_wrap_mode.visit("TWrapMode", visitor);
         let _ = self.mip_count.visit("MipCount", visitor);
         let _ = self.kind.visit("Kind", visitor);
+        let _ = self.color_space.visit("ColorSpace", visitor);
+
+        // Procedural textures cannot be loaded from file, so save pixels too.
+        if self.is_procedural() {
+            let _ = Data::new(&mut self.bytes).visit("Data", visitor);
+        }
 
         visitor.leave_region()
     }
@@ -205,7 +264,10 @@ impl Default for TextureData {
             s_wrap_mode: TextureWrapMode::Repeat,
             t_wrap_mode: TextureWrapMode::Repeat,
             mip_count: 1,
-            anisotropy: 16.0,
+            anisotropy: None,
+            color_space: TextureColorSpace::Srgb,
+            lut_domain: None,
+            modified_region: None,
         }
     }
 }
@@ -232,9 +294,122 @@ impl Texture {
             s_wrap_mode: TextureWrapMode::Repeat,
             t_wrap_mode: TextureWrapMode::Repeat,
             mip_count: 1,
-            anisotropy: 1.0,
+            anisotropy: Some(1.0),
+            // Render target contains colors, they're already in linear space, but UI must
+            // know that it has to convert them to sRGB when render target is drawn on screen.
+            color_space: TextureColorSpace::Srgb,
+            lut_domain: None,
+            modified_region: None,
         }))
     }
+
+    /// Creates new 2D texture from raw pixel data. `data` must contain exactly `width * height`
+    /// pixels of given kind, otherwise `TextureError::UnsupportedFormat` is returned. Created
+    /// texture has no source file, see module docs for more info about procedural textures.
+    pub fn from_bytes(
+        width: u32,
+        height: u32,
+        pixel_kind: TexturePixelKind,
+        data: Vec<u8>,
+    ) -> Result<Self, TextureError> {
+        TextureData::from_bytes(TextureKind::Rectangle { width, height }, pixel_kind, data)
+            .map(|data| Self::new(TextureState::Ok(data)))
+            .map_err(|_| TextureError::UnsupportedFormat)
+    }
+
+    /// Locks the texture and provides access to its pixels, so they can be modified. Renderer
+    /// will upload only modified pixels to GPU, so it is cheap to modify small region of a
+    /// large texture every frame.
+    ///
+    /// # Panic
+    ///
+    /// An attempt to use method result will panic if texture is not loaded yet, or there was
+    /// load error.
+    pub fn modify(&self) -> TextureDataRefMut<'_> {
+        TextureDataRefMut {
+            guard: self.state(),
+        }
+    }
+}
+
+/// Provides mutable access to pixels of a texture, see `Texture::modify`.
+pub struct TextureDataRefMut<'a> {
+    guard: MutexGuard<'a, TextureState>,
+}
+
+impl<'a> Deref for TextureDataRefMut<'a> {
+    type Target = TextureData;
+
+    fn deref(&self) -> &Self::Target {
+        match *self.guard {
+            ResourceState::Ok(ref data) => data,
+            _ => panic!("attempt to modify texture which is not loaded!"),
+        }
+    }
+}
+
+impl<'a> TextureDataRefMut<'a> {
+    fn data_mut(&mut self) -> &mut TextureData {
+        match *self.guard {
+            ResourceState::Ok(ref mut data) => data,
+            _ => panic!("attempt to modify texture which is not loaded!"),
+        }
+    }
+
+    /// Copies given pixels into rectangular region of the texture. `pixels` must contain
+    /// `width * height` pixels of the same kind as pixels of the texture. Only uncompressed
+    /// 2D textures can be modified.
+    pub fn update_region(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Result<(), TextureError> {
+        let data = self.data_mut();
+
+        // Region bounds are checked with overflow in mind, sum of huge offset and size can
+        // wrap around and pass the check otherwise.
+        let fits = |offset: u32, size: u32, limit: u32| {
+            offset.checked_add(size).map_or(false, |end| end <= limit)
+        };
+        let texture_width = match data.kind {
+            TextureKind::Rectangle {
+                width: texture_width,
+                height: texture_height,
+            } if fits(x, width, texture_width) && fits(y, height, texture_height) => texture_width,
+            _ => return Err(TextureError::UnsupportedFormat),
+        };
+
+        let pixel_size = data
+            .pixel_kind
+            .size_in_bytes()
+            .ok_or(TextureError::UnsupportedFormat)? as usize;
+        let row_size = width as usize * pixel_size;
+        if pixels.len() != row_size * height as usize {
+            return Err(TextureError::UnsupportedFormat);
+        }
+
+        for (row, src_row) in pixels.chunks_exact(row_size.max(1)).enumerate() {
+            let begin = ((y as usize + row) * texture_width as usize + x as usize) * pixel_size;
+            data.bytes[begin..(begin + row_size)].copy_from_slice(src_row);
+        }
+
+        data.mark_modified(Rect::new(x, y, width, height));
+
+        Ok(())
+    }
+
+    /// Returns mutable reference to every pixel of the texture, whole texture will be uploaded
+    /// to GPU. Use `update_region` if only small region of the texture should be changed.
+    pub fn bytes_mut(&mut self) -> &mut [u8] {
+        let data = self.data_mut();
+        if let TextureKind::Rectangle { width, height } = data.kind {
+            data.mark_modified(Rect::new(0, 0, width, height));
+        }
+        &mut data.bytes
+    }
 }
 
 /// The texture magnification function is used when the pixel being textured maps to an area
@@ -390,6 +565,44 @@ impl Visit for TextureWrapMode {
     }
 }
 
+/// Defines how pixels of a texture should be interpreted by renderer.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+#[repr(u32)]
+pub enum TextureColorSpace {
+    /// Pixels are colors in sRGB color space (albedo, emission, etc.), they will be converted
+    /// to linear space when sampled. Only 8-bit per channel formats (including compressed)
+    /// are converted, other formats are always linear.
+    Srgb = 0,
+
+    /// Pixels are data in linear space (normal maps, roughness, lightmaps, etc.) and will be
+    /// used as is.
+    Linear = 1,
+}
+
+impl Visit for TextureColorSpace {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id = *self as u32;
+        id.visit("Id", visitor)?;
+
+        if visitor.is_reading() {
+            *self = match id {
+                0 => TextureColorSpace::Srgb,
+                1 => TextureColorSpace::Linear,
+                _ => {
+                    return VisitResult::Err(VisitError::User(format!(
+                        "Invalid color space {}!",
+                        id
+                    )))
+                }
+            }
+        }
+
+        visitor.leave_region()
+    }
+}
+
 /// Texture kind defines pixel format of texture.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(u32)]
@@ -461,6 +674,19 @@ impl TexturePixelKind {
     fn id(self) -> u32 {
         self as u32
     }
+
+    /// Returns size of a pixel in bytes, `None` for compressed formats.
+    fn size_in_bytes(self) -> Option<u32> {
+        match self {
+            Self::R8 => Some(1),
+            Self::R16 | Self::RG8 => Some(2),
+            Self::RGB8 | Self::BGR8 => Some(3),
+            Self::RGBA8 | Self::BGRA8 | Self::RG16 => Some(4),
+            Self::RGB16 => Some(6),
+            Self::RGBA16 => Some(8),
+            Self::DXT1RGB | Self::DXT1RGBA | Self::DXT3RGBA | Self::DXT5RGBA => None,
+        }
+    }
 }
 
 /// An error that may occur during texture operations.
@@ -492,12 +718,33 @@ fn ceil_div_4(x: u32) -> u32 {
 
 impl TextureData {
     pub(in crate) fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, TextureError> {
+        let bytes = std::fs::read(path.as_ref())?;
+        Self::load_from_memory(&bytes, path)
+    }
+
+    /// Decodes texture from encoded file content (dds, png, etc.) which is already in memory.
+    /// Extension of given path is used as a hint for the format, path itself will be used as
+    /// path of the texture.
+    pub(in crate) fn load_from_memory<P: AsRef<Path>>(
+        bytes: &[u8],
+        path: P,
+    ) -> Result<Self, TextureError> {
+        if path
+            .as_ref()
+            .extension()
+            .map_or(false, |ext| ext.eq_ignore_ascii_case("cube"))
+        {
+            let mut data = Self::parse_cube_lut(bytes)?;
+            data.path = path.as_ref().to_path_buf();
+            return Ok(data);
+        }
+
         // DDS is special. It can contain various kinds of textures as well as textures with
         // various pixel formats.
         //
         // TODO: Add support for DXGI formats. This could be difficult because of mismatch
         // between OpenGL and DirectX formats.
-        if let Ok(dds) = ddsfile::Dds::read(&mut File::open(path.as_ref())?) {
+        if let Ok(dds) = ddsfile::Dds::read(&mut Cursor::new(bytes)) {
             let d3dformat = dds
                 .get_d3d_format()
                 .ok_or(TextureError::UnsupportedFormat)?;
@@ -575,12 +822,19 @@ impl TextureData {
                         height: dds.header.height,
                     }
                 },
-                anisotropy: 1.0,
+                anisotropy: None,
+                color_space: TextureColorSpace::Srgb,
+                lut_domain: None,
+                modified_region: None,
             })
         } else {
             // Commonly used formats are all rectangle textures.
 
-            let dyn_img = image::open(path.as_ref())?;
+            // Some formats (tga for example) cannot be detected by content.
+            let dyn_img = match ImageFormat::from_path(path.as_ref()) {
+                Ok(format) => image::load_from_memory_with_format(bytes, format)?,
+                Err(_) => image::load_from_memory(bytes)?,
+            };
 
             let width = dyn_img.width();
             let height = dyn_img.height();
@@ -608,6 +862,104 @@ impl TextureData {
         }
     }
 
+    /// Parses 3D lookup table in Adobe .cube format. Red component changes fastest, so the
+    /// table maps directly to volume texture layout. Domain defines range of input colors, it
+    /// does not affect values of the table.
+    fn parse_cube_lut(bytes: &[u8]) -> Result<Self, TextureError> {
+        fn invalid_data(message: String) -> TextureError {
+            TextureError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                message,
+            ))
+        }
+
+        fn parse_triple<'a>(
+            mut tokens: impl Iterator<Item = &'a str>,
+        ) -> Result<[f32; 3], TextureError> {
+            let mut triple = [0.0; 3];
+            for component in triple.iter_mut() {
+                *component = tokens
+                    .next()
+                    .and_then(|t| t.parse().ok())
+                    .ok_or_else(|| invalid_data("Invalid .cube triple!".to_owned()))?;
+            }
+            Ok(triple)
+        }
+
+        let text = std::str::from_utf8(bytes).map_err(|e| invalid_data(e.to_string()))?;
+
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut table = Vec::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("TITLE") => (),
+                Some("LUT_3D_SIZE") => {
+                    size = Some(
+                        tokens
+                            .next()
+                            .and_then(|t| t.parse::<u32>().ok())
+                            .filter(|&size| size >= 2)
+                            .ok_or_else(|| invalid_data("Invalid LUT_3D_SIZE!".to_owned()))?,
+                    );
+                }
+                Some("DOMAIN_MIN") => domain_min = parse_triple(tokens)?,
+                Some("DOMAIN_MAX") => domain_max = parse_triple(tokens)?,
+                // 1D tables cannot be represented as volume texture.
+                Some("LUT_1D_SIZE") => return Err(TextureError::UnsupportedFormat),
+                Some(_) => table.push(parse_triple(line.split_whitespace())?),
+                None => (),
+            }
+        }
+
+        let size = size.ok_or_else(|| invalid_data("Missing LUT_3D_SIZE!".to_owned()))?;
+        if table.len() != (size * size * size) as usize {
+            return Err(invalid_data(format!(
+                "Expected {} entries in .cube table, got {}!",
+                size * size * size,
+                table.len()
+            )));
+        }
+
+        if (0..3).any(|i| domain_max[i] <= domain_min[i]) {
+            return Err(invalid_data(
+                "DOMAIN_MAX must be greater than DOMAIN_MIN!".to_owned(),
+            ));
+        }
+
+        let mut bytes = Vec::with_capacity(table.len() * 6);
+        for entry in table {
+            for component in entry.iter() {
+                let value = (component.max(0.0).min(1.0) * 65535.0).round() as u16;
+                bytes.extend_from_slice(&value.to_ne_bytes());
+            }
+        }
+
+        Ok(Self {
+            kind: TextureKind::Volume {
+                width: size,
+                height: size,
+                depth: size,
+            },
+            bytes,
+            pixel_kind: TexturePixelKind::RGB16,
+            minification_filter: TextureMinificationFilter::Linear,
+            magnification_filter: TextureMagnificationFilter::Linear,
+            s_wrap_mode: TextureWrapMode::ClampToEdge,
+            t_wrap_mode: TextureWrapMode::ClampToEdge,
+            anisotropy: Some(1.0),
+            color_space: TextureColorSpace::Linear,
+            lut_domain: Some((Vector3::from(domain_min), Vector3::from(domain_max))),
+            ..Default::default()
+        })
+    }
+
     /// Creates new texture instance from given parameters.
     pub fn from_bytes(
         kind: TextureKind,
@@ -724,17 +1076,69 @@ impl TextureData {
         self.kind
     }
 
-    /// Max samples for anisotropic filtering. Default value is 16.0 (max).
-    /// However real value passed to GPU will be clamped to maximum supported
-    /// by current GPU. To disable anisotropic filtering set this to 1.0.
+    /// Max samples for anisotropic filtering. By default texture has no level of its own and
+    /// uses global level from quality settings (see `QualitySettings::anisotropy`), this method
+    /// overrides it. Real value passed to GPU will be clamped to maximum supported by current
+    /// GPU, see `Renderer::max_anisotropy`. To disable anisotropic filtering set this to 1.0.
     /// Typical values are 2.0, 4.0, 8.0, 16.0.
     pub fn set_anisotropy_level(&mut self, anisotropy: f32) {
-        self.anisotropy = anisotropy.max(1.0);
+        self.anisotropy = Some(anisotropy.max(1.0));
     }
 
-    /// Returns current anisotropy level.
+    /// Removes texture-specific anisotropy level, so global level from quality settings will
+    /// be used.
+    pub fn reset_anisotropy_level(&mut self) {
+        self.anisotropy = None;
+    }
+
+    /// Returns anisotropy level of the texture. If the texture has no level of its own, level
+    /// of default quality settings is returned, use `custom_anisotropy_level` to distinguish
+    /// these cases.
     pub fn anisotropy_level(&self) -> f32 {
         self.anisotropy
+            .unwrap_or_else(|| QualitySettings::default().anisotropy)
+    }
+
+    /// Returns texture-specific anisotropy level, `None` means that global level from quality
+    /// settings is used.
+    pub fn custom_anisotropy_level(&self) -> Option<f32> {
+        self.anisotropy
+    }
+
+    /// Sets new color space of pixels. It must be set before the texture is used for rendering
+    /// for the first time, renderer won't re-upload the texture when color space changes.
+    pub fn set_color_space(&mut self, color_space: TextureColorSpace) {
+        self.color_space = color_space;
+    }
+
+    /// Returns current color space of pixels.
+    pub fn color_space(&self) -> TextureColorSpace {
+        self.color_space
+    }
+
+    /// Returns range (min, max) of input colors of 3D lookup table, it is defined by
+    /// `DOMAIN_MIN` and `DOMAIN_MAX` of .cube file. `None` for every other texture, which
+    /// means 0..1 range.
+    pub fn lut_domain(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        self.lut_domain
+    }
+
+    /// Returns true if the texture was created from raw pixel data and has no source file.
+    pub fn is_procedural(&self) -> bool {
+        self.path.as_os_str().is_empty()
+    }
+
+    fn mark_modified(&mut self, region: Rect<u32>) {
+        self.modified_region = Some(match self.modified_region {
+            Some(modified) => {
+                let left = modified.x().min(region.x());
+                let top = modified.y().min(region.y());
+                let right = (modified.x() + modified.w()).max(region.x() + region.w());
+                let bottom = (modified.y() + modified.h()).max(region.y() + region.h());
+                Rect::new(left, top, right - left, bottom - top)
+            }
+            None => region,
+        });
     }
 
     /// Sets new path to source file.
@@ -773,3 +1177,127 @@ impl TextureData {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            algebra::Vector3,
+            math::Rect,
+            visitor::{Visit, Visitor},
+        },
+        renderer::QualitySettings,
+        resource::texture::{Texture, TextureData, TextureKind, TexturePixelKind},
+    };
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_from_bytes_invalid_size() {
+        assert!(Texture::from_bytes(2, 2, TexturePixelKind::RGBA8, vec![0; 15]).is_err());
+    }
+
+    #[test]
+    fn test_update_region() {
+        let texture = Texture::from_bytes(4, 4, TexturePixelKind::R8, vec![0; 16]).unwrap();
+
+        let mut data = texture.modify();
+        data.update_region(1, 1, 2, 2, &[1, 2, 3, 4]).unwrap();
+        data.update_region(3, 3, 1, 1, &[5]).unwrap();
+        assert!(data.update_region(3, 3, 2, 1, &[6, 7]).is_err());
+        // Sum of offset and size overflows.
+        assert!(data.update_region(u32::MAX, 0, 2, 1, &[6, 7]).is_err());
+        assert!(data.update_region(0, 2, 1, u32::MAX, &[]).is_err());
+
+        assert_eq!(
+            data.bytes,
+            vec![0, 0, 0, 0, 0, 1, 2, 0, 0, 3, 4, 0, 0, 0, 0, 5]
+        );
+        // Modified regions are merged into one.
+        assert_eq!(data.modified_region, Some(Rect::new(1, 1, 3, 3)));
+        assert!(data.is_procedural());
+    }
+
+    #[test]
+    fn test_anisotropy_level() {
+        let mut data = TextureData::default();
+        assert_eq!(data.custom_anisotropy_level(), None);
+        assert_eq!(
+            data.anisotropy_level(),
+            QualitySettings::default().anisotropy
+        );
+
+        data.set_anisotropy_level(0.5);
+        assert_eq!(data.custom_anisotropy_level(), Some(1.0));
+        assert_eq!(data.anisotropy_level(), 1.0);
+
+        data.reset_anisotropy_level();
+        assert_eq!(data.custom_anisotropy_level(), None);
+    }
+
+    #[test]
+    fn test_visit_old_anisotropy() {
+        // Older versions saved anisotropy as plain number under different name.
+        let mut visitor = Visitor::new();
+        visitor.enter_region("Texture").unwrap();
+        let mut kind = TexturePixelKind::RGBA8.id();
+        kind.visit("KindId", &mut visitor).unwrap();
+        let mut path = PathBuf::from("test.png");
+        path.visit("Path", &mut visitor).unwrap();
+        let mut anisotropy = 4.0f32;
+        anisotropy.visit("Anisotropy", &mut visitor).unwrap();
+        visitor.leave_region().unwrap();
+
+        let path = std::env::temp_dir().join("rg3d_old_anisotropy_test.bin");
+        visitor.save_binary(&path).unwrap();
+        let mut visitor = Visitor::load_binary(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let mut data = TextureData::default();
+        data.visit("Texture", &mut visitor).unwrap();
+        assert_eq!(data.custom_anisotropy_level(), Some(4.0));
+    }
+
+    #[test]
+    fn test_load_cube_lut() {
+        let cube = "# Comment\n\
+            TITLE \"Test\"\n\
+            LUT_3D_SIZE 2\n\
+            DOMAIN_MIN 0 0 0\n\
+            DOMAIN_MAX 2 2 2\n\
+            0 0 0\n1 0 0\n0 1 0\n1 1 0\n\
+            0 0 1\n1 0 1\n0 1 1\n0.5 0.5 0.5\n";
+        let data = TextureData::load_from_memory(cube.as_bytes(), "test.cube").unwrap();
+        assert!(matches!(
+            data.kind,
+            TextureKind::Volume {
+                width: 2,
+                height: 2,
+                depth: 2
+            }
+        ));
+        assert_eq!(data.pixel_kind, TexturePixelKind::RGB16);
+        assert_eq!(data.bytes.len(), 8 * 6);
+        let pixel = |i: usize| {
+            let mut rgb = [0u16; 3];
+            for (k, c) in rgb.iter_mut().enumerate() {
+                let offset = i * 6 + k * 2;
+                *c = u16::from_ne_bytes([data.bytes[offset], data.bytes[offset + 1]]);
+            }
+            rgb
+        };
+        // Domain does not affect values of the table.
+        assert_eq!(pixel(1), [65535, 0, 0]);
+        assert_eq!(pixel(7), [32768, 32768, 32768]);
+        assert_eq!(
+            data.lut_domain(),
+            Some((Vector3::new(0.0, 0.0, 0.0), Vector3::new(2.0, 2.0, 2.0)))
+        );
+
+        let empty_domain = "LUT_3D_SIZE 2\nDOMAIN_MIN 0 1 0\nDOMAIN_MAX 1 1 1\n\
+            0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+        assert!(TextureData::load_from_memory(empty_domain.as_bytes(), "test.cube").is_err());
+
+        let truncated = "LUT_3D_SIZE 2\n0 0 0\n";
+        assert!(TextureData::load_from_memory(truncated.as_bytes(), "test.cube").is_err());
+    }
+}
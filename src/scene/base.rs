@@ -10,6 +10,7 @@ use crate::scene::graph::Graph;
 use crate::{
     core::{
         algebra::{Matrix4, Vector3},
+        color::Color,
         math::Matrix4Ext,
         pool::Handle,
         visitor::{Visit, VisitError, VisitResult, Visitor},
@@ -17,7 +18,10 @@ use crate::{
     resource::model::Model,
     scene::{node::Node, transform::Transform},
 };
-use std::cell::Cell;
+use std::{
+    cell::Cell,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 /// Level of detail is a collection of objects for given normalized distance range.
 /// Objects will be rendered **only** if they're in specified range.
@@ -180,6 +184,13 @@ impl Visit for Mobility {
     }
 }
 
+/// Source of unique node versions, see [`Base::version`].
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(1);
+
+fn next_version() -> u64 {
+    NEXT_VERSION.fetch_add(1, Ordering::Relaxed)
+}
+
 /// See module docs.
 #[derive(Debug)]
 pub struct Base {
@@ -190,6 +201,12 @@ pub struct Base {
     pub(in crate) parent: Handle<Node>,
     pub(in crate) children: Vec<Handle<Node>>,
     pub(in crate) global_transform: Cell<Matrix4<f32>>,
+    /// Indicates that global transform and global visibility of the node and its descendants
+    /// must be recalculated on next update of hierarchical data. Changes of local transform
+    /// are tracked by transform itself.
+    pub(in crate) hierarchy_dirty: Cell<bool>,
+    /// Unique stamp of the node state that affects rendering, non-serializable.
+    version: Cell<u64>,
     /// Bone-specific matrix. Non-serializable.
     pub(in crate) inv_bind_pose_transform: Matrix4<f32>,
     /// A resource from which this node was instantiated from, can work in pair
@@ -208,6 +225,8 @@ pub struct Base {
     depth_offset: f32,
     lod_group: Option<LodGroup>,
     mobility: Mobility,
+    render_priority: i8,
+    outline: Option<Color>,
 }
 
 impl Base {
@@ -237,6 +256,7 @@ impl Base {
     /// Sets new local transform of a node.
     pub fn set_local_transform(&mut self, transform: Transform) -> &mut Self {
         self.local_transform = transform;
+        self.hierarchy_dirty.set(true);
         self
     }
 
@@ -274,6 +294,13 @@ impl Base {
     /// Returns global transform matrix, such matrix contains combined transformation
     /// of transforms of parent nodes. This is the final matrix that describes real
     /// location of object in the world.
+    ///
+    /// Global transform is calculated in [`Graph::update_hierarchical_data`], which is called
+    /// once per frame by the engine. Changes of local transform or hierarchy made after the
+    /// update will be reflected only after next update, until then this method returns the
+    /// value consistent with last update.
+    ///
+    /// [`Graph::update_hierarchical_data`]: crate::scene::graph::Graph::update_hierarchical_data
     pub fn global_transform(&self) -> Matrix4<f32> {
         self.global_transform.get()
     }
@@ -297,7 +324,10 @@ impl Base {
 
     /// Sets local visibility of a node.
     pub fn set_visibility(&mut self, visibility: bool) -> &mut Self {
-        self.visibility = visibility;
+        if self.visibility != visibility {
+            self.visibility = visibility;
+            self.hierarchy_dirty.set(true);
+        }
         self
     }
 
@@ -346,6 +376,71 @@ impl Base {
         self.global_transform.get().up()
     }
 
+    /// Rotates node so its global "look" vector will be collinear with given direction,
+    /// `up` defines rotation around look vector. Both vectors must be in world coordinates,
+    /// they will be converted to coordinate system of parent and passed to
+    /// [`Transform::look_at`], so pre- and post-rotation of local transform are compensated.
+    ///
+    /// Orientation of parent is extracted from global transform calculated on last update
+    /// of graph, so do not change local rotation or scale between the update and this call
+    /// (changing position is fine). Global vectors of the node will be changed on next
+    /// update of graph.
+    ///
+    /// # Example
+    ///
+    /// Homing missile which turns towards its target and flies forward:
+    ///
+    /// ```
+    /// use rg3d::{
+    ///     core::{algebra::Vector3, pool::Handle},
+    ///     scene::{base::BaseBuilder, graph::Graph, node::Node},
+    /// };
+    ///
+    /// fn update_missile(graph: &mut Graph, missile: Handle<Node>, target: Handle<Node>, dt: f32) {
+    ///     let speed = 10.0;
+    ///     let to_target = graph.global_position(target) - graph.global_position(missile);
+    ///     if let Some(direction) = to_target.try_normalize(std::f32::EPSILON) {
+    ///         let missile = &mut graph[missile];
+    ///         missile.set_look_vector(direction, Vector3::y());
+    ///         missile.local_transform_mut().offset(direction.scale(speed * dt));
+    ///     }
+    /// }
+    ///
+    /// let mut graph = Graph::new();
+    /// let missile = BaseBuilder::new().build(&mut graph);
+    /// let target = BaseBuilder::new().build(&mut graph);
+    /// graph[target]
+    ///     .local_transform_mut()
+    ///     .set_position(Vector3::new(100.0, 0.0, 0.0));
+    /// graph.update_hierarchical_data();
+    ///
+    /// update_missile(&mut graph, missile, target, 0.1);
+    /// graph.update_hierarchical_data();
+    ///
+    /// assert!((graph[missile].look_vector() - Vector3::x()).norm() < 0.001);
+    /// assert!((graph.global_position(missile) - Vector3::x()).norm() < 0.001);
+    /// ```
+    pub fn set_look_vector(&mut self, look: Vector3<f32>, up: Vector3<f32>) -> &mut Self {
+        // Global transform is a product of global transform of parent and local transform,
+        // so transform of parent can be extracted without access to graph. Translation is
+        // ignored, because only directions are converted.
+        let parent_global = self.global_transform.get()
+            * self
+                .local_transform
+                .matrix()
+                .try_inverse()
+                .unwrap_or_else(Matrix4::identity);
+        let inv_parent_global = parent_global
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity);
+        let local_look = inv_parent_global.transform_vector(&look);
+        let local_up = inv_parent_global.transform_vector(&up);
+        let position = self.local_transform.position();
+        self.local_transform
+            .look_at(position + local_look, local_up);
+        self
+    }
+
     /// Sets depth range offset factor. It allows you to move depth range by given
     /// value. This can be used to draw weapons on top of other stuff in scene.
     ///
@@ -357,6 +452,7 @@ impl Base {
     /// abuse this to shift z of fragment by some value.
     pub fn set_depth_offset_factor(&mut self, factor: f32) {
         self.depth_offset = factor.abs().min(1.0).max(0.0);
+        self.invalidate_version();
     }
 
     /// Returns depth offset factor.
@@ -364,6 +460,35 @@ impl Base {
         self.depth_offset
     }
 
+    /// Sets render priority of the node. Transparent drawables (sprites, particle systems) are
+    /// drawn in groups by priority - lowest first, within a group they are sorted by distance
+    /// to camera (back to front). Nodes with higher priority are drawn on top of nodes with
+    /// lower priority, this can be used for example for glow of first-person weapon. Default
+    /// priority is 0.
+    pub fn set_render_priority(&mut self, priority: i8) -> &mut Self {
+        self.render_priority = priority;
+        self
+    }
+
+    /// Returns render priority of the node, see [`set_render_priority`](Self::set_render_priority).
+    pub fn render_priority(&self) -> i8 {
+        self.render_priority
+    }
+
+    /// Sets color of outline which is drawn around silhouette of the node, `None` disables
+    /// outline. Only meshes can be outlined, thickness of outlines is set by
+    /// `Renderer::set_outline_thickness`. Outlines are drawn on top of other geometry, so
+    /// they are useful to highlight selected or interactive objects.
+    pub fn set_outline(&mut self, outline: Option<Color>) -> &mut Self {
+        self.outline = outline;
+        self
+    }
+
+    /// Returns color of outline of the node, see [`set_outline`](Self::set_outline).
+    pub fn outline(&self) -> Option<Color> {
+        self.outline
+    }
+
     /// Sets new lod group.
     pub fn set_lod_group(&mut self, lod_group: LodGroup) -> Option<LodGroup> {
         self.lod_group.replace(lod_group)
@@ -379,6 +504,19 @@ impl Base {
         self.lod_group.as_mut()
     }
 
+    /// Returns current version of the node. Version is unique across all nodes and it changes
+    /// every time when global transform, global visibility or any data used for rendering of
+    /// the node (surfaces, materials, etc.) is changed. It can be used as a key for caches
+    /// of derived data - if version is the same, cached data is still valid.
+    pub fn version(&self) -> u64 {
+        self.version.get()
+    }
+
+    /// Assigns new version to the node, see [`version`](Self::version).
+    pub(in crate) fn invalidate_version(&self) {
+        self.version.set(next_version());
+    }
+
     /// Shallow copy of node data. You should never use this directly, shallow copy
     /// will produce invalid node in most cases!
     pub fn raw_copy(&self) -> Self {
@@ -393,6 +531,8 @@ impl Base {
             is_resource_instance: self.is_resource_instance,
             lifetime: self.lifetime,
             mobility: self.mobility,
+            render_priority: self.render_priority,
+            outline: self.outline,
             // Rest of data is *not* copied!
             ..Default::default()
         }
@@ -421,6 +561,8 @@ impl Visit for Base {
         self.depth_offset.visit("DepthOffset", visitor)?;
         let _ = self.lod_group.visit("LodGroup", visitor);
         let _ = self.mobility.visit("Mobility", visitor);
+        let _ = self.render_priority.visit("RenderPriority", visitor);
+        let _ = self.outline.visit("Outline", visitor);
 
         visitor.leave_region()
     }
@@ -437,6 +579,8 @@ pub struct BaseBuilder {
     lod_group: Option<LodGroup>,
     mobility: Mobility,
     inv_bind_pose_transform: Matrix4<f32>,
+    render_priority: i8,
+    outline: Option<Color>,
 }
 
 impl Default for BaseBuilder {
@@ -458,6 +602,8 @@ impl BaseBuilder {
             lod_group: None,
             mobility: Mobility::Dynamic,
             inv_bind_pose_transform: Matrix4::identity(),
+            render_priority: 0,
+            outline: None,
         }
     }
 
@@ -522,6 +668,18 @@ impl BaseBuilder {
         self
     }
 
+    /// Sets desired render priority, see [`Base::set_render_priority`].
+    pub fn with_render_priority(mut self, priority: i8) -> Self {
+        self.render_priority = priority;
+        self
+    }
+
+    /// Sets desired outline color, see [`Base::set_outline`].
+    pub fn with_outline(mut self, outline: Color) -> Self {
+        self.outline = Some(outline);
+        self
+    }
+
     pub(in crate) fn build_base(self) -> Base {
         Base {
             name: self.name,
@@ -532,6 +690,8 @@ impl BaseBuilder {
             global_visibility: Cell::new(true),
             parent: Handle::NONE,
             global_transform: Cell::new(Matrix4::identity()),
+            hierarchy_dirty: Cell::new(true),
+            version: Cell::new(next_version()),
             inv_bind_pose_transform: self.inv_bind_pose_transform,
             resource: None,
             original: Handle::NONE,
@@ -539,6 +699,8 @@ impl BaseBuilder {
             depth_offset: self.depth_offset,
             lod_group: self.lod_group,
             mobility: self.mobility,
+            render_priority: self.render_priority,
+            outline: self.outline,
         }
     }
 
@@ -552,3 +714,35 @@ impl BaseBuilder {
         graph.add_node(self.build_node())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            color::Color,
+            visitor::{Visit, Visitor},
+        },
+        scene::base::{Base, BaseBuilder},
+    };
+
+    #[test]
+    fn test_outline_visit_and_copy() {
+        let outline = Color::opaque(255, 100, 0);
+        let mut base = BaseBuilder::new().with_outline(outline).build_base();
+        assert_eq!(base.raw_copy().outline(), Some(outline));
+
+        let path = std::env::temp_dir().join("rg3d_base_outline_test.bin");
+        let mut visitor = Visitor::new();
+        base.visit("Base", &mut visitor).unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        let mut visitor = Visitor::load_binary(&path).unwrap();
+        let mut loaded = Base::default();
+        loaded.visit("Base", &mut visitor).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(loaded.outline(), Some(outline));
+
+        base.set_outline(None);
+        assert_eq!(base.raw_copy().outline(), None);
+    }
+}
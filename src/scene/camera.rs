@@ -13,6 +13,21 @@
 //!
 //! Each camera forces engine to re-render same scene one more time, which may cause
 //! almost double load of your GPU.
+//!
+//! # Occlusion culling
+//!
+//! Camera can skip rendering of meshes hidden behind other geometry (walls of indoor levels
+//! for example), see `Camera::set_occlusion_culling`. Visibility of each mesh is checked by
+//! hardware occlusion query of its bounding box, result of the query is used on next frame,
+//! so occlusion culling has one frame latency. When camera moves or turns too fast (camera
+//! cut) every mesh is treated as visible for a frame. Amount of culled meshes is reported in
+//! renderer statistics. Occlusion culling can be turned off for all cameras at once by
+//! `Renderer::set_occlusion_culling`.
+//!
+//! # Post effects
+//!
+//! Each camera has its own set of screen-space post effects (vignette, chromatic aberration,
+//! film grain), see `post_effects` module docs.
 
 use crate::core::algebra::{Matrix4, Vector2, Vector3, Vector4};
 use crate::core::pool::Handle;
@@ -26,6 +41,7 @@ use crate::{
     scene::{
         base::{Base, BaseBuilder},
         node::Node,
+        post_effects::PostEffects,
         VisibilityCache,
     },
 };
@@ -45,6 +61,8 @@ pub struct Camera {
     enabled: bool,
     skybox: Option<SkyBox>,
     environment: Option<Texture>,
+    occlusion_culling: bool,
+    post_effects: PostEffects,
     /// Visibility cache allows you to quickly check if object is visible from the camera or not.
     pub visibility_cache: VisibilityCache,
 }
@@ -80,6 +98,8 @@ impl Visit for Camera {
         self.enabled.visit("Enabled", visitor)?;
         let _ = self.skybox.visit("SkyBox", visitor);
         let _ = self.environment.visit("Environment", visitor);
+        let _ = self.occlusion_culling.visit("OcclusionCulling", visitor);
+        let _ = self.post_effects.visit("PostEffects", visitor);
         // self.visibility_cache intentionally not serialized. It is valid only for one frame.
         visitor.leave_region()
     }
@@ -97,9 +117,12 @@ impl Camera {
         self.view_matrix = Matrix4::look_at_rh(&Point3::from(pos), &Point3::from(pos + look), &up);
 
         let viewport = self.viewport_pixels(frame_size);
-        let aspect = viewport.w() as f32 / viewport.h() as f32;
-        self.projection_matrix =
-            Matrix4::new_perspective(aspect, self.fov, self.z_near, self.z_far);
+        // Keep previous projection if viewport is degenerated (minimized window for example).
+        if viewport.w() > 0 && viewport.h() > 0 {
+            let aspect = viewport.w() as f32 / viewport.h() as f32;
+            self.projection_matrix =
+                Matrix4::new_perspective(aspect, self.fov, self.z_near, self.z_far);
+        }
     }
 
     /// Sets new viewport in resolution-independent format. In other words
@@ -204,6 +227,38 @@ impl Camera {
         self
     }
 
+    /// Enables or disables occlusion culling for the camera, it is disabled by default. It is
+    /// useful for indoor levels with lots of geometry hidden behind walls, but it could make
+    /// things slower for open spaces, because of additional draw call per mesh. See module
+    /// docs for more info.
+    #[inline]
+    pub fn set_occlusion_culling(&mut self, enabled: bool) -> &mut Self {
+        self.occlusion_culling = enabled;
+        self
+    }
+
+    /// Returns true if occlusion culling is enabled for the camera.
+    #[inline]
+    pub fn is_occlusion_culling_enabled(&self) -> bool {
+        self.occlusion_culling
+    }
+
+    /// Sets new set of post effects, see `post_effects` module docs.
+    pub fn set_post_effects(&mut self, post_effects: PostEffects) -> &mut Self {
+        self.post_effects = post_effects;
+        self
+    }
+
+    /// Returns current set of post effects.
+    pub fn post_effects(&self) -> &PostEffects {
+        &self.post_effects
+    }
+
+    /// Returns current set of post effects, could be used to change parameters of effects.
+    pub fn post_effects_mut(&mut self) -> &mut PostEffects {
+        &mut self.post_effects
+    }
+
     /// Sets new skybox. Could be None if no skybox needed.
     pub fn set_skybox(&mut self, skybox: Option<SkyBox>) -> &mut Self {
         self.skybox = skybox;
@@ -290,6 +345,8 @@ impl Camera {
             enabled: self.enabled,
             skybox: self.skybox.clone(),
             environment: self.environment.clone(),
+            occlusion_culling: self.occlusion_culling,
+            post_effects: self.post_effects,
             // No need to copy cache. It is valid only for one frame.
             visibility_cache: Default::default(),
         }
@@ -307,6 +364,8 @@ pub struct CameraBuilder {
     enabled: bool,
     skybox: Option<SkyBox>,
     environment: Option<Texture>,
+    occlusion_culling: bool,
+    post_effects: PostEffects,
 }
 
 impl CameraBuilder {
@@ -321,6 +380,8 @@ impl CameraBuilder {
             viewport: Rect::new(0.0, 0.0, 1.0, 1.0),
             skybox: None,
             environment: None,
+            occlusion_culling: false,
+            post_effects: Default::default(),
         }
     }
 
@@ -366,6 +427,18 @@ impl CameraBuilder {
         self
     }
 
+    /// Sets whether occlusion culling should be used or not.
+    pub fn with_occlusion_culling(mut self, enabled: bool) -> Self {
+        self.occlusion_culling = enabled;
+        self
+    }
+
+    /// Sets desired set of post effects.
+    pub fn with_post_effects(mut self, post_effects: PostEffects) -> Self {
+        self.post_effects = post_effects;
+        self
+    }
+
     /// Creates new instance of camera.
     pub fn build_camera(self) -> Camera {
         Camera {
@@ -382,6 +455,8 @@ impl CameraBuilder {
             visibility_cache: Default::default(),
             skybox: self.skybox,
             environment: self.environment,
+            occlusion_culling: self.occlusion_culling,
+            post_effects: self.post_effects,
         }
     }
 
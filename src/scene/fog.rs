@@ -0,0 +1,204 @@
+//! Distance fog.
+//!
+//! Fog blends color of every pixel of a scene with fog color depending on distance from
+//! camera to a point of geometry visible in the pixel. There are three modes of fog:
+//!
+//! - Linear - fog starts at `start` distance and reaches its full strength at `end` distance.
+//! - Exponential - strength of fog is `1 - e^(-density * distance)`.
+//! - Exponential squared - strength of fog is `1 - e^(-(density * distance)^2)`, it has
+//!   sharper transition between clear and foggy areas.
+//!
+//! Fog is applied to everything that is rendered in the scene: opaque and transparent surfaces,
+//! sprites and particle systems.
+//!
+//! Optionally fog can depend on height, this is useful to make fog that lies in lowlands
+//! with clear sky above. Height fog has full strength below `base_height` and fades out
+//! exponentially above it.
+//!
+//! # Example
+//!
+//! ```
+//! use rg3d::{
+//!     core::color::Color,
+//!     scene::{
+//!         fog::{FogMode, FogSettings, HeightFog},
+//!         Scene,
+//!     },
+//! };
+//!
+//! let mut scene = Scene::new();
+//!
+//! scene.set_fog(
+//!     FogSettings::new(FogMode::Exponential { density: 0.05 })
+//!         .with_color(Color::opaque(180, 190, 200))
+//!         .with_height_fog(HeightFog {
+//!             base_height: 0.0,
+//!             falloff: 0.5,
+//!         }),
+//! );
+//! ```
+
+use crate::core::{
+    color::Color,
+    visitor::{Visit, VisitResult, Visitor},
+};
+
+/// Defines how strength of fog depends on distance.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FogMode {
+    /// Fog strength grows linearly from zero at `start` distance to full at `end` distance.
+    Linear {
+        /// Distance at which fog starts.
+        start: f32,
+        /// Distance at which fog reaches its full strength.
+        end: f32,
+    },
+    /// Fog strength is `1 - e^(-density * distance)`.
+    Exponential {
+        /// Density of fog, larger values makes fog thicker.
+        density: f32,
+    },
+    /// Fog strength is `1 - e^(-(density * distance)^2)`.
+    ExponentialSquared {
+        /// Density of fog, larger values makes fog thicker.
+        density: f32,
+    },
+}
+
+impl Default for FogMode {
+    fn default() -> Self {
+        Self::Linear {
+            start: 10.0,
+            end: 100.0,
+        }
+    }
+}
+
+impl FogMode {
+    /// Returns internal id of the mode, it is used by renderer and for serialization.
+    pub fn id(&self) -> u32 {
+        match self {
+            FogMode::Linear { .. } => 0,
+            FogMode::Exponential { .. } => 1,
+            FogMode::ExponentialSquared { .. } => 2,
+        }
+    }
+
+    /// Creates mode with default parameters from given id.
+    pub fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(Self::default()),
+            1 => Ok(Self::Exponential { density: 0.05 }),
+            2 => Ok(Self::ExponentialSquared { density: 0.05 }),
+            _ => Err(format!("Invalid fog mode id {}!", id)),
+        }
+    }
+}
+
+impl Visit for FogMode {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id = self.id();
+        id.visit("Id", visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+        match self {
+            FogMode::Linear { start, end } => {
+                start.visit("Start", visitor)?;
+                end.visit("End", visitor)?;
+            }
+            FogMode::Exponential { density } | FogMode::ExponentialSquared { density } => {
+                density.visit("Density", visitor)?;
+            }
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// Height dependency of fog.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HeightFog {
+    /// Height (in world coordinates) below which fog has full strength.
+    pub base_height: f32,
+    /// Defines how fast fog fades out above base height, larger values gives thinner
+    /// layer of fog.
+    pub falloff: f32,
+}
+
+impl Default for HeightFog {
+    fn default() -> Self {
+        Self {
+            base_height: 0.0,
+            falloff: 1.0,
+        }
+    }
+}
+
+impl Visit for HeightFog {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.base_height.visit("BaseHeight", visitor)?;
+        self.falloff.visit("Falloff", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// See module docs.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FogSettings {
+    /// Color of fog. Alpha channel defines maximum strength of fog.
+    pub color: Color,
+    /// Dependency of fog strength on distance.
+    pub mode: FogMode,
+    /// Optional dependency of fog strength on height.
+    pub height_fog: Option<HeightFog>,
+}
+
+impl Default for FogSettings {
+    fn default() -> Self {
+        Self {
+            color: Color::opaque(128, 128, 128),
+            mode: Default::default(),
+            height_fog: None,
+        }
+    }
+}
+
+impl FogSettings {
+    /// Creates new fog settings with given mode and default gray color.
+    pub fn new(mode: FogMode) -> Self {
+        Self {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    /// Sets desired color of fog.
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Makes fog height dependent.
+    pub fn with_height_fog(mut self, height_fog: HeightFog) -> Self {
+        self.height_fog = Some(height_fog);
+        self
+    }
+}
+
+impl Visit for FogSettings {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.color.visit("Color", visitor)?;
+        self.mode.visit("Mode", visitor)?;
+        self.height_fog.visit("HeightFog", visitor)?;
+
+        visitor.leave_region()
+    }
+}
@@ -22,13 +22,14 @@
 //! just by linking nodes to each other. Good example of this is skeleton which
 //! is used in skinning (animating 3d model by set of bones).
 
-use crate::core::algebra::{Matrix4, UnitQuaternion, Vector2, Vector3};
+use crate::core::algebra::{Matrix3, Matrix4, UnitQuaternion, Vector2, Vector3};
 use crate::core::math::Matrix4Ext;
-use crate::scene::transform::TransformBuilder;
+use crate::scene::transform::{Transform, TransformBuilder};
 use crate::utils::log::MessageKind;
 use crate::{
     core::{
-        math::frustum::Frustum,
+        math::{aabb::AxisAlignedBoundingBox, frustum::Frustum, ray::Ray},
+        octree::Octree,
         pool::{
             Handle, Pool, PoolIterator, PoolIteratorMut, PoolPairIterator, PoolPairIteratorMut,
             Ticket,
@@ -40,7 +41,9 @@ use crate::{
     utils::log::Log,
 };
 use rapier3d::na::Rotation3;
+use rayon::prelude::*;
 use std::{
+    cmp::Ordering,
     collections::HashMap,
     ops::{Index, IndexMut},
 };
@@ -51,6 +54,38 @@ pub struct Graph {
     root: Handle<Node>,
     pool: Pool<Node>,
     stack: Vec<Handle<Node>>,
+    parallel_update_threshold: usize,
+    spatial_index: SpatialIndex,
+}
+
+/// Default amount of nodes starting from which hierarchical data is updated in parallel.
+pub const DEFAULT_PARALLEL_UPDATE_THRESHOLD: usize = 4096;
+
+/// Maximum amount of meshes in a leaf of octree of spatial index.
+const SPATIAL_INDEX_SPLIT_THRESHOLD: usize = 16;
+
+/// A result of [`Graph::ray_cast`].
+#[derive(Debug, Clone)]
+pub struct RayCastResult {
+    /// A handle of the mesh whose bounds were intersected by the ray.
+    pub node: Handle<Node>,
+
+    /// A position of the intersection with bounds of the mesh in world coordinates.
+    pub position: Vector3<f32>,
+
+    /// Ray equation parameter of the intersection, 0 is the origin of the ray and 1 is its end.
+    pub toi: f32,
+}
+
+/// Octree of world-space bounds of meshes, it is updated once per frame and used to avoid
+/// testing every mesh of the graph when casting a ray.
+#[derive(Debug, Default)]
+struct SpatialIndex {
+    octree: Octree,
+    // Mesh for each object of the octree.
+    nodes: Vec<Handle<Node>>,
+    // Bounds of meshes gathered on last update.
+    entries: Vec<(Handle<Node>, AxisAlignedBoundingBox)>,
 }
 
 impl Default for Graph {
@@ -59,10 +94,151 @@ impl Default for Graph {
             root: Handle::NONE,
             pool: Pool::new(),
             stack: Vec::new(),
+            parallel_update_threshold: DEFAULT_PARALLEL_UPDATE_THRESHOLD,
+            spatial_index: Default::default(),
+        }
+    }
+}
+
+// Nodes cache their global data in Cell's, so the graph cannot be shared between threads.
+// Instead, data of changed nodes of a subtree is copied out of the graph, global data is
+// calculated in parallel and then written back.
+struct SubtreeNode {
+    handle: Handle<Node>,
+    // Index of parent in the subtree, `None` for root of the subtree.
+    parent: Option<usize>,
+    // Copy of local transform, `None` if the node has not changed.
+    local_transform: Option<Transform>,
+    visibility: bool,
+    global_transform: Matrix4<f32>,
+    global_visibility: bool,
+}
+
+struct Subtree {
+    parent_global_transform: Matrix4<f32>,
+    parent_global_visibility: bool,
+    nodes: Vec<SubtreeNode>,
+}
+
+impl Subtree {
+    // Nodes are gathered in depth-first order, so parents always precede their children.
+    fn gather(graph: &Graph, root: Handle<Node>, parent_dirty: bool) -> Self {
+        let (parent_global_transform, parent_global_visibility) =
+            if let Some(parent) = graph.pool.try_borrow(graph.pool[root].parent()) {
+                (parent.global_transform(), parent.global_visibility())
+            } else {
+                (Matrix4::identity(), true)
+            };
+
+        let mut nodes = Vec::new();
+        let mut stack = vec![(root, None, parent_dirty)];
+        while let Some((handle, parent, parent_dirty)) = stack.pop() {
+            let node = &graph.pool[handle];
+
+            // Non-short-circuit evaluation here, both flags must be reset.
+            let dirty = parent_dirty
+                | node.hierarchy_dirty.replace(false)
+                | node.local_transform().take_changed();
+
+            let index = nodes.len();
+            nodes.push(SubtreeNode {
+                handle,
+                parent,
+                local_transform: if dirty {
+                    Some(node.local_transform().clone())
+                } else {
+                    None
+                },
+                visibility: node.visibility(),
+                global_transform: node.global_transform(),
+                global_visibility: node.global_visibility(),
+            });
+
+            stack.extend(
+                node.children()
+                    .iter()
+                    .map(|&child| (child, Some(index), dirty)),
+            );
+        }
+
+        Self {
+            parent_global_transform,
+            parent_global_visibility,
+            nodes,
+        }
+    }
+
+    fn update(&mut self) {
+        for i in 0..self.nodes.len() {
+            let (parent_global_transform, parent_global_visibility) = match self.nodes[i].parent {
+                Some(parent) => {
+                    let parent = &self.nodes[parent];
+                    (parent.global_transform, parent.global_visibility)
+                }
+                None => (self.parent_global_transform, self.parent_global_visibility),
+            };
+
+            let node = &mut self.nodes[i];
+            if let Some(local_transform) = node.local_transform.as_ref() {
+                node.global_transform = parent_global_transform * local_transform.matrix();
+                node.global_visibility = parent_global_visibility && node.visibility;
+            }
+        }
+    }
+
+    fn write_back(&self, graph: &Graph) {
+        for subtree_node in self.nodes.iter() {
+            if subtree_node.local_transform.is_some() {
+                let node = &graph.pool[subtree_node.handle];
+                node.global_transform.set(subtree_node.global_transform);
+                node.global_visibility.set(subtree_node.global_visibility);
+                node.invalidate_version();
+            }
         }
     }
 }
 
+/// Updates global transform and visibility of a node, returns true if the node has changed.
+fn update_node_hierarchical_data(
+    graph: &Graph,
+    node_handle: Handle<Node>,
+    parent_dirty: bool,
+) -> bool {
+    let node = &graph.pool[node_handle];
+
+    // Non-short-circuit evaluation here, both flags must be reset.
+    let dirty =
+        parent_dirty | node.hierarchy_dirty.replace(false) | node.local_transform().take_changed();
+
+    if dirty {
+        let (parent_global_transform, parent_visibility) =
+            if let Some(parent) = graph.pool.try_borrow(node.parent()) {
+                (parent.global_transform(), parent.global_visibility())
+            } else {
+                (Matrix4::identity(), true)
+            };
+
+        node.global_transform
+            .set(parent_global_transform * node.local_transform().matrix());
+        node.global_visibility
+            .set(parent_visibility && node.visibility());
+        node.invalidate_version();
+    }
+
+    dirty
+}
+
+fn update_hierarchical_data_recursively(
+    graph: &Graph,
+    node_handle: Handle<Node>,
+    parent_dirty: bool,
+) {
+    let dirty = update_node_hierarchical_data(graph, node_handle, parent_dirty);
+    for &child in graph.pool[node_handle].children() {
+        update_hierarchical_data_recursively(graph, child, dirty);
+    }
+}
+
 /// Sub-graph is a piece of graph that was extracted from a graph. It has ownership
 /// over its nodes. It is used to temporarily take ownership of a sub-graph. This could
 /// be used if you making a scene editor with a command stack - once you reverted a command,
@@ -89,6 +265,8 @@ impl Graph {
             stack: Vec::new(),
             root,
             pool,
+            parallel_update_threshold: DEFAULT_PARALLEL_UPDATE_THRESHOLD,
+            spatial_index: Default::default(),
         }
     }
 
@@ -171,6 +349,7 @@ impl Graph {
     pub fn link_nodes(&mut self, child: Handle<Node>, parent: Handle<Node>) {
         self.unlink_internal(child);
         self.pool[child].parent = parent;
+        self.pool[child].hierarchy_dirty.set(true);
         self.pool[parent].children.push(child);
     }
 
@@ -184,6 +363,89 @@ impl Graph {
             .set_position(Vector3::default());
     }
 
+    /// Links specified child with specified parent so that the child stays at the same place
+    /// in world coordinates. Unlike [`link_nodes`](Self::link_nodes), which keeps local
+    /// transform of the child and so the child "jumps" to its new parent, this method
+    /// recalculates local transform of the child from its current world transform and world
+    /// transform of new parent. World transforms are calculated from local transforms of
+    /// ancestors, so there is no need to update hierarchical data before the call.
+    ///
+    /// # Transform decomposition
+    ///
+    /// New local transform is a plain translation-rotation-scale transform - pre- and
+    /// post-rotation, pivots and offsets of the child are reset. Decomposition is exact
+    /// unless the parent has non-uniform scale and the child is rotated relative to the
+    /// parent: such combination produces skew which can't be represented by a transform,
+    /// in this case position of the child is still exact, but its rotation is the closest
+    /// rotation to the skewed basis and its scale is the length of basis vectors.
+    ///
+    /// # Example
+    ///
+    /// Weapon pick up - weapon lying on the ground is attached to a hand of a character
+    /// and it must not move at the moment of attachment:
+    ///
+    /// ```
+    /// use rg3d::{
+    ///     core::algebra::{UnitQuaternion, Vector3},
+    ///     scene::{base::BaseBuilder, graph::Graph, transform::TransformBuilder},
+    /// };
+    ///
+    /// let mut graph = Graph::new();
+    /// let hand = BaseBuilder::new()
+    ///     .with_local_transform(
+    ///         TransformBuilder::new()
+    ///             .with_local_position(Vector3::new(1.0, 1.5, 0.0))
+    ///             .with_local_rotation(UnitQuaternion::from_axis_angle(&Vector3::y_axis(), 1.0))
+    ///             .with_local_scale(Vector3::new(0.01, 0.01, 0.01))
+    ///             .build(),
+    ///     )
+    ///     .build(&mut graph);
+    /// let weapon = BaseBuilder::new()
+    ///     .with_local_transform(
+    ///         TransformBuilder::new()
+    ///             .with_local_position(Vector3::new(2.0, 0.0, 3.0))
+    ///             .build(),
+    ///     )
+    ///     .build(&mut graph);
+    /// graph.update_hierarchical_data();
+    ///
+    /// graph.link_nodes_keep_global(weapon, hand);
+    /// graph.update_hierarchical_data();
+    ///
+    /// // Weapon stays put and from now on it follows the hand.
+    /// assert!((graph.global_position(weapon) - Vector3::new(2.0, 0.0, 3.0)).norm() < 0.001);
+    /// ```
+    pub fn link_nodes_keep_global(&mut self, child: Handle<Node>, parent: Handle<Node>) {
+        let parent_global = self.calculate_global_transform(parent);
+        let child_global = self.calculate_global_transform(child);
+        let local = parent_global
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity)
+            * child_global;
+        self.link_nodes(child, parent);
+        *self.pool[child].local_transform_mut() = decompose_transform(&local);
+    }
+
+    /// Unlinks specified node from its parent and attaches it to root graph node, the node
+    /// stays at the same place in world coordinates. See
+    /// [`link_nodes_keep_global`](Self::link_nodes_keep_global) for details.
+    pub fn unlink_keep_global(&mut self, node_handle: Handle<Node>) {
+        self.link_nodes_keep_global(node_handle, self.root);
+    }
+
+    /// Calculates world transform of a node from local transforms of the node and its
+    /// ancestors, unlike global transform of a node it does not depend on last update
+    /// of hierarchical data.
+    fn calculate_global_transform(&self, node: Handle<Node>) -> Matrix4<f32> {
+        let node = &self.pool[node];
+        let local = node.local_transform().matrix();
+        if node.parent().is_some() {
+            self.calculate_global_transform(node.parent()) * local
+        } else {
+            local
+        }
+    }
+
     /// Tries to find a copy of `node_handle` in hierarchy tree starting from `root_handle`.
     pub fn find_copy_of(
         &self,
@@ -245,7 +507,8 @@ impl Graph {
     ///
     /// # Implementation notes
     ///
-    /// This method automatically remaps bones for copied surfaces.
+    /// This method automatically remaps bones for copied surfaces and calls `resolve` of
+    /// copied custom nodes.
     ///
     /// Returns tuple where first element is handle to copy of node, and second element -
     /// old-to-new hash map, which can be used to easily find copy of node by its original.
@@ -266,14 +529,18 @@ impl Graph {
 
         // Iterate over instantiated nodes and remap bones handles.
         for (_, &new_node_handle) in old_new_mapping.iter() {
-            if let Node::Mesh(mesh) = &mut dest_graph.pool[new_node_handle] {
-                for surface in mesh.surfaces_mut() {
-                    for bone_handle in surface.bones.iter_mut() {
-                        if let Some(entry) = old_new_mapping.get(bone_handle) {
-                            *bone_handle = *entry;
+            match &mut dest_graph.pool[new_node_handle] {
+                Node::Mesh(mesh) => {
+                    for surface in mesh.surfaces_mut() {
+                        for bone_handle in surface.bones.iter_mut() {
+                            if let Some(entry) = old_new_mapping.get(bone_handle) {
+                                *bone_handle = *entry;
+                            }
                         }
                     }
                 }
+                Node::Custom(custom) => custom.resolve(&old_new_mapping),
+                _ => (),
             }
         }
 
@@ -434,6 +701,14 @@ impl Graph {
             }
         }
 
+        // Custom nodes stored in save file as is, give them a chance to restore state.
+        let empty_mapping = HashMap::new();
+        for node in self.pool.iter_mut() {
+            if let Node::Custom(custom) = node {
+                custom.resolve(&empty_mapping);
+            }
+        }
+
         Log::writeln(
             MessageKind::Information,
             "Graph resolved successfully!".to_owned(),
@@ -445,28 +720,58 @@ impl Graph {
     /// on each frame. However there is one use case - when you setup complex hierarchy and
     /// need to know global transform of nodes before entering update loop, then you can call
     /// this method.
+    ///
+    /// Only nodes that were changed since last update (their local transform, visibility or
+    /// parent) are recalculated together with their descendants, global transforms of the
+    /// rest of nodes are left untouched. This makes update of static scenes very cheap.
+    ///
+    /// Large graphs are updated in parallel, see `set_parallel_update_threshold`. Result does
+    /// not depend on whether update was parallel or not.
     pub fn update_hierarchical_data(&mut self) {
-        fn update_recursively(graph: &Graph, node_handle: Handle<Node>) {
-            let node = &graph.pool[node_handle];
-
-            let (parent_global_transform, parent_visibility) =
-                if let Some(parent) = graph.pool.try_borrow(node.parent()) {
-                    (parent.global_transform(), parent.global_visibility())
-                } else {
-                    (Matrix4::identity(), true)
-                };
-
-            node.global_transform
-                .set(parent_global_transform * node.local_transform().matrix());
-            node.global_visibility
-                .set(parent_visibility && node.visibility());
+        if self.pool.get_capacity() < self.parallel_update_threshold {
+            update_hierarchical_data_recursively(self, self.root, false);
+            return;
+        }
 
-            for &child in node.children() {
-                update_recursively(graph, child);
+        // Update upper levels of hierarchy serially until there is enough independent
+        // subtrees to load all threads, then update the subtrees in parallel.
+        let min_subtree_count = rayon::current_num_threads() * 4;
+        let mut subtrees = vec![(self.root, false)];
+        let mut next_level = Vec::new();
+        while !subtrees.is_empty() && subtrees.len() < min_subtree_count {
+            for (node_handle, parent_dirty) in subtrees.drain(..) {
+                let dirty = update_node_hierarchical_data(self, node_handle, parent_dirty);
+                next_level.extend(
+                    self.pool[node_handle]
+                        .children()
+                        .iter()
+                        .map(|&child| (child, dirty)),
+                );
             }
+            std::mem::swap(&mut subtrees, &mut next_level);
         }
 
-        update_recursively(self, self.root);
+        let mut subtrees = subtrees
+            .into_iter()
+            .map(|(node_handle, parent_dirty)| Subtree::gather(self, node_handle, parent_dirty))
+            .collect::<Vec<_>>();
+        subtrees.par_iter_mut().for_each(|subtree| subtree.update());
+        for subtree in subtrees.iter() {
+            subtree.write_back(self);
+        }
+    }
+
+    /// Sets amount of nodes starting from which `update_hierarchical_data` updates independent
+    /// subtrees of the graph in parallel. For small graphs overhead of
+    /// threads synchronization is bigger than the gain, so they are updated serially. Default
+    /// value is [DEFAULT_PARALLEL_UPDATE_THRESHOLD], `usize::MAX` disables parallel update.
+    pub fn set_parallel_update_threshold(&mut self, threshold: usize) {
+        self.parallel_update_threshold = threshold;
+    }
+
+    /// Returns amount of nodes starting from which graph is updated in parallel.
+    pub fn parallel_update_threshold(&self) -> usize {
+        self.parallel_update_threshold
     }
 
     /// Checks whether given node handle is valid or not.
@@ -511,12 +816,102 @@ impl Graph {
                                 .as_camera_mut()
                                 .visibility_cache = new_cache;
                         }
-                        Node::ParticleSystem(particle_system) => particle_system.update(dt),
+                        Node::Terrain(terrain) => terrain.update(),
+                        Node::Custom(custom) => custom.update(dt),
                         _ => (),
                     }
                 }
             }
         }
+
+        // Particle systems are independent from each other and from the rest of the graph,
+        // so they're updated in parallel.
+        self.pool.par_iter_mut().for_each(|node| {
+            if let Node::ParticleSystem(particle_system) = node {
+                particle_system.update(dt)
+            }
+        });
+
+        self.update_spatial_index();
+    }
+
+    /// Updates bounds of meshes in the octree used by `ray_cast`. Moved meshes are updated in
+    /// place, octree is rebuilt only if meshes were added or removed, or if too many of them
+    /// left bounds of the octree.
+    fn update_spatial_index(&mut self) {
+        let mut entries = std::mem::take(&mut self.spatial_index.entries);
+        entries.clear();
+        for (handle, node) in self.pool.pair_iter() {
+            if let Node::Mesh(mesh) = node {
+                let aabb = mesh.animated_world_bounding_box(self);
+                // Mesh without vertices has no bounds.
+                if aabb.min.x <= aabb.max.x {
+                    entries.push((handle, aabb));
+                }
+            }
+        }
+
+        let index = &mut self.spatial_index;
+        let same_meshes = index.nodes.len() == entries.len()
+            && index
+                .nodes
+                .iter()
+                .zip(entries.iter())
+                .all(|(node, (handle, _))| node == handle);
+        if same_meshes && index.octree.outside_count() * 4 <= entries.len() {
+            for (i, (_, aabb)) in entries.iter().enumerate() {
+                let i = i as u32;
+                if let Some(old) = index.octree.bounds_of(i) {
+                    if old.min != aabb.min || old.max != aabb.max {
+                        index.octree.move_item(i, *aabb);
+                    }
+                }
+            }
+        } else {
+            let bounds = entries.iter().map(|(_, aabb)| *aabb).collect::<Vec<_>>();
+            index.octree = Octree::from_aabbs(&bounds, SPATIAL_INDEX_SPLIT_THRESHOLD);
+            index.nodes.clear();
+            index
+                .nodes
+                .extend(entries.iter().map(|(handle, _)| *handle));
+        }
+        index.entries = entries;
+    }
+
+    /// Casts a ray and collects every mesh whose world-space bounding box is intersected by
+    /// the ray, results are sorted from closest to farthest. The ray is treated as a segment,
+    /// so length of its direction defines maximum distance of the cast.
+    ///
+    /// This is a fast coarse test which uses bounds of meshes calculated on last
+    /// `update_nodes`, meshes removed since then are skipped. Use
+    /// `Mesh::ray_cast_tex_coords` or physics to find exact point on a surface.
+    pub fn ray_cast(&self, ray: &Ray, results: &mut Vec<RayCastResult>) {
+        results.clear();
+
+        let index = &self.spatial_index;
+        let mut query_buffer = Vec::new();
+        index.octree.ray_query(ray, &mut query_buffer);
+        for i in query_buffer {
+            let node = index.nodes[i as usize];
+            if !self.pool.is_valid_handle(node) {
+                continue;
+            }
+            if let Some(intersection) = index
+                .octree
+                .bounds_of(i)
+                .and_then(|aabb| ray.aabb_intersection(&aabb))
+            {
+                // Origin of the ray could be inside of the bounds.
+                let toi = intersection.min.max(0.0);
+                results.push(RayCastResult {
+                    node,
+                    position: ray.get_point(toi),
+                    toi,
+                });
+            }
+        }
+
+        results.sort_by(|a, b| a.toi.partial_cmp(&b.toi).unwrap_or(Ordering::Equal));
     }
 
     /// Returns capacity of internal pool. Can be used to iterate over all **potentially**
@@ -770,6 +1165,12 @@ impl Graph {
         )
     }
 
+    /// Returns position of a node in world coordinates. It is a shortcut for
+    /// `graph[node].global_position()`.
+    pub fn global_position(&self, node: Handle<Node>) -> Vector3<f32> {
+        self[node].global_position()
+    }
+
     /// Returns global scale of a node.
     pub fn global_scale(&self, node: Handle<Node>) -> Vector3<f32> {
         let m = self.global_scale_matrix(node);
@@ -777,6 +1178,34 @@ impl Graph {
     }
 }
 
+/// Decomposes a matrix into translation-rotation-scale transform, see
+/// [`Graph::link_nodes_keep_global`] for details.
+fn decompose_transform(matrix: &Matrix4<f32>) -> Transform {
+    let basis = matrix.basis();
+    let mut scale = Vector3::new(
+        basis.column(0).norm(),
+        basis.column(1).norm(),
+        basis.column(2).norm(),
+    );
+    // Mirroring is represented by negative scale.
+    if basis.determinant() < 0.0 {
+        scale.x = -scale.x;
+    }
+    let safe = |s: f32| if s.abs() > std::f32::EPSILON { s } else { 1.0 };
+    let rotation_basis = Matrix3::from_columns(&[
+        basis.column(0) / safe(scale.x),
+        basis.column(1) / safe(scale.y),
+        basis.column(2) / safe(scale.z),
+    ]);
+    TransformBuilder::new()
+        .with_local_position(matrix.position())
+        .with_local_rotation(UnitQuaternion::from(Rotation3::from_matrix(
+            &rotation_basis,
+        )))
+        .with_local_scale(scale)
+        .build()
+}
+
 impl Index<Handle<Node>> for Graph {
     type Output = Node;
 
@@ -855,10 +1284,98 @@ impl Visit for Graph {
 #[cfg(test)]
 mod test {
     use crate::{
-        core::pool::Handle,
-        scene::{base::Base, graph::Graph, node::Node},
+        core::{
+            algebra::{Matrix4, UnitQuaternion, Vector2, Vector3},
+            color::Color,
+            math::ray::Ray,
+            numeric_range::NumericRange,
+            pool::Handle,
+            visitor::{Visit, VisitResult, Visitor},
+        },
+        renderer::surface::{Surface, SurfaceSharedData},
+        scene::{
+            base::{Base, BaseBuilder},
+            graph::Graph,
+            mesh::MeshBuilder,
+            node::{CustomNode, Node},
+            particle_system::{
+                BaseEmitterBuilder, DrawData, ParticleSystem, ParticleSystemBuilder,
+                SphereEmitterBuilder,
+            },
+            transform::TransformBuilder,
+        },
+    };
+    use std::{
+        any::Any,
+        collections::HashMap,
+        ops::{Deref, DerefMut},
+        sync::{Arc, RwLock},
     };
 
+    #[derive(Debug, Default)]
+    struct Follower {
+        base: Base,
+        target: Handle<Node>,
+        distance: f32,
+    }
+
+    impl Deref for Follower {
+        type Target = Base;
+
+        fn deref(&self) -> &Self::Target {
+            &self.base
+        }
+    }
+
+    impl DerefMut for Follower {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.base
+        }
+    }
+
+    impl Visit for Follower {
+        fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+            visitor.enter_region(name)?;
+
+            self.base.visit("Base", visitor)?;
+            self.target.visit("Target", visitor)?;
+
+            visitor.leave_region()
+        }
+    }
+
+    impl CustomNode for Follower {
+        fn box_clone(&self) -> Box<dyn CustomNode> {
+            Box::new(Self {
+                base: self.base.raw_copy(),
+                target: self.target,
+                distance: self.distance,
+            })
+        }
+
+        fn kind(&self) -> u64 {
+            0
+        }
+
+        fn update(&mut self, dt: f32) {
+            self.distance += dt;
+        }
+
+        fn resolve(&mut self, old_new_mapping: &HashMap<Handle<Node>, Handle<Node>>) {
+            if let Some(&target) = old_new_mapping.get(&self.target) {
+                self.target = target;
+            }
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
     #[test]
     fn graph_init_test() {
         let graph = Graph::new();
@@ -874,4 +1391,425 @@ mod test {
         graph.add_node(Node::Base(Base::default()));
         assert_eq!(graph.pool.alive_count(), 4);
     }
+
+    #[test]
+    fn graph_set_look_vector_test() {
+        let mut graph = Graph::new();
+        let child = BaseBuilder::new()
+            .with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(Vector3::new(0.0, 0.0, 2.0))
+                    .with_pre_rotation(UnitQuaternion::from_axis_angle(&Vector3::z_axis(), 0.4))
+                    .build(),
+            )
+            .build(&mut graph);
+        let parent = BaseBuilder::new()
+            .with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(Vector3::new(1.0, 0.0, 0.0))
+                    .with_local_rotation(UnitQuaternion::from_axis_angle(
+                        &Vector3::y_axis(),
+                        90.0f32.to_radians(),
+                    ))
+                    .build(),
+            )
+            .with_children(&[child])
+            .build(&mut graph);
+        graph.update_hierarchical_data();
+
+        assert!((graph.global_position(child) - Vector3::new(3.0, 0.0, 0.0)).norm() < 0.001);
+        assert!((graph.global_position(parent) - Vector3::new(1.0, 0.0, 0.0)).norm() < 0.001);
+
+        let look = Vector3::new(0.0, 1.0, -1.0).normalize();
+        graph[child].set_look_vector(look, Vector3::y());
+        graph.update_hierarchical_data();
+
+        assert!((graph[child].look_vector().normalize() - look).norm() < 0.001);
+        // Position must not change.
+        assert!((graph.global_position(child) - Vector3::new(3.0, 0.0, 0.0)).norm() < 0.001);
+    }
+
+    #[test]
+    fn graph_dirty_transform_test() {
+        let mut graph = Graph::new();
+        let child = BaseBuilder::new()
+            .with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(Vector3::new(0.0, 1.0, 0.0))
+                    .build(),
+            )
+            .build(&mut graph);
+        let parent = BaseBuilder::new().with_children(&[child]).build(&mut graph);
+        let other = BaseBuilder::new().build(&mut graph);
+        graph.update_hierarchical_data();
+        assert!((graph.global_position(child) - Vector3::new(0.0, 1.0, 0.0)).norm() < 0.001);
+
+        // Global transform must stay consistent with last update until next one.
+        graph[parent]
+            .local_transform_mut()
+            .set_position(Vector3::new(2.0, 0.0, 0.0));
+        assert!((graph.global_position(child) - Vector3::new(0.0, 1.0, 0.0)).norm() < 0.001);
+
+        // Moving of parent must affect all descendants.
+        graph.update_hierarchical_data();
+        assert!((graph.global_position(parent) - Vector3::new(2.0, 0.0, 0.0)).norm() < 0.001);
+        assert!((graph.global_position(child) - Vector3::new(2.0, 1.0, 0.0)).norm() < 0.001);
+
+        // Re-linking must update global transform even if local transform is not changed.
+        graph[other]
+            .local_transform_mut()
+            .set_position(Vector3::new(0.0, 0.0, 3.0));
+        graph.update_hierarchical_data();
+        graph.link_nodes(child, other);
+        graph.update_hierarchical_data();
+        assert!((graph.global_position(child) - Vector3::new(0.0, 1.0, 3.0)).norm() < 0.001);
+
+        // Visibility is propagated too.
+        graph[other].set_visibility(false);
+        graph.update_hierarchical_data();
+        assert!(!graph[child].global_visibility());
+        assert!(graph[parent].global_visibility());
+    }
+
+    #[test]
+    fn graph_link_keep_global_test() {
+        let mut graph = Graph::new();
+        let parent_rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), 0.7);
+        let make_node = |graph: &mut Graph, position, rotation, scale| {
+            BaseBuilder::new()
+                .with_local_transform(
+                    TransformBuilder::new()
+                        .with_local_position(position)
+                        .with_local_rotation(rotation)
+                        .with_local_scale(scale)
+                        .build(),
+                )
+                .build(graph)
+        };
+        let parent = make_node(
+            &mut graph,
+            Vector3::new(1.0, 2.0, 3.0),
+            parent_rotation,
+            Vector3::new(2.0, 2.0, 2.0),
+        );
+        let skewing_parent = make_node(
+            &mut graph,
+            Vector3::new(-3.0, 0.0, 1.0),
+            parent_rotation,
+            Vector3::new(2.0, 3.0, 4.0),
+        );
+        let child_rotation = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), 0.3);
+        let child = make_node(
+            &mut graph,
+            Vector3::new(-1.0, 0.5, 2.0),
+            child_rotation,
+            Vector3::new(0.5, 0.5, 0.5),
+        );
+        let aligned_child = make_node(
+            &mut graph,
+            Vector3::new(4.0, 0.0, 0.0),
+            parent_rotation,
+            Vector3::new(1.0, 2.0, 3.0),
+        );
+        graph.update_hierarchical_data();
+        let global = graph[child].global_transform();
+        let aligned_global = graph[aligned_child].global_transform();
+
+        // Parent without non-uniform scale - decomposition is exact.
+        graph.link_nodes_keep_global(child, parent);
+        graph.update_hierarchical_data();
+        assert_eq!(graph[child].parent(), parent);
+        assert!((graph[child].global_transform() - global).norm() < 0.001);
+
+        graph.unlink_keep_global(child);
+        graph.update_hierarchical_data();
+        assert_eq!(graph[child].parent(), graph.get_root());
+        assert!((graph[child].global_transform() - global).norm() < 0.001);
+        let rotation = graph[child].local_transform().rotation();
+        assert!(rotation.angle_to(&child_rotation) < 0.001);
+
+        // Child is not rotated relative to parent with non-uniform scale - still exact.
+        graph.link_nodes_keep_global(aligned_child, skewing_parent);
+        graph.update_hierarchical_data();
+        assert!((graph[aligned_child].global_transform() - aligned_global).norm() < 0.001);
+
+        // Skew can't be represented, but position is exact.
+        graph.link_nodes_keep_global(child, skewing_parent);
+        graph.update_hierarchical_data();
+        assert!((graph.global_position(child) - Vector3::new(-1.0, 0.5, 2.0)).norm() < 0.001);
+    }
+
+    #[test]
+    fn graph_node_version_test() {
+        let mut graph = Graph::new();
+        let surface = Surface::new(Arc::new(RwLock::new(SurfaceSharedData::make_cube(
+            Matrix4::identity(),
+        ))));
+        let mesh = MeshBuilder::new(BaseBuilder::new())
+            .with_surfaces(vec![surface])
+            .build(&mut graph);
+        let parent = BaseBuilder::new().with_children(&[mesh]).build(&mut graph);
+        let other = BaseBuilder::new().build(&mut graph);
+        graph.update_hierarchical_data();
+
+        // Version must stay the same while nothing is changed.
+        let version = graph[mesh].version();
+        graph.update_hierarchical_data();
+        assert_eq!(graph[mesh].version(), version);
+
+        // Changes of unrelated nodes must not affect the version.
+        graph[other]
+            .local_transform_mut()
+            .set_position(Vector3::new(1.0, 0.0, 0.0));
+        graph.update_hierarchical_data();
+        assert_eq!(graph[mesh].version(), version);
+
+        // Moving of ancestor changes global transform, so version must change.
+        graph[parent]
+            .local_transform_mut()
+            .set_position(Vector3::new(1.0, 0.0, 0.0));
+        graph.update_hierarchical_data();
+        let moved_version = graph[mesh].version();
+        assert_ne!(moved_version, version);
+
+        // Any mutable access to surfaces must change the version too.
+        if let Node::Mesh(mesh) = &mut graph[mesh] {
+            mesh.surfaces_mut()[0].set_color(Color::RED);
+        }
+        assert_ne!(graph[mesh].version(), moved_version);
+    }
+
+    #[test]
+    fn graph_parallel_update_test() {
+        fn make_graph(parallel_update_threshold: usize) -> (Graph, Vec<Handle<Node>>) {
+            let mut graph = Graph::new();
+            graph.set_parallel_update_threshold(parallel_update_threshold);
+            let mut handles = Vec::new();
+            for i in 0..10 {
+                // Chain of nodes, so subtrees have various depth.
+                let mut parent = Handle::NONE;
+                for j in 0..(i * 3 + 1) {
+                    let mut builder = BaseBuilder::new().with_local_transform(
+                        TransformBuilder::new()
+                            .with_local_position(Vector3::new(i as f32, j as f32, 1.0))
+                            .with_local_rotation(UnitQuaternion::from_axis_angle(
+                                &Vector3::y_axis(),
+                                0.1 * j as f32,
+                            ))
+                            .build(),
+                    );
+                    if parent.is_some() {
+                        builder = builder.with_children(&[parent]);
+                    }
+                    parent = builder.build(&mut graph);
+                    handles.push(parent);
+                }
+            }
+            (graph, handles)
+        }
+
+        let (mut serial, handles) = make_graph(usize::MAX);
+        let (mut parallel, _) = make_graph(0);
+        for i in 0..2 {
+            serial.update_hierarchical_data();
+            parallel.update_hierarchical_data();
+            for &handle in handles.iter() {
+                assert_eq!(
+                    serial[handle].global_transform(),
+                    parallel[handle].global_transform()
+                );
+            }
+
+            // Move some nodes and check again.
+            for graph in [&mut serial, &mut parallel].iter_mut() {
+                for &handle in handles.iter().step_by(7) {
+                    graph[handle]
+                        .local_transform_mut()
+                        .set_position(Vector3::new(0.0, i as f32, 2.0));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn graph_parallel_update_version_test() {
+        fn make_graph(parallel_update_threshold: usize) -> (Graph, Handle<Node>) {
+            let mut graph = Graph::new();
+            graph.set_parallel_update_threshold(parallel_update_threshold);
+            let surface = Surface::new(Arc::new(RwLock::new(SurfaceSharedData::make_cube(
+                Matrix4::identity(),
+            ))));
+            let mesh = MeshBuilder::new(BaseBuilder::new())
+                .with_surfaces(vec![surface])
+                .build(&mut graph);
+            // Enough independent subtrees, so the mesh is updated in parallel part.
+            BaseBuilder::new().with_children(&[mesh]).build(&mut graph);
+            for _ in 0..rayon::current_num_threads() * 4 {
+                BaseBuilder::new().build(&mut graph);
+            }
+            graph.update_hierarchical_data();
+            (graph, mesh)
+        }
+
+        let (mut serial, serial_mesh) = make_graph(usize::MAX);
+        let (mut parallel, parallel_mesh) = make_graph(0);
+        let version = parallel[parallel_mesh].version();
+        for (graph, mesh) in [(&mut serial, serial_mesh), (&mut parallel, parallel_mesh)].iter_mut()
+        {
+            graph[*mesh]
+                .local_transform_mut()
+                .set_position(Vector3::new(1.0, 2.0, 3.0));
+            graph.update_hierarchical_data();
+        }
+
+        // Cached draw data relies on version, so parallel update must change it as well.
+        assert_ne!(parallel[parallel_mesh].version(), version);
+        assert_eq!(
+            serial[serial_mesh].global_transform(),
+            parallel[parallel_mesh].global_transform()
+        );
+    }
+
+    #[test]
+    fn graph_custom_node_test() {
+        let mut graph = Graph::new();
+        // Add some garbage so handles in copy will differ.
+        graph.add_node(Node::Base(Base::default()));
+        let target = graph.add_node(Node::Base(Base::default()));
+        let follower = graph.add_node(Node::Custom(Box::new(Follower {
+            target,
+            ..Default::default()
+        })));
+        graph.link_nodes(follower, target);
+
+        graph.update_nodes(Default::default(), 0.5);
+        assert_eq!(
+            graph[follower].as_custom::<Follower>().unwrap().distance,
+            0.5
+        );
+
+        let mut copy = Graph::new();
+        let (target_copy, mapping) = graph.copy_node(target, &mut copy, &mut |_, _| true);
+        let follower_copy = mapping[&follower];
+        let copied = copy[follower_copy].as_custom::<Follower>().unwrap();
+        assert_eq!(copied.target, target_copy);
+        assert_ne!(copied.target, target);
+        assert_eq!(copied.distance, 0.5);
+        assert!(copy[follower_copy].is_custom());
+        assert!(copy[target_copy].as_custom::<Follower>().is_none());
+    }
+
+    #[test]
+    fn graph_particle_systems_update_test() {
+        let mut graph = Graph::new();
+        let mut particle_systems = Vec::new();
+        for i in 0..32 {
+            let emitter = SphereEmitterBuilder::new(
+                BaseEmitterBuilder::new()
+                    .with_spawn_rate(10 * (i + 1))
+                    .with_max_particles(1000)
+                    // Particles must not die during the test, lifetime is random.
+                    .with_lifetime_range(NumericRange::new(10.0, 11.0)),
+            )
+            .build();
+            particle_systems.push(
+                ParticleSystemBuilder::new(BaseBuilder::new())
+                    .with_emitters(vec![emitter])
+                    .build(&mut graph),
+            );
+        }
+        let mut copies = particle_systems
+            .iter()
+            .map(|&handle| graph[handle].as_particle_system().raw_copy())
+            .collect::<Vec<_>>();
+
+        // Particle systems are updated in parallel, result must be the same as for serial
+        // update. Properties of particles are random, so only amount of them is compared.
+        let alive_count = |particle_system: &ParticleSystem| {
+            let mut sorted_particles = Vec::new();
+            particle_system.generate_draw_data(
+                &mut sorted_particles,
+                &mut DrawData::default(),
+                &Vector3::default(),
+            );
+            sorted_particles.len()
+        };
+        for _ in 0..10 {
+            graph.update_nodes(Default::default(), 0.1);
+            for copy in copies.iter_mut() {
+                copy.update(0.1);
+            }
+            for (&handle, copy) in particle_systems.iter().zip(copies.iter()) {
+                assert_eq!(
+                    alive_count(graph[handle].as_particle_system()),
+                    alive_count(copy)
+                );
+            }
+        }
+        assert!(alive_count(graph[particle_systems[0]].as_particle_system()) > 0);
+    }
+
+    fn cube_at(graph: &mut Graph, position: Vector3<f32>) -> Handle<Node> {
+        let data = SurfaceSharedData::make_cube(Matrix4::identity());
+        MeshBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(position)
+                    .build(),
+            ),
+        )
+        .with_surfaces(vec![Surface::new(Arc::new(RwLock::new(data)))])
+        .build(graph)
+    }
+
+    #[test]
+    fn graph_ray_cast_test() {
+        let mut graph = Graph::new();
+        let near = cube_at(&mut graph, Vector3::new(0.0, 0.0, 5.0));
+        let far = cube_at(&mut graph, Vector3::new(0.0, 0.0, 10.0));
+        cube_at(&mut graph, Vector3::new(5.0, 0.0, 5.0));
+        graph.update_nodes(Vector2::new(1.0, 1.0), 0.0);
+
+        let ray = Ray::from_two_points(&Vector3::default(), &Vector3::new(0.0, 0.0, 20.0)).unwrap();
+        let mut results = Vec::new();
+        graph.ray_cast(&ray, &mut results);
+        let hits = results.iter().map(|r| r.node).collect::<Vec<_>>();
+        assert_eq!(hits, vec![near, far]);
+        assert!((results[0].toi - 4.5 / 20.0).abs() < 1.0e-5);
+        assert!((results[0].position - Vector3::new(0.0, 0.0, 4.5)).norm() < 1.0e-4);
+
+        // Mesh moved inside of bounds of the octree is updated in place.
+        graph[near]
+            .local_transform_mut()
+            .set_position(Vector3::new(2.0, 0.0, 5.0));
+        graph.update_nodes(Vector2::new(1.0, 1.0), 0.0);
+        graph.ray_cast(&ray, &mut results);
+        let hits = results.iter().map(|r| r.node).collect::<Vec<_>>();
+        assert_eq!(hits, vec![far]);
+
+        // Mesh moved outside of the bounds.
+        graph[far]
+            .local_transform_mut()
+            .set_position(Vector3::new(0.0, 0.0, 2.0));
+        graph[near]
+            .local_transform_mut()
+            .set_position(Vector3::new(0.0, 0.0, 5.0));
+        graph.update_nodes(Vector2::new(1.0, 1.0), 0.0);
+        graph.ray_cast(&ray, &mut results);
+        let hits = results.iter().map(|r| r.node).collect::<Vec<_>>();
+        assert_eq!(hits, vec![far, near]);
+
+        // Removed mesh is skipped even before next update.
+        graph.remove_node(far);
+        graph.ray_cast(&ray, &mut results);
+        let hits = results.iter().map(|r| r.node).collect::<Vec<_>>();
+        assert_eq!(hits, vec![near]);
+
+        // New mesh far outside of previous bounds.
+        let new = cube_at(&mut graph, Vector3::new(0.0, 0.0, 15.0));
+        graph.update_nodes(Vector2::new(1.0, 1.0), 0.0);
+        graph.ray_cast(&ray, &mut results);
+        let hits = results.iter().map(|r| r.node).collect::<Vec<_>>();
+        assert_eq!(hits, vec![near, new]);
+    }
 }
@@ -164,7 +164,14 @@ impl SpotLight {
     }
 
     /// Set cookie texture. Also called gobo this texture gets projected
-    /// by the spot light.
+    /// by the spot light. The texture is never tiled - everything outside
+    /// of the texture is black, regardless of wrap mode of the texture.
+    ///
+    /// Cookie is projected on a square that encloses cross-section of the full
+    /// cone (hotspot + falloff), so changing cone angle scales the projected
+    /// image. Non-square textures are stretched to the square, corners of the
+    /// texture are cut by the cone. While the texture is loading (or if it has
+    /// failed to load) light is rendered without cookie.
     #[inline]
     pub fn set_cookie_texture(&mut self, texture: Texture) -> &mut Self {
         self.cookie_texture = Some(texture);
@@ -283,6 +290,61 @@ impl SpotLightBuilder {
     }
 }
 
+/// Defines resolution of shadow map of a light relative to base size of shadow map from
+/// quality settings of the renderer (`point_shadow_map_size` for point lights).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShadowMapResolution {
+    /// Resolution is selected automatically, lights close to camera will use full size
+    /// shadow maps, distant lights will use smaller ones.
+    Auto,
+    /// Full size of shadow map from quality settings.
+    Full,
+    /// Half of size of shadow map from quality settings.
+    Half,
+    /// Quarter of size of shadow map from quality settings.
+    Quarter,
+}
+
+impl Default for ShadowMapResolution {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+impl ShadowMapResolution {
+    /// Returns internal id of the resolution, it is used for serialization.
+    pub fn id(self) -> u32 {
+        match self {
+            ShadowMapResolution::Auto => 0,
+            ShadowMapResolution::Full => 1,
+            ShadowMapResolution::Half => 2,
+            ShadowMapResolution::Quarter => 3,
+        }
+    }
+
+    /// Creates resolution from given id.
+    pub fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(Self::Auto),
+            1 => Ok(Self::Full),
+            2 => Ok(Self::Half),
+            3 => Ok(Self::Quarter),
+            _ => Err(format!("Invalid shadow map resolution id {}!", id)),
+        }
+    }
+}
+
+impl Visit for ShadowMapResolution {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        let mut id = self.id();
+        id.visit(name, visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+        Ok(())
+    }
+}
+
 /// Point light can be represented as light bulb which hangs on wire - it is
 /// spherical light source which emits light in all directions. It has single
 /// parameter - radius at which intensity will be zero. Intensity of light will
@@ -301,11 +363,19 @@ impl SpotLightBuilder {
 /// Point lights supports shadows, but keep in mind - they're very expensive and
 /// can easily ruin performance of your game, especially on low-end hardware. Light
 /// scattering is relatively heavy too.
+///
+/// # Shadows
+///
+/// Point lights use cube shadow maps - scene is rendered six times (once per cube face)
+/// into a depth cube map. Shadows can be disabled per light using `set_cast_shadows`,
+/// size of shadow map can be lowered per light using `set_shadow_map_resolution`, this
+/// is useful for small lights that does not need detailed shadows.
 #[derive(Debug)]
 pub struct PointLight {
     base_light: BaseLight,
     shadow_bias: f32,
     radius: f32,
+    shadow_map_resolution: ShadowMapResolution,
 }
 
 impl Deref for PointLight {
@@ -347,12 +417,24 @@ impl PointLight {
         self.shadow_bias
     }
 
+    /// Sets resolution of shadow map of the light. Default is `Auto`, which means that
+    /// resolution will be selected by distance from light to camera.
+    pub fn set_shadow_map_resolution(&mut self, resolution: ShadowMapResolution) {
+        self.shadow_map_resolution = resolution;
+    }
+
+    /// Returns current resolution of shadow map of the light.
+    pub fn shadow_map_resolution(&self) -> ShadowMapResolution {
+        self.shadow_map_resolution
+    }
+
     /// Creates a raw copy of a point light node.
     pub fn raw_copy(&self) -> Self {
         Self {
             base_light: self.base_light.raw_copy(),
             radius: self.radius,
             shadow_bias: self.shadow_bias,
+            shadow_map_resolution: self.shadow_map_resolution,
         }
     }
 }
@@ -364,6 +446,9 @@ impl Visit for PointLight {
         self.base_light.visit("BaseLight", visitor)?;
         self.radius.visit("Radius", visitor)?;
         let _ = self.shadow_bias.visit("ShadowBias", visitor);
+        let _ = self
+            .shadow_map_resolution
+            .visit("ShadowMapResolution", visitor);
 
         visitor.leave_region()
     }
@@ -375,6 +460,7 @@ impl Default for PointLight {
             base_light: Default::default(),
             shadow_bias: 0.025,
             radius: 10.0,
+            shadow_map_resolution: Default::default(),
         }
     }
 }
@@ -384,6 +470,7 @@ pub struct PointLightBuilder {
     base_light_builder: BaseLightBuilder,
     shadow_bias: f32,
     radius: f32,
+    shadow_map_resolution: ShadowMapResolution,
 }
 
 impl PointLightBuilder {
@@ -393,6 +480,7 @@ impl PointLightBuilder {
             base_light_builder,
             shadow_bias: 0.025,
             radius: 10.0,
+            shadow_map_resolution: Default::default(),
         }
     }
 
@@ -408,12 +496,19 @@ impl PointLightBuilder {
         self
     }
 
+    /// Sets desired resolution of shadow map.
+    pub fn with_shadow_map_resolution(mut self, resolution: ShadowMapResolution) -> Self {
+        self.shadow_map_resolution = resolution;
+        self
+    }
+
     /// Builds new instance of point light.
     pub fn build_point_light(self) -> PointLight {
         PointLight {
             base_light: self.base_light_builder.build(),
             radius: self.radius,
             shadow_bias: self.shadow_bias,
+            shadow_map_resolution: self.shadow_map_resolution,
         }
     }
 
@@ -610,6 +705,7 @@ pub struct BaseLight {
     base: Base,
     color: Color,
     cast_shadows: bool,
+    shadow_distance: Option<f32>,
     scatter: Vector3<f32>,
     scatter_enabled: bool,
 }
@@ -634,6 +730,7 @@ impl Default for BaseLight {
             base: Default::default(),
             color: Color::WHITE,
             cast_shadows: true,
+            shadow_distance: None,
             scatter: Vector3::new(DEFAULT_SCATTER_R, DEFAULT_SCATTER_G, DEFAULT_SCATTER_B),
             scatter_enabled: true,
         }
@@ -649,6 +746,7 @@ impl Visit for BaseLight {
         self.cast_shadows.visit("CastShadows", visitor)?;
         self.scatter.visit("ScatterFactor", visitor)?;
         self.scatter_enabled.visit("ScatterEnabled", visitor)?;
+        let _ = self.shadow_distance.visit("ShadowDistance", visitor);
 
         visitor.leave_region()
     }
@@ -679,6 +777,31 @@ impl BaseLight {
         self.cast_shadows
     }
 
+    /// Sets maximum distance from camera at which light still casts shadows, farther
+    /// light is rendered without shadows. Distance is also limited by shadow distance
+    /// of quality settings of renderer, `None` means that only quality settings is used.
+    /// Small decorative lights should have short shadow distance (or no shadows at all)
+    /// to save time spent on rendering shadow maps.
+    #[inline]
+    pub fn set_shadow_distance(&mut self, distance: Option<f32>) {
+        self.shadow_distance = distance.map(|d| d.max(0.0));
+    }
+
+    /// Returns maximum distance from camera at which light casts shadows.
+    #[inline]
+    pub fn shadow_distance(&self) -> Option<f32> {
+        self.shadow_distance
+    }
+
+    /// Returns actual maximum distance from camera at which light casts shadows for given
+    /// shadow distance from quality settings. Light can have its own shadow distance, but
+    /// it cannot exceed the global one.
+    #[inline]
+    pub fn effective_shadow_distance(&self, global_distance: f32) -> f32 {
+        self.shadow_distance
+            .map_or(global_distance, |distance| distance.min(global_distance))
+    }
+
     /// Sets scatter factor per color channel (red, green, blue) in (0..1) range.
     /// This parameter defines how "thick" environment is and how much light will
     /// be scattered in light volume. Ability to change this parameter per channel
@@ -716,6 +839,7 @@ impl BaseLight {
             base: self.base.raw_copy(),
             color: self.color,
             cast_shadows: self.cast_shadows,
+            shadow_distance: self.shadow_distance,
             scatter: self.scatter,
             scatter_enabled: self.scatter_enabled,
         }
@@ -728,6 +852,7 @@ pub struct BaseLightBuilder {
     base_builder: BaseBuilder,
     color: Color,
     cast_shadows: bool,
+    shadow_distance: Option<f32>,
     scatter_factor: Vector3<f32>,
     scatter_enabled: bool,
 }
@@ -742,6 +867,7 @@ impl BaseLightBuilder {
             base_builder,
             color: Color::WHITE,
             cast_shadows: true,
+            shadow_distance: None,
             scatter_factor: Vector3::new(DEFAULT_SCATTER_R, DEFAULT_SCATTER_G, DEFAULT_SCATTER_B),
             scatter_enabled: true,
         }
@@ -759,6 +885,12 @@ impl BaseLightBuilder {
         self
     }
 
+    /// Sets maximum distance from camera at which light casts shadows.
+    pub fn with_shadow_distance(mut self, distance: f32) -> Self {
+        self.shadow_distance = Some(distance.max(0.0));
+        self
+    }
+
     /// Sets light scatter factor per color channel.
     pub fn with_scatter_factor(mut self, f: Vector3<f32>) -> Self {
         self.scatter_factor = f;
@@ -777,8 +909,52 @@ impl BaseLightBuilder {
             base: self.base_builder.build_base(),
             color: self.color,
             cast_shadows: self.cast_shadows,
+            shadow_distance: self.shadow_distance,
             scatter: self.scatter_factor,
             scatter_enabled: self.scatter_enabled,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::visitor::{Visit, Visitor},
+        scene::{
+            base::BaseBuilder,
+            light::{BaseLight, BaseLightBuilder},
+        },
+    };
+
+    #[test]
+    fn test_shadow_distance() {
+        let mut light = BaseLightBuilder::new(BaseBuilder::new()).build();
+        // Without own shadow distance the one from quality settings is used.
+        assert_eq!(light.shadow_distance(), None);
+        assert_eq!(light.effective_shadow_distance(20.0), 20.0);
+
+        light.set_shadow_distance(Some(5.0));
+        assert_eq!(light.effective_shadow_distance(20.0), 5.0);
+        // Own shadow distance cannot exceed the global one.
+        assert_eq!(light.effective_shadow_distance(3.0), 3.0);
+
+        light.set_shadow_distance(Some(-1.0));
+        assert_eq!(light.shadow_distance(), Some(0.0));
+
+        let mut light = BaseLightBuilder::new(BaseBuilder::new())
+            .with_shadow_distance(7.0)
+            .build();
+        assert_eq!(light.raw_copy().shadow_distance(), Some(7.0));
+
+        let path = std::env::temp_dir().join("rg3d_light_shadow_distance_test.bin");
+        let mut visitor = Visitor::new();
+        light.visit("Light", &mut visitor).unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        let mut visitor = Visitor::load_binary(&path).unwrap();
+        let mut loaded = BaseLight::default();
+        loaded.visit("Light", &mut visitor).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(loaded.shadow_distance(), Some(7.0));
+    }
+}
@@ -8,12 +8,12 @@
 //! modelling software or just download some model you like and load it in engine. But since
 //! 3d model can contain multiple nodes, 3d model loading discussed in model resource section.
 
-use crate::core::algebra::{Matrix4, Point3, Vector3};
+use crate::core::algebra::{Matrix4, Point3, Vector2, Vector3};
 use crate::core::pool::Handle;
 use crate::{
     core::{
         color::Color,
-        math::{aabb::AxisAlignedBoundingBox, frustum::Frustum},
+        math::{aabb::AxisAlignedBoundingBox, frustum::Frustum, get_barycentric_coords, ray::Ray},
         visitor::{Visit, VisitResult, Visitor},
     },
     renderer::surface::Surface,
@@ -22,18 +22,41 @@ use crate::{
         graph::Graph,
         node::Node,
     },
+    utils::simplify,
 };
 use std::{
-    cell::Cell,
+    cell::{Cell, RefCell},
     ops::{Deref, DerefMut},
+    sync::{Arc, RwLock},
 };
 
+/// Cached bounds of a surface in bind pose.
+#[derive(Clone, Debug, Default)]
+struct SurfaceBounds {
+    /// Bounds of every vertex of the surface, `None` if surface has no vertices.
+    local: Option<AxisAlignedBoundingBox>,
+    /// Bounds of vertices influenced by each bone, indices match indices in `Surface::bones`.
+    /// Empty for surfaces without bones.
+    bones: Vec<Option<AxisAlignedBoundingBox>>,
+}
+
+fn add_transformed_box(
+    bounding_box: &mut AxisAlignedBoundingBox,
+    other: &AxisAlignedBoundingBox,
+    transform: &Matrix4<f32>,
+) {
+    for corner in other.corners().iter() {
+        bounding_box.add_point(transform.transform_point(&Point3::from(*corner)).coords);
+    }
+}
+
 /// See module docs.
 #[derive(Debug)]
 pub struct Mesh {
     base: Base,
     surfaces: Vec<Surface>,
     bounding_box: Cell<AxisAlignedBoundingBox>,
+    surface_bounds: RefCell<Vec<SurfaceBounds>>,
     bounding_box_dirty: Cell<bool>,
     cast_shadows: bool,
 }
@@ -44,6 +67,7 @@ impl Default for Mesh {
             base: Default::default(),
             surfaces: Default::default(),
             bounding_box: Default::default(),
+            surface_bounds: Default::default(),
             bounding_box_dirty: Cell::new(true),
             cast_shadows: true,
         }
@@ -89,6 +113,8 @@ impl Mesh {
     /// Returns mutable reference to array of surfaces.
     #[inline]
     pub fn surfaces_mut(&mut self) -> &mut [Surface] {
+        // Surfaces could be changed in any way, so cached render data must be refreshed.
+        self.invalidate_version();
         &mut self.surfaces
     }
 
@@ -97,6 +123,7 @@ impl Mesh {
     pub fn clear_surfaces(&mut self) {
         self.surfaces.clear();
         self.bounding_box_dirty.set(true);
+        self.invalidate_version();
     }
 
     /// Adds new surface into mesh, can be used to procedurally generate meshes.
@@ -104,6 +131,7 @@ impl Mesh {
     pub fn add_surface(&mut self, surface: Surface) {
         self.surfaces.push(surface);
         self.bounding_box_dirty.set(true);
+        self.invalidate_version();
     }
 
     /// Applies given color to all surfaces.
@@ -112,6 +140,7 @@ impl Mesh {
         for surface in self.surfaces.iter_mut() {
             surface.set_color(color);
         }
+        self.invalidate_version();
     }
 
     /// Returns true if mesh should cast shadows, false - otherwise.
@@ -126,24 +155,87 @@ impl Mesh {
         self.cast_shadows = cast_shadows;
     }
 
-    /// Performs lazy bounding box evaluation. Bounding box presented in *local coordinates*
-    /// WARNING: This method does *not* includes bounds of bones!
-    pub fn bounding_box(&self) -> AxisAlignedBoundingBox {
+    fn update_bounds(&self) {
         if self.bounding_box_dirty.get() {
             let mut bounding_box = AxisAlignedBoundingBox::default();
+            let mut surface_bounds = self.surface_bounds.borrow_mut();
+            surface_bounds.clear();
             for surface in self.surfaces.iter() {
                 let data = surface.data();
                 let data = data.read().unwrap();
+                let mut bounds = SurfaceBounds {
+                    local: None,
+                    bones: vec![None; surface.bones.len()],
+                };
                 for vertex in data.get_vertices() {
                     bounding_box.add_point(vertex.position);
+                    bounds
+                        .local
+                        .get_or_insert_with(Default::default)
+                        .add_point(vertex.position);
+                    for (&bone_index, &weight) in
+                        vertex.bone_indices.iter().zip(vertex.bone_weights.iter())
+                    {
+                        if weight > 0.0 {
+                            if let Some(bone_bounds) = bounds.bones.get_mut(bone_index as usize) {
+                                bone_bounds
+                                    .get_or_insert_with(Default::default)
+                                    .add_point(vertex.position);
+                            }
+                        }
+                    }
                 }
+                surface_bounds.push(bounds);
             }
             self.bounding_box.set(bounding_box);
             self.bounding_box_dirty.set(false);
         }
+    }
+
+    /// Performs lazy bounding box evaluation. Bounding box presented in *local coordinates*
+    /// WARNING: This method does *not* includes bounds of bones!
+    pub fn bounding_box(&self) -> AxisAlignedBoundingBox {
+        self.update_bounds();
         self.bounding_box.get()
     }
 
+    /// Calculates conservative bounding box in *world coordinates* which follows current pose of
+    /// bones. Unlike `full_world_bounding_box` it does not transform every vertex: bounds of
+    /// vertices influenced by each bone are calculated once (in bind pose) and then every frame
+    /// only corners of these boxes are transformed by bone matrices. Since a skinned vertex is a
+    /// weighted sum of its positions transformed by each of its bones, it always lies inside
+    /// of the resulting box. Surfaces without bones use global transform of the mesh.
+    ///
+    /// This method is cheap enough to be used every frame, renderer uses it for frustum culling
+    /// of skinned meshes.
+    pub fn animated_world_bounding_box(&self, graph: &Graph) -> AxisAlignedBoundingBox {
+        self.update_bounds();
+        let mut bounding_box = AxisAlignedBoundingBox::default();
+        let surface_bounds = self.surface_bounds.borrow();
+        for (surface, bounds) in self.surfaces.iter().zip(surface_bounds.iter()) {
+            if surface.bones.is_empty() {
+                if let Some(local) = bounds.local.as_ref() {
+                    add_transformed_box(&mut bounding_box, local, &self.global_transform());
+                }
+            } else {
+                for (&bone, bone_bounds) in surface.bones.iter().zip(bounds.bones.iter()) {
+                    if let Some(bone_bounds) = bone_bounds.as_ref() {
+                        let bone_node = &graph[bone];
+                        let bone_matrix =
+                            bone_node.global_transform() * bone_node.inv_bind_pose_transform();
+                        add_transformed_box(&mut bounding_box, bone_bounds, &bone_matrix);
+                    }
+                }
+            }
+        }
+        bounding_box
+    }
+
+    /// Returns true if at least one surface of the mesh is skinned.
+    pub fn is_skinned(&self) -> bool {
+        self.surfaces.iter().any(|s| !s.bones.is_empty())
+    }
+
     /// Calculate bounding box in *world coordinates*. This method is very heavy and not
     /// intended to use every frame! WARNING: This method does *not* includes bounds of bones!
     pub fn world_bounding_box(&self) -> AxisAlignedBoundingBox {
@@ -209,24 +301,58 @@ impl Mesh {
         bounding_box
     }
 
-    /// Performs frustum visibility test. It uses mesh bounding box *and* positions of bones.
-    /// Mesh is considered visible if its bounding box visible by frustum, or if any bones
-    /// position is inside frustum.
+    /// Performs frustum visibility test. Skinned meshes are tested using bounds that follow
+    /// current pose of bones (see `animated_world_bounding_box`), other meshes - using local
+    /// bounding box and global transform.
     pub fn is_intersect_frustum(&self, graph: &Graph, frustum: &Frustum) -> bool {
-        if frustum.is_intersects_aabb_transform(&self.bounding_box(), &self.global_transform.get())
-        {
-            return true;
+        if self.is_skinned() {
+            frustum.is_intersects_aabb(&self.animated_world_bounding_box(graph))
+        } else {
+            frustum.is_intersects_aabb_transform(&self.bounding_box(), &self.global_transform.get())
         }
+    }
 
+    /// Finds closest intersection of a ray (in world coordinates) with surfaces of the mesh and
+    /// returns interpolated texture coordinates at the intersection point. The ray is treated
+    /// as a segment, see `Ray::from_two_points`. Bones are not taken into account. It is useful
+    /// to project input onto a surface with a user interface rendered to a texture (see
+    /// `Renderer::render_ui_to_texture`). This method checks every triangle, so it is
+    /// intended to be used with simple meshes only.
+    pub fn ray_cast_tex_coords(&self, ray: &Ray) -> Option<Vector2<f32>> {
+        let inv_transform = self.global_transform().try_inverse()?;
+        let local_ray = Ray {
+            origin: inv_transform
+                .transform_point(&Point3::from(ray.origin))
+                .coords,
+            dir: inv_transform.transform_vector(&ray.dir),
+        };
+
+        let mut closest = None;
+        let mut closest_distance = std::f32::MAX;
         for surface in self.surfaces.iter() {
-            for &bone in surface.bones.iter() {
-                if frustum.is_contains_point(graph[bone].global_position()) {
-                    return true;
+            let data = surface.data();
+            let data = data.read().unwrap();
+            let vertices = data.get_vertices();
+            for triangle in data.triangles() {
+                let a = &vertices[triangle[0] as usize];
+                let b = &vertices[triangle[1] as usize];
+                let c = &vertices[triangle[2] as usize];
+                if let Some(point) =
+                    local_ray.triangle_intersection(&[a.position, b.position, c.position])
+                {
+                    let distance = (point - local_ray.origin).norm_squared();
+                    if distance < closest_distance {
+                        closest_distance = distance;
+                        let (u, v, w) =
+                            get_barycentric_coords(&point, &a.position, &b.position, &c.position);
+                        closest = Some(
+                            a.tex_coord.scale(u) + b.tex_coord.scale(v) + c.tex_coord.scale(w),
+                        );
+                    }
                 }
             }
         }
-
-        false
+        closest
     }
 
     /// Creates a raw copy of a mesh node.
@@ -235,10 +361,26 @@ impl Mesh {
             base: self.base.raw_copy(),
             surfaces: self.surfaces.clone(),
             bounding_box: self.bounding_box.clone(),
+            surface_bounds: self.surface_bounds.clone(),
             bounding_box_dirty: self.bounding_box_dirty.clone(),
             cast_shadows: self.cast_shadows,
         }
     }
+
+    /// Creates a raw copy of the mesh with simplified geometry, each surface of the copy has
+    /// approximately `target_ratio` of triangles of respective source surface (0.5 means half
+    /// of triangles). It is useful to make meshes for lower levels of details of a lod group
+    /// (see [LodGroup](crate::scene::base::LodGroup)). See [simplify] module docs for more info
+    /// about the algorithm and its limitations.
+    pub fn simplify(&self, target_ratio: f32) -> Self {
+        let mut mesh = self.raw_copy();
+        for surface in mesh.surfaces.iter_mut() {
+            let data = simplify::simplify(&surface.data().read().unwrap(), target_ratio);
+            surface.set_data(Arc::new(RwLock::new(data)));
+        }
+        mesh.bounding_box_dirty.set(true);
+        mesh
+    }
 }
 
 /// Mesh builder allows you to construct mesh in declarative manner.
@@ -277,6 +419,7 @@ impl MeshBuilder {
             cast_shadows: self.cast_shadows,
             surfaces: self.surfaces,
             bounding_box: Default::default(),
+            surface_bounds: Default::default(),
             bounding_box_dirty: Cell::new(true),
         })
     }
@@ -286,3 +429,121 @@ impl MeshBuilder {
         graph.add_node(self.build_node())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            algebra::{Matrix4, Point3, Vector2, Vector3},
+            math::{frustum::Frustum, ray::Ray, TriangleDefinition},
+        },
+        renderer::surface::{Surface, SurfaceSharedData, Vertex},
+        scene::{base::BaseBuilder, graph::Graph, mesh::MeshBuilder, transform::TransformBuilder},
+    };
+    use std::sync::{Arc, RwLock};
+
+    fn skinned_vertex(position: Vector3<f32>, bone_index: u8) -> Vertex {
+        Vertex {
+            position,
+            bone_weights: [1.0, 0.0, 0.0, 0.0],
+            bone_indices: [bone_index, 0, 0, 0],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_skinned_mesh_bounds_follow_pose() {
+        let mut graph = Graph::new();
+
+        let root_bone = BaseBuilder::new().build(&mut graph);
+        let arm_position = Vector3::new(0.0, 1.0, 0.0);
+        let arm_bone = BaseBuilder::new()
+            .with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(arm_position)
+                    .build(),
+            )
+            .with_inv_bind_pose_transform(Matrix4::new_translation(&-arm_position))
+            .build(&mut graph);
+
+        // Small torso around origin and an arm in front of it.
+        let vertices = vec![
+            skinned_vertex(Vector3::new(-0.5, -0.5, -0.5), 0),
+            skinned_vertex(Vector3::new(0.5, 0.5, 0.5), 0),
+            skinned_vertex(Vector3::new(0.0, 1.0, 0.5), 1),
+            skinned_vertex(Vector3::new(0.1, 1.1, 1.0), 1),
+        ];
+        let data = SurfaceSharedData::new(
+            vertices,
+            vec![TriangleDefinition([0, 1, 2]), TriangleDefinition([1, 2, 3])],
+            true,
+        );
+        let mut surface = Surface::new(Arc::new(RwLock::new(data)));
+        surface.bones = vec![root_bone, arm_bone];
+        let mesh = MeshBuilder::new(BaseBuilder::new())
+            .with_surfaces(vec![surface])
+            .build(&mut graph);
+        graph.update_hierarchical_data();
+
+        // Narrow camera which looks from the side at a point 3.75 meters in front of the
+        // character, it sees neither the character itself nor position of the arm bone.
+        let view = Matrix4::look_at_rh(
+            &Point3::new(5.0, 1.0, 3.75),
+            &Point3::new(0.0, 1.0, 3.75),
+            &Vector3::y(),
+        );
+        let projection = Matrix4::new_perspective(1.0, 10.0f32.to_radians(), 0.1, 100.0);
+        let frustum = Frustum::from(projection * view).unwrap();
+
+        assert!(!graph[mesh].as_mesh().is_intersect_frustum(&graph, &frustum));
+
+        // Lunge - arm extends 3 meters forward.
+        graph[arm_bone]
+            .local_transform_mut()
+            .set_position(arm_position + Vector3::new(0.0, 0.0, 3.0));
+        graph.update_hierarchical_data();
+
+        let mesh = graph[mesh].as_mesh();
+        // Bind pose bounds do not contain the arm anymore.
+        assert!(
+            !frustum.is_intersects_aabb_transform(&mesh.bounding_box(), &mesh.global_transform())
+        );
+        assert!(mesh.is_intersect_frustum(&graph, &frustum));
+
+        let bounds = mesh.animated_world_bounding_box(&graph);
+        assert!(bounds.is_contains_point(Vector3::new(0.1, 1.1, 4.0)));
+        assert!(bounds.is_contains_point(Vector3::new(-0.5, -0.5, -0.5)));
+    }
+
+    #[test]
+    fn test_ray_cast_tex_coords() {
+        let mut graph = Graph::new();
+        let data = SurfaceSharedData::make_unit_xy_quad();
+        let mesh = MeshBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(Vector3::new(10.0, 0.0, 0.0))
+                    .build(),
+            ),
+        )
+        .with_surfaces(vec![Surface::new(Arc::new(RwLock::new(data)))])
+        .build(&mut graph);
+        graph.update_hierarchical_data();
+        let mesh = graph[mesh].as_mesh();
+
+        let ray = Ray::from_two_points(
+            &Vector3::new(10.25, 0.75, 1.0),
+            &Vector3::new(10.25, 0.75, -1.0),
+        )
+        .unwrap();
+        let tex_coords = mesh.ray_cast_tex_coords(&ray).unwrap();
+        assert!((tex_coords - Vector2::new(0.25, 0.25)).norm() < 1.0e-5);
+
+        let miss = Ray::from_two_points(
+            &Vector3::new(0.25, 0.75, 1.0),
+            &Vector3::new(0.25, 0.75, -1.0),
+        )
+        .unwrap();
+        assert_eq!(mesh.ray_cast_tex_coords(&miss), None);
+    }
+}
@@ -3,34 +3,75 @@
 //! Contains all structures and methods to create and manage scenes.
 //!
 //! Scene is container for graph nodes, animations and physics.
+//!
+//! # Update order
+//!
+//! Animation poses are applied to graph by game code (see `AnimationPose::apply`), because only
+//! game code knows which animations or animation machines should drive a model. This should be
+//! done before the scene is updated, then `Scene::update` does the following:
+//!
+//! 1. Animations are advanced in time, new poses will be available for the next frame.
+//! 2. Rigid bodies of nodes owned by animation (see `PhysicsBinder::set_physics_weight`) are
+//!    moved to the current (animated) transforms of their nodes.
+//! 3. Physics is simulated, trigger events are generated (see `Physics::drain_trigger_events`).
+//! 4. Transforms of nodes owned by physics are replaced with transforms of their rigid bodies,
+//!    nodes with partial physics weight get a blend of animated and simulated transforms.
+//!    Nodes owned by animation are left untouched.
+//! 5. Graph is updated - global transforms, particle systems, etc.
+//!
+//! So for every node bound to a rigid body exactly one system wins, or they are blended in
+//! a well defined way - for example ragdoll can be smoothly activated by changing physics
+//! weight of its bones from 0 to 1.
+//!
+//! # Time control
+//!
+//! Each scene has its own time scale (`Scene::time_scale`) which is applied to delta time of
+//! animations, graph nodes (particle systems, lifetimes, etc.) and to time step of physics, so
+//! slow motion can be done per scene. A scene can be paused (`Scene::paused`) - animations and
+//! physics won't be updated, graph nodes will be updated with zero delta time, so cameras and
+//! transforms are still correct. Other scenes keep running.
+//!
+//! The engine is supposed to be updated with fixed time step, real time is accumulated by
+//! game loop and each step is passed to every scene, paused scenes just skip simulation.
+//!
+//! Sound sources can be attached to a scene (see `Scene::add_sound_source`), then they're
+//! paused together with the scene and optionally their pitch is scaled by time scale of the
+//! scene (see `Scene::scale_sound_pitch`).
 
 pub mod base;
 pub mod camera;
+pub mod fog;
 pub mod graph;
 pub mod light;
 pub mod mesh;
 pub mod node;
 pub mod particle_system;
 pub mod physics;
+pub mod post_effects;
 pub mod sprite;
+pub mod terrain;
 pub mod transform;
 
 use crate::utils::log::MessageKind;
 use crate::{
     animation::AnimationContainer,
     core::{
-        algebra::{Matrix4, Vector2, Vector3},
+        algebra::{Matrix3, Matrix4, Rotation3, UnitQuaternion, Vector2, Vector3},
         color::Color,
         math::{aabb::AxisAlignedBoundingBox, frustum::Frustum, Matrix4Ext},
         pool::{Handle, Pool, PoolIterator, PoolIteratorMut},
         visitor::{Visit, VisitError, VisitResult, Visitor},
     },
     engine::resource_manager::ResourceManager,
-    resource::texture::Texture,
-    scene::{graph::Graph, node::Node, physics::Physics},
+    resource::texture::{Texture, TextureColorSpace, TextureState},
+    scene::{fog::FogSettings, graph::Graph, light::Light, node::Node, physics::Physics},
+    sound::{
+        context::{Context, SourceDebugInfo},
+        source::{SoundSource, Status},
+    },
     utils::{lightmap::Lightmap, log::Log},
 };
-use rapier3d::na::Point3;
+use rapier3d::na::{Isometry3, Point3, Translation3};
 use std::{
     collections::HashMap,
     ops::{Index, IndexMut},
@@ -111,6 +152,9 @@ pub struct PhysicsBinder {
     /// Whether binder is enabled or not. If binder is disabled, it won't synchronize
     /// node's transform with body's transform.
     pub enabled: bool,
+
+    /// Mapping Node -> Physics weight, only weights that differ from 1.0 are stored.
+    physics_weights: HashMap<Handle<Node>, f32>,
 }
 
 impl Default for PhysicsBinder {
@@ -118,6 +162,7 @@ impl Default for PhysicsBinder {
         Self {
             node_rigid_body_map: Default::default(),
             enabled: true,
+            physics_weights: Default::default(),
         }
     }
 }
@@ -134,9 +179,37 @@ impl PhysicsBinder {
 
     /// Unlinks given graph node from its associated rigid body (if any).
     pub fn unbind(&mut self, node: Handle<Node>) -> Option<RigidBodyHandle> {
+        self.physics_weights.remove(&node);
         self.node_rigid_body_map.remove(&node)
     }
 
+    /// Defines which system drives transform of given node when the node is both linked with
+    /// a rigid body and animated. Weight is clamped to `[0; 1]` range:
+    ///
+    /// - 1.0 (default) - physics owns the node, transform of the node is replaced with transform
+    ///   of the body every frame, animation has no effect.
+    /// - 0.0 - animation owns the node, the body is moved to transform of the node before
+    ///   simulation. Kinematic bodies are moved using next kinematic position, so they get
+    ///   correct velocities and push other bodies; dynamic bodies are teleported and their
+    ///   velocities are reset.
+    /// - Values in between - transform of the node is a blend of animated and simulated
+    ///   transforms, the body is simulated freely. This allows to blend ragdoll with animation.
+    ///
+    /// See "Update order" section of scene module docs for more info.
+    pub fn set_physics_weight(&mut self, node: Handle<Node>, weight: f32) {
+        let weight = weight.max(0.0).min(1.0);
+        if weight >= 1.0 {
+            self.physics_weights.remove(&node);
+        } else {
+            self.physics_weights.insert(node, weight);
+        }
+    }
+
+    /// Returns physics weight of given node, see `set_physics_weight` for more info.
+    pub fn physics_weight(&self, node: Handle<Node>) -> f32 {
+        self.physics_weights.get(&node).copied().unwrap_or(1.0)
+    }
+
     /// Unlinks given body from a node that is linked with the body.
     ///
     /// # Performance
@@ -162,6 +235,7 @@ impl PhysicsBinder {
                 }
             })
             .collect();
+        self.physics_weights.remove(&node);
         node
     }
 
@@ -188,6 +262,7 @@ impl Visit for PhysicsBinder {
 
         self.node_rigid_body_map.visit("Map", visitor)?;
         let _ = self.enabled.visit("Enabled", visitor);
+        let _ = self.physics_weights.visit("PhysicsWeights", visitor);
 
         visitor.leave_region()
     }
@@ -713,6 +788,39 @@ impl SceneDrawingContext {
         }
     }
 
+    /// Draws every playing or paused spatial source of given sound context. Each source is
+    /// drawn as a sphere which is sized by gain of the source as it is heard by listener, and
+    /// a line from the source to the listener. See `draw_sound_source` for more info.
+    pub fn draw_sound_sources(&mut self, context: &Context) {
+        let listener = context.listener().position();
+        for info in context.debug_sources() {
+            self.draw_sound_source(&info, listener);
+        }
+    }
+
+    /// Draws a single sound source. Playing sources are green, paused - yellow. Radius of the
+    /// sphere is radius of the source multiplied by its effective gain, so inaudible sources
+    /// are drawn as small spheres. Line to the listener is colored by effective gain, from
+    /// black (inaudible) to white (full volume). Generic sources have no position and are not
+    /// drawn.
+    pub fn draw_sound_source(&mut self, info: &SourceDebugInfo, listener: Vector3<f32>) {
+        if let Some(position) = info.position {
+            let color = match info.status {
+                Status::Paused => Color::opaque(255, 255, 0),
+                _ => Color::GREEN,
+            };
+            let radius = (info.radius * info.effective_gain).max(0.05);
+            self.draw_sphere(position, 8, 8, radius, color);
+
+            let intensity = (info.effective_gain.max(0.0).min(1.0) * 255.0) as u8;
+            self.add_line(Line {
+                begin: position,
+                end: listener,
+                color: Color::opaque(intensity, intensity, intensity),
+            });
+        }
+    }
+
     /// Adds single line into internal buffer.
     pub fn add_line(&mut self, line: Line) {
         self.lines.push(line);
@@ -757,7 +865,34 @@ pub struct Scene {
     /// Drawing context for simple graphics.
     pub drawing_context: SceneDrawingContext,
 
+    /// Scale of time of the scene, 1.0 is real time, values less than 1.0 give slow motion and
+    /// values greater than 1.0 speed up the scene. Zero freezes the scene just like pause.
+    /// See "Time control" section of module docs.
+    pub time_scale: f32,
+
+    /// Paused scene is not simulated, but still updated with zero delta time. See "Time control"
+    /// section of module docs.
+    pub paused: bool,
+
+    /// Whether pitch of attached sound sources should be multiplied by time scale of the scene
+    /// or not. While enabled, pitch of a source is overwritten by the pitch it had when scaling
+    /// was started multiplied by time scale. Default is false.
+    pub scale_sound_pitch: bool,
+
     lightmap: Option<Lightmap>,
+
+    fog: Option<FogSettings>,
+
+    sound_sources: Vec<SceneSoundSource>,
+}
+
+#[derive(Debug, Clone)]
+struct SceneSoundSource {
+    handle: Handle<SoundSource>,
+    // Pitch of the source before scaling was applied.
+    pitch: Option<f64>,
+    // Whether the source was paused because of pause of the scene.
+    paused: bool,
 }
 
 impl Default for Scene {
@@ -770,14 +905,48 @@ impl Default for Scene {
             render_target: None,
             lightmap: None,
             drawing_context: Default::default(),
+            fog: None,
+            time_scale: 1.0,
+            paused: false,
+            scale_sound_pitch: false,
+            sound_sources: Default::default(),
         }
     }
 }
 
 fn map_texture(tex: Option<Texture>, rm: ResourceManager) -> Option<Texture> {
+    request_texture(tex, rm, false)
+}
+
+// Data textures (metallic, roughness, etc.) must be requested as linear even if they were
+// loaded as sRGB before, otherwise their values will be distorted.
+fn map_linear_texture(tex: Option<Texture>, rm: ResourceManager) -> Option<Texture> {
+    request_texture(tex, rm, true)
+}
+
+fn request_texture(tex: Option<Texture>, rm: ResourceManager, linear: bool) -> Option<Texture> {
     if let Some(shallow_texture) = tex {
-        let shallow_texture = shallow_texture.state();
-        Some(rm.request_texture(shallow_texture.path()))
+        let state = shallow_texture.state();
+        let mut linear = linear;
+        // Procedural textures are saved with their pixels, so they're already fully loaded.
+        if let TextureState::Ok(data) = &*state {
+            if data.is_procedural() {
+                drop(state);
+                return Some(shallow_texture);
+            }
+            // Keep color space of data textures (normal maps, lightmaps, etc.).
+            linear |= data.color_space() == TextureColorSpace::Linear;
+        }
+        if linear {
+            let options = rm
+                .state()
+                .textures_import_options()
+                .clone()
+                .with_color_space(TextureColorSpace::Linear);
+            Some(rm.request_texture_with_options(state.path(), options))
+        } else {
+            Some(rm.request_texture(state.path()))
+        }
     } else {
         None
     }
@@ -801,6 +970,11 @@ impl Scene {
             render_target: None,
             lightmap: None,
             drawing_context: Default::default(),
+            fog: None,
+            time_scale: 1.0,
+            paused: false,
+            scale_sound_pitch: false,
+            sound_sources: Default::default(),
         }
     }
 
@@ -857,6 +1031,34 @@ impl Scene {
                             resource_manager.clone(),
                         ));
 
+                        surface.set_emissive_texture(map_texture(
+                            surface.emissive_texture(),
+                            resource_manager.clone(),
+                        ));
+
+                        if let Some(pbr) = surface.pbr_material_mut() {
+                            pbr.set_albedo_texture(map_texture(
+                                pbr.albedo_texture(),
+                                resource_manager.clone(),
+                            ));
+                            pbr.set_metallic_texture(map_linear_texture(
+                                pbr.metallic_texture(),
+                                resource_manager.clone(),
+                            ));
+                            pbr.set_roughness_texture(map_linear_texture(
+                                pbr.roughness_texture(),
+                                resource_manager.clone(),
+                            ));
+                            pbr.set_ao_texture(map_linear_texture(
+                                pbr.ao_texture(),
+                                resource_manager.clone(),
+                            ));
+                            pbr.set_emissive_texture(map_texture(
+                                pbr.emissive_texture(),
+                                resource_manager.clone(),
+                            ));
+                        }
+
                         // Do not resolve lightmap texture here, it makes no sense anyway,
                         // it will be resolved below.
                     }
@@ -886,6 +1088,33 @@ impl Scene {
                         skybox.back = map_texture(skybox.back.clone(), resource_manager.clone());
                     }
                 }
+                Node::Light(Light::Spot(spot)) => {
+                    if let Some(cookie) =
+                        map_texture(spot.cookie_texture().cloned(), resource_manager.clone())
+                    {
+                        spot.set_cookie_texture(cookie);
+                    }
+                }
+                Node::Terrain(terrain) => {
+                    // Heights are saved with the scene, they will be re-read from heightmap
+                    // only when it is loaded.
+                    terrain
+                        .set_heightmap(map_texture(terrain.heightmap(), resource_manager.clone()));
+                    terrain.set_control_map(map_texture(
+                        terrain.control_map(),
+                        resource_manager.clone(),
+                    ));
+                    for layer in terrain.layers_mut() {
+                        layer.set_diffuse_texture(map_texture(
+                            layer.diffuse_texture(),
+                            resource_manager.clone(),
+                        ));
+                        layer.set_normal_texture(map_texture(
+                            layer.normal_texture(),
+                            resource_manager.clone(),
+                        ));
+                    }
+                }
                 _ => (),
             }
         }
@@ -904,9 +1133,45 @@ impl Scene {
         Ok(scene)
     }
 
-    fn update_physics(&mut self) {
-        self.physics.step();
+    // Rigid bodies are always in world space, while nodes store transform relative to their
+    // parent, so global transform of parent is used to convert poses between these spaces.
+    // Returns parent's global transform, its inverse and its rotation without scale.
+    fn parent_space(
+        &self,
+        node: Handle<Node>,
+    ) -> (Matrix4<f32>, Matrix4<f32>, UnitQuaternion<f32>) {
+        let parent = self.graph[node].parent();
+        if parent.is_none() {
+            return (
+                Matrix4::identity(),
+                Matrix4::identity(),
+                UnitQuaternion::identity(),
+            );
+        }
 
+        let transform = self.graph[parent].global_transform();
+        match transform.try_inverse() {
+            Some(inverse) => {
+                let basis = transform.basis();
+                let rotation = UnitQuaternion::from_rotation_matrix(
+                    &Rotation3::from_matrix_unchecked(Matrix3::from_columns(&[
+                        basis.column(0).normalize(),
+                        basis.column(1).normalize(),
+                        basis.column(2).normalize(),
+                    ])),
+                );
+                (transform, inverse, rotation)
+            }
+            // Degenerated parent (zero scale), there is no meaningful local pose.
+            None => (
+                Matrix4::identity(),
+                Matrix4::identity(),
+                UnitQuaternion::identity(),
+            ),
+        }
+    }
+
+    fn update_physics(&mut self) {
         // Keep pair when node and body are both alive.
         let graph = &self.graph;
         let physics = &self.physics;
@@ -915,15 +1180,72 @@ impl Scene {
             .retain(|node, body| {
                 graph.is_valid_handle(*node) && physics.bodies.contains(body.clone().into())
             });
+        let node_rigid_body_map = &self.physics_binder.node_rigid_body_map;
+        self.physics_binder
+            .physics_weights
+            .retain(|node, _| node_rigid_body_map.contains_key(node));
+
+        // Move bodies owned by animation to their nodes.
+        if self.physics_binder.enabled {
+            for (&node, &weight) in self.physics_binder.physics_weights.iter() {
+                if weight <= 0.0 {
+                    let (parent_transform, _, parent_rotation) = self.parent_space(node);
+                    let transform = self.graph[node].local_transform();
+                    let position = Isometry3::from_parts(
+                        Translation3::from(
+                            parent_transform
+                                .transform_point(&Point3::from(transform.position()))
+                                .coords,
+                        ),
+                        parent_rotation * transform.rotation(),
+                    );
+                    let body = self.physics_binder.node_rigid_body_map[&node];
+                    let body = self.physics.bodies.get_mut(body.into()).unwrap();
+                    if body.is_kinematic() {
+                        body.set_next_kinematic_position(position);
+                    } else {
+                        body.set_position(position, true);
+                        body.set_linvel(Default::default(), true);
+                        body.set_angvel(Default::default(), true);
+                    }
+                }
+            }
+        }
+
+        // Physics is stepped with fixed time step from integration parameters, so time scale
+        // is applied to the step itself.
+        let dt = self.physics.integration_parameters.dt();
+        self.physics
+            .integration_parameters
+            .set_dt(dt * self.time_scale);
+        self.physics.step();
+        self.physics.integration_parameters.set_dt(dt);
+        self.physics
+            .update_trigger_events(&self.physics_binder, &self.graph);
 
         // Sync node positions with assigned physics bodies
         if self.physics_binder.enabled {
             for (&node, &body) in self.physics_binder.node_rigid_body_map.iter() {
-                let body = physics.bodies.get(body.into()).unwrap();
-                self.graph[node]
-                    .local_transform_mut()
-                    .set_position(body.position().translation.vector)
-                    .set_rotation(body.position().rotation);
+                let weight = self.physics_binder.physics_weight(node);
+                if weight <= 0.0 {
+                    continue;
+                }
+                let (_, parent_inverse_transform, parent_rotation) = self.parent_space(node);
+                let body = self.physics.bodies.get(body.into()).unwrap();
+                let body_position = parent_inverse_transform
+                    .transform_point(&Point3::from(body.position().translation.vector))
+                    .coords;
+                let body_rotation = parent_rotation.inverse() * body.position().rotation;
+                let transform = self.graph[node].local_transform_mut();
+                if weight >= 1.0 {
+                    transform
+                        .set_position(body_position)
+                        .set_rotation(body_rotation);
+                } else {
+                    let position = transform.position().lerp(&body_position, weight);
+                    let rotation = transform.rotation().nlerp(&body_rotation, weight);
+                    transform.set_position(position).set_rotation(rotation);
+                }
             }
         }
     }
@@ -935,6 +1257,8 @@ impl Scene {
     /// Panics if handle is invalid.
     pub fn remove_node(&mut self, handle: Handle<Node>) {
         for descendant in self.graph.traverse_handle_iter(handle) {
+            self.physics.remove_trigger_events_of(descendant);
+
             // Remove all associated animations.
             self.animations.retain(|animation| {
                 for track in animation.get_tracks() {
@@ -956,7 +1280,7 @@ impl Scene {
         self.animations.resolve(&self.graph);
 
         self.graph.update_hierarchical_data();
-        self.physics.resolve(&self.physics_binder, &self.graph);
+        self.physics.resolve(&mut self.physics_binder, &self.graph);
 
         // Re-apply lightmap if any. This has to be done after resolve because we must patch surface
         // data at this stage, but if we'd do this before we wouldn't be able to do this because
@@ -980,8 +1304,7 @@ impl Scene {
                 if let Some(patch) = lightmap.patches.get(&data.id()) {
                     data.triangles = patch.triangles.clone();
                     for &v in patch.additional_vertices.iter() {
-                        let vertex = data.vertices[v as usize];
-                        data.vertices.push(vertex);
+                        data.duplicate_vertex(v as usize);
                     }
                     assert_eq!(data.vertices.len(), patch.second_tex_coords.len());
                     for (v, &tex_coord) in
@@ -1032,15 +1355,173 @@ impl Scene {
         Ok(std::mem::replace(&mut self.lightmap, Some(lightmap)))
     }
 
+    /// Enables distance fog with given settings. See `fog` module docs for more info.
+    pub fn set_fog(&mut self, fog: FogSettings) {
+        self.fog = Some(fog);
+    }
+
+    /// Disables fog and returns its previous settings, if any.
+    pub fn remove_fog(&mut self) -> Option<FogSettings> {
+        self.fog.take()
+    }
+
+    /// Returns current fog settings, `None` means that the scene has no fog.
+    pub fn fog(&self) -> Option<&FogSettings> {
+        self.fog.as_ref()
+    }
+
+    /// Returns mutable reference to current fog settings, if any.
+    pub fn fog_mut(&mut self) -> Option<&mut FogSettings> {
+        self.fog.as_mut()
+    }
+
+    /// Returns every unique texture used by nodes of the scene (surfaces, sprites, particle
+    /// systems, skyboxes, light cookies, etc.). Could be useful to find out dependencies of
+    /// the scene, for example to preload them.
+    pub fn textures(&self) -> Vec<Texture> {
+        let mut textures: Vec<Texture> = Vec::new();
+        let mut add = |texture: Option<Texture>| {
+            if let Some(texture) = texture {
+                if textures.iter().all(|t| t.key() != texture.key()) {
+                    textures.push(texture);
+                }
+            }
+        };
+
+        for node in self.graph.linear_iter() {
+            match node {
+                Node::Mesh(mesh) => {
+                    for surface in mesh.surfaces() {
+                        add(surface.diffuse_texture());
+                        add(surface.normal_texture());
+                        add(surface.specular_texture());
+                        add(surface.roughness_texture());
+                        add(surface.lightmap_texture());
+                        add(surface.emissive_texture());
+                        if let Some(pbr) = surface.pbr_material() {
+                            add(pbr.albedo_texture());
+                            add(pbr.metallic_texture());
+                            add(pbr.roughness_texture());
+                            add(pbr.ao_texture());
+                            add(pbr.emissive_texture());
+                        }
+                    }
+                }
+                Node::Sprite(sprite) => add(sprite.texture()),
+                Node::ParticleSystem(particle_system) => add(particle_system.texture()),
+                Node::Camera(camera) => {
+                    add(camera.environment_map());
+                    if let Some(skybox) = camera.skybox_ref() {
+                        for texture in skybox.textures().iter() {
+                            add(texture.clone());
+                        }
+                    }
+                }
+                Node::Light(Light::Spot(spot)) => add(spot.cookie_texture().cloned()),
+                Node::Terrain(terrain) => {
+                    add(terrain.heightmap());
+                    add(terrain.control_map());
+                    for layer in terrain.layers() {
+                        add(layer.diffuse_texture());
+                        add(layer.normal_texture());
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        textures
+    }
+
     /// Performs single update tick with given delta time from last frame. Internally
-    /// it updates physics, animations, and each graph node. In most cases there is
-    /// no need to call it directly, engine automatically updates all available scenes.
+    /// it updates animations, physics, and each graph node, see "Update order" section of
+    /// module docs. In most cases there is no need to call it directly, engine automatically
+    /// updates all available scenes.
+    ///
+    /// Delta time is scaled by time scale of the scene, paused scene is not simulated. See
+    /// "Time control" section of module docs.
     pub fn update(&mut self, frame_size: Vector2<f32>, dt: f32) {
-        self.update_physics();
-        self.animations.update_animations(dt);
+        let dt = if self.is_frozen() {
+            0.0
+        } else {
+            self.animations.update_animations(dt * self.time_scale);
+            self.update_physics();
+            dt * self.time_scale
+        };
         self.graph.update_nodes(frame_size, dt);
     }
 
+    fn is_frozen(&self) -> bool {
+        self.paused || self.time_scale <= 0.0
+    }
+
+    /// Attaches a sound source to the scene, so it will be paused when the scene is paused and
+    /// its pitch will be scaled by time scale of the scene if `scale_sound_pitch` is set.
+    pub fn add_sound_source(&mut self, source: Handle<SoundSource>) {
+        if self.sound_sources.iter().all(|s| s.handle != source) {
+            self.sound_sources.push(SceneSoundSource {
+                handle: source,
+                pitch: None,
+                paused: false,
+            });
+        }
+    }
+
+    /// Detaches a sound source from the scene. The source is left as is - it is not resumed
+    /// if it was paused by the scene and its pitch is not restored.
+    pub fn remove_sound_source(&mut self, source: Handle<SoundSource>) {
+        self.sound_sources.retain(|s| s.handle != source);
+    }
+
+    /// Returns handles of sound sources attached to the scene.
+    pub fn sound_sources(&self) -> impl Iterator<Item = Handle<SoundSource>> + '_ {
+        self.sound_sources.iter().map(|s| s.handle)
+    }
+
+    /// Applies pause and time scale of the scene to attached sound sources, sources that were
+    /// removed from the context are detached. Engine calls this method automatically after
+    /// the scene was updated.
+    pub fn sync_sound_sources(&mut self, context: &mut Context) {
+        let frozen = self.is_frozen();
+        let scale = if self.scale_sound_pitch {
+            Some(self.time_scale as f64)
+        } else {
+            None
+        };
+        self.sound_sources
+            .retain(|s| context.sources().is_valid_handle(s.handle));
+        for entry in self.sound_sources.iter_mut() {
+            let source = context.source_mut(entry.handle);
+
+            if frozen {
+                if source.status() == Status::Playing {
+                    source.pause();
+                    entry.paused = true;
+                }
+            } else if entry.paused {
+                if source.status() == Status::Paused {
+                    source.play();
+                }
+                entry.paused = false;
+            }
+
+            match scale {
+                Some(scale) => {
+                    let pitch = *entry.pitch.get_or_insert_with(|| source.pitch());
+                    // Zero pitch is meaningless, frozen scene pauses its sources anyway.
+                    if scale > 0.0 {
+                        source.set_pitch(pitch * scale);
+                    }
+                }
+                None => {
+                    if let Some(pitch) = entry.pitch.take() {
+                        source.set_pitch(pitch);
+                    }
+                }
+            }
+        }
+    }
+
     /// Creates deep copy of a scene, filter predicate allows you to filter out nodes
     /// by your criteria.
     pub fn clone<F>(&self, filter: &mut F) -> (Self, HashMap<Handle<Node>, Handle<Node>>)
@@ -1057,17 +1538,18 @@ impl Scene {
                 track.set_node(old_new_map[&track.get_node()]);
             }
         }
-        // It is ok to use old binder here, because handles maps one-to-one.
-        let physics = self.physics.deep_copy(&self.physics_binder, &graph);
         let mut physics_binder = PhysicsBinder::default();
         for (node, &body) in self.physics_binder.node_rigid_body_map.iter() {
             // Make sure we bind existing node with new physical body.
             if let Some(&new_node) = old_new_map.get(node) {
-                // Re-use of body handle is fine here because physics copy bodies
-                // directly and handles from previous pool is still suitable for copy.
+                // Body handle is from previous pool, deep copy will remap it to
+                // the handle of the copied body.
                 physics_binder.bind(new_node, body);
+                physics_binder
+                    .set_physics_weight(new_node, self.physics_binder.physics_weight(*node));
             }
         }
+        let physics = self.physics.deep_copy(&mut physics_binder, &graph);
         (
             Self {
                 graph,
@@ -1079,6 +1561,12 @@ impl Scene {
                 render_target: Default::default(),
                 lightmap: self.lightmap.clone(),
                 drawing_context: self.drawing_context.clone(),
+                fog: self.fog,
+                time_scale: self.time_scale,
+                paused: self.paused,
+                scale_sound_pitch: self.scale_sound_pitch,
+                // Sound sources belong to the original scene.
+                sound_sources: Default::default(),
             },
             old_new_map,
         )
@@ -1093,6 +1581,7 @@ impl Visit for Scene {
         self.animations.visit("Animations", visitor)?;
         self.physics.visit("Physics", visitor)?;
         let _ = self.lightmap.visit("Lightmap", visitor);
+        let _ = self.fog.visit("Fog", visitor);
         visitor.leave_region()
     }
 }
@@ -1112,6 +1601,29 @@ impl SceneContainer {
         self.pool.pair_iter()
     }
 
+    /// Returns pair iterator which yields (handle, scene_ref) pairs, scenes can be modified.
+    pub fn pair_iter_mut(&mut self) -> impl Iterator<Item = (Handle<Scene>, &mut Scene)> {
+        self.pool.pair_iter_mut()
+    }
+
+    /// Checks if given handle points to existing scene.
+    #[inline]
+    pub fn is_valid_handle(&self, handle: Handle<Scene>) -> bool {
+        self.pool.is_valid_handle(handle)
+    }
+
+    /// Tries to borrow a scene, returns `None` if handle is invalid.
+    #[inline]
+    pub fn try_get(&self, handle: Handle<Scene>) -> Option<&Scene> {
+        self.pool.try_borrow(handle)
+    }
+
+    /// Tries to mutably borrow a scene, returns `None` if handle is invalid.
+    #[inline]
+    pub fn try_get_mut(&mut self, handle: Handle<Scene>) -> Option<&mut Scene> {
+        self.pool.try_borrow_mut(handle)
+    }
+
     /// Creates new iterator over scenes in container.
     #[inline]
     pub fn iter(&self) -> PoolIterator<Scene> {
@@ -1247,3 +1759,492 @@ impl VisibilityCache {
         self.map.get(&node).cloned().unwrap_or(false)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            algebra::{Matrix4, UnitQuaternion, Vector2, Vector3},
+            math::Matrix4Ext,
+            pool::Handle,
+            visitor::{Visit, Visitor},
+        },
+        engine::resource_manager::ResourceManager,
+        renderer::surface::{PbrMaterialBuilder, Surface, SurfaceSharedData},
+        resource::texture::TextureColorSpace,
+        scene::{
+            base::BaseBuilder,
+            light::{BaseLightBuilder, Light, SpotLightBuilder},
+            mesh::MeshBuilder,
+            node::Node,
+            physics::{TriggerEvent, TriggerEventKind},
+            transform::TransformBuilder,
+            Scene, SceneDrawingContext,
+        },
+        sound::{context::SourceDebugInfo, source::Status},
+    };
+    use rapier3d::{
+        dynamics::RigidBodyBuilder,
+        geometry::ColliderBuilder,
+        na::{Isometry3, Translation3},
+    };
+    use std::sync::{Arc, RwLock};
+
+    #[test]
+    fn test_draw_sound_source() {
+        let mut info = SourceDebugInfo {
+            handle: Handle::NONE,
+            position: None,
+            radius: 1.0,
+            max_distance: 10.0,
+            gain: 1.0,
+            effective_gain: 0.5,
+            status: Status::Playing,
+            priority: 0,
+            is_virtual: false,
+        };
+        let listener = Vector3::new(0.0, 0.0, 5.0);
+
+        // Generic sources have no position.
+        let mut context = SceneDrawingContext::default();
+        context.draw_sound_source(&info, listener);
+        assert!(context.lines.is_empty());
+
+        info.position = Some(Vector3::new(1.0, 2.0, 3.0));
+        context.draw_sound_source(&info, listener);
+        let line = context.lines.last().unwrap();
+        assert_eq!(line.begin, Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(line.end, listener);
+        // Sphere is sized by effective gain.
+        assert!(context.lines[..context.lines.len() - 1]
+            .iter()
+            .all(|l| ((l.begin - info.position.unwrap()).norm() - 0.5).abs() < 1.0e-4));
+    }
+
+    #[test]
+    fn test_physics_weight() {
+        let mut scene = Scene::new();
+
+        let add_node = |scene: &mut Scene, position: Vector3<f32>| {
+            BaseBuilder::new()
+                .with_local_transform(
+                    TransformBuilder::new()
+                        .with_local_position(position)
+                        .build(),
+                )
+                .build(&mut scene.graph)
+        };
+
+        // Falling body owned by physics.
+        let falling = add_node(&mut scene, Vector3::new(0.0, 0.0, 0.0));
+        let body = scene
+            .physics
+            .add_body(RigidBodyBuilder::new_dynamic().build());
+        scene
+            .physics
+            .add_collider(ColliderBuilder::ball(0.5).build(), body);
+        scene.physics_binder.bind(falling, body);
+
+        // Kinematic body owned by animation.
+        let animated = add_node(&mut scene, Vector3::new(0.0, 0.0, 0.0));
+        let animated_body = scene
+            .physics
+            .add_body(RigidBodyBuilder::new_kinematic().build());
+        scene.physics_binder.bind(animated, animated_body);
+        scene.physics_binder.set_physics_weight(animated, 0.0);
+
+        // Half-blended node with static body.
+        let blended = add_node(&mut scene, Vector3::new(0.0, 0.0, 0.0));
+        let blended_body = scene
+            .physics
+            .add_body(RigidBodyBuilder::new_static().build());
+        scene.physics_binder.bind(blended, blended_body);
+        scene.physics_binder.set_physics_weight(blended, 0.5);
+        assert_eq!(scene.physics_binder.physics_weight(blended), 0.5);
+
+        for _ in 0..10 {
+            // Imitate animation pose which is applied by game code.
+            let pose = Vector3::new(5.0, 2.0, 0.0);
+            scene.graph[animated]
+                .local_transform_mut()
+                .set_position(pose);
+            scene.graph[blended]
+                .local_transform_mut()
+                .set_position(pose);
+
+            scene.update(Vector2::new(100.0, 100.0), 1.0 / 60.0);
+
+            // Animation wins, body follows the node.
+            assert_eq!(scene.graph[animated].local_transform().position(), pose);
+            let body_position = scene
+                .physics
+                .bodies
+                .get(animated_body.into())
+                .unwrap()
+                .position()
+                .translation
+                .vector;
+            assert!((body_position - pose).norm() < 1.0e-5);
+
+            // Blend of animation and physics.
+            assert!(
+                (scene.graph[blended].local_transform().position() - Vector3::new(2.5, 1.0, 0.0))
+                    .norm()
+                    < 1.0e-5
+            );
+        }
+
+        // Physics wins.
+        assert!(scene.graph[falling].local_transform().position().y < 0.0);
+
+        scene.physics_binder.unbind(blended);
+        assert_eq!(scene.physics_binder.physics_weight(blended), 1.0);
+    }
+
+    #[test]
+    fn test_physics_in_parent_space() {
+        let mut scene = Scene::new();
+
+        let add_child = |scene: &mut Scene| {
+            let child = BaseBuilder::new().build(&mut scene.graph);
+            BaseBuilder::new()
+                .with_local_transform(
+                    TransformBuilder::new()
+                        .with_local_position(Vector3::new(10.0, 0.0, 0.0))
+                        .with_local_rotation(UnitQuaternion::from_axis_angle(
+                            &Vector3::y_axis(),
+                            90.0f32.to_radians(),
+                        ))
+                        .with_local_scale(Vector3::new(2.0, 2.0, 2.0))
+                        .build(),
+                )
+                .with_children(&[child])
+                .build(&mut scene.graph);
+            child
+        };
+
+        // Node follows body.
+        let body_rotation = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), 0.5);
+        let simulated = add_child(&mut scene);
+        let simulated_body = scene.physics.add_body(
+            RigidBodyBuilder::new_static()
+                .position(Isometry3::from_parts(
+                    Translation3::new(1.0, 2.0, 3.0),
+                    body_rotation,
+                ))
+                .build(),
+        );
+        scene.physics_binder.bind(simulated, simulated_body);
+
+        // Body follows node.
+        let animated = add_child(&mut scene);
+        let animated_body = scene
+            .physics
+            .add_body(RigidBodyBuilder::new_kinematic().build());
+        scene.physics_binder.bind(animated, animated_body);
+        scene.physics_binder.set_physics_weight(animated, 0.0);
+        scene.graph[animated]
+            .local_transform_mut()
+            .set_position(Vector3::new(1.0, 0.0, 0.0));
+
+        scene.graph.update_hierarchical_data();
+        for _ in 0..2 {
+            scene.update(Vector2::new(100.0, 100.0), 1.0 / 60.0);
+        }
+
+        let global_transform = scene.graph[simulated].global_transform();
+        assert!((global_transform.position() - Vector3::new(1.0, 2.0, 3.0)).norm() < 1.0e-4);
+        let expected_look = body_rotation * Vector3::z() * 2.0;
+        assert!((global_transform.look() - expected_look).norm() < 1.0e-4);
+
+        // Unit offset along x axis of parent is rotated to -z and scaled twice.
+        let body_position = scene
+            .physics
+            .bodies
+            .get(animated_body.into())
+            .unwrap()
+            .position();
+        assert!((body_position.translation.vector - Vector3::new(10.0, 0.0, -2.0)).norm() < 1.0e-4);
+        assert!(
+            (scene.graph[animated].global_position() - Vector3::new(10.0, 0.0, -2.0)).norm()
+                < 1.0e-4
+        );
+    }
+
+    #[test]
+    fn test_trigger_events() {
+        let mut scene = Scene::new();
+
+        let trigger = BaseBuilder::new().build(&mut scene.graph);
+        let trigger_body = scene
+            .physics
+            .add_body(RigidBodyBuilder::new_static().build());
+        scene.physics.add_collider(
+            ColliderBuilder::cuboid(2.0, 0.5, 2.0).sensor(true).build(),
+            trigger_body,
+        );
+        scene.physics_binder.bind(trigger, trigger_body);
+
+        let add_ball = |scene: &mut Scene, y: f32| -> Handle<Node> {
+            let node = BaseBuilder::new().build(&mut scene.graph);
+            let body = scene.physics.add_body(
+                RigidBodyBuilder::new_dynamic()
+                    .translation(0.0, y, 0.0)
+                    .build(),
+            );
+            scene
+                .physics
+                .add_collider(ColliderBuilder::ball(0.25).build(), body);
+            scene.physics_binder.bind(node, body);
+            node
+        };
+
+        // Ball falls through the trigger.
+        let ball = add_ball(&mut scene, 2.0);
+        let mut events = Vec::new();
+        for _ in 0..120 {
+            scene.update(Vector2::new(100.0, 100.0), 1.0 / 60.0);
+            events.extend(scene.physics.drain_trigger_events());
+        }
+        assert_eq!(
+            events,
+            vec![
+                TriggerEvent {
+                    trigger,
+                    other: ball,
+                    kind: TriggerEventKind::Enter
+                },
+                TriggerEvent {
+                    trigger,
+                    other: ball,
+                    kind: TriggerEventKind::Exit
+                }
+            ]
+        );
+
+        // Events of a removed node are discarded.
+        let removed = add_ball(&mut scene, 0.0);
+        let ball = add_ball(&mut scene, 0.0);
+        scene.update(Vector2::new(100.0, 100.0), 1.0 / 60.0);
+        scene.remove_node(removed);
+        assert_eq!(
+            scene.physics.drain_trigger_events().collect::<Vec<_>>(),
+            vec![TriggerEvent {
+                trigger,
+                other: ball,
+                kind: TriggerEventKind::Enter
+            }]
+        );
+    }
+
+    #[test]
+    fn test_physics_save_load() {
+        let mut scene = Scene::new();
+
+        let ground = scene
+            .physics
+            .add_body(RigidBodyBuilder::new_static().build());
+        scene
+            .physics
+            .add_collider(ColliderBuilder::cuboid(10.0, 0.1, 10.0).build(), ground);
+
+        // Make a gap in the pool of bodies, so handles of restored bodies will differ.
+        let removed = scene
+            .physics
+            .add_body(RigidBodyBuilder::new_dynamic().build());
+
+        let boxes = (0..3)
+            .map(|i| {
+                let node = BaseBuilder::new().build(&mut scene.graph);
+                let body = scene.physics.add_body(
+                    RigidBodyBuilder::new_dynamic()
+                        .translation(0.0, 2.0 + i as f32 * 1.5, 0.0)
+                        .mass(5.0, true)
+                        .build(),
+                );
+                scene
+                    .physics
+                    .add_collider(ColliderBuilder::cuboid(0.5, 0.5, 0.5).build(), body);
+                scene.physics_binder.bind(node, body);
+                node
+            })
+            .collect::<Vec<_>>();
+
+        scene.physics.remove_body(removed);
+
+        for _ in 0..10 {
+            scene.update(Vector2::new(100.0, 100.0), 1.0 / 60.0);
+        }
+
+        let path = std::env::temp_dir().join("rg3d_physics_save_load_test.bin");
+        let mut visitor = Visitor::new();
+        scene.visit("Scene", &mut visitor).unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        let mut visitor = Visitor::load_binary(&path).unwrap();
+        let mut loaded = Scene::default();
+        loaded.visit("Scene", &mut visitor).unwrap();
+        let _ = std::fs::remove_file(&path);
+        loaded.resolve();
+
+        let body_of = |scene: &Scene, node: Handle<Node>| {
+            let handle = scene.physics_binder.body_of(node).unwrap();
+            scene.physics.bodies.get(handle.into()).unwrap().clone()
+        };
+
+        for &node in boxes.iter() {
+            let body = body_of(&scene, node);
+            let restored = body_of(&loaded, node);
+            assert_eq!(restored.position(), body.position());
+            assert_eq!(restored.linvel(), body.linvel());
+            assert_eq!(restored.mass_properties(), body.mass_properties());
+            // Mid-fall.
+            assert!(body.linvel().y < 0.0);
+        }
+
+        for _ in 0..10 {
+            scene.update(Vector2::new(100.0, 100.0), 1.0 / 60.0);
+            loaded.update(Vector2::new(100.0, 100.0), 1.0 / 60.0);
+        }
+
+        for &node in boxes.iter() {
+            let position = scene.graph[node].global_position();
+            let restored = loaded.graph[node].global_position();
+            assert!((position - restored).norm() < 1.0e-5);
+        }
+    }
+
+    #[test]
+    fn test_time_scale_and_pause() {
+        let make_scene = |time_scale: f32| {
+            let mut scene = Scene::new();
+            scene.time_scale = time_scale;
+            let node = BaseBuilder::new().build(&mut scene.graph);
+            let body = scene
+                .physics
+                .add_body(RigidBodyBuilder::new_dynamic().build());
+            scene
+                .physics
+                .add_collider(ColliderBuilder::ball(0.5).build(), body);
+            scene.physics_binder.bind(node, body);
+            (scene, node)
+        };
+        let velocity = |scene: &Scene, node: Handle<Node>| {
+            let body = scene.physics_binder.body_of(node).unwrap();
+            scene.physics.bodies.get(body.into()).unwrap().linvel().y
+        };
+
+        let (mut normal, normal_node) = make_scene(1.0);
+        let (mut slow, slow_node) = make_scene(0.1);
+        for _ in 0..10 {
+            normal.update(Vector2::new(100.0, 100.0), 1.0 / 60.0);
+            slow.update(Vector2::new(100.0, 100.0), 1.0 / 60.0);
+        }
+        assert!(velocity(&normal, normal_node) < 0.0);
+        assert!((velocity(&slow, slow_node) - velocity(&normal, normal_node) * 0.1).abs() < 1.0e-4);
+        // Time step of physics is restored after each update.
+        assert_eq!(slow.physics.integration_parameters.dt(), 1.0 / 60.0);
+
+        // Paused scene is not simulated while others keep running.
+        slow.paused = true;
+        let position = slow.graph[slow_node].global_position();
+        let slow_velocity = velocity(&slow, slow_node);
+        let normal_velocity = velocity(&normal, normal_node);
+        for _ in 0..10 {
+            normal.update(Vector2::new(100.0, 100.0), 1.0 / 60.0);
+            slow.update(Vector2::new(100.0, 100.0), 1.0 / 60.0);
+        }
+        assert_eq!(slow.graph[slow_node].global_position(), position);
+        assert_eq!(velocity(&slow, slow_node), slow_velocity);
+        assert!(velocity(&normal, normal_node) < normal_velocity);
+
+        slow.paused = false;
+        slow.update(Vector2::new(100.0, 100.0), 1.0 / 60.0);
+        assert!(velocity(&slow, slow_node) < slow_velocity);
+    }
+
+    #[test]
+    fn test_textures_save_load() {
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgba8(1, 1)
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .unwrap();
+
+        let resource_manager = ResourceManager::new();
+        let names = ["albedo", "metallic", "roughness", "ao", "cookie"];
+        let paths = names
+            .iter()
+            .map(|name| {
+                let path = std::env::temp_dir().join(format!("rg3d_scene_{}_test.png", name));
+                std::fs::write(&path, &png).unwrap();
+                path
+            })
+            .collect::<Vec<_>>();
+        // Textures are loaded as sRGB here on purpose, scene loading must fix that.
+        let textures = paths
+            .iter()
+            .map(|path| {
+                futures::executor::block_on(resource_manager.request_texture(path)).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let mut scene = Scene::new();
+        let surface = Surface::new(Arc::new(RwLock::new(SurfaceSharedData::make_cube(
+            Matrix4::identity(),
+        ))));
+        let mesh = MeshBuilder::new(BaseBuilder::new())
+            .with_surfaces(vec![surface])
+            .build(&mut scene.graph);
+        if let Node::Mesh(mesh) = &mut scene.graph[mesh] {
+            mesh.surfaces_mut()[0].set_pbr_material(Some(
+                PbrMaterialBuilder::new()
+                    .with_albedo_texture(textures[0].clone())
+                    .with_metallic_texture(textures[1].clone())
+                    .with_roughness_texture(textures[2].clone())
+                    .with_ao_texture(textures[3].clone())
+                    .build(),
+            ));
+        }
+        let light = SpotLightBuilder::new(BaseLightBuilder::new(BaseBuilder::new()))
+            .with_cookie_texture(textures[4].clone())
+            .build(&mut scene.graph);
+
+        let path = std::env::temp_dir().join("rg3d_scene_textures_test.bin");
+        let mut visitor = Visitor::new();
+        scene.visit("Scene", &mut visitor).unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        let resource_manager = ResourceManager::new();
+        let loaded =
+            futures::executor::block_on(Scene::from_file(&path, resource_manager.clone())).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let pbr = match &loaded.graph[mesh] {
+            Node::Mesh(mesh) => mesh.surfaces()[0].pbr_material().unwrap().clone(),
+            _ => unreachable!(),
+        };
+        let cookie = match &loaded.graph[light] {
+            Node::Light(Light::Spot(spot)) => spot.cookie_texture().cloned().unwrap(),
+            _ => unreachable!(),
+        };
+        let loaded_textures = [
+            (pbr.albedo_texture().unwrap(), TextureColorSpace::Srgb),
+            (pbr.metallic_texture().unwrap(), TextureColorSpace::Linear),
+            (pbr.roughness_texture().unwrap(), TextureColorSpace::Linear),
+            (pbr.ao_texture().unwrap(), TextureColorSpace::Linear),
+            (cookie, TextureColorSpace::Srgb),
+        ];
+        for ((texture, color_space), path) in loaded_textures.iter().zip(paths.iter()) {
+            // Texture must be requested from the resource manager, not left shallow.
+            let requested = resource_manager.state().find_texture(path).unwrap();
+            assert_eq!(requested.key(), texture.key());
+            let texture = futures::executor::block_on(texture.clone()).unwrap();
+            assert_eq!(texture.state().path(), path.as_path());
+            assert_eq!(texture.data_ref().color_space(), *color_space);
+        }
+
+        for path in paths.iter() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
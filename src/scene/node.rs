@@ -1,16 +1,126 @@
 //! Contains all structures and methods to create and manage scene graph nodes.
 //!
 //! Node is enumeration of possible types of scene nodes.
+//!
+//! # Pivots
+//!
+//! There is no special "empty" node type, [Base](../base/struct.Base.html) node is the pivot -
+//! it has only transform, name and hierarchy. Use it to group other nodes or to mark some
+//! point in space.
+//!
+//! # Custom nodes
+//!
+//! Game-specific nodes can be added to a graph using [Node::Custom](enum.Node.html#variant.Custom)
+//! variant. Custom node must implement [CustomNode](trait.CustomNode.html) trait and must give
+//! access to its [Base](../base/struct.Base.html) part via Deref, so graph can calculate its
+//! transforms and maintain hierarchy. To be able to load custom nodes, a callback must be set
+//! to [CustomNodeFactory](struct.CustomNodeFactory.html) that creates empty node by its kind.
+//!
+//! ```
+//! use rg3d::{
+//!     core::{
+//!         pool::Handle,
+//!         visitor::{Visit, VisitResult, Visitor},
+//!     },
+//!     scene::{
+//!         base::Base,
+//!         node::{CustomNode, CustomNodeFactory, Node},
+//!     },
+//! };
+//! use std::{
+//!     any::Any,
+//!     collections::HashMap,
+//!     ops::{Deref, DerefMut},
+//! };
+//!
+//! #[derive(Debug, Default)]
+//! struct Door {
+//!     base: Base,
+//!     open: bool,
+//!     // Handle to a node that will be rotated when door opens.
+//!     hinge: Handle<Node>,
+//! }
+//!
+//! impl Deref for Door {
+//!     type Target = Base;
+//!
+//!     fn deref(&self) -> &Self::Target {
+//!         &self.base
+//!     }
+//! }
+//!
+//! impl DerefMut for Door {
+//!     fn deref_mut(&mut self) -> &mut Self::Target {
+//!         &mut self.base
+//!     }
+//! }
+//!
+//! impl Visit for Door {
+//!     fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+//!         visitor.enter_region(name)?;
+//!
+//!         self.base.visit("Base", visitor)?;
+//!         self.open.visit("Open", visitor)?;
+//!         self.hinge.visit("Hinge", visitor)?;
+//!
+//!         visitor.leave_region()
+//!     }
+//! }
+//!
+//! impl CustomNode for Door {
+//!     fn box_clone(&self) -> Box<dyn CustomNode> {
+//!         Box::new(Door {
+//!             base: self.base.raw_copy(),
+//!             open: self.open,
+//!             hinge: self.hinge,
+//!         })
+//!     }
+//!
+//!     fn kind(&self) -> u64 {
+//!         1
+//!     }
+//!
+//!     fn resolve(&mut self, old_new_mapping: &HashMap<Handle<Node>, Handle<Node>>) {
+//!         if let Some(&hinge) = old_new_mapping.get(&self.hinge) {
+//!             self.hinge = hinge;
+//!         }
+//!     }
+//!
+//!     fn as_any(&self) -> &dyn Any {
+//!         self
+//!     }
+//!
+//!     fn as_any_mut(&mut self) -> &mut dyn Any {
+//!         self
+//!     }
+//! }
+//!
+//! CustomNodeFactory::get()
+//!     .unwrap()
+//!     .set_callback(Box::new(|kind| match kind {
+//!         1 => Ok(Box::new(Door::default())),
+//!         _ => Err(format!("Unknown custom node kind {}", kind)),
+//!     }));
+//! ```
 
 use crate::{
-    core::define_is_as,
-    core::visitor::{Visit, VisitResult, Visitor},
+    core::{
+        define_is_as,
+        pool::Handle,
+        visitor::{Visit, VisitResult, Visitor},
+    },
     scene::{
         base::Base, camera::Camera, light::Light, mesh::Mesh, particle_system::ParticleSystem,
-        sprite::Sprite,
+        sprite::Sprite, terrain::Terrain,
     },
 };
-use std::ops::{Deref, DerefMut};
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt::Debug,
+    ops::{Deref, DerefMut},
+    sync::{LockResult, Mutex, MutexGuard},
+};
 
 /// Helper macros to reduce code bloat - its purpose it to dispatch
 /// specified call by actual enum variant.
@@ -23,15 +133,34 @@ macro_rules! static_dispatch {
             Node::Light(v) => v.$func($($args),*),
             Node::ParticleSystem(v) => v.$func($($args),*),
             Node::Sprite(v) => v.$func($($args),*),
+            Node::Terrain(v) => v.$func($($args),*),
+            Node::Custom(v) => v.$func($($args),*),
         }
     };
 }
 
+/// Id of custom node variant. Built-in kinds of nodes that were added later than custom nodes
+/// have greater ids to keep old scenes loadable.
+const CUSTOM_NODE_ID: u8 = 6;
+
 impl Visit for Node {
     fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
         let mut kind_id = self.id();
         kind_id.visit("KindId", visitor)?;
-        if visitor.is_reading() {
+        if kind_id == CUSTOM_NODE_ID {
+            let mut custom_kind = if let Node::Custom(custom) = self {
+                custom.kind()
+            } else {
+                0
+            };
+            custom_kind.visit("CustomKindId", visitor)?;
+            if visitor.is_reading() {
+                *self = match CustomNodeFactory::get() {
+                    Ok(factory) => Node::Custom(factory.spawn(custom_kind)?),
+                    Err(_) => return Err(String::from("Failed get custom node factory!").into()),
+                };
+            }
+        } else if visitor.is_reading() {
             *self = Node::from_id(kind_id)?;
         }
 
@@ -39,6 +168,75 @@ impl Visit for Node {
     }
 }
 
+/// Callback that creates custom node by its numeric identifier.
+pub type CustomNodeFactoryCallback =
+    dyn Fn(u64) -> Result<Box<dyn CustomNode>, String> + Send + 'static;
+
+/// Custom node factory is used to create custom nodes when a scene is loaded. See module
+/// docs for example.
+pub struct CustomNodeFactory {
+    callback: Option<Box<CustomNodeFactoryCallback>>,
+}
+
+impl Default for CustomNodeFactory {
+    fn default() -> Self {
+        Self { callback: None }
+    }
+}
+
+impl CustomNodeFactory {
+    /// Locks factory singleton and returns lock result.
+    pub fn get() -> LockResult<MutexGuard<'static, Self>> {
+        CUSTOM_NODE_FACTORY_INSTANCE.lock()
+    }
+
+    /// Sets new callback that will be used to create custom nodes.
+    pub fn set_callback(&mut self, callback: Box<CustomNodeFactoryCallback>) {
+        self.callback = Some(callback);
+    }
+
+    fn spawn(&self, kind: u64) -> Result<Box<dyn CustomNode>, String> {
+        match &self.callback {
+            Some(callback) => callback(kind),
+            None => Err(String::from("no callback specified")),
+        }
+    }
+}
+
+lazy_static! {
+    static ref CUSTOM_NODE_FACTORY_INSTANCE: Mutex<CustomNodeFactory> =
+        Mutex::new(Default::default());
+}
+
+/// Custom node allows you to add your own node types to a graph. Graph maintains hierarchy,
+/// transforms and visibility of custom node using its [Base](../base/struct.Base.html) part,
+/// so implementors must provide access to it via Deref. Custom node must be serializable to
+/// be saved and loaded together with a scene. See module docs for example.
+pub trait CustomNode: Any + Visit + Send + Debug + Deref<Target = Base> + DerefMut {
+    /// Creates boxed raw copy of custom node. Handles to other nodes should be copied as is,
+    /// they will be remapped later in `resolve` method.
+    fn box_clone(&self) -> Box<dyn CustomNode>;
+
+    /// Returns unique kind of custom node. It is used to create the node on load using
+    /// [CustomNodeFactory](struct.CustomNodeFactory.html).
+    fn kind(&self) -> u64;
+
+    /// Updates the node, called once per frame by graph.
+    fn update(&mut self, _dt: f32) {}
+
+    /// Resolves handles to other nodes. After the node was copied (graph clone, model
+    /// instantiation, etc.) the mapping contains old-to-new pairs of handles of every copied
+    /// node. After a scene was loaded the mapping is empty - handles are stored in save file
+    /// and valid as is, but the node can restore state that was not saved.
+    fn resolve(&mut self, _old_new_mapping: &HashMap<Handle<Node>, Handle<Node>>) {}
+
+    /// Returns reference to the node as Any, it is used for downcasting.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Returns mutable reference to the node as Any, it is used for downcasting.
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
 /// See module docs.
 #[derive(Debug)]
 pub enum Node {
@@ -54,6 +252,10 @@ pub enum Node {
     Sprite(Sprite),
     /// See ParticleSystem node docs.
     ParticleSystem(ParticleSystem),
+    /// See Terrain node docs.
+    Terrain(Terrain),
+    /// See CustomNode docs.
+    Custom(Box<dyn CustomNode>),
 }
 
 macro_rules! static_dispatch_deref {
@@ -65,6 +267,8 @@ macro_rules! static_dispatch_deref {
             Node::Light(v) => v,
             Node::ParticleSystem(v) => v,
             Node::Sprite(v) => v,
+            Node::Terrain(v) => v,
+            Node::Custom(v) => v,
         }
     };
 }
@@ -99,6 +303,10 @@ impl Node {
             3 => Ok(Self::Mesh(Default::default())),
             4 => Ok(Self::Sprite(Default::default())),
             5 => Ok(Self::ParticleSystem(Default::default())),
+            7 => Ok(Self::Terrain(Default::default())),
+            CUSTOM_NODE_ID => Err(String::from(
+                "Custom nodes must be created by custom node factory",
+            )),
             _ => Err(format!("Invalid node kind {}", id)),
         }
     }
@@ -112,6 +320,8 @@ impl Node {
             Self::Mesh(_) => 3,
             Self::Sprite(_) => 4,
             Self::ParticleSystem(_) => 5,
+            Self::Terrain(_) => 7,
+            Self::Custom(_) => CUSTOM_NODE_ID,
         }
     }
 
@@ -126,6 +336,26 @@ impl Node {
             Node::Mesh(v) => Node::Mesh(v.raw_copy()),
             Node::Sprite(v) => Node::Sprite(v.raw_copy()),
             Node::ParticleSystem(v) => Node::ParticleSystem(v.raw_copy()),
+            Node::Terrain(v) => Node::Terrain(v.raw_copy()),
+            Node::Custom(v) => Node::Custom(v.box_clone()),
+        }
+    }
+
+    /// Tries to downcast the node to custom node of given type. Returns None if the node is
+    /// not custom or has other type.
+    pub fn as_custom<T: CustomNode>(&self) -> Option<&T> {
+        match self {
+            Node::Custom(v) => v.as_any().downcast_ref::<T>(),
+            _ => None,
+        }
+    }
+
+    /// Tries to downcast the node to custom node of given type. Returns None if the node is
+    /// not custom or has other type.
+    pub fn as_custom_mut<T: CustomNode>(&mut self) -> Option<&mut T> {
+        match self {
+            Node::Custom(v) => v.as_any_mut().downcast_mut::<T>(),
+            _ => None,
         }
     }
 
@@ -134,4 +364,10 @@ impl Node {
     define_is_as!(Node : Light -> ref Light => fn is_light, fn as_light, fn as_light_mut);
     define_is_as!(Node : ParticleSystem -> ref ParticleSystem => fn is_particle_system, fn as_particle_system, fn as_particle_system_mut);
     define_is_as!(Node : Sprite -> ref Sprite => fn is_sprite, fn as_sprite, fn as_sprite_mut);
+    define_is_as!(Node : Terrain -> ref Terrain => fn is_terrain, fn as_terrain, fn as_terrain_mut);
+
+    /// Returns true if node is custom.
+    pub fn is_custom(&self) -> bool {
+        matches!(self, Node::Custom(_))
+    }
 }
@@ -11,8 +11,8 @@ use crate::{
     },
     physics::math::AngVector,
     scene::{
-        graph::Graph, node::Node, ColliderHandle, JointHandle, PhysicsBinder, RigidBodyHandle,
-        SceneDrawingContext,
+        graph::Graph, node::Node, ColliderHandle, JointHandle, PhysicsBinder, RapierHandle,
+        RigidBodyHandle, SceneDrawingContext,
     },
     utils::{
         log::Log,
@@ -23,25 +23,38 @@ use rapier3d::{
     data::arena::Index,
     dynamics::{
         BallJoint, BodyStatus, FixedJoint, IntegrationParameters, Joint, JointParams, JointSet,
-        PrismaticJoint, RevoluteJoint, RigidBody, RigidBodyBuilder, RigidBodySet,
+        MassProperties, PrismaticJoint, RevoluteJoint, RigidBody, RigidBodyBuilder, RigidBodySet,
     },
     geometry::{
-        BroadPhase, Collider, ColliderBuilder, ColliderSet, ColliderShape, InteractionGroups,
-        NarrowPhase, Segment, Shape, Trimesh,
+        BroadPhase, Collider, ColliderBuilder, ColliderSet, ColliderShape, ContactEvent,
+        InteractionGroups, NarrowPhase, Proximity, ProximityEvent, Segment, Shape, Triangle,
+        Trimesh, AABB,
     },
     na::{
-        DMatrix, Dynamic, Isometry3, Point3, Translation, Translation3, Unit, UnitQuaternion,
-        VecStorage, Vector3,
+        DMatrix, Dynamic, Isometry3, Matrix3, Matrix4, Point3, Rotation3, Translation,
+        Translation3, Unit, UnitQuaternion, VecStorage, Vector3,
+    },
+    ncollide::{
+        bounding_volume::BoundingVolume,
+        partitioning::{VisitStatus, Visitor as BvVisitor, BVH, BVT},
+        procedural::{IndexBuffer, TriMesh},
+        query::{self, Contact, TOI},
+        shape::{FeatureId, SupportMap, TriMesh as QueryTriMesh},
+        transformation,
     },
-    ncollide::{query, shape::FeatureId},
     pipeline::{EventHandler, PhysicsPipeline, QueryPipeline},
 };
 use rg3d_core::math::aabb::AxisAlignedBoundingBox;
+use rg3d_sound::{
+    context::Context,
+    source::{spatial::Obstruction, SoundSource},
+};
 use std::collections::HashMap;
 use std::{
     cell::{Cell, RefCell},
     cmp::Ordering,
     fmt::{Debug, Formatter},
+    sync::Mutex,
 };
 
 /// A ray intersection result.
@@ -79,6 +92,177 @@ pub struct RayCastOptions {
     pub sort_results: bool,
 }
 
+/// A contact of a shape with a collider, see [`Physics::intersections_with_shape`].
+#[derive(Debug, Copy, Clone)]
+pub struct ShapeIntersection {
+    /// A handle of the collider which is touched by the shape.
+    pub collider: ColliderHandle,
+
+    /// World-space triangle of triangle mesh collider which is touched by the shape, every
+    /// touched triangle gives separate intersection. `None` for other shapes.
+    pub triangle: Option<Triangle>,
+
+    /// World-space contact, first object is the shape and second is the collider. Normal
+    /// points from the shape to the collider, depth is positive when the shape penetrates
+    /// the collider and negative when they are separated.
+    pub contact: Contact<f32>,
+}
+
+// Acceleration structures for shape queries. Rapier's query pipeline can cast only rays, so
+// shape queries use bounding volume tree of colliders and trees of triangle meshes. Trees of
+// triangle meshes do not depend on positions of colliders, so they are built only once.
+#[derive(Default)]
+struct ShapeQuery {
+    colliders: Option<BVT<Index, AABB>>,
+    trimeshes: HashMap<Index, QueryTriMesh<f32>>,
+}
+
+impl ShapeQuery {
+    fn update(&mut self, colliders: &ColliderSet) {
+        let leaves = colliders
+            .iter()
+            .map(|(handle, collider)| (handle, collider.compute_aabb()))
+            .collect::<Vec<_>>();
+        self.colliders = if leaves.is_empty() {
+            None
+        } else {
+            Some(BVT::new_balanced(leaves))
+        };
+
+        self.trimeshes
+            .retain(|handle, _| colliders.get(*handle).is_some());
+        for (handle, collider) in colliders.iter() {
+            if let Some(trimesh) = collider.shape().as_trimesh() {
+                self.trimeshes.entry(handle).or_insert_with(|| {
+                    QueryTriMesh::new(
+                        trimesh.vertices().to_vec(),
+                        trimesh
+                            .indices()
+                            .iter()
+                            .map(|i| Point3::new(i.x as usize, i.y as usize, i.z as usize))
+                            .collect(),
+                        None,
+                    )
+                });
+            }
+        }
+    }
+}
+
+// Visits every leaf of a tree whose bounding box intersects given one, traversal stops
+// when callback returns false.
+struct AabbVisitor<'a, F> {
+    aabb: &'a AABB,
+    callback: F,
+    stopped: bool,
+}
+
+impl<'a, T, F> BvVisitor<T, AABB> for AabbVisitor<'a, F>
+where
+    F: FnMut(&T) -> bool,
+{
+    fn visit(&mut self, bv: &AABB, data: Option<&T>) -> VisitStatus {
+        if !bv.intersects(self.aabb) {
+            return VisitStatus::Stop;
+        }
+        if let Some(data) = data {
+            if !(self.callback)(data) {
+                self.stopped = true;
+                return VisitStatus::ExitEarly;
+            }
+        }
+        VisitStatus::Continue
+    }
+}
+
+fn visit_aabb<T, B, F>(tree: &B, aabb: &AABB, callback: F) -> bool
+where
+    B: BVH<T, AABB>,
+    F: FnMut(&T) -> bool,
+{
+    let mut visitor = AabbVisitor {
+        aabb,
+        callback,
+        stopped: false,
+    };
+    tree.visit(&mut visitor);
+    !visitor.stopped
+}
+
+// Convex shapes of colliders, they are queried directly. Triangle meshes and height fields
+// are queried triangle by triangle.
+fn collider_support_map(shape: &dyn Shape) -> Option<&dyn SupportMap<f32>> {
+    if let Some(ball) = shape.as_ball() {
+        Some(ball)
+    } else if let Some(cuboid) = shape.as_cuboid() {
+        Some(cuboid)
+    } else if let Some(capsule) = shape.as_capsule() {
+        Some(capsule)
+    } else if let Some(triangle) = shape.as_triangle() {
+        Some(triangle)
+    } else if let Some(cylinder) = shape.as_cylinder() {
+        Some(cylinder)
+    } else if let Some(cone) = shape.as_cone() {
+        Some(cone)
+    } else if let Some(round_cylinder) = shape.as_round_cylinder() {
+        Some(round_cylinder)
+    } else {
+        None
+    }
+}
+
+fn support_map_aabb(position: &Isometry3<f32>, shape: &dyn SupportMap<f32>) -> AABB {
+    let mut min = Vector3::default();
+    let mut max = Vector3::default();
+    for axis in 0..3 {
+        let mut direction = Vector3::default();
+        direction[axis] = 1.0;
+        max[axis] = shape.support_point(position, &direction)[axis];
+        min[axis] = shape.support_point(position, &-direction)[axis];
+    }
+    AABB::new(Point3::from(min), Point3::from(max))
+}
+
+/// Acoustic properties of a collider, they define how sound passes through it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AcousticMaterial {
+    /// Fraction of sound energy that passes through the collider, in `[0; 1]` range.
+    pub transmission: f32,
+    /// Cutoff frequency (in Hz) of low-pass filter that is applied to the sound that passed
+    /// through the collider. Dense materials usually pass only low frequencies.
+    pub cutoff_frequency: f32,
+}
+
+impl Default for AcousticMaterial {
+    fn default() -> Self {
+        Self {
+            transmission: 0.5,
+            cutoff_frequency: 1500.0,
+        }
+    }
+}
+
+/// A set of options for sound obstruction calculation, see [`Physics::sound_obstruction`].
+#[derive(Clone, Debug)]
+pub struct SoundObstructionOptions {
+    /// Material of colliders that have no material in `materials`.
+    pub default_material: AcousticMaterial,
+    /// Per-collider acoustic materials.
+    pub materials: HashMap<ColliderHandle, AcousticMaterial>,
+    /// Groups of colliders that obstruct the sound.
+    pub groups: InteractionGroups,
+}
+
+impl Default for SoundObstructionOptions {
+    fn default() -> Self {
+        Self {
+            default_material: Default::default(),
+            materials: Default::default(),
+            groups: InteractionGroups::all(),
+        }
+    }
+}
+
 /// A set of data that has all associations with physics from resource.
 /// It is used to embedding physics from resource to a scene during
 /// the instantiation process.
@@ -104,6 +288,148 @@ impl Visit for ResourceLink {
     }
 }
 
+// Replaces instance handles (keys) of a resource link using given old -> new mapping.
+fn remap_link_handles(
+    link: &mut HashMap<RapierHandle, RapierHandle>,
+    old_to_new: &HashMap<RapierHandle, RapierHandle>,
+) {
+    *link = link
+        .drain()
+        .map(|(instance, resource)| {
+            (
+                old_to_new.get(&instance).copied().unwrap_or(instance),
+                resource,
+            )
+        })
+        .collect();
+}
+
+/// Kind of a trigger event.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TriggerEventKind {
+    /// A node has entered trigger volume.
+    Enter,
+    /// A node has left trigger volume.
+    Exit,
+}
+
+/// An event which is generated when a node enters or leaves a trigger volume. Trigger volume
+/// is a rigid body with a sensor collider (see `ColliderBuilder::sensor`) which is linked with
+/// a scene node by the physics binder, the other body must be linked with a node too, otherwise
+/// no event will be generated.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TriggerEvent {
+    /// A node which is linked with a body of the sensor collider.
+    pub trigger: Handle<Node>,
+    /// A node which is linked with a body of the other collider.
+    pub other: Handle<Node>,
+    /// Kind of the event.
+    pub kind: TriggerEventKind,
+}
+
+// Collects proximity events of a single step and passes every event to user's handler.
+struct EventCollector<'a> {
+    user_handler: &'a dyn EventHandler,
+    proximity_events: Mutex<Vec<ProximityEvent>>,
+}
+
+impl<'a> EventHandler for EventCollector<'a> {
+    fn handle_proximity_event(&self, event: ProximityEvent) {
+        self.proximity_events.lock().unwrap().push(event);
+        self.user_handler.handle_proximity_event(event);
+    }
+
+    fn handle_contact_event(&self, event: ContactEvent) {
+        self.user_handler.handle_contact_event(event);
+    }
+}
+
+/// Space of vertices of colliders generated from meshes, see `Physics::make_mesh_collider_shapes`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MeshColliderSpace {
+    /// Vertices are relative to the root node, only scale of its global transform is baked in.
+    /// Rigid body must have the same global position and rotation as the root node.
+    Local,
+    /// Whole global transform is baked into vertices, rigid body must be at the origin without
+    /// rotation.
+    World,
+}
+
+/// Shape of colliders generated from meshes, see `Physics::mesh_to_collider`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MeshColliderShape {
+    /// Exact triangle mesh. Triangle mesh has no volume and cannot collide with other
+    /// triangle meshes, so it is suitable only for static or kinematic geometry like levels.
+    Trimesh,
+    /// Convex hull of vertices. Rapier has no convex polyhedron shape yet, so the hull is
+    /// stored as triangle mesh with the same limitations as `Trimesh`, but with less triangles.
+    ConvexHull,
+    /// Approximate convex decomposition (HACD), every convex part is approximated by oriented
+    /// box. Result is a set of cuboids, so it can be used for dynamic bodies.
+    ConvexDecomposition {
+        /// Maximum concavity of a part relative to size of the mesh, 0.03 is a good start.
+        concavity: f32,
+        /// Parts are not merged below this amount.
+        min_parts: usize,
+    },
+}
+
+// Minimal half extent of boxes made by convex decomposition, flat parts would give degenerate
+// cuboids otherwise.
+const MIN_BOX_HALF_EXTENT: f32 = 0.001;
+
+// Fits box with given axes around a set of points, returns center, half extents and volume.
+fn fit_box(points: &[Point3<f32>], axes: &Matrix3<f32>) -> (Vector3<f32>, Vector3<f32>, f32) {
+    let mut min = Vector3::repeat(std::f32::MAX);
+    let mut max = Vector3::repeat(-std::f32::MAX);
+    for point in points {
+        let local = axes.tr_mul(&point.coords);
+        min = min.inf(&local);
+        max = max.sup(&local);
+    }
+    let half_extents = ((max - min) * 0.5).map(|e| e.max(MIN_BOX_HALF_EXTENT));
+    let volume = half_extents.x * half_extents.y * half_extents.z;
+    (axes * ((max + min) * 0.5), half_extents, volume)
+}
+
+// Fits oriented box around a set of points. Box is aligned either with principal axes of the
+// points or with coordinate axes, whichever gives smaller box - principal axes are ambiguous
+// for symmetric shapes like cubes.
+fn fit_oriented_box(points: &[Point3<f32>]) -> (Isometry3<f32>, ColliderShape) {
+    let count = points.len() as f32;
+    let center = points
+        .iter()
+        .fold(Vector3::zeros(), |acc, p| acc + p.coords)
+        / count;
+    let covariance = points.iter().fold(Matrix3::zeros(), |acc, p| {
+        let d = p.coords - center;
+        acc + d * d.transpose()
+    }) / count;
+
+    let mut axes = covariance.symmetric_eigen().eigenvectors;
+    // Keep basis right-handed, so it is a rotation.
+    if axes.determinant() < 0.0 {
+        let z = -axes.column(2);
+        axes.set_column(2, &z);
+    }
+
+    let (mut box_center, mut half_extents, volume) = fit_box(points, &axes);
+    let (aligned_center, aligned_half_extents, aligned_volume) =
+        fit_box(points, &Matrix3::identity());
+    if aligned_volume <= volume {
+        axes = Matrix3::identity();
+        box_center = aligned_center;
+        half_extents = aligned_half_extents;
+    }
+
+    let position = Isometry3::from_parts(
+        Translation3::from(box_center),
+        UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(axes)),
+    );
+
+    (position, ColliderShape::cuboid(half_extents))
+}
+
 /// Physics world.
 pub struct Physics {
     /// Current physics pipeline.
@@ -166,8 +492,17 @@ pub struct Physics {
     /// instantiation process.
     pub embedded_resources: Vec<ResourceLink>,
 
+    /// Whether velocities and sleep state of rigid bodies should be saved or not. It is
+    /// useful for mid-game save files, so simulation will continue exactly from the saved
+    /// state. If disabled, bodies will be loaded at rest and awake. Default is true.
+    pub save_dynamic_state: bool,
+
     query_updated: Cell<bool>,
     query: RefCell<QueryPipeline>,
+    shape_query_updated: Cell<bool>,
+    shape_query: RefCell<ShapeQuery>,
+    proximity_events: Vec<ProximityEvent>,
+    trigger_events: Vec<TriggerEvent>,
 }
 
 impl Debug for Physics {
@@ -196,14 +531,22 @@ impl Physics {
             event_handler: Box::new(()),
             query_updated: Cell::new(false),
             query: Default::default(),
+            shape_query_updated: Cell::new(false),
+            shape_query: Default::default(),
             desc: Default::default(),
             embedded_resources: Default::default(),
+            save_dynamic_state: true,
+            proximity_events: Default::default(),
+            trigger_events: Default::default(),
         }
     }
 
     // Deep copy is performed using descriptors.
-    pub(in crate) fn deep_copy(&self, binder: &PhysicsBinder, graph: &Graph) -> Self {
+    // Binder must link nodes of the copy with bodies of this physics, bodies will be remapped
+    // to bodies of the copy.
+    pub(in crate) fn deep_copy(&self, binder: &mut PhysicsBinder, graph: &Graph) -> Self {
         let mut phys = Self::new();
+        phys.save_dynamic_state = self.save_dynamic_state;
         phys.embedded_resources = self.embedded_resources.clone();
         phys.desc = Some(self.generate_desc());
         phys.resolve(binder, graph);
@@ -303,6 +646,11 @@ impl Physics {
     }
 
     pub(in crate) fn step(&mut self) {
+        let collector = EventCollector {
+            user_handler: &*self.event_handler,
+            proximity_events: Default::default(),
+        };
+
         self.pipeline.step(
             &self.gravity,
             &self.integration_parameters,
@@ -313,8 +661,112 @@ impl Physics {
             &mut self.joints,
             None,
             None,
-            &*self.event_handler,
+            &collector,
         );
+
+        self.proximity_events
+            .extend(collector.proximity_events.into_inner().unwrap());
+
+        // Colliders were moved, bounds in shape query are outdated.
+        self.shape_query_updated.set(false);
+    }
+
+    /// Converts proximity events collected by previous steps into trigger events, previous
+    /// trigger events are discarded. Events are deduplicated per pair of colliders: a pair that
+    /// was intersecting at the beginning and at the end gives no events, a pair that entered and
+    /// left a trigger in between gives both `Enter` and `Exit`.
+    pub(in crate) fn update_trigger_events(&mut self, binder: &PhysicsBinder, graph: &Graph) {
+        self.trigger_events.clear();
+
+        if self.proximity_events.is_empty() {
+            return;
+        }
+
+        // Pair -> (was intersecting before first event, is intersecting after last event).
+        let mut pairs = Vec::<((Index, Index), bool, bool)>::new();
+        let mut pair_indices = HashMap::<(Index, Index), usize>::new();
+        for event in self.proximity_events.drain(..) {
+            let was_intersecting = event.prev_status == Proximity::Intersecting;
+            let is_intersecting = event.new_status == Proximity::Intersecting;
+            if was_intersecting == is_intersecting {
+                // Disjoint <-> WithinMargin changes are not interesting.
+                continue;
+            }
+            let key = if event.collider1 < event.collider2 {
+                (event.collider1, event.collider2)
+            } else {
+                (event.collider2, event.collider1)
+            };
+            match pair_indices.get(&key) {
+                Some(&index) => pairs[index].2 = is_intersecting,
+                None => {
+                    pair_indices.insert(key, pairs.len());
+                    pairs.push((key, was_intersecting, is_intersecting));
+                }
+            }
+        }
+
+        let body_node_map = binder
+            .node_rigid_body_map
+            .iter()
+            .map(|(&node, &body)| (body, node))
+            .collect::<HashMap<_, _>>();
+        let colliders = &self.colliders;
+        // Returns node of collider and whether collider is a sensor or not.
+        let node_of = |collider: Index| {
+            colliders.get(collider).and_then(|collider| {
+                body_node_map
+                    .get(&collider.parent().into())
+                    .filter(|&&node| graph.is_valid_handle(node))
+                    .map(|&node| (node, collider.is_sensor()))
+            })
+        };
+
+        for ((a, b), was_intersecting, is_intersecting) in pairs {
+            let kinds: &[TriggerEventKind] = match (was_intersecting, is_intersecting) {
+                (false, true) => &[TriggerEventKind::Enter],
+                (true, false) => &[TriggerEventKind::Exit],
+                (false, false) => &[TriggerEventKind::Enter, TriggerEventKind::Exit],
+                (true, true) => &[],
+            };
+
+            if let (Some((node_a, sensor_a)), Some((node_b, sensor_b))) = (node_of(a), node_of(b)) {
+                for &kind in kinds {
+                    if sensor_a {
+                        self.trigger_events.push(TriggerEvent {
+                            trigger: node_a,
+                            other: node_b,
+                            kind,
+                        });
+                    }
+                    if sensor_b {
+                        self.trigger_events.push(TriggerEvent {
+                            trigger: node_b,
+                            other: node_a,
+                            kind,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns an iterator over trigger events of the last update of the scene, events are
+    /// removed from the physics world. Events are generated only for sensor colliders whose
+    /// bodies are linked with scene nodes, see [TriggerEvent] for more info. Events that were
+    /// not drained are discarded on next update, so this method should be called every frame.
+    ///
+    /// # Notes
+    ///
+    /// Removing a collider or its node does not generate `Exit` event, events that reference
+    /// nodes removed by `Scene::remove_node` are removed too.
+    pub fn drain_trigger_events(&mut self) -> impl Iterator<Item = TriggerEvent> + '_ {
+        self.trigger_events.drain(..)
+    }
+
+    pub(in crate) fn remove_trigger_events_of(&mut self, node: Handle<Node>) {
+        self.trigger_events
+            .retain(|event| event.trigger != node && event.other != node);
     }
 
     #[doc(hidden)]
@@ -325,13 +777,13 @@ impl Physics {
             bodies: self
                 .bodies
                 .iter()
-                .map(|(_, b)| RigidBodyDesc::from_body(b))
+                .map(|(h, b)| RigidBodyDesc::from_body(h.into(), b))
                 .collect::<Vec<_>>(),
 
             colliders: self
                 .colliders
                 .iter()
-                .map(|(_, c)| ColliderDesc::from_collider(c))
+                .map(|(h, c)| ColliderDesc::from_collider(h.into(), c))
                 .collect::<Vec<_>>(),
 
             gravity: self.gravity,
@@ -339,14 +791,18 @@ impl Physics {
             joints: self
                 .joints
                 .iter()
-                .map(|(_, j)| JointDesc::from_joint(j))
+                .map(|(h, j)| JointDesc::from_joint(h.into(), j))
                 .collect::<Vec<_>>(),
         }
     }
 
-    /// Creates new trimesh collider shape from given mesh node. It also bakes scale into
-    /// vertices of trimesh because rapier does not support collider scaling yet.
-    pub fn make_trimesh(root: Handle<Node>, graph: &Graph) -> ColliderShape {
+    // Collects triangles of every mesh in hierarchy of given root node. Scale of nodes is always
+    // baked into vertices, see `MeshColliderSpace` for the rest of transform.
+    fn mesh_geometry(
+        root: Handle<Node>,
+        graph: &Graph,
+        space: MeshColliderSpace,
+    ) -> (Vec<Point3<f32>>, Vec<Point3<u32>>) {
         let mut mesh_builder = RawMeshBuilder::new(0, 0);
 
         // Create inverse transform that will discard rotation and translation, but leave scaling and
@@ -354,10 +810,13 @@ impl Physics {
         // When global transform of node is combined with this transform, we'll get relative transform
         // with scale baked in. We need to do this because root's transform will be synced with body's
         // but we don't want to bake entire transform including root's transform.
-        let root_inv_transform = graph
-            .isometric_global_transform(root)
-            .try_inverse()
-            .unwrap();
+        let root_inv_transform = match space {
+            MeshColliderSpace::Local => graph
+                .isometric_global_transform(root)
+                .try_inverse()
+                .unwrap(),
+            MeshColliderSpace::World => Matrix4::identity(),
+        };
 
         // Iterate over hierarchy of nodes and build one single trimesh.
         let mut stack = vec![root];
@@ -417,9 +876,109 @@ impl Physics {
             .map(|t| Point3::new(t.0[0], t.0[1], t.0[2]))
             .collect();
 
+        (vertices, indices)
+    }
+
+    /// Creates new trimesh collider shape from given mesh node. It also bakes scale into
+    /// vertices of trimesh because rapier does not support collider scaling yet.
+    pub fn make_trimesh(root: Handle<Node>, graph: &Graph) -> ColliderShape {
+        let (vertices, indices) = Self::mesh_geometry(root, graph, MeshColliderSpace::Local);
+
         ColliderShape::trimesh(vertices, indices)
     }
 
+    /// Creates collider shapes of given kind from every mesh in hierarchy of given root node.
+    /// Each shape is returned together with its position relative to rigid body. Scale of nodes
+    /// is baked into vertices because rapier does not support collider scaling yet. Returns
+    /// empty vector if there are no triangles in the hierarchy.
+    pub fn make_mesh_collider_shapes(
+        root: Handle<Node>,
+        graph: &Graph,
+        shape: MeshColliderShape,
+        space: MeshColliderSpace,
+    ) -> Vec<(Isometry3<f32>, ColliderShape)> {
+        let (vertices, indices) = Self::mesh_geometry(root, graph, space);
+
+        if indices.is_empty() {
+            return Vec::new();
+        }
+
+        match shape {
+            MeshColliderShape::Trimesh => vec![(
+                Isometry3::identity(),
+                ColliderShape::trimesh(vertices, indices),
+            )],
+            MeshColliderShape::ConvexHull => {
+                let hull = transformation::convex_hull(&vertices);
+                let indices = hull
+                    .flat_indices()
+                    .chunks_exact(3)
+                    .map(|t| Point3::new(t[0], t[1], t[2]))
+                    .collect();
+                vec![(
+                    Isometry3::identity(),
+                    ColliderShape::trimesh(hull.coords, indices),
+                )]
+            }
+            MeshColliderShape::ConvexDecomposition {
+                concavity,
+                min_parts,
+            } => {
+                let mut mesh =
+                    TriMesh::new(vertices, None, None, Some(IndexBuffer::Unified(indices)));
+                mesh.recompute_normals();
+                let (parts, _) = transformation::hacd(mesh, concavity, min_parts);
+                parts
+                    .iter()
+                    .filter(|part| !part.coords.is_empty())
+                    .map(|part| fit_oriented_box(&part.coords))
+                    .collect()
+            }
+        }
+    }
+
+    /// Creates new rigid body with colliders generated from every mesh in hierarchy of given
+    /// root node and links the body with the node in given binder. Body is placed at global
+    /// position and rotation of the root node, see `make_mesh_collider_shapes` for more info.
+    /// Returns `None` if there are no triangles in the hierarchy.
+    ///
+    /// # Notes
+    ///
+    /// Geometry of triangle meshes is not saved, it is restored from linked node when scene
+    /// is loaded (see `make_trimesh`), so convex hull will be restored as exact triangle mesh.
+    pub fn mesh_to_collider(
+        &mut self,
+        root: Handle<Node>,
+        shape: MeshColliderShape,
+        status: BodyStatus,
+        graph: &Graph,
+        binder: &mut PhysicsBinder,
+    ) -> Option<RigidBodyHandle> {
+        let shapes = Self::make_mesh_collider_shapes(root, graph, shape, MeshColliderSpace::Local);
+
+        if shapes.is_empty() {
+            return None;
+        }
+
+        let (global_rotation, global_position) = graph.isometric_global_rotation_position(root);
+        let body = self.add_body(
+            RigidBodyBuilder::new(status)
+                .position(Isometry3 {
+                    rotation: global_rotation,
+                    translation: Translation {
+                        vector: global_position,
+                    },
+                })
+                .build(),
+        );
+        for (position, shape) in shapes {
+            self.add_collider(ColliderBuilder::new(shape).position(position).build(), body);
+        }
+        binder.bind(root, body);
+
+        Some(body)
+    }
+
     /// Small helper that creates static physics geometry from given mesh.
     ///
     /// # Notes
@@ -491,20 +1050,305 @@ impl Physics {
         }
     }
 
-    pub(in crate) fn resolve(&mut self, binder: &PhysicsBinder, graph: &Graph) {
+    fn shape_query(&self) -> std::cell::Ref<ShapeQuery> {
+        if !self.shape_query_updated.get() {
+            self.shape_query.borrow_mut().update(&self.colliders);
+            self.shape_query_updated.set(true);
+        }
+        self.shape_query.borrow()
+    }
+
+    // Calls `func` for every triangle of triangle mesh or height field collider which bounds
+    // intersect given world-space bounds. Triangles are in local space of the collider.
+    fn for_each_triangle<F>(
+        &self,
+        shape_query: &ShapeQuery,
+        handle: Index,
+        collider: &Collider,
+        aabb: &AABB,
+        mut func: F,
+    ) -> bool
+    where
+        F: FnMut(&Triangle) -> bool,
+    {
+        let local_aabb = aabb.transform_by(&collider.position().inverse());
+        if let Some(trimesh) = shape_query.trimeshes.get(&handle) {
+            visit_aabb(trimesh.bvt(), &local_aabb, |&i: &usize| {
+                func(&trimesh.triangle_at(i))
+            })
+        } else if let Some(heightfield) = collider.shape().as_heightfield() {
+            let mut proceed = true;
+            heightfield.map_elements_in_local_aabb(&local_aabb, &mut |_, triangle, _| {
+                proceed = proceed && func(triangle);
+            });
+            proceed
+        } else {
+            true
+        }
+    }
+
+    /// Calls `callback` for every collider which is closer than `prediction` to a convex shape
+    /// at given position, including colliders that are penetrated by the shape. Triangle meshes
+    /// and height fields give an intersection per touched triangle. Iteration stops when the
+    /// callback returns false. Sensors are reported too, they can be filtered in the callback.
+    pub fn intersections_with_shape<F>(
+        &self,
+        shape_position: &Isometry3<f32>,
+        shape: &dyn SupportMap<f32>,
+        prediction: f32,
+        groups: InteractionGroups,
+        mut callback: F,
+    ) where
+        F: FnMut(&Collider, ShapeIntersection) -> bool,
+    {
+        let shape_query = self.shape_query();
+        let tree = match shape_query.colliders.as_ref() {
+            Some(tree) => tree,
+            None => return,
+        };
+        let aabb = support_map_aabb(shape_position, shape).loosened(prediction.max(0.0));
+
+        visit_aabb(tree, &aabb, |&handle: &Index| {
+            let collider = match self.colliders.get(handle) {
+                Some(collider) if collider.collision_groups().test(groups) => collider,
+                _ => return true,
+            };
+            if let Some(support_map) = collider_support_map(collider.shape()) {
+                match query::contact_support_map_support_map(
+                    shape_position,
+                    shape,
+                    collider.position(),
+                    support_map,
+                    prediction,
+                ) {
+                    Some(contact) => callback(
+                        collider,
+                        ShapeIntersection {
+                            collider: handle.into(),
+                            triangle: None,
+                            contact,
+                        },
+                    ),
+                    None => true,
+                }
+            } else {
+                let position = collider.position();
+                self.for_each_triangle(&shape_query, handle, collider, &aabb, |triangle| {
+                    let triangle = Triangle::new(
+                        position * triangle.a,
+                        position * triangle.b,
+                        position * triangle.c,
+                    );
+                    match query::contact_support_map_support_map(
+                        shape_position,
+                        shape,
+                        &Isometry3::identity(),
+                        &triangle,
+                        prediction,
+                    ) {
+                        Some(contact) => callback(
+                            collider,
+                            ShapeIntersection {
+                                collider: handle.into(),
+                                triangle: Some(triangle),
+                                contact,
+                            },
+                        ),
+                        None => true,
+                    }
+                })
+            }
+        });
+    }
+
+    /// Casts a convex shape from given position along `velocity` and returns first collider
+    /// (and time of impact) which is hit by the shape on its way, colliders are ignored if
+    /// `filter` returns false for them. Shape is moved by `velocity * toi`, so `max_toi` is
+    /// maximum distance of cast when velocity has unit length. Hit is registered when distance
+    /// between the shape and a collider becomes less than `target_distance`, it gives zero time
+    /// of impact if the shape is already closer than that. Witness points and normals of result
+    /// are in local spaces of the shape and the collider, for triangle meshes and height fields
+    /// local space is local space of the collider.
+    #[allow(clippy::too_many_arguments)]
+    pub fn cast_shape<F>(
+        &self,
+        shape_position: &Isometry3<f32>,
+        velocity: &Vector3<f32>,
+        shape: &dyn SupportMap<f32>,
+        max_toi: f32,
+        target_distance: f32,
+        groups: InteractionGroups,
+        filter: F,
+    ) -> Option<(ColliderHandle, TOI<f32>)>
+    where
+        F: Fn(ColliderHandle, &Collider) -> bool,
+    {
+        let shape_query = self.shape_query();
+        let tree = shape_query.colliders.as_ref()?;
+        let start = support_map_aabb(shape_position, shape);
+        let end = start.transform_by(&Isometry3::translation(
+            velocity.x * max_toi,
+            velocity.y * max_toi,
+            velocity.z * max_toi,
+        ));
+        let aabb = start.merged(&end).loosened(target_distance.max(0.0));
+
+        let mut closest: Option<(ColliderHandle, TOI<f32>)> = None;
+        let zero = Vector3::default();
+        visit_aabb(tree, &aabb, |&handle: &Index| {
+            let collider = match self.colliders.get(handle) {
+                Some(collider)
+                    if collider.collision_groups().test(groups)
+                        && filter(handle.into(), collider) =>
+                {
+                    collider
+                }
+                _ => return true,
+            };
+            let mut cast = |target: &dyn SupportMap<f32>, position: &Isometry3<f32>| {
+                let toi_limit = closest.as_ref().map_or(max_toi, |(_, toi)| toi.toi);
+                if let Some(toi) = query::time_of_impact_support_map_support_map(
+                    shape_position,
+                    velocity,
+                    shape,
+                    position,
+                    &zero,
+                    target,
+                    toi_limit,
+                    target_distance,
+                ) {
+                    if toi.toi <= toi_limit {
+                        closest = Some((handle.into(), toi));
+                    }
+                }
+            };
+            if let Some(support_map) = collider_support_map(collider.shape()) {
+                cast(support_map, collider.position());
+            } else {
+                self.for_each_triangle(&shape_query, handle, collider, &aabb, |triangle| {
+                    cast(triangle, collider.position());
+                    true
+                });
+            }
+            true
+        });
+
+        closest
+    }
+
+    /// Calculates obstruction of a sound that travels from `source` to `listener` by casting
+    /// a ray between them. Gain is a product of transmissions of every collider hit, and cutoff
+    /// frequency is the lowest cutoff frequency of colliders hit. Sensors are ignored.
+    pub fn sound_obstruction(
+        &self,
+        source: Vector3<f32>,
+        listener: Vector3<f32>,
+        options: &SoundObstructionOptions,
+        query_buffer: &mut Vec<Intersection>,
+    ) -> Obstruction {
+        let mut obstruction = Obstruction::NONE;
+
+        if let Some(ray) = Ray::from_two_points(&source, &listener) {
+            self.cast_ray(
+                RayCastOptions {
+                    ray,
+                    max_len: ray.dir.norm(),
+                    groups: options.groups,
+                    sort_results: false,
+                },
+                query_buffer,
+            );
+
+            // Sensors are not solid, sound passes through them freely.
+            for intersection in query_buffer.iter().filter(|intersection| {
+                self.colliders
+                    .get(intersection.collider.into())
+                    .map_or(false, |collider| !collider.is_sensor())
+            }) {
+                let material = options
+                    .materials
+                    .get(&intersection.collider)
+                    .unwrap_or(&options.default_material);
+                obstruction.gain *= material.transmission.min(1.0).max(0.0);
+                obstruction.cutoff_frequency =
+                    obstruction.cutoff_frequency.min(material.cutoff_frequency);
+            }
+        }
+
+        obstruction
+    }
+
+    /// Calculates obstruction for every spatial source in given sound context. It is an
+    /// alternative to `Context::set_obstruction_provider` for the cases when obstruction
+    /// should be calculated using physics of a scene, it should be called once per frame
+    /// after physics update. Do not use it together with obstruction provider, because
+    /// the provider will override results.
+    pub fn update_sound_obstruction(
+        &self,
+        context: &mut Context,
+        options: &SoundObstructionOptions,
+    ) {
+        let listener = context.listener().position();
+        let mut query_buffer = Vec::new();
+        for source in context.sources_mut().iter_mut() {
+            if let SoundSource::Spatial(spatial) = source {
+                let obstruction = self.sound_obstruction(
+                    spatial.position(),
+                    listener,
+                    options,
+                    &mut query_buffer,
+                );
+                spatial.set_obstruction(obstruction);
+            }
+        }
+    }
+
+    // Reconstructs physics world from descriptors. Bodies, colliders and joints get new handles
+    // which may differ from saved ones (if there were gaps in pools), so everything that refers
+    // to them (binder, collider parents, joints, resource links) is remapped.
+    pub(in crate) fn resolve(&mut self, binder: &mut PhysicsBinder, graph: &Graph) {
         assert_eq!(self.bodies.len(), 0);
         assert_eq!(self.colliders.len(), 0);
 
         let mut phys_desc = self.desc.take().unwrap();
 
         self.integration_parameters = phys_desc.integration_parameters.into();
+        self.gravity = phys_desc.gravity;
+
+        // OldHandle -> NewHandle mappings. Descriptors from old versions have no handles, in
+        // this case bodies are restored in the same order as they were saved.
+        let mut body_map = HashMap::new();
+        let mut collider_map = HashMap::new();
+        let mut joint_map = HashMap::new();
+        let mut mass_properties = Vec::new();
 
         for desc in phys_desc.bodies.drain(..) {
-            self.bodies.insert(desc.convert_to_body());
+            let old_handle = desc.handle;
+            let props = desc.mass_properties;
+            let new_handle: RigidBodyHandle = self.bodies.insert(desc.convert_to_body()).into();
+            body_map.insert(
+                if old_handle.is_some() {
+                    old_handle
+                } else {
+                    new_handle
+                },
+                new_handle,
+            );
+            if let Some(props) = props {
+                mass_properties.push((new_handle, props));
+            }
+        }
+
+        for body in binder.node_rigid_body_map.values_mut() {
+            if let Some(&new_body) = body_map.get(body) {
+                *body = new_body;
+            }
         }
 
-        for desc in phys_desc.colliders.drain(..) {
-            if let ColliderShapeDesc::Trimesh(_) = desc.shape {
+        for mut desc in phys_desc.colliders.drain(..) {
+            desc.parent = body_map.get(&desc.parent).copied().unwrap_or(desc.parent);
+            let old_handle = desc.handle;
+            let new_handle = if let ColliderShapeDesc::Trimesh(_) = desc.shape {
                 // Trimeshes are special: we never store data for them, but only getting correct
                 // one from associated mesh in the scene.
                 if let Some(associated_node) = binder.node_of(desc.parent) {
@@ -513,8 +1357,9 @@ impl Physics {
                         let collider =
                             ColliderBuilder::new(Self::make_trimesh(associated_node, graph))
                                 .build();
-                        self.colliders
-                            .insert(collider, desc.parent.into(), &mut self.bodies);
+                        let handle =
+                            self.colliders
+                                .insert(collider, desc.parent.into(), &mut self.bodies);
 
                         Log::writeln(
                             MessageKind::Information,
@@ -522,25 +1367,58 @@ impl Physics {
                                 "Geometry for trimesh {:?} was restored from node at handle {:?}!",
                                 desc.parent, associated_node
                             ),
-                        )
+                        );
+
+                        Some(handle)
                     } else {
-                        Log::writeln(MessageKind::Error,format!("Unable to get geometry for trimesh, node at handle {:?} does not exists!", associated_node))
+                        Log::writeln(MessageKind::Error,format!("Unable to get geometry for trimesh, node at handle {:?} does not exists!", associated_node));
+
+                        None
                     }
+                } else {
+                    None
                 }
             } else {
                 let (collider, parent) = desc.convert_to_collider();
-                self.colliders
-                    .insert(collider, parent.into(), &mut self.bodies);
+                Some(
+                    self.colliders
+                        .insert(collider, parent.into(), &mut self.bodies),
+                )
+            };
+            if let Some(new_handle) = new_handle {
+                collider_map.insert(old_handle, new_handle.into());
+            }
+        }
+
+        // Colliders have added their mass to bodies, so restore exact mass properties.
+        for (handle, props) in mass_properties {
+            if let Some(body) = self.bodies.get_mut(handle.into()) {
+                body.set_mass_properties(props.into(), false);
             }
         }
 
         for desc in phys_desc.joints.drain(..) {
-            self.joints.insert(
+            let new_handle = self.joints.insert(
                 &mut self.bodies,
-                desc.body1.into(),
-                desc.body2.into(),
+                body_map
+                    .get(&desc.body1)
+                    .copied()
+                    .unwrap_or(desc.body1)
+                    .into(),
+                body_map
+                    .get(&desc.body2)
+                    .copied()
+                    .unwrap_or(desc.body2)
+                    .into(),
                 desc.params,
             );
+            joint_map.insert(desc.handle, new_handle.into());
+        }
+
+        for link in self.embedded_resources.iter_mut() {
+            remap_link_handles(&mut link.bodies, &body_map);
+            remap_link_handles(&mut link.colliders, &collider_map);
+            remap_link_handles(&mut link.joints, &joint_map);
         }
     }
 
@@ -556,28 +1434,30 @@ impl Physics {
         let resource_binder = &resource_scene.physics_binder;
         let resource_physics = &resource_scene.physics;
         let mut link = ResourceLink::default();
+        // HandleInResource -> HandleInInstance mapping.
+        let mut body_map = HashMap::new();
 
         // Instantiate rigid bodies.
         for (resource_handle, body) in resource_physics.bodies.iter() {
-            let desc = RigidBodyDesc::<ColliderHandle>::from_body(body);
-            let new_handle = self.bodies.insert(desc.convert_to_body());
+            let desc = RigidBodyDesc::<ColliderHandle>::from_body(resource_handle.into(), body);
+            let new_handle: RigidBodyHandle = self.bodies.insert(desc.convert_to_body()).into();
 
-            link.bodies
-                .insert(new_handle.into(), resource_handle.into());
+            link.bodies.insert(new_handle, resource_handle.into());
+            body_map.insert(RigidBodyHandle::from(resource_handle), new_handle);
         }
 
         // Bind instantiated nodes with their respective rigid bodies from resource.
         for (handle, body) in resource_binder.node_rigid_body_map.iter() {
             let new_handle = *old_to_new.get(handle).unwrap();
-            let new_body = *link.bodies.get(body).unwrap();
+            let new_body = *body_map.get(body).unwrap();
             target_binder.bind(new_handle, new_body);
         }
 
         // Instantiate colliders.
         for (resource_handle, collider) in resource_physics.colliders.iter() {
-            let desc = ColliderDesc::from_collider(collider);
+            let desc = ColliderDesc::from_collider(resource_handle.into(), collider);
             // Remap handle from resource to one that was created above.
-            let remapped_parent = *link.bodies.get(&desc.parent).unwrap();
+            let remapped_parent = *body_map.get(&desc.parent).unwrap();
             if let (ColliderShapeDesc::Trimesh(_), Some(associated_node)) =
                 (desc.shape, target_binder.node_of(remapped_parent))
             {
@@ -612,11 +1492,23 @@ impl Physics {
             }
         }
 
+        // Colliders have added their mass to bodies, so restore exact mass properties.
+        for (resource_handle, &new_handle) in body_map.iter() {
+            let resource_body = resource_physics
+                .bodies
+                .get((*resource_handle).into())
+                .unwrap();
+            self.bodies
+                .get_mut(new_handle.into())
+                .unwrap()
+                .set_mass_properties(*resource_body.mass_properties(), false);
+        }
+
         // Instantiate joints.
         for (resource_handle, joint) in resource_physics.joints.iter() {
-            let desc = JointDesc::<RigidBodyHandle>::from_joint(joint);
-            let new_body1_handle = *link.bodies.get(&joint.body1.into()).unwrap();
-            let new_body2_handle = *link.bodies.get(&joint.body2.into()).unwrap();
+            let desc = JointDesc::<RigidBodyHandle>::from_joint(resource_handle.into(), joint);
+            let new_body1_handle = *body_map.get(&joint.body1.into()).unwrap();
+            let new_body2_handle = *body_map.get(&joint.body2.into()).unwrap();
             let new_handle = self.joints.insert(
                 &mut self.bodies,
                 new_body1_handle.into(),
@@ -643,6 +1535,7 @@ impl Physics {
     /// actual state!
     pub fn add_body(&mut self, rigid_body: RigidBody) -> RigidBodyHandle {
         self.query_updated.set(false);
+        self.shape_query_updated.set(false);
         self.bodies.insert(rigid_body).into()
     }
 
@@ -651,6 +1544,7 @@ impl Physics {
     /// actual state!
     pub fn remove_body(&mut self, rigid_body: RigidBodyHandle) -> Option<RigidBody> {
         self.query_updated.set(false);
+        self.shape_query_updated.set(false);
         self.bodies
             .remove(rigid_body.into(), &mut self.colliders, &mut self.joints)
     }
@@ -664,6 +1558,7 @@ impl Physics {
         rigid_body: RigidBodyHandle,
     ) -> ColliderHandle {
         self.query_updated.set(false);
+        self.shape_query_updated.set(false);
         self.colliders
             .insert(collider, rigid_body.into(), &mut self.bodies)
             .into()
@@ -674,6 +1569,7 @@ impl Physics {
     /// actual state!
     pub fn remove_collider(&mut self, collider_handle: ColliderHandle) -> Option<Collider> {
         self.query_updated.set(false);
+        self.shape_query_updated.set(false);
         self.colliders
             .remove(collider_handle.into(), &mut self.bodies, true)
     }
@@ -691,6 +1587,7 @@ impl Physics {
         J: Into<JointParams>,
     {
         self.query_updated.set(false);
+        self.shape_query_updated.set(false);
         self.joints
             .insert(&mut self.bodies, body1.into(), body2.into(), joint_params)
             .into()
@@ -701,6 +1598,7 @@ impl Physics {
     /// actual state!
     pub fn remove_joint(&mut self, joint_handle: JointHandle, wake_up: bool) -> Option<Joint> {
         self.query_updated.set(false);
+        self.shape_query_updated.set(false);
         self.joints
             .remove(joint_handle.into(), &mut self.bodies, wake_up)
     }
@@ -767,9 +1665,57 @@ impl Into<BodyStatus> for BodyStatusDesc {
     }
 }
 
+#[derive(Default, Copy, Clone, Debug)]
+#[doc(hidden)]
+pub struct MassPropertiesDesc {
+    pub local_center_of_mass: Vector3<f32>,
+    pub inv_mass: f32,
+    pub inv_principal_inertia_sqrt: Vector3<f32>,
+    pub principal_inertia_local_frame: UnitQuaternion<f32>,
+}
+
+impl From<MassProperties> for MassPropertiesDesc {
+    fn from(props: MassProperties) -> Self {
+        Self {
+            local_center_of_mass: props.local_com.coords,
+            inv_mass: props.inv_mass,
+            inv_principal_inertia_sqrt: props.inv_principal_inertia_sqrt,
+            principal_inertia_local_frame: props.principal_inertia_local_frame,
+        }
+    }
+}
+
+impl Into<MassProperties> for MassPropertiesDesc {
+    fn into(self) -> MassProperties {
+        MassProperties {
+            local_com: Point3::from(self.local_center_of_mass),
+            inv_mass: self.inv_mass,
+            inv_principal_inertia_sqrt: self.inv_principal_inertia_sqrt,
+            principal_inertia_local_frame: self.principal_inertia_local_frame,
+        }
+    }
+}
+
+impl Visit for MassPropertiesDesc {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.local_center_of_mass
+            .visit("LocalCenterOfMass", visitor)?;
+        self.inv_mass.visit("InvMass", visitor)?;
+        self.inv_principal_inertia_sqrt
+            .visit("InvPrincipalInertiaSqrt", visitor)?;
+        self.principal_inertia_local_frame
+            .visit("PrincipalInertiaLocalFrame", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
 #[derive(Default, Clone, Debug)]
 #[doc(hidden)]
 pub struct RigidBodyDesc<C> {
+    pub handle: RigidBodyHandle,
     pub position: Vector3<f32>,
     pub rotation: UnitQuaternion<f32>,
     pub linvel: Vector3<f32>,
@@ -778,12 +1724,19 @@ pub struct RigidBodyDesc<C> {
     pub status: BodyStatusDesc,
     pub colliders: Vec<C>,
     pub mass: f32,
+    // Final mass properties of a body (including contribution of colliders), they're applied
+    // after colliders were attached to the body. Old descriptors does not have them, in this
+    // case `mass` is used.
+    pub mass_properties: Option<MassPropertiesDesc>,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
 }
 
 impl<C: From<Index>> RigidBodyDesc<C> {
     #[doc(hidden)]
-    pub fn from_body(body: &RigidBody) -> Self {
+    pub fn from_body(handle: RigidBodyHandle, body: &RigidBody) -> Self {
         Self {
+            handle,
             position: body.position().translation.vector,
             rotation: body.position().rotation,
             linvel: *body.linvel(),
@@ -792,21 +1745,28 @@ impl<C: From<Index>> RigidBodyDesc<C> {
             sleeping: body.is_sleeping(),
             colliders: body.colliders().iter().map(|&c| C::from(c)).collect(),
             mass: body.mass(),
+            mass_properties: Some((*body.mass_properties()).into()),
+            linear_damping: body.linear_damping,
+            angular_damping: body.angular_damping,
         }
     }
 
     fn convert_to_body(self) -> RigidBody {
-        let mut body = RigidBodyBuilder::new(self.status.into())
+        let mut builder = RigidBodyBuilder::new(self.status.into())
             .position(Isometry3 {
                 translation: Translation {
                     vector: self.position,
                 },
                 rotation: self.rotation,
             })
-            .mass(self.mass, true)
             .linvel(self.linvel.x, self.linvel.y, self.linvel.z)
             .angvel(AngVector::new(self.angvel.x, self.angvel.y, self.angvel.z))
-            .build();
+            .linear_damping(self.linear_damping)
+            .angular_damping(self.angular_damping);
+        if self.mass_properties.is_none() {
+            builder = builder.mass(self.mass, true);
+        }
+        let mut body = builder.build();
         if self.sleeping {
             body.sleep();
         }
@@ -826,6 +1786,10 @@ impl<C: Visit + Default + 'static> Visit for RigidBodyDesc<C> {
         self.status.visit("Status", visitor)?;
         self.colliders.visit("Colliders", visitor)?;
         let _ = self.mass.visit("Mass", visitor);
+        let _ = self.handle.visit("Handle", visitor);
+        let _ = self.mass_properties.visit("MassProperties", visitor);
+        let _ = self.linear_damping.visit("LinearDamping", visitor);
+        let _ = self.angular_damping.visit("AngularDamping", visitor);
 
         visitor.leave_region()
     }
@@ -1182,6 +2146,7 @@ impl Visit for ColliderShapeDesc {
 #[derive(Clone, Debug)]
 #[doc(hidden)]
 pub struct ColliderDesc<R> {
+    pub handle: ColliderHandle,
     pub shape: ColliderShapeDesc,
     pub parent: R,
     pub friction: f32,
@@ -1197,6 +2162,7 @@ pub struct ColliderDesc<R> {
 impl<R: Default> Default for ColliderDesc<R> {
     fn default() -> Self {
         Self {
+            handle: Default::default(),
             shape: Default::default(),
             parent: Default::default(),
             friction: 0.5,
@@ -1212,8 +2178,9 @@ impl<R: Default> Default for ColliderDesc<R> {
 }
 
 impl<R: From<Index>> ColliderDesc<R> {
-    fn from_collider(collider: &Collider) -> Self {
+    fn from_collider(handle: ColliderHandle, collider: &Collider) -> Self {
         Self {
+            handle,
             shape: ColliderShapeDesc::from_collider_shape(collider.shape()),
             parent: R::from(collider.parent()),
             friction: collider.friction,
@@ -1262,6 +2229,7 @@ impl<R: 'static + Visit + Default> Visit for ColliderDesc<R> {
         self.rotation.visit("Rotation", visitor)?;
         self.collision_groups.visit("CollisionGroups", visitor)?;
         self.solver_groups.visit("SolverGroups", visitor)?;
+        let _ = self.handle.visit("Handle", visitor);
 
         visitor.leave_region()
     }
@@ -1278,6 +2246,13 @@ impl Visit for Physics {
         } else {
             self.generate_desc()
         };
+        if !visitor.is_reading() && !self.save_dynamic_state {
+            for body in desc.bodies.iter_mut() {
+                body.linvel = Default::default();
+                body.angvel = Default::default();
+                body.sleeping = false;
+            }
+        }
         desc.visit("Desc", visitor)?;
 
         let _ = self.embedded_resources.visit("EmbeddedResources", visitor);
@@ -1456,6 +2431,8 @@ pub struct PrismaticJointDesc {
     pub local_axis1: Vector3<f32>,
     pub local_anchor2: Vector3<f32>,
     pub local_axis2: Vector3<f32>,
+    pub limits_enabled: bool,
+    pub limits: [f32; 2],
     // TODO: Rapier does not provide a way to extract tangents, so we can't
     // serialize them yet.
     // pub local_tangent1: Vector3<f32>,
@@ -1470,6 +2447,9 @@ impl Visit for PrismaticJointDesc {
         self.local_axis1.visit("LocalAxis1", visitor)?;
         self.local_anchor2.visit("LocalAnchor2", visitor)?;
         self.local_axis2.visit("LocalAxis2", visitor)?;
+        let _ = self.limits_enabled.visit("LimitsEnabled", visitor);
+        let _ = self.limits[0].visit("LimitMin", visitor);
+        let _ = self.limits[1].visit("LimitMax", visitor);
 
         // TODO: Rapier does not provide a way to extract tangents, so we can't
         // serialize them yet.
@@ -1538,14 +2518,19 @@ impl Into<JointParams> for JointParamsDesc {
                     rotation: v.local_anchor2_rotation,
                 },
             )),
-            JointParamsDesc::PrismaticJoint(v) => JointParams::from(PrismaticJoint::new(
-                Point3::from(v.local_anchor1),
-                Unit::<Vector3<f32>>::new_normalize(v.local_axis1),
-                Default::default(), // TODO
-                Point3::from(v.local_anchor2),
-                Unit::<Vector3<f32>>::new_normalize(v.local_axis2),
-                Default::default(), // TODO
-            )),
+            JointParamsDesc::PrismaticJoint(v) => {
+                let mut joint = PrismaticJoint::new(
+                    Point3::from(v.local_anchor1),
+                    Unit::<Vector3<f32>>::new_normalize(v.local_axis1),
+                    Default::default(), // TODO
+                    Point3::from(v.local_anchor2),
+                    Unit::<Vector3<f32>>::new_normalize(v.local_axis2),
+                    Default::default(), // TODO
+                );
+                joint.limits_enabled = v.limits_enabled;
+                joint.limits = v.limits;
+                JointParams::from(joint)
+            }
             JointParamsDesc::RevoluteJoint(v) => JointParams::from(RevoluteJoint::new(
                 Point3::from(v.local_anchor1),
                 Unit::<Vector3<f32>>::new_normalize(v.local_axis1),
@@ -1618,6 +2603,8 @@ impl JointParamsDesc {
                 local_axis1: v.local_axis1().into_inner(),
                 local_anchor2: v.local_anchor2.coords,
                 local_axis2: v.local_axis2().into_inner(),
+                limits_enabled: v.limits_enabled,
+                limits: v.limits,
             }),
             JointParams::RevoluteJoint(v) => Self::RevoluteJoint(RevoluteJointDesc {
                 local_anchor1: v.local_anchor1.coords,
@@ -1632,6 +2619,7 @@ impl JointParamsDesc {
 #[derive(Clone, Debug, Default)]
 #[doc(hidden)]
 pub struct JointDesc<R> {
+    pub handle: JointHandle,
     pub body1: R,
     pub body2: R,
     pub params: JointParamsDesc,
@@ -1639,8 +2627,9 @@ pub struct JointDesc<R> {
 
 impl<R: From<Index>> JointDesc<R> {
     #[doc(hidden)]
-    pub fn from_joint(joint: &Joint) -> Self {
+    pub fn from_joint(handle: JointHandle, joint: &Joint) -> Self {
         Self {
+            handle,
             body1: R::from(joint.body1),
             body2: R::from(joint.body2),
             params: JointParamsDesc::from_params(&joint.params),
@@ -1655,6 +2644,7 @@ impl<R: 'static + Visit + Default> Visit for JointDesc<R> {
         self.body1.visit("Body1", visitor)?;
         self.body2.visit("Body2", visitor)?;
         self.params.visit("Params", visitor)?;
+        let _ = self.handle.visit("Handle", visitor);
 
         visitor.leave_region()
     }
@@ -1684,3 +2674,231 @@ impl Visit for PhysicsDesc {
         visitor.leave_region()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{algebra::Matrix4, pool::Handle},
+        renderer::surface::{Surface, SurfaceSharedData},
+        scene::{
+            base::BaseBuilder,
+            graph::Graph,
+            mesh::MeshBuilder,
+            node::Node,
+            physics::{
+                AcousticMaterial, MeshColliderShape, MeshColliderSpace, Physics,
+                SoundObstructionOptions,
+            },
+            transform::TransformBuilder,
+            PhysicsBinder,
+        },
+    };
+    use rapier3d::{
+        dynamics::{BodyStatus, RigidBodyBuilder},
+        geometry::{ColliderBuilder, ColliderShape},
+        na::{Isometry3, Vector3},
+    };
+    use rg3d_sound::source::spatial::Obstruction;
+    use std::sync::{Arc, RwLock};
+
+    #[test]
+    fn sound_obstruction_test() {
+        let mut physics = Physics::new();
+        let body = physics.add_body(RigidBodyBuilder::new_static().build());
+        let wall = physics.add_collider(ColliderBuilder::cuboid(0.1, 5.0, 5.0).build(), body);
+
+        let material = AcousticMaterial {
+            transmission: 0.25,
+            cutoff_frequency: 500.0,
+        };
+        let mut options = SoundObstructionOptions::default();
+        options.materials.insert(wall, material);
+
+        let mut query_buffer = Vec::new();
+
+        let obstruction = physics.sound_obstruction(
+            Vector3::new(-2.0, 0.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            &options,
+            &mut query_buffer,
+        );
+        assert_eq!(obstruction.gain, material.transmission);
+        assert_eq!(obstruction.cutoff_frequency, material.cutoff_frequency);
+
+        // Both points are on the same side of the wall.
+        let obstruction = physics.sound_obstruction(
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            &options,
+            &mut query_buffer,
+        );
+        assert_eq!(obstruction, Obstruction::NONE);
+
+        // Sensors do not obstruct the sound.
+        let sensor = physics.add_collider(
+            ColliderBuilder::cuboid(0.1, 5.0, 5.0)
+                .translation(1.5, 0.0, 0.0)
+                .sensor(true)
+                .build(),
+            body,
+        );
+        options.materials.insert(sensor, material);
+        let obstruction = physics.sound_obstruction(
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            &options,
+            &mut query_buffer,
+        );
+        assert!(query_buffer.iter().any(|i| i.collider == sensor));
+        assert_eq!(obstruction, Obstruction::NONE);
+    }
+
+    // Unit cube at (0, 5, 0) scaled by (2, 1, 1).
+    fn make_scaled_cube(graph: &mut Graph) -> Handle<Node> {
+        let data = SurfaceSharedData::make_cube(Matrix4::identity());
+        let mesh = MeshBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(Vector3::new(0.0, 5.0, 0.0))
+                    .with_local_scale(Vector3::new(2.0, 1.0, 1.0))
+                    .build(),
+            ),
+        )
+        .with_surfaces(vec![Surface::new(Arc::new(RwLock::new(data)))])
+        .build(graph);
+        graph.update_hierarchical_data();
+        mesh
+    }
+
+    // Returns sorted half extents of AABB of a shape at given position.
+    fn half_extents(position: &Isometry3<f32>, shape: &ColliderShape) -> Vec<f32> {
+        let aabb = shape.compute_aabb(position);
+        let mut extents = aabb.half_extents().as_slice().to_vec();
+        extents.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        extents
+    }
+
+    fn assert_extents(extents: Vec<f32>, expected: [f32; 3]) {
+        for (a, b) in extents.iter().zip(expected.iter()) {
+            assert!((a - b).abs() < 1.0e-3, "{:?} != {:?}", extents, expected);
+        }
+    }
+
+    #[test]
+    fn mesh_collider_shapes() {
+        let mut graph = Graph::new();
+        let cube = make_scaled_cube(&mut graph);
+
+        // Scale is baked, position is not.
+        for &shape in &[MeshColliderShape::Trimesh, MeshColliderShape::ConvexHull] {
+            let shapes =
+                Physics::make_mesh_collider_shapes(cube, &graph, shape, MeshColliderSpace::Local);
+            assert_eq!(shapes.len(), 1);
+            let (position, shape) = &shapes[0];
+            assert!(shape.as_trimesh().is_some());
+            assert_extents(half_extents(position, shape), [0.5, 0.5, 1.0]);
+            assert!(shape.compute_aabb(position).center().coords.norm() < 1.0e-3);
+        }
+
+        // Whole transform is baked.
+        let shapes = Physics::make_mesh_collider_shapes(
+            cube,
+            &graph,
+            MeshColliderShape::Trimesh,
+            MeshColliderSpace::World,
+        );
+        let (position, shape) = &shapes[0];
+        let aabb = shape.compute_aabb(position);
+        assert!((aabb.center().coords - Vector3::new(0.0, 5.0, 0.0)).norm() < 1.0e-3);
+
+        // Convex mesh is decomposed into a single box.
+        let shapes = Physics::make_mesh_collider_shapes(
+            cube,
+            &graph,
+            MeshColliderShape::ConvexDecomposition {
+                concavity: 0.03,
+                min_parts: 1,
+            },
+            MeshColliderSpace::Local,
+        );
+        assert_eq!(shapes.len(), 1);
+        let (position, shape) = &shapes[0];
+        let cuboid = shape.as_cuboid().unwrap();
+        let mut extents = cuboid.half_extents.as_slice().to_vec();
+        extents.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_extents(extents, [0.5, 0.5, 1.0]);
+        assert!(position.translation.vector.norm() < 1.0e-3);
+
+        // Two separate cubes cannot be merged into one convex part.
+        let surfaces = [-2.0, 2.0]
+            .iter()
+            .map(|&x| {
+                let data = SurfaceSharedData::make_cube(Matrix4::new_translation(&Vector3::new(
+                    x, 0.0, 0.0,
+                )));
+                Surface::new(Arc::new(RwLock::new(data)))
+            })
+            .collect();
+        let pair = MeshBuilder::new(BaseBuilder::new())
+            .with_surfaces(surfaces)
+            .build(&mut graph);
+        graph.update_hierarchical_data();
+        let shapes = Physics::make_mesh_collider_shapes(
+            pair,
+            &graph,
+            MeshColliderShape::ConvexDecomposition {
+                concavity: 0.03,
+                min_parts: 1,
+            },
+            MeshColliderSpace::Local,
+        );
+        assert_eq!(shapes.len(), 2);
+        for (position, shape) in shapes.iter() {
+            assert_extents(half_extents(position, shape), [0.5, 0.5, 0.5]);
+            assert!((position.translation.vector.x.abs() - 2.0).abs() < 1.0e-3);
+        }
+    }
+
+    #[test]
+    fn mesh_to_collider() {
+        let mut graph = Graph::new();
+        let cube = make_scaled_cube(&mut graph);
+        let mut physics = Physics::new();
+        let mut binder = PhysicsBinder::default();
+
+        let body = physics
+            .mesh_to_collider(
+                cube,
+                MeshColliderShape::ConvexDecomposition {
+                    concavity: 0.03,
+                    min_parts: 1,
+                },
+                BodyStatus::Dynamic,
+                &graph,
+                &mut binder,
+            )
+            .unwrap();
+        assert_eq!(binder.body_of(cube), Some(body));
+
+        let body = physics.bodies.get(body.into()).unwrap();
+        assert_eq!(
+            body.position().translation.vector,
+            Vector3::new(0.0, 5.0, 0.0)
+        );
+        assert_eq!(body.colliders().len(), 1);
+        // Box has volume, so dynamic body has mass.
+        assert!(body.mass() > 0.0);
+
+        // Empty node gives nothing.
+        let empty = BaseBuilder::new().build(&mut graph);
+        assert!(physics
+            .mesh_to_collider(
+                empty,
+                MeshColliderShape::Trimesh,
+                BodyStatus::Static,
+                &graph,
+                &mut binder
+            )
+            .is_none());
+    }
+}
@@ -0,0 +1,168 @@
+//! Screen-space post effects of a camera.
+//!
+//! Each camera has its own set of small post effects which are applied when the camera renders
+//! into the back buffer (cameras of scenes with render target are not affected). Every effect
+//! is optional, `None` disables it. All effects are done in a single pass together with tone
+//! mapping and color grading, in the following fixed order:
+//!
+//! 1. Chromatic aberration - frame is sampled with offsets for red and blue channels, it is done
+//!    in linear HDR space.
+//! 2. Exposure and tone mapping (see `Renderer::set_tone_mapping`).
+//! 3. Gamma correction (see `Renderer::set_gamma`).
+//! 4. Color grading (see `Renderer::set_color_grading`).
+//! 5. Vignette - darkens (or tints) edges of the frame in display space.
+//! 6. Film grain - noise is added last, so it is not affected by other effects.
+//!
+//! Parameters are plain public fields, so they can be changed every frame from game code, for
+//! example to pulse vignette when player takes damage.
+//!
+//! # Example
+//!
+//! ```
+//! use rg3d::{
+//!     core::color::Color,
+//!     scene::{
+//!         base::BaseBuilder,
+//!         camera::CameraBuilder,
+//!         post_effects::{FilmGrain, PostEffects, Vignette},
+//!     },
+//! };
+//!
+//! let camera = CameraBuilder::new(BaseBuilder::new())
+//!     .with_post_effects(PostEffects {
+//!         vignette: Some(Vignette {
+//!             color: Color::from_rgba(40, 0, 0, 200),
+//!             ..Default::default()
+//!         }),
+//!         film_grain: Some(FilmGrain::default()),
+//!         ..Default::default()
+//!     })
+//!     .build_camera();
+//! ```
+
+use crate::core::{
+    color::Color,
+    visitor::{Visit, VisitResult, Visitor},
+};
+
+/// Darkens (or tints) edges of the frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Vignette {
+    /// Distance from the center of the frame at which vignette starts. Distance is normalized,
+    /// so 0 is the center and 1 is a corner of the frame.
+    pub radius: f32,
+    /// Width of transition from clear center to full vignette, in the same units as `radius`.
+    pub softness: f32,
+    /// Color of vignette, alpha channel defines maximum strength of vignette.
+    pub color: Color,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            softness: 0.5,
+            color: Color::BLACK,
+        }
+    }
+}
+
+impl Visit for Vignette {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.radius.visit("Radius", visitor)?;
+        self.softness.visit("Softness", visitor)?;
+        self.color.visit("Color", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Splits color channels towards edges of the frame, imitates imperfect camera lens.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ChromaticAberration {
+    /// Offset of red and blue channels at edges of the frame, as a fraction of the frame size.
+    pub strength: f32,
+}
+
+impl Default for ChromaticAberration {
+    fn default() -> Self {
+        Self { strength: 0.005 }
+    }
+}
+
+impl Visit for ChromaticAberration {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.strength.visit("Strength", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Adds noise to the frame, imitates film grain.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FilmGrain {
+    /// Maximum amount of noise added to each color channel.
+    pub intensity: f32,
+    /// Seed of the noise pattern.
+    pub seed: f32,
+    /// If true, renderer changes the seed every frame, so the noise is animated. Otherwise the
+    /// noise pattern is static.
+    pub animated: bool,
+}
+
+impl Default for FilmGrain {
+    fn default() -> Self {
+        Self {
+            intensity: 0.05,
+            seed: 0.0,
+            animated: true,
+        }
+    }
+}
+
+impl Visit for FilmGrain {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.intensity.visit("Intensity", visitor)?;
+        self.seed.visit("Seed", visitor)?;
+        self.animated.visit("Animated", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// See module docs.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PostEffects {
+    /// Optional vignette.
+    pub vignette: Option<Vignette>,
+    /// Optional chromatic aberration.
+    pub chromatic_aberration: Option<ChromaticAberration>,
+    /// Optional film grain.
+    pub film_grain: Option<FilmGrain>,
+}
+
+impl PostEffects {
+    /// Returns true if at least one effect is enabled.
+    pub fn is_any_enabled(&self) -> bool {
+        self.vignette.is_some() || self.chromatic_aberration.is_some() || self.film_grain.is_some()
+    }
+}
+
+impl Visit for PostEffects {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.vignette.visit("Vignette", visitor)?;
+        self.chromatic_aberration
+            .visit("ChromaticAberration", visitor)?;
+        self.film_grain.visit("FilmGrain", visitor)?;
+
+        visitor.leave_region()
+    }
+}
@@ -0,0 +1,1029 @@
+//! Contains all structures and methods to create and manage terrains.
+//!
+//! Terrain is a height field - a grid of height samples that is stretched over rectangle
+//! in XZ plane of local coordinates, first sample is at the origin. Heights are usually
+//! taken from a grayscale heightmap image (each row of the image is a row of samples along
+//! Z axis), but they also can be set directly, for example from procedural noise.
+//!
+//! # Layers
+//!
+//! Terrain surface is a blend of up to [MAX_TERRAIN_LAYERS](constant.MAX_TERRAIN_LAYERS.html)
+//! layers, each layer has its own diffuse and normal textures which are tiled over the
+//! terrain. Weights of layers are taken from control map - each channel of the map (R, G, B
+//! and A) defines weight of respective layer at the point. Without control map only first
+//! layer is visible.
+//!
+//! # Level of details
+//!
+//! Terrain geometry is split into square chunks, each chunk is frustum-culled independently
+//! and has a set of meshes with decreasing amount of details. Each next level skips every
+//! second height sample of previous, it is selected when distance from camera to a chunk
+//! exceeds `lod_distance * 2^(level - 1)`. Chunks have vertical "skirts" along their borders
+//! that hide cracks between neighbour chunks with different levels of details.
+//!
+//! # Limitations
+//!
+//! Terrain does not cast shadows and does not have lightmaps.
+
+use crate::{
+    core::{
+        algebra::{Point3, Vector2, Vector3, Vector4},
+        math::{aabb::AxisAlignedBoundingBox, ray::Ray, TriangleDefinition},
+        pool::Handle,
+        visitor::{Data, Visit, VisitResult, Visitor},
+    },
+    renderer::surface::{SurfaceSharedData, Vertex},
+    resource::texture::{Texture, TextureKind, TexturePixelKind, TextureState},
+    scene::{
+        base::{Base, BaseBuilder},
+        graph::Graph,
+        node::Node,
+    },
+    utils::log::{Log, MessageKind},
+};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, RwLock},
+};
+
+/// Maximum amount of layers of a terrain, control map has one channel per layer.
+pub const MAX_TERRAIN_LAYERS: usize = 4;
+
+/// Textures of a single layer of terrain, see module docs.
+#[derive(Debug, Clone)]
+pub struct TerrainLayer {
+    diffuse_texture: Option<Texture>,
+    normal_texture: Option<Texture>,
+    tile_scale: f32,
+}
+
+impl Default for TerrainLayer {
+    fn default() -> Self {
+        Self {
+            diffuse_texture: None,
+            normal_texture: None,
+            tile_scale: 1.0,
+        }
+    }
+}
+
+impl Visit for TerrainLayer {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.diffuse_texture.visit("DiffuseTexture", visitor)?;
+        self.normal_texture.visit("NormalTexture", visitor)?;
+        self.tile_scale.visit("TileScale", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+impl TerrainLayer {
+    /// Creates new layer with given diffuse texture.
+    pub fn new(diffuse_texture: Option<Texture>) -> Self {
+        Self {
+            diffuse_texture,
+            ..Default::default()
+        }
+    }
+
+    /// Sets normal texture of the layer.
+    pub fn with_normal_texture(mut self, normal_texture: Option<Texture>) -> Self {
+        self.normal_texture = normal_texture;
+        self
+    }
+
+    /// Sets how many times textures of the layer are repeated over whole terrain.
+    pub fn with_tile_scale(mut self, tile_scale: f32) -> Self {
+        self.tile_scale = tile_scale;
+        self
+    }
+
+    /// Returns diffuse texture of the layer.
+    pub fn diffuse_texture(&self) -> Option<Texture> {
+        self.diffuse_texture.clone()
+    }
+
+    /// Sets diffuse texture of the layer.
+    pub fn set_diffuse_texture(&mut self, diffuse_texture: Option<Texture>) {
+        self.diffuse_texture = diffuse_texture;
+    }
+
+    /// Returns normal texture of the layer.
+    pub fn normal_texture(&self) -> Option<Texture> {
+        self.normal_texture.clone()
+    }
+
+    /// Sets normal texture of the layer.
+    pub fn set_normal_texture(&mut self, normal_texture: Option<Texture>) {
+        self.normal_texture = normal_texture;
+    }
+
+    /// Returns how many times textures of the layer are repeated over whole terrain.
+    pub fn tile_scale(&self) -> f32 {
+        self.tile_scale
+    }
+
+    /// Sets how many times textures of the layer are repeated over whole terrain.
+    pub fn set_tile_scale(&mut self, tile_scale: f32) {
+        self.tile_scale = tile_scale;
+    }
+}
+
+/// Part of terrain geometry that is culled and switches level of details as a whole.
+#[derive(Debug, Clone)]
+pub struct TerrainChunk {
+    lods: Vec<Arc<RwLock<SurfaceSharedData>>>,
+    bounds: AxisAlignedBoundingBox,
+}
+
+impl TerrainChunk {
+    /// Returns geometry of each level of details, first level is the most detailed one.
+    pub fn lods(&self) -> &[Arc<RwLock<SurfaceSharedData>>] {
+        &self.lods
+    }
+
+    /// Returns bounds of the chunk in local coordinates of terrain.
+    pub fn bounding_box(&self) -> AxisAlignedBoundingBox {
+        self.bounds
+    }
+}
+
+/// See module docs.
+#[derive(Debug)]
+pub struct Terrain {
+    base: Base,
+    width: f32,
+    length: f32,
+    height_scale: f32,
+    heightmap: Option<Texture>,
+    heights: Vec<f32>,
+    columns: u32,
+    rows: u32,
+    chunk_size: u32,
+    lod_count: u32,
+    lod_distance: f32,
+    layers: Vec<TerrainLayer>,
+    control_map: Option<Texture>,
+    chunks: Vec<TerrainChunk>,
+    heightmap_dirty: bool,
+    geometry_dirty: bool,
+}
+
+impl Deref for Terrain {
+    type Target = Base;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl DerefMut for Terrain {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+impl Default for Terrain {
+    fn default() -> Self {
+        TerrainBuilder::new(BaseBuilder::new()).build_terrain()
+    }
+}
+
+impl Visit for Terrain {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.base.visit("Common", visitor)?;
+        self.width.visit("Width", visitor)?;
+        self.length.visit("Length", visitor)?;
+        self.height_scale.visit("HeightScale", visitor)?;
+        self.heightmap.visit("Heightmap", visitor)?;
+        self.columns.visit("Columns", visitor)?;
+        self.rows.visit("Rows", visitor)?;
+
+        // Heights are saved as raw bytes, terrain may have millions of samples.
+        let mut bytes = self
+            .heights
+            .iter()
+            .flat_map(|h| h.to_le_bytes().to_vec())
+            .collect::<Vec<u8>>();
+        Data::new(&mut bytes).visit("Heights", visitor)?;
+        if visitor.is_reading() {
+            self.heights = bytes
+                .chunks_exact(4)
+                .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                .collect();
+        }
+
+        self.chunk_size.visit("ChunkSize", visitor)?;
+        self.lod_count.visit("LodCount", visitor)?;
+        self.lod_distance.visit("LodDistance", visitor)?;
+        self.layers.visit("Layers", visitor)?;
+        self.control_map.visit("ControlMap", visitor)?;
+
+        if visitor.is_reading() {
+            // Heights are already loaded, there is no need to read heightmap again.
+            self.heightmap_dirty = false;
+            self.geometry_dirty = true;
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// Reads first channel of every pixel of a heightmap and normalizes it to [0; 1] range.
+fn heights_from_texture(texture: &Texture) -> Option<(u32, u32, Vec<f32>)> {
+    let state = texture.state();
+    let data = match &*state {
+        TextureState::Ok(data) => data,
+        _ => return None,
+    };
+
+    let (width, height) = match data.kind {
+        TextureKind::Rectangle { width, height } => (width, height),
+        _ => return None,
+    };
+
+    let u8_channel = |stride: usize, offset: usize| {
+        data.bytes
+            .chunks_exact(stride)
+            .map(|p| p[offset] as f32 / 255.0)
+            .collect::<Vec<_>>()
+    };
+    let u16_channel = |stride: usize| {
+        data.bytes
+            .chunks_exact(stride)
+            .map(|p| u16::from_le_bytes([p[0], p[1]]) as f32 / 65535.0)
+            .collect::<Vec<_>>()
+    };
+
+    let heights = match data.pixel_kind {
+        TexturePixelKind::R8 => u8_channel(1, 0),
+        TexturePixelKind::RG8 => u8_channel(2, 0),
+        TexturePixelKind::RGB8 => u8_channel(3, 0),
+        TexturePixelKind::RGBA8 => u8_channel(4, 0),
+        TexturePixelKind::BGR8 => u8_channel(3, 2),
+        TexturePixelKind::BGRA8 => u8_channel(4, 2),
+        TexturePixelKind::R16 => u16_channel(2),
+        TexturePixelKind::RG16 => u16_channel(4),
+        TexturePixelKind::RGB16 => u16_channel(6),
+        TexturePixelKind::RGBA16 => u16_channel(8),
+        TexturePixelKind::DXT1RGB
+        | TexturePixelKind::DXT1RGBA
+        | TexturePixelKind::DXT3RGBA
+        | TexturePixelKind::DXT5RGBA => return None,
+    };
+
+    if heights.len() == (width * height) as usize {
+        Some((width, height, heights))
+    } else {
+        None
+    }
+}
+
+/// Returns indices of samples of a chunk for given level of details, last sample of chunk
+/// is always included so neighbour chunks share border samples.
+fn lod_samples(begin: u32, end: u32, step: u32) -> Vec<u32> {
+    let mut samples = (begin..end).step_by(step as usize).collect::<Vec<_>>();
+    samples.push(end);
+    samples
+}
+
+impl Terrain {
+    /// Creates a raw copy of a terrain node.
+    pub fn raw_copy(&self) -> Self {
+        Self {
+            base: self.base.raw_copy(),
+            width: self.width,
+            length: self.length,
+            height_scale: self.height_scale,
+            heightmap: self.heightmap.clone(),
+            heights: self.heights.clone(),
+            columns: self.columns,
+            rows: self.rows,
+            chunk_size: self.chunk_size,
+            lod_count: self.lod_count,
+            lod_distance: self.lod_distance,
+            layers: self.layers.clone(),
+            control_map: self.control_map.clone(),
+            chunks: self.chunks.clone(),
+            heightmap_dirty: self.heightmap_dirty,
+            geometry_dirty: self.geometry_dirty,
+        }
+    }
+
+    /// Returns size of terrain along X axis in local coordinates.
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    /// Returns size of terrain along Z axis in local coordinates.
+    pub fn length(&self) -> f32 {
+        self.length
+    }
+
+    /// Sets size of terrain in XZ plane in local coordinates.
+    pub fn set_size(&mut self, width: f32, length: f32) {
+        self.width = width;
+        self.length = length;
+        self.geometry_dirty = true;
+    }
+
+    /// Returns height of terrain at points with height sample equal to 1.0.
+    pub fn height_scale(&self) -> f32 {
+        self.height_scale
+    }
+
+    /// Sets height of terrain at points with height sample equal to 1.0.
+    pub fn set_height_scale(&mut self, height_scale: f32) {
+        self.height_scale = height_scale;
+        self.geometry_dirty = true;
+    }
+
+    /// Returns heightmap texture from which heights were taken.
+    pub fn heightmap(&self) -> Option<Texture> {
+        self.heightmap.clone()
+    }
+
+    /// Sets new heightmap texture. Heights will be taken from the texture on next update
+    /// of the graph, after the texture is loaded.
+    pub fn set_heightmap(&mut self, heightmap: Option<Texture>) {
+        self.heightmap_dirty = heightmap.is_some();
+        self.heightmap = heightmap;
+    }
+
+    /// Returns height samples, row by row, in [0; 1] range.
+    pub fn heights(&self) -> &[f32] {
+        &self.heights
+    }
+
+    /// Returns amount of height samples along X axis.
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    /// Returns amount of height samples along Z axis.
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    /// Sets height samples directly, `heights` must contain `columns * rows` samples row by
+    /// row, samples should be in [0; 1] range. Heightmap texture is detached from terrain.
+    ///
+    /// # Panic
+    ///
+    /// Panics if amount of samples does not match size of the grid.
+    pub fn set_heights(&mut self, columns: u32, rows: u32, heights: Vec<f32>) {
+        assert_eq!((columns * rows) as usize, heights.len());
+        self.columns = columns;
+        self.rows = rows;
+        self.heights = heights;
+        self.heightmap = None;
+        self.heightmap_dirty = false;
+        self.geometry_dirty = true;
+    }
+
+    /// Returns amount of cells along each side of a chunk.
+    pub fn chunk_size(&self) -> u32 {
+        self.chunk_size
+    }
+
+    /// Sets amount of cells along each side of a chunk, it should be power of two to allow
+    /// every level of details to be used.
+    pub fn set_chunk_size(&mut self, chunk_size: u32) {
+        self.chunk_size = chunk_size.max(1);
+        self.geometry_dirty = true;
+    }
+
+    /// Returns maximum amount of levels of details.
+    pub fn lod_count(&self) -> u32 {
+        self.lod_count
+    }
+
+    /// Sets maximum amount of levels of details, levels that skip more samples than a chunk
+    /// has are not created.
+    pub fn set_lod_count(&mut self, lod_count: u32) {
+        self.lod_count = lod_count.max(1);
+        self.geometry_dirty = true;
+    }
+
+    /// Returns distance at which second level of details is used.
+    pub fn lod_distance(&self) -> f32 {
+        self.lod_distance
+    }
+
+    /// Sets distance at which second level of details is used, every next level is used at
+    /// twice bigger distance.
+    pub fn set_lod_distance(&mut self, lod_distance: f32) {
+        self.lod_distance = lod_distance;
+    }
+
+    /// Returns layers of terrain.
+    pub fn layers(&self) -> &[TerrainLayer] {
+        &self.layers
+    }
+
+    /// Returns layers of terrain.
+    pub fn layers_mut(&mut self) -> &mut [TerrainLayer] {
+        &mut self.layers
+    }
+
+    /// Sets new layers, only first [MAX_TERRAIN_LAYERS](constant.MAX_TERRAIN_LAYERS.html)
+    /// layers are used.
+    pub fn set_layers(&mut self, mut layers: Vec<TerrainLayer>) {
+        layers.truncate(MAX_TERRAIN_LAYERS);
+        self.layers = layers;
+    }
+
+    /// Returns control map which defines weights of layers.
+    pub fn control_map(&self) -> Option<Texture> {
+        self.control_map.clone()
+    }
+
+    /// Sets control map which defines weights of layers, see module docs.
+    pub fn set_control_map(&mut self, control_map: Option<Texture>) {
+        self.control_map = control_map;
+    }
+
+    /// Returns chunks of terrain geometry.
+    pub fn chunks(&self) -> &[TerrainChunk] {
+        &self.chunks
+    }
+
+    /// Returns bounds of terrain in local coordinates.
+    pub fn bounding_box(&self) -> AxisAlignedBoundingBox {
+        let mut bounding_box = AxisAlignedBoundingBox::default();
+        for chunk in self.chunks.iter() {
+            bounding_box.add_box(chunk.bounds);
+        }
+        bounding_box
+    }
+
+    /// Returns index of level of details that should be used for a chunk at given distance
+    /// from camera.
+    pub fn lod_for_distance(&self, distance: f32) -> usize {
+        if self.lod_distance <= 0.0 || distance < self.lod_distance {
+            0
+        } else {
+            let level = (distance / self.lod_distance).log2().floor() as u32 + 1;
+            level.min(self.lod_count.saturating_sub(1)) as usize
+        }
+    }
+
+    fn cell_size(&self) -> Vector2<f32> {
+        Vector2::new(
+            self.width / (self.columns.max(2) - 1) as f32,
+            self.length / (self.rows.max(2) - 1) as f32,
+        )
+    }
+
+    fn sample(&self, column: i64, row: i64) -> f32 {
+        let column = column.max(0).min(self.columns as i64 - 1) as u32;
+        let row = row.max(0).min(self.rows as i64 - 1) as u32;
+        self.heights[(row * self.columns + column) as usize] * self.height_scale
+    }
+
+    fn vertex_position(&self, column: u32, row: u32) -> Vector3<f32> {
+        let cell = self.cell_size();
+        Vector3::new(
+            column as f32 * cell.x,
+            self.sample(column as i64, row as i64),
+            row as f32 * cell.y,
+        )
+    }
+
+    fn make_vertex(&self, column: u32, row: u32) -> Vertex {
+        let cell = self.cell_size();
+        let (c, r) = (column as i64, row as i64);
+        // Central differences, one-sided on borders.
+        let dx = (self.sample(c + 1, r) - self.sample(c - 1, r))
+            / (((c + 1).min(self.columns as i64 - 1) - (c - 1).max(0)) as f32 * cell.x);
+        let dz = (self.sample(c, r + 1) - self.sample(c, r - 1))
+            / (((r + 1).min(self.rows as i64 - 1) - (r - 1).max(0)) as f32 * cell.y);
+
+        let mut vertex = Vertex::from_pos_uv(
+            self.vertex_position(column, row),
+            Vector2::new(
+                column as f32 / (self.columns - 1) as f32,
+                row as f32 / (self.rows - 1) as f32,
+            ),
+        );
+        vertex.normal = Vector3::new(-dx, 1.0, -dz).normalize();
+        let tangent = Vector3::new(1.0, dx, 0.0).normalize();
+        vertex.tangent = Vector4::new(tangent.x, tangent.y, tangent.z, 1.0);
+        vertex
+    }
+
+    fn make_chunk_lod(&self, columns: &[u32], rows: &[u32], skirt_depth: f32) -> SurfaceSharedData {
+        let mut vertices = Vec::with_capacity(columns.len() * rows.len());
+        let mut triangles = Vec::new();
+
+        for &row in rows {
+            for &column in columns {
+                vertices.push(self.make_vertex(column, row));
+            }
+        }
+
+        let stride = columns.len() as u32;
+        for r in 0..rows.len() as u32 - 1 {
+            for c in 0..stride - 1 {
+                let i00 = r * stride + c;
+                let i10 = i00 + 1;
+                let i01 = i00 + stride;
+                let i11 = i01 + 1;
+                triangles.push(TriangleDefinition([i00, i01, i10]));
+                triangles.push(TriangleDefinition([i10, i01, i11]));
+            }
+        }
+
+        // Walk along border of the chunk, so every skirt faces outside of the chunk.
+        let (last_column, last_row) = (stride - 1, rows.len() as u32 - 1);
+        let mut border = Vec::new();
+        border.extend(0..last_column);
+        border.extend((0..last_row).map(|r| r * stride + last_column));
+        border.extend((1..=last_column).rev().map(|c| last_row * stride + c));
+        border.extend((1..=last_row).rev().map(|r| r * stride));
+
+        let first_skirt_vertex = vertices.len() as u32;
+        for &index in border.iter() {
+            let mut vertex = vertices[index as usize];
+            vertex.position.y -= skirt_depth;
+            vertices.push(vertex);
+        }
+        for (i, &top) in border.iter().enumerate() {
+            let next = (i + 1) % border.len();
+            let next_top = border[next];
+            let bottom = first_skirt_vertex + i as u32;
+            let next_bottom = first_skirt_vertex + next as u32;
+            triangles.push(TriangleDefinition([top, next_top, next_bottom]));
+            triangles.push(TriangleDefinition([top, next_bottom, bottom]));
+        }
+
+        SurfaceSharedData::new(vertices, triangles, true)
+    }
+
+    fn rebuild_geometry(&mut self) {
+        self.chunks.clear();
+
+        if self.columns < 2
+            || self.rows < 2
+            || self.heights.len() != (self.columns * self.rows) as usize
+        {
+            return;
+        }
+
+        let chunk_size = self.chunk_size.max(1);
+        for row_begin in (0..self.rows - 1).step_by(chunk_size as usize) {
+            for column_begin in (0..self.columns - 1).step_by(chunk_size as usize) {
+                let row_end = (row_begin + chunk_size).min(self.rows - 1);
+                let column_end = (column_begin + chunk_size).min(self.columns - 1);
+
+                let mut bounds = AxisAlignedBoundingBox::default();
+                for row in row_begin..=row_end {
+                    for column in column_begin..=column_end {
+                        bounds.add_point(self.vertex_position(column, row));
+                    }
+                }
+
+                // Crack between levels of details can't be deeper than height range of the
+                // chunk.
+                let skirt_depth = (bounds.max.y - bounds.min.y).max(self.cell_size().min());
+                bounds.min.y -= skirt_depth;
+
+                let mut lods = Vec::new();
+                for lod in 0..self.lod_count.max(1) {
+                    let step = 1 << lod;
+                    if lod > 0 && step > chunk_size {
+                        break;
+                    }
+                    let columns = lod_samples(column_begin, column_end, step);
+                    let rows = lod_samples(row_begin, row_end, step);
+                    lods.push(Arc::new(RwLock::new(self.make_chunk_lod(
+                        &columns,
+                        &rows,
+                        skirt_depth,
+                    ))));
+                }
+
+                self.chunks.push(TerrainChunk { lods, bounds });
+            }
+        }
+    }
+
+    /// Reads heights from heightmap if it is loaded and rebuilds geometry if needed. Called
+    /// by graph on every update, so there is no need to call it manually.
+    pub fn update(&mut self) {
+        if self.heightmap_dirty {
+            if let Some(heightmap) = self.heightmap.as_ref() {
+                let loaded = matches!(*heightmap.state(), TextureState::Ok(_));
+                if loaded {
+                    match heights_from_texture(heightmap) {
+                        Some((columns, rows, heights)) => {
+                            self.columns = columns;
+                            self.rows = rows;
+                            self.heights = heights;
+                        }
+                        None => Log::writeln(
+                            MessageKind::Error,
+                            "Unable to read heights of terrain, heightmap must be \
+                            uncompressed 2D texture."
+                                .to_owned(),
+                        ),
+                    }
+                    self.heightmap_dirty = false;
+                    self.geometry_dirty = true;
+                }
+            } else {
+                self.heightmap_dirty = false;
+            }
+        }
+
+        if self.geometry_dirty {
+            self.rebuild_geometry();
+            self.geometry_dirty = false;
+        }
+    }
+
+    /// Returns height of terrain in local coordinates at given point of XZ plane in local
+    /// coordinates, heights between samples are interpolated the same way as they rendered.
+    /// Returns `None` if point is outside of terrain or terrain has no heights.
+    pub fn local_height_at(&self, x: f32, z: f32) -> Option<f32> {
+        if self.columns < 2 || self.rows < 2 || self.heights.is_empty() {
+            return None;
+        }
+
+        let cell = self.cell_size();
+        let (fx, fz) = (x / cell.x, z / cell.y);
+        if fx < 0.0 || fz < 0.0 || fx > (self.columns - 1) as f32 || fz > (self.rows - 1) as f32 {
+            return None;
+        }
+
+        let column = (fx.floor() as u32).min(self.columns - 2);
+        let row = (fz.floor() as u32).min(self.rows - 2);
+        let (tx, tz) = (fx - column as f32, fz - row as f32);
+
+        let h00 = self.sample(column as i64, row as i64);
+        let h10 = self.sample(column as i64 + 1, row as i64);
+        let h01 = self.sample(column as i64, row as i64 + 1);
+        let h11 = self.sample(column as i64 + 1, row as i64 + 1);
+
+        // Each cell is split by diagonal from (1, 0) to (0, 1), same as in geometry.
+        Some(if tx + tz <= 1.0 {
+            h00 + (h10 - h00) * tx + (h01 - h00) * tz
+        } else {
+            h11 + (h01 - h11) * (1.0 - tx) + (h10 - h11) * (1.0 - tz)
+        })
+    }
+
+    /// Returns world-space height of terrain surface below (or above) given world-space
+    /// point. Terrain must not be rotated around X or Z axes. Returns `None` if point is
+    /// outside of terrain.
+    pub fn height_at(&self, position: Vector3<f32>) -> Option<f32> {
+        let transform = self.global_transform();
+        let local = transform
+            .try_inverse()?
+            .transform_point(&Point3::from(position));
+        let height = self.local_height_at(local.x, local.z)?;
+        Some(
+            transform
+                .transform_point(&Point3::new(local.x, height, local.z))
+                .y,
+        )
+    }
+
+    /// Casts ray (in world coordinates, see [Ray](../../core/math/ray/struct.Ray.html)) and
+    /// returns closest intersection point with terrain surface in world coordinates.
+    pub fn raycast(&self, ray: &Ray) -> Option<Vector3<f32>> {
+        let transform = self.global_transform();
+        let inv = transform.try_inverse()?;
+        let local_ray = Ray {
+            origin: inv.transform_point(&Point3::from(ray.origin)).coords,
+            dir: inv.transform_vector(&ray.dir),
+        };
+        self.local_raycast(&local_ray)
+            .map(|p| transform.transform_point(&Point3::from(p)).coords)
+    }
+
+    fn local_raycast(&self, ray: &Ray) -> Option<Vector3<f32>> {
+        if self.chunks.is_empty() {
+            return None;
+        }
+
+        let mut bounds = self.bounding_box();
+        bounds.min.x = 0.0;
+        bounds.min.z = 0.0;
+        bounds.max.x = self.width;
+        bounds.max.z = self.length;
+        let range = ray.aabb_intersection(&bounds)?;
+        let t_min = range.min.max(0.0);
+        let t_max = range.max.min(1.0);
+
+        // Walk over cells of the grid which are crossed by ray projection on XZ plane.
+        let cell = self.cell_size();
+        let begin = ray.get_point(t_min);
+        let max_column = self.columns as i64 - 2;
+        let max_row = self.rows as i64 - 2;
+        let mut column = ((begin.x / cell.x).floor() as i64).max(0).min(max_column);
+        let mut row = ((begin.z / cell.y).floor() as i64).max(0).min(max_row);
+
+        let step_column = if ray.dir.x >= 0.0 { 1 } else { -1 };
+        let step_row = if ray.dir.z >= 0.0 { 1 } else { -1 };
+        let boundary = |index: i64, step: i64, size: f32, origin: f32, dir: f32| {
+            if dir == 0.0 {
+                std::f32::MAX
+            } else {
+                let edge = (index + if step > 0 { 1 } else { 0 }) as f32 * size;
+                (edge - origin) / dir
+            }
+        };
+        let delta = |size: f32, dir: f32| {
+            if dir == 0.0 {
+                std::f32::MAX
+            } else {
+                (size / dir).abs()
+            }
+        };
+        let mut t_column = boundary(column, step_column, cell.x, ray.origin.x, ray.dir.x);
+        let mut t_row = boundary(row, step_row, cell.y, ray.origin.z, ray.dir.z);
+        let delta_column = delta(cell.x, ray.dir.x);
+        let delta_row = delta(cell.y, ray.dir.z);
+
+        loop {
+            let (c, r) = (column as u32, row as u32);
+            let p00 = self.vertex_position(c, r);
+            let p10 = self.vertex_position(c + 1, r);
+            let p01 = self.vertex_position(c, r + 1);
+            let p11 = self.vertex_position(c + 1, r + 1);
+
+            let closest = [[p00, p01, p10], [p10, p01, p11]]
+                .iter()
+                .filter_map(|triangle| ray.triangle_intersection(triangle))
+                .min_by(|a, b| {
+                    let da = (a - ray.origin).norm_squared();
+                    let db = (b - ray.origin).norm_squared();
+                    da.partial_cmp(&db).unwrap()
+                });
+            if closest.is_some() {
+                return closest;
+            }
+
+            if t_column < t_row {
+                if t_column > t_max {
+                    return None;
+                }
+                column += step_column;
+                t_column += delta_column;
+            } else {
+                if t_row > t_max {
+                    return None;
+                }
+                row += step_row;
+                t_row += delta_row;
+            }
+
+            if column < 0 || column > max_column || row < 0 || row > max_row {
+                return None;
+            }
+        }
+    }
+}
+
+/// Terrain builder allows you to construct terrain in declarative manner.
+pub struct TerrainBuilder {
+    base_builder: BaseBuilder,
+    width: f32,
+    length: f32,
+    height_scale: f32,
+    heightmap: Option<Texture>,
+    heights: Option<(u32, u32, Vec<f32>)>,
+    chunk_size: u32,
+    lod_count: u32,
+    lod_distance: f32,
+    layers: Vec<TerrainLayer>,
+    control_map: Option<Texture>,
+}
+
+impl TerrainBuilder {
+    /// Creates new builder instance.
+    pub fn new(base_builder: BaseBuilder) -> Self {
+        Self {
+            base_builder,
+            width: 64.0,
+            length: 64.0,
+            height_scale: 10.0,
+            heightmap: None,
+            heights: None,
+            chunk_size: 32,
+            lod_count: 4,
+            lod_distance: 50.0,
+            layers: Default::default(),
+            control_map: None,
+        }
+    }
+
+    /// Sets desired size of terrain in XZ plane.
+    pub fn with_size(mut self, width: f32, length: f32) -> Self {
+        self.width = width;
+        self.length = length;
+        self
+    }
+
+    /// Sets desired height of terrain at points with height sample equal to 1.0.
+    pub fn with_height_scale(mut self, height_scale: f32) -> Self {
+        self.height_scale = height_scale;
+        self
+    }
+
+    /// Sets heightmap texture, heights will be read when texture is loaded.
+    pub fn with_heightmap(mut self, heightmap: Texture) -> Self {
+        self.heightmap = Some(heightmap);
+        self
+    }
+
+    /// Sets height samples directly, see [Terrain::set_heights](struct.Terrain.html#method.set_heights).
+    pub fn with_heights(mut self, columns: u32, rows: u32, heights: Vec<f32>) -> Self {
+        self.heights = Some((columns, rows, heights));
+        self
+    }
+
+    /// Sets desired amount of cells along each side of a chunk.
+    pub fn with_chunk_size(mut self, chunk_size: u32) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Sets desired maximum amount of levels of details.
+    pub fn with_lod_count(mut self, lod_count: u32) -> Self {
+        self.lod_count = lod_count;
+        self
+    }
+
+    /// Sets desired distance at which second level of details is used.
+    pub fn with_lod_distance(mut self, lod_distance: f32) -> Self {
+        self.lod_distance = lod_distance;
+        self
+    }
+
+    /// Sets desired layers.
+    pub fn with_layers(mut self, layers: Vec<TerrainLayer>) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Sets desired control map.
+    pub fn with_control_map(mut self, control_map: Texture) -> Self {
+        self.control_map = Some(control_map);
+        self
+    }
+
+    fn build_terrain(self) -> Terrain {
+        let mut terrain = Terrain {
+            base: self.base_builder.build_base(),
+            width: self.width,
+            length: self.length,
+            height_scale: self.height_scale,
+            heightmap: None,
+            heights: Default::default(),
+            columns: 0,
+            rows: 0,
+            chunk_size: self.chunk_size.max(1),
+            lod_count: self.lod_count.max(1),
+            lod_distance: self.lod_distance,
+            layers: Default::default(),
+            control_map: self.control_map,
+            chunks: Default::default(),
+            heightmap_dirty: false,
+            geometry_dirty: false,
+        };
+        terrain.set_layers(self.layers);
+        if let Some((columns, rows, heights)) = self.heights {
+            terrain.set_heights(columns, rows, heights);
+        }
+        if self.heightmap.is_some() {
+            terrain.set_heightmap(self.heightmap);
+        }
+        // Build geometry right away if heights are available.
+        terrain.update();
+        terrain
+    }
+
+    /// Creates new terrain node.
+    pub fn build_node(self) -> Node {
+        Node::Terrain(self.build_terrain())
+    }
+
+    /// Creates new terrain node and adds it to the graph.
+    pub fn build(self, graph: &mut Graph) -> Handle<Node> {
+        graph.add_node(self.build_node())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            algebra::{Point3, Vector3},
+            math::ray::Ray,
+        },
+        scene::{
+            base::BaseBuilder,
+            graph::Graph,
+            terrain::{Terrain, TerrainBuilder},
+            transform::TransformBuilder,
+        },
+    };
+
+    fn make_terrain(graph: &mut Graph, transform: TransformBuilder) -> &Terrain {
+        // 9x9 samples with a ridge along Z axis in the middle.
+        let heights = (0..81)
+            .map(|i| {
+                let column = (i % 9) as f32;
+                1.0 - (column - 4.0).abs() / 4.0
+            })
+            .collect();
+        let handle =
+            TerrainBuilder::new(BaseBuilder::new().with_local_transform(transform.build()))
+                .with_size(8.0, 8.0)
+                .with_height_scale(4.0)
+                .with_heights(9, 9, heights)
+                .with_chunk_size(4)
+                .with_lod_count(4)
+                .build(graph);
+        graph.update_hierarchical_data();
+        graph[handle].as_terrain()
+    }
+
+    #[test]
+    fn test_terrain_geometry() {
+        let mut graph = Graph::new();
+        let terrain = make_terrain(&mut graph, TransformBuilder::new());
+
+        assert_eq!(terrain.chunks().len(), 4);
+        for chunk in terrain.chunks() {
+            // Third level skips more samples than chunk has.
+            assert_eq!(chunk.lods().len(), 3);
+
+            let data = chunk.lods()[0].read().unwrap();
+            let center = chunk.bounding_box().center();
+            for triangle in data.triangles() {
+                let a = data.vertices[triangle[0] as usize].position;
+                let b = data.vertices[triangle[1] as usize].position;
+                let c = data.vertices[triangle[2] as usize].position;
+                let normal = (b - a).cross(&(c - a));
+                // Surface faces up, skirts face outside of chunk.
+                let centroid = (a + b + c).scale(1.0 / 3.0);
+                let outside = Vector3::new(centroid.x - center.x, 0.0, centroid.z - center.z);
+                assert!(normal.y > 0.0 || normal.dot(&outside) > 0.0);
+            }
+        }
+
+        assert_eq!(terrain.lod_for_distance(10.0), 0);
+        assert_eq!(terrain.lod_for_distance(60.0), 1);
+        assert_eq!(terrain.lod_for_distance(150.0), 2);
+        assert_eq!(terrain.lod_for_distance(10000.0), 3);
+    }
+
+    #[test]
+    fn test_terrain_queries() {
+        let mut graph = Graph::new();
+        let terrain = make_terrain(
+            &mut graph,
+            TransformBuilder::new().with_local_position(Vector3::new(10.0, 1.0, 0.0)),
+        );
+
+        assert_eq!(terrain.height_at(Vector3::new(14.0, 0.0, 3.0)), Some(5.0));
+        assert_eq!(terrain.height_at(Vector3::new(12.0, 100.0, 3.5)), Some(3.0));
+        assert_eq!(terrain.height_at(Vector3::new(9.0, 0.0, 3.0)), None);
+
+        // Vertical ray hits the ridge.
+        let ray = Ray::from_two_points(
+            &Vector3::new(13.0, 10.0, 2.5),
+            &Vector3::new(13.0, -10.0, 2.5),
+        )
+        .unwrap();
+        let hit = terrain.raycast(&ray).unwrap();
+        assert!((hit - Vector3::new(13.0, 4.0, 2.5)).norm() < 0.001);
+
+        // Horizontal ray along X axis hits slope of the ridge.
+        let ray = Ray::from_two_points(&Vector3::new(0.0, 3.0, 5.5), &Vector3::new(30.0, 3.0, 5.5))
+            .unwrap();
+        let hit = terrain.raycast(&ray).unwrap();
+        assert!((hit - Vector3::new(12.0, 3.0, 5.5)).norm() < 0.001);
+
+        // Ray above terrain.
+        let ray = Ray::from_two_points(&Vector3::new(0.0, 6.0, 5.5), &Vector3::new(30.0, 6.0, 5.5))
+            .unwrap();
+        assert!(terrain.raycast(&ray).is_none());
+
+        let local = terrain.local_height_at(4.0, 0.0).unwrap();
+        assert_eq!(
+            terrain
+                .global_transform()
+                .transform_point(&Point3::new(4.0, local, 0.0))
+                .y,
+            5.0
+        );
+    }
+}
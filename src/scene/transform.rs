@@ -10,7 +10,7 @@
 //!
 //! http://download.autodesk.com/us/fbx/20112/FBX_SDK_HELP/index.html?url=WS1a9193826455f5ff1f92379812724681e696651.htm,topicNumber=d0e7429
 //!
-//! Transform = T * Roff * Rp * Rpre * R * Rpost * Rp⁻¹ * Soff * Sp * S * Sp⁻¹
+//! Transform = T * Roff * Rp * Rpre * R * Rpost⁻¹ * Rp⁻¹ * Soff * Sp * S * Sp⁻¹
 //!
 //! where
 //! T     - Translation
@@ -18,7 +18,7 @@
 //! Rp    - Rotation pivot
 //! Rpre  - Pre-rotation
 //! R     - Rotation
-//! Rpost - Post-rotation
+//! Rpost⁻¹ - Inverse of the post-rotation
 //! Rp⁻¹  - Inverse of the rotation pivot
 //! Soff  - Scaling offset
 //! Sp    - Scaling pivot
@@ -40,6 +40,20 @@
 //! this approach very bug prone, and it is still heavy from computation side. It is much
 //! easier to use it as is.
 //!
+//! # Rotation composition order
+//!
+//! Final orientation of a transform is `Rpre * R * Rpost⁻¹` - note that post-rotation
+//! is *inverted*, exactly as in FBX. Matrices are applied from right to left, so a vector
+//! is first rotated by inverse of post-rotation, then by local rotation and only then by
+//! pre-rotation. In other words local rotation is performed in the coordinate system
+//! defined by pre-rotation, not in the coordinate system of a parent node.
+//!
+//! This is important when you aim something (turrets, heads of characters, etc.) which was
+//! imported from FBX: such nodes often have non-identity pre- or post-rotation, so setting
+//! local rotation to some "world-aligned" quaternion will give unexpected orientation.
+//! Use [`Transform::look_at`] which compensates pre- and post-rotation, or compensate them
+//! manually: `R = Rpre⁻¹ * Desired * Rpost`.
+//!
 //! # Decomposition
 //!
 //! Once transform baked into matrix, it is *almost* impossible to decompose it back into
@@ -62,6 +76,9 @@ pub struct Transform {
     /// Indicates that some property has changed and matrix must be
     /// recalculated before use. This is some sort of lazy evaluation.
     dirty: Cell<bool>,
+    /// Indicates that some property has changed since last update of global transforms
+    /// of scene graph. Unlike `dirty` flag, it is reset by the graph, not by the transform.
+    changed: Cell<bool>,
     local_scale: Vector3<f32>,
     local_position: Vector3<f32>,
     local_rotation: UnitQuaternion<f32>,
@@ -92,6 +109,7 @@ impl Visit for Transform {
 
         if visitor.is_reading() {
             self.post_rotation_matrix = build_post_rotation_matrix(self.post_rotation);
+            self.invalidate();
         }
 
         visitor.leave_region()
@@ -124,6 +142,7 @@ impl Transform {
     pub fn identity() -> Self {
         Self {
             dirty: Cell::new(true),
+            changed: Cell::new(true),
             local_position: Vector3::default(),
             local_scale: Vector3::new(1.0, 1.0, 1.0),
             local_rotation: UnitQuaternion::identity(),
@@ -149,7 +168,7 @@ impl Transform {
     pub fn set_position(&mut self, local_position: Vector3<f32>) -> &mut Self {
         if self.dirty.get() || self.local_position != local_position {
             self.local_position = local_position;
-            self.dirty.set(true);
+            self.invalidate();
         }
         self
     }
@@ -165,7 +184,7 @@ impl Transform {
     pub fn set_rotation(&mut self, local_rotation: UnitQuaternion<f32>) -> &mut Self {
         if self.dirty.get() || self.local_rotation != local_rotation {
             self.local_rotation = local_rotation;
-            self.dirty.set(true);
+            self.invalidate();
         }
         self
     }
@@ -184,7 +203,7 @@ impl Transform {
     pub fn set_scale(&mut self, local_scale: Vector3<f32>) -> &mut Self {
         if self.dirty.get() || self.local_scale != local_scale {
             self.local_scale = local_scale;
-            self.dirty.set(true);
+            self.invalidate();
         }
         self
     }
@@ -196,7 +215,7 @@ impl Transform {
     pub fn set_pre_rotation(&mut self, pre_rotation: UnitQuaternion<f32>) -> &mut Self {
         if self.dirty.get() || self.pre_rotation != pre_rotation {
             self.pre_rotation = pre_rotation;
-            self.dirty.set(true);
+            self.invalidate();
         }
         self
     }
@@ -215,7 +234,7 @@ impl Transform {
         if self.dirty.get() || self.post_rotation != post_rotation {
             self.post_rotation = post_rotation;
             self.post_rotation_matrix = build_post_rotation_matrix(self.post_rotation);
-            self.dirty.set(true);
+            self.invalidate();
         }
         self
     }
@@ -232,7 +251,7 @@ impl Transform {
     pub fn set_rotation_offset(&mut self, rotation_offset: Vector3<f32>) -> &mut Self {
         if self.dirty.get() || self.rotation_offset != rotation_offset {
             self.rotation_offset = rotation_offset;
-            self.dirty.set(true);
+            self.invalidate();
         }
         self
     }
@@ -250,7 +269,7 @@ impl Transform {
     pub fn set_rotation_pivot(&mut self, rotation_pivot: Vector3<f32>) -> &mut Self {
         if self.dirty.get() || self.rotation_pivot != rotation_pivot {
             self.rotation_pivot = rotation_pivot;
-            self.dirty.set(true);
+            self.invalidate();
         }
         self
     }
@@ -267,7 +286,7 @@ impl Transform {
     pub fn set_scaling_offset(&mut self, scaling_offset: Vector3<f32>) -> &mut Self {
         if self.dirty.get() || self.scaling_offset != scaling_offset {
             self.scaling_offset = scaling_offset;
-            self.dirty.set(true);
+            self.invalidate();
         }
         self
     }
@@ -284,7 +303,7 @@ impl Transform {
     pub fn set_scaling_pivot(&mut self, scaling_pivot: Vector3<f32>) -> &mut Self {
         if self.dirty.get() || self.scaling_pivot != scaling_pivot {
             self.scaling_pivot = scaling_pivot;
-            self.dirty.set(true);
+            self.invalidate();
         }
         self
     }
@@ -300,10 +319,38 @@ impl Transform {
     #[inline]
     pub fn offset(&mut self, vec: Vector3<f32>) -> &mut Self {
         self.local_position += vec;
-        self.dirty.set(true);
+        self.invalidate();
         self
     }
 
+    /// Rotates transform so its "look" (+Z) axis will point at given target, "up" vector
+    /// defines rotation around look axis. Target and up vector must be in coordinate system
+    /// of parent (in world coordinates if there is no parent). Local position is used as
+    /// origin, rotation pivot and offset are ignored.
+    ///
+    /// Pre- and post-rotation are compensated, so combined rotation `Rpre * R * Rpost⁻¹`
+    /// (see module docs) will face the target, not just local rotation. If target is at
+    /// the position of transform, rotation remains unchanged.
+    pub fn look_at(&mut self, target: Vector3<f32>, up: Vector3<f32>) -> &mut Self {
+        let look = target - self.local_position;
+        if look.norm_squared() <= f32::EPSILON {
+            return self;
+        }
+        // Pick another up vector if given one is collinear with look vector, otherwise
+        // basis will be degenerated.
+        let up = if look.cross(&up).norm_squared() <= f32::EPSILON {
+            if look.x.abs() < look.y.abs() {
+                Vector3::x()
+            } else {
+                Vector3::y()
+            }
+        } else {
+            up
+        };
+        let combined = UnitQuaternion::face_towards(&look, &up);
+        self.set_rotation(self.pre_rotation.inverse() * combined * self.post_rotation)
+    }
+
     fn calculate_local_transform(&self) -> Matrix4<f32> {
         // Make shortcuts to remove visual clutter.
         let por = &self.post_rotation_matrix;
@@ -425,6 +472,19 @@ impl Transform {
         )
     }
 
+    #[inline]
+    fn invalidate(&mut self) {
+        self.dirty.set(true);
+        self.changed.set(true);
+    }
+
+    /// Returns `true` if transform has changed since last call and resets the flag. It is
+    /// used by graph to update global transforms only for nodes that were moved.
+    #[inline]
+    pub(in crate) fn take_changed(&self) -> bool {
+        self.changed.replace(false)
+    }
+
     /// Returns matrix which is final result of transform. Matrix then can be used to transform
     /// a vector, or combine with other matrix, to make transform hierarchy for example.
     pub fn matrix(&self) -> Matrix4<f32> {
@@ -531,6 +591,7 @@ impl TransformBuilder {
     pub fn build(self) -> Transform {
         Transform {
             dirty: Cell::new(true),
+            changed: Cell::new(true),
             local_scale: self.local_scale,
             local_position: self.local_position,
             local_rotation: self.local_rotation,
@@ -545,3 +606,65 @@ impl TransformBuilder {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            algebra::{UnitQuaternion, Vector3},
+            math::Matrix4Ext,
+        },
+        scene::transform::TransformBuilder,
+    };
+
+    fn assert_collinear(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!(
+            (a.normalize() - b.normalize()).norm() < 0.001,
+            "{} != {}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn test_rotation_composition_order() {
+        let pre = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), 0.3);
+        let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), 1.2);
+        let post = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), -0.7);
+
+        let transform = TransformBuilder::new()
+            .with_pre_rotation(pre)
+            .with_local_rotation(rotation)
+            .with_post_rotation(post)
+            .build();
+
+        let expected = (pre * rotation * post.inverse()).to_homogeneous();
+        assert!((transform.matrix() - expected).norm() < 0.001);
+    }
+
+    #[test]
+    fn test_look_at() {
+        let mut transform = TransformBuilder::new()
+            .with_local_position(Vector3::new(1.0, 2.0, 3.0))
+            .build();
+        transform.look_at(Vector3::new(5.0, 2.0, 3.0), Vector3::y());
+        assert_collinear(transform.matrix().look(), Vector3::x());
+        assert_collinear(transform.matrix().up(), Vector3::y());
+
+        // Up vector collinear with look vector must not break basis.
+        transform.look_at(Vector3::new(1.0, 10.0, 3.0), Vector3::y());
+        assert_collinear(transform.matrix().look(), Vector3::y());
+    }
+
+    #[test]
+    fn test_look_at_compensates_pre_post_rotation() {
+        let mut transform = TransformBuilder::new()
+            .with_pre_rotation(UnitQuaternion::from_axis_angle(&Vector3::x_axis(), 0.5))
+            .with_post_rotation(UnitQuaternion::from_axis_angle(&Vector3::y_axis(), 1.1))
+            .build();
+        let target = Vector3::new(-3.0, 1.0, 2.0);
+        transform.look_at(target, Vector3::y());
+        assert_collinear(transform.matrix().look(), target);
+        assert!(transform.matrix().up().dot(&Vector3::y()) > 0.0);
+    }
+}
@@ -167,11 +167,41 @@ impl PathFinder {
         to: usize,
         path: &mut Vec<Vector3<f32>>,
     ) -> Result<PathKind, PathError> {
-        if self.vertices.is_empty() {
-            return Ok(PathKind::Empty);
+        path.clear();
+        match self.search(from, to)? {
+            Some((last, kind)) => {
+                self.reconstruct_path(last, |_, vertex| path.push(vertex.position));
+                Ok(kind)
+            }
+            None => Ok(PathKind::Empty),
         }
+    }
 
+    /// Same as [build](Self::build), but fills `path` with indices of vertices instead of
+    /// their positions. Indices are stored in the same order as positions - from end to
+    /// begin.
+    pub fn build_indices(
+        &mut self,
+        from: usize,
+        to: usize,
+        path: &mut Vec<usize>,
+    ) -> Result<PathKind, PathError> {
         path.clear();
+        match self.search(from, to)? {
+            Some((last, kind)) => {
+                self.reconstruct_path(last, |index, _| path.push(index));
+                Ok(kind)
+            }
+            None => Ok(PathKind::Empty),
+        }
+    }
+
+    /// Runs A* search and returns index of the last vertex of the path and kind of the path,
+    /// or None if there is no vertices.
+    fn search(&mut self, from: usize, to: usize) -> Result<Option<(usize, PathKind)>, PathError> {
+        if self.vertices.is_empty() {
+            return Ok(None);
+        }
 
         for vertex in self.vertices.iter_mut() {
             vertex.clear();
@@ -204,8 +234,7 @@ impl PathFinder {
             }
 
             if current_index == to {
-                self.reconstruct_path(current_index, path);
-                return Ok(PathKind::Full);
+                return Ok(Some((current_index, PathKind::Full)));
             }
 
             open_set_size -= 1;
@@ -262,18 +291,12 @@ impl PathFinder {
             }
         }
 
-        self.reconstruct_path(closest_index, path);
-
-        if path.is_empty() {
-            Ok(PathKind::Empty)
-        } else {
-            Ok(PathKind::Partial)
-        }
+        Ok(Some((closest_index, PathKind::Partial)))
     }
 
-    fn reconstruct_path(&self, mut current: usize, path: &mut Vec<Vector3<f32>>) {
+    fn reconstruct_path<F: FnMut(usize, &PathVertex)>(&self, mut current: usize, mut func: F) {
         while let Some(vertex) = self.vertices.get(current) {
-            path.push(vertex.position);
+            func(current, vertex);
             if let Some(parent) = vertex.parent {
                 current = parent;
             } else {
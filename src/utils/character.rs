@@ -0,0 +1,1119 @@
+//! Kinematic character controller.
+//!
+//! # Overview
+//!
+//! Character controller moves a vertical capsule through physics world the way players expect
+//! it from first or third person games: it slides along walls, walks up and down slopes which
+//! are not steeper than a limit, climbs stairs and small obstacles, sticks to the ground when
+//! walking down slopes and stairs and falls when there is no ground below it. Y axis is treated
+//! as up.
+//!
+//! Capsule is a kinematic rigid body with capsule collider, so other bodies collide with the
+//! character as usual, but the character itself is moved only by the controller. Movement is
+//! done in small sub-steps (a half of capsule radius) and after each sub-step intersections with
+//! surrounding colliders are resolved; every touched surface restricts further movement of the
+//! step, so the character slides along walls and stops in corners instead of jittering between
+//! them.
+//!
+//! Dynamic bodies block the character like any other obstacle, but the controller can push them
+//! (see [CharacterControllerBuilder::with_push_dynamic_bodies]).
+//!
+//! # Usage
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::algebra::Vector3,
+//!     scene::Scene,
+//!     utils::character::{CharacterController, CharacterControllerBuilder},
+//! };
+//!
+//! fn create(scene: &mut Scene) -> CharacterController {
+//!     CharacterControllerBuilder::new()
+//!         .with_radius(0.35)
+//!         .with_height(1.8)
+//!         .with_step_offset(0.3)
+//!         .with_max_slope_angle(45.0f32.to_radians())
+//!         .with_position(Vector3::new(0.0, 1.0, 0.0))
+//!         .build(&mut scene.physics)
+//! }
+//!
+//! fn update(controller: &mut CharacterController, scene: &mut Scene, jump: bool, dt: f32) {
+//!     if jump && controller.is_grounded() {
+//!         controller.jump(5.0);
+//!     }
+//!     controller.move_with_velocity(&mut scene.physics, Vector3::new(0.0, 0.0, 2.0), dt);
+//! }
+//! ```
+//!
+//! Obstacles are found by shape queries of physics world (see [Physics::cast_shape] and
+//! [Physics::intersections_with_shape]), so only colliders near the character are checked and
+//! triangle meshes are checked only by triangles near the character.
+
+use crate::{
+    core::{
+        algebra::Vector3,
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    scene::{
+        physics::{Physics, ShapeIntersection},
+        ColliderHandle, RigidBodyHandle,
+    },
+};
+use rapier3d::{
+    dynamics::RigidBodyBuilder,
+    geometry::{Capsule, Collider, ColliderBuilder, InteractionGroups, Ray},
+    na::{Isometry3, Point3},
+    ncollide::query::RayCast,
+};
+
+/// Maximum amount of iterations to resolve intersections after each movement sub-step.
+const MAX_DEPENETRATION_ITERATIONS: usize = 8;
+
+/// Maximum amount of movement iterations, each iteration moves character to next obstacle or
+/// by a half of capsule radius.
+const MAX_SLIDE_ITERATIONS: usize = 64;
+
+/// Maximum slope angle, must be less than 90 degrees because walkable surfaces are resolved
+/// vertically.
+const MAX_SLOPE_ANGLE_LIMIT: f32 = 89.0 * std::f32::consts::PI / 180.0;
+
+/// Character can stand on an edge of walkable surface (a stair for example) while horizontal
+/// distance from capsule axis to the edge is less than this fraction of radius.
+const PERCH_RADIUS_FRACTION: f32 = 0.9;
+
+#[derive(Copy, Clone, Debug)]
+struct Contact {
+    /// Direction from obstacle to the character.
+    normal: Vector3<f32>,
+    /// Point on obstacle.
+    point: Point3<f32>,
+    /// Positive when capsule penetrates obstacle.
+    depth: f32,
+    /// Normal of the surface at contact point, it differs from contact normal when capsule
+    /// touches an edge.
+    surface_normal: Vector3<f32>,
+    /// Whether character can stand on the surface or not.
+    walkable: bool,
+    body: RigidBodyHandle,
+    dynamic: bool,
+}
+
+#[derive(Default)]
+struct Scratch {
+    contacts: Vec<Contact>,
+    touched: Vec<Contact>,
+}
+
+/// Removes components of motion that go into given planes. When motion is blocked by two
+/// planes, it goes along the crease between them, and stops completely in corners.
+fn clip_motion(motion: &Vector3<f32>, planes: &[Vector3<f32>]) -> Vector3<f32> {
+    const EPS: f32 = -1.0e-6;
+
+    for (i, plane) in planes.iter().enumerate() {
+        if motion.dot(plane) >= EPS {
+            continue;
+        }
+        let clipped = motion - plane.scale(motion.dot(plane));
+        for (j, other) in planes.iter().enumerate() {
+            if i == j || clipped.dot(other) >= EPS {
+                continue;
+            }
+            let crease = match plane.cross(other).try_normalize(std::f32::EPSILON) {
+                Some(crease) => crease,
+                None => return Vector3::default(),
+            };
+            let along_crease = crease.scale(crease.dot(motion));
+            let blocked = planes
+                .iter()
+                .enumerate()
+                .any(|(k, third)| k != i && k != j && along_crease.dot(third) < EPS);
+            return if blocked {
+                Vector3::default()
+            } else {
+                along_crease
+            };
+        }
+        return clipped;
+    }
+    *motion
+}
+
+/// See module docs.
+#[derive(Debug)]
+pub struct CharacterController {
+    body: RigidBodyHandle,
+    collider: ColliderHandle,
+    radius: f32,
+    height: f32,
+    step_offset: f32,
+    max_slope_angle: f32,
+    skin_width: f32,
+    push_dynamic_bodies: bool,
+    mass: f32,
+    position: Vector3<f32>,
+    velocity: Vector3<f32>,
+    vertical_velocity: f32,
+    grounded: bool,
+    ground_normal: Vector3<f32>,
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        CharacterControllerBuilder::new().build_controller(Default::default(), Default::default())
+    }
+}
+
+impl Visit for CharacterController {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.body.visit("Body", visitor)?;
+        self.collider.visit("Collider", visitor)?;
+        self.radius.visit("Radius", visitor)?;
+        self.height.visit("Height", visitor)?;
+        self.step_offset.visit("StepOffset", visitor)?;
+        self.max_slope_angle.visit("MaxSlopeAngle", visitor)?;
+        self.skin_width.visit("SkinWidth", visitor)?;
+        self.push_dynamic_bodies
+            .visit("PushDynamicBodies", visitor)?;
+        self.mass.visit("Mass", visitor)?;
+        self.position.visit("Position", visitor)?;
+        self.velocity.visit("Velocity", visitor)?;
+        self.vertical_velocity.visit("VerticalVelocity", visitor)?;
+        self.grounded.visit("Grounded", visitor)?;
+        self.ground_normal.visit("GroundNormal", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+impl CharacterController {
+    /// Moves character with given velocity for `dt` seconds. Vertical velocity caused by
+    /// gravity (and jumps) is tracked by the controller and added to desired velocity, so
+    /// usually desired velocity is horizontal.
+    pub fn move_with_velocity(
+        &mut self,
+        physics: &mut Physics,
+        desired_velocity: Vector3<f32>,
+        dt: f32,
+    ) {
+        if dt <= 0.0 {
+            return;
+        }
+
+        if self.grounded && self.vertical_velocity <= 0.0 {
+            self.vertical_velocity = 0.0;
+        } else {
+            self.vertical_velocity += physics.gravity.y * dt;
+        }
+
+        let velocity = desired_velocity + Vector3::new(0.0, self.vertical_velocity, 0.0);
+        let motion = velocity.scale(dt);
+        let start = self.position;
+        let walking = self.grounded && self.vertical_velocity <= 0.0;
+
+        let mut scratch = Scratch::default();
+
+        let mut position = self.slide(physics, start, motion, walking, false, &mut scratch);
+
+        // Try to step over an obstacle: move up, forward and then down, and use the result if
+        // it went further than plain movement and ended up on walkable ground.
+        let horizontal_motion = Vector3::new(motion.x, 0.0, motion.z);
+        if walking && self.step_offset > 0.0 && horizontal_motion.norm() > std::f32::EPSILON {
+            let up = self.slide(
+                physics,
+                start,
+                Vector3::new(0.0, self.step_offset, 0.0),
+                false,
+                false,
+                &mut scratch,
+            );
+            let forward = self.slide(physics, up, horizontal_motion, true, false, &mut scratch);
+            let down = self.slide(
+                physics,
+                forward,
+                Vector3::new(0.0, start.y - up.y, 0.0),
+                true,
+                true,
+                &mut scratch,
+            );
+            let horizontal_distance = |p: Vector3<f32>| (p - start).xz().norm();
+            if horizontal_distance(down) > horizontal_distance(position) + 1.0e-3
+                && self.find_ground(physics, down, &mut scratch).is_some()
+            {
+                position = down;
+            }
+        }
+
+        let mut ground = self.find_ground(physics, position, &mut scratch);
+        // Stick to the ground when walking down slopes and stairs. Snapping is applied only if
+        // there is ground within step offset, otherwise character just starts falling with
+        // zero vertical velocity.
+        if ground.is_none() && walking && self.step_offset > 0.0 {
+            let snapped = self.slide(
+                physics,
+                position,
+                Vector3::new(0.0, -self.step_offset, 0.0),
+                true,
+                true,
+                &mut scratch,
+            );
+            if let Some(normal) = self.find_ground(physics, snapped, &mut scratch) {
+                position = snapped;
+                ground = Some(normal);
+            }
+        }
+
+        // Vertical velocity must not exceed actual vertical movement - it happens when
+        // character hits a ceiling or slides down a steep slope.
+        let vertical_movement = (position.y - start.y) / dt;
+        if self.vertical_velocity > 0.0 {
+            self.vertical_velocity = self.vertical_velocity.min(vertical_movement).max(0.0);
+        } else {
+            self.vertical_velocity = self.vertical_velocity.max(vertical_movement).min(0.0);
+        }
+
+        self.grounded = ground.is_some() && self.vertical_velocity <= 0.0;
+        self.ground_normal = if self.grounded {
+            ground.unwrap_or_default()
+        } else {
+            Vector3::default()
+        };
+        if self.grounded {
+            self.vertical_velocity = 0.0;
+        }
+
+        self.velocity = (position - start) / dt;
+        self.position = position;
+
+        if self.push_dynamic_bodies {
+            self.push_bodies(physics, &velocity, &scratch.touched);
+        }
+
+        if let Some(body) = physics.bodies.get_mut(self.body.into()) {
+            body.set_next_kinematic_position(Isometry3::translation(
+                position.x, position.y, position.z,
+            ));
+        }
+    }
+
+    /// Makes character jump with given vertical speed. Has no effect on ground state until
+    /// next [CharacterController::move_with_velocity] call.
+    pub fn jump(&mut self, speed: f32) {
+        self.vertical_velocity = speed;
+    }
+
+    /// Teleports character to given position, it does not check for collisions.
+    pub fn set_position(&mut self, physics: &mut Physics, position: Vector3<f32>) {
+        self.position = position;
+        self.grounded = false;
+        if let Some(body) = physics.bodies.get_mut(self.body.into()) {
+            let isometry = Isometry3::translation(position.x, position.y, position.z);
+            body.set_position(isometry, true);
+            body.set_next_kinematic_position(isometry);
+        }
+    }
+
+    /// Returns position of center of the capsule.
+    pub fn position(&self) -> Vector3<f32> {
+        self.position
+    }
+
+    /// Returns actual velocity of the character during last move, it can be used to drive
+    /// animations.
+    pub fn velocity(&self) -> Vector3<f32> {
+        self.velocity
+    }
+
+    /// Returns vertical velocity which is caused by gravity and jumps.
+    pub fn vertical_velocity(&self) -> f32 {
+        self.vertical_velocity
+    }
+
+    /// Returns true if character stands on walkable ground.
+    pub fn is_grounded(&self) -> bool {
+        self.grounded
+    }
+
+    /// Returns normal of ground on which character stands.
+    pub fn ground_normal(&self) -> Option<Vector3<f32>> {
+        if self.grounded {
+            Some(self.ground_normal)
+        } else {
+            None
+        }
+    }
+
+    /// Returns handle of kinematic rigid body of the character.
+    pub fn body(&self) -> RigidBodyHandle {
+        self.body
+    }
+
+    /// Returns handle of capsule collider of the character.
+    pub fn collider(&self) -> ColliderHandle {
+        self.collider
+    }
+
+    /// Returns radius of the capsule.
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// Returns full height of the capsule.
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    /// Sets maximum height of obstacles which character can step over.
+    pub fn set_step_offset(&mut self, step_offset: f32) {
+        self.step_offset = step_offset.max(0.0);
+    }
+
+    /// Returns maximum height of obstacles which character can step over.
+    pub fn step_offset(&self) -> f32 {
+        self.step_offset
+    }
+
+    /// Sets maximum angle (in radians) of a slope which character can walk on.
+    pub fn set_max_slope_angle(&mut self, angle: f32) {
+        self.max_slope_angle = angle.clamp(0.0, MAX_SLOPE_ANGLE_LIMIT);
+    }
+
+    /// Returns maximum angle (in radians) of a slope which character can walk on.
+    pub fn max_slope_angle(&self) -> f32 {
+        self.max_slope_angle
+    }
+
+    /// Sets whether character should push dynamic bodies or not.
+    pub fn set_push_dynamic_bodies(&mut self, push: bool) {
+        self.push_dynamic_bodies = push;
+    }
+
+    /// Returns true if character pushes dynamic bodies.
+    pub fn is_pushing_dynamic_bodies(&self) -> bool {
+        self.push_dynamic_bodies
+    }
+
+    fn half_segment(&self) -> f32 {
+        (self.height * 0.5 - self.radius).max(0.0)
+    }
+
+    fn is_walkable(&self, normal: &Vector3<f32>) -> bool {
+        normal.y >= self.max_slope_angle.cos() - 1.0e-4
+    }
+
+    /// Returns normal of a plane which restricts movement. Steep slopes act as vertical walls
+    /// for walking character, so it cannot climb them, but it still slides down along them.
+    fn clip_normal(&self, contact: &Contact, walking: bool, moving_down: bool) -> Vector3<f32> {
+        let normal = &contact.normal;
+        if walking && !moving_down && normal.y > 0.0 && !contact.walkable {
+            Vector3::new(normal.x, 0.0, normal.z)
+                .try_normalize(std::f32::EPSILON)
+                .unwrap_or(*normal)
+        } else {
+            *normal
+        }
+    }
+
+    fn shape(&self) -> Capsule {
+        Capsule::new_y(self.half_segment(), self.radius)
+    }
+
+    fn collision_groups(&self, physics: &Physics) -> InteractionGroups {
+        physics
+            .colliders
+            .get(self.collider.into())
+            .map_or(InteractionGroups::all(), |c| c.collision_groups())
+    }
+
+    fn is_obstacle(&self, handle: ColliderHandle, collider: &Collider) -> bool {
+        handle != self.collider && !collider.is_sensor()
+    }
+
+    /// Makes contact from an intersection of the capsule with a collider and checks whether
+    /// character can stand on it.
+    fn make_contact(
+        &self,
+        physics: &Physics,
+        collider: &Collider,
+        intersection: &ShapeIntersection,
+    ) -> Contact {
+        // Contact normal points from the capsule to the obstacle.
+        let normal = -intersection.contact.normal.into_inner();
+        let point = intersection.contact.world2;
+
+        let (surface_normal, walkable) = if self.is_walkable(&normal) {
+            (normal, true)
+        } else if normal.y >= (1.0 - PERCH_RADIUS_FRACTION * PERCH_RADIUS_FRACTION).sqrt() {
+            // Capsule touches an edge, so find actual normal of the surface by casting a short
+            // ray down near the edge.
+            let offset = self.skin_width.max(1.0e-3);
+            let horizontal = Vector3::new(normal.x, 0.0, normal.z)
+                .try_normalize(std::f32::EPSILON)
+                .unwrap_or_default();
+            let ray = Ray::new(
+                point + Vector3::new(0.0, 4.0 * offset, 0.0) - horizontal.scale(offset),
+                Vector3::new(0.0, -1.0, 0.0),
+            );
+            let max_toi = 8.0 * offset;
+            let hit = match intersection.triangle.as_ref() {
+                Some(triangle) => {
+                    triangle.toi_and_normal_with_ray(&Isometry3::identity(), &ray, max_toi, true)
+                }
+                None => collider.shape().toi_and_normal_with_ray(
+                    collider.position(),
+                    &ray,
+                    max_toi,
+                    true,
+                ),
+            };
+            match hit {
+                Some(hit) if hit.toi > 0.0 => (hit.normal, self.is_walkable(&hit.normal)),
+                _ => (normal, false),
+            }
+        } else {
+            (normal, false)
+        };
+
+        Contact {
+            normal,
+            point,
+            depth: intersection.contact.depth,
+            surface_normal,
+            walkable,
+            body: collider.parent().into(),
+            dynamic: physics
+                .bodies
+                .get(collider.parent())
+                .map_or(false, |body| body.is_dynamic()),
+        }
+    }
+
+    fn contacts_at(
+        &self,
+        physics: &Physics,
+        position: Vector3<f32>,
+        margin: f32,
+        contacts: &mut Vec<Contact>,
+    ) {
+        contacts.clear();
+
+        physics.intersections_with_shape(
+            &Isometry3::translation(position.x, position.y, position.z),
+            &self.shape(),
+            margin,
+            self.collision_groups(physics),
+            |collider, intersection| {
+                if self.is_obstacle(intersection.collider, collider) {
+                    contacts.push(self.make_contact(physics, collider, &intersection));
+                }
+                true
+            },
+        );
+    }
+
+    /// Pushes character out of obstacles, every touched surface is added to `planes`.
+    fn depenetrate(
+        &self,
+        physics: &Physics,
+        position: &mut Vector3<f32>,
+        walking: bool,
+        planes: &mut Vec<Vector3<f32>>,
+        scratch: &mut Scratch,
+    ) {
+        for _ in 0..MAX_DEPENETRATION_ITERATIONS {
+            self.contacts_at(
+                physics,
+                *position,
+                self.skin_width * 2.0,
+                &mut scratch.contacts,
+            );
+
+            let mut deepest: Option<Contact> = None;
+            for contact in scratch.contacts.iter() {
+                let plane = self.clip_normal(contact, walking, false);
+                if !planes.iter().any(|p| p.dot(&plane) > 0.999) {
+                    planes.push(plane);
+                }
+                if contact.dynamic {
+                    scratch.touched.push(*contact);
+                }
+                if deepest.map_or(true, |d| contact.depth > d.depth) {
+                    deepest = Some(*contact);
+                }
+            }
+
+            let contact = match deepest {
+                Some(contact) => contact,
+                None => break,
+            };
+            // Character is kept at skin width distance from obstacles.
+            let push = contact.depth + self.skin_width;
+            if push <= self.skin_width * 0.01 {
+                break;
+            }
+            let normal = contact.normal;
+            if contact.walkable {
+                // Resolve vertically so character won't slide down slopes.
+                position.y += push / normal.y;
+            } else if walking && normal.y > 0.0 {
+                // Resolve horizontally so character won't climb steep slopes.
+                let horizontal = Vector3::new(normal.x, 0.0, normal.z);
+                let length = horizontal.norm();
+                *position += horizontal.scale(push / (length * length));
+            } else {
+                *position += normal.scale(push);
+            }
+        }
+    }
+
+    /// Moves character along given motion and slides it along obstacles. Every iteration moves
+    /// character to the first obstacle on its way (obstacles are assumed to be flat, so distance
+    /// is refined on next iteration) and removes part of motion which goes into the obstacle.
+    /// If `stop_on_ground` is set, movement stops at first walkable surface.
+    fn slide(
+        &self,
+        physics: &Physics,
+        start: Vector3<f32>,
+        motion: Vector3<f32>,
+        walking: bool,
+        stop_on_ground: bool,
+        scratch: &mut Scratch,
+    ) -> Vector3<f32> {
+        let mut planes = Vec::new();
+        let mut position = start;
+
+        self.depenetrate(physics, &mut position, walking, &mut planes, scratch);
+
+        let mut remaining = motion;
+        for _ in 0..MAX_SLIDE_ITERATIONS {
+            let clipped = clip_motion(&remaining, &planes);
+            let length = clipped.norm();
+            if length <= 1.0e-5 {
+                break;
+            }
+            let direction = clipped / length;
+
+            // Sweep the capsule to the first obstacle. Character rests at skin width distance
+            // from obstacles, so hits are registered closer than that, otherwise it would not
+            // be able to move along surfaces it touches.
+            let mut advance = length.min(self.radius * 0.5);
+            let hit = physics.cast_shape(
+                &Isometry3::translation(position.x, position.y, position.z),
+                &direction,
+                &self.shape(),
+                advance,
+                self.skin_width * 0.5,
+                self.collision_groups(physics),
+                |handle, collider| self.is_obstacle(handle, collider),
+            );
+            if let Some((_, toi)) = hit.as_ref() {
+                advance = advance.min(toi.toi);
+            }
+
+            position += direction.scale(advance);
+            remaining = clipped - direction.scale(advance);
+
+            // Obstacle which stopped the capsule restricts further motion.
+            let mut blocking: Option<Contact> = None;
+            if hit.is_some() {
+                self.contacts_at(
+                    physics,
+                    position,
+                    self.skin_width * 2.0,
+                    &mut scratch.contacts,
+                );
+                for contact in scratch.contacts.iter() {
+                    if -direction.dot(&contact.normal) > 1.0e-4
+                        && blocking.map_or(true, |b| contact.depth > b.depth)
+                    {
+                        blocking = Some(*contact);
+                    }
+                }
+            }
+
+            if let Some(contact) = blocking {
+                let plane = self.clip_normal(&contact, walking, direction.y < 0.0);
+                if !planes.iter().any(|p| p.dot(&plane) > 0.999) {
+                    planes.push(plane);
+                }
+            }
+
+            self.depenetrate(physics, &mut position, walking, &mut planes, scratch);
+
+            if stop_on_ground
+                && blocking.is_some()
+                && self.find_ground(physics, position, scratch).is_some()
+            {
+                break;
+            }
+        }
+
+        position
+    }
+
+    fn find_ground(
+        &self,
+        physics: &Physics,
+        position: Vector3<f32>,
+        scratch: &mut Scratch,
+    ) -> Option<Vector3<f32>> {
+        self.contacts_at(
+            physics,
+            position,
+            self.skin_width * 2.0,
+            &mut scratch.contacts,
+        );
+        scratch
+            .contacts
+            .iter()
+            .filter(|contact| contact.walkable)
+            .fold(None, |ground: Option<Vector3<f32>>, contact| match ground {
+                Some(normal) if normal.y >= contact.surface_normal.y => Some(normal),
+                _ => Some(contact.surface_normal),
+            })
+    }
+
+    fn push_bodies(&self, physics: &mut Physics, velocity: &Vector3<f32>, touched: &[Contact]) {
+        for (i, contact) in touched.iter().enumerate() {
+            if touched[..i].iter().any(|c| c.body == contact.body) {
+                continue;
+            }
+            if let Some(body) = physics.bodies.get_mut(contact.body.into()) {
+                let direction = -contact.normal;
+                let approach_speed = velocity.dot(&direction);
+                if approach_speed <= 0.0 {
+                    continue;
+                }
+                // Body gets speed as if character hits it with inelastic collision.
+                let body_mass = body.mass();
+                let target_speed = approach_speed * self.mass / (self.mass + body_mass);
+                let current_speed = body.linvel().dot(&direction);
+                if target_speed > current_speed {
+                    body.apply_impulse_at_point(
+                        direction.scale((target_speed - current_speed) * body_mass),
+                        contact.point,
+                        true,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Allows you to create character controller in declarative manner.
+pub struct CharacterControllerBuilder {
+    radius: f32,
+    height: f32,
+    step_offset: f32,
+    max_slope_angle: f32,
+    skin_width: f32,
+    push_dynamic_bodies: bool,
+    mass: f32,
+    position: Vector3<f32>,
+    collision_groups: InteractionGroups,
+}
+
+impl Default for CharacterControllerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CharacterControllerBuilder {
+    /// Creates new builder with default parameters: radius 0.35, height 1.8, step offset 0.3,
+    /// max slope angle 45 degrees, skin width 0.01, mass 80 kg, pushing of dynamic bodies is
+    /// enabled.
+    pub fn new() -> Self {
+        Self {
+            radius: 0.35,
+            height: 1.8,
+            step_offset: 0.3,
+            max_slope_angle: 45.0f32.to_radians(),
+            skin_width: 0.01,
+            push_dynamic_bodies: true,
+            mass: 80.0,
+            position: Default::default(),
+            collision_groups: InteractionGroups::all(),
+        }
+    }
+
+    /// Sets radius of the capsule.
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    /// Sets full height of the capsule, it is clamped to be at least two radii.
+    pub fn with_height(mut self, height: f32) -> Self {
+        self.height = height;
+        self
+    }
+
+    /// Sets maximum height of obstacles (stairs for example) which character can step over.
+    pub fn with_step_offset(mut self, step_offset: f32) -> Self {
+        self.step_offset = step_offset;
+        self
+    }
+
+    /// Sets maximum angle (in radians) of a slope which character can walk on, steeper slopes
+    /// are treated as walls.
+    pub fn with_max_slope_angle(mut self, angle: f32) -> Self {
+        self.max_slope_angle = angle;
+        self
+    }
+
+    /// Sets distance which character keeps from obstacles, it prevents capsule from getting
+    /// stuck because of precision issues.
+    pub fn with_skin_width(mut self, skin_width: f32) -> Self {
+        self.skin_width = skin_width;
+        self
+    }
+
+    /// Sets whether character should push dynamic bodies it walks into.
+    pub fn with_push_dynamic_bodies(mut self, push: bool) -> Self {
+        self.push_dynamic_bodies = push;
+        self
+    }
+
+    /// Sets mass of the character, it defines how strong dynamic bodies are pushed.
+    pub fn with_mass(mut self, mass: f32) -> Self {
+        self.mass = mass;
+        self
+    }
+
+    /// Sets initial position of center of the capsule.
+    pub fn with_position(mut self, position: Vector3<f32>) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Sets collision groups of the capsule, controller will ignore colliders which do not
+    /// interact with these groups.
+    pub fn with_collision_groups(mut self, groups: InteractionGroups) -> Self {
+        self.collision_groups = groups;
+        self
+    }
+
+    fn build_controller(
+        self,
+        body: RigidBodyHandle,
+        collider: ColliderHandle,
+    ) -> CharacterController {
+        let radius = self.radius.max(std::f32::EPSILON);
+        CharacterController {
+            body,
+            collider,
+            radius,
+            height: self.height.max(2.0 * radius),
+            step_offset: self.step_offset.max(0.0),
+            max_slope_angle: self.max_slope_angle.clamp(0.0, MAX_SLOPE_ANGLE_LIMIT),
+            skin_width: self.skin_width.max(0.0),
+            push_dynamic_bodies: self.push_dynamic_bodies,
+            mass: self.mass.max(0.0),
+            position: self.position,
+            velocity: Default::default(),
+            vertical_velocity: 0.0,
+            grounded: false,
+            ground_normal: Default::default(),
+        }
+    }
+
+    /// Creates kinematic rigid body with capsule collider in given physics world and returns
+    /// new character controller.
+    pub fn build(self, physics: &mut Physics) -> CharacterController {
+        let position = self.position;
+        let collision_groups = self.collision_groups;
+        let mut controller = self.build_controller(Default::default(), Default::default());
+
+        controller.body = physics.add_body(
+            RigidBodyBuilder::new_kinematic()
+                .translation(position.x, position.y, position.z)
+                .build(),
+        );
+        controller.collider = physics.add_collider(
+            ColliderBuilder::capsule_y(controller.half_segment(), controller.radius)
+                .collision_groups(collision_groups)
+                .build(),
+            controller.body,
+        );
+
+        controller
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::algebra::Vector3,
+        scene::physics::Physics,
+        utils::character::{CharacterController, CharacterControllerBuilder},
+    };
+    use rapier3d::{
+        dynamics::RigidBodyBuilder,
+        geometry::ColliderBuilder,
+        na::{Isometry3, Point3, UnitQuaternion},
+    };
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn add_box(
+        physics: &mut Physics,
+        center: Vector3<f32>,
+        half_extents: Vector3<f32>,
+        rotation: UnitQuaternion<f32>,
+    ) {
+        let body = physics.add_body(
+            RigidBodyBuilder::new_static()
+                .position(Isometry3::from_parts(center.into(), rotation))
+                .build(),
+        );
+        physics.add_collider(
+            ColliderBuilder::cuboid(half_extents.x, half_extents.y, half_extents.z).build(),
+            body,
+        );
+    }
+
+    // Floor with top surface at zero height.
+    fn add_floor(physics: &mut Physics) {
+        add_box(
+            physics,
+            Vector3::new(0.0, -0.5, 0.0),
+            Vector3::new(50.0, 0.5, 50.0),
+            UnitQuaternion::identity(),
+        );
+    }
+
+    fn make_controller(physics: &mut Physics, position: Vector3<f32>) -> CharacterController {
+        CharacterControllerBuilder::new()
+            .with_radius(0.3)
+            .with_height(1.8)
+            .with_step_offset(0.3)
+            .with_position(position)
+            .build(physics)
+    }
+
+    fn run(
+        controller: &mut CharacterController,
+        physics: &mut Physics,
+        velocity: Vector3<f32>,
+        frames: usize,
+    ) {
+        for _ in 0..frames {
+            controller.move_with_velocity(physics, velocity, DT);
+        }
+    }
+
+    #[test]
+    fn test_fall_and_land() {
+        let mut physics = Physics::new();
+        add_floor(&mut physics);
+        let mut controller = make_controller(&mut physics, Vector3::new(0.0, 3.0, 0.0));
+
+        run(&mut controller, &mut physics, Vector3::default(), 120);
+
+        assert!(controller.is_grounded());
+        assert!((controller.position().y - 0.9).abs() < 0.05);
+        let normal = controller.ground_normal().unwrap();
+        assert!((normal - Vector3::y()).norm() < 1.0e-3);
+    }
+
+    #[test]
+    fn test_walk_on_trimesh() {
+        let mut physics = Physics::new();
+        // Grid of quads with a ramp up to one meter height, triangles of a mesh are found
+        // through its bounding volume tree.
+        let size = 20;
+        let height = |z: usize| ((z as f32 - 10.0) * 0.25).clamp(0.0, 1.0);
+        let mut vertices = Vec::new();
+        for z in 0..=size {
+            for x in 0..=size {
+                vertices.push(Point3::new(x as f32 - 10.0, height(z), z as f32 - 5.0));
+            }
+        }
+        let mut indices = Vec::new();
+        for z in 0..size as u32 {
+            for x in 0..size as u32 {
+                let i = z * (size as u32 + 1) + x;
+                let next_row = i + size as u32 + 1;
+                indices.push(Point3::new(i, next_row, i + 1));
+                indices.push(Point3::new(i + 1, next_row, next_row + 1));
+            }
+        }
+        let body = physics.add_body(RigidBodyBuilder::new_static().build());
+        physics.add_collider(ColliderBuilder::trimesh(vertices, indices).build(), body);
+
+        let mut controller = make_controller(&mut physics, Vector3::new(0.0, 1.0, 0.0));
+        run(&mut controller, &mut physics, Vector3::default(), 30);
+        assert!(controller.is_grounded());
+        assert!((controller.position().y - 0.9).abs() < 0.05);
+
+        run(
+            &mut controller,
+            &mut physics,
+            Vector3::new(0.0, 0.0, 3.0),
+            240,
+        );
+        assert!(controller.is_grounded());
+        assert!(controller.position().z > 10.0);
+        assert!((controller.position().y - 1.9).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_step_up_and_wall() {
+        let mut physics = Physics::new();
+        add_floor(&mut physics);
+        // Low step which must be climbed.
+        add_box(
+            &mut physics,
+            Vector3::new(0.0, 0.1, 5.0),
+            Vector3::new(5.0, 0.1, 2.0),
+            UnitQuaternion::identity(),
+        );
+        // High wall which must block character.
+        add_box(
+            &mut physics,
+            Vector3::new(0.0, 1.0, 10.0),
+            Vector3::new(5.0, 1.0, 1.0),
+            UnitQuaternion::identity(),
+        );
+        let mut controller = make_controller(&mut physics, Vector3::new(0.0, 0.9, 0.0));
+        run(&mut controller, &mut physics, Vector3::default(), 10);
+
+        run(
+            &mut controller,
+            &mut physics,
+            Vector3::new(0.0, 0.0, 3.0),
+            80,
+        );
+        assert!(controller.is_grounded());
+        assert!((controller.position().y - 1.1).abs() < 0.05);
+        assert!(controller.position().z > 3.0 && controller.position().z < 7.0);
+
+        run(
+            &mut controller,
+            &mut physics,
+            Vector3::new(0.0, 0.0, 3.0),
+            200,
+        );
+        assert!(controller.is_grounded());
+        assert!(controller.position().z < 9.0 - 0.3 + 0.05);
+    }
+
+    fn walk_to_slope(angle: f32) -> CharacterController {
+        let mut physics = Physics::new();
+        add_floor(&mut physics);
+        // Slope facing -Z, it crosses the floor at z = 5.
+        add_box(
+            &mut physics,
+            Vector3::new(0.0, 0.0, 5.0),
+            Vector3::new(5.0, 0.05, 5.0),
+            UnitQuaternion::from_axis_angle(&Vector3::x_axis(), -angle.to_radians()),
+        );
+        let mut controller = make_controller(&mut physics, Vector3::new(0.0, 0.9, 0.0));
+        run(
+            &mut controller,
+            &mut physics,
+            Vector3::new(0.0, 0.0, 3.0),
+            120,
+        );
+        controller
+    }
+
+    #[test]
+    fn test_slope_limit() {
+        let controller = walk_to_slope(30.0);
+        assert!(controller.is_grounded());
+        assert!(controller.position().y > 0.9 + 0.3);
+        let normal = controller.ground_normal().unwrap();
+        assert!((normal.y - 30.0f32.to_radians().cos()).abs() < 1.0e-2);
+
+        let controller = walk_to_slope(60.0);
+        assert!(controller.is_grounded());
+        assert!(controller.position().y < 0.9 + 0.3 + 0.05);
+        assert!(controller.position().z < 5.0);
+    }
+
+    #[test]
+    fn test_acute_corner_does_not_jitter() {
+        let mut physics = Physics::new();
+        add_floor(&mut physics);
+        // Two walls forming a 30 degrees corner at (0, 5), it is open towards -Z.
+        for &angle in &[15.0f32, -15.0] {
+            let rotation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), angle.to_radians());
+            add_box(
+                &mut physics,
+                Vector3::new(0.0, 1.0, 5.0) + rotation * Vector3::new(0.0, 0.0, -2.0),
+                Vector3::new(0.05, 1.0, 2.0),
+                rotation,
+            );
+        }
+        let mut controller = make_controller(&mut physics, Vector3::new(0.2, 0.9, 0.0));
+        run(
+            &mut controller,
+            &mut physics,
+            Vector3::new(0.0, 0.0, 4.0),
+            180,
+        );
+
+        let settled = controller.position();
+        // Capsule must be stopped by both walls near the corner.
+        assert!(settled.x.abs() < 0.05 && settled.z > 3.5);
+        for _ in 0..30 {
+            controller.move_with_velocity(&mut physics, Vector3::new(0.0, 0.0, 4.0), DT);
+            assert!((controller.position() - settled).norm() < 1.0e-3);
+        }
+    }
+
+    #[test]
+    fn test_walk_off_ledge_without_hop() {
+        let mut physics = Physics::new();
+        // Platform which ends at z = 2.
+        add_box(
+            &mut physics,
+            Vector3::new(0.0, -0.5, -3.0),
+            Vector3::new(5.0, 0.5, 5.0),
+            UnitQuaternion::identity(),
+        );
+        let mut controller = make_controller(&mut physics, Vector3::new(0.0, 0.9, 0.0));
+        run(&mut controller, &mut physics, Vector3::default(), 10);
+        let start_height = controller.position().y;
+
+        let mut was_grounded = true;
+        for _ in 0..120 {
+            controller.move_with_velocity(&mut physics, Vector3::new(0.0, 0.0, 3.0), DT);
+            assert!(controller.position().y <= start_height + 1.0e-4);
+            assert!(controller.vertical_velocity() <= 0.0);
+            if !controller.is_grounded() {
+                was_grounded = false;
+            } else {
+                assert!(was_grounded);
+            }
+        }
+        assert!(!was_grounded);
+        assert!(controller.position().y < -1.0);
+    }
+
+    #[test]
+    fn test_push_dynamic_body() {
+        let mut physics = Physics::new();
+        add_floor(&mut physics);
+        let body = physics.add_body(
+            RigidBodyBuilder::new_dynamic()
+                .translation(0.0, 0.5, 1.5)
+                .build(),
+        );
+        physics.add_collider(ColliderBuilder::cuboid(0.5, 0.5, 0.5).build(), body);
+        let mut controller = make_controller(&mut physics, Vector3::new(0.0, 0.9, 0.0));
+        run(&mut controller, &mut physics, Vector3::default(), 10);
+
+        run(
+            &mut controller,
+            &mut physics,
+            Vector3::new(0.0, 0.0, 2.0),
+            30,
+        );
+        let velocity = *physics.bodies.get(body.into()).unwrap().linvel();
+        assert!(velocity.z > 0.1);
+    }
+}
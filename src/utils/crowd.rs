@@ -0,0 +1,867 @@
+//! Path following and local avoidance for agents on a navmesh.
+//!
+//! # Overview
+//!
+//! [NavmeshAgent] follows a path on a navmesh: the path is built when a target is set and it is
+//! rebuilt only when the target moves further than a threshold from the point the path was built
+//! for, so a target can be changed every frame (chasing a player for example) without searching
+//! a path every frame. Each update the agent advances to the next waypoint of the path and
+//! produces desired velocity towards it, the agent slows down when it arrives at the target.
+//!
+//! Agents registered in a [Crowd] also avoid each other and circular dynamic obstacles (see
+//! [DynamicObstacle]) using optimal reciprocal collision avoidance (ORCA): each pair of agents
+//! shares responsibility to avoid collision, so they pass each other smoothly instead of
+//! oscillating. Avoidance may push an agent off the navmesh, so after each step position of the
+//! agent is projected back on the navmesh (see [Navmesh::project_point]). Navmesh is treated as
+//! walkable surface with Y axis pointing up, avoidance is done in projection on XZ plane.
+//!
+//! # Usage
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::{algebra::Vector3, pool::Handle},
+//!     utils::{
+//!         crowd::{Crowd, NavmeshAgent, NavmeshAgentBuilder},
+//!         navmesh::Navmesh,
+//!     },
+//! };
+//!
+//! fn spawn(crowd: &mut Crowd, position: Vector3<f32>) -> Handle<NavmeshAgent> {
+//!     crowd.add_agent(
+//!         NavmeshAgentBuilder::new()
+//!             .with_position(position)
+//!             .with_radius(0.3)
+//!             .with_speed(1.5)
+//!             .build(),
+//!     )
+//! }
+//!
+//! fn update(crowd: &mut Crowd, navmesh: &mut Navmesh, agent: Handle<NavmeshAgent>, dt: f32) {
+//!     crowd.agent_mut(agent).set_target(Vector3::new(10.0, 0.0, 10.0));
+//!     crowd.update(navmesh, dt).unwrap();
+//!     // Position of the agent is used to move its model.
+//!     let position = crowd.agent(agent).position();
+//! }
+//! ```
+//!
+//! # Limitations
+//!
+//! Neighbours of an agent are searched by brute force, so the crowd should not have more than a
+//! few hundreds of agents. Walls of the navmesh are not taken into account by avoidance, agents
+//! just slide along them because of projection on the navmesh.
+
+use crate::{
+    core::{
+        algebra::{Vector2, Vector3},
+        pool::{Handle, Pool},
+    },
+    utils::{astar::PathError, navmesh::Navmesh},
+};
+
+/// Agent is considered at a waypoint when it is closer than this fraction of its radius.
+const ARRIVAL_TOLERANCE: f32 = 0.1;
+
+/// Sine of angle between relative velocity and direction to an obstacle below which an agent
+/// is considered to move head-on to the obstacle.
+const HEAD_ON_THRESHOLD: f32 = 0.1;
+
+/// See module docs.
+#[derive(Clone, Debug)]
+pub struct NavmeshAgent {
+    position: Vector3<f32>,
+    velocity: Vector3<f32>,
+    desired_velocity: Vector3<f32>,
+    speed: f32,
+    radius: f32,
+    recalculation_threshold: f32,
+    target: Option<Vector3<f32>>,
+    // Target for which current path was built.
+    path_target: Vector3<f32>,
+    path_dirty: bool,
+    path: Vec<Vector3<f32>>,
+    current_waypoint: usize,
+}
+
+impl Default for NavmeshAgent {
+    fn default() -> Self {
+        NavmeshAgentBuilder::new().build()
+    }
+}
+
+fn xz(v: Vector3<f32>) -> Vector2<f32> {
+    Vector2::new(v.x, v.z)
+}
+
+impl NavmeshAgent {
+    /// Sets new target of the agent. Path to the target will be built on next update, if
+    /// the agent already has a path and the target is closer than recalculation threshold to
+    /// the point the path was built for, then the path is left as is.
+    pub fn set_target(&mut self, target: Vector3<f32>) {
+        if self.target.is_none()
+            || (target - self.path_target).norm() > self.recalculation_threshold
+        {
+            self.path_dirty = true;
+        }
+        self.target = Some(target);
+    }
+
+    /// Removes target and path of the agent, the agent will stop.
+    pub fn clear_target(&mut self) {
+        self.target = None;
+        self.path.clear();
+        self.path_dirty = false;
+        self.current_waypoint = 0;
+    }
+
+    /// Returns current target of the agent.
+    pub fn target(&self) -> Option<Vector3<f32>> {
+        self.target
+    }
+
+    /// Returns true if the agent has a target and it has reached the end of its path. The end
+    /// of the path can differ from the target if the target is unreachable or if the target was
+    /// moved less than recalculation threshold.
+    pub fn is_arrived(&self) -> bool {
+        self.target.is_some() && !self.path_dirty && self.current_waypoint >= self.path.len()
+    }
+
+    /// Moves the agent to given position immediately, path will be rebuilt on next update.
+    pub fn set_position(&mut self, position: Vector3<f32>) {
+        self.position = position;
+        self.path_dirty = self.target.is_some();
+    }
+
+    /// Returns current position of the agent.
+    pub fn position(&self) -> Vector3<f32> {
+        self.position
+    }
+
+    /// Returns actual velocity of the agent after avoidance.
+    pub fn velocity(&self) -> Vector3<f32> {
+        self.velocity
+    }
+
+    /// Returns velocity with which the agent wants to move along its path.
+    pub fn desired_velocity(&self) -> Vector3<f32> {
+        self.desired_velocity
+    }
+
+    /// Returns current path of the agent.
+    pub fn path(&self) -> &[Vector3<f32>] {
+        &self.path
+    }
+
+    /// Sets maximum speed of the agent.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+
+    /// Returns maximum speed of the agent.
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Sets radius of the agent, it is used for avoidance.
+    pub fn set_radius(&mut self, radius: f32) {
+        self.radius = radius.max(0.0);
+    }
+
+    /// Returns radius of the agent.
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// Sets distance which the target must move for to rebuild path of the agent.
+    pub fn set_recalculation_threshold(&mut self, threshold: f32) {
+        self.recalculation_threshold = threshold.max(0.0);
+    }
+
+    /// Returns distance which the target must move for to rebuild path of the agent.
+    pub fn recalculation_threshold(&self) -> f32 {
+        self.recalculation_threshold
+    }
+
+    fn update_path(&mut self, navmesh: &mut Navmesh) -> Result<(), PathError> {
+        if let (true, Some(target)) = (self.path_dirty, self.target) {
+            navmesh.find_path(self.position, target, &mut self.path)?;
+            self.path_target = target;
+            self.path_dirty = false;
+            // First waypoint is current position.
+            self.current_waypoint = 1;
+        }
+        Ok(())
+    }
+
+    fn steer(&mut self, dt: f32) {
+        let tolerance = (self.radius * ARRIVAL_TOLERANCE).max(f32::EPSILON);
+        // Arrived agent can be pushed away by other agents, it must return back then.
+        if let (Some(last), true) = (self.path.last(), self.current_waypoint >= self.path.len()) {
+            if (xz(*last) - xz(self.position)).norm() > self.radius {
+                self.current_waypoint = self.path.len() - 1;
+            }
+        }
+        while let Some(waypoint) = self.path.get(self.current_waypoint) {
+            if (xz(*waypoint) - xz(self.position)).norm() > tolerance {
+                break;
+            }
+            self.current_waypoint += 1;
+        }
+
+        self.desired_velocity = match self.path.get(self.current_waypoint) {
+            Some(waypoint) => {
+                let offset = xz(*waypoint) - xz(self.position);
+                let distance = offset.norm();
+                // Slow down at the end of the path to stop exactly at the target.
+                let speed = if self.current_waypoint + 1 == self.path.len() && dt > 0.0 {
+                    self.speed.min(distance / dt)
+                } else {
+                    self.speed
+                };
+                let velocity = offset.scale(speed / distance);
+                Vector3::new(velocity.x, 0.0, velocity.y)
+            }
+            None => Vector3::default(),
+        };
+    }
+
+    fn advance(&mut self, navmesh: &mut Navmesh, velocity: Vector3<f32>, dt: f32) {
+        let position = self.position + velocity.scale(dt);
+        self.position = navmesh.project_point(position).unwrap_or(position);
+        self.velocity = velocity;
+    }
+
+    /// Moves the agent along its path without avoidance, it should be used for agents that are
+    /// not registered in a crowd.
+    pub fn update(&mut self, navmesh: &mut Navmesh, dt: f32) -> Result<(), PathError> {
+        self.update_path(navmesh)?;
+        self.steer(dt);
+        self.advance(navmesh, self.desired_velocity, dt);
+        Ok(())
+    }
+}
+
+/// Allows you to create navmesh agent in declarative manner.
+pub struct NavmeshAgentBuilder {
+    position: Vector3<f32>,
+    speed: f32,
+    radius: f32,
+    recalculation_threshold: f32,
+}
+
+impl Default for NavmeshAgentBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NavmeshAgentBuilder {
+    /// Creates new builder instance.
+    pub fn new() -> Self {
+        Self {
+            position: Default::default(),
+            speed: 1.5,
+            radius: 0.3,
+            recalculation_threshold: 0.25,
+        }
+    }
+
+    /// Sets desired position of the agent.
+    pub fn with_position(mut self, position: Vector3<f32>) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Sets desired maximum speed of the agent.
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed.max(0.0);
+        self
+    }
+
+    /// Sets desired radius of the agent.
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius.max(0.0);
+        self
+    }
+
+    /// Sets distance which the target must move for to rebuild path of the agent.
+    pub fn with_recalculation_threshold(mut self, threshold: f32) -> Self {
+        self.recalculation_threshold = threshold.max(0.0);
+        self
+    }
+
+    /// Creates new agent.
+    pub fn build(self) -> NavmeshAgent {
+        NavmeshAgent {
+            position: self.position,
+            velocity: Default::default(),
+            desired_velocity: Default::default(),
+            speed: self.speed,
+            radius: self.radius,
+            recalculation_threshold: self.recalculation_threshold,
+            target: None,
+            path_target: Default::default(),
+            path_dirty: false,
+            path: Default::default(),
+            current_waypoint: 0,
+        }
+    }
+}
+
+/// Circular obstacle which is avoided by agents of a crowd, but does not avoid them itself -
+/// a vehicle, a player, etc. Obstacle is moved by user, velocity of obstacle is used to predict
+/// its movement, so it should be set too.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DynamicObstacle {
+    /// Position of the obstacle, only X and Z are used.
+    pub position: Vector3<f32>,
+    /// Velocity of the obstacle, only X and Z are used.
+    pub velocity: Vector3<f32>,
+    /// Radius of the obstacle.
+    pub radius: f32,
+}
+
+impl DynamicObstacle {
+    /// Creates new still obstacle.
+    pub fn new(position: Vector3<f32>, radius: f32) -> Self {
+        Self {
+            position,
+            velocity: Default::default(),
+            radius,
+        }
+    }
+}
+
+// Half-plane of permitted velocities, velocities on the left side of the line are permitted.
+#[derive(Copy, Clone, Debug)]
+struct Line {
+    point: Vector2<f32>,
+    direction: Vector2<f32>,
+}
+
+fn det(a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+// Builds ORCA half-plane for an agent and an obstacle (other agent or dynamic obstacle),
+// `responsibility` is a part of avoidance which is done by the agent.
+#[allow(clippy::too_many_arguments)]
+fn orca_line(
+    position: Vector2<f32>,
+    velocity: Vector2<f32>,
+    other_position: Vector2<f32>,
+    other_velocity: Vector2<f32>,
+    combined_radius: f32,
+    time_horizon: f32,
+    dt: f32,
+    responsibility: f32,
+) -> Line {
+    let relative_position = other_position - position;
+    let relative_velocity = velocity - other_velocity;
+    let distance_squared = relative_position.norm_squared();
+    let combined_radius_squared = combined_radius * combined_radius;
+
+    let (direction, u) = if distance_squared > combined_radius_squared {
+        // No collision yet, velocity obstacle is a truncated cone.
+        let w = relative_velocity - relative_position.scale(1.0 / time_horizon);
+        let w_length_squared = w.norm_squared();
+        let dot = w.dot(&relative_position);
+        // Projection on cut-off circle is symmetric when agent moves head-on to the obstacle
+        // and collides with it, agent would just slow down in front of it. Legs of the cone are
+        // a stricter limit which makes the agent sidestep.
+        let head_on = w_length_squared < combined_radius_squared / (time_horizon * time_horizon)
+            && relative_velocity.dot(&relative_position) > 0.0
+            && det(relative_position, relative_velocity).abs()
+                <= HEAD_ON_THRESHOLD * relative_position.norm() * relative_velocity.norm();
+        if dot < 0.0 && dot * dot > combined_radius_squared * w_length_squared && !head_on {
+            // Project on cut-off circle.
+            let w_length = w_length_squared.sqrt();
+            let unit_w = w.scale(1.0 / w_length);
+            (
+                Vector2::new(unit_w.y, -unit_w.x),
+                unit_w.scale(combined_radius / time_horizon - w_length),
+            )
+        } else {
+            // Project on legs of the cone.
+            let leg = (distance_squared - combined_radius_squared).sqrt();
+            let direction = if det(relative_position, w) > 0.0 {
+                Vector2::new(
+                    relative_position.x * leg - relative_position.y * combined_radius,
+                    relative_position.x * combined_radius + relative_position.y * leg,
+                )
+            } else {
+                -Vector2::new(
+                    relative_position.x * leg + relative_position.y * combined_radius,
+                    -relative_position.x * combined_radius + relative_position.y * leg,
+                )
+            }
+            .scale(1.0 / distance_squared);
+            (
+                direction,
+                direction.scale(relative_velocity.dot(&direction)) - relative_velocity,
+            )
+        }
+    } else {
+        // Already collided, resolve collision in one step.
+        let w = relative_velocity - relative_position.scale(1.0 / dt);
+        let w_length = w.norm();
+        let unit_w = if w_length > f32::EPSILON {
+            w.scale(1.0 / w_length)
+        } else {
+            Vector2::x()
+        };
+        (
+            Vector2::new(unit_w.y, -unit_w.x),
+            unit_w.scale(combined_radius / dt - w_length),
+        )
+    };
+
+    Line {
+        point: velocity + u.scale(responsibility),
+        direction,
+    }
+}
+
+// Searches velocity closest to optimal one (or furthest in given direction) on a line,
+// satisfying previous lines and speed limit.
+fn linear_program1(
+    lines: &[Line],
+    line_index: usize,
+    max_speed: f32,
+    optimal: Vector2<f32>,
+    direction_optimal: bool,
+    result: &mut Vector2<f32>,
+) -> bool {
+    let line = lines[line_index];
+    let dot = line.point.dot(&line.direction);
+    let discriminant = dot * dot + max_speed * max_speed - line.point.norm_squared();
+    if discriminant < 0.0 {
+        // Speed limit circle does not intersect the line.
+        return false;
+    }
+
+    let discriminant_sqrt = discriminant.sqrt();
+    let mut t_left = -dot - discriminant_sqrt;
+    let mut t_right = -dot + discriminant_sqrt;
+
+    for other in lines[..line_index].iter() {
+        let denominator = det(line.direction, other.direction);
+        let numerator = det(other.direction, line.point - other.point);
+        if denominator.abs() <= f32::EPSILON {
+            // Lines are parallel.
+            if numerator < 0.0 {
+                return false;
+            }
+            continue;
+        }
+        let t = numerator / denominator;
+        if denominator >= 0.0 {
+            t_right = t_right.min(t);
+        } else {
+            t_left = t_left.max(t);
+        }
+        if t_left > t_right {
+            return false;
+        }
+    }
+
+    let t = if direction_optimal {
+        if optimal.dot(&line.direction) > 0.0 {
+            t_right
+        } else {
+            t_left
+        }
+    } else {
+        line.direction
+            .dot(&(optimal - line.point))
+            .max(t_left)
+            .min(t_right)
+    };
+    *result = line.point + line.direction.scale(t);
+    true
+}
+
+// Searches velocity closest to optimal one satisfying all lines and speed limit. Returns
+// index of line at which it has failed, or amount of lines on success.
+fn linear_program2(
+    lines: &[Line],
+    max_speed: f32,
+    optimal: Vector2<f32>,
+    direction_optimal: bool,
+    result: &mut Vector2<f32>,
+) -> usize {
+    *result = if direction_optimal {
+        optimal.scale(max_speed)
+    } else if optimal.norm_squared() > max_speed * max_speed {
+        optimal.normalize().scale(max_speed)
+    } else {
+        optimal
+    };
+
+    for (i, line) in lines.iter().enumerate() {
+        if det(line.direction, line.point - *result) > 0.0 {
+            let previous = *result;
+            if !linear_program1(lines, i, max_speed, optimal, direction_optimal, result) {
+                *result = previous;
+                return i;
+            }
+        }
+    }
+
+    lines.len()
+}
+
+// Called when there is no velocity satisfying all lines, searches velocity which minimizes
+// maximum penetration in lines starting from `begin`. First `hard_count` lines are never
+// violated.
+fn linear_program3(
+    lines: &[Line],
+    hard_count: usize,
+    begin: usize,
+    max_speed: f32,
+    projected: &mut Vec<Line>,
+    result: &mut Vector2<f32>,
+) {
+    let mut distance = 0.0;
+    for i in begin..lines.len() {
+        let line = lines[i];
+        if det(line.direction, line.point - *result) > distance {
+            projected.clear();
+            projected.extend_from_slice(&lines[..hard_count]);
+            for other in lines[hard_count..i].iter() {
+                let determinant = det(line.direction, other.direction);
+                let point = if determinant.abs() <= f32::EPSILON {
+                    if line.direction.dot(&other.direction) > 0.0 {
+                        // Lines point in the same direction.
+                        continue;
+                    }
+                    (line.point + other.point).scale(0.5)
+                } else {
+                    line.point
+                        + line
+                            .direction
+                            .scale(det(other.direction, line.point - other.point) / determinant)
+                };
+                projected.push(Line {
+                    point,
+                    direction: (other.direction - line.direction).normalize(),
+                });
+            }
+
+            let previous = *result;
+            let optimal = Vector2::new(-line.direction.y, line.direction.x);
+            if linear_program2(projected, max_speed, optimal, true, result) < projected.len() {
+                // Can fail only because of floating point errors, keep previous result.
+                *result = previous;
+            }
+
+            distance = det(line.direction, line.point - *result);
+        }
+    }
+}
+
+/// Set of agents that avoid each other and dynamic obstacles, see module docs.
+pub struct Crowd {
+    agents: Pool<NavmeshAgent>,
+    obstacles: Pool<DynamicObstacle>,
+    time_horizon: f32,
+    lines: Vec<Line>,
+    projected_lines: Vec<Line>,
+    velocities: Vec<(Handle<NavmeshAgent>, Vector3<f32>)>,
+}
+
+impl Default for Crowd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crowd {
+    /// Creates new empty crowd.
+    pub fn new() -> Self {
+        Self {
+            agents: Default::default(),
+            obstacles: Default::default(),
+            time_horizon: 2.0,
+            lines: Default::default(),
+            projected_lines: Default::default(),
+            velocities: Default::default(),
+        }
+    }
+
+    /// Adds new agent to the crowd.
+    pub fn add_agent(&mut self, agent: NavmeshAgent) -> Handle<NavmeshAgent> {
+        self.agents.spawn(agent)
+    }
+
+    /// Removes agent from the crowd.
+    pub fn remove_agent(&mut self, handle: Handle<NavmeshAgent>) -> NavmeshAgent {
+        self.agents.free(handle)
+    }
+
+    /// Returns shared reference to an agent. Panics if handle is invalid.
+    pub fn agent(&self, handle: Handle<NavmeshAgent>) -> &NavmeshAgent {
+        &self.agents[handle]
+    }
+
+    /// Returns mutable reference to an agent. Panics if handle is invalid.
+    pub fn agent_mut(&mut self, handle: Handle<NavmeshAgent>) -> &mut NavmeshAgent {
+        &mut self.agents[handle]
+    }
+
+    /// Returns reference to the pool of agents.
+    pub fn agents(&self) -> &Pool<NavmeshAgent> {
+        &self.agents
+    }
+
+    /// Adds new dynamic obstacle to the crowd.
+    pub fn add_obstacle(&mut self, obstacle: DynamicObstacle) -> Handle<DynamicObstacle> {
+        self.obstacles.spawn(obstacle)
+    }
+
+    /// Removes dynamic obstacle from the crowd.
+    pub fn remove_obstacle(&mut self, handle: Handle<DynamicObstacle>) -> DynamicObstacle {
+        self.obstacles.free(handle)
+    }
+
+    /// Returns mutable reference to a dynamic obstacle, it should be used to move the obstacle.
+    /// Panics if handle is invalid.
+    pub fn obstacle_mut(&mut self, handle: Handle<DynamicObstacle>) -> &mut DynamicObstacle {
+        &mut self.obstacles[handle]
+    }
+
+    /// Returns reference to the pool of dynamic obstacles.
+    pub fn obstacles(&self) -> &Pool<DynamicObstacle> {
+        &self.obstacles
+    }
+
+    /// Sets how far in time (in seconds) agents look for collisions. Larger values make agents
+    /// react earlier but make them more "shy" in dense crowds.
+    pub fn set_time_horizon(&mut self, time_horizon: f32) {
+        self.time_horizon = time_horizon.max(f32::EPSILON);
+    }
+
+    /// Returns how far in time (in seconds) agents look for collisions.
+    pub fn time_horizon(&self) -> f32 {
+        self.time_horizon
+    }
+
+    /// Updates paths of agents, calculates velocities of agents avoiding collisions and moves
+    /// agents. Agents that are not moving still take part in avoidance.
+    pub fn update(&mut self, navmesh: &mut Navmesh, dt: f32) -> Result<(), PathError> {
+        if dt <= 0.0 {
+            return Ok(());
+        }
+
+        for agent in self.agents.iter_mut() {
+            agent.update_path(navmesh)?;
+            agent.steer(dt);
+        }
+
+        self.velocities.clear();
+        for (handle, agent) in self.agents.pair_iter() {
+            let position = xz(agent.position);
+            let velocity = xz(agent.velocity);
+
+            // Obstacles do not avoid agents, so lines of obstacles are never relaxed.
+            self.lines.clear();
+            for obstacle in self.obstacles.iter() {
+                let combined_radius = agent.radius + obstacle.radius;
+                let range =
+                    (agent.speed + obstacle.velocity.norm()) * self.time_horizon + combined_radius;
+                if (xz(obstacle.position) - position).norm_squared() < range * range {
+                    self.lines.push(orca_line(
+                        position,
+                        velocity,
+                        xz(obstacle.position),
+                        xz(obstacle.velocity),
+                        combined_radius,
+                        self.time_horizon,
+                        dt,
+                        1.0,
+                    ));
+                }
+            }
+            let hard_count = self.lines.len();
+
+            for (other_handle, other) in self.agents.pair_iter() {
+                if other_handle == handle {
+                    continue;
+                }
+                let combined_radius = agent.radius + other.radius;
+                let range = (agent.speed + other.speed) * self.time_horizon + combined_radius;
+                if (xz(other.position) - position).norm_squared() < range * range {
+                    self.lines.push(orca_line(
+                        position,
+                        velocity,
+                        xz(other.position),
+                        xz(other.velocity),
+                        combined_radius,
+                        self.time_horizon,
+                        dt,
+                        0.5,
+                    ));
+                }
+            }
+
+            let mut result = Vector2::default();
+            let failed = linear_program2(
+                &self.lines,
+                agent.speed,
+                xz(agent.desired_velocity),
+                false,
+                &mut result,
+            );
+            if failed < self.lines.len() {
+                linear_program3(
+                    &self.lines,
+                    hard_count,
+                    failed,
+                    agent.speed,
+                    &mut self.projected_lines,
+                    &mut result,
+                );
+            }
+
+            self.velocities
+                .push((handle, Vector3::new(result.x, 0.0, result.y)));
+        }
+
+        for &(handle, velocity) in self.velocities.iter() {
+            self.agents[handle].advance(navmesh, velocity, dt);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{algebra::Vector3, math::TriangleDefinition},
+        utils::{
+            crowd::{Crowd, DynamicObstacle, NavmeshAgentBuilder},
+            navmesh::Navmesh,
+        },
+    };
+
+    fn make_plaza(size: f32) -> Navmesh {
+        let half = size * 0.5;
+        Navmesh::new(
+            &[TriangleDefinition([0, 1, 2]), TriangleDefinition([0, 2, 3])],
+            &[
+                Vector3::new(-half, 0.0, -half),
+                Vector3::new(-half, 0.0, half),
+                Vector3::new(half, 0.0, half),
+                Vector3::new(half, 0.0, -half),
+            ],
+        )
+    }
+
+    fn min_distance(crowd: &Crowd) -> f32 {
+        let mut min = f32::MAX;
+        for (a, first) in crowd.agents().pair_iter() {
+            for (b, second) in crowd.agents().pair_iter() {
+                if a != b {
+                    min = min.min((first.position() - second.position()).norm());
+                }
+            }
+        }
+        min
+    }
+
+    #[test]
+    fn test_crowd_crossing_plaza() {
+        let mut navmesh = make_plaza(20.0);
+        let mut crowd = Crowd::new();
+
+        // Agents stand on a circle and walk to the opposite side of it, so all of them meet
+        // in the center of the plaza.
+        let count = 50;
+        let radius = 0.3;
+        let mut agents = Vec::new();
+        for i in 0..count {
+            let angle = i as f32 / count as f32 * 2.0 * std::f32::consts::PI;
+            let position = Vector3::new(angle.cos(), 0.0, angle.sin()).scale(8.0);
+            let agent = crowd.add_agent(
+                NavmeshAgentBuilder::new()
+                    .with_position(position)
+                    .with_radius(radius)
+                    .with_speed(1.5)
+                    .build(),
+            );
+            crowd.agent_mut(agent).set_target(-position);
+            agents.push((agent, -position));
+        }
+
+        let dt = 1.0 / 30.0;
+        let mut elapsed = 0.0;
+        while agents
+            .iter()
+            .any(|&(agent, _)| !crowd.agent(agent).is_arrived())
+        {
+            crowd.update(&mut navmesh, dt).unwrap();
+            elapsed += dt;
+            assert!(elapsed < 60.0, "agents got stuck");
+            // Agents may touch each other, but must not walk through each other.
+            assert!(min_distance(&crowd) > 2.0 * radius * 0.9);
+        }
+
+        for &(agent, target) in agents.iter() {
+            let position = crowd.agent(agent).position();
+            assert!((position - target).norm() < radius);
+        }
+    }
+
+    #[test]
+    fn test_crowd_obstacle_avoidance() {
+        let mut navmesh = make_plaza(10.0);
+        let mut crowd = Crowd::new();
+
+        let agent = crowd.add_agent(
+            NavmeshAgentBuilder::new()
+                .with_position(Vector3::new(-4.0, 0.0, 0.0))
+                .build(),
+        );
+        let target = Vector3::new(4.0, 0.0, 0.0);
+        crowd.agent_mut(agent).set_target(target);
+        crowd.add_obstacle(DynamicObstacle::new(Vector3::default(), 1.0));
+
+        for _ in 0..300 {
+            crowd.update(&mut navmesh, 1.0 / 30.0).unwrap();
+            let agent = crowd.agent(agent);
+            assert!(agent.position().norm() >= (1.0 + agent.radius()) * 0.99);
+        }
+        assert!(crowd.agent(agent).is_arrived());
+
+        // Agent stays on the navmesh even if it is pushed off.
+        crowd
+            .agent_mut(agent)
+            .set_position(Vector3::new(6.0, 0.0, 0.0));
+        crowd
+            .agent_mut(agent)
+            .set_target(Vector3::new(8.0, 0.0, 0.0));
+        crowd.update(&mut navmesh, 1.0 / 30.0).unwrap();
+        assert!(crowd.agent(agent).position().x <= 5.0);
+    }
+
+    #[test]
+    fn test_agent_path_recalculation() {
+        let mut navmesh = make_plaza(10.0);
+        let mut agent = NavmeshAgentBuilder::new()
+            .with_recalculation_threshold(1.0)
+            .build();
+
+        agent.set_target(Vector3::new(4.0, 0.0, 0.0));
+        agent.update(&mut navmesh, 0.1).unwrap();
+        assert_eq!(agent.path().last(), Some(&Vector3::new(4.0, 0.0, 0.0)));
+
+        // Small movement of the target does not rebuild the path.
+        agent.set_target(Vector3::new(4.0, 0.0, 0.5));
+        agent.update(&mut navmesh, 0.1).unwrap();
+        assert_eq!(agent.path().last(), Some(&Vector3::new(4.0, 0.0, 0.0)));
+
+        agent.set_target(Vector3::new(4.0, 0.0, 2.0));
+        agent.update(&mut navmesh, 0.1).unwrap();
+        assert_eq!(agent.path().last(), Some(&Vector3::new(4.0, 0.0, 2.0)));
+    }
+}
@@ -12,15 +12,16 @@
 use crate::{
     core::{
         algebra::{Matrix3, Matrix4, Point3, Vector2, Vector3, Vector4},
-        arrayvec::ArrayVec,
         math::{self, ray::Ray, Matrix4Ext, Rect, TriangleDefinition, Vector2Ext},
-        octree::{Octree, OctreeNode},
+        octree::Octree,
         pool::Handle,
         visitor::{Visit, VisitResult, Visitor},
     },
     engine::resource_manager::{ResourceManager, TextureRegistrationError},
     renderer::surface::SurfaceSharedData,
-    resource::texture::{Texture, TextureData, TextureKind, TexturePixelKind, TextureState},
+    resource::texture::{
+        Texture, TextureColorSpace, TextureData, TextureKind, TexturePixelKind, TextureState,
+    },
     scene::{light::Light, node::Node, Scene},
     utils::{uvgen, uvgen::SurfaceDataPatch},
 };
@@ -671,10 +672,9 @@ fn generate_lightmap(
         vec![Vector4::new(0, 0, 0, 0); (atlas_size * atlas_size) as usize];
 
     let half_pixel = scale * 0.5;
-    pixels
-        .par_iter_mut()
-        .enumerate()
-        .for_each(|(i, pixel): (usize, &mut Vector4<u8>)| {
+    pixels.par_iter_mut().enumerate().for_each_init(
+        Vec::new,
+        |query_buffer, (i, pixel): (usize, &mut Vector4<u8>)| {
             let x = i as u32 % atlas_size;
             let y = i as u32 / atlas_size;
 
@@ -713,40 +713,26 @@ fn generate_lightmap(
                     };
                     // Shadows
                     if attenuation >= 0.01 {
-                        let mut query_buffer = ArrayVec::<[Handle<OctreeNode>; 64]>::new();
                         let shadow_bias = 0.01;
                         if let Some(ray) = Ray::from_two_points(&light_position, &world_position) {
                             'outer_loop: for other_instance in other_instances {
-                                other_instance
-                                    .data()
-                                    .octree
-                                    .ray_query_static(&ray, &mut query_buffer);
-                                for &node in query_buffer.iter() {
-                                    match other_instance.data().octree.node(node) {
-                                        OctreeNode::Leaf { indices, .. } => {
-                                            let other_data = other_instance.data();
-                                            for &triangle_index in indices {
-                                                let triangle =
-                                                    &other_data.triangles[triangle_index as usize];
-                                                let a = other_data.vertices[triangle[0] as usize]
-                                                    .world_position;
-                                                let b = other_data.vertices[triangle[1] as usize]
-                                                    .world_position;
-                                                let c = other_data.vertices[triangle[2] as usize]
-                                                    .world_position;
-                                                if let Some(pt) =
-                                                    ray.triangle_intersection(&[a, b, c])
-                                                {
-                                                    if ray.origin.metric_distance(&pt) + shadow_bias
-                                                        < ray.dir.norm()
-                                                    {
-                                                        attenuation = 0.0;
-                                                        break 'outer_loop;
-                                                    }
-                                                }
-                                            }
+                                let other_data = other_instance.data();
+                                other_data.octree.ray_query(&ray, query_buffer);
+                                for &triangle_index in query_buffer.iter() {
+                                    let triangle = &other_data.triangles[triangle_index as usize];
+                                    let a =
+                                        other_data.vertices[triangle[0] as usize].world_position;
+                                    let b =
+                                        other_data.vertices[triangle[1] as usize].world_position;
+                                    let c =
+                                        other_data.vertices[triangle[2] as usize].world_position;
+                                    if let Some(pt) = ray.triangle_intersection(&[a, b, c]) {
+                                        if ray.origin.metric_distance(&pt) + shadow_bias
+                                            < ray.dir.norm()
+                                        {
+                                            attenuation = 0.0;
+                                            break 'outer_loop;
                                         }
-                                        OctreeNode::Branch { .. } => unreachable!(),
                                     }
                                 }
                             }
@@ -762,7 +748,8 @@ fn generate_lightmap(
                     255, // Indicates that this pixel was "filled"
                 );
             }
-        });
+        },
+    );
 
     // Prepare light map for bilinear filtration. This step is mandatory to prevent bleeding.
     let mut rgb_pixels: Vec<Vector3<u8>> = Vec::with_capacity((atlas_size * atlas_size) as usize);
@@ -849,7 +836,7 @@ fn generate_lightmap(
         }
     }
 
-    TextureData::from_bytes(
+    let mut data = TextureData::from_bytes(
         TextureKind::Rectangle {
             width: atlas_size,
             height: atlas_size,
@@ -857,49 +844,60 @@ fn generate_lightmap(
         TexturePixelKind::RGB8,
         bytes,
     )
-    .unwrap()
+    .unwrap();
+    // Lightmap contains light intensity, not color, so it must be used as is.
+    data.set_color_space(TextureColorSpace::Linear);
+    data
 }
 
 #[cfg(test)]
 mod test {
     use crate::{
-        core::{
-            algebra::{Matrix4, Vector3},
-            color::Color,
-        },
-        renderer::surface::SurfaceSharedData,
+        core::algebra::{Matrix4, Vector3},
+        renderer::surface::{Surface, SurfaceSharedData},
         resource::texture::TextureKind,
-        utils::{
-            lightmap::{generate_lightmap, LightDefinition, PointLightDefinition},
-            uvgen::generate_uvs,
+        scene::{
+            base::BaseBuilder,
+            light::{BaseLightBuilder, PointLightBuilder},
+            mesh::MeshBuilder,
+            transform::TransformBuilder,
+            Scene,
         },
+        utils::lightmap::Lightmap,
     };
-    use image::RgbaImage;
+    use std::sync::{Arc, RwLock};
 
     #[test]
     fn test_generate_lightmap() {
-        //let mut data = SurfaceSharedData::make_sphere(20, 20, 1.0);
-        let mut data = SurfaceSharedData::make_cone(
+        let mut scene = Scene::new();
+        let data = SurfaceSharedData::make_cone(
             16,
             1.0,
             1.0,
             Matrix4::new_nonuniform_scaling(&Vector3::new(1.0, 1.1, 1.0)),
         );
-        let lights = [LightDefinition::Point(PointLightDefinition {
-            intensity: 3.0,
-            position: Vector3::new(0.0, 2.0, 0.0),
-            color: Color::WHITE.as_frgb(),
-            radius: 4.0,
-        })];
-        let lightmap = generate_lightmap(&mut data, &Matrix4::identity(), &lights, 128, true);
-
-        let (w, h) = if let TextureKind::Rectangle { width, height } = lightmap.kind {
-            (width, height)
+        let mesh = MeshBuilder::new(BaseBuilder::new())
+            .with_surfaces(vec![Surface::new(Arc::new(RwLock::new(data)))])
+            .build(&mut scene.graph);
+        PointLightBuilder::new(BaseLightBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(Vector3::new(0.0, 2.0, 0.0))
+                    .build(),
+            ),
+        ))
+        .with_radius(4.0)
+        .build(&mut scene.graph);
+
+        let lightmap =
+            Lightmap::new(&mut scene, 64, Default::default(), Default::default()).unwrap();
+
+        let texture = lightmap.map[&mesh][0].texture.clone().unwrap();
+        let kind = texture.data_ref().kind;
+        if let TextureKind::Rectangle { width, height } = kind {
+            assert!(width > 0 && height > 0);
         } else {
             unreachable!();
-        };
-
-        let image = RgbaImage::from_raw(w, h, lightmap.bytes).unwrap();
-        image.save("lightmap.png").unwrap();
+        }
     }
 }
@@ -3,10 +3,16 @@
 //! Utilities module provides set of commonly used algorithms.
 
 pub mod astar;
+pub mod character;
+pub mod crowd;
 pub mod lightmap;
 pub mod log;
 pub mod navmesh;
+pub mod noise;
 pub mod raw_mesh;
+pub mod reverb_zone;
+pub mod simplify;
+pub mod texture_atlas;
 pub mod uvgen;
 
 use crate::core::algebra::Vector2;
@@ -3,19 +3,23 @@
 //! Navigation mesh is a set of convex polygons which is used for path finding in complex
 //! environment.
 //!
-//! # Limitations
+//! There are two ways of path finding on navmesh: [Navmesh::build_path] searches path from
+//! vertex to vertex along edges of the mesh, [Navmesh::find_path] searches path between
+//! arbitrary points on the mesh - it finds a chain of triangles (corridor) using A* and then
+//! pulls the path through the corridor (funnel algorithm), so the path is as short as possible
+//! and has waypoints only at corners. Navmesh is treated as walkable surface with Y axis
+//! pointing up.
 //!
-//! Current implementation can only build paths from vertex to vertex in mesh, it can't
-//! search path from arbitrary point in polygon to other point in other polygon. It can
-//! be added pretty easily, but requires some extensive tests. This is still TODO.
+//! Separate parts of a navmesh can be connected by off-mesh links - jumps, ladders, teleports
+//! and so on. See [OffMeshLink] for more info.
 
 #![warn(missing_docs)]
 
-use crate::core::algebra::Vector3;
+use crate::core::algebra::{Vector2, Vector3};
 use crate::utils::raw_mesh::RawVertex;
 use crate::{
     core::{
-        math::{self, TriangleDefinition},
+        math::{self, PositionProvider, TriangleDefinition},
         octree::Octree,
     },
     scene::mesh::Mesh,
@@ -26,16 +30,63 @@ use crate::{
 };
 use rapier3d::na::Point3;
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     hash::{Hash, Hasher},
 };
 
+/// Off-mesh link is a connection between two points on a navmesh which can't be reached by
+/// walking - a jump over a gap, a ladder, a teleport, etc. Path built by [Navmesh::find_path]
+/// goes through begin and end points of a link, so it is possible to check when an agent
+/// reaches a link and play appropriate animation.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OffMeshLink {
+    begin: Vector3<f32>,
+    end: Vector3<f32>,
+    bidirectional: bool,
+    begin_triangle: usize,
+    end_triangle: usize,
+}
+
+impl OffMeshLink {
+    /// Creates new link from `begin` to `end`. Bidirectional link can be passed in both
+    /// directions, otherwise only from begin to end (jump down from a cliff for example).
+    pub fn new(begin: Vector3<f32>, end: Vector3<f32>, bidirectional: bool) -> Self {
+        Self {
+            begin,
+            end,
+            bidirectional,
+            begin_triangle: 0,
+            end_triangle: 0,
+        }
+    }
+
+    /// Returns begin point of the link.
+    pub fn begin(&self) -> Vector3<f32> {
+        self.begin
+    }
+
+    /// Returns end point of the link.
+    pub fn end(&self) -> Vector3<f32> {
+        self.end
+    }
+
+    /// Returns true if the link can be passed in both directions.
+    pub fn is_bidirectional(&self) -> bool {
+        self.bidirectional
+    }
+}
+
 /// See module docs.
 pub struct Navmesh {
     octree: Octree,
     triangles: Vec<TriangleDefinition>,
     pathfinder: PathFinder,
     query_buffer: Vec<u32>,
+    // Graph of triangles, each vertex of the graph is at the center of respective triangle.
+    triangle_graph: PathFinder,
+    off_mesh_links: Vec<OffMeshLink>,
+    corridor: Vec<usize>,
+    portals: Vec<(Vector3<f32>, Vector3<f32>)>,
 }
 
 #[derive(Copy, Clone)]
@@ -67,10 +118,113 @@ impl Default for Navmesh {
             triangles: Default::default(),
             pathfinder: Default::default(),
             query_buffer: Default::default(),
+            triangle_graph: Default::default(),
+            off_mesh_links: Default::default(),
+            corridor: Default::default(),
+            portals: Default::default(),
         }
     }
 }
 
+// Doubled signed area of triangle projected on XZ plane.
+fn triangle_area_xz(a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>) -> f32 {
+    (c.x - a.x) * (b.z - a.z) - (b.x - a.x) * (c.z - a.z)
+}
+
+// Closest point of a segment to given point in projection on XZ plane, height of the result
+// is interpolated along the segment.
+fn closest_point_on_segment_xz(
+    a: Vector3<f32>,
+    b: Vector3<f32>,
+    point: Vector3<f32>,
+) -> Vector3<f32> {
+    let ab = Vector2::new(b.x - a.x, b.z - a.z);
+    let ap = Vector2::new(point.x - a.x, point.z - a.z);
+    let length_squared = ab.norm_squared();
+    let t = if length_squared > f32::EPSILON {
+        (ap.dot(&ab) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    a.lerp(&b, t)
+}
+
+fn push_waypoint(path: &mut Vec<Vector3<f32>>, point: Vector3<f32>) {
+    if path.last() != Some(&point) {
+        path.push(point);
+    }
+}
+
+// Simple stupid funnel algorithm. Each portal is a pair of left and right points, first and
+// last portals are degenerated into start and end points of the path. Walks through portals
+// narrowing the funnel and adds a waypoint each time when one side of the funnel crosses
+// the other.
+fn string_pull(portals: &[(Vector3<f32>, Vector3<f32>)], path: &mut Vec<Vector3<f32>>) {
+    let (mut apex, mut right) = match portals.first() {
+        Some(&(left, right)) => (left, right),
+        None => return,
+    };
+    let mut left = apex;
+    let (mut left_index, mut right_index) = (0, 0);
+
+    push_waypoint(path, apex);
+
+    // Skip portals which pass through start point (it lies on an edge between triangles),
+    // they do not restrict the path but make the funnel degenerated.
+    let mut i = 1;
+    while i + 1 < portals.len() {
+        let (portal_left, portal_right) = portals[i];
+        if triangle_area_xz(apex, portal_left, portal_right).abs() > std::f32::EPSILON
+            || (portal_left - apex).dot(&(portal_right - apex)) > 0.0
+        {
+            break;
+        }
+        i += 1;
+    }
+
+    while i < portals.len() {
+        let (portal_left, portal_right) = portals[i];
+
+        // Try to narrow right side of the funnel.
+        if triangle_area_xz(apex, right, portal_right) <= 0.0 {
+            if apex == right || triangle_area_xz(apex, left, portal_right) > 0.0 {
+                right = portal_right;
+                right_index = i;
+            } else {
+                // Right side crossed left side, left point becomes new apex.
+                apex = left;
+                push_waypoint(path, apex);
+                right = apex;
+                right_index = left_index;
+                i = left_index + 1;
+                continue;
+            }
+        }
+
+        // Try to narrow left side of the funnel.
+        if triangle_area_xz(apex, left, portal_left) >= 0.0 {
+            if apex == left || triangle_area_xz(apex, right, portal_left) < 0.0 {
+                left = portal_left;
+                left_index = i;
+            } else {
+                // Left side crossed right side, right point becomes new apex.
+                apex = right;
+                push_waypoint(path, apex);
+                left = apex;
+                left_index = right_index;
+                i = right_index + 1;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    if let Some(&(end, _)) = portals.last() {
+        push_waypoint(path, end);
+    }
+}
+
 impl Navmesh {
     /// Creates new navigation mesh from given set of triangles and vertices. This is
     /// low level method that allows to specify triangles and vertices directly. In
@@ -108,6 +262,35 @@ impl Navmesh {
             });
         }
 
+        // Triangles are linked if they share an edge.
+        let mut edge_triangles = HashMap::<Edge, Vec<usize>>::new();
+        for (index, triangle) in triangles.iter().enumerate() {
+            for i in 0..3 {
+                edge_triangles
+                    .entry(Edge {
+                        a: triangle[i],
+                        b: triangle[(i + 1) % 3],
+                    })
+                    .or_default()
+                    .push(index);
+            }
+        }
+
+        let mut triangle_graph = PathFinder::new();
+        triangle_graph.set_vertices(
+            raw_triangles
+                .iter()
+                .map(|t| PathVertex::new((t[0] + t[1] + t[2]).scale(1.0 / 3.0)))
+                .collect(),
+        );
+        for adjacent in edge_triangles.values() {
+            for (i, &a) in adjacent.iter().enumerate() {
+                for &b in adjacent[(i + 1)..].iter() {
+                    triangle_graph.link_bidirect(a, b);
+                }
+            }
+        }
+
         for edge in edges {
             pathfinder.link_bidirect(edge.a as usize, edge.b as usize);
         }
@@ -117,6 +300,10 @@ impl Navmesh {
             octree: Octree::new(&raw_triangles, 32),
             pathfinder,
             query_buffer: Default::default(),
+            triangle_graph,
+            off_mesh_links: Default::default(),
+            corridor: Default::default(),
+            portals: Default::default(),
         }
     }
 
@@ -194,6 +381,259 @@ impl Navmesh {
         }
     }
 
+    /// Searches a triangle which contains given point in projection on XZ plane, if there are
+    /// several such triangles (multi-level navmesh) then the triangle which is closest to
+    /// the point by height is returned. If point is outside of the navmesh, returns triangle
+    /// with closest center. Returns None if navmesh is empty.
+    pub fn query_triangle(&mut self, point: Vector3<f32>) -> Option<usize> {
+        if self.triangles.is_empty() {
+            return None;
+        }
+
+        self.octree.point_query(point, &mut self.query_buffer);
+        if let Some(index) = self.query_triangle_in(point, self.query_buffer.iter().cloned()) {
+            return Some(index);
+        }
+        if let Some(index) = self.query_triangle_in(point, 0..self.triangles.len() as u32) {
+            return Some(index);
+        }
+
+        math::get_closest_point(self.triangle_graph.vertices(), point)
+    }
+
+    fn query_triangle_in<I: Iterator<Item = u32>>(
+        &self,
+        point: Vector3<f32>,
+        indices: I,
+    ) -> Option<usize> {
+        let point_2d = Vector2::new(point.x, point.z);
+        let mut closest = None;
+        let mut closest_distance = std::f32::MAX;
+        for index in indices {
+            let [a, b, c] = self.triangle_points(index as usize);
+            let bary = math::get_barycentric_coords_2d(
+                point_2d,
+                Vector2::new(a.x, a.z),
+                Vector2::new(b.x, b.z),
+                Vector2::new(c.x, c.z),
+            );
+            // Degenerated (vertical) triangles give NaN here and will be skipped.
+            let eps = -0.0001;
+            if bary.0 >= eps && bary.1 >= eps && bary.2 >= eps {
+                let height = math::barycentric_to_world(bary, a, b, c).y;
+                let distance = (point.y - height).abs();
+                if distance < closest_distance {
+                    closest_distance = distance;
+                    closest = Some(index as usize);
+                }
+            }
+        }
+        closest
+    }
+
+    fn triangle_points(&self, index: usize) -> [Vector3<f32>; 3] {
+        let triangle = &self.triangles[index];
+        let vertices = self.pathfinder.vertices();
+        [
+            vertices[triangle[0] as usize].position(),
+            vertices[triangle[1] as usize].position(),
+            vertices[triangle[2] as usize].position(),
+        ]
+    }
+
+    /// Returns closest point on the navmesh to given point. If the point is above or below the
+    /// navmesh (in projection on XZ plane), then the point on the surface of the navmesh is
+    /// returned, otherwise the point is moved to the closest edge of the navmesh. It is useful
+    /// to keep agents on the navmesh. Returns None if navmesh is empty.
+    pub fn project_point(&mut self, point: Vector3<f32>) -> Option<Vector3<f32>> {
+        if self.triangles.is_empty() {
+            return None;
+        }
+
+        self.octree.point_query(point, &mut self.query_buffer);
+        let inside = self
+            .query_triangle_in(point, self.query_buffer.iter().cloned())
+            .or_else(|| self.query_triangle_in(point, 0..self.triangles.len() as u32));
+        if let Some(index) = inside {
+            let [a, b, c] = self.triangle_points(index);
+            let bary = math::get_barycentric_coords_2d(
+                Vector2::new(point.x, point.z),
+                Vector2::new(a.x, a.z),
+                Vector2::new(b.x, b.z),
+                Vector2::new(c.x, c.z),
+            );
+            let height = math::barycentric_to_world(bary, a, b, c).y;
+            return Some(Vector3::new(point.x, height, point.z));
+        }
+
+        // Point is outside of the navmesh, search closest point on edges of triangles.
+        let mut closest = None;
+        let mut closest_distance = f32::MAX;
+        for index in 0..self.triangles.len() {
+            let points = self.triangle_points(index);
+            for i in 0..3 {
+                let candidate = closest_point_on_segment_xz(points[i], points[(i + 1) % 3], point);
+                let distance = (candidate - point).norm_squared();
+                if distance < closest_distance {
+                    closest_distance = distance;
+                    closest = Some(candidate);
+                }
+            }
+        }
+        closest
+    }
+
+    /// Adds new off-mesh link. Both ends of the link are attached to the closest triangles
+    /// of the navmesh, so they should lie on the navmesh. Returns false if the navmesh is
+    /// empty and the link was not added.
+    pub fn add_off_mesh_link(&mut self, mut link: OffMeshLink) -> bool {
+        match (
+            self.query_triangle(link.begin),
+            self.query_triangle(link.end),
+        ) {
+            (Some(begin_triangle), Some(end_triangle)) => {
+                link.begin_triangle = begin_triangle;
+                link.end_triangle = end_triangle;
+                // Link inside single triangle makes no sense and will confuse path finder.
+                if begin_triangle != end_triangle {
+                    if link.bidirectional {
+                        self.triangle_graph
+                            .link_bidirect(begin_triangle, end_triangle);
+                    } else {
+                        self.triangle_graph
+                            .link_unidirect(begin_triangle, end_triangle);
+                    }
+                }
+                self.off_mesh_links.push(link);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns reference to array of off-mesh links.
+    pub fn off_mesh_links(&self) -> &[OffMeshLink] {
+        &self.off_mesh_links
+    }
+
+    /// Returns points of shared edge of two triangles as (left, right) pair when looking from
+    /// the first triangle to the second.
+    fn portal(&self, from: usize, to: usize) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        let a = &self.triangles[from];
+        let b = &self.triangles[to];
+        for i in 0..3 {
+            let (p, q) = (a[i], a[(i + 1) % 3]);
+            if b.indices().contains(&p) && b.indices().contains(&q) {
+                let vertices = self.pathfinder.vertices();
+                let (p, q) = (
+                    vertices[p as usize].position(),
+                    vertices[q as usize].position(),
+                );
+                let center = self.triangle_graph.vertices()[from].position();
+                return if triangle_area_xz(center, p, q) < 0.0 {
+                    Some((q, p))
+                } else {
+                    Some((p, q))
+                };
+            }
+        }
+        None
+    }
+
+    /// Returns (begin, end) points of an off-mesh link which leads from one triangle to other.
+    fn off_mesh_link_between(
+        &self,
+        from: usize,
+        to: usize,
+    ) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        self.off_mesh_links.iter().find_map(|link| {
+            if link.begin_triangle == from && link.end_triangle == to {
+                Some((link.begin, link.end))
+            } else if link.bidirectional && link.begin_triangle == to && link.end_triangle == from {
+                Some((link.end, link.begin))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Searches shortest path between two arbitrary points on navmesh and fills `path` with
+    /// waypoints from `begin` to `end`. If there is no full path (end point is unreachable),
+    /// then partial path to the center of the closest reachable triangle is built and
+    /// [PathKind::Partial] is returned. When the path goes through an off-mesh link, it
+    /// contains begin and end points of the link as waypoints.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use rg3d::utils::navmesh::Navmesh;
+    /// use rg3d::core::algebra::Vector3;
+    /// use rg3d::utils::astar::{PathKind, PathError};
+    ///
+    /// fn move_agent(navmesh: &mut Navmesh, agent: Vector3<f32>, target: Vector3<f32>) -> Result<Vector3<f32>, PathError> {
+    ///     let mut path = Vec::new();
+    ///     navmesh.find_path(agent, target, &mut path)?;
+    ///     // First waypoint is the position of agent, move agent towards the second one.
+    ///     Ok(path.get(1).cloned().unwrap_or(agent))
+    /// }
+    /// ```
+    pub fn find_path(
+        &mut self,
+        begin: Vector3<f32>,
+        end: Vector3<f32>,
+        path: &mut Vec<Vector3<f32>>,
+    ) -> Result<PathKind, PathError> {
+        path.clear();
+
+        let (begin_triangle, end_triangle) =
+            match (self.query_triangle(begin), self.query_triangle(end)) {
+                (Some(begin_triangle), Some(end_triangle)) => (begin_triangle, end_triangle),
+                _ => return Ok(PathKind::Empty),
+            };
+
+        let mut corridor = std::mem::take(&mut self.corridor);
+        let kind =
+            self.triangle_graph
+                .build_indices(begin_triangle, end_triangle, &mut corridor)?;
+        corridor.reverse();
+
+        let end = match kind {
+            PathKind::Full => end,
+            PathKind::Partial => {
+                let last = *corridor.last().unwrap();
+                self.triangle_graph.vertices()[last].position()
+            }
+            PathKind::Empty => {
+                self.corridor = corridor;
+                return Ok(kind);
+            }
+        };
+
+        let mut portals = std::mem::take(&mut self.portals);
+        portals.clear();
+        portals.push((begin, begin));
+        for pair in corridor.windows(2) {
+            if let Some(portal) = self.portal(pair[0], pair[1]) {
+                portals.push(portal);
+            } else if let Some((link_begin, link_end)) =
+                self.off_mesh_link_between(pair[0], pair[1])
+            {
+                // Path is pulled separately before and after the link.
+                portals.push((link_begin, link_begin));
+                string_pull(&portals, path);
+                portals.clear();
+                portals.push((link_end, link_end));
+            }
+        }
+        portals.push((end, end));
+        string_pull(&portals, path);
+
+        self.portals = portals;
+        self.corridor = corridor;
+
+        Ok(kind)
+    }
+
     /// Returns reference to array of triangles.
     pub fn triangles(&self) -> &[TriangleDefinition] {
         &self.triangles
@@ -231,3 +671,127 @@ impl Navmesh {
         self.pathfinder.build(from, to, path)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{algebra::Vector3, math::TriangleDefinition},
+        utils::{
+            astar::PathKind,
+            navmesh::{Navmesh, OffMeshLink},
+        },
+    };
+
+    // Creates flat navmesh on XZ plane from given unit cells of 4x4 grid.
+    fn make_navmesh(cells: &[(u32, u32)]) -> Navmesh {
+        let mut vertices = Vec::new();
+        for z in 0..5 {
+            for x in 0..5 {
+                vertices.push(Vector3::new(x as f32, 0.0, z as f32));
+            }
+        }
+        let mut triangles = Vec::new();
+        for &(x, z) in cells {
+            let i = z * 5 + x;
+            triangles.push(TriangleDefinition([i, i + 5, i + 1]));
+            triangles.push(TriangleDefinition([i + 1, i + 5, i + 6]));
+        }
+        Navmesh::new(&triangles, &vertices)
+    }
+
+    #[test]
+    fn test_navmesh_find_path() {
+        let mut path = Vec::new();
+        assert_eq!(
+            Navmesh::default()
+                .find_path(Vector3::default(), Vector3::default(), &mut path)
+                .unwrap(),
+            PathKind::Empty
+        );
+
+        // L-shaped corridor.
+        let mut navmesh = make_navmesh(&[(0, 0), (1, 0), (2, 0), (2, 1), (2, 2)]);
+
+        let begin = Vector3::new(0.5, 0.0, 0.5);
+        let end = Vector3::new(2.5, 0.0, 0.5);
+        assert_eq!(
+            navmesh.find_path(begin, end, &mut path).unwrap(),
+            PathKind::Full
+        );
+        assert_eq!(path, vec![begin, end]);
+
+        // Path must go around the inner corner.
+        let end = Vector3::new(2.5, 0.0, 2.5);
+        assert_eq!(
+            navmesh.find_path(begin, end, &mut path).unwrap(),
+            PathKind::Full
+        );
+        assert_eq!(path, vec![begin, Vector3::new(2.0, 0.0, 1.0), end]);
+
+        navmesh.find_path(end, begin, &mut path).unwrap();
+        assert_eq!(path, vec![end, Vector3::new(2.0, 0.0, 1.0), begin]);
+
+        // Begin and end in the same triangle.
+        let end = Vector3::new(0.6, 0.0, 0.3);
+        navmesh.find_path(begin, end, &mut path).unwrap();
+        assert_eq!(path, vec![begin, end]);
+    }
+
+    #[test]
+    fn test_navmesh_off_mesh_links() {
+        // Two islands with a gap between them.
+        let mut navmesh = make_navmesh(&[(0, 0), (1, 0), (3, 0)]);
+        let begin = Vector3::new(0.5, 0.0, 0.5);
+        let end = Vector3::new(3.5, 0.0, 0.5);
+        let mut path = Vec::new();
+        assert_eq!(
+            navmesh.find_path(begin, end, &mut path).unwrap(),
+            PathKind::Partial
+        );
+
+        let link_begin = Vector3::new(1.9, 0.0, 0.5);
+        let link_end = Vector3::new(3.1, 0.0, 0.5);
+        assert!(navmesh.add_off_mesh_link(OffMeshLink::new(link_begin, link_end, false)));
+        assert_eq!(navmesh.off_mesh_links().len(), 1);
+
+        assert_eq!(
+            navmesh.find_path(begin, end, &mut path).unwrap(),
+            PathKind::Full
+        );
+        assert_eq!(path, vec![begin, link_begin, link_end, end]);
+
+        // Link is unidirectional, so there is no way back.
+        assert_eq!(
+            navmesh.find_path(end, begin, &mut path).unwrap(),
+            PathKind::Partial
+        );
+
+        assert!(navmesh.add_off_mesh_link(OffMeshLink::new(link_begin, link_end, true)));
+        assert_eq!(
+            navmesh.find_path(end, begin, &mut path).unwrap(),
+            PathKind::Full
+        );
+        assert_eq!(path, vec![end, link_end, link_begin, begin]);
+    }
+
+    #[test]
+    fn test_navmesh_project_point() {
+        let mut navmesh = make_navmesh(&[(0, 0), (1, 0)]);
+        assert_eq!(Navmesh::default().project_point(Vector3::default()), None);
+
+        // Point above the navmesh is moved on its surface.
+        assert_eq!(
+            navmesh.project_point(Vector3::new(0.5, 1.0, 0.5)),
+            Some(Vector3::new(0.5, 0.0, 0.5))
+        );
+        // Point outside is moved to the closest edge.
+        assert_eq!(
+            navmesh.project_point(Vector3::new(1.5, 0.0, -2.0)),
+            Some(Vector3::new(1.5, 0.0, 0.0))
+        );
+        assert_eq!(
+            navmesh.project_point(Vector3::new(3.0, 0.0, 2.0)),
+            Some(Vector3::new(2.0, 0.0, 1.0))
+        );
+    }
+}
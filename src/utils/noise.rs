@@ -0,0 +1,273 @@
+//! Gradient (Perlin) noise for procedural content - terrains, clouds, textures, etc. Noise
+//! is smooth pseudo-random function, same input and same seed always give same output.
+//!
+//! # Example
+//!
+//! ```
+//! use rg3d::utils::noise::{perlin_3d, Perlin};
+//!
+//! // Single sample of noise with default seed.
+//! let value = perlin_3d(0.3, 1.7, 2.5);
+//! assert!(value >= -1.0 && value <= 1.0);
+//!
+//! // Fractal noise with custom seed, 5 octaves each with twice bigger frequency and
+//! // twice smaller amplitude than previous.
+//! let noise = Perlin::new(123);
+//! let height = noise.fbm_2d(10.5, 3.25, 5, 2.0, 0.5);
+//! ```
+
+lazy_static! {
+    static ref DEFAULT_PERLIN: Perlin = Perlin::new(0);
+}
+
+/// Seeded Perlin noise generator, see module docs.
+#[derive(Clone)]
+pub struct Perlin {
+    // Permutation table is doubled to avoid wrapping of indices.
+    permutation: [u8; 512],
+}
+
+impl Default for Perlin {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn grad_2d(hash: u8, x: f32, y: f32) -> f32 {
+    // Eight directions: four diagonals and four axes.
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+fn grad_3d(hash: u8, x: f32, y: f32, z: f32) -> f32 {
+    // Twelve directions to the middles of edges of a cube, four of them are repeated
+    // to make 16 cases.
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+impl Perlin {
+    /// Creates new noise generator, different seeds give different noise.
+    pub fn new(seed: u64) -> Self {
+        let mut values = [0u8; 256];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = i as u8;
+        }
+
+        // Fisher-Yates shuffle driven by xorshift, it does not depend on external random
+        // generators so the same seed gives the same noise on every platform and version.
+        let mut state = seed
+            .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            .wrapping_add(0x2545_F491_4F6C_DD1D)
+            | 1;
+        for i in (1..values.len()).rev() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            values.swap(i, (state % (i as u64 + 1)) as usize);
+        }
+
+        let mut permutation = [0u8; 512];
+        for (i, value) in permutation.iter_mut().enumerate() {
+            *value = values[i & 255];
+        }
+        Self { permutation }
+    }
+
+    fn hash(&self, i: usize) -> usize {
+        self.permutation[i] as usize
+    }
+
+    /// Samples 2D noise at given point, result is in [-1; 1] range. Noise is zero at
+    /// every point with integer coordinates.
+    pub fn sample_2d(&self, x: f32, y: f32) -> f32 {
+        let (xf, yf) = (x.floor(), y.floor());
+        let (xi, yi) = ((xf as i32 & 255) as usize, (yf as i32 & 255) as usize);
+        let (x, y) = (x - xf, y - yf);
+        let (u, v) = (fade(x), fade(y));
+
+        let a = self.hash(xi) + yi;
+        let b = self.hash(xi + 1) + yi;
+        let p = &self.permutation;
+
+        lerp(
+            lerp(grad_2d(p[a], x, y), grad_2d(p[b], x - 1.0, y), u),
+            lerp(
+                grad_2d(p[a + 1], x, y - 1.0),
+                grad_2d(p[b + 1], x - 1.0, y - 1.0),
+                u,
+            ),
+            v,
+        )
+        .clamp(-1.0, 1.0)
+    }
+
+    /// Samples 3D noise at given point, result is in [-1; 1] range. Noise is zero at
+    /// every point with integer coordinates.
+    pub fn sample_3d(&self, x: f32, y: f32, z: f32) -> f32 {
+        let (xf, yf, zf) = (x.floor(), y.floor(), z.floor());
+        let xi = (xf as i32 & 255) as usize;
+        let yi = (yf as i32 & 255) as usize;
+        let zi = (zf as i32 & 255) as usize;
+        let (x, y, z) = (x - xf, y - yf, z - zf);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let a = self.hash(xi) + yi;
+        let aa = self.hash(a) + zi;
+        let ab = self.hash(a + 1) + zi;
+        let b = self.hash(xi + 1) + yi;
+        let ba = self.hash(b) + zi;
+        let bb = self.hash(b + 1) + zi;
+        let p = &self.permutation;
+
+        lerp(
+            lerp(
+                lerp(grad_3d(p[aa], x, y, z), grad_3d(p[ba], x - 1.0, y, z), u),
+                lerp(
+                    grad_3d(p[ab], x, y - 1.0, z),
+                    grad_3d(p[bb], x - 1.0, y - 1.0, z),
+                    u,
+                ),
+                v,
+            ),
+            lerp(
+                lerp(
+                    grad_3d(p[aa + 1], x, y, z - 1.0),
+                    grad_3d(p[ba + 1], x - 1.0, y, z - 1.0),
+                    u,
+                ),
+                lerp(
+                    grad_3d(p[ab + 1], x, y - 1.0, z - 1.0),
+                    grad_3d(p[bb + 1], x - 1.0, y - 1.0, z - 1.0),
+                    u,
+                ),
+                v,
+            ),
+            w,
+        )
+        .clamp(-1.0, 1.0)
+    }
+
+    /// Fractal Brownian motion - sum of `octaves` layers of 2D noise. Frequency of each
+    /// next layer is multiplied by `lacunarity` (usually 2.0) and its amplitude by `gain`
+    /// (usually 0.5). Result is normalized to [-1; 1] range.
+    pub fn fbm_2d(&self, x: f32, y: f32, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+        fbm(octaves, lacunarity, gain, |frequency| {
+            self.sample_2d(x * frequency, y * frequency)
+        })
+    }
+
+    /// Fractal Brownian motion - sum of `octaves` layers of 3D noise, see
+    /// [fbm_2d](Self::fbm_2d) for details.
+    pub fn fbm_3d(&self, x: f32, y: f32, z: f32, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+        fbm(octaves, lacunarity, gain, |frequency| {
+            self.sample_3d(x * frequency, y * frequency, z * frequency)
+        })
+    }
+}
+
+fn fbm<F: Fn(f32) -> f32>(octaves: u32, lacunarity: f32, gain: f32, sample: F) -> f32 {
+    let mut sum = 0.0;
+    let mut total_amplitude = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    for _ in 0..octaves {
+        sum += sample(frequency) * amplitude;
+        total_amplitude += amplitude;
+        amplitude *= gain;
+        frequency *= lacunarity;
+    }
+    if total_amplitude > 0.0 {
+        sum / total_amplitude
+    } else {
+        0.0
+    }
+}
+
+/// Samples 2D Perlin noise with default seed, see [Perlin::sample_2d].
+pub fn perlin_2d(x: f32, y: f32) -> f32 {
+    DEFAULT_PERLIN.sample_2d(x, y)
+}
+
+/// Samples 3D Perlin noise with default seed, see [Perlin::sample_3d].
+pub fn perlin_3d(x: f32, y: f32, z: f32) -> f32 {
+    DEFAULT_PERLIN.sample_3d(x, y, z)
+}
+
+/// Fractal 2D noise with default seed, see [Perlin::fbm_2d].
+pub fn fbm_2d(x: f32, y: f32, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+    DEFAULT_PERLIN.fbm_2d(x, y, octaves, lacunarity, gain)
+}
+
+/// Fractal 3D noise with default seed, see [Perlin::fbm_3d].
+pub fn fbm_3d(x: f32, y: f32, z: f32, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+    DEFAULT_PERLIN.fbm_3d(x, y, z, octaves, lacunarity, gain)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utils::noise::{fbm_3d, perlin_2d, perlin_3d, Perlin};
+
+    #[test]
+    fn test_perlin_range_and_lattice() {
+        let mut non_zero = 0;
+        for i in 0..2000 {
+            let x = i as f32 * 0.137 - 100.0;
+            let y = i as f32 * 0.291 + 7.0;
+            let z = i as f32 * 0.053;
+            let v2 = perlin_2d(x, y);
+            let v3 = perlin_3d(x, y, z);
+            let f = fbm_3d(x, y, z, 4, 2.0, 0.5);
+            for v in [v2, v3, f].iter() {
+                assert!(*v >= -1.0 && *v <= 1.0);
+            }
+            if v3.abs() > 0.01 {
+                non_zero += 1;
+            }
+        }
+        assert!(non_zero > 1000);
+
+        assert_eq!(perlin_2d(3.0, -5.0), 0.0);
+        assert_eq!(perlin_3d(1.0, 2.0, -3.0), 0.0);
+    }
+
+    #[test]
+    fn test_perlin_seed() {
+        let a = Perlin::new(1);
+        let b = Perlin::new(1);
+        let c = Perlin::new(2);
+        let mut differs = false;
+        for i in 0..100 {
+            let x = i as f32 * 0.37;
+            assert_eq!(a.sample_3d(x, 0.5, 0.25), b.sample_3d(x, 0.5, 0.25));
+            if a.sample_3d(x, 0.5, 0.25) != c.sample_3d(x, 0.5, 0.25) {
+                differs = true;
+            }
+        }
+        assert!(differs);
+    }
+}
@@ -0,0 +1,335 @@
+//! Contains all structures and methods to create and manage reverb zones.
+//!
+//! Reverb zone is a volume in space defined by a collider (usually a sensor) with a reverb preset
+//! attached. When listener enters a zone, parameters of a reverb effect of sound context are
+//! smoothly changed to the preset of the zone, so sounds in a cave will have long and muffled
+//! reflections, sounds in a small room - short ones, and so on. When listener is not inside of
+//! any zone, default preset is used.
+//!
+//! # Usage
+//!
+//! Reverb zones work with existing reverb effect, every source that should be affected by
+//! environment must be added as an input to the effect. [ReverbZones::update] should be called
+//! once per frame after physics update.
+//!
+//! ```no_run
+//! use rg3d::{
+//!     scene::{physics::Physics, ColliderHandle},
+//!     sound::{
+//!         context::Context,
+//!         effects::{reverb::{Reverb, ReverbPreset}, BaseEffect, Effect},
+//!     },
+//!     utils::reverb_zone::{ReverbZone, ReverbZones},
+//! };
+//!
+//! fn setup(context: &mut Context, cave_collider: ColliderHandle) -> ReverbZones {
+//!     let reverb = context.add_effect(Effect::Reverb(Reverb::new(BaseEffect::default())));
+//!     let mut zones = ReverbZones::new(reverb);
+//!     zones.add_zone(ReverbZone::new(cave_collider, ReverbPreset::cave()));
+//!     zones
+//! }
+//!
+//! fn update(zones: &mut ReverbZones, physics: &Physics, context: &mut Context, dt: f32) {
+//!     zones.update(physics, context, dt);
+//! }
+//! ```
+
+#![warn(missing_docs)]
+
+use crate::{
+    core::{
+        algebra::Vector3,
+        pool::{Handle, Pool},
+    },
+    scene::{physics::Physics, ColliderHandle},
+    sound::{
+        context::Context,
+        effects::{reverb::ReverbPreset, Effect},
+    },
+};
+use rapier3d::{geometry::ShapeType, na::Point3};
+
+/// A volume in space with reverb preset attached. See module docs.
+#[derive(Clone, Debug)]
+pub struct ReverbZone {
+    /// Collider that defines volume of the zone. Triangle meshes and height fields are
+    /// approximated by their bounding boxes.
+    pub collider: ColliderHandle,
+    /// Preset that will be used when listener is inside of the zone.
+    pub preset: ReverbPreset,
+    /// Priority of the zone, it is used to select a zone when listener is inside of multiple
+    /// zones at once, zone with highest priority wins. For example a small room inside of a
+    /// cave should have higher priority than the cave.
+    pub priority: i32,
+}
+
+impl ReverbZone {
+    /// Creates new reverb zone with zero priority.
+    pub fn new(collider: ColliderHandle, preset: ReverbPreset) -> Self {
+        Self {
+            collider,
+            preset,
+            priority: 0,
+        }
+    }
+
+    /// Sets priority of the zone.
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    fn contains(&self, physics: &Physics, point: Vector3<f32>) -> bool {
+        if let Some(collider) = physics.colliders.get(self.collider.into()) {
+            let shape = collider.shape();
+            let point = Point3::from(point);
+            match shape.shape_type() {
+                // Rapier can't do point queries for these shapes.
+                ShapeType::Trimesh | ShapeType::HeightField => shape
+                    .compute_aabb(collider.position())
+                    .contains_local_point(&point),
+                _ => shape.contains_point(collider.position(), &point),
+            }
+        } else {
+            false
+        }
+    }
+}
+
+/// A set of reverb zones that controls a reverb effect. See module docs.
+pub struct ReverbZones {
+    zones: Pool<ReverbZone>,
+    reverb: Handle<Effect>,
+    default_preset: ReverbPreset,
+    transition_time: f32,
+    active_zone: Handle<ReverbZone>,
+    source_preset: ReverbPreset,
+    target_preset: ReverbPreset,
+    current_preset: Option<ReverbPreset>,
+    transition: f32,
+}
+
+impl ReverbZones {
+    /// Creates new set of reverb zones which will control given reverb effect. Default preset
+    /// is [ReverbPreset::outdoors] and transition time is 1 second.
+    pub fn new(reverb: Handle<Effect>) -> Self {
+        Self {
+            zones: Default::default(),
+            reverb,
+            default_preset: ReverbPreset::outdoors(),
+            transition_time: 1.0,
+            active_zone: Handle::NONE,
+            source_preset: ReverbPreset::outdoors(),
+            target_preset: ReverbPreset::outdoors(),
+            current_preset: None,
+            transition: 1.0,
+        }
+    }
+
+    /// Adds new zone and returns its handle.
+    pub fn add_zone(&mut self, zone: ReverbZone) -> Handle<ReverbZone> {
+        self.zones.spawn(zone)
+    }
+
+    /// Removes a zone. If listener is inside of the zone, reverb will be blended to preset of
+    /// other zone or to default preset.
+    pub fn remove_zone(&mut self, handle: Handle<ReverbZone>) -> ReverbZone {
+        self.zones.free(handle)
+    }
+
+    /// Returns shared reference to a zone.
+    pub fn zone(&self, handle: Handle<ReverbZone>) -> &ReverbZone {
+        self.zones.borrow(handle)
+    }
+
+    /// Returns mutable reference to a zone.
+    pub fn zone_mut(&mut self, handle: Handle<ReverbZone>) -> &mut ReverbZone {
+        self.zones.borrow_mut(handle)
+    }
+
+    /// Returns iterator over every zone.
+    pub fn zones(&self) -> impl Iterator<Item = &ReverbZone> {
+        self.zones.iter()
+    }
+
+    /// Sets preset that is used when listener is not inside of any zone.
+    pub fn set_default_preset(&mut self, preset: ReverbPreset) {
+        self.default_preset = preset;
+    }
+
+    /// Returns preset that is used when listener is not inside of any zone.
+    pub fn default_preset(&self) -> ReverbPreset {
+        self.default_preset
+    }
+
+    /// Sets time (in seconds) of transition from one preset to another.
+    pub fn set_transition_time(&mut self, time: f32) {
+        self.transition_time = time.max(0.0);
+    }
+
+    /// Returns time (in seconds) of transition from one preset to another.
+    pub fn transition_time(&self) -> f32 {
+        self.transition_time
+    }
+
+    /// Returns handle of a zone in which listener is, or NONE if listener is not inside of
+    /// any zone.
+    pub fn active_zone(&self) -> Handle<ReverbZone> {
+        self.active_zone
+    }
+
+    /// Returns current (possibly blended) preset.
+    pub fn current_preset(&self) -> ReverbPreset {
+        self.current_preset.unwrap_or(self.default_preset)
+    }
+
+    /// Returns handle of reverb effect that is controlled by the zones.
+    pub fn reverb(&self) -> Handle<Effect> {
+        self.reverb
+    }
+
+    /// Selects active zone using position of listener of given sound context and blends
+    /// parameters of reverb effect to the preset of the zone. Reverb effect must be a
+    /// `Effect::Reverb`, otherwise the method does nothing.
+    pub fn update(&mut self, physics: &Physics, context: &mut Context, dt: f32) {
+        let listener = context.listener().position();
+
+        if let Some(preset) = self.blend(physics, listener, dt) {
+            if let Effect::Reverb(reverb) = context.effect_mut(self.reverb) {
+                reverb.set_preset(&preset);
+            }
+        }
+    }
+
+    /// Returns new preset if it has changed.
+    fn blend(
+        &mut self,
+        physics: &Physics,
+        listener: Vector3<f32>,
+        dt: f32,
+    ) -> Option<ReverbPreset> {
+        let mut active_zone = Handle::NONE;
+        let mut priority = std::i32::MIN;
+        for (handle, zone) in self.zones.pair_iter() {
+            if (active_zone.is_none() || zone.priority > priority)
+                && zone.contains(physics, listener)
+            {
+                active_zone = handle;
+                priority = zone.priority;
+            }
+        }
+
+        let target_preset = if active_zone.is_some() {
+            self.zones.borrow(active_zone).preset
+        } else {
+            self.default_preset
+        };
+
+        let current_preset = match self.current_preset {
+            // First update - apply preset immediately.
+            None => {
+                self.current_preset = Some(target_preset);
+                self.active_zone = active_zone;
+                self.target_preset = target_preset;
+                self.transition = 1.0;
+                return self.current_preset;
+            }
+            Some(current_preset) => current_preset,
+        };
+
+        if active_zone != self.active_zone || target_preset != self.target_preset {
+            // Start new transition from current, possibly blended, state.
+            self.active_zone = active_zone;
+            self.source_preset = current_preset;
+            self.target_preset = target_preset;
+            self.transition = 0.0;
+        }
+
+        if self.transition >= 1.0 {
+            return None;
+        }
+
+        self.transition = if self.transition_time > 0.0 {
+            (self.transition + dt / self.transition_time).min(1.0)
+        } else {
+            1.0
+        };
+
+        self.current_preset = Some(
+            self.source_preset
+                .lerp(&self.target_preset, self.transition),
+        );
+        self.current_preset
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::algebra::Vector3,
+        scene::physics::Physics,
+        sound::effects::reverb::ReverbPreset,
+        utils::reverb_zone::{ReverbZone, ReverbZones},
+    };
+    use rapier3d::{dynamics::RigidBodyBuilder, geometry::ColliderBuilder};
+
+    #[test]
+    fn test_reverb_zones() {
+        let mut physics = Physics::new();
+        let body = physics.add_body(RigidBodyBuilder::new_static().build());
+        let cave = physics.add_collider(
+            ColliderBuilder::cuboid(10.0, 10.0, 10.0)
+                .sensor(true)
+                .build(),
+            body,
+        );
+        let room = physics.add_collider(
+            ColliderBuilder::cuboid(1.0, 1.0, 1.0).sensor(true).build(),
+            body,
+        );
+
+        let mut zones = ReverbZones::new(Default::default());
+        zones.set_transition_time(1.0);
+        let cave = zones.add_zone(ReverbZone::new(cave, ReverbPreset::cave()));
+        let room =
+            zones.add_zone(ReverbZone::new(room, ReverbPreset::small_room()).with_priority(1));
+
+        let outside = Vector3::new(20.0, 0.0, 0.0);
+        let in_cave = Vector3::new(5.0, 0.0, 0.0);
+        let in_room = Vector3::new(0.0, 0.0, 0.0);
+
+        // First update applies preset without blending.
+        assert_eq!(
+            zones.blend(&physics, outside, 0.1),
+            Some(ReverbPreset::outdoors())
+        );
+        assert!(zones.active_zone().is_none());
+        assert_eq!(zones.blend(&physics, outside, 0.1), None);
+
+        // Halfway blending.
+        let preset = zones.blend(&physics, in_cave, 0.5).unwrap();
+        assert_eq!(zones.active_zone(), cave);
+        assert_eq!(
+            preset,
+            ReverbPreset::outdoors().lerp(&ReverbPreset::cave(), 0.5)
+        );
+
+        // Entering a room inside of the cave, blending starts from current state.
+        let preset = zones.blend(&physics, in_room, 0.25).unwrap();
+        assert_eq!(zones.active_zone(), room);
+        assert_eq!(
+            preset,
+            ReverbPreset::outdoors()
+                .lerp(&ReverbPreset::cave(), 0.5)
+                .lerp(&ReverbPreset::small_room(), 0.25)
+        );
+
+        // Transition finishes and stays.
+        assert_eq!(
+            zones.blend(&physics, in_room, 2.0),
+            Some(ReverbPreset::small_room())
+        );
+        assert_eq!(zones.blend(&physics, in_room, 0.1), None);
+        assert_eq!(zones.current_preset(), ReverbPreset::small_room());
+    }
+}
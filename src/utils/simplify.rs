@@ -0,0 +1,503 @@
+//! Mesh simplification (decimation). Used to generate lower levels of details for meshes.
+//!
+//! # Overview
+//!
+//! Simplification is done by collapsing edges one by one in order of an error which a collapse
+//! brings, error is measured using quadric error metrics (Garland and Heckbert): each vertex
+//! accumulates squared distances to planes of triangles around it, so vertices on flat areas
+//! are removed first while sharp features are kept as long as possible.
+//!
+//! Each collapse moves a vertex into one of its neighbours (half-edge collapse), so positions and
+//! attributes (normals, texture coordinates, bone weights, etc.) of remaining vertices are never
+//! changed and there is no need to interpolate them. Collapses which flip triangles or make
+//! the mesh non-manifold are rejected.
+//!
+//! # Seams and borders
+//!
+//! Vertices at attribute seams (several vertices with the same position but different normals
+//! or texture coordinates) and vertices at open borders of a mesh are never removed, so texture
+//! mapping does not break and holes do not appear. This limits amount of reduction for meshes
+//! with many seams, such meshes may have more triangles than requested.
+
+use crate::{
+    core::{algebra::Vector3, math::TriangleDefinition},
+    renderer::surface::SurfaceSharedData,
+};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+// Symmetric 4x4 matrix of plane equation products.
+#[derive(Copy, Clone, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn from_plane(normal: Vector3<f64>, d: f64, weight: f64) -> Self {
+        let (a, b, c) = (normal.x, normal.y, normal.z);
+        Self([
+            a * a * weight,
+            a * b * weight,
+            a * c * weight,
+            a * d * weight,
+            b * b * weight,
+            b * c * weight,
+            b * d * weight,
+            c * c * weight,
+            c * d * weight,
+            d * d * weight,
+        ])
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+            *a += *b;
+        }
+    }
+
+    // Sum of weighted squared distances from the point to the planes.
+    fn error(&self, p: Vector3<f64>) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (p.x, p.y, p.z);
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+struct Collapse {
+    error: f64,
+    from: u32,
+    to: u32,
+    version: u32,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed to make max-heap pop collapses with smallest error first.
+        other
+            .error
+            .partial_cmp(&self.error)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+struct Simplifier {
+    positions: Vec<Vector3<f64>>,
+    // Index of group of vertices with the same position for each vertex.
+    groups: Vec<u32>,
+    locked: Vec<bool>,
+    quadrics: Vec<Quadric>,
+    // Triangles around each group of vertices.
+    group_triangles: Vec<Vec<usize>>,
+    triangles: Vec<[u32; 3]>,
+    alive: Vec<bool>,
+    alive_count: usize,
+    versions: Vec<u32>,
+    heap: BinaryHeap<Collapse>,
+}
+
+fn triangle_normal(a: Vector3<f64>, b: Vector3<f64>, c: Vector3<f64>) -> Vector3<f64> {
+    (b - a).cross(&(c - a))
+}
+
+impl Simplifier {
+    fn new(data: &SurfaceSharedData) -> Self {
+        let positions = data
+            .get_vertices()
+            .iter()
+            .map(|v| {
+                Vector3::new(
+                    v.position.x as f64,
+                    v.position.y as f64,
+                    v.position.z as f64,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut group_map = HashMap::new();
+        let groups = data
+            .get_vertices()
+            .iter()
+            .map(|v| {
+                let key = [
+                    v.position.x.to_bits(),
+                    v.position.y.to_bits(),
+                    v.position.z.to_bits(),
+                ];
+                let next = group_map.len() as u32;
+                *group_map.entry(key).or_insert(next)
+            })
+            .collect::<Vec<_>>();
+        let group_count = group_map.len();
+
+        // Degenerated triangles are invisible and will only confuse the simplifier.
+        let triangles = data
+            .triangles()
+            .iter()
+            .map(|t| [t[0], t[1], t[2]])
+            .filter(|t| {
+                let [a, b, c] = [
+                    groups[t[0] as usize],
+                    groups[t[1] as usize],
+                    groups[t[2] as usize],
+                ];
+                a != b && b != c && c != a
+            })
+            .collect::<Vec<_>>();
+
+        let mut locked = vec![false; group_count];
+        let mut wedges = vec![0u32; group_count];
+        for &group in groups.iter() {
+            wedges[group as usize] += 1;
+        }
+        for (locked, &wedges) in locked.iter_mut().zip(wedges.iter()) {
+            *locked = wedges > 1;
+        }
+
+        let mut edges = HashMap::new();
+        let mut quadrics = vec![Quadric::default(); group_count];
+        let mut group_triangles = vec![Vec::new(); group_count];
+        for (index, triangle) in triangles.iter().enumerate() {
+            for i in 0..3 {
+                let a = groups[triangle[i] as usize];
+                let b = groups[triangle[(i + 1) % 3] as usize];
+                *edges.entry((a.min(b), a.max(b))).or_insert(0u32) += 1;
+                group_triangles[a as usize].push(index);
+            }
+
+            let [a, b, c] = [
+                positions[triangle[0] as usize],
+                positions[triangle[1] as usize],
+                positions[triangle[2] as usize],
+            ];
+            let normal = triangle_normal(a, b, c);
+            let double_area = normal.norm();
+            if double_area > f64::EPSILON {
+                let normal = normal.scale(1.0 / double_area);
+                let quadric = Quadric::from_plane(normal, -normal.dot(&a), double_area * 0.5);
+                for &vertex in triangle.iter() {
+                    quadrics[groups[vertex as usize] as usize].add(&quadric);
+                }
+            }
+        }
+        // Edges of open borders (and non-manifold edges) are not shared by exactly two
+        // triangles.
+        for (&(a, b), &count) in edges.iter() {
+            if count != 2 {
+                locked[a as usize] = true;
+                locked[b as usize] = true;
+            }
+        }
+
+        let alive_count = triangles.len();
+        let mut simplifier = Self {
+            versions: vec![0; positions.len()],
+            positions,
+            groups,
+            locked,
+            quadrics,
+            group_triangles,
+            alive: vec![true; alive_count],
+            alive_count,
+            triangles,
+            heap: Default::default(),
+        };
+        for index in 0..simplifier.triangles.len() {
+            let triangle = simplifier.triangles[index];
+            for i in 0..3 {
+                simplifier.push_collapse(triangle[i], triangle[(i + 1) % 3]);
+                simplifier.push_collapse(triangle[(i + 1) % 3], triangle[i]);
+            }
+        }
+        simplifier
+    }
+
+    fn group(&self, vertex: u32) -> usize {
+        self.groups[vertex as usize] as usize
+    }
+
+    fn push_collapse(&mut self, from: u32, to: u32) {
+        let group = self.group(from);
+        if !self.locked[group] {
+            self.heap.push(Collapse {
+                error: self.quadrics[group].error(self.positions[to as usize]),
+                from,
+                to,
+                version: self.versions[from as usize],
+            });
+        }
+    }
+
+    fn neighbours(&self, group: usize) -> HashSet<usize> {
+        self.group_triangles[group]
+            .iter()
+            .flat_map(|&t| self.triangles[t].iter())
+            .map(|&v| self.group(v))
+            .filter(|&g| g != group)
+            .collect()
+    }
+
+    fn is_valid(&self, from: u32, to: u32) -> bool {
+        let (from_group, to_group) = (self.group(from), self.group(to));
+
+        // Triangles sharing the edge will be removed.
+        let shared = self.group_triangles[from_group]
+            .iter()
+            .filter(|&&t| self.triangles[t].iter().any(|&v| self.group(v) == to_group))
+            .count();
+        if shared == 0 {
+            // Edge does not exist anymore.
+            return false;
+        }
+
+        // Link condition - vertices of the edge must not have other common neighbours than
+        // opposite vertices of removed triangles, otherwise the mesh becomes non-manifold.
+        let common = self
+            .neighbours(from_group)
+            .intersection(&self.neighbours(to_group))
+            .count();
+        if common != shared {
+            return false;
+        }
+
+        // Remaining triangles must not flip.
+        let new_position = self.positions[to as usize];
+        for &t in self.group_triangles[from_group].iter() {
+            let triangle = self.triangles[t];
+            if triangle.iter().any(|&v| self.group(v) == to_group) {
+                continue;
+            }
+            let old = |i: usize| self.positions[triangle[i] as usize];
+            let new = |i: usize| {
+                if triangle[i] == from {
+                    new_position
+                } else {
+                    old(i)
+                }
+            };
+            let old_normal = triangle_normal(old(0), old(1), old(2));
+            let new_normal = triangle_normal(new(0), new(1), new(2));
+            if new_normal.dot(&old_normal) <= 0.0 {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn collapse(&mut self, from: u32, to: u32) {
+        let (from_group, to_group) = (self.group(from), self.group(to));
+
+        for t in std::mem::take(&mut self.group_triangles[from_group]) {
+            let triangle = self.triangles[t];
+            if triangle.iter().any(|&v| self.group(v) == to_group) {
+                self.alive[t] = false;
+                self.alive_count -= 1;
+                for &v in triangle.iter() {
+                    let group = self.group(v);
+                    self.group_triangles[group].retain(|&other| other != t);
+                }
+            } else {
+                for v in self.triangles[t].iter_mut() {
+                    if *v == from {
+                        *v = to;
+                    }
+                }
+                self.group_triangles[to_group].push(t);
+            }
+        }
+
+        let quadric = self.quadrics[from_group];
+        self.quadrics[to_group].add(&quadric);
+
+        // Error of collapses of the target vertex has changed, old collapses are discarded
+        // by version.
+        self.versions[to as usize] += 1;
+        for t in self.group_triangles[to_group].clone() {
+            let triangle = self.triangles[t];
+            for &v in triangle.iter() {
+                if v != to {
+                    self.push_collapse(to, v);
+                    self.push_collapse(v, to);
+                }
+            }
+        }
+    }
+
+    fn run(&mut self, target_count: usize) {
+        while self.alive_count > target_count {
+            let collapse = match self.heap.pop() {
+                Some(collapse) => collapse,
+                None => break,
+            };
+            if collapse.version == self.versions[collapse.from as usize]
+                && !self.group_triangles[self.group(collapse.from)].is_empty()
+                && self.is_valid(collapse.from, collapse.to)
+            {
+                self.collapse(collapse.from, collapse.to);
+            }
+        }
+    }
+}
+
+/// Creates simplified copy of given surface data which has approximately `target_ratio` of
+/// triangles of the source data (0.5 means half of triangles). See module docs for more info.
+pub fn simplify(data: &SurfaceSharedData, target_ratio: f32) -> SurfaceSharedData {
+    let target_ratio = target_ratio.clamp(0.0, 1.0);
+    let target_count = (data.triangles().len() as f32 * target_ratio).ceil() as usize;
+
+    let mut simplifier = Simplifier::new(data);
+    simplifier.run(target_count);
+
+    // Remove unused vertices.
+    let source = data.get_vertices();
+    let mut remap = vec![None; source.len()];
+    let mut vertices = Vec::new();
+    let mut triangles = Vec::with_capacity(simplifier.alive_count);
+    for (triangle, _) in simplifier
+        .triangles
+        .iter()
+        .zip(simplifier.alive.iter())
+        .filter(|(_, &alive)| alive)
+    {
+        let mut indices = [0; 3];
+        for (index, &vertex) in indices.iter_mut().zip(triangle.iter()) {
+            *index = *remap[vertex as usize].get_or_insert_with(|| {
+                vertices.push(source[vertex as usize]);
+                vertices.len() as u32 - 1
+            });
+        }
+        triangles.push(TriangleDefinition(indices));
+    }
+
+    SurfaceSharedData::new(vertices, triangles, true)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            algebra::{Matrix4, Vector3},
+            math::TriangleDefinition,
+        },
+        renderer::surface::SurfaceSharedData,
+        utils::simplify::simplify,
+    };
+
+    fn area(data: &SurfaceSharedData) -> f32 {
+        let vertices = data.get_vertices();
+        data.triangles()
+            .iter()
+            .map(|t| {
+                let [a, b, c] = [
+                    vertices[t[0] as usize].position,
+                    vertices[t[1] as usize].position,
+                    vertices[t[2] as usize].position,
+                ];
+                (b - a).cross(&(c - a)).norm() * 0.5
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_simplify_plane() {
+        let data = SurfaceSharedData::make_plane(16, 16, Matrix4::new_scaling(2.0));
+        let simplified = simplify(&data, 0.25);
+
+        assert!(simplified.triangles().len() <= data.triangles().len() / 4);
+        // Plane must stay flat, without holes and flipped triangles.
+        assert!((area(&simplified) - 4.0).abs() < 1.0e-4);
+        for t in simplified.triangles() {
+            let vertices = simplified.get_vertices();
+            let [a, b, c] = [
+                vertices[t[0] as usize].position,
+                vertices[t[1] as usize].position,
+                vertices[t[2] as usize].position,
+            ];
+            assert!((b - a).cross(&(c - a)).y > 0.0);
+        }
+        // Border is kept.
+        let border = |data: &SurfaceSharedData| {
+            data.get_vertices()
+                .iter()
+                .filter(|v| v.position.x.abs() == 1.0 || v.position.z.abs() == 1.0)
+                .count()
+        };
+        assert_eq!(border(&simplified), border(&data));
+    }
+
+    #[test]
+    fn test_simplify_keeps_seams() {
+        // Two quads with different texture coordinates on shared edge.
+        let mut vertices = SurfaceSharedData::make_plane(4, 4, Matrix4::identity())
+            .get_vertices()
+            .to_vec();
+        let offset = vertices.len() as u32;
+        let mut other = SurfaceSharedData::make_plane(
+            4,
+            4,
+            Matrix4::new_translation(&Vector3::new(1.0, 0.0, 0.0)),
+        )
+        .get_vertices()
+        .to_vec();
+        for v in other.iter_mut() {
+            v.tex_coord.x += 10.0;
+        }
+        vertices.extend_from_slice(&other);
+        let mut triangles = SurfaceSharedData::make_plane(4, 4, Matrix4::identity())
+            .triangles()
+            .to_vec();
+        for t in SurfaceSharedData::make_plane(4, 4, Matrix4::identity()).triangles() {
+            triangles.push(TriangleDefinition([
+                t[0] + offset,
+                t[1] + offset,
+                t[2] + offset,
+            ]));
+        }
+        let data = SurfaceSharedData::new(vertices, triangles, true);
+
+        let simplified = simplify(&data, 0.0);
+        assert!(simplified.triangles().len() < data.triangles().len());
+        let seam = simplified
+            .get_vertices()
+            .iter()
+            .filter(|v| v.position.x == 0.5)
+            .count();
+        // Five vertices on each side of the seam.
+        assert_eq!(seam, 10);
+        assert!((area(&simplified) - 2.0).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn test_simplify_sphere() {
+        let data = SurfaceSharedData::make_sphere(32, 32, 1.0);
+        let simplified = simplify(&data, 0.3);
+
+        assert!(simplified.triangles().len() < data.triangles().len() / 2);
+        // Shape is preserved - area of simplified sphere is close to the source one.
+        let (source_area, simplified_area) = (area(&data), area(&simplified));
+        assert!((source_area - simplified_area).abs() / source_area < 0.1);
+    }
+}
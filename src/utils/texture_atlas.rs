@@ -0,0 +1,409 @@
+//! Texture atlas packs many small images into one big texture, so a lot of sprites or UI
+//! elements can be drawn using single texture, this reduces amount of texture binds and
+//! draw calls.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{resource::texture::Texture, utils::texture_atlas::TextureAtlasBuilder};
+//!
+//! fn make_atlas(coin: Texture, heart: Texture) {
+//!     let atlas = TextureAtlasBuilder::new()
+//!         .with_texture("coin", coin)
+//!         .with_texture("heart", heart)
+//!         .build()
+//!         .unwrap();
+//!
+//!     // Use atlas.texture() as texture of a sprite or image widget and tex coords of an
+//!     // image to draw only that image.
+//!     let coin_tex_coords = atlas.tex_coords("coin").unwrap();
+//! }
+//! ```
+
+use crate::{
+    core::{algebra::Vector2, math::Rect, rectpack::RectPacker},
+    resource::texture::{Texture, TextureKind, TexturePixelKind, TextureState},
+};
+use std::collections::HashMap;
+
+/// Maximum size of atlas side in pixels by default.
+pub const DEFAULT_MAX_ATLAS_SIZE: u32 = 4096;
+
+/// An error that may occur during atlas packing.
+#[derive(Debug)]
+pub enum TextureAtlasError {
+    /// Texture is not loaded yet or failed to load.
+    TextureNotLoaded(String),
+    /// Pixel format of a texture cannot be converted to RGBA8 (compressed textures).
+    UnsupportedPixelKind {
+        /// Name of image.
+        name: String,
+        /// Pixel format of image.
+        pixel_kind: TexturePixelKind,
+    },
+    /// Amount of pixels does not match size of an image, or image is not a rectangle.
+    InvalidImage(String),
+    /// There are two or more images with same name.
+    DuplicateName(String),
+    /// Images do not fit into atlas of maximum size.
+    NotEnoughSpace {
+        /// Maximum size of atlas side in pixels.
+        max_size: u32,
+    },
+}
+
+enum ImageSource {
+    Pixels {
+        width: u32,
+        height: u32,
+        rgba: Vec<u8>,
+    },
+    Texture(Texture),
+}
+
+struct AtlasImage {
+    name: String,
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+fn convert_to_rgba8(pixel_kind: TexturePixelKind, bytes: &[u8]) -> Option<Vec<u8>> {
+    // 16-bit channels are little-endian, only high byte is taken.
+    let rgba = match pixel_kind {
+        TexturePixelKind::R8 => bytes.iter().flat_map(|&l| vec![l, l, l, 255]).collect(),
+        TexturePixelKind::RG8 => bytes
+            .chunks_exact(2)
+            .flat_map(|p| vec![p[0], p[0], p[0], p[1]])
+            .collect(),
+        TexturePixelKind::RGB8 => bytes
+            .chunks_exact(3)
+            .flat_map(|p| vec![p[0], p[1], p[2], 255])
+            .collect(),
+        TexturePixelKind::RGBA8 => bytes.to_vec(),
+        TexturePixelKind::BGR8 => bytes
+            .chunks_exact(3)
+            .flat_map(|p| vec![p[2], p[1], p[0], 255])
+            .collect(),
+        TexturePixelKind::BGRA8 => bytes
+            .chunks_exact(4)
+            .flat_map(|p| vec![p[2], p[1], p[0], p[3]])
+            .collect(),
+        TexturePixelKind::R16 => bytes
+            .chunks_exact(2)
+            .flat_map(|p| vec![p[1], p[1], p[1], 255])
+            .collect(),
+        TexturePixelKind::RG16 => bytes
+            .chunks_exact(4)
+            .flat_map(|p| vec![p[1], p[1], p[1], p[3]])
+            .collect(),
+        TexturePixelKind::RGB16 => bytes
+            .chunks_exact(6)
+            .flat_map(|p| vec![p[1], p[3], p[5], 255])
+            .collect(),
+        TexturePixelKind::RGBA16 => bytes
+            .chunks_exact(8)
+            .flat_map(|p| vec![p[1], p[3], p[5], p[7]])
+            .collect(),
+        TexturePixelKind::DXT1RGB
+        | TexturePixelKind::DXT1RGBA
+        | TexturePixelKind::DXT3RGBA
+        | TexturePixelKind::DXT5RGBA => return None,
+    };
+    Some(rgba)
+}
+
+/// Packed texture atlas, see module docs.
+pub struct TextureAtlas {
+    texture: Texture,
+    size: u32,
+    rects: HashMap<String, Rect<u32>>,
+}
+
+impl TextureAtlas {
+    /// Returns texture of the atlas.
+    pub fn texture(&self) -> Texture {
+        self.texture.clone()
+    }
+
+    /// Returns size of atlas side in pixels, atlas is always square.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Returns bounds of an image in atlas in pixels.
+    pub fn pixel_rect(&self, name: &str) -> Option<Rect<u32>> {
+        self.rects.get(name).cloned()
+    }
+
+    /// Returns bounds of an image in atlas in texture coordinates ([0; 1] range).
+    pub fn uv_rect(&self, name: &str) -> Option<Rect<f32>> {
+        let k = 1.0 / self.size as f32;
+        self.rects.get(name).map(|r| {
+            Rect::new(
+                r.x() as f32 * k,
+                r.y() as f32 * k,
+                r.w() as f32 * k,
+                r.h() as f32 * k,
+            )
+        })
+    }
+
+    /// Returns texture coordinates of corners of an image in atlas in order: left-top,
+    /// right-top, right-bottom, left-bottom. This order is used by UI drawing context.
+    pub fn tex_coords(&self, name: &str) -> Option<[Vector2<f32>; 4]> {
+        self.uv_rect(name).map(|r| {
+            [
+                Vector2::new(r.x(), r.y()),
+                Vector2::new(r.x() + r.w(), r.y()),
+                Vector2::new(r.x() + r.w(), r.y() + r.h()),
+                Vector2::new(r.x(), r.y() + r.h()),
+            ]
+        })
+    }
+
+    /// Returns iterator over names of all images in the atlas.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.rects.keys().map(|name| name.as_str())
+    }
+}
+
+/// Texture atlas builder collects images and packs them into atlas.
+pub struct TextureAtlasBuilder {
+    images: Vec<(String, ImageSource)>,
+    padding: u32,
+    max_size: u32,
+}
+
+impl Default for TextureAtlasBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextureAtlasBuilder {
+    /// Creates new empty atlas builder.
+    pub fn new() -> Self {
+        Self {
+            images: Default::default(),
+            padding: 2,
+            max_size: DEFAULT_MAX_ATLAS_SIZE,
+        }
+    }
+
+    /// Sets amount of empty pixels between images, it prevents colors of neighbour images
+    /// from bleeding when atlas is filtered. Default is 2.
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Sets maximum size of atlas side in pixels. Default is 4096.
+    pub fn with_max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Adds image from raw RGBA8 pixels, `rgba` must contain exactly `width * height * 4`
+    /// bytes.
+    pub fn with_image(mut self, name: &str, width: u32, height: u32, rgba: Vec<u8>) -> Self {
+        self.images.push((
+            name.to_owned(),
+            ImageSource::Pixels {
+                width,
+                height,
+                rgba,
+            },
+        ));
+        self
+    }
+
+    /// Adds image from a texture, the texture must be loaded when `build` is called.
+    pub fn with_texture(mut self, name: &str, texture: Texture) -> Self {
+        self.images
+            .push((name.to_owned(), ImageSource::Texture(texture)));
+        self
+    }
+
+    fn collect_images(self) -> Result<Vec<AtlasImage>, TextureAtlasError> {
+        let mut images: Vec<AtlasImage> = Vec::with_capacity(self.images.len());
+        for (name, source) in self.images {
+            if images.iter().any(|i| i.name == name) {
+                return Err(TextureAtlasError::DuplicateName(name));
+            }
+
+            let (width, height, rgba) = match source {
+                ImageSource::Pixels {
+                    width,
+                    height,
+                    rgba,
+                } => (width, height, rgba),
+                ImageSource::Texture(texture) => {
+                    let state = texture.state();
+                    let data = match &*state {
+                        TextureState::Ok(data) => data,
+                        _ => return Err(TextureAtlasError::TextureNotLoaded(name)),
+                    };
+                    let (width, height) = match data.kind {
+                        TextureKind::Rectangle { width, height } => (width, height),
+                        _ => return Err(TextureAtlasError::InvalidImage(name)),
+                    };
+                    match convert_to_rgba8(data.pixel_kind, &data.bytes) {
+                        Some(rgba) => (width, height, rgba),
+                        None => {
+                            return Err(TextureAtlasError::UnsupportedPixelKind {
+                                pixel_kind: data.pixel_kind,
+                                name,
+                            })
+                        }
+                    }
+                }
+            };
+
+            if rgba.len() != (width * height * 4) as usize {
+                return Err(TextureAtlasError::InvalidImage(name));
+            }
+
+            images.push(AtlasImage {
+                name,
+                width,
+                height,
+                rgba,
+            });
+        }
+        Ok(images)
+    }
+
+    /// Packs every image into atlas. Size of atlas is the smallest power of two that is able
+    /// to fit all images, but not larger than maximum size.
+    pub fn build(self) -> Result<TextureAtlas, TextureAtlasError> {
+        let padding = self.padding;
+        let max_size = self.max_size;
+
+        let mut images = self.collect_images()?;
+        // Tall images first, it gives much better packing.
+        images.sort_by(|a, b| b.height.cmp(&a.height).then(b.width.cmp(&a.width)));
+
+        let area = images
+            .iter()
+            .map(|i| (i.width + padding) as u64 * (i.height + padding) as u64)
+            .sum::<u64>();
+        let mut size = ((area as f64).sqrt().ceil() as u32)
+            .next_power_of_two()
+            .max(1);
+
+        let rects = loop {
+            if size > max_size {
+                return Err(TextureAtlasError::NotEnoughSpace { max_size });
+            }
+
+            let mut packer = RectPacker::new(size, size);
+            let rects = images
+                .iter()
+                .map(|i| packer.find_free(i.width + padding, i.height + padding))
+                .collect::<Option<Vec<_>>>();
+
+            match rects {
+                Some(rects) => break rects,
+                None => size *= 2,
+            }
+        };
+
+        let mut pixels = vec![0u8; (size * size * 4) as usize];
+        let mut image_rects = HashMap::new();
+        for (image, bounds) in images.into_iter().zip(rects) {
+            let x = bounds.x() + padding / 2;
+            let y = bounds.y() + padding / 2;
+            let row_size = (image.width * 4) as usize;
+            for (row, src) in image.rgba.chunks_exact(row_size).enumerate() {
+                let start = (((y + row as u32) * size + x) * 4) as usize;
+                pixels[start..start + row_size].copy_from_slice(src);
+            }
+            image_rects.insert(image.name, Rect::new(x, y, image.width, image.height));
+        }
+
+        let texture = Texture::from_bytes(size, size, TexturePixelKind::RGBA8, pixels)
+            .map_err(|_| TextureAtlasError::NotEnoughSpace { max_size })?;
+
+        Ok(TextureAtlas {
+            texture,
+            size,
+            rects: image_rects,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::math::Rect,
+        utils::texture_atlas::{TextureAtlasBuilder, TextureAtlasError},
+    };
+
+    #[test]
+    fn test_atlas_packing() {
+        let atlas = TextureAtlasBuilder::new()
+            .with_padding(0)
+            .with_image("red", 2, 2, [255, 0, 0, 255].repeat(4))
+            .with_image("green", 2, 1, [0, 255, 0, 255].repeat(2))
+            .with_image("blue", 1, 1, vec![0, 0, 255, 255])
+            .build()
+            .unwrap();
+
+        assert_eq!(atlas.size(), 4);
+        assert_eq!(atlas.names().count(), 3);
+
+        let texture = atlas.texture();
+        let data = texture.data_ref();
+        for name in ["red", "green", "blue"].iter() {
+            let rect = atlas.pixel_rect(name).unwrap();
+            let uv = atlas.uv_rect(name).unwrap();
+            assert_eq!(
+                uv,
+                Rect::new(
+                    rect.x() as f32 / 4.0,
+                    rect.y() as f32 / 4.0,
+                    rect.w() as f32 / 4.0,
+                    rect.h() as f32 / 4.0
+                )
+            );
+            let expected = match *name {
+                "red" => [255, 0, 0, 255],
+                "green" => [0, 255, 0, 255],
+                _ => [0, 0, 255, 255],
+            };
+            for y in rect.y()..rect.y() + rect.h() {
+                for x in rect.x()..rect.x() + rect.w() {
+                    let i = ((y * 4 + x) * 4) as usize;
+                    assert_eq!(&data.bytes[i..i + 4], &expected);
+                }
+            }
+        }
+
+        assert!(atlas.uv_rect("yellow").is_none());
+    }
+
+    #[test]
+    fn test_atlas_errors() {
+        assert!(matches!(
+            TextureAtlasBuilder::new()
+                .with_image("a", 1, 1, vec![0; 4])
+                .with_image("a", 1, 1, vec![0; 4])
+                .build(),
+            Err(TextureAtlasError::DuplicateName(_))
+        ));
+        assert!(matches!(
+            TextureAtlasBuilder::new()
+                .with_image("a", 2, 2, vec![0; 4])
+                .build(),
+            Err(TextureAtlasError::InvalidImage(_))
+        ));
+        assert!(matches!(
+            TextureAtlasBuilder::new()
+                .with_max_size(8)
+                .with_image("a", 16, 16, vec![0; 16 * 16 * 4])
+                .build(),
+            Err(TextureAtlasError::NotEnoughSpace { max_size: 8 })
+        ));
+    }
+}
@@ -76,6 +76,10 @@ fn face_vs_face(
                         let vertex = data.vertices[other_vertex_index as usize];
                         *vertex_index = data.vertices.len() as u32;
                         data.vertices.push(vertex);
+                        if let Some(colors) = data.colors.as_mut() {
+                            let color = colors[other_vertex_index as usize];
+                            colors.push(color);
+                        }
                         continue 'outer_loop;
                     }
                 }